@@ -0,0 +1,117 @@
+//! Heap allocations made by one `batch_push_with_data` call of 10,000 leaves,
+//! alongside its wall time. Kept apart from the `merkle_tree` benches so the
+//! counting allocator does not skew their timings.
+
+use compactlog::merkle_storage::StorageBackedMerkleTree;
+use compactlog::storage::RateLimitedDb;
+use compactlog::types::{LogEntry, LogEntryType};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use object_store::memory::InMemory;
+use slatedb::Db;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+const BATCH_SIZE: u64 = 10_000;
+
+struct CountingAllocator;
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+fn leaf(index: u64) -> Vec<u8> {
+    let mut certificate = vec![0x30; 512];
+    certificate[..8].copy_from_slice(&index.to_be_bytes());
+    LogEntry::compute_leaf_data_with_index(
+        &certificate,
+        LogEntryType::X509Entry,
+        None,
+        chrono::Utc::now(),
+        index,
+    )
+}
+
+async fn open_tree() -> StorageBackedMerkleTree {
+    let db = Db::open("bench", Arc::new(InMemory::new())).await.unwrap();
+    StorageBackedMerkleTree::new(RateLimitedDb::new(Arc::new(db), None))
+        .await
+        .unwrap()
+}
+
+/// Allocations per pushed leaf, counted across every runtime thread. Reported
+/// rather than timed, since it does not depend on the machine.
+fn bench_allocations(_c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+
+    rt.block_on(async {
+        let tree = open_tree().await;
+        // The first batch also pays for the tree's caches; count the second
+        for start in [0, BATCH_SIZE] {
+            let leaves: Vec<_> = (start..start + BATCH_SIZE).map(leaf).collect();
+            let before = ALLOCATIONS.load(Ordering::Relaxed);
+            tree.batch_push_with_data(leaves, vec![]).await.unwrap();
+            let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+            if start > 0 {
+                println!(
+                    "batch_push_10k/allocations: {} total, {:.1} per leaf",
+                    allocations,
+                    allocations as f64 / BATCH_SIZE as f64
+                );
+            }
+        }
+    });
+}
+
+fn bench_batch_push(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("batch_push_10k");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(BATCH_SIZE));
+
+    let tree = rt.block_on(open_tree());
+    let mut next_index = 0;
+    group.bench_function("batch_push_with_data", |b| {
+        b.to_async(&rt).iter_batched(
+            || {
+                let leaves = (next_index..next_index + BATCH_SIZE).map(leaf).collect();
+                next_index += BATCH_SIZE;
+                leaves
+            },
+            |leaves| {
+                let tree = tree.clone();
+                async move { tree.batch_push_with_data(leaves, vec![]).await.unwrap() }
+            },
+            BatchSize::PerIteration,
+        );
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_allocations, bench_batch_push);
+criterion_main!(benches);
@@ -0,0 +1,198 @@
+//! Append and proof throughput of the storage-backed Merkle tree, against an
+//! in-memory object store.
+//!
+//! Sizes are kept small so the benches run quickly in CI. Set
+//! `COMPACTLOG_BENCH_SCALE` to multiply every tree size for larger manual runs,
+//! e.g. `COMPACTLOG_BENCH_SCALE=100 cargo bench`.
+
+use compactlog::merkle_storage::StorageBackedMerkleTree;
+use compactlog::storage::RateLimitedDb;
+use compactlog::types::{LogEntry, LogEntryType};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use object_store::memory::InMemory;
+use slatedb::Db;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+
+const BATCH_SIZES: [u64; 3] = [1, 100, 1_000];
+const TREE_SIZES: [u64; 2] = [256, 4_096];
+const PROOFS_PER_SAMPLE: u64 = 64;
+
+fn scale() -> u64 {
+    std::env::var("COMPACTLOG_BENCH_SCALE")
+        .ok()
+        .and_then(|scale| scale.parse().ok())
+        .unwrap_or(1)
+        .max(1)
+}
+
+fn tree_sizes() -> Vec<u64> {
+    TREE_SIZES.iter().map(|size| size * scale()).collect()
+}
+
+fn leaf(index: u64) -> Vec<u8> {
+    let mut certificate = vec![0x30; 512];
+    certificate[..8].copy_from_slice(&index.to_be_bytes());
+    LogEntry::compute_leaf_data_with_index(
+        &certificate,
+        LogEntryType::X509Entry,
+        None,
+        chrono::Utc::now(),
+        index,
+    )
+}
+
+async fn open_db() -> RateLimitedDb {
+    let db = Db::open("bench", Arc::new(InMemory::new())).await.unwrap();
+    RateLimitedDb::new(Arc::new(db), None)
+}
+
+/// Tree of `size` leaves appended in batches of up to 1,000
+async fn build_tree(size: u64) -> (RateLimitedDb, StorageBackedMerkleTree) {
+    let db = open_db().await;
+    let tree = StorageBackedMerkleTree::new(db.clone()).await.unwrap();
+
+    let mut start = 0;
+    while start < size {
+        let end = (start + 1_000).min(size);
+        tree.batch_push_with_data((start..end).map(leaf).collect(), vec![])
+            .await
+            .unwrap();
+        start = end;
+    }
+
+    (db, tree)
+}
+
+fn bench_batch_push(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("batch_push_with_data");
+
+    for batch_size in BATCH_SIZES {
+        group.throughput(Throughput::Elements(batch_size));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(batch_size),
+            &batch_size,
+            |b, &batch_size| {
+                let (_, tree) = rt.block_on(build_tree(0));
+                let mut next_index = 0;
+                b.to_async(&rt).iter_batched(
+                    || {
+                        let leaves = (next_index..next_index + batch_size).map(leaf).collect();
+                        next_index += batch_size;
+                        leaves
+                    },
+                    |leaves| {
+                        let tree = tree.clone();
+                        async move { tree.batch_push_with_data(leaves, vec![]).await.unwrap() }
+                    },
+                    BatchSize::SmallInput,
+                );
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn bench_prove_inclusion(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("prove_inclusion");
+    group.throughput(Throughput::Elements(PROOFS_PER_SAMPLE));
+
+    for size in tree_sizes() {
+        let (_, tree) = rt.block_on(build_tree(size));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.to_async(&rt).iter(|| {
+                let tree = tree.clone();
+                async move {
+                    for i in 0..PROOFS_PER_SAMPLE {
+                        let leaf_index = i * size / PROOFS_PER_SAMPLE;
+                        tree.prove_inclusion_efficient(size, leaf_index)
+                            .await
+                            .unwrap();
+                    }
+                }
+            });
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_root(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("root");
+
+    for size in tree_sizes() {
+        let (db, tree) = rt.block_on(build_tree(size));
+
+        group.bench_with_input(BenchmarkId::new("warm", size), &size, |b, _| {
+            b.to_async(&rt).iter(|| {
+                let tree = tree.clone();
+                async move { tree.committed_root().await.unwrap() }
+            });
+        });
+
+        // A fresh handle over the same database starts with an empty node cache
+        group.bench_with_input(BenchmarkId::new("cold", size), &size, |b, _| {
+            b.to_async(&rt).iter_custom(|iters| {
+                let db = db.clone();
+                async move {
+                    let mut elapsed = Duration::ZERO;
+                    for _ in 0..iters {
+                        let tree = StorageBackedMerkleTree::new(db.clone()).await.unwrap();
+                        let start = Instant::now();
+                        tree.committed_root().await.unwrap();
+                        elapsed += start.elapsed();
+                    }
+                    elapsed
+                }
+            });
+        });
+    }
+
+    group.finish();
+}
+
+/// Database reads per inclusion proof. Reported rather than timed, since it
+/// does not depend on the machine.
+fn bench_gets_per_proof(_c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+
+    for size in tree_sizes() {
+        rt.block_on(async {
+            let (db, _) = build_tree(size).await;
+            let tree = StorageBackedMerkleTree::new(db.clone()).await.unwrap();
+
+            let mut gets = [0; 2];
+            for gets in gets.iter_mut() {
+                let (before, _) = db.get_read_stats();
+                for i in 0..PROOFS_PER_SAMPLE {
+                    tree.prove_inclusion_efficient(size, i * size / PROOFS_PER_SAMPLE)
+                        .await
+                        .unwrap();
+                }
+                let (after, _) = db.get_read_stats();
+                *gets = after - before;
+            }
+
+            println!(
+                "gets_per_proof/{}: {:.1} cold, {:.1} warm",
+                size,
+                gets[0] as f64 / PROOFS_PER_SAMPLE as f64,
+                gets[1] as f64 / PROOFS_PER_SAMPLE as f64
+            );
+        });
+    }
+}
+
+criterion_group!(
+    benches,
+    bench_batch_push,
+    bench_prove_inclusion,
+    bench_root,
+    bench_gets_per_proof
+);
+criterion_main!(benches);
@@ -0,0 +1,143 @@
+//! add-chain validation throughput when the same 100 RSA chains are submitted
+//! over and over, with and without the validator's signature cache.
+
+use compactlog::types::LogEntryType;
+use compactlog::validation::{Rfc6962ValidationConfig, Rfc6962Validator};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use der::Decode;
+use openssl::asn1::Asn1Time;
+use openssl::bn::BigNum;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private};
+use openssl::rsa::Rsa;
+use openssl::x509::extension::BasicConstraints;
+use openssl::x509::{X509Builder, X509NameBuilder, X509};
+use tokio::runtime::Runtime;
+use x509_cert::Certificate;
+
+const CHAINS: u32 = 100;
+const INTERMEDIATES: u32 = 4;
+
+fn rsa_key() -> PKey<Private> {
+    PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap()
+}
+
+/// A certificate for `subject` and `key`, signed by `issuer`, or self-signed
+/// when `issuer` is `None`
+fn certificate(
+    subject: &str,
+    serial: u32,
+    key: &PKey<Private>,
+    issuer: Option<(&X509, &PKey<Private>)>,
+    is_ca: bool,
+) -> X509 {
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", subject).unwrap();
+    let name = name.build();
+
+    let now = chrono::Utc::now().timestamp();
+    let mut builder = X509Builder::new().unwrap();
+    builder.set_version(2).unwrap();
+    builder
+        .set_serial_number(&BigNum::from_u32(serial).unwrap().to_asn1_integer().unwrap())
+        .unwrap();
+    builder.set_subject_name(&name).unwrap();
+    builder
+        .set_not_before(&Asn1Time::from_unix(now - 3600).unwrap())
+        .unwrap();
+    builder
+        .set_not_after(&Asn1Time::from_unix(now + 365 * 24 * 3600).unwrap())
+        .unwrap();
+    builder.set_pubkey(key).unwrap();
+    if is_ca {
+        builder
+            .append_extension(BasicConstraints::new().critical().ca().build().unwrap())
+            .unwrap();
+    }
+
+    let (issuer_name, issuer_key) = match issuer {
+        Some((issuer, issuer_key)) => (issuer.subject_name(), issuer_key),
+        None => (&*name, key),
+    };
+    builder.set_issuer_name(issuer_name).unwrap();
+    builder.sign(issuer_key, MessageDigest::sha256()).unwrap();
+    builder.build()
+}
+
+/// The root, and `CHAINS` chains of a leaf and the intermediate that issued it
+fn chains() -> (Certificate, Vec<Vec<Vec<u8>>>) {
+    let root_key = rsa_key();
+    let root = certificate("Bench Root", 1, &root_key, None, true);
+
+    let intermediates: Vec<_> = (0..INTERMEDIATES)
+        .map(|i| {
+            let key = rsa_key();
+            let cert = certificate(
+                &format!("Bench Intermediate {}", i),
+                100 + i,
+                &key,
+                Some((&root, &root_key)),
+                true,
+            );
+            (cert, key)
+        })
+        .collect();
+
+    // Leaf keys are never used to verify anything, so one will do
+    let leaf_key = rsa_key();
+    let chains = (0..CHAINS)
+        .map(|i| {
+            let (intermediate, intermediate_key) = &intermediates[(i % INTERMEDIATES) as usize];
+            let leaf = certificate(
+                &format!("leaf-{}.example.com", i),
+                1_000 + i,
+                &leaf_key,
+                Some((intermediate, intermediate_key)),
+                false,
+            );
+            vec![leaf.to_der().unwrap(), intermediate.to_der().unwrap()]
+        })
+        .collect();
+
+    (
+        Certificate::from_der(&root.to_der().unwrap()).unwrap(),
+        chains,
+    )
+}
+
+fn bench_replay(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let (root, chains) = chains();
+    let mut group = c.benchmark_group("signature_cache_replay");
+    group.throughput(Throughput::Elements(CHAINS as u64));
+
+    for cache_size in [0, Rfc6962ValidationConfig::default().signature_cache_size] {
+        let config = Rfc6962ValidationConfig {
+            signature_cache_size: cache_size,
+            ..Default::default()
+        };
+        let validator = Rfc6962Validator::with_trusted_roots(config, vec![root.clone()]).unwrap();
+        let (validator, chains) = (&validator, &chains);
+
+        let name = if cache_size == 0 {
+            "uncached"
+        } else {
+            "cached"
+        };
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.to_async(&rt).iter(|| async move {
+                for chain in chains {
+                    validator
+                        .validate_chain_as(chain, LogEntryType::X509Entry)
+                        .await
+                        .unwrap();
+                }
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_replay);
+criterion_main!(benches);
@@ -0,0 +1,52 @@
+//! A binary transparency log kept in memory: append release digests, then
+//! prove the log only grew and that a release is in it.
+//!
+//! Run with `cargo run --example embedded_log`.
+
+use compactlog::LogBuilder;
+use p256::SecretKey;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let log = LogBuilder::new("example.com/releases")
+        .with_signing_key(SecretKey::random(&mut rand::thread_rng()))
+        .with_roots(None)
+        .build()
+        .await?;
+
+    let (index, receipt) = log
+        .append(b"app-1.0.0.tar.gz sha256:9f86d0".to_vec())
+        .await?;
+    println!("appended entry {} at {}", index, receipt.timestamp);
+    let first = log.current_checkpoint().await?;
+
+    for release in ["1.0.1", "1.1.0", "2.0.0"] {
+        let leaf = format!("app-{}.tar.gz", release).into_bytes();
+        log.append(leaf).await?;
+    }
+    let latest = log.current_checkpoint().await?;
+    println!("{}", latest.format());
+
+    let tree_size = |checkpoint: &compactlog::types::signed_note::SignedNote| -> u64 {
+        checkpoint.text.lines().nth(1).unwrap().parse().unwrap()
+    };
+    let (old_size, new_size) = (tree_size(&first), tree_size(&latest));
+
+    let consistency = log.prove_consistency(old_size, new_size).await?;
+    println!(
+        "consistency {} -> {}: {} hashes",
+        old_size,
+        new_size,
+        consistency.as_bytes().len() / 32
+    );
+
+    let inclusion = log.prove_inclusion(index, new_size).await?;
+    println!(
+        "inclusion of entry {} in {}: {} hashes",
+        index,
+        new_size,
+        inclusion.as_bytes().len() / 32
+    );
+
+    Ok(())
+}
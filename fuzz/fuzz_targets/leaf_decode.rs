@@ -0,0 +1,14 @@
+//! Leaves as the tree reads them back from the database: postcard-encoded
+//! `Certificate`s whose contents must be a well-formed MerkleTreeLeaf.
+
+#![no_main]
+
+use compactlog::merkle_storage::Certificate;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(certificate) = postcard::from_bytes::<Certificate>(data) {
+        let _ = Certificate::from_leaf_bytes(certificate.data);
+    }
+    let _ = Certificate::from_leaf_bytes(data.to_vec());
+});
@@ -0,0 +1,17 @@
+//! RFC 6962 MerkleTreeLeaf parsing, as applied to stored leaves and tiles
+
+#![no_main]
+
+use compactlog::types::merkle_leaf::{MerkleTreeLeaf, TimestampedEntry};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(leaf) = MerkleTreeLeaf::decode(data) {
+        // Decoding consumes the whole input, so encoding must give it back
+        assert_eq!(leaf.encode().unwrap(), data);
+    }
+
+    if let Ok(entry) = MerkleTreeLeaf::timestamped_entry_bytes(data) {
+        let _ = TimestampedEntry::decode(entry);
+    }
+});
@@ -0,0 +1,46 @@
+//! Inclusion and consistency proofs from untrusted bytes, checked against
+//! tree sizes and roots taken from the same input.
+//!
+//! Input layout: `first` (u64 BE), `second` (u64 BE), two 32-byte roots, then
+//! the proof. For inclusion, the leaf `b"leaf"` is at index `first` in the
+//! tree of `second` leaves with the second root. For consistency, the roots
+//! are those of the trees of `first` and `second` leaves. The proof bytes are
+//! also read as a comma-separated list of base64 hashes, as clients send them.
+
+#![no_main]
+
+use compactlog::merkle_storage::serialization;
+use compactlog::merkle_tree::{ConsistencyProof, InclusionProof, RootHash};
+use libfuzzer_sys::fuzz_target;
+use sha2::{digest::Output, Sha256};
+
+const HEADER_LEN: usize = 8 + 8 + 32 + 32;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < HEADER_LEN {
+        return;
+    }
+    let (header, proof) = data.split_at(HEADER_LEN);
+    let first = u64::from_be_bytes(header[..8].try_into().unwrap());
+    let second = u64::from_be_bytes(header[8..16].try_into().unwrap());
+    let first_root = Output::<Sha256>::clone_from_slice(&header[16..48]);
+    let second_root = Output::<Sha256>::clone_from_slice(&header[48..]);
+
+    if let Ok(inclusion) = InclusionProof::<Sha256>::try_from_bytes(proof.to_vec()) {
+        let root = RootHash::<Sha256>::new(second_root, second);
+        let _ = inclusion.verify(b"leaf", first, &root);
+    }
+
+    if let Ok(consistency) = ConsistencyProof::<Sha256>::try_from_bytes(proof.to_vec()) {
+        let old_root = RootHash::<Sha256>::new(first_root, first);
+        let new_root = RootHash::<Sha256>::new(second_root, second);
+        let _ = consistency.verify(&old_root, &new_root);
+    }
+
+    let path: Vec<String> = String::from_utf8_lossy(proof)
+        .split(',')
+        .map(str::to_string)
+        .collect();
+    let _ = serialization::audit_path_to_inclusion_proof::<Sha256>(&path);
+    let _ = serialization::path_to_consistency_proof::<Sha256>(&path);
+});
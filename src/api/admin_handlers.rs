@@ -0,0 +1,249 @@
+use crate::api::{api_error, ApiState, ErrorCode, ErrorResponse};
+use crate::storage::{RejectionReason, RejectionRecord, StorageError};
+use crate::types::signed_note::{checkpoint_body, SignedNote};
+use crate::types::CtError;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+const MAX_REJECTIONS_PER_REQUEST: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct GetRejectionsRequest {
+    /// Milliseconds since the epoch; records from this time on are returned
+    #[serde(default)]
+    pub since: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RejectionEntry {
+    pub timestamp: u64,
+    pub client_ip: Option<String>,
+    pub reason: RejectionReason,
+    /// Hex SHA-256 of the submitted chain
+    pub chain_sha256: String,
+    /// Base64 DER of the certificates kept within the size limit
+    pub chain: Vec<String>,
+}
+
+impl From<RejectionRecord> for RejectionEntry {
+    fn from(record: RejectionRecord) -> Self {
+        Self {
+            timestamp: record.timestamp_ms,
+            client_ip: record.client_ip.map(|ip| ip.to_string()),
+            reason: record.reason,
+            chain_sha256: hex::encode(record.chain_sha256),
+            chain: record
+                .chain
+                .iter()
+                .map(|cert| STANDARD.encode(cert))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct GetRejectionsResponse {
+    pub rejections: Vec<RejectionEntry>,
+}
+
+/// Recent rejected submissions, oldest first. To page, pass the timestamp of
+/// the last record as `since`; records sharing it are returned again.
+pub async fn get_rejections(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<GetRejectionsRequest>,
+) -> Result<Json<GetRejectionsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let rejection_log = state
+        .rejection_log
+        .as_ref()
+        .ok_or_else(|| api_error(ErrorCode::NotFound, "Rejection log is not enabled"))?;
+
+    let records = rejection_log
+        .records_since(params.since, MAX_REJECTIONS_PER_REQUEST)
+        .await
+        .map_err(|e| api_error(ErrorCode::Internal, e.to_string()))?;
+
+    Ok(Json(GetRejectionsResponse {
+        rejections: records.into_iter().map(RejectionEntry::from).collect(),
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReloadResponse {
+    /// Submission tokens now accepted, when tokens are in use
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub submission_tokens: Option<usize>,
+    /// Whether the TLS listener's certificate was reloaded
+    pub tls_certificate: bool,
+}
+
+/// Re-read reloadable configuration: the submission token file and the TLS
+/// listener's certificate. If either cannot be read or parsed, what is in use
+/// is kept and the error is returned.
+pub async fn reload(
+    State(state): State<Arc<ApiState>>,
+) -> Result<Json<ReloadResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if state.submission_auth.is_none() && state.server_tls.is_none() {
+        return Err(api_error(
+            ErrorCode::NotFound,
+            "Neither submission authentication nor TLS is enabled",
+        ));
+    }
+
+    let submission_tokens = match &state.submission_auth {
+        Some(submission_auth) => {
+            let count = submission_auth.reload().map_err(|e| {
+                tracing::warn!("Failed to reload submission tokens: {}", e);
+                api_error(ErrorCode::from(&e), e.to_string())
+            })?;
+            tracing::info!("Reloaded {} submission tokens", count);
+            Some(count)
+        }
+        None => None,
+    };
+
+    if let Some(server_tls) = &state.server_tls {
+        server_tls.reload().map_err(|e| {
+            tracing::warn!("Failed to reload TLS certificate: {}", e);
+            api_error(ErrorCode::from(&e), e.to_string())
+        })?;
+        tracing::info!("Reloaded TLS certificate");
+    }
+
+    Ok(Json(ReloadResponse {
+        submission_tokens,
+        tls_certificate: state.server_tls.is_some(),
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct AddCosignaturesResponse {
+    /// Tree size of the cosigned checkpoint
+    pub tree_size: u64,
+    /// Witness cosignatures recorded from the note
+    pub recorded: usize,
+}
+
+/// Record the witness cosignatures on a checkpoint, posted as the signed note
+/// with the signature lines witnesses returned from `add-checkpoint`
+/// appended. The note text must be this log's checkpoint at a published
+/// size; the log's own signature on it is skipped. Cosignatures are checked
+/// and stored in order, and the first one refused fails the request.
+pub async fn add_cosignatures(
+    State(state): State<Arc<ApiState>>,
+    note: String,
+) -> Result<Json<AddCosignaturesResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let note =
+        SignedNote::parse(&note).map_err(|e| api_error(ErrorCode::BadRequest, e.to_string()))?;
+    let tree_size = note
+        .text
+        .lines()
+        .nth(1)
+        .and_then(|line| line.parse::<u64>().ok())
+        .ok_or_else(|| api_error(ErrorCode::BadRequest, "Note is not a checkpoint"))?;
+
+    let root = state
+        .merkle_tree
+        .root_at_size(tree_size)
+        .await
+        .map_err(|e| match e {
+            CtError::BadRequest(_) => api_error(ErrorCode::NotFound, e.to_string()),
+            _ => api_error(ErrorCode::Internal, e.to_string()),
+        })?;
+    let origin = state.sth_builder.origin();
+    if note.text != checkpoint_body(origin, tree_size, root.as_bytes()) {
+        return Err(api_error(
+            ErrorCode::BadRequest,
+            format!("Note is not this log's checkpoint at size {}", tree_size),
+        ));
+    }
+
+    let mut recorded = 0;
+    for cosignature in note.signatures.iter().filter(|s| s.key_name != origin) {
+        state
+            .storage
+            .record_cosignature(origin, &root, cosignature)
+            .await
+            .map_err(|e| match e {
+                StorageError::InvalidCosignature(_) => {
+                    api_error(ErrorCode::BadRequest, e.to_string())
+                }
+                _ => api_error(ErrorCode::Internal, e.to_string()),
+            })?;
+        recorded += 1;
+    }
+    tracing::info!(
+        "Recorded {} cosignatures on the checkpoint at size {}",
+        recorded,
+        tree_size
+    );
+
+    Ok(Json(AddCosignaturesResponse {
+        tree_size,
+        recorded,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::handlers::tests::create_test_state_with_witnesses;
+    use crate::storage::WitnessKey;
+    use ed25519_dalek::{Signer, SigningKey};
+    use object_store::memory::InMemory;
+    use slatedb::Db;
+
+    #[tokio::test]
+    async fn test_add_cosignatures_checks_the_checkpoint() {
+        let witness_key = SigningKey::generate(&mut rand::rngs::OsRng);
+        let witness = WitnessKey::new("witness.example".into(), witness_key.verifying_key());
+        let db = Arc::new(Db::open("test", Arc::new(InMemory::new())).await.unwrap());
+        let state = create_test_state_with_witnesses(
+            db,
+            3,
+            crate::metrics::global(),
+            vec![witness.clone()],
+        )
+        .await;
+
+        let root = state.merkle_tree.committed_root().await.unwrap();
+        let cosigned = |root_hash: Vec<u8>| {
+            let mut note = state
+                .sth_builder
+                .create_checkpoint(3, root_hash, Some(1700000000000))
+                .unwrap();
+            let timestamp = 1700000000u64;
+            let message = format!("cosignature/v1\ntime {}\n{}", timestamp, note.text);
+            let mut signature = timestamp.to_be_bytes().to_vec();
+            signature.extend_from_slice(&witness_key.sign(message.as_bytes()).to_bytes());
+            note.add_signature(crate::types::signed_note::NoteSignature {
+                key_name: witness.name().to_string(),
+                key_id: witness.key_id(),
+                signature,
+            });
+            note.format()
+        };
+
+        // A checkpoint over another root is not this log's, even if cosigned
+        let error = add_cosignatures(State(state.clone()), cosigned(vec![0; 32]))
+            .await
+            .unwrap_err();
+        assert_eq!(error.0, StatusCode::BAD_REQUEST);
+        assert!(state.storage.get_cosignatures(3).await.unwrap().is_empty());
+
+        let Json(response) =
+            add_cosignatures(State(state.clone()), cosigned(root.as_bytes().to_vec()))
+                .await
+                .unwrap();
+        assert_eq!(response.tree_size, 3);
+        assert_eq!(response.recorded, 1);
+        let stored = state.storage.get_cosignatures(3).await.unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].key_name, "witness.example");
+    }
+}
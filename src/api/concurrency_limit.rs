@@ -0,0 +1,233 @@
+//! Per route class concurrency limits. Reads and submissions each get their
+//! own pool of slots, so a monitor crawling get-entries cannot take the
+//! runtime and database bandwidth add-chain needs. A request arriving when its
+//! class is full is turned away with 503 and `Retry-After` instead of queuing.
+
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{Request, State},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http_body::{Frame, SizeHint};
+use prometheus::{IntCounter, IntGauge};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::{api_error, ErrorCode};
+use crate::metrics::Metrics;
+
+/// Seconds a shed client is asked to wait before retrying
+const RETRY_AFTER_SECS: &str = "1";
+
+/// Requests of one class, such as reads, served at once
+pub(super) struct RouteClass {
+    name: &'static str,
+    /// `None` when the class is unlimited; its requests are still counted
+    slots: Option<Arc<Semaphore>>,
+    in_flight: IntGauge,
+    shed: IntCounter,
+}
+
+impl RouteClass {
+    pub(super) fn new(name: &'static str, limit: Option<usize>, metrics: &Metrics) -> Self {
+        Self {
+            name,
+            slots: limit.map(|limit| Arc::new(Semaphore::new(limit.max(1)))),
+            in_flight: metrics.in_flight_requests.with_label_values(&[name]),
+            shed: metrics.shed_requests_total.with_label_values(&[name]),
+        }
+    }
+}
+
+/// Decrements the in-flight gauge even if the request is dropped mid-flight
+struct InFlightGuard(IntGauge);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+/// Response body that keeps its request's slot until the last frame is sent
+/// or the body is dropped, so a streamed response counts against its class
+/// for as long as it streams
+struct SlotBody {
+    inner: Body,
+    slot: Option<(Option<OwnedSemaphorePermit>, InFlightGuard)>,
+}
+
+impl HttpBody for SlotBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, axum::Error>>> {
+        let frame = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(None | Some(Err(_))) = frame {
+            self.slot = None;
+        }
+        frame
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+pub(super) async fn limit_concurrency(
+    State(class): State<Arc<RouteClass>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let permit = match &class.slots {
+        Some(slots) => match slots.clone().try_acquire_owned() {
+            Ok(permit) => Some(permit),
+            Err(_) => {
+                class.shed.inc();
+                let mut response = api_error(
+                    ErrorCode::Unavailable,
+                    format!("Too many concurrent {} requests, retry later", class.name),
+                )
+                .into_response();
+                response.headers_mut().insert(
+                    header::RETRY_AFTER,
+                    HeaderValue::from_static(RETRY_AFTER_SECS),
+                );
+                return response;
+            }
+        },
+        None => None,
+    };
+
+    class.in_flight.inc();
+    let in_flight = InFlightGuard(class.in_flight.clone());
+    next.run(req).await.map(|body| {
+        Body::new(SlotBody {
+            inner: body,
+            slot: Some((permit, in_flight)),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get, Router};
+    use futures::StreamExt;
+    use tokio::sync::Notify;
+
+    #[tokio::test]
+    async fn test_full_class_sheds_with_retry_after() {
+        let metrics = Metrics::new();
+        let reads = Arc::new(RouteClass::new("read", Some(1), &metrics));
+        let submissions = Arc::new(RouteClass::new("submission", Some(1), &metrics));
+
+        let release = Arc::new(Notify::new());
+        let held = release.clone();
+        let router = Router::new()
+            .route(
+                "/read",
+                get(move || {
+                    let held = held.clone();
+                    async move { held.notified().await }
+                }),
+            )
+            .route_layer(middleware::from_fn_with_state(
+                reads.clone(),
+                limit_concurrency,
+            ))
+            .merge(
+                Router::new()
+                    .route("/submit", get(|| async {}))
+                    .route_layer(middleware::from_fn_with_state(
+                        submissions,
+                        limit_concurrency,
+                    )),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        let url = |path: &str| format!("http://{}{}", addr, path);
+
+        let first = tokio::spawn(reqwest::get(url("/read")));
+        while reads.in_flight.get() == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let shed = reqwest::get(url("/read")).await.unwrap();
+        assert_eq!(shed.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(shed.headers()[header::RETRY_AFTER], RETRY_AFTER_SECS);
+        assert_eq!(reads.shed.get(), 1);
+
+        // Submissions have their own slots
+        let submitted = reqwest::get(url("/submit")).await.unwrap();
+        assert_eq!(submitted.status(), reqwest::StatusCode::OK);
+
+        release.notify_one();
+        assert_eq!(
+            first.await.unwrap().unwrap().status(),
+            reqwest::StatusCode::OK
+        );
+        while reads.in_flight.get() != 0 {
+            tokio::task::yield_now().await;
+        }
+        // The freed slot serves the next read; the stored permit lets it finish
+        release.notify_one();
+        let again = reqwest::get(url("/read")).await.unwrap();
+        assert_eq!(again.status(), reqwest::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_slot_held_while_body_streams() {
+        let metrics = Metrics::new();
+        let reads = Arc::new(RouteClass::new("read", Some(1), &metrics));
+
+        let release = Arc::new(Notify::new());
+        let held = release.clone();
+        let router = Router::new()
+            .route(
+                "/read",
+                get(move || {
+                    let held = held.clone();
+                    async move {
+                        let tail = futures::stream::once(async move {
+                            held.notified().await;
+                            Ok::<_, std::io::Error>("tail")
+                        });
+                        Body::from_stream(futures::stream::once(async { Ok("head,") }).chain(tail))
+                    }
+                }),
+            )
+            .route_layer(middleware::from_fn_with_state(
+                reads.clone(),
+                limit_concurrency,
+            ));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        let url = format!("http://{}/read", addr);
+
+        // The handler has returned, but its body has not finished
+        let streaming = reqwest::get(&url).await.unwrap();
+        assert_eq!(streaming.status(), reqwest::StatusCode::OK);
+        assert_eq!(reads.in_flight.get(), 1);
+        let shed = reqwest::get(&url).await.unwrap();
+        assert_eq!(shed.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+
+        release.notify_one();
+        assert_eq!(streaming.text().await.unwrap(), "head,tail");
+        while reads.in_flight.get() != 0 {
+            tokio::task::yield_now().await;
+        }
+    }
+}
@@ -0,0 +1,421 @@
+use crate::api::{api_error, ApiState, ErrorCode, ErrorResponse};
+use crate::merkle_storage::serialization::{
+    audit_path_to_inclusion_proof, consistency_proof_to_path, inclusion_proof_to_audit_path,
+    path_to_consistency_proof,
+};
+use crate::merkle_tree::ct_merkle_vendored::VerificationTrace;
+use crate::merkle_tree::RootHash;
+use crate::types::CtError;
+use axum::{extract::State, http::StatusCode, response::Json};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::Arc;
+
+type DebugResult<T> = Result<Json<T>, (StatusCode, Json<ErrorResponse>)>;
+
+/// An inclusion proof as a client holds it
+#[derive(Debug, Deserialize)]
+pub struct VerifyInclusionRequest {
+    /// Base64 RFC 6962 leaf hash
+    pub leaf_hash: String,
+    pub leaf_index: u64,
+    pub tree_size: u64,
+    /// Base64 root hash of the STH at `tree_size`
+    pub root_hash: String,
+    pub audit_path: Vec<String>,
+}
+
+/// A consistency proof as a client holds it
+#[derive(Debug, Deserialize)]
+pub struct VerifyConsistencyRequest {
+    pub first: u64,
+    pub second: u64,
+    /// Base64 root hashes of the STHs at `first` and `second`
+    pub first_root_hash: String,
+    pub second_root_hash: String,
+    pub consistency: Vec<String>,
+}
+
+/// One hash the verifier computed, all hashes base64
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerificationStep {
+    /// Level of the computed node, leaves being level 0
+    pub level: u32,
+    /// The proof element combined at this step
+    pub sibling: String,
+    pub sibling_is_left: bool,
+    pub hash: String,
+    /// For consistency proofs, the old tree's hash if this step advanced it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old_hash: Option<String>,
+}
+
+/// The first element where a submitted path differs from the log's own proof
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PathMismatch {
+    /// Position in the submitted path
+    pub element: usize,
+    /// Level of the step that used the element, if verification reached it
+    pub level: Option<u32>,
+    /// The log's element, absent if the submitted path is too long
+    pub expected: Option<String>,
+    /// The submitted element, absent if the submitted path is too short
+    pub received: Option<String>,
+}
+
+/// The submitted proof next to the one this log builds for the same sizes
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogComparison {
+    /// The log's root hash at the tree size proved against
+    pub root_hash: String,
+    pub first_mismatch: Option<PathMismatch>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerificationVerdict {
+    pub valid: bool,
+    /// Why verification failed
+    pub error: Option<String>,
+    /// Hash the walk starts from: the leaf hash, or for consistency the
+    /// first node the two trees share
+    pub start: String,
+    pub steps: Vec<VerificationStep>,
+    /// The hash verification compared with the root
+    pub computed_root: String,
+    /// Present when this log has an STH at the sizes given
+    pub log_comparison: Option<LogComparison>,
+}
+
+fn decode_hash(name: &str, value: &str) -> Result<digest::Output<Sha256>, CtError> {
+    let bytes = STANDARD
+        .decode(value)
+        .map_err(|e| CtError::BadRequest(format!("{} is not valid base64: {}", name, e)))?;
+    if bytes.len() != 32 {
+        return Err(CtError::BadRequest(format!(
+            "{} is {} bytes, expected 32",
+            name,
+            bytes.len()
+        )));
+    }
+    Ok(*digest::Output::<Sha256>::from_slice(&bytes))
+}
+
+fn bad_request(e: CtError) -> (StatusCode, Json<ErrorResponse>) {
+    api_error(ErrorCode::from(&e), e.to_string())
+}
+
+/// What the log proves for the same sizes, or `None` if it has no STH there
+async fn log_proof<T>(
+    proof: impl std::future::Future<Output = crate::types::Result<T>>,
+) -> Result<Option<T>, (StatusCode, Json<ErrorResponse>)> {
+    match proof.await {
+        Ok(proof) => Ok(Some(proof)),
+        Err(CtError::BadRequest(_)) => Ok(None),
+        Err(e) => Err(api_error(ErrorCode::from(&e), e.to_string())),
+    }
+}
+
+fn first_mismatch(
+    submitted: &[String],
+    expected: &[String],
+    trace: &VerificationTrace<Sha256>,
+) -> Option<PathMismatch> {
+    let element =
+        (0..submitted.len().max(expected.len())).find(|&i| submitted.get(i) != expected.get(i))?;
+    // A consistency proof from a size that is not a power of two starts from
+    // its first element rather than hashing it into a step
+    let skipped = submitted.len().saturating_sub(trace.steps.len());
+    let level = element
+        .checked_sub(skipped)
+        .and_then(|step| trace.steps.get(step))
+        .map(|step| step.level);
+    Some(PathMismatch {
+        element,
+        level,
+        expected: expected.get(element).cloned(),
+        received: submitted.get(element).cloned(),
+    })
+}
+
+fn verdict(
+    trace: VerificationTrace<Sha256>,
+    log_comparison: Option<LogComparison>,
+) -> VerificationVerdict {
+    VerificationVerdict {
+        valid: trace.result.is_ok(),
+        error: trace.result.err().map(str::to_string),
+        start: STANDARD.encode(trace.start),
+        computed_root: STANDARD.encode(trace.computed_root()),
+        steps: trace
+            .steps
+            .into_iter()
+            .map(|step| VerificationStep {
+                level: step.level,
+                sibling: STANDARD.encode(step.sibling),
+                sibling_is_left: step.sibling_is_left,
+                hash: STANDARD.encode(step.hash),
+                old_hash: step.old_hash.map(|hash| STANDARD.encode(hash)),
+            })
+            .collect(),
+        log_comparison,
+    }
+}
+
+/// Run a client's inclusion proof through the log's verifier, reporting each
+/// hash computed and, if the log has an STH at `tree_size`, where the path
+/// first departs from the log's own
+pub async fn verify_inclusion(
+    State(state): State<Arc<ApiState>>,
+    Json(request): Json<VerifyInclusionRequest>,
+) -> DebugResult<VerificationVerdict> {
+    let leaf_hash = decode_hash("leaf_hash", &request.leaf_hash).map_err(bad_request)?;
+    let root_hash = decode_hash("root_hash", &request.root_hash).map_err(bad_request)?;
+    let proof =
+        audit_path_to_inclusion_proof::<Sha256>(&request.audit_path).map_err(bad_request)?;
+
+    let trace = proof.verify_with_trace(
+        leaf_hash,
+        request.leaf_index,
+        &RootHash::new(root_hash, request.tree_size),
+    );
+
+    let tree = &state.merkle_tree;
+    let log_comparison = match log_proof(tree.root_at_size(request.tree_size)).await? {
+        Some(log_root) => {
+            let expected = match log_proof(
+                tree.prove_inclusion_efficient(request.tree_size, request.leaf_index),
+            )
+            .await?
+            {
+                Some(proof) => Some(
+                    inclusion_proof_to_audit_path(&proof)
+                        .map_err(|e| api_error(ErrorCode::Internal, e.to_string()))?,
+                ),
+                None => None,
+            };
+            Some(LogComparison {
+                root_hash: STANDARD.encode(log_root.as_bytes()),
+                first_mismatch: expected
+                    .and_then(|expected| first_mismatch(&request.audit_path, &expected, &trace)),
+            })
+        }
+        None => None,
+    };
+
+    Ok(Json(verdict(trace, log_comparison)))
+}
+
+/// Run a client's consistency proof through the log's verifier, reporting
+/// each hash computed and, if the log has STHs at both sizes, where the proof
+/// first departs from the log's own
+pub async fn verify_consistency(
+    State(state): State<Arc<ApiState>>,
+    Json(request): Json<VerifyConsistencyRequest>,
+) -> DebugResult<VerificationVerdict> {
+    let first_root =
+        decode_hash("first_root_hash", &request.first_root_hash).map_err(bad_request)?;
+    let second_root =
+        decode_hash("second_root_hash", &request.second_root_hash).map_err(bad_request)?;
+    let proof = path_to_consistency_proof::<Sha256>(&request.consistency).map_err(bad_request)?;
+
+    let trace = proof.verify_with_trace(
+        &RootHash::new(first_root, request.first),
+        &RootHash::new(second_root, request.second),
+    );
+
+    let tree = &state.merkle_tree;
+    let log_roots = (
+        log_proof(tree.root_at_size(request.first)).await?,
+        log_proof(tree.root_at_size(request.second)).await?,
+    );
+    let log_comparison = match log_roots {
+        (Some(_), Some(log_root)) if request.first <= request.second => {
+            let expected = match log_proof(
+                tree.consistency_proof_between_sizes(request.first, request.second),
+            )
+            .await?
+            {
+                Some(proof) => Some(
+                    consistency_proof_to_path(&proof)
+                        .map_err(|e| api_error(ErrorCode::Internal, e.to_string()))?,
+                ),
+                None => None,
+            };
+            Some(LogComparison {
+                root_hash: STANDARD.encode(log_root.as_bytes()),
+                first_mismatch: expected
+                    .and_then(|expected| first_mismatch(&request.consistency, &expected, &trace)),
+            })
+        }
+        _ => None,
+    };
+
+    Ok(Json(verdict(trace, log_comparison)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::handlers::tests::{append_test_entries, create_test_state};
+    use sha2::Digest;
+
+    fn corrupt(path: &[String], element: usize) -> Vec<String> {
+        let mut path = path.to_vec();
+        let mut bytes = STANDARD.decode(&path[element]).unwrap();
+        bytes[0] ^= 1;
+        path[element] = STANDARD.encode(bytes);
+        path
+    }
+
+    #[tokio::test]
+    async fn test_verify_inclusion_pinpoints_corrupted_level() {
+        let state = create_test_state(13).await;
+        let tree = &state.merkle_tree;
+        let root = tree.committed_root().await.unwrap();
+
+        let leaf_index = 4;
+        let leaf_input = state
+            .storage
+            .get_entry(leaf_index)
+            .await
+            .unwrap()
+            .unwrap()
+            .serialize()
+            .unwrap();
+        let leaf_hash = Sha256::new_with_prefix([0u8])
+            .chain_update(&leaf_input)
+            .finalize();
+        let audit_path = inclusion_proof_to_audit_path(
+            &tree
+                .prove_inclusion_efficient(13, leaf_index)
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+
+        let verify = |audit_path: Vec<String>| {
+            verify_inclusion(
+                State(state.clone()),
+                Json(VerifyInclusionRequest {
+                    leaf_hash: STANDARD.encode(leaf_hash),
+                    leaf_index,
+                    tree_size: 13,
+                    root_hash: STANDARD.encode(root.as_bytes()),
+                    audit_path,
+                }),
+            )
+        };
+
+        let Json(valid) = verify(audit_path.clone()).await.unwrap();
+        assert!(valid.valid);
+        assert_eq!(valid.computed_root, STANDARD.encode(root.as_bytes()));
+        assert_eq!(valid.steps.len(), audit_path.len());
+        let comparison = valid.log_comparison.unwrap();
+        assert!(comparison.first_mismatch.is_none());
+
+        for element in 0..audit_path.len() {
+            let Json(invalid) = verify(corrupt(&audit_path, element)).await.unwrap();
+            assert!(!invalid.valid);
+            assert!(invalid.error.is_some());
+
+            let mismatch = invalid.log_comparison.unwrap().first_mismatch.unwrap();
+            assert_eq!(mismatch.element, element);
+            assert_eq!(mismatch.level, Some(valid.steps[element].level));
+            assert_eq!(mismatch.expected.as_ref(), Some(&audit_path[element]));
+
+            // Every hash below the corrupted level still matches
+            for (before, after) in valid.steps.iter().zip(&invalid.steps).take(element) {
+                assert_eq!(before.hash, after.hash);
+            }
+            assert_ne!(invalid.steps[element].hash, valid.steps[element].hash);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_consistency_pinpoints_corrupted_level() {
+        let state = create_test_state(5).await;
+        let tree = &state.merkle_tree;
+        let first_root = tree.committed_root().await.unwrap();
+        append_test_entries(&state.storage, &state.log_id, 5..13).await;
+        let second_root = tree.committed_root().await.unwrap();
+
+        let consistency =
+            consistency_proof_to_path(&tree.consistency_proof_between_sizes(5, 13).await.unwrap())
+                .unwrap();
+
+        let verify = |consistency: Vec<String>| {
+            verify_consistency(
+                State(state.clone()),
+                Json(VerifyConsistencyRequest {
+                    first: 5,
+                    second: 13,
+                    first_root_hash: STANDARD.encode(first_root.as_bytes()),
+                    second_root_hash: STANDARD.encode(second_root.as_bytes()),
+                    consistency,
+                }),
+            )
+        };
+
+        let Json(valid) = verify(consistency.clone()).await.unwrap();
+        assert!(valid.valid, "{:?}", valid.error);
+        // Five is not a power of two, so the proof's first element is the
+        // node the walk starts from
+        assert_eq!(valid.start, consistency[0]);
+        assert_eq!(valid.steps.len(), consistency.len() - 1);
+        assert!(valid.log_comparison.unwrap().first_mismatch.is_none());
+
+        let Json(invalid) = verify(corrupt(&consistency, 0)).await.unwrap();
+        assert!(!invalid.valid);
+        let mismatch = invalid.log_comparison.unwrap().first_mismatch.unwrap();
+        assert_eq!((mismatch.element, mismatch.level), (0, None));
+
+        for element in 1..consistency.len() {
+            let Json(invalid) = verify(corrupt(&consistency, element)).await.unwrap();
+            assert!(!invalid.valid);
+
+            let mismatch = invalid.log_comparison.unwrap().first_mismatch.unwrap();
+            assert_eq!(mismatch.element, element);
+            assert_eq!(mismatch.level, Some(valid.steps[element - 1].level));
+            for (before, after) in valid.steps.iter().zip(&invalid.steps).take(element - 1) {
+                assert_eq!(before.hash, after.hash);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_without_log_sth() {
+        let state = create_test_state(5).await;
+        let root = state.merkle_tree.committed_root().await.unwrap();
+
+        let Json(verdict) = verify_inclusion(
+            State(state.clone()),
+            Json(VerifyInclusionRequest {
+                leaf_hash: STANDARD.encode([0u8; 32]),
+                leaf_index: 0,
+                tree_size: 8,
+                root_hash: STANDARD.encode(root.as_bytes()),
+                audit_path: (1..4u8).map(|i| STANDARD.encode([i; 32])).collect(),
+            }),
+        )
+        .await
+        .unwrap();
+        assert!(!verdict.valid);
+        assert_eq!(verdict.steps.len(), 3);
+        assert!(verdict.log_comparison.is_none());
+
+        let (status, _) = verify_inclusion(
+            State(state),
+            Json(VerifyInclusionRequest {
+                leaf_hash: "not base64".to_string(),
+                leaf_index: 0,
+                tree_size: 5,
+                root_hash: STANDARD.encode(root.as_bytes()),
+                audit_path: vec![],
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
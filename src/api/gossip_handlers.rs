@@ -0,0 +1,76 @@
+use crate::api::{api_error, ApiState, ErrorCode, ErrorResponse};
+use crate::gossip::{self, GossipOutcome, GossipSth};
+use crate::types::tree_head::verify_sth;
+use crate::types::CtError;
+use axum::{extract::State, http::StatusCode, response::Json};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::Serialize;
+use std::sync::Arc;
+
+type GossipError = (StatusCode, Json<ErrorResponse>);
+
+#[derive(Debug, Serialize)]
+pub struct ReceiveGossipSthResponse {
+    /// "consistent", "ahead" or "inconsistent"
+    pub status: &'static str,
+    pub local_tree_size: u64,
+}
+
+fn error_response(e: CtError) -> GossipError {
+    api_error(ErrorCode::from(&e), e.to_string())
+}
+
+/// Current STH in the form peers accept on receive-gossip-sth
+pub async fn get_gossip_sth(
+    State(state): State<Arc<ApiState>>,
+) -> Result<Json<GossipSth>, GossipError> {
+    gossip::current_gossip_sth(&state.merkle_tree, &state.sth_builder, &state.log_id)
+        .await
+        .map(Json)
+        .map_err(error_response)
+}
+
+/// Accept an STH from another instance of this log and check it against the local tree
+pub async fn receive_gossip_sth(
+    State(state): State<Arc<ApiState>>,
+    Json(gossip): Json<GossipSth>,
+) -> Result<(StatusCode, Json<ReceiveGossipSthResponse>), GossipError> {
+    if STANDARD.decode(&gossip.log_id).ok().as_deref() != Some(&state.log_id.to_bytes()[..]) {
+        return Err(error_response(CtError::BadRequest(
+            "STH is for a different log".into(),
+        )));
+    }
+
+    let sth = verify_sth(&state.public_key_der, &gossip.sth).map_err(error_response)?;
+
+    let outcome = gossip::check_consistency(&state.merkle_tree, &sth, &state.metrics)
+        .await
+        .map_err(error_response)?;
+
+    let local_tree_size = state
+        .merkle_tree
+        .committed_size()
+        .await
+        .map_err(error_response)?;
+
+    let (status_code, status) = match outcome {
+        GossipOutcome::Consistent => (StatusCode::OK, "consistent"),
+        GossipOutcome::Ahead { local_size } => {
+            tracing::info!(
+                "Peer STH at size {} is ahead of local tree size {}",
+                sth.tree_size,
+                local_size
+            );
+            (StatusCode::OK, "ahead")
+        }
+        GossipOutcome::Inconsistent(_) => (StatusCode::CONFLICT, "inconsistent"),
+    };
+
+    Ok((
+        status_code,
+        Json(ReceiveGossipSthResponse {
+            status,
+            local_tree_size,
+        }),
+    ))
+}
@@ -1,29 +1,42 @@
-use crate::types::{LogEntry, LogEntryType};
+use crate::types::{extra_data::ExtraData, LogEntry, LogEntryType};
 use crate::{
-    api::{ApiState, ErrorResponse},
+    api::{
+        api_error, submission_auth::Submitter, ApiState, CachedConsistencyProof, ChainSubmission,
+        ClientIp, ErrorCode, ErrorResponse, RootsOrder,
+    },
     merkle_storage::serialization,
+    oids::{self, EC_PUBLIC_KEY_OID, RSA_ENCRYPTION_OID, SECP256R1_OID, SECP384R1_OID},
+    storage::RejectionReason,
     types::{
-        AddChainRequest, AddChainResponse, GetConsistencyProofRequest, GetConsistencyProofResponse,
-        GetEntriesRequest, GetEntriesResponse, GetProofByHashRequest, GetProofByHashResponse,
-        GetRootsResponse, InclusionRequestResponse, LeafEntry, TemporalInterval,
+        tree_head::SignedTreeHead, AddChainRequest, AddChainResponse, CtError, EntriesCursor,
+        GetConsistencyProofRequest, GetConsistencyProofResponse, GetEntriesRequest,
+        GetProofByHashRequest, GetRecentEntriesRequest, GetRecentEntriesResponse, GetRootsRequest,
+        GetRootsResponse, GetSthRequest, GetSthResponse, GetTimestampResponse,
+        InclusionRequestParams, InclusionRequestResponse, LeafEntry, LeafInclusionResponse,
+        ProofFormat, RecentLeafEntry, TemporalInterval,
+    },
+    validation::{
+        check_tbs_signature_algorithm, RejectionCheck, TbsExtractor, ValidationPoolError,
     },
-    validation::TbsExtractor,
 };
 use axum::{
+    body::Body,
     extract::{Query, State},
-    http::StatusCode,
-    response::Json,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
 };
 use base64::{engine::general_purpose::STANDARD, Engine as _};
-use futures;
+use bytes::Bytes;
+use futures::{self, future, stream, StreamExt, TryStreamExt};
 use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
 use std::sync::Arc;
 use std::time::Instant;
 use x509_cert::der::Decode;
 
-type ApiResult<T> = std::result::Result<Json<T>, (StatusCode, Json<ErrorResponse>)>;
+pub(super) type ApiResult<T> = std::result::Result<Json<T>, (StatusCode, Json<ErrorResponse>)>;
 
-async fn handle_storage_error(
+pub(super) async fn handle_storage_error(
     e: crate::storage::StorageError,
 ) -> (StatusCode, Json<ErrorResponse>) {
     match e {
@@ -36,36 +49,96 @@ async fn handle_storage_error(
             tracing::debug!("Queue full, delaying response by {}ms", delay_ms);
             tokio::time::sleep(tokio::time::Duration::from_millis(delay_ms)).await;
 
-            (
-                StatusCode::SERVICE_UNAVAILABLE,
-                Json(ErrorResponse {
-                    error: "Service temporarily unavailable - system at capacity".to_string(),
-                }),
+            api_error(
+                ErrorCode::Unavailable,
+                "Service temporarily unavailable - system at capacity",
             )
         }
-        _ => (StatusCode::INTERNAL_SERVER_ERROR, Json(e.into())),
+        crate::storage::StorageError::InvalidTimestamp(_) => {
+            api_error(ErrorCode::Unavailable, e.to_string())
+        }
+        _ => api_error(ErrorCode::Internal, e.to_string()),
+    }
+}
+
+fn validation_busy_response() -> (StatusCode, Json<ErrorResponse>) {
+    api_error(
+        ErrorCode::RateLimited,
+        "Too many pending validations, retry later",
+    )
+}
+
+/// Error class for a chain that failed to parse or that the validator rejected
+fn rejected_chain_code(error: &CtError) -> ErrorCode {
+    match error {
+        CtError::Validation(error) => error.into(),
+        CtError::InvalidCertificate(_) => ErrorCode::MalformedCertificate,
+        CtError::BadRequest(_) => ErrorCode::InvalidChain,
+        CtError::Storage(_) | CtError::Internal(_) | CtError::InvalidTimestamp(_) => {
+            ErrorCode::from(error)
+        }
+    }
+}
+
+/// Count a chain that failed validation and, with the rejection log enabled,
+/// record it in the background so the write does not delay the response
+fn record_rejection(
+    state: &ApiState,
+    client_ip: Option<IpAddr>,
+    error: &CtError,
+    chain: Vec<Vec<u8>>,
+) {
+    let reason = RejectionReason::classify(error);
+    state
+        .metrics
+        .rejected_submissions_total
+        .with_label_values(&[reason.as_str()])
+        .inc();
+    state
+        .metrics
+        .add_chain_rejections_total
+        .with_label_values(&[RejectionCheck::classify(error).as_str()])
+        .inc();
+
+    if let Some(rejection_log) = state.rejection_log.clone() {
+        tokio::spawn(async move {
+            if let Err(e) = rejection_log.record(client_ip, reason, &chain).await {
+                tracing::warn!("Failed to record rejected submission: {}", e);
+            }
+        });
+    }
+}
+
+/// Attribute a new entry to the principal that submitted it. The entry is
+/// already logged by then, so a failed write is only reported.
+pub(super) async fn record_submitter(state: &ApiState, index: u64, submitter: Option<String>) {
+    if let Some(principal) = submitter {
+        if let Err(e) = state.storage.record_submitter(index, &principal).await {
+            tracing::warn!("Failed to record the submitter of entry {}: {}", index, e);
+        }
     }
 }
 
 pub async fn add_chain(
     State(state): State<Arc<ApiState>>,
-    Json(request): Json<AddChainRequest>,
+    ClientIp(client_ip): ClientIp,
+    Submitter(submitter): Submitter,
+    ChainSubmission(request): ChainSubmission,
 ) -> ApiResult<AddChainResponse> {
     let validation_start = Instant::now();
 
     if request.chain.is_empty() {
-        crate::metrics::CERTIFICATE_SUBMISSIONS_TOTAL
+        state
+            .metrics
+            .certificate_submissions_total
             .with_label_values(&["x509", "failed"])
             .inc();
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: "Chain cannot be empty".to_string(),
-            }),
-        ));
+        return Err(api_error(ErrorCode::BadRequest, "Chain cannot be empty"));
     }
 
-    crate::metrics::CERTIFICATE_CHAIN_LENGTH
+    state
+        .metrics
+        .certificate_chain_length
         .with_label_values(&["x509"])
         .observe(request.chain.len() as f64);
 
@@ -74,47 +147,62 @@ pub async fn add_chain(
         .iter()
         .map(|cert| STANDARD.decode(cert))
         .collect();
-    let chain = chain.map_err(|_| {
-        (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: "Invalid base64 in certificate chain".to_string(),
-            }),
-        )
-    })?;
+    let chain = chain
+        .map_err(|_| api_error(ErrorCode::BadRequest, "Invalid base64 in certificate chain"))?;
 
     let cert_der = chain[0].clone();
 
     let _ = x509_cert::Certificate::from_der(&cert_der).map_err(|e| {
-        (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: format!(
-                    "Invalid certificate format: Failed to parse certificate: {}",
-                    e
-                ),
-            }),
+        api_error(
+            ErrorCode::MalformedCertificate,
+            format!(
+                "Invalid certificate format: Failed to parse certificate: {}",
+                e
+            ),
         )
     })?;
 
-    if let Some(validator_lock) = &state.validator {
-        let validator = validator_lock.read().await;
-        validator.validate_chain(&chain).await.map_err(|e| {
-            crate::metrics::CERTIFICATE_SUBMISSIONS_TOTAL
-                .with_label_values(&["x509", "validation_failed"])
-                .inc();
-            (
-                StatusCode::BAD_REQUEST,
-                Json(ErrorResponse {
-                    error: format!("Certificate validation failed: {}", e),
-                }),
-            )
-        })?;
+    // With a validator configured, log the verified path to the root rather
+    // than whatever the submitter sent
+    let chain = if let (Some(validator_lock), Some(validation_pool)) =
+        (&state.validator, &state.validation_pool)
+    {
+        let validated_chain = validation_pool
+            .validate(validator_lock, chain.clone(), LogEntryType::X509Entry)
+            .await
+            .map_err(|e| match e {
+                ValidationPoolError::Busy => {
+                    state
+                        .metrics
+                        .certificate_submissions_total
+                        .with_label_values(&["x509", "rate_limited"])
+                        .inc();
+                    validation_busy_response()
+                }
+                ValidationPoolError::Rejected(e) => {
+                    state
+                        .metrics
+                        .certificate_submissions_total
+                        .with_label_values(&["x509", "validation_failed"])
+                        .inc();
+                    record_rejection(&state, client_ip, &e, chain);
+                    api_error(
+                        rejected_chain_code(&e),
+                        format!("Certificate validation failed: {}", e),
+                    )
+                }
+            })?;
 
-        crate::metrics::VALIDATION_DURATION_SECONDS
+        state
+            .metrics
+            .validation_duration_seconds
             .with_label_values(&["x509"])
             .observe(validation_start.elapsed().as_secs_f64());
-    }
+
+        validated_chain
+    } else {
+        chain
+    };
 
     use sha2::{Digest, Sha256};
     let mut hasher = Sha256::new();
@@ -125,9 +213,11 @@ pub async fn add_chain(
         .storage
         .get_sct_by_cert_hash(&cert_hash)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(e.into())))?
+        .map_err(|e| api_error(ErrorCode::Internal, e.to_string()))?
     {
-        crate::metrics::CERTIFICATE_SUBMISSIONS_TOTAL
+        state
+            .metrics
+            .certificate_submissions_total
             .with_label_values(&["x509", "deduplicated"])
             .inc();
         let response = AddChainResponse {
@@ -141,21 +231,13 @@ pub async fn add_chain(
     }
 
     // Check if this is actually a pre-certificate (should use add-pre-chain instead)
-    let is_precert = LogEntry::is_precertificate(&cert_der).map_err(|e| {
-        (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: e.to_string(),
-            }),
-        )
-    })?;
+    let is_precert = LogEntry::is_precertificate(&cert_der)
+        .map_err(|e| api_error(rejected_chain_code(&e), e.to_string()))?;
 
     if is_precert {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: "Pre-certificates should be submitted to /ct/v1/add-pre-chain".to_string(),
-            }),
+        return Err(api_error(
+            ErrorCode::InvalidChain,
+            "Pre-certificates should be submitted to /ct/v1/add-pre-chain",
         ));
     }
 
@@ -168,11 +250,9 @@ pub async fn add_chain(
 
             // Validate each chain certificate
             let _ = x509_cert::Certificate::from_der(&chain_cert_der).map_err(|e| {
-                (
-                    StatusCode::BAD_REQUEST,
-                    Json(ErrorResponse {
-                        error: format!("Invalid X.509 certificate in chain {}", e),
-                    }),
+                api_error(
+                    ErrorCode::MalformedCertificate,
+                    format!("Invalid X.509 certificate in chain {}", e),
                 )
             })?;
 
@@ -197,26 +277,27 @@ pub async fn add_chain(
     let sct_builder = state.sct_builder.clone();
     let cert_der_for_sct = cert_der.clone();
 
-    let (_assigned_index, sct) = match state
+    let (assigned_index, sct) = match state
         .storage
         .add_entry_batched(log_entry, cert_hash, move |index| {
-            sct_builder
-                .create_sct_with_timestamp_and_index(
-                    &cert_der_for_sct,
-                    LogEntryType::X509Entry,
-                    None,
-                    timestamp_ms,
-                    Some(index),
-                )
-                .expect("Failed to create SCT")
+            sct_builder.create_sct_with_timestamp_and_index(
+                &cert_der_for_sct,
+                LogEntryType::X509Entry,
+                None,
+                timestamp_ms,
+                Some(index),
+            )
         })
         .await
     {
         Ok(result) => result,
         Err(e) => return Err(handle_storage_error(e).await),
     };
+    record_submitter(&state, assigned_index, submitter).await;
 
-    crate::metrics::CERTIFICATE_SUBMISSIONS_TOTAL
+    state
+        .metrics
+        .certificate_submissions_total
         .with_label_values(&["x509", "success"])
         .inc();
 
@@ -233,18 +314,15 @@ pub async fn add_chain(
 
 pub async fn add_pre_chain(
     State(state): State<Arc<ApiState>>,
+    ClientIp(client_ip): ClientIp,
+    Submitter(submitter): Submitter,
     Json(request): Json<AddChainRequest>,
 ) -> ApiResult<AddChainResponse> {
     use crate::types::{LogEntry, LogEntryType};
     use base64::{engine::general_purpose::STANDARD, Engine as _};
 
     if request.chain.is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: "Chain cannot be empty".to_string(),
-            }),
-        ));
+        return Err(api_error(ErrorCode::BadRequest, "Chain cannot be empty"));
     }
 
     let chain: std::result::Result<Vec<Vec<u8>>, _> = request
@@ -253,47 +331,39 @@ pub async fn add_pre_chain(
         .map(|cert| STANDARD.decode(cert))
         .collect();
 
-    let chain = chain.map_err(|_e| {
-        (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: "Invalid base64 in certificate chain".to_string(),
-            }),
-        )
-    })?;
+    let chain = chain
+        .map_err(|_e| api_error(ErrorCode::BadRequest, "Invalid base64 in certificate chain"))?;
 
     let precert_der = chain[0].clone();
 
-    let is_precert = LogEntry::is_precertificate(&precert_der).map_err(|e| {
-        (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: e.to_string(),
-            }),
-        )
-    })?;
+    let is_precert = LogEntry::is_precertificate(&precert_der)
+        .map_err(|e| api_error(rejected_chain_code(&e), e.to_string()))?;
 
     if !is_precert {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: "Certificate is not a pre-certificate (missing poison extension)"
-                    .to_string(),
-            }),
+        return Err(api_error(
+            ErrorCode::InvalidChain,
+            "Certificate is not a pre-certificate (missing poison extension)",
         ));
     }
 
+    let precert = x509_cert::Certificate::from_der(&precert_der).map_err(|e| {
+        api_error(
+            ErrorCode::MalformedCertificate,
+            format!("Invalid pre-certificate: {}", e),
+        )
+    })?;
+    check_tbs_signature_algorithm(&precert)
+        .map_err(|e| api_error(ErrorCode::InvalidChain, e.to_string()))?;
+
     let mut processed_chain = Vec::new();
     if chain.len() > 1 {
         for chain_cert in &chain[1..] {
             let chain_cert_der = chain_cert.clone();
 
             let _ = x509_cert::Certificate::from_der(&chain_cert_der).map_err(|e| {
-                (
-                    StatusCode::BAD_REQUEST,
-                    Json(ErrorResponse {
-                        error: format!("Invalid X.509 certificate in chain {}", e),
-                    }),
+                api_error(
+                    ErrorCode::MalformedCertificate,
+                    format!("Invalid X.509 certificate in chain {}", e),
                 )
             })?;
 
@@ -302,58 +372,52 @@ pub async fn add_pre_chain(
     }
 
     let mut complete_chain = vec![precert_der.clone()];
-    complete_chain.extend(processed_chain.clone());
+    complete_chain.extend(processed_chain);
 
-    if let Some(validator_lock) = &state.validator {
-        let validator = validator_lock.read().await;
-        validator
-            .validate_chain(&complete_chain)
+    if let (Some(validator_lock), Some(validation_pool)) =
+        (&state.validator, &state.validation_pool)
+    {
+        complete_chain = validation_pool
+            .validate(
+                validator_lock,
+                complete_chain.clone(),
+                LogEntryType::PrecertEntry,
+            )
             .await
-            .map_err(|e| {
-                (
-                    StatusCode::BAD_REQUEST,
-                    Json(ErrorResponse {
-                        error: format!("Certificate validation failed: {}", e),
-                    }),
-                )
+            .map_err(|e| match e {
+                ValidationPoolError::Busy => {
+                    state
+                        .metrics
+                        .certificate_submissions_total
+                        .with_label_values(&["precert", "rate_limited"])
+                        .inc();
+                    validation_busy_response()
+                }
+                ValidationPoolError::Rejected(e) => {
+                    record_rejection(&state, client_ip, &e, complete_chain);
+                    api_error(
+                        rejected_chain_code(&e),
+                        format!("Certificate validation failed: {}", e),
+                    )
+                }
             })?;
     }
+    let processed_chain = complete_chain[1..].to_vec();
 
     let issuer_key_hash = if let Some(validator_lock) = &state.validator {
         let validator = validator_lock.read().await;
         validator
             .extract_issuer_key_hash(&complete_chain)
             .map(|hash| hash.to_vec())
-            .map_err(|e| {
-                (
-                    StatusCode::BAD_REQUEST,
-                    Json(ErrorResponse {
-                        error: e.to_string(),
-                    }),
-                )
-            })?
+            .map_err(|e| api_error(ErrorCode::InvalidChain, e.to_string()))?
     } else {
         crate::validation::extract_issuer_key_hash_minimal(&complete_chain)
             .map(|hash| hash.to_vec())
-            .map_err(|e| {
-                (
-                    StatusCode::BAD_REQUEST,
-                    Json(ErrorResponse {
-                        error: e.to_string(),
-                    }),
-                )
-            })?
+            .map_err(|e| api_error(ErrorCode::InvalidChain, e.to_string()))?
     };
 
     let tbs_certificate = TbsExtractor::extract_tbs_certificate(&precert_der, &processed_chain)
-        .map_err(|e| {
-            (
-                StatusCode::BAD_REQUEST,
-                Json(ErrorResponse {
-                    error: e.to_string(),
-                }),
-            )
-        })?;
+        .map_err(|e| api_error(ErrorCode::InvalidChain, e.to_string()))?;
 
     use sha2::{Digest, Sha256};
     let mut hasher = Sha256::new();
@@ -365,7 +429,7 @@ pub async fn add_pre_chain(
         .storage
         .get_sct_by_cert_hash(&cert_hash)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(e.into())))?
+        .map_err(|e| api_error(ErrorCode::Internal, e.to_string()))?
     {
         let response = AddChainResponse {
             sct_version: existing_sct_entry.sct.version as u8,
@@ -397,24 +461,23 @@ pub async fn add_pre_chain(
     let tbs_cert_for_sct = tbs_certificate.clone();
     let issuer_key_hash_for_sct = issuer_key_hash.clone();
 
-    let (_assigned_index, sct) = match state
+    let (assigned_index, sct) = match state
         .storage
         .add_entry_batched(log_entry, cert_hash, move |index| {
-            sct_builder
-                .create_sct_with_timestamp_and_index(
-                    &tbs_cert_for_sct,
-                    LogEntryType::PrecertEntry,
-                    Some(&issuer_key_hash_for_sct),
-                    timestamp_ms,
-                    Some(index),
-                )
-                .expect("Failed to create SCT")
+            sct_builder.create_sct_with_timestamp_and_index(
+                &tbs_cert_for_sct,
+                LogEntryType::PrecertEntry,
+                Some(&issuer_key_hash_for_sct),
+                timestamp_ms,
+                Some(index),
+            )
         })
         .await
     {
         Ok(result) => result,
         Err(e) => return Err(handle_storage_error(e).await),
     };
+    record_submitter(&state, assigned_index, submitter).await;
 
     let response = AddChainResponse {
         sct_version: sct.version as u8,
@@ -427,263 +490,540 @@ pub async fn add_pre_chain(
     Ok(Json(response))
 }
 
+/// get-sth, with `since=N` adding the proof that the STH served is
+/// consistent with the client's STH at size `N`
 pub async fn get_sth(
     State(state): State<Arc<ApiState>>,
-) -> ApiResult<crate::types::tree_head::SthResponse> {
+    Query(params): Query<GetSthRequest>,
+) -> ApiResult<GetSthResponse> {
+    let sth = current_sth(&state).await?;
+    let consistency = match params.since {
+        Some(since) if since > sth.tree_size => {
+            return Err(api_error(
+                ErrorCode::BadRequest,
+                format!("since {} is past the tree size {}", since, sth.tree_size),
+            ));
+        }
+        Some(since) => Some(
+            consistency_proof(&state, since, sth.tree_size)
+                .await?
+                .consistency,
+        ),
+        None => None,
+    };
+    Ok(Json(GetSthResponse {
+        sth: sth.to_api_response(),
+        consistency,
+    }))
+}
+
+/// The log's clock, for clients to compare theirs with before submitting.
+/// SCTs are only signed for timestamps close to it.
+pub async fn get_timestamp() -> Json<GetTimestampResponse> {
+    Json(GetTimestampResponse {
+        timestamp: chrono::Utc::now().timestamp_millis() as u64,
+    })
+}
+
+/// The STH the signing task last published, or one signed now before it has
+pub(super) async fn current_sth(
+    state: &ApiState,
+) -> Result<SignedTreeHead, (StatusCode, Json<ErrorResponse>)> {
+    if let Some(cached) = state.cached_sth.load_full() {
+        return Ok(cached.sth.clone());
+    }
+
     // Get the committed root (which includes the committed size)
     let committed_root = state
         .merkle_tree
         .committed_root()
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(e.into())))?;
+        .map_err(|e| api_error(ErrorCode::Internal, e.to_string()))?;
 
     let tree_size = committed_root.num_leaves();
     let root_hash = committed_root.as_bytes().to_vec();
 
     let timestamp = chrono::Utc::now().timestamp_millis() as u64;
-    let sth = state
+    state
         .sth_builder
         .create_sth(tree_size, root_hash, Some(timestamp))
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(e.into())))?;
+        .map_err(|e| api_error(ErrorCode::Internal, e.to_string()))
+}
 
-    Ok(Json(sth.to_api_response()))
+/// A proof the tree produced but cannot serialize points at corrupt storage
+fn proof_serialization_error(e: crate::types::CtError) -> (StatusCode, Json<ErrorResponse>) {
+    tracing::error!("Failed to serialize proof: {}", e);
+    api_error(ErrorCode::Internal, e.to_string())
 }
 
 pub async fn get_sth_consistency(
     State(state): State<Arc<ApiState>>,
     Query(params): Query<GetConsistencyProofRequest>,
 ) -> ApiResult<GetConsistencyProofResponse> {
+    Ok(Json(
+        consistency_proof(&state, params.first, params.second).await?,
+    ))
+}
+
+/// Consistency proof between two STH sizes, from the cache when fresh
+async fn consistency_proof(
+    state: &ApiState,
+    first: u64,
+    second: u64,
+) -> Result<GetConsistencyProofResponse, (StatusCode, Json<ErrorResponse>)> {
+    let key = (first, second);
+    match state.consistency_cache.get(&key) {
+        Some(entry) if entry.value().is_fresh() => {
+            state.metrics.consistency_cache_hits.inc();
+            return Ok(entry.value().response.clone());
+        }
+        Some(_) => {
+            state.consistency_cache.remove(&key);
+        }
+        None => {}
+    }
+    state.metrics.consistency_cache_misses.inc();
+
+    // Failures, such as a size past the tree, are not cached
     let proof = state
         .merkle_tree
-        .consistency_proof_between_sizes(params.first, params.second)
+        .consistency_proof_between_sizes(first, second)
         .await
-        .map_err(|e| match e {
-            crate::types::CtError::BadRequest(_) => (StatusCode::BAD_REQUEST, Json(e.into())),
-            _ => (StatusCode::INTERNAL_SERVER_ERROR, Json(e.into())),
-        })?;
+        .map_err(|e| api_error(ErrorCode::from(&e), e.to_string()))?;
 
     let response = GetConsistencyProofResponse {
-        consistency: serialization::consistency_proof_to_path(&proof),
+        consistency: serialization::consistency_proof_to_path(&proof)
+            .map_err(proof_serialization_error)?,
     };
+    state.consistency_cache.insert(
+        key,
+        Arc::new(CachedConsistencyProof {
+            response: response.clone(),
+            cached_at: Instant::now(),
+        }),
+    );
 
-    Ok(Json(response))
+    Ok(response)
+}
+
+/// `Content-Type` of a proof in the tlog layout, and the `Accept` value that
+/// asks for one
+const TLOG_PROOF_CONTENT_TYPE: &str = "text/plain; charset=utf-8";
+
+/// The proof format `format` names, or else the one `Accept` asks for
+fn proof_format(format: Option<ProofFormat>, headers: &HeaderMap) -> ProofFormat {
+    format.unwrap_or_else(|| {
+        let accepts_text = headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|media_type| media_type.trim().starts_with("text/plain"));
+        if accepts_text {
+            ProofFormat::Tlog
+        } else {
+            ProofFormat::Rfc6962
+        }
+    })
 }
 
+/// get-proof-by-hash, as RFC 6962 JSON or, with `format=tlog` or
+/// `Accept: text/plain`, as a tlog record proof
 pub async fn get_proof_by_hash(
     State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
     Query(params): Query<GetProofByHashRequest>,
-) -> ApiResult<GetProofByHashResponse> {
-    let hash = STANDARD.decode(&params.hash).map_err(|_| {
-        (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: "Invalid base64 hash".to_string(),
-            }),
-        )
-    })?;
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let hash = STANDARD
+        .decode(&params.hash)
+        .map_err(|_| api_error(ErrorCode::BadRequest, "Invalid base64 hash"))?;
+
+    // The empty tree contains no leaves to prove
+    if params.tree_size == 0 {
+        return Err(api_error(
+            ErrorCode::BadRequest,
+            "tree_size must be at least 1",
+        ));
+    }
 
-    let leaf_index = state
-        .storage
-        .find_index_by_hash(&hash)
+    // A proof is only useful against an STH the log has issued
+    let committed_size = state
+        .merkle_tree
+        .committed_size()
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(e.into())))?
-        .ok_or_else(|| {
-            (
-                StatusCode::NOT_FOUND,
-                Json(ErrorResponse {
-                    error: "Leaf not found".to_string(),
-                }),
-            )
-        })?;
+        .map_err(|e| api_error(ErrorCode::Internal, e.to_string()))?;
+    if params.tree_size > committed_size {
+        return Err(api_error(
+            ErrorCode::BadRequest,
+            format!(
+                "tree_size {} exceeds the current tree size {}",
+                params.tree_size, committed_size
+            ),
+        ));
+    }
+
+    let leaf_index = find_leaf_index(&state, &hash).await?;
 
     if leaf_index >= params.tree_size {
-        return Err((
-            StatusCode::NOT_FOUND,
-            Json(ErrorResponse {
-                error: "Leaf not found in requested tree size".to_string(),
-            }),
+        return Err(api_error(
+            ErrorCode::NotFound,
+            "Leaf not found in requested tree size",
         ));
     }
 
+    // Built from the nodes as they were at `tree_size`, so the path verifies
+    // against that STH however far the tree has grown since
     let proof = state
         .merkle_tree
         .prove_inclusion_efficient(params.tree_size, leaf_index)
         .await
-        .map_err(|e| match e {
-            crate::types::CtError::BadRequest(_) => (StatusCode::BAD_REQUEST, Json(e.into())),
-            _ => (StatusCode::INTERNAL_SERVER_ERROR, Json(e.into())),
-        })?;
+        .map_err(|e| api_error(ErrorCode::from(&e), e.to_string()))?;
 
-    let response = GetProofByHashResponse {
-        leaf_index,
-        audit_path: serialization::inclusion_proof_to_audit_path(&proof),
-    };
+    match proof_format(params.format, &headers) {
+        ProofFormat::Rfc6962 => {
+            let response = serialization::inclusion_proof_to_response(leaf_index, &proof)
+                .map_err(proof_serialization_error)?;
+            Ok(Json(response).into_response())
+        }
+        ProofFormat::Tlog => {
+            let record = serialization::RecordProof {
+                leaf_index,
+                tree_size: params.tree_size,
+                proof,
+            };
+            let text = record.to_tlog().map_err(proof_serialization_error)?;
+            Ok(([(header::CONTENT_TYPE, TLOG_PROOF_CONTENT_TYPE)], text).into_response())
+        }
+    }
+}
 
-    Ok(Json(response))
+async fn find_leaf_index(
+    state: &ApiState,
+    hash: &[u8],
+) -> Result<u64, (StatusCode, Json<ErrorResponse>)> {
+    match state.merkle_tree.get_leaf_index_by_hash(hash).await {
+        Ok(Some(index)) => Ok(Some(index)),
+        // Entries logged before the tree indexed leaf hashes
+        Ok(None) => state
+            .storage
+            .find_index_by_hash(hash)
+            .await
+            .map_err(Into::into),
+        Err(e) => Err(e),
+    }
+    .map_err(|e| api_error(ErrorCode::Internal, e.to_string()))?
+    .ok_or_else(|| api_error(ErrorCode::NotFound, "Leaf not found"))
 }
 
-fn build_extra_data(log_entry: &LogEntry) -> Result<Vec<u8>, (StatusCode, Json<ErrorResponse>)> {
-    let mut extra_data = Vec::new();
-    match log_entry.entry_type {
-        crate::types::LogEntryType::X509Entry => {
-            // For X509ChainEntry, extra_data contains just the certificate_chain
-            if let Some(ref chain_data) = log_entry.chain {
-                // Calculate total chain length for TLS vector length prefix
-                let mut total_chain_len = 0u32;
-                for chain_cert in chain_data {
-                    total_chain_len += 3 + chain_cert.len() as u32; // 3-byte length + cert data
-                }
+fn build_extra_data(log_entry: &LogEntry) -> crate::types::Result<Vec<u8>> {
+    ExtraData::for_entry(log_entry)?.encode()
+}
 
-                // TLS variable-length vector: chain length (3 bytes)
-                extra_data.push((total_chain_len >> 16) as u8);
-                extra_data.push((total_chain_len >> 8) as u8);
-                extra_data.push(total_chain_len as u8);
-
-                // Each certificate in the chain: length (3 bytes) + cert data
-                for chain_cert in chain_data {
-                    let cert_len = chain_cert.len() as u32;
-                    extra_data.push((cert_len >> 16) as u8);
-                    extra_data.push((cert_len >> 8) as u8);
-                    extra_data.push(cert_len as u8);
-                    extra_data.extend_from_slice(chain_cert);
-                }
-            } else {
-                // Empty chain: just the length field (0)
-                extra_data.extend_from_slice(&[0x00, 0x00, 0x00]);
-            }
-        }
-        crate::types::LogEntryType::PrecertEntry => {
-            // For PrecertChainEntry, extra_data contains the whole PrecertChainEntry structure
-            // struct { ASN.1Cert pre_certificate; ASN.1Cert precertificate_chain<0..2^24-1>; }
-
-            // Pre_certificate length (3 bytes) + pre_certificate data (original, not TBS)
-            if let Some(ref original_precert) = log_entry.original_precert {
-                let precert_len = original_precert.len() as u32;
-                extra_data.push((precert_len >> 16) as u8);
-                extra_data.push((precert_len >> 8) as u8);
-                extra_data.push(precert_len as u8);
-                extra_data.extend_from_slice(original_precert);
-            } else {
-                return Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ErrorResponse {
-                        error: "Missing original pre-certificate for PrecertEntry".to_string(),
-                    }),
-                ));
-            }
+/// Entries fetched ahead of the one currently being written to the response
+const GET_ENTRIES_PREFETCH: usize = 16;
 
-            if let Some(ref chain_data) = log_entry.chain {
-                let mut total_chain_len = 0u32;
-                for chain_cert in chain_data {
-                    total_chain_len += 3 + chain_cert.len() as u32; // 3-byte length + cert data
-                }
+/// Most entries returned by one get-entries or get-recent-entries request
+const MAX_ENTRIES: u64 = 1000;
 
-                // TLS variable-length vector: chain length (3 bytes)
-                extra_data.push((total_chain_len >> 16) as u8);
-                extra_data.push((total_chain_len >> 8) as u8);
-                extra_data.push(total_chain_len as u8);
-
-                // Each certificate in the chain: length (3 bytes) + cert data
-                for chain_cert in chain_data {
-                    let cert_len = chain_cert.len() as u32;
-                    extra_data.push((cert_len >> 16) as u8);
-                    extra_data.push((cert_len >> 8) as u8);
-                    extra_data.push(cert_len as u8);
-                    extra_data.extend_from_slice(chain_cert);
-                }
-            } else {
-                // Empty chain: just the length field (0)
-                extra_data.extend_from_slice(&[0x00, 0x00, 0x00]);
-            }
-        }
-    }
-    Ok(extra_data)
-}
+/// Response header carrying the cursor for the page after this one
+pub const NEXT_CURSOR_HEADER: &str = "ct-next-cursor";
 
+/// Streams the response one entry at a time so memory stays proportional to
+/// the prefetch window rather than the requested range. A failure after the
+/// first entry aborts the body instead of producing truncated JSON.
+///
+/// With `cursor` the page runs from the cursor's position up to its tree size
+/// and the response carries the next cursor. Once a cursor reaches its tree
+/// size, the next page is cut against the current committed size, so a
+/// client walking a growing log sees every entry exactly once.
 pub async fn get_entries(
     State(state): State<Arc<ApiState>>,
     Query(params): Query<GetEntriesRequest>,
-) -> ApiResult<GetEntriesResponse> {
-    if params.start > params.end {
-        crate::metrics::GET_ENTRIES_REQUESTS
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let bad_request = |message: String| {
+        state
+            .metrics
+            .get_entries_requests
             .with_label_values(&["failed"])
             .inc();
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: "Invalid range: start > end".to_string(),
-            }),
-        ));
-    }
-
-    const MAX_ENTRIES: u64 = 1000;
+        api_error(ErrorCode::BadRequest, message)
+    };
 
-    let count = (params.end - params.start + 1).min(MAX_ENTRIES);
-    let end = params.start + count - 1;
+    let committed_size = state
+        .merkle_tree
+        .committed_size()
+        .await
+        .map_err(|e| api_error(ErrorCode::Internal, e.to_string()))?;
+
+    // Entries `start..end`, the end of the range asked for, and the cursor to
+    // hand back when paging by cursor
+    let (start, end, wanted_end, next_cursor) = match (&params.cursor, params.start, params.end) {
+        (Some(token), _, _) => {
+            let cursor = if token.is_empty() {
+                EntriesCursor {
+                    next: 0,
+                    tree_size: committed_size,
+                }
+            } else {
+                EntriesCursor::decode(token).map_err(|e| bad_request(e.to_string()))?
+            };
+            if cursor.tree_size > committed_size {
+                return Err(bad_request("Cursor tree size exceeds the log".to_string()));
+            }
 
-    crate::metrics::GET_ENTRIES_BATCH_SIZE
+            let tree_size = if cursor.next == cursor.tree_size {
+                committed_size
+            } else {
+                cursor.tree_size
+            };
+            let end = tree_size.min(cursor.next + MAX_ENTRIES);
+            let next = EntriesCursor {
+                next: end,
+                tree_size,
+            };
+            (cursor.next, end, tree_size, Some(next))
+        }
+        (None, Some(start), Some(end)) => {
+            if start > end {
+                return Err(bad_request("Invalid range: start > end".to_string()));
+            }
+            // Only committed entries are served, so a range running past the
+            // tree counts as covered once it reaches the end of the tree
+            let wanted_end = end.saturating_add(1).min(committed_size).max(start);
+            (
+                start,
+                wanted_end.min(start.saturating_add(MAX_ENTRIES)),
+                wanted_end,
+                None,
+            )
+        }
+        (None, _, _) => {
+            return Err(bad_request(
+                "Either start and end or cursor is required".to_string(),
+            ))
+        }
+    };
+    let count = end - start;
+    // Clients told where to resume need not count the entries they got,
+    // which they cannot do anyway when filtering by type
+    let next_entry = (end < wanted_end).then_some(end);
+
+    state
+        .metrics
+        .get_entries_batch_size
         .with_label_values::<&str>(&[])
         .observe(count as f64);
 
     let storage = state.storage.clone();
+    let entry_type = params.entry_type.map(LogEntryType::from);
+    let leaf_only = params.leaf_only;
 
-    let futures: Vec<_> = (params.start..=end)
-        .map(|i| {
+    let indices: Vec<u64> = match entry_type {
+        _ if count == 0 => Vec::new(),
+        Some(entry_type) => storage
+            .candidate_indices_for_type(entry_type, start, end - 1)
+            .await
+            .map_err(|e| {
+                state
+                    .metrics
+                    .get_entries_requests
+                    .with_label_values(&["failed"])
+                    .inc();
+                api_error(ErrorCode::Internal, e.to_string())
+            })?,
+        None => (start..end).collect(),
+    };
+
+    let mut entries = stream::iter(indices)
+        .map(move |i| {
             let storage = storage.clone();
             async move { storage.get_entry(i).await }
         })
-        .collect();
+        .buffered(GET_ENTRIES_PREFETCH)
+        .filter_map(move |result| async move {
+            match result {
+                // Entries logged before the type index existed are filtered here
+                Ok(Some(log_entry)) if entry_type.is_none_or(|t| t == log_entry.entry_type) => {
+                    Some(leaf_entry_json(&log_entry, leaf_only))
+                }
+                Ok(_) => None,
+                Err(e) => Some(Err(e.into())),
+            }
+        })
+        .boxed();
 
-    let results = futures::future::join_all(futures).await;
+    // Until the first entry is ready nothing has been sent, so errors can
+    // still become a regular error response
+    let first = entries.next().await.transpose().map_err(|e| {
+        state
+            .metrics
+            .get_entries_requests
+            .with_label_values(&["failed"])
+            .inc();
+        api_error(ErrorCode::Internal, e.to_string())
+    })?;
 
-    let mut entries = Vec::new();
+    let mut head = b"{\"entries\":[".to_vec();
+    if let Some(json) = first {
+        head.extend_from_slice(&json);
+    }
 
-    for result in results.into_iter() {
-        if let Some(log_entry) =
-            result.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(e.into())))?
-        {
-            let leaf_input = log_entry
-                .serialize()
-                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(e.into())))?;
+    let failed = state
+        .metrics
+        .get_entries_requests
+        .with_label_values(&["failed"]);
+    let rest = entries
+        .map_ok(|json| {
+            let mut chunk = Vec::with_capacity(json.len() + 1);
+            chunk.push(b',');
+            chunk.extend_from_slice(&json);
+            Bytes::from(chunk)
+        })
+        .inspect_err(move |e| {
+            failed.inc();
+            tracing::error!("get-entries aborted mid-response: {}", e);
+        });
+
+    let tail = format!(
+        "],\"next_entry\":{},\"tree_size\":{}}}",
+        next_entry.map_or_else(|| "null".to_string(), |next| next.to_string()),
+        committed_size
+    );
+    let succeeded = state
+        .metrics
+        .get_entries_requests
+        .with_label_values(&["success"]);
+    let body = stream::once(async move { Ok(Bytes::from(head)) })
+        .chain(rest)
+        .chain(stream::once(async move {
+            // Counted once the last entry is out, as the response can still
+            // fail until then
+            succeeded.inc();
+            Ok(Bytes::from(tail))
+        }))
+        // Nothing follows an error, so a failed response is never also
+        // counted as a success
+        .scan(false, |errored, chunk| {
+            if *errored {
+                return future::ready(None);
+            }
+            *errored = chunk.is_err();
+            future::ready(Some(chunk))
+        });
 
-            let extra_data = build_extra_data(&log_entry)?;
+    let mut response = Response::builder().header(header::CONTENT_TYPE, "application/json");
+    if let Some(cursor) = next_cursor {
+        response = response.header(NEXT_CURSOR_HEADER, cursor.encode());
+    }
+    response.body(Body::from_stream(body)).map_err(|e| {
+        api_error(
+            ErrorCode::Internal,
+            format!("Failed to build response: {}", e),
+        )
+    })
+}
 
-            let leaf_entry = LeafEntry {
-                leaf_input: STANDARD.encode(&leaf_input),
-                extra_data: STANDARD.encode(&extra_data),
-            };
+/// The newest `count` committed leaves, newest first, so a monitor can tail
+/// the log without first fetching the STH to work out the range
+pub async fn get_recent_entries(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<GetRecentEntriesRequest>,
+) -> ApiResult<GetRecentEntriesResponse> {
+    let leaves = state
+        .merkle_tree
+        .get_recent(params.count.min(MAX_ENTRIES))
+        .await
+        .map_err(|e| api_error(ErrorCode::Internal, e.to_string()))?;
 
-            entries.push(leaf_entry);
-        }
-    }
+    Ok(Json(GetRecentEntriesResponse {
+        entries: leaves
+            .into_iter()
+            .map(|(index, leaf)| RecentLeafEntry {
+                index,
+                leaf_input: STANDARD.encode(&leaf.data),
+            })
+            .collect(),
+    }))
+}
 
-    crate::metrics::GET_ENTRIES_REQUESTS
-        .with_label_values(&["success"])
-        .inc();
+/// One element of the get-entries `entries` array, serialized
+fn leaf_entry_json(log_entry: &LogEntry, leaf_only: bool) -> crate::types::Result<Vec<u8>> {
+    let leaf_input = log_entry.serialize()?;
+    let extra_data = if leaf_only {
+        Vec::new()
+    } else {
+        build_extra_data(log_entry)?
+    };
+
+    let leaf_entry = LeafEntry {
+        leaf_input: STANDARD.encode(&leaf_input),
+        extra_data: STANDARD.encode(&extra_data),
+    };
 
-    Ok(Json(GetEntriesResponse { entries }))
+    serde_json::to_vec(&leaf_entry)
+        .map_err(|e| crate::types::CtError::Internal(format!("Failed to encode entry: {}", e)))
 }
 
-pub async fn get_roots(State(state): State<Arc<ApiState>>) -> ApiResult<GetRootsResponse> {
-    if let Some(validator_lock) = &state.validator {
-        let validator = validator_lock.read().await;
-        let root_certs = validator
+pub async fn get_roots(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<GetRootsRequest>,
+) -> ApiResult<GetRootsResponse> {
+    let root_certs = match &state.validator {
+        Some(validator_lock) => validator_lock
+            .read()
+            .await
             .get_accepted_roots()
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(e.into())))?;
+            .map_err(|e| api_error(ErrorCode::Internal, e.to_string()))?,
+        // No validator configured, so no roots
+        None => Vec::new(),
+    };
 
-        // Convert DER certificates to base64
-        let certificates = root_certs
-            .into_iter()
-            .map(|cert_der| STANDARD.encode(&cert_der))
-            .collect();
+    // Roots by the key `start` refers to, in the order they are served
+    let roots: Vec<(u64, Vec<u8>)> = match state.roots_order {
+        // Pages are ordered by stored index, so they stay put across root updates
+        RootsOrder::Index => {
+            let indices = state
+                .storage
+                .root_indices(&root_certs)
+                .await
+                .map_err(|e| api_error(ErrorCode::Internal, e.to_string()))?;
+            let mut roots: Vec<_> = indices.into_iter().zip(root_certs).collect();
+            roots.sort_unstable_by_key(|(index, _)| *index);
+            roots
+        }
+        RootsOrder::Fingerprint => {
+            use sha2::{Digest, Sha256};
+            let mut by_fingerprint: Vec<_> = root_certs
+                .into_iter()
+                .map(|cert_der| (Sha256::digest(&cert_der), cert_der))
+                .collect();
+            by_fingerprint.sort_unstable();
+            by_fingerprint
+                .into_iter()
+                .enumerate()
+                .map(|(position, (_, cert_der))| (position as u64, cert_der))
+                .collect()
+        }
+    };
 
-        Ok(Json(GetRootsResponse { certificates }))
-    } else {
-        // No validator configured, return empty list
-        Ok(Json(GetRootsResponse {
-            certificates: vec![],
-        }))
-    }
+    let start = params.start.unwrap_or(0);
+    let limit = params
+        .limit
+        .unwrap_or(state.max_roots_per_page)
+        .min(state.max_roots_per_page);
+    let mut page = roots.iter().skip_while(|(index, _)| *index < start);
+    let certificates = page
+        .by_ref()
+        .take(limit as usize)
+        .map(|(_, cert_der)| STANDARD.encode(cert_der))
+        .collect();
+    let next_start = page.next().map(|(index, _)| *index);
+
+    Ok(Json(GetRootsResponse {
+        certificates,
+        total: roots.len() as u64,
+        next_start,
+    }))
 }
 
 pub async fn get_entry_and_proof(
@@ -695,51 +1035,55 @@ pub async fn get_entry_and_proof(
             .merkle_tree
             .size()
             .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(e.into())))?
+            .map_err(|e| api_error(ErrorCode::Internal, e.to_string()))?
     });
 
-    if params.leaf_index >= tree_size {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: "Leaf index out of bounds".to_string(),
-            }),
+    if tree_size == 0 {
+        return Err(api_error(
+            ErrorCode::BadRequest,
+            "tree_size must be at least 1",
         ));
     }
+    if params.leaf_index >= tree_size {
+        return Err(api_error(ErrorCode::BadRequest, "Leaf index out of bounds"));
+    }
 
     let log_entry = state
         .storage
         .get_entry(params.leaf_index)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(e.into())))?
-        .ok_or_else(|| {
-            (
-                StatusCode::NOT_FOUND,
-                Json(ErrorResponse {
-                    error: "Log entry not found".to_string(),
-                }),
-            )
-        })?;
+        .map_err(|e| api_error(ErrorCode::Internal, e.to_string()))?
+        .ok_or_else(|| api_error(ErrorCode::NotFound, "Log entry not found"))?;
 
     let leaf_input = log_entry
         .serialize()
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(e.into())))?;
+        .map_err(|e| api_error(ErrorCode::Internal, e.to_string()))?;
+
+    if state.verify_served_leaves {
+        state
+            .merkle_tree
+            .verify_served_leaf(params.leaf_index, &leaf_input)
+            .await
+            .map_err(|e| {
+                tracing::error!("Refusing to serve leaf {}: {}", params.leaf_index, e);
+                api_error(ErrorCode::Internal, e.to_string())
+            })?;
+    }
 
-    let extra_data = build_extra_data(&log_entry)?;
+    let extra_data =
+        build_extra_data(&log_entry).map_err(|e| api_error(ErrorCode::Internal, e.to_string()))?;
 
     let proof = state
         .merkle_tree
         .prove_inclusion_efficient(tree_size, params.leaf_index)
         .await
-        .map_err(|e| match e {
-            crate::types::CtError::BadRequest(_) => (StatusCode::BAD_REQUEST, Json(e.into())),
-            _ => (StatusCode::INTERNAL_SERVER_ERROR, Json(e.into())),
-        })?;
+        .map_err(|e| api_error(ErrorCode::from(&e), e.to_string()))?;
 
     let response = GetEntryAndProofResponse {
         leaf_input: STANDARD.encode(&leaf_input),
         extra_data: STANDARD.encode(&extra_data),
-        audit_path: serialization::inclusion_proof_to_audit_path(&proof),
+        audit_path: serialization::inclusion_proof_to_audit_path(&proof)
+            .map_err(proof_serialization_error)?,
     };
 
     Ok(Json(response))
@@ -758,18 +1102,95 @@ pub struct GetEntryAndProofResponse {
     pub audit_path: Vec<String>,
 }
 
+/// Name of the algorithm of a DER SubjectPublicKeyInfo, falling back to its OID
+fn key_algorithm(public_key_der: &[u8]) -> String {
+    let Ok(spki) = spki::SubjectPublicKeyInfoRef::from_der(public_key_der) else {
+        return "unknown".to_string();
+    };
+
+    match (spki.algorithm.oid, spki.algorithm.parameters_oid().ok()) {
+        (EC_PUBLIC_KEY_OID, Some(SECP256R1_OID)) => "ecdsa-p256".to_string(),
+        (EC_PUBLIC_KEY_OID, Some(SECP384R1_OID)) => "ecdsa-p384".to_string(),
+        (RSA_ENCRYPTION_OID, _) => "rsa".to_string(),
+        (oid, _) => oid.to_string(),
+    }
+}
+
+/// The log's inclusion request, or with `hash` an inclusion proof of that leaf
 pub async fn inclusion_request(
     State(state): State<Arc<ApiState>>,
-) -> ApiResult<InclusionRequestResponse> {
+    Query(params): Query<InclusionRequestParams>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    match params.hash {
+        Some(hash) => Ok(Json(leaf_inclusion(&state, &hash).await?).into_response()),
+        None => Ok(Json(log_inclusion_request(&state).await?).into_response()),
+    }
+}
+
+async fn leaf_inclusion(
+    state: &ApiState,
+    hash: &str,
+) -> Result<LeafInclusionResponse, (StatusCode, Json<ErrorResponse>)> {
+    let hash = STANDARD
+        .decode(hash)
+        .map_err(|_| api_error(ErrorCode::BadRequest, "Invalid base64 hash"))?;
+
+    // Size and timestamp come from one STH, taken before the leaf lookup, so
+    // the tree growing meanwhile cannot pair a proof with the wrong head
+    let sth = current_sth(state).await?;
+    let leaf_index = find_leaf_index(state, &hash).await?;
+    if leaf_index >= sth.tree_size {
+        return Err(api_error(
+            ErrorCode::NotFound,
+            "Leaf is not yet covered by a signed tree head",
+        ));
+    }
+
+    let proof = state
+        .merkle_tree
+        .prove_inclusion_efficient(sth.tree_size, leaf_index)
+        .await
+        .map_err(|e| api_error(ErrorCode::Internal, e.to_string()))?;
+
+    Ok(LeafInclusionResponse {
+        log_id: state.log_id.to_base64(),
+        tree_size: sth.tree_size,
+        timestamp: sth.timestamp,
+        leaf_index,
+        leaf_hash: STANDARD.encode(&hash),
+        proof: serialization::inclusion_proof_to_audit_path(&proof)
+            .map_err(proof_serialization_error)?,
+    })
+}
+
+async fn log_inclusion_request(
+    state: &ApiState,
+) -> Result<InclusionRequestResponse, (StatusCode, Json<ErrorResponse>)> {
     let public_key_base64 = STANDARD.encode(&state.public_key_der);
 
-    let log_id_base64 = STANDARD.encode(state.log_id.as_bytes());
+    let log_id_base64 = state.log_id.to_base64();
 
-    let mmd = 0;
+    let mmd = state
+        .log_metadata
+        .as_ref()
+        .map_or(0, |metadata| metadata.mmd_seconds);
+
+    let mut accepted_signature_algorithms = None;
 
     let temporal_interval = if let Some(validator_lock) = &state.validator {
         let validator = validator_lock.read().await;
         let config = validator.get_config();
+
+        let mut algorithms: Vec<String> = config
+            .allowed_signature_algorithms
+            .iter()
+            .map(|oid| {
+                oids::signature_algorithm_name(oid).map_or_else(|| oid.clone(), str::to_string)
+            })
+            .collect();
+        algorithms.sort();
+        accepted_signature_algorithms = Some(algorithms);
+
         if let Some(window) = &config.temporal_window {
             TemporalInterval {
                 start_inclusive: window.start.to_rfc3339(),
@@ -793,11 +1214,1155 @@ pub async fn inclusion_request(
 
     let response = InclusionRequestResponse {
         key: public_key_base64,
+        key_algorithm: key_algorithm(&state.public_key_der),
         log_id: log_id_base64,
         mmd,
         temporal_interval,
         url,
+        accepted_signature_algorithms,
     };
 
-    Ok(Json(response))
+    Ok(response)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::merkle_storage::StorageBackedMerkleTree;
+    use crate::storage::{BatchConfig, CtStorage, RateLimitedDb};
+    use crate::test_utils::alloc;
+    use crate::types::merkle_leaf::{MerkleTreeLeaf, SignedEntry};
+    use crate::types::sct::{SctVersion, SignedCertificateTimestamp};
+    use crate::types::{
+        DeduplicatedLogEntry, EntryTypeFilter, GetEntriesResponse, GetProofByHashResponse, LogId,
+    };
+    use object_store::memory::InMemory;
+    use p256::ecdsa::SigningKey;
+    use p256::pkcs8::EncodePublicKey;
+    use slatedb::Db;
+    use std::ops::Range;
+
+    const ENTRY_COUNT: u64 = 1000;
+    const CERT_SIZE: usize = 4096;
+
+    fn test_certificate(index: u64) -> Vec<u8> {
+        let mut certificate = vec![0x5a; CERT_SIZE];
+        certificate[..8].copy_from_slice(&index.to_be_bytes());
+        certificate
+    }
+
+    pub(crate) async fn create_test_state(entry_count: u64) -> Arc<ApiState> {
+        create_test_state_with_metrics(entry_count, crate::metrics::global()).await
+    }
+
+    pub(crate) async fn create_test_state_with_metrics(
+        entry_count: u64,
+        metrics: Arc<crate::metrics::Metrics>,
+    ) -> Arc<ApiState> {
+        let object_store = Arc::new(InMemory::new());
+        let db = Arc::new(Db::open("test", object_store).await.unwrap());
+        create_test_state_on(db, entry_count, metrics).await
+    }
+
+    /// A test log on `db`, for tests that tamper with the database under it
+    pub(crate) async fn create_test_state_on(
+        db: Arc<Db>,
+        entry_count: u64,
+        metrics: Arc<crate::metrics::Metrics>,
+    ) -> Arc<ApiState> {
+        create_test_state_with_witnesses(db, entry_count, metrics, Vec::new()).await
+    }
+
+    /// A test log on `db` that records cosignatures from `witness_keys`
+    pub(crate) async fn create_test_state_with_witnesses(
+        db: Arc<Db>,
+        entry_count: u64,
+        metrics: Arc<crate::metrics::Metrics>,
+        witness_keys: Vec<crate::storage::WitnessKey>,
+    ) -> Arc<ApiState> {
+        let rate_limited_db = RateLimitedDb::new(db, None);
+        let merkle_tree = StorageBackedMerkleTree::new(rate_limited_db.clone())
+            .await
+            .unwrap();
+        let storage = CtStorage::new_with_metrics(
+            rate_limited_db,
+            BatchConfig::default(),
+            merkle_tree.clone(),
+            metrics,
+        )
+        .await
+        .unwrap()
+        .with_witness_keys(witness_keys);
+
+        let signing_key = SigningKey::random(&mut rand::thread_rng());
+        let public_key_der = signing_key
+            .verifying_key()
+            .to_public_key_der()
+            .unwrap()
+            .into_vec();
+        let log_id = LogId::from_public_key_der(&public_key_der);
+
+        append_test_entries(&storage, &log_id, 0..entry_count).await;
+
+        Arc::new(
+            ApiState::new(
+                Arc::new(storage),
+                merkle_tree,
+                Box::new(signing_key),
+                "http://localhost/".to_string(),
+                None,
+            )
+            .unwrap(),
+        )
+    }
+
+    pub(crate) async fn append_test_entries(
+        storage: &CtStorage,
+        log_id: &LogId,
+        indices: Range<u64>,
+    ) {
+        let submissions = indices.map(|i| {
+            let log_id = log_id.clone();
+            async move {
+                let entry = LogEntry::new_with_timestamp(
+                    i,
+                    test_certificate(i),
+                    Some(vec![vec![0x04, 0x05]]),
+                    chrono::Utc::now(),
+                );
+                let cert_hash = DeduplicatedLogEntry::hash_certificate(&entry.certificate);
+                storage
+                    .add_entry_batched(entry, cert_hash, move |_| {
+                        Ok(SignedCertificateTimestamp {
+                            version: SctVersion::V1,
+                            log_id: log_id.clone(),
+                            timestamp: 0,
+                            extensions: vec![],
+                            signature: vec![],
+                        })
+                    })
+                    .await
+                    .unwrap()
+            }
+        });
+        futures::future::join_all(submissions).await;
+    }
+
+    #[tokio::test]
+    async fn test_get_recent_entries() {
+        let state = create_test_state(5).await;
+
+        let recent = |count| {
+            let state = state.clone();
+            async move {
+                get_recent_entries(State(state), Query(GetRecentEntriesRequest { count }))
+                    .await
+                    .unwrap()
+                    .0
+                    .entries
+            }
+        };
+
+        let entries = recent(2).await;
+        let indices: Vec<u64> = entries.iter().map(|e| e.index).collect();
+        assert_eq!(indices, vec![4, 3]);
+        let tail = state.merkle_tree.get_leaf_range(4, 4).await.unwrap();
+        assert_eq!(
+            STANDARD.decode(&entries[0].leaf_input).unwrap(),
+            tail[0].data
+        );
+
+        assert_eq!(recent(100).await.len(), 5);
+        assert!(recent(0).await.is_empty());
+    }
+
+    async fn request_all(state: &Arc<ApiState>) -> Response {
+        get_entries(
+            State(state.clone()),
+            Query(GetEntriesRequest {
+                start: Some(0),
+                end: Some(ENTRY_COUNT - 1),
+                cursor: None,
+                entry_type: None,
+                leaf_only: false,
+            }),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_get_entries_streams_with_bounded_memory() {
+        let state = create_test_state(ENTRY_COUNT).await;
+
+        let response = request_all(&state).await;
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let parsed: GetEntriesResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(parsed.entries.len() as u64, ENTRY_COUNT);
+
+        let certificates: Vec<Vec<u8>> = parsed
+            .entries
+            .iter()
+            .map(|entry| {
+                let leaf =
+                    MerkleTreeLeaf::decode(&STANDARD.decode(&entry.leaf_input).unwrap()).unwrap();
+                match leaf.timestamped_entry.signed_entry {
+                    SignedEntry::X509(certificate) => certificate,
+                    other => panic!("unexpected entry {:?}", other),
+                }
+            })
+            .collect();
+        let mut expected: Vec<Vec<u8>> = (0..ENTRY_COUNT).map(test_certificate).collect();
+        let mut actual = certificates.clone();
+        expected.sort();
+        actual.sort();
+        assert_eq!(actual, expected);
+
+        // Entries are cached now, so the second response only allocates for
+        // serialization. Read it the way a client would, chunk by chunk.
+        let response = request_all(&state).await;
+        alloc::start_tracking();
+        let mut chunks = response.into_body().into_data_stream();
+        let mut streamed = 0;
+        while let Some(chunk) = chunks.next().await {
+            streamed += chunk.unwrap().len();
+        }
+        drop(chunks);
+        let peak = alloc::peak_bytes();
+
+        assert_eq!(streamed, body.len());
+        assert!(
+            peak < streamed / 8,
+            "peak of {} live bytes while streaming a {} byte response",
+            peak,
+            streamed
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_sth_since() {
+        use crate::audit::verify_consistency;
+
+        let state = create_test_state(5).await;
+        let sth_at = |since| get_sth(State(state.clone()), Query(GetSthRequest { since }));
+
+        let Json(old) = sth_at(None).await.unwrap();
+        assert!(old.consistency.is_none());
+        let old = SignedTreeHead::from_api_response(&old.sth).unwrap();
+
+        append_test_entries(&state.storage, &state.log_id, 5..13).await;
+        let Json(new) = sth_at(Some(5)).await.unwrap();
+        let consistency = new.consistency.unwrap();
+        let new = SignedTreeHead::from_api_response(&new.sth).unwrap();
+        assert_eq!(new.tree_size, 13);
+        verify_consistency(&old, &new, &consistency).unwrap();
+
+        // The proof matches get-sth-consistency's
+        let Json(separate) = get_sth_consistency(
+            State(state.clone()),
+            Query(GetConsistencyProofRequest {
+                first: 5,
+                second: 13,
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(separate.consistency, consistency);
+
+        for since in [0, 13] {
+            let Json(trivial) = sth_at(Some(since)).await.unwrap();
+            assert_eq!(trivial.consistency, Some(vec![]));
+        }
+        let (status, _) = sth_at(Some(14)).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_get_sth_verifies_externally() {
+        use openssl::{hash::MessageDigest, pkey::PKey, sign::Verifier};
+
+        let state = create_test_state(7).await;
+        let Json(response) = get_sth(State(state.clone()), Query(GetSthRequest::default()))
+            .await
+            .unwrap();
+
+        // Rebuild the signed struct from the JSON fields, as an auditor would:
+        // version v1 (0), signature_type tree_hash (1), timestamp, tree_size,
+        // sha256_root_hash
+        let json: serde_json::Value = serde_json::to_value(&response).unwrap();
+        let root_hash = STANDARD
+            .decode(json["sha256_root_hash"].as_str().unwrap())
+            .unwrap();
+        let mut signed = vec![0u8, 1];
+        signed.extend_from_slice(&json["timestamp"].as_u64().unwrap().to_be_bytes());
+        signed.extend_from_slice(&json["tree_size"].as_u64().unwrap().to_be_bytes());
+        signed.extend_from_slice(&root_hash);
+
+        // DigitallySigned: hash sha256 (4), signature ecdsa (3), u16 length
+        let digitally_signed = STANDARD
+            .decode(json["tree_head_signature"].as_str().unwrap())
+            .unwrap();
+        assert_eq!(digitally_signed[..2], [4, 3]);
+        let length = u16::from_be_bytes([digitally_signed[2], digitally_signed[3]]) as usize;
+        assert_eq!(digitally_signed.len(), 4 + length);
+
+        let key = PKey::public_key_from_der(&state.public_key_der).unwrap();
+        let mut verifier = Verifier::new(MessageDigest::sha256(), &key).unwrap();
+        verifier.update(&signed).unwrap();
+        assert!(verifier.verify(&digitally_signed[4..]).unwrap());
+
+        let committed_root = state.merkle_tree.committed_root().await.unwrap();
+        assert_eq!(json["tree_size"], 7);
+        assert_eq!(root_hash, committed_root.as_bytes().as_slice());
+    }
+
+    #[tokio::test]
+    async fn test_get_sth_consistency_from_empty_tree() {
+        let state = create_test_state(10).await;
+        let tree_size = state.merkle_tree.committed_size().await.unwrap();
+        assert_eq!(tree_size, 10);
+
+        for second in [0, tree_size] {
+            let Json(response) = get_sth_consistency(
+                State(state.clone()),
+                Query(GetConsistencyProofRequest { first: 0, second }),
+            )
+            .await
+            .unwrap();
+            assert_eq!(
+                serde_json::to_string(&response).unwrap(),
+                r#"{"consistency":[]}"#
+            );
+        }
+
+        let result = get_sth_consistency(
+            State(state),
+            Query(GetConsistencyProofRequest {
+                first: 0,
+                second: tree_size + 1,
+            }),
+        )
+        .await;
+        assert!(matches!(result, Err((StatusCode::BAD_REQUEST, _))));
+    }
+
+    #[tokio::test]
+    async fn test_get_sth_consistency_cached() {
+        // Sizes 3 and 10 are both batch boundaries, so (3, 10) has a proof
+        let state = create_test_state(3).await;
+        let leaves = (3..10)
+            .map(|i| {
+                LogEntry::compute_leaf_data_with_index(
+                    &test_certificate(i),
+                    LogEntryType::X509Entry,
+                    None,
+                    chrono::Utc::now(),
+                    i,
+                )
+            })
+            .collect();
+        state
+            .merkle_tree
+            .batch_push_with_data(leaves, vec![])
+            .await
+            .unwrap();
+        let request = || {
+            get_sth_consistency(
+                State(state.clone()),
+                Query(GetConsistencyProofRequest {
+                    first: 3,
+                    second: 10,
+                }),
+            )
+        };
+
+        let Json(first) = request().await.unwrap();
+        assert!(state.consistency_cache.contains(&(3, 10)));
+
+        let hits = &state.metrics.consistency_cache_hits;
+        let misses = &state.metrics.consistency_cache_misses;
+        assert_eq!((hits.get(), misses.get()), (0, 1));
+        let Json(second) = request().await.unwrap();
+        assert_eq!((hits.get(), misses.get()), (1, 1));
+        assert!(!first.consistency.is_empty());
+        assert_eq!(
+            serde_json::to_vec(&first).unwrap(),
+            serde_json::to_vec(&second).unwrap()
+        );
+
+        // An expired proof is computed again
+        state.consistency_cache.insert(
+            (3, 10),
+            Arc::new(CachedConsistencyProof {
+                response: GetConsistencyProofResponse {
+                    consistency: vec![],
+                },
+                cached_at: Instant::now() - crate::api::CONSISTENCY_CACHE_TTL,
+            }),
+        );
+        let Json(third) = request().await.unwrap();
+        assert_eq!((hits.get(), misses.get()), (1, 2));
+        assert_eq!(third.consistency, first.consistency);
+        assert!(state
+            .consistency_cache
+            .get(&(3, 10))
+            .unwrap()
+            .value()
+            .is_fresh());
+
+        // Sizes beyond the tree fail and leave nothing behind
+        let result = get_sth_consistency(
+            State(state.clone()),
+            Query(GetConsistencyProofRequest {
+                first: 3,
+                second: 11,
+            }),
+        )
+        .await;
+        assert!(result.is_err());
+        assert!(!state.consistency_cache.contains(&(3, 11)));
+    }
+
+    #[tokio::test]
+    async fn test_get_entries_cursor_across_growth() {
+        let state = create_test_state(ENTRY_COUNT).await;
+
+        // Certificate numbers on one page, and the cursor for the next
+        let page = |cursor: String| {
+            let state = state.clone();
+            async move {
+                let response = get_entries(
+                    State(state),
+                    Query(GetEntriesRequest {
+                        cursor: Some(cursor),
+                        leaf_only: true,
+                        ..Default::default()
+                    }),
+                )
+                .await
+                .unwrap();
+                let next = response.headers()[NEXT_CURSOR_HEADER]
+                    .to_str()
+                    .unwrap()
+                    .to_string();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let parsed: GetEntriesResponse = serde_json::from_slice(&body).unwrap();
+                let numbers: Vec<u64> = parsed
+                    .entries
+                    .iter()
+                    .map(|entry| {
+                        let leaf =
+                            MerkleTreeLeaf::decode(&STANDARD.decode(&entry.leaf_input).unwrap())
+                                .unwrap();
+                        match leaf.timestamped_entry.signed_entry {
+                            SignedEntry::X509(certificate) => {
+                                u64::from_be_bytes(certificate[..8].try_into().unwrap())
+                            }
+                            other => panic!("unexpected entry {:?}", other),
+                        }
+                    })
+                    .collect();
+                (numbers, next)
+            }
+        };
+
+        let mut seen = Vec::new();
+        let (numbers, cursor) = page(String::new()).await;
+        assert_eq!(numbers.len(), 1000);
+        seen.extend(numbers);
+
+        // Growth after a cursor reaches its tree size shows up on the next page
+        append_test_entries(&state.storage, &state.log_id, ENTRY_COUNT..2500).await;
+        let (numbers, cursor) = page(cursor).await;
+        assert_eq!(numbers.len(), 1000);
+        seen.extend(numbers);
+
+        // Growth mid-walk waits until the cursor's tree size is exhausted
+        append_test_entries(&state.storage, &state.log_id, 2500..2600).await;
+        let (numbers, cursor) = page(cursor).await;
+        assert_eq!(numbers.len(), 500);
+        seen.extend(numbers);
+        let (numbers, cursor) = page(cursor).await;
+        assert_eq!(numbers.len(), 100);
+        seen.extend(numbers);
+
+        // Caught up: an empty page, and the same position to poll from later
+        let (numbers, next) = page(cursor.clone()).await;
+        assert!(numbers.is_empty());
+        assert_eq!(
+            EntriesCursor::decode(&next).unwrap(),
+            EntriesCursor::decode(&cursor).unwrap()
+        );
+
+        seen.sort_unstable();
+        assert_eq!(seen, (0..2600).collect::<Vec<_>>());
+
+        let rejected = |cursor: String| {
+            get_entries(
+                State(state.clone()),
+                Query(GetEntriesRequest {
+                    cursor: Some(cursor),
+                    ..Default::default()
+                }),
+            )
+        };
+        let future = EntriesCursor {
+            next: 0,
+            tree_size: 5000,
+        };
+        assert_eq!(
+            rejected(future.encode()).await.unwrap_err().0,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            rejected("not a cursor".to_string()).await.unwrap_err().0,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_proof_by_hash_at_old_sizes() {
+        use crate::audit::verify_inclusion;
+        use sha2::{Digest, Sha256};
+
+        let state = create_test_state(0).await;
+        let append =
+            |indices: Range<u64>| append_test_entries(&state.storage, &state.log_id, indices);
+
+        let mut sths = Vec::new();
+        for indices in [0..7, 7..40, 40..41, 41..300] {
+            append(indices).await;
+            let Json(GetSthResponse { sth, .. }) =
+                get_sth(State(state.clone()), Query(GetSthRequest::default()))
+                    .await
+                    .unwrap();
+            sths.push(SignedTreeHead::from_api_response(&sth).unwrap());
+        }
+        append(300..3000).await;
+
+        let prove = |hash: &[u8], tree_size| {
+            get_proof_by_hash(
+                State(state.clone()),
+                HeaderMap::new(),
+                Query(GetProofByHashRequest {
+                    hash: STANDARD.encode(hash),
+                    tree_size,
+                    format: None,
+                }),
+            )
+        };
+        for sth in &sths {
+            for index in [0, sth.tree_size / 2, sth.tree_size - 1] {
+                let entry = state.storage.get_entry(index).await.unwrap().unwrap();
+                let leaf_input = entry.serialize().unwrap();
+                let hash = Sha256::new_with_prefix([0u8])
+                    .chain_update(&leaf_input)
+                    .finalize();
+
+                let response = prove(&hash, sth.tree_size).await.unwrap();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let proof: GetProofByHashResponse = serde_json::from_slice(&body).unwrap();
+                assert_eq!(proof.leaf_index, index);
+                verify_inclusion(&leaf_input, index, sth, &proof.audit_path).unwrap();
+
+                // Sizes the leaf is not part of have no proof
+                if index > 0 {
+                    let (status, _) = prove(&hash, index).await.unwrap_err();
+                    assert_eq!(status, StatusCode::NOT_FOUND);
+                }
+            }
+        }
+
+        let entry = state.storage.get_entry(0).await.unwrap().unwrap();
+        let hash = Sha256::new_with_prefix([0u8])
+            .chain_update(entry.serialize().unwrap())
+            .finalize();
+        let (status, _) = prove(&hash, 3001).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_get_proof_by_hash_formats() {
+        use crate::audit::{verify_inclusion, verify_record_proof};
+        use sha2::{Digest, Sha256};
+
+        let state = create_test_state(37).await;
+        let Json(GetSthResponse { sth, .. }) =
+            get_sth(State(state.clone()), Query(GetSthRequest::default()))
+                .await
+                .unwrap();
+        let sth = SignedTreeHead::from_api_response(&sth).unwrap();
+
+        let index = 22;
+        let leaf_input = state
+            .storage
+            .get_entry(index)
+            .await
+            .unwrap()
+            .unwrap()
+            .serialize()
+            .unwrap();
+        let hash = Sha256::new_with_prefix([0u8])
+            .chain_update(&leaf_input)
+            .finalize();
+
+        let prove = |format, accept: Option<&'static str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(accept) = accept {
+                headers.insert(header::ACCEPT, accept.parse().unwrap());
+            }
+            let request = get_proof_by_hash(
+                State(state.clone()),
+                headers,
+                Query(GetProofByHashRequest {
+                    hash: STANDARD.encode(hash),
+                    tree_size: sth.tree_size,
+                    format,
+                }),
+            );
+            async move {
+                let response = request.await.unwrap();
+                let content_type = response.headers()[header::CONTENT_TYPE].clone();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                (content_type, body)
+            }
+        };
+
+        for (format, accept) in [
+            (None, None),
+            (Some(ProofFormat::Rfc6962), Some("text/plain")),
+        ] {
+            let (content_type, body) = prove(format, accept).await;
+            assert_eq!(content_type, "application/json");
+            let proof: GetProofByHashResponse = serde_json::from_slice(&body).unwrap();
+            assert_eq!(proof.leaf_index, index);
+            verify_inclusion(&leaf_input, index, &sth, &proof.audit_path).unwrap();
+        }
+
+        for (format, accept) in [
+            (Some(ProofFormat::Tlog), None),
+            (None, Some("application/json;q=0.5, text/plain")),
+        ] {
+            let (content_type, body) = prove(format, accept).await;
+            assert_eq!(content_type, TLOG_PROOF_CONTENT_TYPE);
+            let text = String::from_utf8(body.to_vec()).unwrap();
+            assert!(text.starts_with(&format!(
+                "tlog-record-proof\nindex {}\nsize {}\n",
+                index, sth.tree_size
+            )));
+            verify_record_proof(&leaf_input, &sth, &text).unwrap();
+
+            // The proof is bound to its leaf
+            let other = state
+                .storage
+                .get_entry(index + 1)
+                .await
+                .unwrap()
+                .unwrap()
+                .serialize()
+                .unwrap();
+            assert!(verify_record_proof(&other, &sth, &text).is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_entries_type_filter() {
+        let state = create_test_state(10).await;
+
+        let request = |entry_type| {
+            get_entries(
+                State(state.clone()),
+                Query(GetEntriesRequest {
+                    start: Some(0),
+                    end: Some(9),
+                    cursor: None,
+                    entry_type: Some(entry_type),
+                    leaf_only: false,
+                }),
+            )
+        };
+
+        let body = axum::body::to_bytes(
+            request(EntryTypeFilter::X509).await.unwrap().into_body(),
+            usize::MAX,
+        )
+        .await
+        .unwrap();
+        let parsed: GetEntriesResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(parsed.entries.len(), 10);
+
+        let body = axum::body::to_bytes(
+            request(EntryTypeFilter::Precert).await.unwrap().into_body(),
+            usize::MAX,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            &body[..],
+            br#"{"entries":[],"next_entry":null,"tree_size":10}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_entries_next_entry() {
+        let state = create_test_state(MAX_ENTRIES + 10).await;
+
+        let request = |start, end, entry_type| {
+            let state = state.clone();
+            async move {
+                let response = get_entries(
+                    State(state),
+                    Query(GetEntriesRequest {
+                        start: Some(start),
+                        end: Some(end),
+                        cursor: None,
+                        entry_type,
+                        leaf_only: true,
+                    }),
+                )
+                .await
+                .unwrap();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                serde_json::from_slice::<GetEntriesResponse>(&body).unwrap()
+            }
+        };
+
+        // Cut short at the page limit
+        let page = request(0, MAX_ENTRIES + 9, None).await;
+        assert_eq!(page.entries.len() as u64, MAX_ENTRIES);
+        assert_eq!(page.next_entry, Some(MAX_ENTRIES));
+        assert_eq!(page.tree_size, Some(MAX_ENTRIES + 10));
+
+        // The rest of the range, and a range running past the tree
+        for end in [MAX_ENTRIES + 9, MAX_ENTRIES + 500] {
+            let page = request(MAX_ENTRIES, end, None).await;
+            assert_eq!(page.entries.len(), 10);
+            assert_eq!(page.next_entry, None);
+        }
+
+        // Filtered pages resume after the range they scanned
+        let page = request(0, MAX_ENTRIES + 9, Some(EntryTypeFilter::Precert)).await;
+        assert!(page.entries.is_empty());
+        assert_eq!(page.next_entry, Some(MAX_ENTRIES));
+    }
+
+    #[tokio::test]
+    async fn test_get_entries_outcome_counted_once_at_stream_end() {
+        let db = Arc::new(Db::open("test", Arc::new(InMemory::new())).await.unwrap());
+        let metrics = Arc::new(crate::metrics::Metrics::new());
+        let state = create_test_state_on(db.clone(), 10, metrics.clone()).await;
+        let outcome = |label: &str| {
+            metrics
+                .get_entries_requests
+                .with_label_values(&[label])
+                .get()
+        };
+        let request = |end| {
+            get_entries(
+                State(state.clone()),
+                Query(GetEntriesRequest {
+                    start: Some(0),
+                    end: Some(end),
+                    cursor: None,
+                    entry_type: None,
+                    leaf_only: true,
+                }),
+            )
+        };
+
+        // An entry that fails after the response has started
+        db.put(b"entry:\0\0\0\0\0\0\0\x07", b"bad").await.unwrap();
+        let response = request(9).await.unwrap();
+        assert!(axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .is_err());
+        assert_eq!((outcome("success"), outcome("failed")), (0, 1));
+
+        let response = request(6).await.unwrap();
+        assert_eq!(outcome("success"), 0);
+        axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!((outcome("success"), outcome("failed")), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_get_entries_leaf_only() {
+        let state = create_test_state(10).await;
+
+        let request = |leaf_only| {
+            get_entries(
+                State(state.clone()),
+                Query(GetEntriesRequest {
+                    start: Some(0),
+                    end: Some(9),
+                    cursor: None,
+                    entry_type: None,
+                    leaf_only,
+                }),
+            )
+        };
+
+        let full = axum::body::to_bytes(request(false).await.unwrap().into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let leaves = axum::body::to_bytes(request(true).await.unwrap().into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(leaves.len() < full.len());
+
+        let full: GetEntriesResponse = serde_json::from_slice(&full).unwrap();
+        let leaves: GetEntriesResponse = serde_json::from_slice(&leaves).unwrap();
+        assert_eq!(leaves.entries.len(), 10);
+        for (full, leaf) in full.entries.iter().zip(&leaves.entries) {
+            assert_eq!(leaf.leaf_input, full.leaf_input);
+            assert!(leaf.extra_data.is_empty());
+            assert!(!full.extra_data.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_entries_reconstructs_submitted_chains() {
+        use crate::test_utils::utils::{
+            create_precertificate_with_poison, create_test_certificate,
+            create_test_certificate_with_serial,
+        };
+        use sha2::{Digest, Sha256};
+
+        let state = create_test_state(0).await;
+        let issuer = create_test_certificate();
+        let submitted = vec![
+            vec![create_test_certificate_with_serial(7), issuer.clone()],
+            vec![create_precertificate_with_poison(), issuer],
+        ];
+        for (i, chain) in submitted.iter().enumerate() {
+            let request = AddChainRequest {
+                chain: chain.iter().map(|cert| STANDARD.encode(cert)).collect(),
+            };
+            let state = State(state.clone());
+            let response = if i == 0 {
+                add_chain(
+                    state,
+                    ClientIp(None),
+                    Submitter(None),
+                    ChainSubmission(request),
+                )
+                .await
+            } else {
+                add_pre_chain(state, ClientIp(None), Submitter(None), Json(request)).await
+            };
+            assert!(response.is_ok(), "submission {} rejected", i);
+        }
+
+        let response = get_entries(
+            State(state.clone()),
+            Query(GetEntriesRequest {
+                start: Some(0),
+                end: Some(1),
+                cursor: None,
+                entry_type: None,
+                leaf_only: false,
+            }),
+        )
+        .await
+        .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let parsed: GetEntriesResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(parsed.entries.len(), 2);
+
+        for (index, (entry, chain)) in parsed.entries.iter().zip(&submitted).enumerate() {
+            // As a monitor would: the leaf hash locates the entry in the tree
+            let leaf_input = STANDARD.decode(&entry.leaf_input).unwrap();
+            let leaf_hash = Sha256::new()
+                .chain_update([0x00])
+                .chain_update(&leaf_input)
+                .finalize();
+            assert_eq!(
+                state
+                    .merkle_tree
+                    .get_leaf_index_by_hash(&leaf_hash)
+                    .await
+                    .unwrap(),
+                Some(index as u64)
+            );
+
+            let leaf = MerkleTreeLeaf::decode(&leaf_input).unwrap();
+            let signed_entry = leaf.timestamped_entry.signed_entry;
+            let extra_data = ExtraData::decode(
+                signed_entry.entry_type(),
+                &STANDARD.decode(&entry.extra_data).unwrap(),
+            )
+            .unwrap();
+
+            let mut reconstructed = match (signed_entry, &extra_data) {
+                (SignedEntry::X509(cert), ExtraData::X509Chain(_)) => vec![cert],
+                (
+                    SignedEntry::Precert { .. },
+                    ExtraData::PrecertChain {
+                        pre_certificate, ..
+                    },
+                ) => vec![pre_certificate.clone()],
+                _ => panic!("extra_data does not match the entry type"),
+            };
+            reconstructed.extend_from_slice(extra_data.chain());
+            assert_eq!(&reconstructed, chain);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_roots_pagination() {
+        use crate::test_utils::utils::create_test_certificate_with_serial;
+        use crate::validation::Rfc6962Validator;
+        use tokio::sync::RwLock;
+
+        let validator = |serials: std::ops::Range<u8>| {
+            let roots = serials
+                .map(|serial| {
+                    x509_cert::Certificate::from_der(&create_test_certificate_with_serial(serial))
+                        .unwrap()
+                })
+                .collect();
+            Some(Arc::new(RwLock::new(
+                Rfc6962Validator::with_trusted_roots(Default::default(), roots).unwrap(),
+            )))
+        };
+        let encoded = |serial| STANDARD.encode(create_test_certificate_with_serial(serial));
+
+        let mut state = Arc::into_inner(create_test_state(0).await).unwrap();
+        state.validator = validator(1..6);
+        let state = Arc::new(state.with_max_roots_per_page(4));
+        let get = |start, limit| {
+            let state = state.clone();
+            async move {
+                get_roots(State(state), Query(GetRootsRequest { start, limit }))
+                    .await
+                    .unwrap()
+                    .0
+            }
+        };
+
+        // Without parameters, as many roots as one page holds
+        let all = get(None, None).await;
+        assert_eq!(all.certificates, (1..5).map(encoded).collect::<Vec<_>>());
+        assert_eq!(all.total, 5);
+        assert_eq!(all.next_start, Some(4));
+        assert_eq!(get(None, Some(100)).await.certificates.len(), 4);
+
+        let mut paged = Vec::new();
+        let mut start = None;
+        loop {
+            let page = get(start, Some(2)).await;
+            assert_eq!(page.total, 5);
+            paged.extend(page.certificates);
+            match page.next_start {
+                Some(next) => start = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(paged, (1..6).map(encoded).collect::<Vec<_>>());
+
+        // A removed root leaves a gap and new roots go at the end, so a
+        // page started before the change continues where it left off
+        let mut state = Arc::into_inner(state).unwrap();
+        state.validator = validator(2..8);
+        let state = Arc::new(state);
+        let page = get_roots(
+            State(state.clone()),
+            Query(GetRootsRequest {
+                start: Some(4),
+                limit: None,
+            }),
+        )
+        .await
+        .unwrap()
+        .0;
+        assert_eq!(page.certificates, (5..8).map(encoded).collect::<Vec<_>>());
+        assert_eq!(page.total, 6);
+        assert_eq!(page.next_start, None);
+
+        // Without a validator there are no roots
+        let mut state = Arc::into_inner(state).unwrap();
+        state.validator = None;
+        let empty = get_roots(State(Arc::new(state)), Query(GetRootsRequest::default()))
+            .await
+            .unwrap()
+            .0;
+        assert!(empty.certificates.is_empty());
+        assert_eq!(empty.total, 0);
+        assert_eq!(empty.next_start, None);
+    }
+
+    #[tokio::test]
+    async fn test_get_entry_and_proof_refuses_corrupt_leaf() {
+        let mut state = Arc::new(
+            Arc::into_inner(create_test_state(8).await)
+                .unwrap()
+                .with_leaf_verification(true),
+        );
+        let entry_and_proof = |state: &Arc<ApiState>| {
+            get_entry_and_proof(
+                State(state.clone()),
+                Query(GetEntryAndProofRequest {
+                    leaf_index: 3,
+                    tree_size: Some(8),
+                }),
+            )
+        };
+        assert!(entry_and_proof(&state).await.is_ok());
+
+        // Leaf 3 now holds leaf 4, which decodes fine but hashes elsewhere
+        let other = state.merkle_tree.get_leaf_range(4, 4).await.unwrap();
+        state
+            .merkle_tree
+            .overwrite_leaf(3, &other[0])
+            .await
+            .unwrap();
+        let (status, Json(error)) = entry_and_proof(&state).await.err().unwrap();
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(error.error.contains("Leaf 3"), "{}", error.error);
+
+        // Unchecked, the entry is served from the log entry as before
+        Arc::get_mut(&mut state).unwrap().verify_served_leaves = false;
+        assert!(entry_and_proof(&state).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_get_roots_order_survives_restart() {
+        use crate::test_utils::utils::create_test_certificate_with_serial;
+        use crate::validation::Rfc6962Validator;
+        use sha2::{Digest, Sha256};
+        use tokio::sync::RwLock;
+
+        // The roots directory may list its files in any order
+        let validator = |serials: Vec<u8>| {
+            let roots = serials
+                .into_iter()
+                .map(|serial| {
+                    x509_cert::Certificate::from_der(&create_test_certificate_with_serial(serial))
+                        .unwrap()
+                })
+                .collect();
+            Some(Arc::new(RwLock::new(
+                Rfc6962Validator::with_trusted_roots(Default::default(), roots).unwrap(),
+            )))
+        };
+        let roots = |state: ApiState| async move {
+            get_roots(State(Arc::new(state)), Query(GetRootsRequest::default()))
+                .await
+                .unwrap()
+                .0
+                .certificates
+        };
+        let forward: Vec<u8> = (1..9).collect();
+        let backward: Vec<u8> = (1..9).rev().collect();
+
+        let mut by_fingerprint: Vec<_> = forward
+            .iter()
+            .map(|&serial| create_test_certificate_with_serial(serial))
+            .collect();
+        by_fingerprint.sort_by_key(|cert_der| Sha256::digest(cert_der));
+        let by_fingerprint: Vec<_> = by_fingerprint
+            .iter()
+            .map(|der| STANDARD.encode(der))
+            .collect();
+
+        let mut orders = Vec::new();
+        for serials in [&forward, &forward, &backward] {
+            // A fresh log each time, as on another node or after a restart
+            let mut state = Arc::into_inner(create_test_state(0).await)
+                .unwrap()
+                .with_roots_order(RootsOrder::Fingerprint);
+            state.validator = validator(serials.clone());
+            orders.push(roots(state).await);
+        }
+        assert!(orders.iter().all(|order| *order == by_fingerprint));
+
+        // By index, the order is whatever was first served, kept in storage
+        let shared = create_test_state(0).await;
+        let mut orders = Vec::new();
+        for serials in [&backward, &backward, &forward] {
+            let mut state = Arc::into_inner(create_test_state(0).await).unwrap();
+            state.storage = shared.storage.clone();
+            state.validator = validator(serials.clone());
+            orders.push(roots(state).await);
+        }
+        let first_served: Vec<_> = backward
+            .iter()
+            .map(|&serial| STANDARD.encode(create_test_certificate_with_serial(serial)))
+            .collect();
+        assert!(orders.iter().all(|order| *order == first_served));
+    }
+
+    #[tokio::test]
+    async fn test_add_chain_rejects_unordered_chain() {
+        use crate::validation::{rfc6962_validator::tests::two_intermediate_chain, ValidationPool};
+        use tokio::sync::RwLock;
+
+        let (validator, chain) = two_intermediate_chain();
+        let mut state = Arc::into_inner(create_test_state(0).await).unwrap();
+        state.validator = Some(Arc::new(RwLock::new(validator)));
+        state.validation_pool = Some(Arc::new(ValidationPool::new(1, 1).unwrap()));
+
+        let swapped = [&chain[0], &chain[2], &chain[1]];
+        let request = AddChainRequest {
+            chain: swapped.iter().map(|cert| STANDARD.encode(cert)).collect(),
+        };
+        let (status, Json(body)) = add_chain(
+            State(Arc::new(state)),
+            ClientIp(None),
+            Submitter(None),
+            ChainSubmission(request),
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let body = serde_json::to_value(&body).unwrap();
+        assert_eq!(body["code"], "unordered_chain");
+        assert!(body["error"].as_str().unwrap().contains("index 0"));
+    }
+
+    #[tokio::test]
+    async fn test_inclusion_request_reports_policy() {
+        use crate::oids::ECDSA_WITH_SHA256_OID;
+        use crate::validation::{Rfc6962ValidationConfig, Rfc6962Validator};
+        use p256::pkcs8::DecodePublicKey;
+        use tokio::sync::RwLock;
+
+        let state = create_test_state(0).await;
+
+        // The reported algorithm must describe the key the log actually signs with
+        p256::ecdsa::VerifyingKey::from_public_key_der(&state.public_key_der).unwrap();
+        let response = log_inclusion_request(&state).await.unwrap();
+        assert_eq!(response.key_algorithm, "ecdsa-p256");
+        assert!(response.accepted_signature_algorithms.is_none());
+        let mut state = Arc::into_inner(state).unwrap();
+
+        let config = Rfc6962ValidationConfig {
+            allowed_signature_algorithms: [ECDSA_WITH_SHA256_OID.to_string()].into(),
+            ..Default::default()
+        };
+        let validator = Rfc6962Validator::with_trusted_roots(config, vec![]).unwrap();
+        state.validator = Some(Arc::new(RwLock::new(validator)));
+
+        let response = log_inclusion_request(&state).await.unwrap();
+        assert_eq!(response.key_algorithm, "ecdsa-p256");
+        assert_eq!(
+            response.accepted_signature_algorithms,
+            Some(vec!["ecdsa-with-SHA256".to_string()])
+        );
+    }
 }
@@ -0,0 +1,138 @@
+use crate::api::{api_error, ApiState, ErrorCode, ErrorResponse};
+use axum::{extract::State, http::StatusCode, response::Json};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+
+#[derive(Debug, Serialize)]
+pub struct LogList {
+    pub operators: Vec<LogListOperator>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LogListOperator {
+    pub name: String,
+    pub email: Vec<String>,
+    pub logs: Vec<LogListEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LogListEntry {
+    pub description: String,
+    pub log_id: String,
+    /// Base64 DER SubjectPublicKeyInfo
+    pub key: String,
+    pub url: String,
+    pub maximum_merge_delay: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temporal_interval: Option<TemporalInterval>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<LogState>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TemporalInterval {
+    pub start_inclusive: String,
+    pub end_exclusive: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LogState {
+    pub usable: StateTimestamp,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StateTimestamp {
+    pub timestamp: String,
+}
+
+fn log_list_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// This log as a single-operator list in the format of Chrome's log list, so
+/// it can be discovered without a manual submission
+pub async fn get_log_list(
+    State(state): State<Arc<ApiState>>,
+) -> Result<Json<LogList>, (StatusCode, Json<ErrorResponse>)> {
+    let metadata = state
+        .log_metadata
+        .as_ref()
+        .ok_or_else(|| api_error(ErrorCode::NotFound, "Log list metadata is not configured"))?;
+
+    let log = LogListEntry {
+        description: state.sth_builder.origin().to_string(),
+        log_id: state.log_id.to_base64(),
+        key: STANDARD.encode(&state.public_key_der),
+        url: format!("{}/", state.base_url.trim_end_matches('/')),
+        maximum_merge_delay: metadata.mmd_seconds,
+        temporal_interval: metadata.temporal_window.map(|window| TemporalInterval {
+            start_inclusive: log_list_time(window.start),
+            end_exclusive: log_list_time(window.end),
+        }),
+        state: metadata.usable_since.map(|since| LogState {
+            usable: StateTimestamp {
+                timestamp: log_list_time(since),
+            },
+        }),
+    };
+
+    Ok(Json(LogList {
+        operators: vec![LogListOperator {
+            name: metadata.operator_name.clone(),
+            email: vec![metadata.operator_email.clone()],
+            logs: vec![log],
+        }],
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::handlers::tests::create_test_state;
+    use crate::api::LogMetadata;
+    use crate::validation::rfc6962_validator::TemporalWindow;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn test_log_list_entry() {
+        let state = Arc::into_inner(create_test_state(0).await).unwrap();
+        assert!(get_log_list(State(Arc::new(state))).await.is_err());
+
+        let state = Arc::into_inner(create_test_state(0).await)
+            .unwrap()
+            .with_log_metadata(LogMetadata {
+                operator_name: "Example CT".into(),
+                operator_email: "ct@example.com".into(),
+                mmd_seconds: 86400,
+                temporal_window: Some(TemporalWindow {
+                    start: Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap(),
+                    end: Utc.with_ymd_and_hms(2026, 7, 1, 0, 0, 0).unwrap(),
+                }),
+                usable_since: None,
+            });
+        let public_key_der = state.public_key_der.clone();
+        let log_id = *state.log_id.to_bytes();
+
+        let Json(list) = get_log_list(State(Arc::new(state))).await.unwrap();
+        let json = serde_json::to_value(&list).unwrap();
+        let log = &json["operators"][0]["logs"][0];
+
+        assert_eq!(json["operators"][0]["name"], "Example CT");
+        assert_eq!(json["operators"][0]["email"][0], "ct@example.com");
+        assert_eq!(log["log_id"], STANDARD.encode(log_id));
+        assert_eq!(log["key"], STANDARD.encode(public_key_der));
+        assert_eq!(log["url"], "http://localhost/");
+        assert_eq!(log["maximum_merge_delay"], 86400);
+        assert_eq!(
+            log["temporal_interval"]["start_inclusive"],
+            "2026-01-01T00:00:00Z"
+        );
+        assert_eq!(
+            log["temporal_interval"]["end_exclusive"],
+            "2026-07-01T00:00:00Z"
+        );
+        assert!(log.get("state").is_none());
+    }
+}
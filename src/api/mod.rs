@@ -1,26 +1,57 @@
+use arc_swap::ArcSwapOption;
 use axum::{
-    extract::Path,
+    body::Bytes,
+    extract::{ConnectInfo, DefaultBodyLimit, FromRequest, FromRequestParts, Path},
+    http::{header, request::Parts, StatusCode},
     middleware,
-    response::{Json, Response},
+    response::{IntoResponse, Json, Response},
     routing::{get, post},
     Router,
 };
-use prometheus::{Encoder, TextEncoder};
-use serde::Serialize;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
-use tower_http::compression::CompressionLayer;
+use tower_http::{compression::CompressionLayer, decompression::RequestDecompressionLayer};
+
+use foyer::{Cache, CacheBuilder};
+
+use concurrency_limit::{limit_concurrency, RouteClass};
+use submission_auth::{require_submitter, SubmissionAuth};
+use tls::ServerTls;
 
 use crate::{
     merkle_storage::StorageBackedMerkleTree,
-    storage::CtStorage,
-    types::{sct::SctBuilder, tree_head::SthBuilder, LogId},
-    validation::Rfc6962Validator,
+    metrics::Metrics,
+    sth_signer::CachedSth,
+    storage::{CtStorage, RejectionLog, StorageError},
+    types::{
+        sct::{SctBuilder, TimestampWindow},
+        signer::Signer,
+        tree_head::SthBuilder,
+        AddChainRequest, CtError, GetConsistencyProofResponse, LogId,
+    },
+    validation::{
+        rfc6962_validator::TemporalWindow, Rfc6962Validator, ValidationError, ValidationPool,
+    },
 };
 
+pub mod admin_handlers;
+mod concurrency_limit;
+pub mod debug_handlers;
+pub mod gossip_handlers;
 pub mod handlers;
+pub mod log_list_handlers;
 pub mod pages_handlers;
+pub mod raw_handlers;
 pub mod static_handlers;
+pub mod stats_handlers;
+pub mod submission_auth;
+pub mod tls;
 
 pub struct ApiState {
     pub storage: Arc<CtStorage>,
@@ -28,25 +59,121 @@ pub struct ApiState {
     pub sct_builder: Arc<SctBuilder>,
     pub sth_builder: Arc<SthBuilder>,
     pub validator: Option<Arc<RwLock<Rfc6962Validator>>>,
+    /// Runs `validator` off the async workers; present whenever `validator` is
+    pub validation_pool: Option<Arc<ValidationPool>>,
     pub log_id: LogId,
     pub public_key_der: Vec<u8>,
     pub base_url: String,
+    /// Records chains that fail validation, when enabled
+    pub rejection_log: Option<Arc<RejectionLog>>,
+    /// Served as a log list entry, when configured
+    pub log_metadata: Option<LogMetadata>,
+    /// Kept fresh by the STH signing task; get-sth signs on demand until it is set
+    pub cached_sth: Arc<ArcSwapOption<CachedSth>>,
+    /// Consistency proofs by `(first, second)`. A proof between two committed
+    /// sizes never changes as the tree grows, so entries only expire, letting
+    /// pairs no longer asked for give way to recent STH sizes.
+    pub consistency_cache: Cache<(u64, u64), Arc<CachedConsistencyProof>>,
+    /// Shared with `storage`, so everything this log records lands in one registry
+    pub metrics: Arc<Metrics>,
+    /// Most roots in one get-roots response
+    pub max_roots_per_page: u64,
+    pub roots_order: RootsOrder,
+    /// Read requests served at once; unlimited if unset
+    pub max_concurrent_reads: Option<usize>,
+    /// add-chain and add-pre-chain requests served at once; unlimited if unset
+    pub max_concurrent_submissions: Option<usize>,
+    /// Check each leaf get-entry-and-proof serves against the tree first
+    pub verify_served_leaves: bool,
+    pub entry_format: EntryFormat,
+    /// Largest entry add-entry accepts in raw mode, in bytes
+    pub max_entry_bytes: usize,
+    /// Serve the /debug proof verification endpoints
+    pub debug_endpoints: bool,
+    /// Who may submit; anyone if unset
+    pub submission_auth: Option<Arc<SubmissionAuth>>,
+    /// Certificate of the built-in TLS listener, reloaded by /admin/reload
+    pub server_tls: Option<Arc<ServerTls>>,
+}
+
+const CONSISTENCY_CACHE_CAPACITY: usize = 1_024;
+
+/// How long a cached consistency proof is served
+pub const CONSISTENCY_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// A get-sth-consistency response and when it was cached
+pub struct CachedConsistencyProof {
+    pub response: GetConsistencyProofResponse,
+    pub cached_at: Instant,
+}
+
+impl CachedConsistencyProof {
+    pub fn is_fresh(&self) -> bool {
+        self.cached_at.elapsed() < CONSISTENCY_CACHE_TTL
+    }
+}
+
+pub const DEFAULT_MAX_ROOTS_PER_PAGE: u64 = 1000;
+
+pub const DEFAULT_MAX_ENTRY_BYTES: usize = 64 * 1024;
+
+/// Largest `max_entry_bytes` a log can be configured with. Leaves encode the
+/// entry behind a 24-bit length, so nothing longer can be logged.
+pub const MAX_ENTRY_BYTES_LIMIT: usize = (1 << 24) - 1;
+
+/// Room in an add-entry body for the JSON around the base64 entry
+const ADD_ENTRY_JSON_OVERHEAD: usize = 1024;
+
+/// What the log accepts as entries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EntryFormat {
+    /// X.509 certificates and precertificates, through the RFC 6962 API
+    #[default]
+    X509,
+    /// Arbitrary byte strings, as for binary transparency. The log serves
+    /// add-entry and a raw get-entries in place of the X.509 endpoints, and
+    /// logs each entry as the certificate of an X.509 leaf, so leaf hashes,
+    /// proofs and receipts follow RFC 6962 unchanged.
+    Raw,
+}
+
+/// Order of the roots get-roots returns
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RootsOrder {
+    /// By the index each root was first served under. Pages stay put when
+    /// roots are added or removed, and `start` is a root index.
+    #[default]
+    Index,
+    /// By SHA-256 fingerprint, so any two logs with the same roots answer
+    /// alike. `start` is a position in that order.
+    Fingerprint,
+}
+
+/// What a CT log list says about this log beyond its key and URL
+#[derive(Debug, Clone)]
+pub struct LogMetadata {
+    pub operator_name: String,
+    pub operator_email: String,
+    pub mmd_seconds: u64,
+    /// Range of certificate expiry dates the log accepts
+    pub temporal_window: Option<TemporalWindow>,
+    /// When the log entered the usable state; the state is omitted if unset
+    pub usable_since: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 impl ApiState {
+    /// A log signing with `signer`, whose public key also gives the log ID
     pub fn new(
         storage: Arc<CtStorage>,
         merkle_tree: StorageBackedMerkleTree,
-        log_id: LogId,
-        private_key: Vec<u8>,
-        public_key_der: Vec<u8>,
+        signer: Box<dyn Signer>,
         base_url: String,
-        validator: Option<Rfc6962Validator>,
+        validator: Option<(Rfc6962Validator, ValidationPool)>,
     ) -> crate::types::Result<Self> {
-        let sct_builder = Arc::new(SctBuilder::from_private_key_bytes(
-            log_id.clone(),
-            &private_key,
-        )?);
+        let signer: Arc<dyn Signer> = Arc::from(signer);
+        let public_key_der = signer.public_key_der()?;
+        let log_id = LogId::from_public_key_der(&public_key_der);
+        let sct_builder = Arc::new(SctBuilder::new(log_id.clone(), signer.clone()));
 
         // Derive origin from base_url for checkpoints
         // Remove scheme (http:// or https://) and trailing slashes
@@ -59,9 +186,16 @@ impl ApiState {
         // Use the actual LogId bytes (which is already the SHA-256 of the public key)
         let log_id_bytes = log_id.to_bytes().to_vec();
 
-        let sth_builder = Arc::new(SthBuilder::new(&private_key, origin, log_id_bytes)?);
+        let sth_builder = Arc::new(SthBuilder::with_signer(signer, origin, log_id_bytes));
 
-        let validator = validator.map(|v| Arc::new(RwLock::new(v)));
+        let metrics = storage.metrics().clone();
+        let (validator, validation_pool) = match validator {
+            Some((validator, pool)) => (
+                Some(Arc::new(RwLock::new(validator))),
+                Some(Arc::new(pool.with_metrics(&metrics))),
+            ),
+            None => (None, None),
+        };
 
         Ok(Self {
             storage,
@@ -69,55 +203,478 @@ impl ApiState {
             sct_builder,
             sth_builder,
             validator,
+            validation_pool,
             log_id,
             public_key_der,
             base_url,
+            rejection_log: None,
+            log_metadata: None,
+            cached_sth: Arc::new(ArcSwapOption::empty()),
+            consistency_cache: CacheBuilder::new(CONSISTENCY_CACHE_CAPACITY)
+                .with_name("ct_api_consistency_proof")
+                .build(),
+            metrics,
+            max_roots_per_page: DEFAULT_MAX_ROOTS_PER_PAGE,
+            roots_order: RootsOrder::default(),
+            max_concurrent_reads: None,
+            max_concurrent_submissions: None,
+            verify_served_leaves: false,
+            entry_format: EntryFormat::default(),
+            max_entry_bytes: DEFAULT_MAX_ENTRY_BYTES,
+            debug_endpoints: false,
+            submission_auth: None,
+            server_tls: None,
         })
     }
+
+    /// Override the origin line used in checkpoints, which otherwise derives from `base_url`
+    pub fn with_checkpoint_origin(mut self, origin: String) -> Self {
+        self.sth_builder = Arc::new(self.sth_builder.with_origin(origin));
+        self
+    }
+
+    pub fn with_rejection_log(mut self, rejection_log: RejectionLog) -> Self {
+        self.rejection_log = Some(Arc::new(rejection_log));
+        self
+    }
+
+    pub fn with_log_metadata(mut self, log_metadata: LogMetadata) -> Self {
+        self.log_metadata = Some(log_metadata);
+        self
+    }
+
+    pub fn with_max_roots_per_page(mut self, max_roots_per_page: u64) -> Self {
+        self.max_roots_per_page = max_roots_per_page.max(1);
+        self
+    }
+
+    pub fn with_roots_order(mut self, roots_order: RootsOrder) -> Self {
+        self.roots_order = roots_order;
+        self
+    }
+
+    /// Cap how many read and submission requests run at once. Each class gets
+    /// its own slots, and requests beyond them are shed with 503.
+    pub fn with_concurrency_limits(
+        mut self,
+        max_concurrent_reads: Option<usize>,
+        max_concurrent_submissions: Option<usize>,
+    ) -> Self {
+        self.max_concurrent_reads = max_concurrent_reads;
+        self.max_concurrent_submissions = max_concurrent_submissions;
+        self
+    }
+
+    /// Before get-entry-and-proof serves a leaf, check it against the leaf
+    /// the tree stores and that leaf's node hash, refusing with 500 on a
+    /// mismatch. Costs two extra reads per request.
+    pub fn with_leaf_verification(mut self, verify_served_leaves: bool) -> Self {
+        self.verify_served_leaves = verify_served_leaves;
+        self
+    }
+
+    pub fn with_entry_format(mut self, entry_format: EntryFormat) -> Self {
+        self.entry_format = entry_format;
+        self
+    }
+
+    /// Capped at [`MAX_ENTRY_BYTES_LIMIT`]
+    pub fn with_max_entry_bytes(mut self, max_entry_bytes: usize) -> Self {
+        self.max_entry_bytes = max_entry_bytes.min(MAX_ENTRY_BYTES_LIMIT);
+        self
+    }
+
+    /// Refuse submissions whose SCT timestamp has drifted further than
+    /// `window` from the log's clock by the time it is signed
+    pub fn with_sct_timestamp_window(mut self, window: TimestampWindow) -> Self {
+        self.sct_builder = Arc::new(self.sct_builder.with_timestamp_window(window));
+        self
+    }
+
+    /// Serve POST /debug/verify-inclusion and /debug/verify-consistency,
+    /// which trace a client's proof through the log's verifier
+    pub fn with_debug_endpoints(mut self, debug_endpoints: bool) -> Self {
+        self.debug_endpoints = debug_endpoints;
+        self
+    }
+
+    /// Require submissions to authenticate, recording the principal of each
+    /// new entry. Read endpoints stay public.
+    pub fn with_submission_auth(mut self, submission_auth: SubmissionAuth) -> Self {
+        self.submission_auth = Some(Arc::new(submission_auth));
+        self
+    }
+
+    /// Let /admin/reload reload the certificate of the TLS listener serving this log
+    pub fn with_server_tls(mut self, server_tls: Arc<ServerTls>) -> Self {
+        self.server_tls = Some(server_tls);
+        self
+    }
+}
+
+/// Address of the connecting client, when the server was started with
+/// connect info. Behind a proxy this is the proxy's address.
+pub struct ClientIp(pub Option<IpAddr>);
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(ClientIp(
+            parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip()),
+        ))
+    }
 }
 
+/// An add-chain body: the JSON `{"chain": [...]}` of RFC 6962, or a PEM
+/// bundle sent as `application/x-pem-file`. PEM certificates are converted to
+/// the JSON form so both take the same path through the handler.
+pub struct ChainSubmission(pub AddChainRequest);
+
+impl<S: Send + Sync> FromRequest<S> for ChainSubmission {
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let is_pem = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .is_some_and(|mime| mime.trim().eq_ignore_ascii_case(PEM_CONTENT_TYPE));
+        if !is_pem {
+            let Json(request) = Json::<AddChainRequest>::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            return Ok(ChainSubmission(request));
+        }
+
+        // Bytes enforces the same body limit as Json
+        let body = Bytes::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let bundle = std::str::from_utf8(&body).map_err(|_| {
+            api_error(ErrorCode::BadRequest, "PEM body is not valid UTF-8").into_response()
+        })?;
+        let chain =
+            pem_chain(bundle).map_err(|e| api_error(ErrorCode::BadRequest, e).into_response())?;
+        Ok(ChainSubmission(AddChainRequest {
+            chain: chain.iter().map(|der| STANDARD.encode(der)).collect(),
+        }))
+    }
+}
+
+const PEM_CONTENT_TYPE: &str = "application/x-pem-file";
+
+/// DER certificates of a PEM bundle in order. Text between blocks is ignored,
+/// as OpenSSL does, so annotated bundles are accepted.
+fn pem_chain(bundle: &str) -> Result<Vec<Vec<u8>>, String> {
+    const BEGIN: &str = "-----BEGIN ";
+    const END: &str = "-----END ";
+
+    let mut chain = Vec::new();
+    let mut rest = bundle;
+    while let Some(start) = rest.find(BEGIN) {
+        let index = chain.len();
+        let block = &rest[start..];
+        let end = block
+            .find(END)
+            .and_then(|end| {
+                let label_end = block[end + END.len()..].find("-----")?;
+                Some(end + END.len() + label_end + "-----".len())
+            })
+            .ok_or_else(|| format!("PEM block {} has no END line", index))?;
+        let (label, der) = pem_rfc7468::decode_vec(&block.as_bytes()[..end])
+            .map_err(|e| format!("PEM block {} is malformed: {}", index, e))?;
+        if label != "CERTIFICATE" {
+            return Err(format!(
+                "PEM block {} is a {}, not a CERTIFICATE",
+                index, label
+            ));
+        }
+        chain.push(der);
+        rest = &block[end..];
+    }
+    Ok(chain)
+}
+
+/// Shard label used in metrics when a single log is served at the root
+pub const DEFAULT_SHARD: &str = "default";
+
+/// Independent logs served by one process, each mounted under `/logs/{shard}`
+#[derive(Default)]
+pub struct LogRegistry {
+    shards: BTreeMap<String, ApiState>,
+}
+
+impl LogRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a log under `shard`, which becomes a path segment and a metrics label
+    pub fn insert(
+        &mut self,
+        shard: impl Into<String>,
+        state: ApiState,
+    ) -> crate::types::Result<()> {
+        let shard = shard.into();
+        if shard.is_empty()
+            || !shard
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(crate::types::CtError::Internal(format!(
+                "Invalid shard id '{}': use ASCII letters, digits, '-' and '_'",
+                shard
+            )));
+        }
+        if self.shards.contains_key(&shard) {
+            return Err(crate::types::CtError::Internal(format!(
+                "Duplicate shard id '{}'",
+                shard
+            )));
+        }
+        self.shards.insert(shard, state);
+        Ok(())
+    }
+
+    /// Routers serving every shard under `/logs/{shard}`. The log and
+    /// operational routers also serve process-wide `/health`, and every
+    /// router a `/metrics` combining every shard's metrics under a `log` label.
+    pub fn into_routers(self) -> LogRouters {
+        let shard_metrics: Arc<ShardMetrics> = Arc::new(
+            self.shards
+                .iter()
+                .map(|(shard, state)| (shard.clone(), state.metrics.clone()))
+                .collect(),
+        );
+        let process_routes =
+            || combined_metrics_router(shard_metrics.clone()).route("/health", get(health_check));
+
+        let mut routers = LogRouters {
+            log: process_routes(),
+            operational: process_routes(),
+            admin: combined_metrics_router(shard_metrics.clone()),
+        };
+        for (shard, state) in self.shards {
+            let prefix = format!("/logs/{}", shard);
+            let state = Arc::new(state);
+            routers.log = routers.log.nest(&prefix, log_router(state.clone(), &shard));
+            routers.operational = routers.operational.merge(
+                Router::new()
+                    .route(&format!("{}/ready", prefix), get(readiness_check))
+                    .with_state(state.clone()),
+            );
+            routers.admin = routers.admin.nest(&prefix, admin_router(state));
+        }
+        routers
+    }
+}
+
+/// The log's public router. It never serves /admin; see [`create_routers`].
 pub fn create_router(state: ApiState) -> Router {
+    log_router(Arc::new(state), DEFAULT_SHARD)
+}
+
+/// Routers for the listeners serving one or more logs
+pub struct LogRouters {
+    /// Everything clients use; served over HTTPS with the built-in TLS listener
+    pub log: Router,
+    /// Health, readiness and metrics, for the plain HTTP listener that runs
+    /// next to the HTTPS one
+    pub operational: Router,
+    /// The /admin endpoints, for a listener only operators can reach, and a
+    /// /metrics labelling every log's metrics with its shard
+    pub admin: Router,
+}
+
+pub fn create_routers(state: ApiState) -> LogRouters {
+    let state = Arc::new(state);
+    LogRouters {
+        log: log_router(state.clone(), DEFAULT_SHARD),
+        operational: Router::new()
+            .route("/health", get(health_check))
+            .route("/ready", get(readiness_check))
+            .route("/metrics", get(metrics_handler))
+            .with_state(state.clone()),
+        admin: admin_router(state.clone()).merge(combined_metrics_router(Arc::new(vec![(
+            DEFAULT_SHARD.to_string(),
+            state.metrics.clone(),
+        )]))),
+    }
+}
+
+/// `/metrics` of every log in `shards`, each labelled `log="{shard}"`
+fn combined_metrics_router(shards: Arc<ShardMetrics>) -> Router {
     Router::new()
+        .route("/metrics", get(combined_metrics_handler))
+        .with_state(shards)
+}
+
+fn admin_router(state: Arc<ApiState>) -> Router {
+    Router::new()
+        .route("/admin/rejections", get(admin_handlers::get_rejections))
+        .route("/admin/reload", post(admin_handlers::reload))
+        .route(
+            "/admin/cosignatures",
+            post(admin_handlers::add_cosignatures),
+        )
+        .layer(middleware::from_fn_with_state(
+            state.metrics.clone(),
+            json_error_middleware,
+        ))
+        .with_state(state)
+}
+
+fn log_router(state: Arc<ApiState>, shard: &str) -> Router {
+    // Gzip request bodies are inflated before extraction, so the default body
+    // limit caps the decompressed size rather than the bytes on the wire
+    let decompress = RequestDecompressionLayer::new();
+
+    let submissions = RouteClass::new(
+        "submission",
+        state.max_concurrent_submissions,
+        &state.metrics,
+    );
+    let submission_routes = match state.entry_format {
+        EntryFormat::X509 => Router::new()
+            .route(
+                "/ct/v1/add-chain",
+                post(handlers::add_chain).layer(decompress.clone()),
+            )
+            .route(
+                "/ct/v1/add-pre-chain",
+                post(handlers::add_pre_chain).layer(decompress.clone()),
+            ),
+        // The default body limit would refuse entries well below
+        // max_entry_bytes once base64 has inflated them
+        EntryFormat::Raw => Router::new().route(
+            "/v1/add-entry",
+            post(raw_handlers::add_entry)
+                .layer(decompress.clone())
+                .layer(DefaultBodyLimit::max(
+                    state.max_entry_bytes.div_ceil(3) * 4 + ADD_ENTRY_JSON_OVERHEAD,
+                )),
+        ),
+    }
+    .route_layer(middleware::from_fn_with_state(
+        Arc::new(submissions),
+        limit_concurrency,
+    ));
+    // Outermost, so unauthenticated requests never take a submission slot
+    let submission_routes = match &state.submission_auth {
+        Some(auth) => submission_routes.route_layer(middleware::from_fn_with_state(
+            auth.clone(),
+            require_submitter,
+        )),
+        None => submission_routes,
+    };
+
+    let reads = RouteClass::new("read", state.max_concurrent_reads, &state.metrics);
+    let read_routes = Router::new()
         // RFC 6962 endpoints
-        .route("/ct/v1/add-chain", post(handlers::add_chain))
-        .route("/ct/v1/add-pre-chain", post(handlers::add_pre_chain))
         .route("/ct/v1/get-sth", get(handlers::get_sth))
         .route(
             "/ct/v1/get-sth-consistency",
             get(handlers::get_sth_consistency),
         )
         .route("/ct/v1/get-proof-by-hash", get(handlers::get_proof_by_hash))
-        .route("/ct/v1/get-entries", get(handlers::get_entries))
-        .route("/ct/v1/get-roots", get(handlers::get_roots))
         .route(
-            "/ct/v1/get-entry-and-proof",
-            get(handlers::get_entry_and_proof),
+            "/ct/v1/get-recent-entries",
+            get(handlers::get_recent_entries),
         )
         // Static CT API endpoints
         .route("/checkpoint", get(static_handlers::get_checkpoint))
+        .route(
+            "/checkpoint/{size}",
+            get(static_handlers::get_checkpoint_at_size),
+        )
         .route("/tile/{level}/{*index}", get(handle_tile_request_with_path))
         .route(
             "/tile/data/{*index}",
             get(handle_data_tile_request_with_path),
+        );
+    // Endpoints that serve entries as certificates exist only in X.509 mode
+    let read_routes = match state.entry_format {
+        EntryFormat::X509 => read_routes
+            .route("/ct/v1/get-entries", get(handlers::get_entries))
+            .route("/ct/v1/get-roots", get(handlers::get_roots))
+            .route(
+                "/ct/v1/get-entry-and-proof",
+                get(handlers::get_entry_and_proof),
+            )
+            .route("/issuer/{fingerprint}", get(static_handlers::get_issuer))
+            // RFC 6962 Pages Extension endpoints
+            .route("/ct-pages/v1/discover", get(pages_handlers::discover))
+            .route(
+                "/ct-pages/v1/page/{page_number}",
+                get(pages_handlers::get_page),
+            )
+            .route(
+                "/ct-pages/v1/certificate/{hash}",
+                get(pages_handlers::get_certificate),
+            ),
+        EntryFormat::Raw => read_routes.route("/v1/get-entries", get(raw_handlers::get_entries)),
+    }
+    .route_layer(middleware::from_fn_with_state(
+        Arc::new(reads),
+        limit_concurrency,
+    ));
+
+    let debug_routes = if state.debug_endpoints {
+        Router::new()
+            .route(
+                "/debug/verify-inclusion",
+                post(debug_handlers::verify_inclusion),
+            )
+            .route(
+                "/debug/verify-consistency",
+                post(debug_handlers::verify_consistency),
+            )
+    } else {
+        Router::new()
+    };
+
+    // Gossip, discovery and operational endpoints are cheap and stay unlimited
+    Router::new()
+        .merge(submission_routes)
+        .merge(read_routes)
+        .merge(debug_routes)
+        // STH gossip between instances of this log
+        .route(
+            "/ct/v1/get-gossip-sth",
+            get(gossip_handlers::get_gossip_sth),
         )
-        .route("/issuer/{fingerprint}", get(static_handlers::get_issuer))
-        .route("/inclusion_request.json", get(handlers::inclusion_request))
-        // RFC 6962 Pages Extension endpoints
-        .route("/ct-pages/v1/discover", get(pages_handlers::discover))
         .route(
-            "/ct-pages/v1/page/{page_number}",
-            get(pages_handlers::get_page),
+            "/ct/v1/receive-gossip-sth",
+            post(gossip_handlers::receive_gossip_sth).layer(decompress),
         )
+        .route("/inclusion_request.json", get(handlers::inclusion_request))
         .route(
-            "/ct-pages/v1/certificate/{hash}",
-            get(pages_handlers::get_certificate),
+            "/.well-known/ct-log-list.json",
+            get(log_list_handlers::get_log_list),
         )
+        .route("/stats", get(stats_handlers::get_stats))
         .route("/health", get(health_check))
+        .route("/ready", get(readiness_check))
         // Prometheus metrics endpoint
         .route("/metrics", get(metrics_handler))
-        .layer(middleware::from_fn(metrics_middleware))
+        .layer(middleware::from_fn_with_state(
+            state.metrics.clone(),
+            json_error_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            (Arc::<str>::from(shard), state.metrics.clone()),
+            metrics_middleware,
+        ))
+        // Polled by clients syncing their clocks, so kept out of the request
+        // metrics, which only cover the routes above
+        .route("/ct/v1/get-timestamp", get(handlers::get_timestamp))
         .layer(CompressionLayer::new())
-        .with_state(Arc::new(state))
+        .with_state(state)
 }
 
 async fn health_check() -> Json<HealthResponse> {
@@ -127,6 +684,22 @@ async fn health_check() -> Json<HealthResponse> {
     })
 }
 
+/// Readiness probe: unlike `/health`, reads the tree size and committed root,
+/// answering 503 if the database cannot serve them
+async fn readiness_check(
+    State(state): State<Arc<ApiState>>,
+) -> Result<Json<ReadyResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let (_, root) = tokio::try_join!(state.merkle_tree.size(), state.merkle_tree.committed_root())
+        .map_err(|e| {
+            tracing::warn!("Readiness check failed: {}", e);
+            api_error(ErrorCode::Unavailable, format!("Not ready: {}", e))
+        })?;
+    Ok(Json(ReadyResponse {
+        status: "ready".to_string(),
+        tree_size: root.num_leaves(),
+    }))
+}
+
 // Handler wrappers for tile routes
 async fn handle_tile_request_with_path(
     state: axum::extract::State<Arc<ApiState>>,
@@ -166,56 +739,229 @@ struct HealthResponse {
     version: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Serialize)]
+struct ReadyResponse {
+    status: String,
+    /// Committed tree size
+    tree_size: u64,
+}
+
+/// Body of every error response, so clients can branch on `code` rather than
+/// matching `error` strings
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub error: String,
+    pub code: ErrorCode,
+}
+
+/// Machine-readable error class. Each maps to exactly one HTTP status, and
+/// its snake_case name is the `code` of the response body. Codes are part of
+/// the API: clients and dashboards match on them, so existing ones are never
+/// renamed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Malformed request: bad parameters, base64 or JSON
+    BadRequest,
+    /// A submitted chain that failed validation for a reason without its own code
+    InvalidChain,
+    /// A submitted certificate that is not valid DER X.509
+    MalformedCertificate,
+    /// A submitted chain that does not lead to a root the log accepts
+    UntrustedRoot,
+    /// A submitted chain with a certificate not signed by its issuer
+    BadSignature,
+    /// A submitted chain whose certificates are not each followed by their issuer
+    UnorderedChain,
+    /// A submitted chain with more certificates than the log accepts
+    ChainTooLong,
+    /// A submitted chain containing a certificate larger than the log accepts
+    CertificateTooLarge,
+    /// A submitted chain that needs too many signature checks to validate
+    TooManySignatureVerifications,
+    /// A raw entry larger than the log accepts
+    EntryTooLarge,
+    /// A submission without the credential the log requires
+    Unauthorized,
+    /// A submission whose credential the log does not accept
+    Forbidden,
+    NotFound,
+    /// A request body over the size limit, after any decompression
+    PayloadTooLarge,
+    RateLimited,
+    /// The submission queue is full
+    Unavailable,
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::BadRequest
+            | ErrorCode::InvalidChain
+            | ErrorCode::MalformedCertificate
+            | ErrorCode::UntrustedRoot
+            | ErrorCode::BadSignature
+            | ErrorCode::UnorderedChain
+            | ErrorCode::ChainTooLong
+            | ErrorCode::CertificateTooLarge
+            | ErrorCode::TooManySignatureVerifications
+            | ErrorCode::EntryTooLarge => StatusCode::BAD_REQUEST,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::Forbidden => StatusCode::FORBIDDEN,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// The `code` in response bodies, and the `code` label of
+    /// `ct_http_errors_total`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::BadRequest => "bad_request",
+            ErrorCode::InvalidChain => "invalid_chain",
+            ErrorCode::MalformedCertificate => "malformed_certificate",
+            ErrorCode::UntrustedRoot => "untrusted_root",
+            ErrorCode::BadSignature => "bad_signature",
+            ErrorCode::UnorderedChain => "unordered_chain",
+            ErrorCode::ChainTooLong => "chain_too_long",
+            ErrorCode::CertificateTooLarge => "certificate_too_large",
+            ErrorCode::TooManySignatureVerifications => "too_many_signature_verifications",
+            ErrorCode::EntryTooLarge => "entry_too_large",
+            ErrorCode::Unauthorized => "unauthorized",
+            ErrorCode::Forbidden => "forbidden",
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::PayloadTooLarge => "payload_too_large",
+            ErrorCode::RateLimited => "rate_limited",
+            ErrorCode::Unavailable => "unavailable",
+            ErrorCode::Internal => "internal",
+        }
+    }
+
+    /// Class of a response that did not come from [`api_error`], such as an
+    /// extractor rejection
+    fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => ErrorCode::Unauthorized,
+            StatusCode::FORBIDDEN => ErrorCode::Forbidden,
+            StatusCode::NOT_FOUND => ErrorCode::NotFound,
+            StatusCode::PAYLOAD_TOO_LARGE => ErrorCode::PayloadTooLarge,
+            StatusCode::TOO_MANY_REQUESTS => ErrorCode::RateLimited,
+            StatusCode::SERVICE_UNAVAILABLE => ErrorCode::Unavailable,
+            status if status.is_client_error() => ErrorCode::BadRequest,
+            _ => ErrorCode::Internal,
+        }
+    }
 }
 
-impl From<crate::types::CtError> for ErrorResponse {
-    fn from(err: crate::types::CtError) -> Self {
-        Self {
-            error: err.to_string(),
+/// Class of an error raised below the handlers. Storage failures are the
+/// log's fault, never the client's.
+impl From<&CtError> for ErrorCode {
+    fn from(error: &CtError) -> Self {
+        match error {
+            CtError::InvalidCertificate(_) => ErrorCode::MalformedCertificate,
+            CtError::BadRequest(_) => ErrorCode::BadRequest,
+            CtError::Validation(error) => error.into(),
+            // Both pass with time, so the submission can be retried as is
+            CtError::Storage(StorageError::QueueFull | StorageError::InvalidTimestamp(_))
+            | CtError::InvalidTimestamp(_) => ErrorCode::Unavailable,
+            CtError::Storage(_) | CtError::Internal(_) => ErrorCode::Internal,
         }
     }
 }
 
-impl From<crate::storage::StorageError> for ErrorResponse {
-    fn from(err: crate::storage::StorageError) -> Self {
-        Self {
-            error: format!("Storage error: {}", err),
+/// Class of a chain the validator rejected
+impl From<&ValidationError> for ErrorCode {
+    fn from(error: &ValidationError) -> Self {
+        match error {
+            ValidationError::ChainTooLong { .. } => ErrorCode::ChainTooLong,
+            ValidationError::CertificateTooLarge { .. } => ErrorCode::CertificateTooLarge,
+            ValidationError::TooManySignatureVerifications { .. } => {
+                ErrorCode::TooManySignatureVerifications
+            }
+            ValidationError::UnorderedChain { .. } => ErrorCode::UnorderedChain,
+            ValidationError::EmptyChain | ValidationError::UnparsableCertificate { .. } => {
+                ErrorCode::MalformedCertificate
+            }
+            ValidationError::NoTrustedRoots | ValidationError::UntrustedRoot { .. } => {
+                ErrorCode::UntrustedRoot
+            }
+            ValidationError::InvalidSignature { .. } | ValidationError::InvalidRootSignature => {
+                ErrorCode::BadSignature
+            }
+            ValidationError::DisallowedSignatureAlgorithm { .. }
+            | ValidationError::SignatureAlgorithmMismatch { .. }
+            | ValidationError::DuplicateCertificate { .. }
+            | ValidationError::ExpiresBeforeWindow { .. }
+            | ValidationError::ExpiresAfterWindow { .. }
+            | ValidationError::NotYetValid { .. }
+            | ValidationError::Expired { .. }
+            | ValidationError::PoisonNotCritical
+            | ValidationError::PoisonNotNull
+            | ValidationError::PrecertIssuerMissing
+            | ValidationError::PrecertSigningCertNotDirectlyIssued => ErrorCode::InvalidChain,
         }
     }
 }
 
-async fn metrics_handler() -> Result<String, (axum::http::StatusCode, String)> {
-    let encoder = TextEncoder::new();
-    let metric_families = prometheus::gather();
-    let mut buffer = Vec::new();
-    encoder.encode(&metric_families, &mut buffer).map_err(|e| {
-        (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Failed to encode metrics: {}", e),
-        )
-    })?;
-    String::from_utf8(buffer).map_err(|e| {
-        (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Failed to convert metrics to string: {}", e),
-        )
-    })
+/// Error response whose status always agrees with its `code`
+pub fn api_error(code: ErrorCode, error: impl Into<String>) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        code.status(),
+        Json(ErrorResponse {
+            error: error.into(),
+            code,
+        }),
+    )
+}
+
+async fn metrics_handler(
+    State(state): State<Arc<ApiState>>,
+) -> Result<String, (axum::http::StatusCode, String)> {
+    state.metrics.encode().map_err(metrics_error)
+}
+
+/// Metrics of every shard served by a [`LogRegistry`], by shard id
+type ShardMetrics = Vec<(String, Arc<Metrics>)>;
+
+async fn combined_metrics_handler(
+    State(shards): State<Arc<ShardMetrics>>,
+) -> Result<String, (axum::http::StatusCode, String)> {
+    let families = crate::metrics::gather_labelled(
+        shards
+            .iter()
+            .map(|(shard, metrics)| (shard.as_str(), metrics.as_ref())),
+    );
+    crate::metrics::encode(&families).map_err(metrics_error)
 }
 
-use crate::metrics;
-use axum::{body::Body, extract::Request};
+fn metrics_error(e: prometheus::Error) -> (axum::http::StatusCode, String) {
+    (
+        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Failed to encode metrics: {}", e),
+    )
+}
+use axum::{
+    body::Body,
+    extract::{Request, State},
+};
 use std::time::Instant;
 
 fn is_static_ct_endpoint(path: &str) -> bool {
-    path == "/checkpoint" || path.starts_with("/tile/") || path.starts_with("/issuer/")
+    path == "/checkpoint"
+        || path.starts_with("/checkpoint/")
+        || path.starts_with("/tile/")
+        || path.starts_with("/issuer/")
 }
 
 fn normalize_path_for_metrics(path: &str) -> String {
     if path == "/checkpoint" {
         path.to_string()
+    } else if path.starts_with("/checkpoint/") {
+        "/checkpoint/{size}".to_string()
     } else if path.starts_with("/tile/") {
         "/tile".to_string()
     } else if path.starts_with("/issuer/") {
@@ -226,6 +972,7 @@ fn normalize_path_for_metrics(path: &str) -> String {
 }
 
 async fn metrics_middleware(
+    State((shard, metrics)): State<(Arc<str>, Arc<Metrics>)>,
     req: Request<Body>,
     next: axum::middleware::Next,
 ) -> Result<Response, axum::response::Response> {
@@ -233,7 +980,7 @@ async fn metrics_middleware(
     let path = req.uri().path().to_string();
     let method = req.method().to_string();
 
-    metrics::ACTIVE_CONNECTIONS.inc();
+    metrics.active_connections.inc();
 
     let response = next.run(req).await;
 
@@ -245,16 +992,1167 @@ async fn metrics_middleware(
         // Normalize path to avoid cardinality explosion
         let normalized_path = normalize_path_for_metrics(&path);
 
-        metrics::HTTP_REQUEST_DURATION_SECONDS
-            .with_label_values(&[&normalized_path, &method])
+        metrics
+            .http_request_duration_seconds
+            .with_label_values(&[&*shard, &normalized_path, &method])
             .observe(duration);
 
-        metrics::HTTP_REQUESTS_TOTAL
-            .with_label_values(&[&normalized_path, &method, &status])
+        metrics
+            .http_requests_total
+            .with_label_values(&[&*shard, &normalized_path, &method, &status])
             .inc();
     }
 
-    metrics::ACTIVE_CONNECTIONS.dec();
+    metrics.active_connections.dec();
 
     Ok(response)
 }
+
+/// Upper bound on an error body read by [`json_error_middleware`]
+const MAX_PLAIN_ERROR_BYTES: usize = 64 * 1024;
+
+/// Count every error response in `ct_http_errors_total` by its code, and give
+/// those that did not come from [`api_error`], such as extractor rejections
+/// and unmatched routes, the same JSON body
+async fn json_error_middleware(
+    State(metrics): State<Arc<Metrics>>,
+    req: Request<Body>,
+    next: axum::middleware::Next,
+) -> Response {
+    let response = next.run(req).await;
+    let status = response.status();
+    if !(status.is_client_error() || status.is_server_error()) {
+        return response;
+    }
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+
+    let (mut parts, body) = response.into_parts();
+    let body = axum::body::to_bytes(body, MAX_PLAIN_ERROR_BYTES)
+        .await
+        .unwrap_or_default();
+    if is_json {
+        let code = serde_json::from_slice::<ErrorResponse>(&body)
+            .map(|response| response.code)
+            .unwrap_or_else(|_| ErrorCode::from_status(status));
+        metrics
+            .http_errors_total
+            .with_label_values(&[code.as_str()])
+            .inc();
+        return Response::from_parts(parts, Body::from(body));
+    }
+
+    let message = String::from_utf8_lossy(&body).trim().to_string();
+    let error = if message.is_empty() {
+        status.canonical_reason().unwrap_or("Error").to_string()
+    } else {
+        message
+    };
+
+    let code = ErrorCode::from_status(status);
+    metrics
+        .http_errors_total
+        .with_label_values(&[code.as_str()])
+        .inc();
+    let body = serde_json::to_vec(&ErrorResponse { error, code }).unwrap_or_default();
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/json"),
+    );
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::tree_head::{SignedTreeHead, SthResponse};
+
+    async fn shard_state(entry_count: u64) -> ApiState {
+        Arc::into_inner(handlers::tests::create_test_state(entry_count).await).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_shards_serve_independent_sths() {
+        let shard_a = shard_state(3).await;
+        let shard_b = shard_state(5).await;
+        let key_a = shard_a.public_key_der.clone();
+        let key_b = shard_b.public_key_der.clone();
+
+        let mut registry = LogRegistry::new();
+        registry.insert("a", shard_a).unwrap();
+        registry.insert("b", shard_b).unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, registry.into_routers().log)
+                .await
+                .unwrap();
+        });
+
+        let get_sth = |shard: &'static str| async move {
+            let response: SthResponse =
+                reqwest::get(format!("http://{}/logs/{}/ct/v1/get-sth", addr, shard))
+                    .await
+                    .unwrap()
+                    .json()
+                    .await
+                    .unwrap();
+            SignedTreeHead::from_api_response(&response).unwrap()
+        };
+
+        let sth_a = get_sth("a").await;
+        let sth_b = get_sth("b").await;
+
+        assert_eq!(sth_a.tree_size, 3);
+        assert_eq!(sth_b.tree_size, 5);
+        assert_ne!(sth_a.root_hash, sth_b.root_hash);
+
+        sth_a.verify(&key_a).unwrap();
+        sth_b.verify(&key_b).unwrap();
+        assert!(sth_a.verify(&key_b).is_err());
+
+        let missing = reqwest::get(format!("http://{}/logs/c/ct/v1/get-sth", addr))
+            .await
+            .unwrap();
+        assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+
+    async fn serve(router: Router) -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+        addr
+    }
+
+    async fn scrape(url: String) -> String {
+        let response = reqwest::get(url).await.unwrap();
+        assert!(response.status().is_success());
+        response.text().await.unwrap()
+    }
+
+    async fn isolated_state(entry_count: u64) -> ApiState {
+        let metrics = Arc::new(Metrics::new());
+        let state = handlers::tests::create_test_state_with_metrics(entry_count, metrics).await;
+        Arc::into_inner(state).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_logs_scrape_their_own_metrics() {
+        let (state_a, state_b) = tokio::join!(isolated_state(3), isolated_state(5));
+        let addr_a = serve(create_router(state_a)).await;
+        let addr_b = serve(create_router(state_b)).await;
+
+        let (metrics_a, metrics_b) = tokio::join!(
+            scrape(format!("http://{}/metrics", addr_a)),
+            scrape(format!("http://{}/metrics", addr_b)),
+        );
+        assert!(metrics_a.contains("ct_log_entries_total{type=\"x509\"} 3\n"));
+        assert!(metrics_b.contains("ct_log_entries_total{type=\"x509\"} 5\n"));
+    }
+
+    #[tokio::test]
+    async fn test_registry_metrics_are_labelled_by_log() {
+        let (state_a, state_b) = tokio::join!(isolated_state(3), isolated_state(5));
+        let mut registry = LogRegistry::new();
+        registry.insert("a", state_a).unwrap();
+        registry.insert("b", state_b).unwrap();
+        let addr = serve(registry.into_routers().log).await;
+
+        let combined = scrape(format!("http://{}/metrics", addr)).await;
+        assert!(combined.contains("ct_log_entries_total{type=\"x509\",log=\"a\"} 3\n"));
+        assert!(combined.contains("ct_log_entries_total{type=\"x509\",log=\"b\"} 5\n"));
+        assert_eq!(combined.matches("# TYPE ct_log_entries_total ").count(), 1);
+
+        let shard_b = scrape(format!("http://{}/logs/b/metrics", addr)).await;
+        assert!(shard_b.contains("ct_log_entries_total{type=\"x509\"} 5\n"));
+    }
+
+    #[tokio::test]
+    async fn test_registry_routers_keep_admin_off_the_log_listener() {
+        let mut registry = LogRegistry::new();
+        registry.insert("a", isolated_state(2).await).unwrap();
+        let routers = registry.into_routers();
+        let log_addr = serve(routers.log).await;
+        let operational_addr = serve(routers.operational).await;
+        let admin_addr = serve(routers.admin).await;
+
+        let response = reqwest::get(format!("http://{}/logs/a/admin/rejections", log_addr))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(response.text().await.unwrap().is_empty());
+
+        // Routed to the shard, which has no rejection log
+        let response = reqwest::get(format!("http://{}/logs/a/admin/rejections", admin_addr))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body: ErrorResponse = response.json().await.unwrap();
+        assert!(body.error.contains("Rejection log"));
+
+        let ready = scrape(format!("http://{}/logs/a/ready", operational_addr)).await;
+        assert!(ready.contains("\"tree_size\":2"));
+        for addr in [operational_addr, admin_addr] {
+            let combined = scrape(format!("http://{}/metrics", addr)).await;
+            assert!(combined.contains("ct_log_entries_total{type=\"x509\",log=\"a\"} 2\n"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_admin_metrics_label_a_single_log() {
+        let routers = create_routers(isolated_state(4).await);
+        let admin_addr = serve(routers.admin).await;
+
+        let metrics = scrape(format!("http://{}/metrics", admin_addr)).await;
+        assert!(metrics.contains("ct_log_entries_total{type=\"x509\",log=\"default\"} 4\n"));
+    }
+
+    #[tokio::test]
+    async fn test_get_timestamp_is_not_tracked() {
+        let addr = serve(create_router(isolated_state(0).await)).await;
+
+        let before = chrono::Utc::now().timestamp_millis() as u64;
+        let response: serde_json::Value =
+            reqwest::get(format!("http://{}/ct/v1/get-timestamp", addr))
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+        let after = chrono::Utc::now().timestamp_millis() as u64;
+        let timestamp = response["timestamp"].as_u64().unwrap();
+        assert!((before..=after).contains(&timestamp));
+
+        scrape(format!("http://{}/ct/v1/get-sth", addr)).await;
+        let metrics = scrape(format!("http://{}/metrics", addr)).await;
+        assert!(metrics.contains("/ct/v1/get-sth"));
+        assert!(!metrics.contains("get-timestamp"));
+    }
+
+    #[tokio::test]
+    async fn test_ready_fails_when_tree_unreadable() {
+        use object_store::memory::InMemory;
+        use slatedb::Db;
+
+        let db = Arc::new(Db::open("test", Arc::new(InMemory::new())).await.unwrap());
+        let state =
+            handlers::tests::create_test_state_on(db.clone(), 3, Arc::new(Metrics::new())).await;
+        let addr = serve(create_router(Arc::into_inner(state).unwrap())).await;
+
+        let response = reqwest::get(format!("http://{}/ready", addr))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["status"], "ready");
+        assert_eq!(body["tree_size"], 3);
+
+        // A tree whose metadata cannot be read is not ready, though still live
+        db.put(b"meta", b"bad").await.unwrap();
+        let response = reqwest::get(format!("http://{}/ready", addr))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: ErrorResponse = response.json().await.unwrap();
+        assert_eq!(body.code, ErrorCode::Unavailable);
+        scrape(format!("http://{}/health", addr)).await;
+    }
+
+    #[tokio::test]
+    async fn test_invalid_and_duplicate_shard_ids_rejected() {
+        let mut registry = LogRegistry::new();
+        assert!(registry.insert("a/b", shard_state(0).await).is_err());
+        assert!(registry.insert("", shard_state(0).await).is_err());
+        registry.insert("a", shard_state(0).await).unwrap();
+        assert!(registry.insert("a", shard_state(0).await).is_err());
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_gzip_add_chain() {
+        let state = shard_state(0).await;
+        let log_id = state.log_id.clone();
+        let addr = serve(create_router(state)).await;
+        let client = reqwest::Client::new();
+        let url = format!("http://{}/ct/v1/add-chain", addr);
+
+        let cert = crate::test_utils::utils::create_test_certificate_with_serial(9);
+        let body = serde_json::to_vec(&serde_json::json!({
+            "chain": [base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &cert)]
+        }))
+        .unwrap();
+        let response = client
+            .post(&url)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::CONTENT_ENCODING, "gzip")
+            .body(gzip(&body))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let sct: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(sct["sct_version"], 0);
+        assert_eq!(
+            sct["id"],
+            base64::Engine::encode(
+                &base64::engine::general_purpose::STANDARD,
+                log_id.as_bytes()
+            )
+        );
+        assert!(!sct["signature"].as_str().unwrap().is_empty());
+
+        // A small gzip body that inflates past the body limit is refused
+        let bomb = gzip(&vec![b' '; 4 * 1024 * 1024]);
+        assert!(bomb.len() < 64 * 1024);
+        let response = client
+            .post(&url)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::CONTENT_ENCODING, "gzip")
+            .body(bomb)
+            .send()
+            .await
+            .unwrap();
+        assert_error(response, 413, "payload_too_large").await;
+    }
+
+    /// Crawls get-entries from many clients at once and checks that add-chain
+    /// latency, which the read limit should keep close to an idle log's,
+    /// stays within a bound loose enough for a loaded CI machine
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_add_chain_latency_under_read_load() {
+        const CRAWLERS: usize = 64;
+        const SUBMISSIONS: u8 = 100;
+        const MAX_P99: std::time::Duration = std::time::Duration::from_secs(2);
+
+        let state = shard_state(1000)
+            .await
+            .with_concurrency_limits(Some(8), Some(64));
+        let metrics = state.metrics.clone();
+        let addr = serve(create_router(state)).await;
+        let client = reqwest::Client::new();
+
+        let crawling = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let crawlers: Vec<_> = (0..CRAWLERS)
+            .map(|_| {
+                let client = client.clone();
+                let crawling = crawling.clone();
+                let url = format!("http://{}/ct/v1/get-entries?start=0&end=255", addr);
+                tokio::spawn(async move {
+                    while crawling.load(std::sync::atomic::Ordering::Relaxed) {
+                        let _ = client.get(&url).send().await;
+                    }
+                })
+            })
+            .collect();
+
+        let url = format!("http://{}/ct/v1/add-chain", addr);
+        let mut latencies = Vec::new();
+        for serial in 0..SUBMISSIONS {
+            let cert = crate::test_utils::utils::create_test_certificate_with_serial(serial);
+            let started = std::time::Instant::now();
+            let response = client
+                .post(&url)
+                .json(&serde_json::json!({ "chain": [STANDARD.encode(&cert)] }))
+                .send()
+                .await
+                .unwrap();
+            latencies.push(started.elapsed());
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        crawling.store(false, std::sync::atomic::Ordering::Relaxed);
+        for crawler in crawlers {
+            crawler.await.unwrap();
+        }
+
+        latencies.sort();
+        let shed = metrics
+            .shed_requests_total
+            .with_label_values(&["read"])
+            .get();
+        let p99 = latencies[latencies.len() * 99 / 100];
+        assert!(
+            p99 <= MAX_P99,
+            "add-chain p50 {:?}, p99 {:?} with {} reads shed",
+            latencies[latencies.len() / 2],
+            p99,
+            shed
+        );
+    }
+
+    fn pem_bundle(chain: &[&[u8]]) -> String {
+        chain
+            .iter()
+            .map(|der| {
+                pem_rfc7468::encode_string("CERTIFICATE", pem_rfc7468::LineEnding::LF, der).unwrap()
+            })
+            .collect::<Vec<_>>()
+            .join("# issuer\n")
+    }
+
+    #[tokio::test]
+    async fn test_pem_add_chain() {
+        let addr = serve(create_router(shard_state(0).await)).await;
+        let client = reqwest::Client::new();
+        let url = format!("http://{}/ct/v1/add-chain", addr);
+        let post_json = |chain: &[&[u8]]| {
+            let chain: Vec<String> = chain.iter().map(|der| STANDARD.encode(der)).collect();
+            client
+                .post(&url)
+                .json(&serde_json::json!({ "chain": chain }))
+                .send()
+        };
+        let post_pem = |body: String| {
+            client
+                .post(&url)
+                .header(header::CONTENT_TYPE, "application/x-pem-file")
+                .body(body)
+                .send()
+        };
+
+        let issuer = crate::test_utils::utils::create_test_certificate_with_serial(20);
+        let first = crate::test_utils::utils::create_test_certificate_with_serial(21);
+        let second = crate::test_utils::utils::create_test_certificate_with_serial(22);
+
+        // Whichever encoding comes second gets the first submission's SCT back
+        let by_json: serde_json::Value = post_json(&[&first, &issuer])
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let response = post_pem(pem_bundle(&[&first, &issuer])).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let by_pem: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(by_pem, by_json);
+        let first_sct = by_json;
+
+        let by_pem: serde_json::Value = post_pem(pem_bundle(&[&second]))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let by_json: serde_json::Value = post_json(&[&second]).await.unwrap().json().await.unwrap();
+        assert_eq!(by_json, by_pem);
+        assert_ne!(by_json["signature"], first_sct["signature"]);
+
+        // Errors name the offending block
+        let valid = pem_bundle(&[&first]);
+        for (body, block) in [
+            (
+                format!(
+                    "{}-----BEGIN CERTIFICATE-----\n!!!!\n-----END CERTIFICATE-----\n",
+                    valid
+                ),
+                "block 1",
+            ),
+            ("-----BEGIN CERTIFICATE-----\nMIIB\n".to_string(), "block 0"),
+            (
+                pem_rfc7468::encode_string("PRIVATE KEY", pem_rfc7468::LineEnding::LF, &first)
+                    .unwrap(),
+                "block 0",
+            ),
+        ] {
+            let response = post_pem(body).await.unwrap();
+            assert_eq!(response.status().as_u16(), 400);
+            let error: serde_json::Value = response.json().await.unwrap();
+            assert!(
+                error["error"].as_str().unwrap().contains(block),
+                "{}",
+                error
+            );
+        }
+
+        let response = post_pem(String::new()).await.unwrap();
+        assert_error(response, 400, "bad_request").await;
+
+        let response = post_pem(format!("{}{}", valid, " ".repeat(3 * 1024 * 1024)))
+            .await
+            .unwrap();
+        assert_error(response, 413, "payload_too_large").await;
+    }
+
+    async fn assert_error(response: reqwest::Response, status: u16, code: &str) {
+        assert_eq!(response.status().as_u16(), status);
+        assert_eq!(
+            response.headers()[reqwest::header::CONTENT_TYPE],
+            "application/json"
+        );
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["code"], code);
+        assert!(!body["error"].as_str().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_error_responses() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = create_router(shard_state(2).await);
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+        let client = reqwest::Client::new();
+        let url = |path: &str| format!("http://{}{}", addr, path);
+
+        let response = client
+            .get(url("/ct/v1/get-entries?start=1&end=0"))
+            .send()
+            .await
+            .unwrap();
+        assert_error(response, 400, "bad_request").await;
+
+        // Extractor rejections get the same body
+        let response = client.get(url("/ct/v1/get-entries")).send().await.unwrap();
+        assert_error(response, 400, "bad_request").await;
+
+        let response = client
+            .post(url("/ct/v1/add-chain"))
+            .json(&serde_json::json!({ "chain": ["AAAA"] }))
+            .send()
+            .await
+            .unwrap();
+        assert_error(response, 400, "malformed_certificate").await;
+
+        let response = client
+            .get(url(&format!(
+                "/ct/v1/get-proof-by-hash?hash={}&tree_size=2",
+                base64::Engine::encode(&base64::engine::general_purpose::STANDARD, [0; 32])
+            )))
+            .send()
+            .await
+            .unwrap();
+        assert_error(response, 404, "not_found").await;
+
+        // The empty tree has no inclusion proofs, whatever the leaf
+        for path in [
+            format!(
+                "/ct/v1/get-proof-by-hash?hash={}&tree_size=0",
+                base64::Engine::encode(&base64::engine::general_purpose::STANDARD, [0; 32])
+            ),
+            "/ct/v1/get-entry-and-proof?leaf_index=0&tree_size=0".to_string(),
+        ] {
+            let response = client.get(url(&path)).send().await.unwrap();
+            assert_error(response, 400, "bad_request").await;
+        }
+
+        let response = client.get(url("/no-such-endpoint")).send().await.unwrap();
+        assert_error(response, 404, "not_found").await;
+
+        for (code, status, name) in [
+            (ErrorCode::RateLimited, 429, "rate_limited"),
+            (ErrorCode::Unavailable, 503, "unavailable"),
+            (ErrorCode::Internal, 500, "internal"),
+        ] {
+            let response = axum::response::IntoResponse::into_response(api_error(code, "error"));
+            assert_eq!(response.status().as_u16(), status);
+            assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+            let body = axum::body::to_bytes(response.into_body(), 1024)
+                .await
+                .unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["code"], name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inclusion_request_proves_a_leaf() {
+        use crate::merkle_storage::serialization::audit_path_to_inclusion_proof;
+        use crate::merkle_tree::RootHash;
+        use crate::types::LeafInclusionResponse;
+        use sha2::{Digest, Sha256};
+
+        let state = shard_state(20).await;
+        let storage = state.storage.clone();
+        let log_id = state.log_id.to_base64();
+        let addr = serve(create_router(state)).await;
+        let client = reqwest::Client::new();
+        let url = format!("http://{}/inclusion_request.json", addr);
+
+        let leaf_input = storage
+            .get_entry(13)
+            .await
+            .unwrap()
+            .unwrap()
+            .serialize()
+            .unwrap();
+        let leaf_hash = Sha256::new_with_prefix([0u8])
+            .chain_update(&leaf_input)
+            .finalize();
+        let inclusion: LeafInclusionResponse = client
+            .get(&url)
+            .query(&[("hash", STANDARD.encode(leaf_hash))])
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(inclusion.log_id, log_id);
+        assert_eq!(inclusion.tree_size, 20);
+        assert_eq!(inclusion.leaf_index, 13);
+        assert_eq!(inclusion.leaf_hash, STANDARD.encode(leaf_hash));
+
+        let sth: SthResponse = reqwest::get(format!("http://{}/ct/v1/get-sth", addr))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let sth = SignedTreeHead::from_api_response(&sth).unwrap();
+        assert_eq!(sth.tree_size, inclusion.tree_size);
+        let root = RootHash::<Sha256>::new(
+            sha2::digest::Output::<Sha256>::clone_from_slice(&sth.root_hash),
+            sth.tree_size,
+        );
+        let proof = audit_path_to_inclusion_proof::<Sha256>(&inclusion.proof).unwrap();
+        root.verify_inclusion(&leaf_hash, inclusion.leaf_index, &proof)
+            .unwrap();
+        assert!(root
+            .verify_inclusion(&leaf_hash, inclusion.leaf_index + 1, &proof)
+            .is_err());
+
+        let unknown = client
+            .get(&url)
+            .query(&[("hash", STANDARD.encode([0; 32]))])
+            .send()
+            .await
+            .unwrap();
+        assert_error(unknown, 404, "not_found").await;
+
+        // Without a hash, the log's own inclusion request as before
+        let request: serde_json::Value = reqwest::get(&url).await.unwrap().json().await.unwrap();
+        assert_eq!(request["log_id"], log_id);
+        assert!(request["key"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_raw_entry_mode() {
+        use crate::merkle_storage::serialization::audit_path_to_inclusion_proof;
+        use crate::merkle_tree::RootHash;
+        use crate::types::{
+            sct::SignedCertificateTimestamp, AddChainResponse, GetProofByHashResponse,
+            GetRawEntriesResponse, LogEntryType,
+        };
+        use p256::ecdsa::{signature::Verifier, DerSignature, VerifyingKey};
+        use p256::pkcs8::DecodePublicKey;
+        use sha2::{Digest, Sha256};
+
+        let state = shard_state(0)
+            .await
+            .with_entry_format(EntryFormat::Raw)
+            .with_max_entry_bytes(1024);
+        let verifying_key = VerifyingKey::from_public_key_der(&state.public_key_der).unwrap();
+        let log_id = state.log_id.clone();
+        let addr = serve(create_router(state)).await;
+        let client = reqwest::Client::new();
+        let url = |path: &str| format!("http://{}{}", addr, path);
+        let add_entry = |data: Vec<u8>| {
+            client
+                .post(url("/v1/add-entry"))
+                .json(&serde_json::json!({ "data": STANDARD.encode(data) }))
+                .send()
+        };
+
+        let payloads: Vec<Vec<u8>> = (0..5)
+            .map(|i| format!("release v1.{}.0", i).into_bytes())
+            .collect();
+        let mut receipts = Vec::new();
+        for payload in &payloads {
+            let response = add_entry(payload.clone()).await.unwrap();
+            assert_eq!(response.status(), reqwest::StatusCode::OK);
+            let receipt: AddChainResponse = response.json().await.unwrap();
+
+            // The receipt signs the payload and its timestamp
+            let mut sct = SignedCertificateTimestamp::new(log_id.clone(), receipt.timestamp);
+            sct.extensions = STANDARD.decode(&receipt.extensions).unwrap();
+            let signed = sct.get_signature_input(payload, LogEntryType::X509Entry, None);
+            let signature = STANDARD.decode(&receipt.signature).unwrap();
+            let signature = DerSignature::from_bytes(&signature[4..]).unwrap();
+            verifying_key.verify(&signed, &signature).unwrap();
+            receipts.push(receipt);
+        }
+
+        // Resubmitting an entry returns its original receipt
+        let again: AddChainResponse = add_entry(payloads[2].clone())
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(again.signature, receipts[2].signature);
+
+        assert_error(
+            add_entry(vec![0; 1025]).await.unwrap(),
+            400,
+            "entry_too_large",
+        )
+        .await;
+        assert_error(add_entry(Vec::new()).await.unwrap(), 400, "bad_request").await;
+
+        let entries: GetRawEntriesResponse = reqwest::get(url("/v1/get-entries?start=0&end=99"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(entries.entries.len(), payloads.len());
+
+        let sth: SthResponse = reqwest::get(url("/ct/v1/get-sth"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let sth = SignedTreeHead::from_api_response(&sth).unwrap();
+        assert_eq!(sth.tree_size, payloads.len() as u64);
+        let root = RootHash::<Sha256>::new(
+            sha2::digest::Output::<Sha256>::clone_from_slice(&sth.root_hash),
+            sth.tree_size,
+        );
+
+        for (index, entry) in entries.entries.iter().enumerate() {
+            // Entries submitted one at a time are logged in order
+            assert_eq!(STANDARD.decode(&entry.data).unwrap(), payloads[index]);
+            assert_eq!(entry.timestamp, receipts[index].timestamp);
+
+            let leaf_input = STANDARD.decode(&entry.leaf_input).unwrap();
+            let leaf_hash = Sha256::new_with_prefix([0u8])
+                .chain_update(&leaf_input)
+                .finalize();
+            let proof: GetProofByHashResponse = client
+                .get(url("/ct/v1/get-proof-by-hash"))
+                .query(&[
+                    ("hash", STANDARD.encode(leaf_hash)),
+                    ("tree_size", sth.tree_size.to_string()),
+                ])
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            assert_eq!(proof.leaf_index, index as u64);
+            let proof = audit_path_to_inclusion_proof::<Sha256>(&proof.audit_path).unwrap();
+            root.verify_inclusion(&leaf_hash, index as u64, &proof)
+                .unwrap();
+        }
+
+        // Endpoints that serve certificates are not routed in raw mode
+        for path in [
+            "/ct/v1/get-roots",
+            "/ct/v1/get-entries?start=0&end=0",
+            "/ct/v1/get-entry-and-proof?leaf_index=0&tree_size=1",
+            "/ct-pages/v1/discover",
+        ] {
+            assert_eq!(reqwest::get(url(path)).await.unwrap().status(), 404);
+        }
+        let add_chain = client
+            .post(url("/ct/v1/add-chain"))
+            .json(&serde_json::json!({ "chain": [] }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(add_chain.status(), 404);
+
+        // and raw endpoints are not routed in X.509 mode
+        let x509 = serve(create_router(shard_state(0).await)).await;
+        let add_entry = client
+            .post(format!("http://{}/v1/add-entry", x509))
+            .json(&serde_json::json!({ "data": "AA==" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(add_entry.status(), 404);
+    }
+
+    #[tokio::test]
+    async fn test_raw_body_limit_follows_max_entry_bytes() {
+        let max_entry_bytes = 3 * 1024 * 1024;
+        let state = shard_state(0)
+            .await
+            .with_entry_format(EntryFormat::Raw)
+            .with_max_entry_bytes(max_entry_bytes);
+        let addr = serve(create_router(state)).await;
+        let client = reqwest::Client::new();
+        let add_entry = |data: Vec<u8>| {
+            client
+                .post(format!("http://{}/v1/add-entry", addr))
+                .json(&serde_json::json!({ "data": STANDARD.encode(data) }))
+                .send()
+        };
+
+        // Well past axum's default body limit once base64 encoded
+        let response = add_entry(vec![7; max_entry_bytes]).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        assert_error(
+            add_entry(vec![7; max_entry_bytes + 1]).await.unwrap(),
+            400,
+            "entry_too_large",
+        )
+        .await;
+
+        let capped = shard_state(0).await.with_max_entry_bytes(usize::MAX);
+        assert_eq!(capped.max_entry_bytes, MAX_ENTRY_BYTES_LIMIT);
+    }
+
+    #[tokio::test]
+    async fn test_add_chain_rejections_by_check() {
+        use crate::validation::{rfc6962_validator::tests::two_intermediate_chain, ValidationPool};
+        use base64::{engine::general_purpose::STANDARD, Engine as _};
+        use tokio::sync::RwLock;
+
+        let (validator, chain) = two_intermediate_chain();
+        let mut state = isolated_state(0).await;
+        state.validator = Some(Arc::new(RwLock::new(validator)));
+        state.validation_pool = Some(Arc::new(ValidationPool::new(1, 1).unwrap()));
+        let addr = serve(create_router(state)).await;
+        let client = reqwest::Client::new();
+        let url = format!("http://{}/ct/v1/add-chain", addr);
+
+        let unordered = [&chain[0], &chain[2], &chain[1]];
+        let submissions = [
+            unordered.map(|cert| STANDARD.encode(cert)).to_vec(),
+            unordered.map(|cert| STANDARD.encode(cert)).to_vec(),
+            vec![
+                STANDARD.encode(&chain[0]),
+                STANDARD.encode(b"not a certificate"),
+            ],
+        ];
+        for submission in submissions {
+            let response = client
+                .post(&url)
+                .json(&serde_json::json!({ "chain": submission }))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+
+        let metrics = scrape(format!("http://{}/metrics", addr)).await;
+        assert!(metrics.contains("ct_add_chain_rejections_total{reason=\"unordered_chain\"} 2\n"));
+        assert!(metrics.contains("ct_add_chain_rejections_total{reason=\"malformed\"} 1\n"));
+        assert!(!metrics.contains("ct_add_chain_rejections_total{reason=\"untrusted_root\"}"));
+    }
+
+    #[tokio::test]
+    async fn test_error_codes_by_scenario() {
+        use crate::validation::{rfc6962_validator::tests::two_intermediate_chain, ValidationPool};
+        use der::{Decode, Encode};
+
+        let (validator, chain) = two_intermediate_chain();
+        let mut state = isolated_state(2).await;
+        state.validator = Some(Arc::new(RwLock::new(validator)));
+        state.validation_pool = Some(Arc::new(ValidationPool::new(1, 1).unwrap()));
+        let addr = serve(create_router(state)).await;
+        let client = reqwest::Client::new();
+        let url = |path: &str| format!("http://{}{}", addr, path);
+
+        let mut leaf = x509_cert::Certificate::from_der(&chain[0]).unwrap();
+        let mut signature = leaf.signature.raw_bytes().to_vec();
+        *signature.last_mut().unwrap() ^= 1;
+        leaf.signature = der::asn1::BitString::from_bytes(&signature).unwrap();
+        let bad_signature = [leaf.to_der().unwrap(), chain[1].clone(), chain[2].clone()];
+
+        let encode = |chain: &[Vec<u8>]| -> Vec<String> {
+            chain.iter().map(|cert| STANDARD.encode(cert)).collect()
+        };
+        let submissions = [
+            (
+                "add-chain",
+                vec!["AAAA".to_string()],
+                "malformed_certificate",
+            ),
+            (
+                "add-pre-chain",
+                vec!["AAAA".to_string()],
+                "malformed_certificate",
+            ),
+            ("add-chain", vec![], "bad_request"),
+            ("add-chain", vec!["!!".to_string()], "bad_request"),
+            ("add-chain", encode(&chain[..1]), "untrusted_root"),
+            ("add-chain", encode(&bad_signature), "bad_signature"),
+            (
+                "add-chain",
+                encode(&[chain[0].clone(), chain[2].clone(), chain[1].clone()]),
+                "unordered_chain",
+            ),
+            (
+                "add-chain",
+                encode(&vec![chain[0].clone(); 11]),
+                "chain_too_long",
+            ),
+        ];
+        for (endpoint, submission, code) in submissions {
+            let response = client
+                .post(url(&format!("/ct/v1/{}", endpoint)))
+                .json(&serde_json::json!({ "chain": submission }))
+                .send()
+                .await
+                .unwrap();
+            assert_error(response, 400, code).await;
+        }
+
+        for (path, status, code) in [
+            (
+                "/ct/v1/get-sth-consistency?first=2&second=1",
+                400,
+                "bad_request",
+            ),
+            (
+                "/ct/v1/get-sth-consistency?first=1&second=5",
+                400,
+                "bad_request",
+            ),
+            ("/ct/v1/get-entries?start=1&end=0", 400, "bad_request"),
+            (
+                "/ct/v1/get-entry-and-proof?leaf_index=5&tree_size=2",
+                400,
+                "bad_request",
+            ),
+            ("/no-such-endpoint", 404, "not_found"),
+        ] {
+            let response = client.get(url(path)).send().await.unwrap();
+            assert_error(response, status, code).await;
+        }
+
+        let metrics = scrape(url("/metrics")).await;
+        for (code, count) in [
+            ("malformed_certificate", 2),
+            ("bad_request", 6),
+            ("untrusted_root", 1),
+            ("bad_signature", 1),
+            ("unordered_chain", 1),
+            ("chain_too_long", 1),
+            ("not_found", 1),
+        ] {
+            let sample = format!("ct_http_errors_total{{code=\"{}\"}} {}\n", code, count);
+            assert!(metrics.contains(&sample), "missing {}", sample);
+        }
+    }
+
+    fn token_line(principal: &str, token: &str) -> String {
+        use sha2::{Digest, Sha256};
+        format!("{} {}\n", principal, hex::encode(Sha256::digest(token)))
+    }
+
+    #[tokio::test]
+    async fn test_bearer_submission_auth() {
+        let tokens_file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(tokens_file.path(), token_line("alice", "first-token")).unwrap();
+        let state = isolated_state(2)
+            .await
+            .with_submission_auth(SubmissionAuth::bearer_tokens(tokens_file.path()).unwrap());
+        let storage = state.storage.clone();
+        let routers = create_routers(state);
+        let addr = serve(routers.log).await;
+        let admin_addr = serve(routers.admin).await;
+        let client = reqwest::Client::new();
+        let url = |path: &str| format!("http://{}{}", addr, path);
+        let reload = || {
+            client
+                .post(format!("http://{}/admin/reload", admin_addr))
+                .send()
+        };
+
+        // The public listener never serves /admin
+        let response = client.post(url("/admin/reload")).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Reads need no credential
+        for path in ["/ct/v1/get-sth", "/ct/v1/get-entries?start=0&end=1"] {
+            let response = client.get(url(path)).send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{}", path);
+        }
+
+        let submit = |serial: u8, token: Option<&'static str>| {
+            let cert = crate::test_utils::utils::create_test_certificate_with_serial(serial);
+            let request = client
+                .post(url("/ct/v1/add-chain"))
+                .json(&serde_json::json!({ "chain": [STANDARD.encode(&cert)] }));
+            match token {
+                Some(token) => request.bearer_auth(token),
+                None => request,
+            }
+            .send()
+        };
+
+        let response = submit(10, None).await.unwrap();
+        assert_eq!(response.headers()[header::WWW_AUTHENTICATE], "Bearer");
+        assert_error(response, 401, "unauthorized").await;
+        assert_error(submit(10, Some("guess")).await.unwrap(), 403, "forbidden").await;
+        let response = client
+            .post(url("/ct/v1/add-pre-chain"))
+            .json(&serde_json::json!({ "chain": [] }))
+            .send()
+            .await
+            .unwrap();
+        assert_error(response, 401, "unauthorized").await;
+
+        assert_eq!(submit(10, Some("first-token")).await.unwrap().status(), 200);
+        assert_eq!(
+            storage.get_submitter(2).await.unwrap().as_deref(),
+            Some("alice")
+        );
+        assert_eq!(storage.get_submitter(0).await.unwrap(), None);
+
+        // Rotate: the new token works as soon as the file is reloaded, and the
+        // old one stops working
+        std::fs::write(tokens_file.path(), token_line("bob", "second-token")).unwrap();
+        let response = reload().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let reloaded: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(reloaded["submission_tokens"], 1);
+
+        assert_error(
+            submit(11, Some("first-token")).await.unwrap(),
+            403,
+            "forbidden",
+        )
+        .await;
+        assert_eq!(
+            submit(11, Some("second-token")).await.unwrap().status(),
+            200
+        );
+        assert_eq!(
+            storage.get_submitter(3).await.unwrap().as_deref(),
+            Some("bob")
+        );
+
+        // A broken file leaves the tokens in use
+        std::fs::write(tokens_file.path(), "bob not-a-hash\n").unwrap();
+        let response = reload().await.unwrap();
+        assert_error(response, 500, "internal").await;
+        assert_eq!(
+            submit(12, Some("second-token")).await.unwrap().status(),
+            200
+        );
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate_submission_auth() {
+        use der::Decode;
+        use submission_auth::ClientCertificate;
+
+        let state = isolated_state(0)
+            .await
+            .with_submission_auth(SubmissionAuth::client_certificate());
+        let storage = state.storage.clone();
+        let routers = create_routers(state);
+        let router = routers.log;
+        let admin_addr = serve(routers.admin).await;
+        // Stands in for the TLS listener, which attaches the verified certificate
+        let client_cert = crate::test_utils::utils::create_test_certificate_with_serial(200);
+        let with_cert = serve(
+            router
+                .clone()
+                .layer(axum::Extension(ClientCertificate(client_cert.clone()))),
+        )
+        .await;
+        let without_cert = serve(router).await;
+        let client = reqwest::Client::new();
+
+        let submit = |addr: SocketAddr| {
+            let cert = crate::test_utils::utils::create_test_certificate_with_serial(20);
+            client
+                .post(format!("http://{}/ct/v1/add-chain", addr))
+                .json(&serde_json::json!({ "chain": [STANDARD.encode(&cert)] }))
+                .send()
+        };
+
+        assert_error(submit(without_cert).await.unwrap(), 401, "unauthorized").await;
+        let response = reqwest::get(format!("http://{}/ct/v1/get-sth", without_cert))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        assert_eq!(submit(with_cert).await.unwrap().status(), 200);
+        let subject = x509_cert::Certificate::from_der(&client_cert)
+            .unwrap()
+            .tbs_certificate
+            .subject
+            .to_string();
+        assert_eq!(storage.get_submitter(0).await.unwrap(), Some(subject));
+
+        // Nothing to reload without token authentication
+        let response = client
+            .post(format!("http://{}/admin/reload", admin_addr))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_add_chains_share_batches() {
+        use crate::types::{sct::SignedCertificateTimestamp, AddChainResponse, LogEntryType};
+        use p256::ecdsa::{signature::Verifier, DerSignature, VerifyingKey};
+        use p256::pkcs8::DecodePublicKey;
+
+        const SUBMISSIONS: u8 = 100;
+
+        let state = isolated_state(5).await;
+        let tree = state.merkle_tree.clone();
+        let metrics = state.metrics.clone();
+        let log_id = state.log_id.clone();
+        let verifying_key = VerifyingKey::from_public_key_der(&state.public_key_der).unwrap();
+        let addr = serve(create_router(state)).await;
+        let client = reqwest::Client::new();
+        let url = format!("http://{}/ct/v1/add-chain", addr);
+
+        let submissions = (0..SUBMISSIONS).map(|serial| {
+            let cert = crate::test_utils::utils::create_test_certificate_with_serial(serial);
+            let request = client
+                .post(&url)
+                .json(&serde_json::json!({ "chain": [STANDARD.encode(&cert)] }));
+            async move {
+                let sent = chrono::Utc::now().timestamp_millis() as u64;
+                let response = request.send().await.unwrap();
+                let answered = chrono::Utc::now().timestamp_millis() as u64;
+                assert_eq!(response.status(), StatusCode::OK);
+                let sct: AddChainResponse = response.json().await.unwrap();
+                (cert, sct, sent..=answered)
+            }
+        });
+        let responses = futures::future::join_all(submissions).await;
+
+        for (cert, response, window) in responses {
+            // Timestamped on arrival, before the batch holding it was committed
+            assert!(window.contains(&response.timestamp));
+            let sct = SignedCertificateTimestamp {
+                timestamp: response.timestamp,
+                extensions: STANDARD.decode(&response.extensions).unwrap(),
+                ..SignedCertificateTimestamp::new(log_id.clone(), 0)
+            };
+            let signature = STANDARD.decode(&response.signature).unwrap();
+            let signature = DerSignature::from_bytes(&signature[4..]).unwrap();
+            verifying_key
+                .verify(
+                    &sct.get_signature_input(&cert, LogEntryType::X509Entry, None),
+                    &signature,
+                )
+                .unwrap();
+        }
+
+        assert_eq!(tree.size().await.unwrap(), 5 + SUBMISSIONS as u64);
+        let batches = metrics
+            .storage_batches_flushed
+            .with_label_values(&["success"])
+            .get();
+        assert!(batches < SUBMISSIONS as u64, "{} batches", batches);
+    }
+}
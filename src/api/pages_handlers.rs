@@ -1,6 +1,6 @@
-use crate::api::{ApiState, ErrorResponse};
+use crate::api::{api_error, ApiState, ErrorCode, ErrorResponse};
 use crate::types::pages::{base64url_to_hash, DiscoveryResponse, EntriesPage};
-use crate::types::DeduplicatedLogEntry;
+use crate::types::{merkle_leaf::MerkleTreeLeaf, DeduplicatedLogEntry};
 use axum::extract::{Path, State};
 use axum::http::{header, HeaderMap, StatusCode};
 use axum::response::{IntoResponse, Json, Response};
@@ -26,25 +26,21 @@ pub async fn get_page(
 
     // Get current tree size
     let tree_size = state.merkle_tree.size().await.map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: format!("Failed to get tree size: {}", e),
-            }),
+        api_error(
+            ErrorCode::Internal,
+            format!("Failed to get tree size: {}", e),
         )
     })?;
 
     if start_index >= tree_size {
-        return Err((
-            StatusCode::NOT_FOUND,
-            Json(ErrorResponse {
-                error: format!(
-                    "Page not found. Page number: {}, tree size: {}, max page: {}",
-                    page_number,
-                    tree_size,
-                    tree_size / PAGE_SIZE
-                ),
-            }),
+        return Err(api_error(
+            ErrorCode::NotFound,
+            format!(
+                "Page not found. Page number: {}, tree size: {}, max page: {}",
+                page_number,
+                tree_size,
+                tree_size / PAGE_SIZE
+            ),
         ));
     }
 
@@ -61,33 +57,25 @@ pub async fn get_page(
             .get_deduplicated_entry(index)
             .await
             .map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ErrorResponse {
-                        error: format!("Failed to retrieve entry: {}", e),
-                    }),
+                api_error(
+                    ErrorCode::Internal,
+                    format!("Failed to retrieve entry: {}", e),
                 )
             })?;
 
         if let Some(entry) = entry {
             // Serialize the TimestampedEntry according to RFC 6962
-            let timestamped_entry = serialize_timestamped_entry(&entry).map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ErrorResponse { error: e }),
-                )
-            })?;
+            let timestamped_entry = serialize_timestamped_entry(&entry)
+                .map_err(|e| api_error(ErrorCode::Internal, e))?;
 
             // Convert chain hashes to the expected format
             let issuer_hashes = entry.chain_hashes.clone().unwrap_or_default();
 
             page.add_entry(timestamped_entry, issuer_hashes);
         } else {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: format!("Missing entry in log at index: {}", index),
-                }),
+            return Err(api_error(
+                ErrorCode::Internal,
+                format!("Missing entry in log at index: {}", index),
             ));
         }
     }
@@ -130,21 +118,17 @@ pub async fn get_certificate(
 ) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
     // Decode the base64url hash
     let hash = base64url_to_hash(&hash_str).map_err(|e| {
-        (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: format!("Invalid certificate hash: {}", e),
-            }),
+        api_error(
+            ErrorCode::BadRequest,
+            format!("Invalid certificate hash: {}", e),
         )
     })?;
 
     // Retrieve the certificate from storage
     let cert_data = state.storage.get_certificate(&hash).await.map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse {
-                error: format!("Failed to retrieve certificate: {}", e),
-            }),
+        api_error(
+            ErrorCode::Internal,
+            format!("Failed to retrieve certificate: {}", e),
         )
     })?;
 
@@ -161,32 +145,17 @@ pub async fn get_certificate(
 
         Ok((headers, cert_data).into_response())
     } else {
-        Err((
-            StatusCode::NOT_FOUND,
-            Json(ErrorResponse {
-                error: format!("Certificate not found for hash: {}", hash_str),
-            }),
+        Err(api_error(
+            ErrorCode::NotFound,
+            format!("Certificate not found for hash: {}", hash_str),
         ))
     }
 }
 
 fn serialize_timestamped_entry(entry: &DeduplicatedLogEntry) -> Result<Vec<u8>, String> {
-    // The DeduplicatedLogEntry already contains the serialized MerkleTreeLeaf data
-    // which is the TimestampedEntry in RFC 6962 format
-    // We need to extract just the TimestampedEntry part (skip the MerkleTreeLeaf version and type)
-
-    // MerkleTreeLeaf structure:
-    // - Version (1 byte) - always 0
-    // - LeafType (1 byte) - always 0 (timestamped_entry)
-    // - TimestampedEntry (rest of the data)
-
-    if entry.leaf_data.len() < 2 {
-        return Err(format!(
-            "Invalid leaf data: too short (length: {})",
-            entry.leaf_data.len()
-        ));
-    }
-
-    // Skip the first 2 bytes (version and leaf type) to get the TimestampedEntry
-    Ok(entry.leaf_data[2..].to_vec())
+    // The DeduplicatedLogEntry already contains the serialized MerkleTreeLeaf data;
+    // strip its version and leaf type header to get the TimestampedEntry
+    MerkleTreeLeaf::timestamped_entry_bytes(&entry.leaf_data)
+        .map(|bytes| bytes.to_vec())
+        .map_err(|e| format!("Invalid leaf data: {}", e))
 }
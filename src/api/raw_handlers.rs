@@ -0,0 +1,173 @@
+//! Endpoints of a log in [`EntryFormat::Raw`](super::EntryFormat::Raw) mode,
+//! which takes any byte string as an entry. Entries skip validation and are
+//! logged as the certificate of an X.509 leaf, so their receipts are SCTs
+//! over the entry and its timestamp, and their leaf hashes are those of
+//! RFC 6962.
+
+use super::handlers::{handle_storage_error, record_submitter, ApiResult};
+use super::submission_auth::Submitter;
+use crate::api::{api_error, ApiState, ErrorCode};
+use crate::types::{
+    sct::SignedCertificateTimestamp, AddChainResponse, AddEntryRequest, GetRawEntriesResponse,
+    LogEntry, LogEntryType, RawEntry,
+};
+use axum::extract::{Query, State};
+use axum::Json;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+/// Most entries returned by one get-entries request. Lower than for
+/// certificates since raw entries may each be up to `max_entry_bytes`.
+const MAX_RAW_ENTRIES: u64 = 256;
+
+/// Entries fetched at once while building a get-entries response
+const GET_RAW_ENTRIES_CONCURRENCY: usize = 16;
+
+pub async fn add_entry(
+    State(state): State<Arc<ApiState>>,
+    Submitter(submitter): Submitter,
+    Json(request): Json<AddEntryRequest>,
+) -> ApiResult<AddChainResponse> {
+    let submissions = &state.metrics.certificate_submissions_total;
+    let data = STANDARD.decode(&request.data).map_err(|_| {
+        submissions.with_label_values(&["raw", "failed"]).inc();
+        api_error(ErrorCode::BadRequest, "Invalid base64 in entry data")
+    })?;
+    if data.is_empty() {
+        submissions.with_label_values(&["raw", "failed"]).inc();
+        return Err(api_error(ErrorCode::BadRequest, "Entry cannot be empty"));
+    }
+    if data.len() > state.max_entry_bytes {
+        submissions.with_label_values(&["raw", "failed"]).inc();
+        return Err(api_error(
+            ErrorCode::EntryTooLarge,
+            format!(
+                "Entry of {} bytes exceeds the limit of {} bytes",
+                data.len(),
+                state.max_entry_bytes
+            ),
+        ));
+    }
+
+    let entry_hash: [u8; 32] = Sha256::digest(&data).into();
+    if let Some(existing) = state
+        .storage
+        .get_sct_by_cert_hash(&entry_hash)
+        .await
+        .map_err(|e| api_error(ErrorCode::Internal, e.to_string()))?
+    {
+        submissions
+            .with_label_values(&["raw", "deduplicated"])
+            .inc();
+        return Ok(Json(receipt_response(&existing.sct)));
+    }
+
+    let timestamp = chrono::Utc::now();
+    let timestamp_ms = timestamp.timestamp_millis() as u64;
+    let log_entry = LogEntry::new_with_timestamp(0, data.clone(), None, timestamp);
+
+    let sct_builder = state.sct_builder.clone();
+    let (index, sct) = match state
+        .storage
+        .add_entry_batched(log_entry, entry_hash, move |index| {
+            sct_builder.create_sct_with_timestamp_and_index(
+                &data,
+                LogEntryType::X509Entry,
+                None,
+                timestamp_ms,
+                Some(index),
+            )
+        })
+        .await
+    {
+        Ok(result) => result,
+        Err(e) => return Err(handle_storage_error(e).await),
+    };
+    record_submitter(&state, index, submitter).await;
+
+    submissions.with_label_values(&["raw", "success"]).inc();
+    Ok(Json(receipt_response(&sct)))
+}
+
+fn receipt_response(sct: &SignedCertificateTimestamp) -> AddChainResponse {
+    AddChainResponse {
+        sct_version: sct.version as u8,
+        id: STANDARD.encode(sct.log_id.as_bytes()),
+        timestamp: sct.timestamp,
+        extensions: STANDARD.encode(&sct.extensions),
+        signature: STANDARD.encode(&sct.signature),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetRawEntriesRequest {
+    pub start: u64,
+    pub end: u64,
+}
+
+/// Entries `start..=end`, cut at the committed tree size and at
+/// [`MAX_RAW_ENTRIES`]
+pub async fn get_entries(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<GetRawEntriesRequest>,
+) -> ApiResult<GetRawEntriesResponse> {
+    let requests = &state.metrics.get_entries_requests;
+    if params.start > params.end {
+        requests.with_label_values(&["failed"]).inc();
+        return Err(api_error(
+            ErrorCode::BadRequest,
+            "Invalid range: start > end",
+        ));
+    }
+    let internal = |e: String| {
+        requests.with_label_values(&["failed"]).inc();
+        api_error(ErrorCode::Internal, e)
+    };
+
+    let tree_size = state
+        .merkle_tree
+        .committed_size()
+        .await
+        .map_err(|e| internal(e.to_string()))?;
+    let end = tree_size
+        .min(params.end.saturating_add(1))
+        .min(params.start.saturating_add(MAX_RAW_ENTRIES));
+
+    let fetches: Vec<_> = (params.start..end.max(params.start))
+        .map(|index| {
+            let storage = state.storage.clone();
+            async move {
+                match storage.get_entry(index).await? {
+                    Some(entry) => Ok(entry),
+                    None => Err(crate::storage::StorageError::InvalidFormat(format!(
+                        "Entry {} is missing",
+                        index
+                    ))),
+                }
+            }
+        })
+        .collect();
+    let entries: Vec<LogEntry> = stream::iter(fetches)
+        .buffered(GET_RAW_ENTRIES_CONCURRENCY)
+        .try_collect()
+        .await
+        .map_err(|e| internal(e.to_string()))?;
+
+    let entries = entries
+        .iter()
+        .map(|entry| {
+            Ok(RawEntry {
+                leaf_input: STANDARD.encode(entry.serialize()?),
+                data: STANDARD.encode(&entry.certificate),
+                timestamp: entry.timestamp.timestamp_millis() as u64,
+            })
+        })
+        .collect::<crate::types::Result<Vec<_>>>()
+        .map_err(|e| internal(e.to_string()))?;
+
+    requests.with_label_values(&["success"]).inc();
+    Ok(Json(GetRawEntriesResponse { entries }))
+}
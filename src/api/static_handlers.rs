@@ -1,6 +1,5 @@
-use crate::api::{ApiState, ErrorResponse};
+use crate::api::{api_error, ApiState, ErrorCode, ErrorResponse};
 use crate::merkle_tree::compute_subtree_root;
-use crate::metrics;
 use crate::types::tiles::{parse_tile_index, DataTile, Tile, TileLeaf};
 use axum::{
     body::Body,
@@ -19,44 +18,101 @@ pub async fn get_checkpoint(
     State(state): State<Arc<ApiState>>,
 ) -> Result<Response, (StatusCode, axum::Json<ErrorResponse>)> {
     let committed_root = state.merkle_tree.committed_root().await.map_err(|e| {
-        metrics::STATIC_CT_CHECKPOINT_REQUESTS
+        state
+            .metrics
+            .static_ct_checkpoint_requests
             .with_label_values(&["error"])
             .inc();
-        (StatusCode::INTERNAL_SERVER_ERROR, axum::Json(e.into()))
+        api_error(ErrorCode::Internal, e.to_string())
     })?;
 
-    let tree_size = committed_root.num_leaves();
-    let root_hash = committed_root.as_bytes().to_vec();
+    cosigned_checkpoint_response(&state, committed_root, "max-age=5, must-revalidate").await
+}
+
+/// Get the checkpoint, with any witness cosignatures, at a previously published tree size
+pub async fn get_checkpoint_at_size(
+    State(state): State<Arc<ApiState>>,
+    Path(tree_size): Path<u64>,
+) -> Result<Response, (StatusCode, axum::Json<ErrorResponse>)> {
+    let root = state
+        .merkle_tree
+        .root_at_size(tree_size)
+        .await
+        .map_err(|e| {
+            state
+                .metrics
+                .static_ct_checkpoint_requests
+                .with_label_values(&["error"])
+                .inc();
+            match e {
+                crate::types::CtError::BadRequest(_) => {
+                    api_error(ErrorCode::NotFound, e.to_string())
+                }
+                _ => api_error(ErrorCode::Internal, e.to_string()),
+            }
+        })?;
+
+    // The note body for a given size never changes, but each response carries
+    // a fresh log signature and the cosignatures recorded so far
+    cosigned_checkpoint_response(&state, root, "max-age=60, must-revalidate").await
+}
+
+/// Sign a checkpoint for `root` and append the witness cosignatures recorded
+/// for its tree size, log signature first and cosignatures sorted by key name.
+async fn cosigned_checkpoint_response(
+    state: &ApiState,
+    root: crate::merkle_tree::RootHash<sha2::Sha256>,
+    cache_control: &'static str,
+) -> Result<Response, (StatusCode, axum::Json<ErrorResponse>)> {
+    let tree_size = root.num_leaves();
+    let root_hash = root.as_bytes().to_vec();
 
     let timestamp = chrono::Utc::now().timestamp_millis() as u64;
-    let checkpoint = state
+    let mut checkpoint = state
         .sth_builder
         .create_checkpoint(tree_size, root_hash, Some(timestamp))
         .map_err(|e| {
-            metrics::STATIC_CT_CHECKPOINT_REQUESTS
+            state
+                .metrics
+                .static_ct_checkpoint_requests
                 .with_label_values(&["error"])
                 .inc();
-            (StatusCode::INTERNAL_SERVER_ERROR, axum::Json(e.into()))
+            api_error(ErrorCode::Internal, e.to_string())
         })?;
 
+    let cosignatures = state
+        .storage
+        .get_cosignatures(tree_size)
+        .await
+        .map_err(|e| {
+            state
+                .metrics
+                .static_ct_checkpoint_requests
+                .with_label_values(&["error"])
+                .inc();
+            api_error(ErrorCode::Internal, e.to_string())
+        })?;
+    for cosignature in cosignatures {
+        checkpoint.add_signature(cosignature);
+    }
+
     let response = Response::builder()
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
-        .header(header::CACHE_CONTROL, "max-age=5, must-revalidate")
+        .header(header::CACHE_CONTROL, cache_control)
         .body(checkpoint.format())
         .map_err(|_| {
-            metrics::STATIC_CT_CHECKPOINT_REQUESTS
+            state
+                .metrics
+                .static_ct_checkpoint_requests
                 .with_label_values(&["error"])
                 .inc();
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                axum::Json(ErrorResponse {
-                    error: "Failed to build response".to_string(),
-                }),
-            )
+            api_error(ErrorCode::Internal, "Failed to build response")
         })?;
 
-    metrics::STATIC_CT_CHECKPOINT_REQUESTS
+    state
+        .metrics
+        .static_ct_checkpoint_requests
         .with_label_values(&["success"])
         .inc();
 
@@ -68,48 +124,47 @@ pub async fn get_tile(
     Path((level, index_path, width)): Path<(u8, String, Option<u16>)>,
 ) -> Result<Response, (StatusCode, axum::Json<ErrorResponse>)> {
     if level > 5 {
-        metrics::STATIC_CT_TILE_REQUESTS
+        state
+            .metrics
+            .static_ct_tile_requests
             .with_label_values(&["merkle", "error"])
             .inc();
-        return Err((
-            StatusCode::BAD_REQUEST,
-            axum::Json(ErrorResponse {
-                error: "Invalid tile level, must be 0-5".to_string(),
-            }),
+        return Err(api_error(
+            ErrorCode::BadRequest,
+            "Invalid tile level, must be 0-5",
         ));
     }
 
     let tile_index = parse_tile_index(&index_path).map_err(|e| {
-        metrics::STATIC_CT_TILE_REQUESTS
+        state
+            .metrics
+            .static_ct_tile_requests
             .with_label_values(&["merkle", "error"])
             .inc();
-        (
-            StatusCode::BAD_REQUEST,
-            axum::Json(ErrorResponse {
-                error: format!("Invalid tile index: {}", e),
-            }),
-        )
+        api_error(ErrorCode::BadRequest, format!("Invalid tile index: {}", e))
     })?;
 
     if let Some(w) = width {
         if w == 0 || w > 256 {
-            metrics::STATIC_CT_TILE_REQUESTS
+            state
+                .metrics
+                .static_ct_tile_requests
                 .with_label_values(&["merkle", "error"])
                 .inc();
-            return Err((
-                StatusCode::BAD_REQUEST,
-                axum::Json(ErrorResponse {
-                    error: "Invalid tile width, must be 1-256".to_string(),
-                }),
+            return Err(api_error(
+                ErrorCode::BadRequest,
+                "Invalid tile width, must be 1-256",
             ));
         }
     }
 
     let committed_root = state.merkle_tree.committed_root().await.map_err(|e| {
-        metrics::STATIC_CT_TILE_REQUESTS
+        state
+            .metrics
+            .static_ct_tile_requests
             .with_label_values(&["merkle", "error"])
             .inc();
-        (StatusCode::INTERNAL_SERVER_ERROR, axum::Json(e.into()))
+        api_error(ErrorCode::Internal, e.to_string())
     })?;
     let tree_size = committed_root.num_leaves();
 
@@ -125,7 +180,9 @@ pub async fn get_tile(
         let tile = generate_merkle_tile(&state, level, tile_index, tree_size, width)
             .await
             .inspect_err(|_| {
-                metrics::STATIC_CT_TILE_REQUESTS
+                state
+                    .metrics
+                    .static_ct_tile_requests
                     .with_label_values(&["merkle", "error"])
                     .inc();
             })?;
@@ -149,7 +206,9 @@ pub async fn get_tile(
                 let tile = generate_merkle_tile(&state, level, tile_index, tree_size, None)
                     .await
                     .inspect_err(|_| {
-                        metrics::STATIC_CT_TILE_REQUESTS
+                        state
+                            .metrics
+                            .static_ct_tile_requests
                             .with_label_values(&["merkle", "error"])
                             .inc();
                     })?;
@@ -157,15 +216,12 @@ pub async fn get_tile(
             }
             Err(e) => {
                 tracing::error!("Failed to get precomputed tile: {}", e);
-                metrics::STATIC_CT_TILE_REQUESTS
+                state
+                    .metrics
+                    .static_ct_tile_requests
                     .with_label_values(&["merkle", "error"])
                     .inc();
-                return Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    axum::Json(ErrorResponse {
-                        error: "Failed to retrieve tile".to_string(),
-                    }),
-                ));
+                return Err(api_error(ErrorCode::Internal, "Failed to retrieve tile"));
             }
         }
     };
@@ -178,7 +234,9 @@ pub async fn get_tile(
         );
     }
 
-    metrics::STATIC_CT_TILE_SIZE_BYTES
+    state
+        .metrics
+        .static_ct_tile_size_bytes
         .with_label_values(&["merkle"])
         .observe(tile_data.len() as f64);
 
@@ -188,18 +246,17 @@ pub async fn get_tile(
         .header(header::CACHE_CONTROL, "max-age=31536000, immutable")
         .body(Body::from(tile_data))
         .map_err(|_| {
-            metrics::STATIC_CT_TILE_REQUESTS
+            state
+                .metrics
+                .static_ct_tile_requests
                 .with_label_values(&["merkle", "error"])
                 .inc();
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                axum::Json(ErrorResponse {
-                    error: "Failed to build response".to_string(),
-                }),
-            )
+            api_error(ErrorCode::Internal, "Failed to build response")
         })?;
 
-    metrics::STATIC_CT_TILE_REQUESTS
+    state
+        .metrics
+        .static_ct_tile_requests
         .with_label_values(&["merkle", "success"])
         .inc();
 
@@ -224,12 +281,7 @@ async fn generate_merkle_tile(
     let end_position = std::cmp::min(start_position + entries_per_tile, total_positions);
 
     if start_position >= total_positions {
-        return Err((
-            StatusCode::NOT_FOUND,
-            axum::Json(ErrorResponse {
-                error: "Tile not found".to_string(),
-            }),
-        ));
+        return Err(api_error(ErrorCode::NotFound, "Tile not found"));
     }
 
     let max_hashes = if let Some(w) = requested_width {
@@ -264,23 +316,16 @@ async fn generate_merkle_tile(
         .collect();
 
     let results = futures::future::try_join_all(futures).await.map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            axum::Json(ErrorResponse {
-                error: format!("Failed to get node hash: {}", e),
-            }),
+        api_error(
+            ErrorCode::Internal,
+            format!("Failed to get node hash: {}", e),
         )
     })?;
 
     let hashes: Vec<[u8; 32]> = results.into_iter().flatten().collect();
 
     if hashes.is_empty() {
-        return Err((
-            StatusCode::NOT_FOUND,
-            axum::Json(ErrorResponse {
-                error: "Tile not found".to_string(),
-            }),
-        ));
+        return Err(api_error(ErrorCode::NotFound, "Tile not found"));
     }
 
     Ok(Tile::new(hashes))
@@ -291,43 +336,44 @@ pub async fn get_data_tile(
     Path((index_path, width)): Path<(String, Option<u16>)>,
 ) -> Result<Response, (StatusCode, axum::Json<ErrorResponse>)> {
     let tile_index = parse_tile_index(&index_path).map_err(|e| {
-        metrics::STATIC_CT_TILE_REQUESTS
+        state
+            .metrics
+            .static_ct_tile_requests
             .with_label_values(&["data", "error"])
             .inc();
-        (
-            StatusCode::BAD_REQUEST,
-            axum::Json(ErrorResponse {
-                error: format!("Invalid tile index: {}", e),
-            }),
-        )
+        api_error(ErrorCode::BadRequest, format!("Invalid tile index: {}", e))
     })?;
 
     if let Some(w) = width {
         if w == 0 || w > 256 {
-            metrics::STATIC_CT_TILE_REQUESTS
+            state
+                .metrics
+                .static_ct_tile_requests
                 .with_label_values(&["data", "error"])
                 .inc();
-            return Err((
-                StatusCode::BAD_REQUEST,
-                axum::Json(ErrorResponse {
-                    error: "Invalid tile width, must be 1-256".to_string(),
-                }),
+            return Err(api_error(
+                ErrorCode::BadRequest,
+                "Invalid tile width, must be 1-256",
             ));
         }
     }
 
     let committed_root = state.merkle_tree.committed_root().await.map_err(|e| {
-        metrics::STATIC_CT_TILE_REQUESTS
+        state
+            .metrics
+            .static_ct_tile_requests
             .with_label_values(&["data", "error"])
             .inc();
-        (StatusCode::INTERNAL_SERVER_ERROR, axum::Json(e.into()))
+        api_error(ErrorCode::Internal, e.to_string())
     })?;
     let tree_size = committed_root.num_leaves();
 
     let data_tile = generate_data_tile(&state, tile_index, tree_size, width)
         .await
         .inspect_err(|_| {
-            metrics::STATIC_CT_TILE_REQUESTS
+            state
+                .metrics
+                .static_ct_tile_requests
                 .with_label_values(&["data", "error"])
                 .inc();
         })?;
@@ -335,29 +381,25 @@ pub async fn get_data_tile(
     // Always compress data tiles with gzip as per spec
     let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
     encoder.write_all(&data_tile.data).map_err(|_| {
-        metrics::STATIC_CT_TILE_REQUESTS
+        state
+            .metrics
+            .static_ct_tile_requests
             .with_label_values(&["data", "error"])
             .inc();
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            axum::Json(ErrorResponse {
-                error: "Failed to compress data tile".to_string(),
-            }),
-        )
+        api_error(ErrorCode::Internal, "Failed to compress data tile")
     })?;
     let compressed = encoder.finish().map_err(|_| {
-        metrics::STATIC_CT_TILE_REQUESTS
+        state
+            .metrics
+            .static_ct_tile_requests
             .with_label_values(&["data", "error"])
             .inc();
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            axum::Json(ErrorResponse {
-                error: "Failed to finish compression".to_string(),
-            }),
-        )
+        api_error(ErrorCode::Internal, "Failed to finish compression")
     })?;
 
-    metrics::STATIC_CT_TILE_SIZE_BYTES
+    state
+        .metrics
+        .static_ct_tile_size_bytes
         .with_label_values(&["data"])
         .observe(compressed.len() as f64);
 
@@ -368,18 +410,17 @@ pub async fn get_data_tile(
         .header(header::CACHE_CONTROL, "max-age=31536000, immutable")
         .body(Body::from(compressed))
         .map_err(|_| {
-            metrics::STATIC_CT_TILE_REQUESTS
+            state
+                .metrics
+                .static_ct_tile_requests
                 .with_label_values(&["data", "error"])
                 .inc();
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                axum::Json(ErrorResponse {
-                    error: "Failed to build response".to_string(),
-                }),
-            )
+            api_error(ErrorCode::Internal, "Failed to build response")
         })?;
 
-    metrics::STATIC_CT_TILE_REQUESTS
+    state
+        .metrics
+        .static_ct_tile_requests
         .with_label_values(&["data", "success"])
         .inc();
 
@@ -441,23 +482,16 @@ async fn generate_data_tile(
         .collect();
 
     let results = futures::future::try_join_all(futures).await.map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            axum::Json(ErrorResponse {
-                error: format!("Failed to get entry data: {}", e),
-            }),
+        api_error(
+            ErrorCode::Internal,
+            format!("Failed to get entry data: {}", e),
         )
     })?;
 
     let leaf_bytes_vec: Vec<Vec<u8>> = results.into_iter().flatten().collect();
 
     if leaf_bytes_vec.is_empty() {
-        return Err((
-            StatusCode::NOT_FOUND,
-            axum::Json(ErrorResponse {
-                error: "Data tile not found".to_string(),
-            }),
-        ));
+        return Err(api_error(ErrorCode::NotFound, "Data tile not found"));
     }
 
     let total_size = leaf_bytes_vec.iter().map(|v| v.len()).sum();
@@ -474,26 +508,26 @@ pub async fn get_issuer(
     Path(fingerprint): Path<String>,
 ) -> Result<Response, (StatusCode, axum::Json<ErrorResponse>)> {
     let fingerprint_bytes = hex::decode(&fingerprint).map_err(|_| {
-        metrics::STATIC_CT_ISSUER_REQUESTS
+        state
+            .metrics
+            .static_ct_issuer_requests
             .with_label_values(&["error"])
             .inc();
-        (
-            StatusCode::BAD_REQUEST,
-            axum::Json(ErrorResponse {
-                error: "Invalid fingerprint format, must be hex-encoded".to_string(),
-            }),
+        api_error(
+            ErrorCode::BadRequest,
+            "Invalid fingerprint format, must be hex-encoded",
         )
     })?;
 
     if fingerprint_bytes.len() != 32 {
-        metrics::STATIC_CT_ISSUER_REQUESTS
+        state
+            .metrics
+            .static_ct_issuer_requests
             .with_label_values(&["error"])
             .inc();
-        return Err((
-            StatusCode::BAD_REQUEST,
-            axum::Json(ErrorResponse {
-                error: "Invalid fingerprint length, must be 32 bytes (64 hex chars)".to_string(),
-            }),
+        return Err(api_error(
+            ErrorCode::BadRequest,
+            "Invalid fingerprint length, must be 32 bytes (64 hex chars)",
         ));
     }
 
@@ -505,26 +539,23 @@ pub async fn get_issuer(
         .get_certificate(&fingerprint_array)
         .await
         .map_err(|e| {
-            metrics::STATIC_CT_ISSUER_REQUESTS
+            state
+                .metrics
+                .static_ct_issuer_requests
                 .with_label_values(&["error"])
                 .inc();
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                axum::Json(ErrorResponse {
-                    error: format!("Failed to get certificate: {}", e),
-                }),
+            api_error(
+                ErrorCode::Internal,
+                format!("Failed to get certificate: {}", e),
             )
         })?
         .ok_or_else(|| {
-            metrics::STATIC_CT_ISSUER_REQUESTS
+            state
+                .metrics
+                .static_ct_issuer_requests
                 .with_label_values(&["not_found"])
                 .inc();
-            (
-                StatusCode::NOT_FOUND,
-                axum::Json(ErrorResponse {
-                    error: "Certificate not found".to_string(),
-                }),
-            )
+            api_error(ErrorCode::NotFound, "Certificate not found")
         })?;
 
     let response = Response::builder()
@@ -533,18 +564,17 @@ pub async fn get_issuer(
         .header(header::CACHE_CONTROL, "max-age=31536000, immutable")
         .body(Body::from(certificate))
         .map_err(|_| {
-            metrics::STATIC_CT_ISSUER_REQUESTS
+            state
+                .metrics
+                .static_ct_issuer_requests
                 .with_label_values(&["error"])
                 .inc();
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                axum::Json(ErrorResponse {
-                    error: "Failed to build response".to_string(),
-                }),
-            )
+            api_error(ErrorCode::Internal, "Failed to build response")
         })?;
 
-    metrics::STATIC_CT_ISSUER_REQUESTS
+    state
+        .metrics
+        .static_ct_issuer_requests
         .with_label_values(&["success"])
         .inc();
 
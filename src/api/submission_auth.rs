@@ -0,0 +1,252 @@
+//! Optional authentication of submissions, for private logs. Reads stay
+//! public; only the submission routes pass through [`require_submitter`],
+//! which turns away unauthenticated requests and tells the handlers who
+//! submitted, so the principal can be recorded with the entry.
+//!
+//! Two modes are supported:
+//! - Bearer tokens, listed by SHA-256 in a file that can be reloaded without
+//!   a restart. Each line is `<principal> <hex SHA-256 of the token>`.
+//! - Client certificates, verified against the allowed client CAs during the
+//!   TLS handshake. The listener hands the verified leaf over as a
+//!   [`ClientCertificate`] extension and its subject becomes the principal.
+
+use axum::{
+    body::Body,
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use x509_cert::der::Decode;
+
+use super::{api_error, ErrorCode, ErrorResponse};
+use crate::types::{CtError, Result};
+
+/// DER of the client certificate the TLS listener verified for a connection
+#[derive(Debug, Clone)]
+pub struct ClientCertificate(pub Vec<u8>);
+
+/// Principal that submitted a request, when submissions are authenticated
+#[derive(Debug, Clone, Default)]
+pub struct Submitter(pub Option<String>);
+
+impl<S: Send + Sync> FromRequestParts<S> for Submitter {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<Submitter>()
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+/// How submitters prove who they are
+#[derive(Debug, Clone)]
+pub enum SubmissionAuthMode {
+    /// `Authorization: Bearer` tokens listed in `tokens_file`
+    BearerTokens { tokens_file: PathBuf },
+    /// Client certificates checked by the TLS listener
+    ClientCertificate,
+}
+
+pub struct SubmissionAuth {
+    mode: SubmissionAuthMode,
+    /// Principal by SHA-256 of its token
+    tokens: RwLock<HashMap<[u8; 32], String>>,
+}
+
+impl SubmissionAuth {
+    /// Bearer token authentication with the tokens listed in `tokens_file`
+    pub fn bearer_tokens(tokens_file: impl Into<PathBuf>) -> Result<Self> {
+        let auth = Self {
+            mode: SubmissionAuthMode::BearerTokens {
+                tokens_file: tokens_file.into(),
+            },
+            tokens: RwLock::new(HashMap::new()),
+        };
+        auth.reload()?;
+        Ok(auth)
+    }
+
+    /// Client certificate authentication. The client CAs are enforced by the
+    /// TLS listener, so requests without a verified certificate are refused.
+    pub fn client_certificate() -> Self {
+        Self {
+            mode: SubmissionAuthMode::ClientCertificate,
+            tokens: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn mode(&self) -> &SubmissionAuthMode {
+        &self.mode
+    }
+
+    /// Re-read the token file, so tokens can be rotated without a restart.
+    /// On error the tokens in use are kept. Returns the number of tokens.
+    pub fn reload(&self) -> Result<usize> {
+        let SubmissionAuthMode::BearerTokens { tokens_file } = &self.mode else {
+            return Ok(0);
+        };
+        let contents = std::fs::read_to_string(tokens_file).map_err(|e| {
+            CtError::Internal(format!(
+                "Failed to read submission tokens from {}: {}",
+                tokens_file.display(),
+                e
+            ))
+        })?;
+        let tokens = parse_tokens(&contents)?;
+        let count = tokens.len();
+        *self.tokens.write().unwrap() = tokens;
+        Ok(count)
+    }
+
+    /// The principal behind a request, or the response refusing it: 401 when
+    /// no credential was presented, 403 when it is not recognised
+    fn authenticate(
+        &self,
+        request: &Request<Body>,
+    ) -> std::result::Result<String, (StatusCode, Json<ErrorResponse>)> {
+        match &self.mode {
+            SubmissionAuthMode::BearerTokens { .. } => {
+                let token = bearer_token(request.headers()).ok_or_else(|| {
+                    api_error(
+                        ErrorCode::Unauthorized,
+                        "Submissions require an Authorization: Bearer token",
+                    )
+                })?;
+                let hash: [u8; 32] = Sha256::digest(token.as_bytes()).into();
+                self.tokens
+                    .read()
+                    .unwrap()
+                    .get(&hash)
+                    .cloned()
+                    .ok_or_else(|| {
+                        api_error(ErrorCode::Forbidden, "Submission token is not recognised")
+                    })
+            }
+            SubmissionAuthMode::ClientCertificate => {
+                let ClientCertificate(der) = request
+                    .extensions()
+                    .get::<ClientCertificate>()
+                    .ok_or_else(|| {
+                        api_error(
+                            ErrorCode::Unauthorized,
+                            "Submissions require a client certificate",
+                        )
+                    })?;
+                let certificate = x509_cert::Certificate::from_der(der).map_err(|_| {
+                    api_error(ErrorCode::Forbidden, "Client certificate is malformed")
+                })?;
+                Ok(certificate.tbs_certificate.subject.to_string())
+            }
+        }
+    }
+}
+
+/// Token file contents: `<principal> <hex SHA-256>` per line, with blank
+/// lines and `#` comments ignored
+fn parse_tokens(contents: &str) -> Result<HashMap<[u8; 32], String>> {
+    let mut tokens = HashMap::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |reason: &str| {
+            CtError::Internal(format!("Submission token line {} {}", number + 1, reason))
+        };
+        let mut fields = line.split_whitespace();
+        let (Some(principal), Some(hash), None) = (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(invalid("is not '<principal> <sha256>'"));
+        };
+        let hash: [u8; 32] = hex::decode(hash)
+            .ok()
+            .and_then(|hash| hash.try_into().ok())
+            .ok_or_else(|| invalid("does not have a hex SHA-256"))?;
+        if tokens.insert(hash, principal.to_string()).is_some() {
+            return Err(invalid("repeats a token"));
+        }
+    }
+    Ok(tokens)
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    let token = token.trim();
+    (scheme.eq_ignore_ascii_case("bearer") && !token.is_empty()).then_some(token)
+}
+
+pub(super) async fn require_submitter(
+    State(auth): State<Arc<SubmissionAuth>>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    match auth.authenticate(&req) {
+        Ok(principal) => {
+            req.extensions_mut().insert(Submitter(Some(principal)));
+            next.run(req).await
+        }
+        Err(error) => {
+            let challenge = error.0 == StatusCode::UNAUTHORIZED
+                && matches!(auth.mode, SubmissionAuthMode::BearerTokens { .. });
+            let mut response = error.into_response();
+            if challenge {
+                response
+                    .headers_mut()
+                    .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            }
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tokens() {
+        let hash = hex::encode(Sha256::digest(b"secret"));
+        let tokens =
+            parse_tokens(&format!("# issuer CAs\n\nca-one {}\n", hash.to_uppercase())).unwrap();
+        assert_eq!(
+            tokens.get(&<[u8; 32]>::from(Sha256::digest(b"secret"))),
+            Some(&"ca-one".to_string())
+        );
+
+        for contents in [
+            "ca-one".to_string(),
+            "ca-one abcd".to_string(),
+            format!("ca-one {} extra", hash),
+            format!("ca-one {}\nca-two {}", hash, hash),
+        ] {
+            assert!(parse_tokens(&contents).is_err(), "accepted {:?}", contents);
+        }
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        let headers = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::AUTHORIZATION, HeaderValue::from_static(value));
+            headers
+        };
+        assert_eq!(bearer_token(&headers("Bearer abc")), Some("abc"));
+        assert_eq!(bearer_token(&headers("bearer  abc ")), Some("abc"));
+        assert_eq!(bearer_token(&headers("Basic abc")), None);
+        assert_eq!(bearer_token(&headers("Bearer ")), None);
+        assert_eq!(bearer_token(&HeaderMap::new()), None);
+    }
+}
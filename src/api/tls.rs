@@ -0,0 +1,531 @@
+//! Built-in HTTPS listener, so the log can face the internet without a
+//! separate TLS terminator. The certificate and key are read from PEM files
+//! and reloaded when they change, as they do under an ACME client: new
+//! handshakes use the new certificate while established connections carry on
+//! with the one they negotiated.
+//!
+//! When client CAs are configured, clients may present a certificate, which
+//! is verified during the handshake and handed to the routes as a
+//! [`ClientCertificate`] extension. Presenting one is optional, so reads stay
+//! public while [`super::submission_auth`] decides who may submit.
+
+use axum::{http::Request, Router};
+use axum_server::accept::Accept;
+use axum_server::tls_rustls::{RustlsAcceptor, RustlsConfig};
+use futures::future::BoxFuture;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::server::TlsStream;
+
+use super::submission_auth::ClientCertificate;
+use crate::types::{CtError, Result};
+
+/// How often the certificate files are checked for changes by default
+pub const DEFAULT_TLS_RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The listener's certificate, key and client CAs, and the rustls
+/// configuration built from them
+pub struct ServerTls {
+    cert_file: PathBuf,
+    key_file: PathBuf,
+    client_ca_file: Option<PathBuf>,
+    config: RustlsConfig,
+    /// Modification times of the certificate and key last loaded
+    loaded: Mutex<Option<(SystemTime, SystemTime)>>,
+}
+
+impl ServerTls {
+    /// Load the PEM certificate chain and private key, and the client CAs to
+    /// verify client certificates against, if any
+    pub fn load(
+        cert_file: impl Into<PathBuf>,
+        key_file: impl Into<PathBuf>,
+        client_ca_file: Option<PathBuf>,
+    ) -> Result<Self> {
+        let cert_file = cert_file.into();
+        let key_file = key_file.into();
+        let loaded = modified(&cert_file, &key_file);
+        let config = server_config(&cert_file, &key_file, client_ca_file.as_deref())?;
+        Ok(Self {
+            cert_file,
+            key_file,
+            client_ca_file,
+            config: RustlsConfig::from_config(Arc::new(config)),
+            loaded: Mutex::new(loaded),
+        })
+    }
+
+    /// Re-read the certificate and key, which new handshakes then use. On
+    /// error the certificate in use is kept.
+    pub fn reload(&self) -> Result<()> {
+        let loaded = modified(&self.cert_file, &self.key_file);
+        let config = server_config(
+            &self.cert_file,
+            &self.key_file,
+            self.client_ca_file.as_deref(),
+        )?;
+        self.config.reload_from_config(Arc::new(config));
+        *self.loaded.lock().unwrap() = loaded;
+        Ok(())
+    }
+
+    /// Reload whenever the certificate or key file changes, checking every
+    /// `interval`. A failed reload, such as one that catches the ACME client
+    /// between writing the certificate and the key, is retried on the next check.
+    pub async fn watch(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let current = modified(&self.cert_file, &self.key_file);
+            if current.is_none() || current == *self.loaded.lock().unwrap() {
+                continue;
+            }
+            match self.reload() {
+                Ok(()) => {
+                    tracing::info!("Reloaded TLS certificate from {}", self.cert_file.display())
+                }
+                Err(e) => tracing::warn!("Failed to reload TLS certificate: {}", e),
+            }
+        }
+    }
+
+    fn acceptor(&self) -> ClientCertificateAcceptor {
+        ClientCertificateAcceptor {
+            inner: RustlsAcceptor::new(self.config.clone()),
+        }
+    }
+}
+
+/// Serve `app` over HTTPS on `listener` until the server fails
+pub async fn serve_tls(
+    listener: std::net::TcpListener,
+    tls: Arc<ServerTls>,
+    app: Router,
+) -> std::io::Result<()> {
+    axum_server::from_tcp(listener)
+        .acceptor(tls.acceptor())
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+}
+
+fn modified(cert_file: &Path, key_file: &Path) -> Option<(SystemTime, SystemTime)> {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    Some((modified(cert_file)?, modified(key_file)?))
+}
+
+fn server_config(
+    cert_file: &Path,
+    key_file: &Path,
+    client_ca_file: Option<&Path>,
+) -> Result<ServerConfig> {
+    let invalid = |what: &str, e: &dyn std::fmt::Display| {
+        CtError::Internal(format!("Invalid TLS {}: {}", what, e))
+    };
+
+    let certs = read_certs(cert_file)?;
+    let key = read_key(key_file)?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| invalid("protocol versions", &e))?;
+    let builder = match client_ca_file {
+        Some(client_ca_file) => {
+            let mut roots = RootCertStore::empty();
+            for ca in read_certs(client_ca_file)? {
+                roots.add(ca).map_err(|e| invalid("client CA", &e))?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .allow_unauthenticated()
+                .build()
+                .map_err(|e| invalid("client CAs", &e))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut config = builder
+        .with_single_cert(certs, key)
+        .map_err(|e| invalid("certificate", &e))?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+fn read_pem(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path)
+        .map_err(|e| CtError::Internal(format!("Failed to read {}: {}", path.display(), e)))
+}
+
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let pem = read_pem(path)?;
+    let certs = rustls_pemfile::certs(&mut pem.as_slice())
+        .collect::<std::io::Result<Vec<_>>>()
+        .map_err(|e| CtError::Internal(format!("Invalid PEM in {}: {}", path.display(), e)))?;
+    if certs.is_empty() {
+        return Err(CtError::Internal(format!(
+            "No certificates in {}",
+            path.display()
+        )));
+    }
+    Ok(certs)
+}
+
+fn read_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
+    let pem = read_pem(path)?;
+    rustls_pemfile::private_key(&mut pem.as_slice())
+        .map_err(|e| CtError::Internal(format!("Invalid PEM in {}: {}", path.display(), e)))?
+        .ok_or_else(|| CtError::Internal(format!("No private key in {}", path.display())))
+}
+
+/// Completes the TLS handshake, then attaches the verified client
+/// certificate, if any, to every request on the connection
+#[derive(Clone)]
+struct ClientCertificateAcceptor {
+    inner: RustlsAcceptor,
+}
+
+impl<I, S> Accept<I, S> for ClientCertificateAcceptor
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
+{
+    type Stream = TlsStream<I>;
+    type Service = WithClientCertificate<S>;
+    type Future = BoxFuture<'static, std::io::Result<(Self::Stream, Self::Service)>>;
+
+    fn accept(&self, stream: I, service: S) -> Self::Future {
+        let acceptor = self.inner.clone();
+        Box::pin(async move {
+            let (stream, service) = acceptor.accept(stream, service).await?;
+            let certificate = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|chain| chain.first())
+                .map(|leaf| ClientCertificate(leaf.to_vec()));
+            Ok((
+                stream,
+                WithClientCertificate {
+                    inner: service,
+                    certificate,
+                },
+            ))
+        })
+    }
+}
+
+#[derive(Clone)]
+struct WithClientCertificate<S> {
+    inner: S,
+    certificate: Option<ClientCertificate>,
+}
+
+impl<S, B> tower::Service<Request<B>> for WithClientCertificate<S>
+where
+    S: tower::Service<Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        if let Some(certificate) = &self.certificate {
+            req.extensions_mut().insert(certificate.clone());
+        }
+        self.inner.call(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{create_routers, handlers, submission_auth::SubmissionAuth};
+    use openssl::asn1::Asn1Time;
+    use openssl::bn::BigNum;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::pkey::{PKey, Private};
+    use openssl::x509::extension::{BasicConstraints, SubjectAlternativeName};
+    use openssl::x509::{X509Builder, X509NameBuilder, X509};
+
+    /// A certificate for `subject`, valid for 127.0.0.1, signed by `issuer`
+    /// or self-signed
+    fn certificate(
+        subject: &str,
+        issuer: Option<(&X509, &PKey<Private>)>,
+        is_ca: bool,
+    ) -> (X509, PKey<Private>) {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", subject).unwrap();
+        let name = name.build();
+
+        let now = chrono::Utc::now().timestamp();
+        let mut builder = X509Builder::new().unwrap();
+        builder.set_version(2).unwrap();
+        builder
+            .set_serial_number(&BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap())
+            .unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder
+            .set_not_before(&Asn1Time::from_unix(now - 3600).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::from_unix(now + 24 * 3600).unwrap())
+            .unwrap();
+        builder.set_pubkey(&key).unwrap();
+        if is_ca {
+            builder
+                .append_extension(BasicConstraints::new().critical().ca().build().unwrap())
+                .unwrap();
+        } else {
+            let san = SubjectAlternativeName::new()
+                .ip("127.0.0.1")
+                .build(&builder.x509v3_context(issuer.map(|(cert, _)| &**cert), None))
+                .unwrap();
+            builder.append_extension(san).unwrap();
+        }
+        let (issuer_name, issuer_key) = match issuer {
+            Some((issuer, issuer_key)) => (issuer.subject_name(), issuer_key),
+            None => (&*name, &key),
+        };
+        builder.set_issuer_name(issuer_name).unwrap();
+        builder.sign(issuer_key, MessageDigest::sha256()).unwrap();
+        (builder.build(), key)
+    }
+
+    fn write_pair(dir: &Path, (cert, key): &(X509, PKey<Private>)) {
+        std::fs::write(dir.join("cert.pem"), cert.to_pem().unwrap()).unwrap();
+        std::fs::write(dir.join("key.pem"), key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+    }
+
+    /// A client that trusts only `root`
+    fn pinned_client(root: &X509, identity: Option<reqwest::Identity>) -> reqwest::Client {
+        let builder = reqwest::Client::builder()
+            .use_rustls_tls()
+            .tls_built_in_root_certs(false)
+            .add_root_certificate(reqwest::Certificate::from_pem(&root.to_pem().unwrap()).unwrap());
+        match identity {
+            Some(identity) => builder.identity(identity),
+            None => builder,
+        }
+        .build()
+        .unwrap()
+    }
+
+    async fn serve(tls: Arc<ServerTls>, app: Router) -> SocketAddr {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_tls(listener, tls, app));
+        addr
+    }
+
+    async fn serve_plain(app: Router) -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_reload_serves_new_certificate() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = certificate("first", None, false);
+        let second = certificate("second", None, false);
+        write_pair(dir.path(), &first);
+
+        let tls = Arc::new(
+            ServerTls::load(
+                dir.path().join("cert.pem"),
+                dir.path().join("key.pem"),
+                None,
+            )
+            .unwrap(),
+        );
+        let state = Arc::into_inner(handlers::tests::create_test_state(3).await).unwrap();
+        let routers = create_routers(state.with_server_tls(tls.clone()));
+        let addr = serve(tls, routers.log).await;
+        let plain_addr = serve_plain(routers.operational).await;
+        let admin_addr = serve_plain(routers.admin).await;
+        let sth_url = format!("https://{}/ct/v1/get-sth", addr);
+
+        let response = pinned_client(&first.0, None)
+            .get(&sth_url)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.version(), reqwest::Version::HTTP_2);
+        assert!(pinned_client(&second.0, None)
+            .get(&sth_url)
+            .send()
+            .await
+            .is_err());
+
+        // Health and metrics stay on plain HTTP, and the API does not
+        let response = reqwest::get(format!("http://{}/health", plain_addr))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let response = reqwest::get(format!("http://{}/ct/v1/get-sth", plain_addr))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+
+        // Only the admin listener can reload
+        write_pair(dir.path(), &second);
+        let response = reqwest::Client::new()
+            .post(format!("http://{}/admin/reload", plain_addr))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+        let response = pinned_client(&first.0, None)
+            .post(format!("https://{}/admin/reload", addr))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+        let response = reqwest::Client::new()
+            .post(format!("http://{}/admin/reload", admin_addr))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let reloaded: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(reloaded["tls_certificate"], true);
+
+        let response = pinned_client(&second.0, None)
+            .get(&sth_url)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert!(pinned_client(&first.0, None)
+            .get(&sth_url)
+            .send()
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_watch_reloads_changed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = certificate("first", None, false);
+        let second = certificate("second", None, false);
+        write_pair(dir.path(), &first);
+
+        let tls = Arc::new(
+            ServerTls::load(
+                dir.path().join("cert.pem"),
+                dir.path().join("key.pem"),
+                None,
+            )
+            .unwrap(),
+        );
+        let app = Router::new().route("/", axum::routing::get(|| async { "ok" }));
+        let addr = serve(tls.clone(), app).await;
+        tokio::spawn(tls.watch(Duration::from_millis(20)));
+
+        // Far enough ahead that the new files look modified even on
+        // filesystems with coarse timestamps
+        write_pair(dir.path(), &second);
+        let later = SystemTime::now() + Duration::from_secs(10);
+        for file in ["cert.pem", "key.pem"] {
+            std::fs::File::options()
+                .write(true)
+                .open(dir.path().join(file))
+                .unwrap()
+                .set_modified(later)
+                .unwrap();
+        }
+
+        let client = pinned_client(&second.0, None);
+        for _ in 0..100 {
+            if client
+                .get(format!("https://{}/", addr))
+                .send()
+                .await
+                .is_ok()
+            {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("the changed certificate was never served");
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate_identifies_submitter() {
+        use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+        let dir = tempfile::tempdir().unwrap();
+        let server = certificate("server", None, false);
+        write_pair(dir.path(), &server);
+        let client_ca = certificate("Submitter CA", None, true);
+        let client = certificate("submitter", Some((&client_ca.0, &client_ca.1)), false);
+        std::fs::write(dir.path().join("ca.pem"), client_ca.0.to_pem().unwrap()).unwrap();
+
+        let tls = Arc::new(
+            ServerTls::load(
+                dir.path().join("cert.pem"),
+                dir.path().join("key.pem"),
+                Some(dir.path().join("ca.pem")),
+            )
+            .unwrap(),
+        );
+        let state = Arc::into_inner(handlers::tests::create_test_state(0).await)
+            .unwrap()
+            .with_submission_auth(SubmissionAuth::client_certificate());
+        let storage = state.storage.clone();
+        let addr = serve(tls, create_routers(state).log).await;
+
+        let identity = [
+            client.0.to_pem().unwrap(),
+            client.1.private_key_to_pem_pkcs8().unwrap(),
+        ]
+        .concat();
+        let with_identity = pinned_client(
+            &server.0,
+            Some(reqwest::Identity::from_pem(&identity).unwrap()),
+        );
+        let anonymous = pinned_client(&server.0, None);
+
+        let submit = |client: &reqwest::Client| {
+            let cert = crate::test_utils::utils::create_test_certificate_with_serial(30);
+            client
+                .post(format!("https://{}/ct/v1/add-chain", addr))
+                .json(&serde_json::json!({ "chain": [STANDARD.encode(&cert)] }))
+                .send()
+        };
+
+        // Anonymous clients can still read, but not submit
+        let response = anonymous
+            .get(format!("https://{}/ct/v1/get-sth", addr))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(submit(&anonymous).await.unwrap().status(), 401);
+
+        assert_eq!(submit(&with_identity).await.unwrap().status(), 200);
+        assert_eq!(
+            storage.get_submitter(0).await.unwrap(),
+            Some("CN=submitter".to_string())
+        );
+    }
+}
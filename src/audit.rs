@@ -0,0 +1,506 @@
+//! `compactlog audit`: checks any RFC 6962 log from the outside.
+//!
+//! Each run verifies the current STH signature, its consistency with the STH
+//! accepted by the previous run, and inclusion proofs for a sample of entries.
+//! The accepted STH is kept in a state file so later runs only prove the
+//! growth since then. A JSON report goes to stdout and the process exits
+//! nonzero if any check failed.
+
+use crate::merkle_storage::serialization::{
+    audit_path_to_inclusion_proof, path_to_consistency_proof, RecordProof,
+};
+use crate::merkle_tree::RootHash;
+use crate::types::tree_head::{verify_sth, SignedTreeHead, SthResponse};
+use crate::types::{
+    CtError, GetConsistencyProofResponse, GetEntriesResponse, GetProofByHashResponse, Result,
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const DEFAULT_SAMPLES: usize = 16;
+const DEFAULT_STATE_FILE: &str = "audit-state.json";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+const USAGE: &str =
+    "usage: compactlog audit <log-url> --key <public-key.pem> [--state <file>] [--samples <n>]";
+
+/// What a run leaves behind for the next one
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditState {
+    pub log_url: String,
+    /// Last STH that passed every check
+    pub sth: SthResponse,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditCheck {
+    pub name: String,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditReport {
+    pub log_url: String,
+    pub ok: bool,
+    pub previous_tree_size: Option<u64>,
+    pub tree_size: Option<u64>,
+    pub checks: Vec<AuditCheck>,
+}
+
+impl AuditReport {
+    fn record(&mut self, name: impl Into<String>, result: Result<()>) {
+        self.checks.push(AuditCheck {
+            name: name.into(),
+            ok: result.is_ok(),
+            detail: result.err().map(|e| e.to_string()),
+        });
+    }
+}
+
+fn root_hash(sth: &SignedTreeHead) -> Result<RootHash<Sha256>> {
+    if sth.root_hash.len() != Sha256::output_size() {
+        return Err(CtError::BadRequest("Root hash must be 32 bytes".into()));
+    }
+    Ok(RootHash::new(
+        *digest::Output::<Sha256>::from_slice(&sth.root_hash),
+        sth.tree_size,
+    ))
+}
+
+/// Check a get-sth-consistency path proving `old` is a prefix of `new`
+pub fn verify_consistency(
+    old: &SignedTreeHead,
+    new: &SignedTreeHead,
+    path: &[String],
+) -> Result<()> {
+    if new.tree_size < old.tree_size {
+        return Err(CtError::BadRequest(format!(
+            "Tree shrank from {} to {}",
+            old.tree_size, new.tree_size
+        )));
+    }
+    if old.tree_size == new.tree_size || old.tree_size == 0 {
+        if old.tree_size != 0 && old.root_hash != new.root_hash {
+            return Err(CtError::BadRequest(format!(
+                "Root hash changed at tree size {}",
+                new.tree_size
+            )));
+        }
+        return Ok(());
+    }
+
+    path_to_consistency_proof::<Sha256>(path)?
+        .verify(&root_hash(old)?, &root_hash(new)?)
+        .map_err(|e| CtError::BadRequest(format!("Consistency proof invalid: {}", e)))
+}
+
+/// Check a get-proof-by-hash path for `leaf_input` at `leaf_index` under `sth`
+pub fn verify_inclusion(
+    leaf_input: &[u8],
+    leaf_index: u64,
+    sth: &SignedTreeHead,
+    audit_path: &[String],
+) -> Result<()> {
+    audit_path_to_inclusion_proof::<Sha256>(audit_path)?
+        .verify(&leaf_input, leaf_index, &root_hash(sth)?)
+        .map_err(|e| CtError::BadRequest(format!("Inclusion proof invalid: {}", e)))
+}
+
+/// Check a tlog record proof for `leaf_input` under `sth`. The proof names
+/// its own leaf index and tree size, which must be the STH's.
+pub fn verify_record_proof(leaf_input: &[u8], sth: &SignedTreeHead, text: &str) -> Result<()> {
+    let record = RecordProof::<Sha256>::from_tlog(text)?;
+    if record.tree_size != sth.tree_size {
+        return Err(CtError::BadRequest(format!(
+            "Record proof is for tree size {}, not {}",
+            record.tree_size, sth.tree_size
+        )));
+    }
+    record
+        .proof
+        .verify(&leaf_input, record.leaf_index, &root_hash(sth)?)
+        .map_err(|e| CtError::BadRequest(format!("Record proof invalid: {}", e)))
+}
+
+/// Audits one log over its public RFC 6962 API
+pub struct Auditor {
+    client: reqwest::Client,
+    log_url: String,
+    public_key_der: Vec<u8>,
+    samples: usize,
+}
+
+impl Auditor {
+    pub fn new(log_url: &str, public_key_der: Vec<u8>, samples: usize) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| CtError::Internal(format!("Failed to build HTTP client: {}", e)))?;
+
+        Ok(Self {
+            client,
+            log_url: log_url.trim_end_matches('/').to_string(),
+            public_key_der,
+            samples,
+        })
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        query: &[(&str, String)],
+    ) -> Result<T> {
+        let url = format!("{}/ct/v1/{}", self.log_url, endpoint);
+        let response = self
+            .client
+            .get(&url)
+            .query(query)
+            .send()
+            .await
+            .map_err(|e| CtError::Internal(format!("{} failed: {}", endpoint, e)))?;
+
+        if !response.status().is_success() {
+            return Err(CtError::Internal(format!(
+                "{} returned HTTP {}",
+                endpoint,
+                response.status()
+            )));
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| CtError::Internal(format!("{} returned invalid JSON: {}", endpoint, e)))
+    }
+
+    async fn fetch_sth(&self) -> Result<SignedTreeHead> {
+        let response: SthResponse = self.get("get-sth", &[]).await?;
+        verify_sth(&self.public_key_der, &response)
+    }
+
+    async fn check_consistency(&self, old: &SignedTreeHead, new: &SignedTreeHead) -> Result<()> {
+        let path = if old.tree_size == 0 || old.tree_size >= new.tree_size {
+            Vec::new()
+        } else {
+            let response: GetConsistencyProofResponse = self
+                .get(
+                    "get-sth-consistency",
+                    &[
+                        ("first", old.tree_size.to_string()),
+                        ("second", new.tree_size.to_string()),
+                    ],
+                )
+                .await?;
+            response.consistency
+        };
+
+        verify_consistency(old, new, &path)
+    }
+
+    async fn check_inclusion(&self, leaf_index: u64, sth: &SignedTreeHead) -> Result<()> {
+        let entries: GetEntriesResponse = self
+            .get(
+                "get-entries",
+                &[
+                    ("start", leaf_index.to_string()),
+                    ("end", leaf_index.to_string()),
+                ],
+            )
+            .await?;
+        let entry = entries
+            .entries
+            .first()
+            .ok_or_else(|| CtError::Internal("get-entries returned no entries".into()))?;
+        let leaf_input = STANDARD
+            .decode(&entry.leaf_input)
+            .map_err(|_| CtError::BadRequest("Invalid base64 leaf_input".into()))?;
+
+        let leaf_hash = Sha256::new_with_prefix([0u8])
+            .chain_update(&leaf_input)
+            .finalize();
+        let proof: GetProofByHashResponse = self
+            .get(
+                "get-proof-by-hash",
+                &[
+                    ("hash", STANDARD.encode(leaf_hash)),
+                    ("tree_size", sth.tree_size.to_string()),
+                ],
+            )
+            .await?;
+        if proof.leaf_index != leaf_index {
+            return Err(CtError::BadRequest(format!(
+                "Proof is for leaf {} instead of {}",
+                proof.leaf_index, leaf_index
+            )));
+        }
+
+        verify_inclusion(&leaf_input, leaf_index, sth, &proof.audit_path)
+    }
+
+    /// Run every check against the log, returning the STH to persist if all passed
+    pub async fn run(&self, previous: Option<&SthResponse>) -> (AuditReport, Option<SthResponse>) {
+        let mut report = AuditReport {
+            log_url: self.log_url.clone(),
+            ok: false,
+            previous_tree_size: previous.map(|sth| sth.tree_size),
+            tree_size: None,
+            checks: Vec::new(),
+        };
+
+        let sth = match self.fetch_sth().await {
+            Ok(sth) => sth,
+            Err(e) => {
+                report.record("sth_signature", Err(e));
+                return (report, None);
+            }
+        };
+        report.tree_size = Some(sth.tree_size);
+        report.record("sth_signature", Ok(()));
+
+        if let Some(previous) = previous {
+            let result = match SignedTreeHead::from_api_response(previous) {
+                Ok(old) => self.check_consistency(&old, &sth).await,
+                Err(e) => Err(e),
+            };
+            report.record("consistency", result);
+        }
+
+        if sth.tree_size > 0 {
+            let mut rng = rand::thread_rng();
+            let indices: BTreeSet<u64> = (0..self.samples)
+                .map(|_| rng.gen_range(0..sth.tree_size))
+                .collect();
+            for leaf_index in indices {
+                let result = self.check_inclusion(leaf_index, &sth).await;
+                report.record(format!("inclusion[{}]", leaf_index), result);
+            }
+        }
+
+        report.ok = report.checks.iter().all(|check| check.ok);
+        let accepted = report.ok.then(|| sth.to_api_response());
+        (report, accepted)
+    }
+}
+
+struct AuditArgs {
+    log_url: String,
+    key_path: PathBuf,
+    state_path: PathBuf,
+    samples: usize,
+}
+
+fn parse_args(args: &[String]) -> std::result::Result<AuditArgs, String> {
+    let mut log_url = None;
+    let mut key_path = None;
+    let mut state_path = PathBuf::from(DEFAULT_STATE_FILE);
+    let mut samples = DEFAULT_SAMPLES;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--key" => key_path = Some(PathBuf::from(value()?)),
+            "--state" => state_path = PathBuf::from(value()?),
+            "--samples" => {
+                samples = value()?
+                    .parse()
+                    .map_err(|_| "--samples must be a number".to_string())?
+            }
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            url if log_url.is_none() => log_url = Some(url.to_string()),
+            extra => return Err(format!("unexpected argument {}", extra)),
+        }
+    }
+
+    Ok(AuditArgs {
+        log_url: log_url.ok_or("missing log URL")?,
+        key_path: key_path.ok_or("missing --key")?,
+        state_path,
+        samples,
+    })
+}
+
+/// Accepts a PEM `PUBLIC KEY` or raw DER SubjectPublicKeyInfo
+fn load_public_key(path: &Path) -> std::result::Result<Vec<u8>, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if !bytes.starts_with(b"-----BEGIN") {
+        return Ok(bytes);
+    }
+
+    match pem_rfc7468::decode_vec(&bytes) {
+        Ok(("PUBLIC KEY", der)) => Ok(der),
+        Ok((label, _)) => Err(format!(
+            "{}: expected PUBLIC KEY, found {}",
+            path.display(),
+            label
+        )),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+fn load_state(path: &Path, log_url: &str) -> std::result::Result<Option<AuditState>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let data = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let state: AuditState =
+        serde_json::from_slice(&data).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if state.log_url != log_url {
+        return Err(format!(
+            "{} belongs to {}; use a separate --state per log",
+            path.display(),
+            state.log_url
+        ));
+    }
+    Ok(Some(state))
+}
+
+/// Entry point for `compactlog audit`; returns the process exit code
+pub async fn run_cli(args: &[String]) -> i32 {
+    let args = match parse_args(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    let log_url = args.log_url.trim_end_matches('/').to_string();
+
+    let setup = load_public_key(&args.key_path).and_then(|key| {
+        let state = load_state(&args.state_path, &log_url)?;
+        let auditor = Auditor::new(&log_url, key, args.samples).map_err(|e| e.to_string())?;
+        Ok((auditor, state))
+    });
+    let (auditor, state) = match setup {
+        Ok(setup) => setup,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+
+    let (report, accepted) = auditor.run(state.as_ref().map(|s| &s.sth)).await;
+
+    if let Some(sth) = accepted {
+        let state = AuditState { log_url, sth };
+        let saved = serde_json::to_vec_pretty(&state)
+            .map_err(|e| e.to_string())
+            .and_then(|data| {
+                std::fs::write(&args.state_path, data)
+                    .map_err(|e| format!("{}: {}", args.state_path.display(), e))
+            });
+        if let Err(e) = saved {
+            eprintln!("Failed to save audit state: {}", e);
+            return 2;
+        }
+    }
+
+    match serde_json::to_string_pretty(&report) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Failed to encode report: {}", e),
+    }
+
+    if report.ok {
+        0
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{create_router, handlers::tests::create_test_state};
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    fn leaf_hash(leaf: &[u8]) -> Vec<u8> {
+        Sha256::new_with_prefix([0u8])
+            .chain_update(leaf)
+            .finalize()
+            .to_vec()
+    }
+
+    fn parent_hash(left: &[u8], right: &[u8]) -> Vec<u8> {
+        Sha256::new_with_prefix([1u8])
+            .chain_update(left)
+            .chain_update(right)
+            .finalize()
+            .to_vec()
+    }
+
+    #[test]
+    fn test_hand_crafted_proofs() {
+        let (a, b) = (b"leaf a".as_slice(), b"leaf b".as_slice());
+        let old = SignedTreeHead::new(1, 0, leaf_hash(a));
+        let new = SignedTreeHead::new(2, 0, parent_hash(&leaf_hash(a), &leaf_hash(b)));
+        let good_path = vec![STANDARD.encode(leaf_hash(b))];
+        let bad_path = vec![STANDARD.encode(leaf_hash(b"leaf c"))];
+
+        verify_inclusion(a, 0, &new, &good_path).unwrap();
+        assert!(verify_inclusion(a, 0, &new, &bad_path).is_err());
+        assert!(verify_inclusion(a, 1, &new, &good_path).is_err());
+        assert!(verify_inclusion(a, 0, &new, &[]).is_err());
+
+        verify_consistency(&old, &new, &good_path).unwrap();
+        assert!(verify_consistency(&old, &new, &bad_path).is_err());
+        assert!(verify_consistency(&new, &old, &good_path).is_err());
+
+        let forked = SignedTreeHead::new(2, 0, parent_hash(&leaf_hash(b), &leaf_hash(a)));
+        assert!(verify_consistency(&new, &forked, &[]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_audit_in_process_log() {
+        let state = create_test_state(7).await;
+        let public_key_der = state.public_key_der.clone();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, create_router(Arc::into_inner(state).unwrap()))
+                .await
+                .unwrap();
+        });
+
+        let auditor = Auditor::new(&format!("http://{}", addr), public_key_der, 4).unwrap();
+        let (report, accepted) = auditor.run(None).await;
+        assert!(report.ok, "{:?}", report);
+        assert_eq!(report.tree_size, Some(7));
+        assert!(report
+            .checks
+            .iter()
+            .any(|c| c.name.starts_with("inclusion")));
+        let accepted = accepted.unwrap();
+
+        // A later run proves consistency with the accepted STH
+        let (report, _) = auditor.run(Some(&accepted)).await;
+        assert!(report.ok, "{:?}", report);
+
+        // An STH the log never signed at this size must not be consistent
+        let mut forged = accepted.clone();
+        forged.sha256_root_hash = STANDARD.encode([7u8; 32]);
+        let (report, accepted) = auditor.run(Some(&forged)).await;
+        assert!(!report.ok);
+        assert!(accepted.is_none());
+        let consistency = report.checks.iter().find(|c| c.name == "consistency");
+        assert!(!consistency.unwrap().ok);
+
+        let wrong_key = Auditor::new(
+            &format!("http://{}", addr),
+            create_test_state(0).await.public_key_der.clone(),
+            4,
+        )
+        .unwrap();
+        let (report, _) = wrong_key.run(None).await;
+        assert!(!report.ok);
+        assert_eq!(report.checks[0].name, "sth_signature");
+    }
+}
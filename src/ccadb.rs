@@ -25,6 +25,12 @@ pub struct RootCertificateStore {
     certificates: Arc<RwLock<HashMap<String, CcadbRootEntry>>>,
 }
 
+impl Default for RootCertificateStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RootCertificateStore {
     pub fn new() -> Self {
         Self {
@@ -375,7 +381,16 @@ impl CcadbWorker {
         if let (Some(validator_lock), Some(config)) = (&self.validator, &self.validation_config) {
             let trusted_roots = self.store.get_all_certificates().await;
 
-            match Rfc6962Validator::with_trusted_roots(config.clone(), trusted_roots) {
+            // CCADB only supplies the final-certificate roots; keep any separate
+            // precertificate roots as they were
+            let precert_roots = validator_lock.read().await.precert_roots();
+            let rebuilt = Rfc6962Validator::with_trusted_roots(config.clone(), trusted_roots)
+                .and_then(|validator| match precert_roots {
+                    Some(precert_roots) => validator.with_precert_roots(precert_roots),
+                    None => Ok(validator),
+                });
+
+            match rebuilt {
                 Ok(new_validator) => {
                     let mut validator_guard = validator_lock.write().await;
                     *validator_guard = new_validator;
@@ -0,0 +1,325 @@
+//! Running a log inside another process, without the HTTP server.
+//!
+//! [`LogBuilder`] wires up the same storage, tree and signers the binary uses
+//! and returns a [`Log`] whose methods call them directly. Without roots the
+//! log accepts arbitrary byte strings as leaves, which suits binary or key
+//! transparency logs that have no certificates to validate.
+
+use crate::{
+    api::{create_router, ApiState},
+    merkle_storage::StorageBackedMerkleTree,
+    merkle_tree::{ConsistencyProof, InclusionProof},
+    storage::{BatchConfig, CtStorage, RateLimitedDb},
+    types::{
+        sct::SignedCertificateTimestamp, signed_note::SignedNote, signer::Signer, CtError,
+        LogEntry, LogEntryType, LogId, Result,
+    },
+    validation::{Rfc6962Validator, ValidationPool, ValidationPoolError},
+};
+use axum::Router;
+use object_store::memory::InMemory;
+use p256::{ecdsa::SigningKey, SecretKey};
+use sha2::{Digest, Sha256};
+use slatedb::Db;
+use std::sync::Arc;
+
+/// Pending validations allowed per validation thread
+const PENDING_VALIDATIONS_PER_THREAD: usize = 8;
+
+/// Configures and opens an embedded [`Log`]
+pub struct LogBuilder {
+    origin: String,
+    db: Option<Arc<Db>>,
+    signer: Option<Box<dyn Signer>>,
+    validator: Option<Rfc6962Validator>,
+    batch_config: BatchConfig,
+}
+
+impl LogBuilder {
+    /// `origin` is the first line of the log's checkpoints, e.g. `example.com/log`
+    pub fn new(origin: impl Into<String>) -> Self {
+        Self {
+            origin: origin.into(),
+            db: None,
+            signer: None,
+            validator: None,
+            batch_config: BatchConfig::default(),
+        }
+    }
+
+    /// Database holding the log; an in-memory one is used if unset
+    pub fn with_storage(mut self, db: Arc<Db>) -> Self {
+        self.db = Some(db);
+        self
+    }
+
+    /// P-256 key that signs receipts and checkpoints. This or a signer is
+    /// required.
+    pub fn with_signing_key(self, signing_key: SecretKey) -> Self {
+        self.with_signer(Box::new(SigningKey::from(signing_key)))
+    }
+
+    /// Sign with a key held elsewhere, such as in an HSM
+    pub fn with_signer(mut self, signer: Box<dyn Signer>) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Validate appended chains as RFC 6962 X.509 chains, or accept any bytes with `None`
+    pub fn with_roots(mut self, validator: Option<Rfc6962Validator>) -> Self {
+        self.validator = validator;
+        self
+    }
+
+    pub fn with_batch_config(mut self, batch_config: BatchConfig) -> Self {
+        self.batch_config = batch_config;
+        self
+    }
+
+    pub async fn build(self) -> Result<Log> {
+        let signer = self
+            .signer
+            .ok_or_else(|| CtError::Internal("A signing key is required".to_string()))?;
+
+        let db = match self.db {
+            Some(db) => db,
+            None => Arc::new(
+                Db::open("embedded", Arc::new(InMemory::new()))
+                    .await
+                    .map_err(|e| CtError::Internal(format!("Failed to open database: {}", e)))?,
+            ),
+        };
+        let db = RateLimitedDb::new(db, None);
+        let merkle_tree = StorageBackedMerkleTree::new(db.clone()).await?;
+        let storage = CtStorage::new(db, self.batch_config, merkle_tree.clone()).await?;
+
+        let validator = match self.validator {
+            Some(validator) => {
+                let threads = num_cpus::get();
+                let pool = ValidationPool::new(threads, threads * PENDING_VALIDATIONS_PER_THREAD)?;
+                Some((validator, pool))
+            }
+            None => None,
+        };
+
+        let state = ApiState::new(
+            Arc::new(storage),
+            merkle_tree,
+            signer,
+            self.origin.clone(),
+            validator,
+        )?
+        .with_checkpoint_origin(self.origin);
+
+        Ok(Log { state })
+    }
+}
+
+/// A transparency log running in this process
+pub struct Log {
+    state: ApiState,
+}
+
+impl Log {
+    /// Append `leaf` and wait for it to be committed to the tree. With roots
+    /// configured, `leaf` must be a certificate issued directly by one of them;
+    /// use [`Log::append_chain`] to supply intermediates.
+    pub async fn append(&self, leaf: Vec<u8>) -> Result<(u64, SignedCertificateTimestamp)> {
+        self.append_chain(vec![leaf]).await
+    }
+
+    /// Append the first entry of `chain`, keeping the rest as its extra data.
+    /// Appending the same leaf again returns the original index and receipt.
+    pub async fn append_chain(
+        &self,
+        chain: Vec<Vec<u8>>,
+    ) -> Result<(u64, SignedCertificateTimestamp)> {
+        if chain.is_empty() {
+            return Err(CtError::BadRequest("Chain cannot be empty".to_string()));
+        }
+
+        let chain = match (&self.state.validator, &self.state.validation_pool) {
+            (Some(validator), Some(pool)) => pool
+                .validate(validator, chain, LogEntryType::X509Entry)
+                .await
+                .map_err(|e| match e {
+                    ValidationPoolError::Busy => {
+                        CtError::Internal("Too many pending validations".to_string())
+                    }
+                    ValidationPoolError::Rejected(e) => e,
+                })?,
+            _ => chain,
+        };
+
+        let leaf = chain[0].clone();
+        let leaf_hash: [u8; 32] = Sha256::digest(&leaf).into();
+        if let Some(existing) = self.state.storage.get_sct_by_cert_hash(&leaf_hash).await? {
+            return Ok((existing.index, existing.sct));
+        }
+
+        let timestamp = chrono::Utc::now();
+        let timestamp_ms = timestamp.timestamp_millis() as u64;
+        let extra = chain[1..].to_vec();
+        let log_entry = LogEntry::new_with_timestamp(
+            0,
+            leaf.clone(),
+            (!extra.is_empty()).then_some(extra),
+            timestamp,
+        );
+
+        let sct_builder = self.state.sct_builder.clone();
+        let (index, sct) = self
+            .state
+            .storage
+            .add_entry_batched(log_entry, leaf_hash, move |index| {
+                sct_builder.create_sct_with_timestamp_and_index(
+                    &leaf,
+                    LogEntryType::X509Entry,
+                    None,
+                    timestamp_ms,
+                    Some(index),
+                )
+            })
+            .await?;
+
+        Ok((index, sct))
+    }
+
+    /// Proof that entry `index` is in the tree of `tree_size` leaves, which
+    /// must be the size of a checkpoint
+    pub async fn prove_inclusion(
+        &self,
+        index: u64,
+        tree_size: u64,
+    ) -> Result<InclusionProof<Sha256>> {
+        self.state
+            .merkle_tree
+            .prove_inclusion_efficient(tree_size, index)
+            .await
+    }
+
+    /// Proof that the tree of `first` leaves is a prefix of the tree of `second`
+    pub async fn prove_consistency(
+        &self,
+        first: u64,
+        second: u64,
+    ) -> Result<ConsistencyProof<Sha256>> {
+        self.state
+            .merkle_tree
+            .consistency_proof_between_sizes(first, second)
+            .await
+    }
+
+    /// Checkpoint signed over everything appended so far
+    pub async fn current_checkpoint(&self) -> Result<SignedNote> {
+        let root = self.state.merkle_tree.committed_root().await?;
+        self.state
+            .sth_builder
+            .create_checkpoint(root.num_leaves(), root.as_bytes().to_vec(), None)
+    }
+
+    pub fn log_id(&self) -> &LogId {
+        &self.state.log_id
+    }
+
+    /// DER SubjectPublicKeyInfo of the signing key, for verifying receipts and checkpoints
+    pub fn public_key_der(&self) -> &[u8] {
+        &self.state.public_key_der
+    }
+
+    pub fn merkle_tree(&self) -> &StorageBackedMerkleTree {
+        &self.state.merkle_tree
+    }
+
+    /// Serve the log over the RFC 6962 and Static CT APIs
+    pub fn into_router(self) -> Router {
+        create_router(self.state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+    async fn test_log() -> Log {
+        LogBuilder::new("example.com/embedded")
+            .with_signing_key(SecretKey::random(&mut rand::thread_rng()))
+            .build()
+            .await
+            .unwrap()
+    }
+
+    /// Tree size and root hash from a checkpoint body
+    fn checkpoint_root(checkpoint: &SignedNote) -> (u64, Vec<u8>) {
+        let mut lines = checkpoint.text.lines().skip(1);
+        let size = lines.next().unwrap().parse().unwrap();
+        let root = STANDARD.decode(lines.next().unwrap()).unwrap();
+        (size, root)
+    }
+
+    #[tokio::test]
+    async fn test_append_arbitrary_bytes() {
+        let log = test_log().await;
+
+        let (first, receipt) = log.append(b"release v1.0.0".to_vec()).await.unwrap();
+        let (second, _) = log.append(b"release v1.0.1".to_vec()).await.unwrap();
+        assert_eq!((first, second), (0, 1));
+        assert_eq!(&receipt.log_id, log.log_id());
+
+        // Appending the same bytes again is a no-op
+        let (again, again_receipt) = log.append(b"release v1.0.0".to_vec()).await.unwrap();
+        assert_eq!(again, 0);
+        assert_eq!(again_receipt.signature, receipt.signature);
+        assert_eq!(log.merkle_tree().committed_size().await.unwrap(), 2);
+
+        assert!(log.append_chain(vec![]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_proofs_verify_against_checkpoints() {
+        let log = test_log().await;
+
+        log.append(b"key 0".to_vec()).await.unwrap();
+        let old = log.current_checkpoint().await.unwrap();
+        assert!(old.text.starts_with("example.com/embedded\n"));
+        for i in 1..5 {
+            log.append(format!("key {}", i).into_bytes()).await.unwrap();
+        }
+        let new = log.current_checkpoint().await.unwrap();
+
+        let (old_size, old_root) = checkpoint_root(&old);
+        let (new_size, new_root) = checkpoint_root(&new);
+        assert_eq!((old_size, new_size), (1, 5));
+        let old_root = crate::merkle_tree::RootHash::<Sha256>::new(
+            *digest::Output::<Sha256>::from_slice(&old_root),
+            old_size,
+        );
+        let new_root = crate::merkle_tree::RootHash::<Sha256>::new(
+            *digest::Output::<Sha256>::from_slice(&new_root),
+            new_size,
+        );
+
+        let consistency = log.prove_consistency(old_size, new_size).await.unwrap();
+        consistency.verify(&old_root, &new_root).unwrap();
+
+        let leaf = log
+            .merkle_tree()
+            .get_leaf_range(3, 3)
+            .await
+            .unwrap()
+            .remove(0);
+        let inclusion = log.prove_inclusion(3, new_size).await.unwrap();
+        inclusion.verify(&leaf.data, 3, &new_root).unwrap();
+
+        assert!(log.prove_inclusion(5, new_size).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_build_requires_signing_key() {
+        assert!(LogBuilder::new("example.com/embedded")
+            .build()
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use crate::merkle_storage::StorageBackedMerkleTree;
+use crate::merkle_tree::RootHash;
+use crate::metrics::Metrics;
+use crate::types::tree_head::{SignedTreeHead, SthBuilder, SthResponse};
+use crate::types::{CtError, LogId, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// STH as exchanged between instances of the same log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GossipSth {
+    /// Base64 log ID, so a peer can reject STHs meant for another log
+    pub log_id: String,
+    #[serde(flatten)]
+    pub sth: SthResponse,
+}
+
+/// How a received STH relates to the local tree
+#[derive(Debug, PartialEq)]
+pub enum GossipOutcome {
+    Consistent,
+    /// The peer has committed entries this instance has not seen yet
+    Ahead {
+        local_size: u64,
+    },
+    Inconsistent(String),
+}
+
+/// Sign the current committed tree head for gossip
+pub async fn current_gossip_sth(
+    merkle_tree: &StorageBackedMerkleTree,
+    sth_builder: &SthBuilder,
+    log_id: &LogId,
+) -> Result<GossipSth> {
+    let committed_root = merkle_tree.committed_root().await?;
+    let sth = sth_builder.create_sth(
+        committed_root.num_leaves(),
+        committed_root.as_bytes().to_vec(),
+        None,
+    )?;
+
+    Ok(GossipSth {
+        log_id: STANDARD.encode(log_id.to_bytes()),
+        sth: sth.to_api_response(),
+    })
+}
+
+/// Compare a peer's (already verified) STH with the local tree.
+///
+/// Inconsistencies are logged and counted in `metrics` here so callers only
+/// need to report the outcome.
+pub async fn check_consistency(
+    merkle_tree: &StorageBackedMerkleTree,
+    sth: &SignedTreeHead,
+    metrics: &Metrics,
+) -> Result<GossipOutcome> {
+    let local_root = merkle_tree.committed_root().await?;
+    let local_size = local_root.num_leaves();
+
+    let outcome = if sth.tree_size > local_size {
+        GossipOutcome::Ahead { local_size }
+    } else if sth.tree_size == local_size {
+        roots_match(local_root.as_bytes(), sth)
+    } else if sth.tree_size == 0 {
+        roots_match(&Sha256::digest([]), sth)
+    } else {
+        match merkle_tree
+            .consistency_proof_between_sizes(sth.tree_size, local_size)
+            .await
+        {
+            Ok(proof) => {
+                let peer_root = RootHash::<Sha256>::new(
+                    sha2::digest::Output::<Sha256>::clone_from_slice(&sth.root_hash),
+                    sth.tree_size,
+                );
+                match proof.verify(&peer_root, &local_root) {
+                    Ok(()) => GossipOutcome::Consistent,
+                    Err(e) => GossipOutcome::Inconsistent(format!(
+                        "consistency proof {} -> {} failed: {}",
+                        sth.tree_size, local_size, e
+                    )),
+                }
+            }
+            // The same log never signs a size it did not publish
+            Err(CtError::BadRequest(e)) => GossipOutcome::Inconsistent(format!(
+                "tree size {} was never published locally: {}",
+                sth.tree_size, e
+            )),
+            Err(e) => return Err(e),
+        }
+    };
+
+    if let GossipOutcome::Inconsistent(reason) = &outcome {
+        metrics.gossip_inconsistency_total.inc();
+        tracing::error!(
+            "Gossiped STH (size {}, timestamp {}, root {}) is inconsistent with local tree of size {}: {}",
+            sth.tree_size,
+            sth.timestamp,
+            hex::encode(&sth.root_hash),
+            local_size,
+            reason
+        );
+    }
+
+    Ok(outcome)
+}
+
+fn roots_match(local_root: &[u8], sth: &SignedTreeHead) -> GossipOutcome {
+    if local_root == sth.root_hash.as_slice() {
+        GossipOutcome::Consistent
+    } else {
+        GossipOutcome::Inconsistent(format!(
+            "root at size {} differs (local {})",
+            sth.tree_size,
+            hex::encode(local_root)
+        ))
+    }
+}
+
+/// Periodically pushes the current STH to peer log instances
+pub struct GossipWorker {
+    peers: Vec<String>,
+    client: reqwest::Client,
+    merkle_tree: StorageBackedMerkleTree,
+    sth_builder: Arc<SthBuilder>,
+    log_id: LogId,
+}
+
+impl GossipWorker {
+    pub fn new(
+        peers: Vec<String>,
+        merkle_tree: StorageBackedMerkleTree,
+        sth_builder: Arc<SthBuilder>,
+        log_id: LogId,
+    ) -> Self {
+        Self {
+            peers,
+            client: reqwest::Client::new(),
+            merkle_tree,
+            sth_builder,
+            log_id,
+        }
+    }
+
+    pub async fn push_to_peers(&self) -> Result<()> {
+        let gossip = current_gossip_sth(&self.merkle_tree, &self.sth_builder, &self.log_id).await?;
+
+        for peer in &self.peers {
+            let url = format!("{}/ct/v1/receive-gossip-sth", peer.trim_end_matches('/'));
+            match self.client.post(&url).json(&gossip).send().await {
+                Ok(response) if response.status().is_success() => {
+                    tracing::debug!("Gossiped STH of size {} to {}", gossip.sth.tree_size, peer);
+                }
+                Ok(response) => {
+                    tracing::warn!(
+                        "Peer {} rejected gossiped STH: HTTP {}",
+                        peer,
+                        response.status()
+                    );
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to gossip STH to {}: {}", peer, e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn run_periodic(self, interval: Duration) {
+        let mut interval = tokio::time::interval(interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = self.push_to_peers().await {
+                tracing::error!("STH gossip failed: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_storage::tests::create_test_tree;
+
+    fn sth_at(size: u64, root: &[u8]) -> SignedTreeHead {
+        SignedTreeHead::new(size, 1700000000000, root.to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_earlier_and_current_roots_are_consistent() {
+        let tree = create_test_tree(6).await;
+        let metrics = Metrics::new();
+
+        for size in [0, 1, 3, 6] {
+            let root = tree.root_at_size(size).await.unwrap();
+            let outcome = check_consistency(&tree, &sth_at(size, root.as_bytes()), &metrics)
+                .await
+                .unwrap();
+            assert_eq!(outcome, GossipOutcome::Consistent, "size {}", size);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_peer_ahead() {
+        let tree = create_test_tree(2).await;
+        let outcome = check_consistency(&tree, &sth_at(5, &[0u8; 32]), &Metrics::new())
+            .await
+            .unwrap();
+        assert_eq!(outcome, GossipOutcome::Ahead { local_size: 2 });
+    }
+
+    #[tokio::test]
+    async fn test_forked_root_is_counted() {
+        let tree = create_test_tree(6).await;
+        let metrics = Metrics::new();
+
+        for size in [0, 3, 6] {
+            let outcome = check_consistency(&tree, &sth_at(size, &[0xee; 32]), &metrics)
+                .await
+                .unwrap();
+            assert!(matches!(outcome, GossipOutcome::Inconsistent(_)));
+        }
+        assert_eq!(metrics.gossip_inconsistency_total.get(), 3);
+    }
+}
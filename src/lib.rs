@@ -0,0 +1,20 @@
+pub mod api;
+pub mod audit;
+pub mod ccadb;
+pub mod embedded;
+pub mod gossip;
+pub mod merkle_storage;
+pub mod merkle_tree;
+pub mod metrics;
+pub mod oids;
+pub mod rate_limiter;
+pub mod sth_archive;
+pub mod sth_signer;
+pub mod storage;
+pub mod types;
+pub mod validation;
+
+pub use embedded::{Log, LogBuilder};
+
+#[cfg(test)]
+mod test_utils;
@@ -1,8 +1,7 @@
 use config::Config;
-use object_store::{BackoffConfig, RetryConfig};
 use p256::pkcs8::LineEnding;
 use p256::pkcs8::{DecodePrivateKey, EncodePrivateKey, EncodePublicKey};
-use p256::SecretKey;
+use p256::{ecdsa::SigningKey, SecretKey};
 use serde::{Deserialize, Serialize};
 use slatedb::config::{
     CompactorOptions, CompressionCodec, GarbageCollectorDirectoryOptions, GarbageCollectorOptions,
@@ -10,14 +9,12 @@ use slatedb::config::{
 };
 use slatedb::db_cache::foyer::{FoyerCache, FoyerCacheOptions};
 use slatedb::{
-    object_store::{
-        aws::AmazonS3Builder, azure::MicrosoftAzureBuilder, local::LocalFileSystem, path::Path,
-        ObjectStore,
-    },
+    object_store::{path::Path, ObjectStore},
     Db, Settings,
 };
 use tokio::runtime::{Handle, Runtime};
 
+use std::net::SocketAddr;
 use std::path::{Path as StdPath, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
@@ -25,62 +22,118 @@ use std::{default, fs};
 use tokio::net::TcpListener;
 use tracing::info;
 
-mod api;
-mod ccadb;
-mod merkle_storage;
-mod merkle_tree;
-mod metrics;
-mod oids;
-mod rate_limiter;
-mod storage;
-mod types;
-mod validation;
-
-#[cfg(test)]
-mod test_utils;
-
-use api::{create_router, ApiState};
+use compactlog::{
+    api, audit, ccadb, gossip, merkle_storage, metrics, rate_limiter, sth_archive, sth_signer,
+    storage, types, validation,
+};
+
+use api::{
+    create_routers,
+    submission_auth::SubmissionAuth,
+    tls::{serve_tls, ServerTls, DEFAULT_TLS_RELOAD_INTERVAL},
+    ApiState, EntryFormat, LogMetadata, LogRegistry, RootsOrder, MAX_ENTRY_BYTES_LIMIT,
+};
 use ccadb::{CcadbWorker, RootCertificateStore};
+use gossip::GossipWorker;
+use merkle_storage::StorageBackedMerkleTree;
+use metrics::Metrics;
 use rate_limiter::ReadRateLimiter;
-use storage::{BatchConfig, CtStorage, RateLimitedDb};
-use types::LogId;
-use validation::{CcadbEnvironment, Rfc6962ValidationConfig, Rfc6962Validator};
+use sth_archive::SthArchive;
+use sth_signer::{SthSigningTask, DEFAULT_STH_SIGNING_INTERVAL};
+use storage::{
+    BatchConfig, Compression, CtStorage, LocalConfig, RateLimitedDb, RejectionLog, StorageConfig,
+    WitnessKey,
+};
+use types::{sct::TimestampWindow, tree_head::verify_sth, LogId};
+use validation::rfc6962_validator::{ClockTolerance, TemporalWindow};
+use validation::{
+    CcadbEnvironment, Rfc6962ValidationConfig, Rfc6962Validator, SignatureAlgorithmOid,
+    ValidationMode, ValidationPool,
+};
 
 #[derive(Debug, Deserialize, Serialize)]
-struct StorageConfig {
-    provider: String,
-    azure: Option<AzureConfig>,
-    aws: Option<AwsConfig>,
-    local: Option<LocalConfig>,
+struct AppConfig {
+    server: ServerConfig,
+    storage: StorageConfig,
+    keys: KeysConfig,
+    cache: Option<CacheConfig>,
+    validation: Option<ValidationConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    gossip: Option<GossipConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rejection_log: Option<RejectionLogConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    log_list: Option<LogListConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sth_archive: Option<SthArchiveConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    compression: Option<CompressionConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    submission_auth: Option<SubmissionAuthConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tls: Option<TlsConfig>,
+    /// Witnesses whose cosignatures are recorded for every log served
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    witnesses: Option<Vec<WitnessConfig>>,
+    /// Logs served under /logs/{id} instead of the one described above, whose
+    /// [storage], [keys], [validation] and [gossip] then only serve the
+    /// epoch-report and verify-sth-archive commands
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    shards: Option<Vec<ShardConfig>>,
 }
 
+/// One of several logs served by this process, e.g. a temporal shard. The
+/// other sections of the config apply to every shard.
 #[derive(Debug, Deserialize, Serialize)]
-struct AzureConfig {
-    account: String,
-    access_key: String,
-    container_name: String,
+struct ShardConfig {
+    /// Path segment and `log` metrics label of the shard
+    id: String,
+    base_url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checkpoint_origin: Option<String>,
+    storage: StorageConfig,
+    keys: KeysConfig,
+    validation: Option<ValidationConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    gossip: Option<GossipConfig>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct AwsConfig {
-    region: String,
-    bucket: String,
-    access_key_id: String,
-    secret_access_key: String,
+/// The sections of the config that differ between the logs one process serves
+#[derive(Clone, Copy)]
+struct LogConfig<'a> {
+    base_url: &'a str,
+    checkpoint_origin: Option<&'a String>,
+    storage: &'a StorageConfig,
+    keys: &'a KeysConfig,
+    validation: Option<&'a ValidationConfig>,
+    gossip: Option<&'a GossipConfig>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct LocalConfig {
-    path: String,
+impl AppConfig {
+    /// The log described by the top-level sections
+    fn log(&self) -> LogConfig<'_> {
+        LogConfig {
+            base_url: &self.server.base_url,
+            checkpoint_origin: self.server.checkpoint_origin.as_ref(),
+            storage: &self.storage,
+            keys: &self.keys,
+            validation: self.validation.as_ref(),
+            gossip: self.gossip.as_ref(),
+        }
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct AppConfig {
-    server: ServerConfig,
-    storage: StorageConfig,
-    keys: KeysConfig,
-    cache: Option<CacheConfig>,
-    validation: Option<ValidationConfig>,
+impl ShardConfig {
+    fn log(&self) -> LogConfig<'_> {
+        LogConfig {
+            base_url: &self.base_url,
+            checkpoint_origin: self.checkpoint_origin.as_ref(),
+            storage: &self.storage,
+            keys: &self.keys,
+            validation: self.validation.as_ref(),
+            gossip: self.gossip.as_ref(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -89,6 +142,52 @@ struct ServerConfig {
     base_url: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     static_endpoint: Option<String>,
+    /// Origin line for /checkpoint notes; defaults to base_url without its scheme
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checkpoint_origin: Option<String>,
+    /// How often a new STH is signed in the background (defaults to 60)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sth_interval_secs: Option<u64>,
+    /// Most roots in one get-roots response (defaults to 1000)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_roots_per_page: Option<u64>,
+    /// get-roots order: "index" (default) or "fingerprint"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    roots_order: Option<String>,
+    /// Read requests served at once before shedding with 503 (unlimited if unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_concurrent_reads: Option<usize>,
+    /// add-chain and add-pre-chain requests served at once (unlimited if unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_concurrent_submissions: Option<usize>,
+    /// Check leaves served by get-entry-and-proof against the tree (defaults to false)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    verify_served_leaves: Option<bool>,
+    /// Entries the log accepts: "x509" (default) or "raw" byte strings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    entry_format: Option<String>,
+    /// Largest entry accepted in raw mode, in bytes (defaults to 65536, at most 16777215)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_entry_bytes: Option<usize>,
+    /// How far ahead of the clock an SCT timestamp may be when it is signed (defaults to 10)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sct_max_future_secs: Option<u64>,
+    /// How far behind the clock an SCT timestamp may be when it is signed (defaults to 30)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sct_max_past_secs: Option<u64>,
+    /// Serve the /debug proof verification endpoints (defaults to false)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    debug_endpoints: Option<bool>,
+    /// Most submissions committed to the tree in one batch (defaults to 2000)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_buffered: Option<usize>,
+    /// Longest a submission waits for its batch to fill, in milliseconds (defaults to 50)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    flush_interval_ms: Option<u64>,
+    /// Plain HTTP address serving the /admin endpoints, which no other
+    /// listener serves. Keep it reachable by operators only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    admin_bind_addr: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -97,7 +196,11 @@ struct KeysConfig {
     public_key_path: String,
 }
 
+/// Leaves per epoch of `compactlog epoch-report`, unless given
+const DEFAULT_ENTRIES_PER_EPOCH: u64 = 1 << 20;
+
 const DEFAULT_MEMORY_BLOCK_CACHE_CAPACITY_MB: u64 = 64; // 64 MB default
+const DEFAULT_PENDING_VALIDATIONS_PER_THREAD: usize = 8;
 
 #[derive(Debug, Deserialize, Serialize)]
 struct ValidationConfig {
@@ -105,10 +208,153 @@ struct ValidationConfig {
     ccadb: Option<String>, // "Production" or "Test"
     #[serde(default = "default_trusted_roots_dir")]
     trusted_roots_dir: String,
+    /// Roots for add-pre-chain only (defaults to trusted_roots_dir). Loaded at
+    /// startup and not updated from CCADB.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    precert_trusted_roots_dir: Option<String>,
     temporal_window_start: Option<String>,
     temporal_window_end: Option<String>,
     max_chain_length: Option<usize>,
+    /// Largest DER certificate accepted in a chain, in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_certificate_size: Option<usize>,
+    /// Signature verifications one submission may cost before it is rejected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_signature_verifications: Option<usize>,
+    /// Successful signature checks remembered across submissions, 0 to disable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature_cache_size: Option<usize>,
     allowed_signature_algorithms: Option<Vec<String>>,
+    /// Threads dedicated to chain validation (defaults to the number of CPUs)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    validation_threads: Option<usize>,
+    /// In-flight validations allowed before submissions get 429
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_pending_validations: Option<usize>,
+    /// "strict" (the default) or "permissive", which logs chains failing
+    /// only soft checks instead of rejecting them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    validation_mode: Option<String>,
+    /// Reject leaves outside their validity period when submitted (defaults to false)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    check_validity_period: Option<bool>,
+    /// Seconds before its notBefore a leaf is already accepted (defaults to 600)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    not_before_tolerance_secs: Option<u64>,
+    /// Seconds after its notAfter a leaf is still accepted (defaults to 600)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    not_after_tolerance_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct GossipConfig {
+    /// Base URLs of other instances of this log
+    peers: Vec<String>,
+    #[serde(default = "default_gossip_interval_secs")]
+    interval_secs: u64,
+}
+
+fn default_gossip_interval_secs() -> u64 {
+    60
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct RejectionLogConfig {
+    /// Records kept before the oldest are evicted
+    #[serde(default = "default_rejection_log_max_records")]
+    max_records: u64,
+    /// DER bytes of each rejected chain to keep; 0 keeps only its hash
+    #[serde(default)]
+    max_der_bytes: usize,
+    /// Records written per client address per minute
+    #[serde(default = "default_rejection_log_per_client_per_minute")]
+    per_client_per_minute: u32,
+}
+
+/// Restricts submissions to authenticated clients; reads stay public
+#[derive(Debug, Deserialize, Serialize)]
+struct SubmissionAuthConfig {
+    /// "bearer" for tokens or "mtls" for client certificates
+    mode: String,
+    /// Bearer mode: one `<principal> <hex SHA-256 of the token>` per line,
+    /// re-read on POST /admin/reload
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tokens_file: Option<String>,
+    /// mTLS mode: PEM bundle of the CAs client certificates must chain to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    client_ca_file: Option<String>,
+}
+
+/// Built-in HTTPS listener. With it, server.bind_addr serves only health and
+/// metrics endpoints over plain HTTP.
+#[derive(Debug, Deserialize, Serialize)]
+struct TlsConfig {
+    bind_addr: String,
+    /// PEM certificate chain, leaf first
+    cert_file: String,
+    /// PEM private key
+    key_file: String,
+    /// How often to check the certificate and key for changes, in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reload_interval_secs: Option<u64>,
+}
+
+/// A witness allowed to cosign the log's checkpoints
+#[derive(Debug, Deserialize, Serialize)]
+struct WitnessConfig {
+    /// The witness's `cosignature/v1` verifier key, as it publishes it:
+    /// `<name>+<hex key ID>+<base64 key>`
+    vkey: String,
+}
+
+fn default_rejection_log_max_records() -> u64 {
+    100_000
+}
+
+fn default_rejection_log_per_client_per_minute() -> u32 {
+    10
+}
+
+/// Publishes every signed STH to the storage bucket, see `sth_archive`
+#[derive(Debug, Deserialize, Serialize)]
+struct SthArchiveConfig {
+    /// Object path prefix of the archive entries
+    #[serde(default = "default_sth_archive_prefix")]
+    prefix: String,
+}
+
+fn default_sth_archive_prefix() -> String {
+    sth_archive::DEFAULT_STH_ARCHIVE_PREFIX.to_string()
+}
+
+/// zstd compression of stored leaves and certificates. Values written either
+/// way stay readable, so it can be turned on or off at any time.
+#[derive(Debug, Deserialize, Serialize)]
+struct CompressionConfig {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default = "default_compression_level")]
+    level: i32,
+}
+
+fn default_compression_level() -> i32 {
+    storage::DEFAULT_ZSTD_LEVEL
+}
+
+/// Operator details for /.well-known/ct-log-list.json
+#[derive(Debug, Deserialize, Serialize)]
+struct LogListConfig {
+    operator_name: String,
+    operator_email: String,
+    #[serde(default = "default_log_list_mmd_seconds")]
+    mmd_seconds: u64,
+    /// RFC 3339 time the log became usable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    usable_since: Option<String>,
+}
+
+fn default_log_list_mmd_seconds() -> u64 {
+    86400
 }
 
 fn default_trusted_roots_dir() -> String {
@@ -121,16 +367,25 @@ struct CacheConfig {
     max_cache_size_gb: Option<u64>,
     #[serde(default = "default_memory_block_cache_capacity_mb")]
     memory_block_cache_capacity_mb: u64,
+    /// Levels at the top of the Merkle tree loaded into the node cache on
+    /// startup, 0 to skip
+    #[serde(default = "default_warm_node_cache_levels")]
+    warm_node_cache_levels: u32,
 }
 
 fn default_memory_block_cache_capacity_mb() -> u64 {
     DEFAULT_MEMORY_BLOCK_CACHE_CAPACITY_MB
 }
 
-#[cfg(not(target_env = "msvc"))]
+fn default_warm_node_cache_levels() -> u32 {
+    merkle_storage::DEFAULT_WARM_CACHE_LEVELS
+}
+
+#[cfg(all(not(target_env = "msvc"), not(test)))]
 use tikv_jemallocator::Jemalloc;
 
-#[cfg(not(target_env = "msvc"))]
+// Tests install a counting allocator instead, see test_utils::alloc
+#[cfg(all(not(target_env = "msvc"), not(test)))]
 #[global_allocator]
 static GLOBAL: Jemalloc = Jemalloc;
 
@@ -138,6 +393,11 @@ static GLOBAL: Jemalloc = Jemalloc;
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing_subscriber::fmt::init();
 
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("audit") {
+        std::process::exit(audit::run_cli(&args[2..]).await);
+    }
+
     info!("Starting Certificate Transparency Log");
 
     metrics::init_metrics();
@@ -148,22 +408,185 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let background_runtime = Runtime::new().unwrap();
 
-    let (storage, _db_path, _object_store) = initialize_storage(
-        &config.storage,
-        &config.cache,
-        background_runtime.handle().clone(),
-    )
-    .await?;
+    if matches!(
+        args.get(1).map(String::as_str),
+        Some("epoch-report" | "verify-sth-archive")
+    ) {
+        return run_command(&args, &config, background_runtime.handle().clone()).await;
+    }
+
+    let server_tls = match &config.tls {
+        Some(tls_config) => Some(Arc::new(load_server_tls(
+            tls_config,
+            config.submission_auth.as_ref(),
+        )?)),
+        None => None,
+    };
+
+    let routers = match &config.shards {
+        Some(shards) => {
+            let mut registry = LogRegistry::new();
+            for shard in shards {
+                info!("Opening shard '{}'", shard.id);
+                // Shards count into metrics of their own, merged by the combined /metrics
+                let api_state = open_log(
+                    &config,
+                    shard.log(),
+                    server_tls.as_ref(),
+                    Arc::new(Metrics::new()),
+                    background_runtime.handle().clone(),
+                )
+                .await?;
+                registry.insert(shard.id.clone(), api_state)?;
+            }
+            info!("Serving {} logs under /logs/{{shard}}", shards.len());
+            registry.into_routers()
+        }
+        None => create_routers(
+            open_log(
+                &config,
+                config.log(),
+                server_tls.as_ref(),
+                metrics::global(),
+                background_runtime.handle().clone(),
+            )
+            .await?,
+        ),
+    };
+
+    let listener = TcpListener::bind(&bind_addr).await?;
+
+    let admin_listener = match &config.server.admin_bind_addr {
+        Some(admin_bind_addr) => {
+            info!("Serving admin endpoints on {}", admin_bind_addr);
+            Some(TcpListener::bind(admin_bind_addr).await?)
+        }
+        None => {
+            info!("No server.admin_bind_addr set, /admin endpoints are disabled");
+            None
+        }
+    };
+    let admin = routers.admin;
+    let serve_admin = async move {
+        match admin_listener {
+            Some(listener) => {
+                axum::serve(
+                    listener,
+                    admin.into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .await
+            }
+            None => Ok(()),
+        }
+    };
+
+    match (server_tls, &config.tls) {
+        (Some(server_tls), Some(tls_config)) => {
+            let reload_interval = tls_config
+                .reload_interval_secs
+                .map(|secs| Duration::from_secs(secs.max(1)))
+                .unwrap_or(DEFAULT_TLS_RELOAD_INTERVAL);
+            tokio::spawn(server_tls.clone().watch(reload_interval));
+
+            let tls_listener = std::net::TcpListener::bind(&tls_config.bind_addr)?;
+            tls_listener.set_nonblocking(true)?;
+
+            info!("Listening for HTTPS on {}", tls_config.bind_addr);
+            info!("Serving health and metrics on {}", bind_addr);
+            tokio::try_join!(
+                serve_tls(tls_listener, server_tls, routers.log),
+                async {
+                    axum::serve(
+                        listener,
+                        routers
+                            .operational
+                            .into_make_service_with_connect_info::<SocketAddr>(),
+                    )
+                    .await
+                },
+                serve_admin,
+            )?;
+        }
+        _ => {
+            info!("Listening on {}", bind_addr);
+
+            tokio::try_join!(
+                async {
+                    axum::serve(
+                        listener,
+                        routers
+                            .log
+                            .into_make_service_with_connect_info::<SocketAddr>(),
+                    )
+                    .await
+                },
+                serve_admin,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs `epoch-report` or `verify-sth-archive` against the log described by
+/// the top-level sections of the config
+async fn run_command(
+    args: &[String],
+    config: &AppConfig,
+    background_runtime: Handle,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let OpenedLog {
+        merkle_tree,
+        public_key_der,
+        archive,
+        ..
+    } = open_tree(config, config.log(), &metrics::global(), background_runtime).await?;
+
+    if args.get(1).map(String::as_str) == Some("epoch-report") {
+        let entries_per_epoch = match args.get(2) {
+            Some(arg) => arg
+                .parse()
+                .map_err(|_| format!("Invalid entries per epoch '{}'", arg))?,
+            None => DEFAULT_ENTRIES_PER_EPOCH,
+        };
+        let report = merkle_tree.epoch_usage(entries_per_epoch).await?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    if args.get(1).map(String::as_str) == Some("verify-sth-archive") {
+        let archive = archive.ok_or("verify-sth-archive needs an [sth_archive] section")?;
+        let report = sth_archive::verify_archive(&archive, &merkle_tree, &public_key_der).await?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        std::process::exit(if report.ok() { 0 } else { 1 });
+    }
+    Err(format!("Unknown command '{}'", args[1]).into())
+}
+
+/// A log's tree, opened from its storage and keys
+struct OpenedLog {
+    db: RateLimitedDb,
+    merkle_tree: StorageBackedMerkleTree,
+    private_key: SecretKey,
+    public_key_der: Vec<u8>,
+    archive: Option<Arc<SthArchive>>,
+}
+
+async fn open_tree(
+    config: &AppConfig,
+    log: LogConfig<'_>,
+    metrics: &Metrics,
+    background_runtime: Handle,
+) -> Result<OpenedLog, Box<dyn std::error::Error>> {
+    let (storage, _db_path, object_store) =
+        initialize_storage(log.storage, &config.cache, background_runtime).await?;
 
     // Load keys from config
-    let private_key = load_private_key(&config.keys.private_key_path)?;
+    let private_key = load_private_key(&log.keys.private_key_path)?;
     let public_key_der = derive_public_key_der_from_p256(&private_key);
-    let log_id = LogId::new(&public_key_der);
+    let log_id = LogId::from_public_key_der(&public_key_der);
 
     info!("Log ID: {}", log_id.to_hex());
 
-    let batch_config = BatchConfig::default();
-
     // Create rate limiter for warmup period (5 concurrent reads for 30 seconds)
     let read_rate_limiter = ReadRateLimiter::new(5, 30);
     let rate_limited_db = RateLimitedDb::new(storage.clone(), Some(read_rate_limiter.clone()));
@@ -171,19 +594,120 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("Creating merkle tree...");
     info!("DB initialized, attempting to create StorageBackedMerkleTree");
 
-    let merkle_tree = merkle_storage::StorageBackedMerkleTree::new(rate_limited_db.clone()).await?;
+    let warm_node_cache_levels = config
+        .cache
+        .as_ref()
+        .map_or(merkle_storage::DEFAULT_WARM_CACHE_LEVELS, |c| {
+            c.warm_node_cache_levels
+        });
+    let leaf_compression = match &config.compression {
+        Some(compression) if compression.enabled => Compression::Zstd(compression.level),
+        _ => Compression::None,
+    };
+    let merkle_tree = StorageBackedMerkleTree::open(
+        rate_limited_db.clone(),
+        warm_node_cache_levels,
+        leaf_compression,
+        metrics,
+    )
+    .await?;
     info!("StorageBackedMerkleTree created successfully");
 
     info!("Merkle tree created");
 
+    let archive = config.sth_archive.as_ref().map(|archive_config| {
+        Arc::new(SthArchive::new(
+            object_store.clone(),
+            &archive_config.prefix,
+        ))
+    });
+
+    Ok(OpenedLog {
+        db: rate_limited_db,
+        merkle_tree,
+        private_key,
+        public_key_der,
+        archive,
+    })
+}
+
+/// Opens the log described by `log` and starts its background tasks
+async fn open_log(
+    config: &AppConfig,
+    log: LogConfig<'_>,
+    server_tls: Option<&Arc<ServerTls>>,
+    metrics: Arc<Metrics>,
+    background_runtime: Handle,
+) -> Result<ApiState, Box<dyn std::error::Error>> {
+    let OpenedLog {
+        db,
+        merkle_tree,
+        private_key,
+        archive,
+        ..
+    } = open_tree(config, log, &metrics, background_runtime).await?;
+
+    let default_batch_config = BatchConfig::default();
+    let batch_config = BatchConfig {
+        max_batch_size: config
+            .server
+            .max_buffered
+            .unwrap_or(default_batch_config.max_batch_size)
+            .max(1),
+        max_batch_timeout_ms: config
+            .server
+            .flush_interval_ms
+            .unwrap_or(default_batch_config.max_batch_timeout_ms),
+    };
+    info!(
+        "Committing submissions in batches of up to {} every {}ms",
+        batch_config.max_batch_size, batch_config.max_batch_timeout_ms
+    );
+
     info!("Creating CT storage...");
 
-    let ct_storage = CtStorage::new(rate_limited_db, batch_config, merkle_tree.clone()).await?;
+    let rejection_log = match &config.rejection_log {
+        Some(rejection_config) => Some(
+            RejectionLog::open(
+                db.clone(),
+                rejection_config.max_records,
+                rejection_config.max_der_bytes,
+                rejection_config.per_client_per_minute,
+            )
+            .await?,
+        ),
+        None => None,
+    };
+
+    let witness_keys = config
+        .witnesses
+        .iter()
+        .flatten()
+        .map(|witness| WitnessKey::from_vkey(&witness.vkey))
+        .collect::<Result<Vec<_>, _>>()?;
+    if !witness_keys.is_empty() {
+        info!(
+            "Accepting cosignatures from {} witnesses",
+            witness_keys.len()
+        );
+    }
+
+    let ct_storage = CtStorage::new_with_metrics(db, batch_config, merkle_tree.clone(), metrics)
+        .await?
+        .with_witness_keys(witness_keys);
 
     info!("CT storage created");
 
+    let entry_format = parse_entry_format(config.server.entry_format.as_deref())?;
+    if entry_format == EntryFormat::Raw {
+        if log.validation.is_some_and(|v| v.enabled) {
+            return Err("Validation cannot be enabled with entry_format 'raw'".into());
+        }
+        info!("Accepting raw entries");
+    }
+
     // Initialize validation if configured
-    let (validator, validation_context) = if let Some(validation_config) = &config.validation {
+    let (validator, validation_context) = if let Some(validation_config) = log.validation {
         if !validation_config.enabled {
             info!("Validation is disabled in configuration");
             (None, None)
@@ -211,36 +735,47 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             // Initialize the shared root certificate store
             let root_store = RootCertificateStore::new();
 
-            // Parse temporal window if configured
-            let temporal_window = match (
-                &validation_config.temporal_window_start,
-                &validation_config.temporal_window_end,
-            ) {
-                (Some(start), Some(end)) => {
-                    use crate::validation::rfc6962_validator::TemporalWindow;
-                    use chrono::DateTime;
-
-                    let start_dt = DateTime::parse_from_rfc3339(start)
-                        .map_err(|e| format!("Failed to parse temporal_window_start: {}", e))?
-                        .with_timezone(&chrono::Utc);
-                    let end_dt = DateTime::parse_from_rfc3339(end)
-                        .map_err(|e| format!("Failed to parse temporal_window_end: {}", e))?
-                        .with_timezone(&chrono::Utc);
-
-                    Some(TemporalWindow {
-                        start: start_dt,
-                        end: end_dt,
-                    })
-                }
-                _ => None,
-            };
+            let temporal_window = parse_temporal_window(validation_config)?;
+            let mode = parse_validation_mode(validation_config.validation_mode.as_deref())?;
+            if mode == ValidationMode::Permissive {
+                tracing::warn!("Validation is permissive: chains failing soft checks are logged");
+            }
+
+            let clock_tolerance = validation_config
+                .check_validity_period
+                .unwrap_or(false)
+                .then(|| {
+                    let default_tolerance = ClockTolerance::default();
+                    ClockTolerance {
+                        not_before: validation_config
+                            .not_before_tolerance_secs
+                            .map_or(default_tolerance.not_before, Duration::from_secs),
+                        not_after: validation_config
+                            .not_after_tolerance_secs
+                            .map_or(default_tolerance.not_after, Duration::from_secs),
+                    }
+                });
 
+            let defaults = Rfc6962ValidationConfig::default();
             let rfc6962_config = Rfc6962ValidationConfig {
                 trusted_roots_dir: PathBuf::from(&validation_config.trusted_roots_dir),
                 ccadb: ccadb_env,
-                max_chain_length: validation_config.max_chain_length.unwrap_or(10),
+                max_chain_length: validation_config
+                    .max_chain_length
+                    .unwrap_or(defaults.max_chain_length),
+                max_certificate_size: validation_config
+                    .max_certificate_size
+                    .unwrap_or(defaults.max_certificate_size),
+                max_signature_verifications: validation_config
+                    .max_signature_verifications
+                    .unwrap_or(defaults.max_signature_verifications),
+                signature_cache_size: validation_config
+                    .signature_cache_size
+                    .unwrap_or(defaults.signature_cache_size),
                 temporal_window,
-                ..Default::default()
+                mode,
+                clock_tolerance,
+                ..defaults
             };
 
             // Load existing certificates from disk
@@ -254,30 +789,159 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             // Create validator with the shared root store
             let trusted_roots = root_store.get_all_certificates().await;
-            let validator =
-                Rfc6962Validator::with_trusted_roots(rfc6962_config.clone(), trusted_roots)?;
+            let mut validator = match &validation_config.allowed_signature_algorithms {
+                Some(algorithms) => Rfc6962Validator::new_with_signature_policy(
+                    rfc6962_config.clone(),
+                    trusted_roots,
+                    parse_signature_algorithms(algorithms)?,
+                )?,
+                None => {
+                    Rfc6962Validator::with_trusted_roots(rfc6962_config.clone(), trusted_roots)?
+                }
+            };
+            // CCADB updates rebuild the validator from this config
+            let rfc6962_config = validator.get_config().clone();
             info!(
                 "RFC 6962 validator initialized with {} trusted roots",
                 root_store.count().await
             );
 
-            (Some(validator), Some((root_store, rfc6962_config)))
+            if let Some(precert_dir) = validation_config
+                .precert_trusted_roots_dir
+                .as_ref()
+                .filter(|dir| **dir != validation_config.trusted_roots_dir)
+            {
+                let precert_store = RootCertificateStore::new();
+                precert_store
+                    .load_from_directory(StdPath::new(precert_dir))
+                    .await?;
+                validator =
+                    validator.with_precert_roots(precert_store.get_all_certificates().await)?;
+                info!(
+                    "Loaded {} precertificate root certificates from {}",
+                    precert_store.count().await,
+                    precert_dir
+                );
+            }
+
+            let validation_threads = validation_config
+                .validation_threads
+                .unwrap_or_else(num_cpus::get);
+            let max_pending_validations = validation_config
+                .max_pending_validations
+                .unwrap_or(validation_threads * DEFAULT_PENDING_VALIDATIONS_PER_THREAD);
+            let validation_pool = ValidationPool::new(validation_threads, max_pending_validations)?;
+            info!(
+                "Validation pool started with {} threads, {} max pending validations",
+                validation_threads, max_pending_validations
+            );
+
+            (
+                Some((validator, validation_pool)),
+                Some((root_store, rfc6962_config)),
+            )
         }
     } else {
         info!("No validation configured, running without certificate validation");
         (None, None)
     };
 
-    let private_key_bytes = private_key.to_bytes().to_vec();
     let api_state = ApiState::new(
         Arc::new(ct_storage),
         merkle_tree,
-        log_id,
-        private_key_bytes,
-        public_key_der,
-        config.server.base_url.clone(),
+        Box::new(SigningKey::from(&private_key)),
+        log.base_url.to_string(),
         validator,
     )?;
+    let api_state = match log.checkpoint_origin {
+        Some(origin) => {
+            info!("Using checkpoint origin '{}'", origin);
+            api_state.with_checkpoint_origin(origin.clone())
+        }
+        None => api_state,
+    };
+    let api_state = match config.server.max_roots_per_page {
+        Some(max_roots_per_page) => api_state.with_max_roots_per_page(max_roots_per_page),
+        None => api_state,
+    };
+    let api_state =
+        api_state.with_roots_order(parse_roots_order(config.server.roots_order.as_deref())?);
+    let api_state = api_state.with_concurrency_limits(
+        config.server.max_concurrent_reads,
+        config.server.max_concurrent_submissions,
+    );
+    let api_state =
+        api_state.with_leaf_verification(config.server.verify_served_leaves.unwrap_or(false));
+    let api_state = api_state.with_entry_format(entry_format);
+    let api_state = match config.server.max_entry_bytes {
+        Some(max_entry_bytes) if max_entry_bytes > MAX_ENTRY_BYTES_LIMIT => {
+            return Err(format!(
+                "max_entry_bytes {} exceeds the limit of {} bytes",
+                max_entry_bytes, MAX_ENTRY_BYTES_LIMIT
+            )
+            .into());
+        }
+        Some(max_entry_bytes) => api_state.with_max_entry_bytes(max_entry_bytes),
+        None => api_state,
+    };
+    let default_window = TimestampWindow::default();
+    let api_state = api_state.with_sct_timestamp_window(TimestampWindow {
+        max_future: config
+            .server
+            .sct_max_future_secs
+            .map_or(default_window.max_future, Duration::from_secs),
+        max_past: config
+            .server
+            .sct_max_past_secs
+            .map_or(default_window.max_past, Duration::from_secs),
+    });
+    let api_state = if config.server.debug_endpoints.unwrap_or(false) {
+        info!("Serving proof verification debug endpoints");
+        api_state.with_debug_endpoints(true)
+    } else {
+        api_state
+    };
+    let api_state = match server_tls {
+        Some(server_tls) => api_state.with_server_tls(server_tls.clone()),
+        None => api_state,
+    };
+    let api_state = match &config.submission_auth {
+        Some(auth_config) => api_state
+            .with_submission_auth(parse_submission_auth(auth_config, server_tls.is_some())?),
+        None => api_state,
+    };
+    let api_state = match rejection_log {
+        Some(rejection_log) => {
+            info!("Recording rejected submissions");
+            api_state.with_rejection_log(rejection_log)
+        }
+        None => api_state,
+    };
+
+    let api_state = match &config.log_list {
+        Some(log_list_config) => {
+            let temporal_window = match log.validation {
+                Some(validation_config) => parse_temporal_window(validation_config)?,
+                None => None,
+            };
+            let usable_since = log_list_config
+                .usable_since
+                .as_deref()
+                .map(chrono::DateTime::parse_from_rfc3339)
+                .transpose()
+                .map_err(|e| format!("Failed to parse log_list.usable_since: {}", e))?
+                .map(|t| t.with_timezone(&chrono::Utc));
+            info!("Serving /.well-known/ct-log-list.json");
+            api_state.with_log_metadata(LogMetadata {
+                operator_name: log_list_config.operator_name.clone(),
+                operator_email: log_list_config.operator_email.clone(),
+                mmd_seconds: log_list_config.mmd_seconds,
+                temporal_window,
+                usable_since,
+            })
+        }
+        None => api_state,
+    };
 
     // Start the CCADB worker if validation is enabled
     if let (Some(validator_lock), Some((root_store, rfc6962_config))) =
@@ -300,14 +964,191 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         });
     }
 
-    let app = create_router(api_state);
+    if let Some(gossip_config) = log.gossip.filter(|g| !g.peers.is_empty()) {
+        info!(
+            "Gossiping STHs to {} peers every {}s",
+            gossip_config.peers.len(),
+            gossip_config.interval_secs
+        );
+        let gossip_worker = GossipWorker::new(
+            gossip_config.peers.clone(),
+            api_state.merkle_tree.clone(),
+            api_state.sth_builder.clone(),
+            api_state.log_id.clone(),
+        );
+        tokio::spawn(
+            gossip_worker.run_periodic(Duration::from_secs(gossip_config.interval_secs.max(1))),
+        );
+    }
 
-    info!("Listening on {}", bind_addr);
+    // Refuse to start if the STHs this log signs would not verify under the
+    // key it publishes
+    let committed_root = api_state.merkle_tree.committed_root().await?;
+    let self_check_sth = api_state.sth_builder.create_sth(
+        committed_root.num_leaves(),
+        committed_root.as_bytes().to_vec(),
+        None,
+    )?;
+    verify_sth(&api_state.public_key_der, &self_check_sth.to_api_response())
+        .map_err(|e| format!("STH self-check failed: {}", e))?;
+    info!(
+        "STH self-check passed at tree size {}",
+        self_check_sth.tree_size
+    );
+
+    let sth_interval = config
+        .server
+        .sth_interval_secs
+        .map(|secs| Duration::from_secs(secs.max(1)))
+        .unwrap_or(DEFAULT_STH_SIGNING_INTERVAL);
+    info!(
+        "Signing the STH of every merge, and a new one every {}s while idle",
+        sth_interval.as_secs()
+    );
+    let sth_signing_task = SthSigningTask::new(
+        sth_interval,
+        api_state.sth_builder.clone(),
+        api_state.merkle_tree.clone(),
+        api_state.cached_sth.clone(),
+        api_state.metrics.clone(),
+    );
+    let sth_signing_task = match archive {
+        Some(archive) => {
+            info!("Publishing signed STHs to the STH archive");
+            sth_signing_task.with_archive(archive)
+        }
+        None => sth_signing_task,
+    };
+    api_state
+        .storage
+        .set_merge_observer(Arc::new(sth_signing_task.clone()));
+    tokio::spawn(sth_signing_task.run());
 
-    let listener = TcpListener::bind(&bind_addr).await?;
-    axum::serve(listener, app).await?;
+    Ok(api_state)
+}
 
-    Ok(())
+fn parse_temporal_window(
+    validation_config: &ValidationConfig,
+) -> Result<Option<TemporalWindow>, Box<dyn std::error::Error>> {
+    match (
+        &validation_config.temporal_window_start,
+        &validation_config.temporal_window_end,
+    ) {
+        (Some(start), Some(end)) => {
+            use chrono::DateTime;
+
+            let start_dt = DateTime::parse_from_rfc3339(start)
+                .map_err(|e| format!("Failed to parse temporal_window_start: {}", e))?
+                .with_timezone(&chrono::Utc);
+            let end_dt = DateTime::parse_from_rfc3339(end)
+                .map_err(|e| format!("Failed to parse temporal_window_end: {}", e))?
+                .with_timezone(&chrono::Utc);
+
+            Ok(Some(TemporalWindow {
+                start: start_dt,
+                end: end_dt,
+            }))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn parse_validation_mode(mode: Option<&str>) -> Result<ValidationMode, Box<dyn std::error::Error>> {
+    match mode.map(str::to_lowercase).as_deref() {
+        None | Some("strict") => Ok(ValidationMode::Strict),
+        Some("permissive") => Ok(ValidationMode::Permissive),
+        Some(other) => Err(format!(
+            "Invalid validation_mode '{}'. Must be 'strict' or 'permissive'",
+            other
+        )
+        .into()),
+    }
+}
+
+fn parse_roots_order(order: Option<&str>) -> Result<RootsOrder, Box<dyn std::error::Error>> {
+    match order.map(str::to_lowercase).as_deref() {
+        None | Some("index") => Ok(RootsOrder::Index),
+        Some("fingerprint") => Ok(RootsOrder::Fingerprint),
+        Some(other) => Err(format!(
+            "Invalid roots_order '{}'. Must be 'index' or 'fingerprint'",
+            other
+        )
+        .into()),
+    }
+}
+
+fn load_server_tls(
+    tls_config: &TlsConfig,
+    auth_config: Option<&SubmissionAuthConfig>,
+) -> Result<ServerTls, Box<dyn std::error::Error>> {
+    // Client certificates are only asked for when they identify submitters
+    let client_ca_file = auth_config
+        .filter(|auth_config| auth_config.mode.eq_ignore_ascii_case("mtls"))
+        .and_then(|auth_config| auth_config.client_ca_file.as_ref())
+        .map(PathBuf::from);
+    let server_tls = ServerTls::load(&tls_config.cert_file, &tls_config.key_file, client_ca_file)?;
+    info!("Loaded TLS certificate from {}", tls_config.cert_file);
+    Ok(server_tls)
+}
+
+fn parse_submission_auth(
+    auth_config: &SubmissionAuthConfig,
+    tls_enabled: bool,
+) -> Result<SubmissionAuth, Box<dyn std::error::Error>> {
+    match auth_config.mode.to_lowercase().as_str() {
+        "bearer" => {
+            let tokens_file = auth_config
+                .tokens_file
+                .as_deref()
+                .ok_or("submission_auth.tokens_file is required in bearer mode")?;
+            let auth = SubmissionAuth::bearer_tokens(tokens_file)?;
+            info!("Submissions require a bearer token from {}", tokens_file);
+            Ok(auth)
+        }
+        // Client certificates are verified during the TLS handshake
+        "mtls" => {
+            if !tls_enabled {
+                return Err("submission_auth mode 'mtls' needs the [tls] listener".into());
+            }
+            let client_ca_file = auth_config
+                .client_ca_file
+                .as_deref()
+                .ok_or("submission_auth.client_ca_file is required in mtls mode")?;
+            info!(
+                "Submissions require a client certificate issued by a CA in {}",
+                client_ca_file
+            );
+            Ok(SubmissionAuth::client_certificate())
+        }
+        other => Err(format!(
+            "Invalid submission_auth mode '{}'. Must be 'bearer' or 'mtls'",
+            other
+        )
+        .into()),
+    }
+}
+
+fn parse_entry_format(format: Option<&str>) -> Result<EntryFormat, Box<dyn std::error::Error>> {
+    match format.map(str::to_lowercase).as_deref() {
+        None | Some("x509") => Ok(EntryFormat::X509),
+        Some("raw") => Ok(EntryFormat::Raw),
+        Some(other) => {
+            Err(format!("Invalid entry_format '{}'. Must be 'x509' or 'raw'", other).into())
+        }
+    }
+}
+
+/// Parse `allowed_signature_algorithms`, given as dotted OIDs
+fn parse_signature_algorithms(
+    algorithms: &[String],
+) -> Result<Vec<SignatureAlgorithmOid>, Box<dyn std::error::Error>> {
+    algorithms
+        .iter()
+        .map(|oid| {
+            SignatureAlgorithmOid::new(oid)
+                .map_err(|e| format!("Invalid signature algorithm OID {}: {}", oid, e).into())
+        })
+        .collect()
 }
 
 async fn load_config() -> Result<AppConfig, Box<dyn std::error::Error>> {
@@ -323,6 +1164,9 @@ async fn load_config() -> Result<AppConfig, Box<dyn std::error::Error>> {
     let app_config: AppConfig = config.try_deserialize()?;
 
     ensure_keys_exist(&app_config.keys).await?;
+    for shard in app_config.shards.iter().flatten() {
+        ensure_keys_exist(&shard.keys).await?;
+    }
 
     Ok(app_config)
 }
@@ -333,11 +1177,28 @@ async fn initialize_config() -> Result<AppConfig, Box<dyn std::error::Error>> {
             bind_addr: "0.0.0.0:8080".to_string(),
             base_url: "http://localhost:8080/".to_string(),
             static_endpoint: None,
+            checkpoint_origin: None,
+            sth_interval_secs: None,
+            max_roots_per_page: None,
+            roots_order: None,
+            max_concurrent_reads: None,
+            max_concurrent_submissions: None,
+            verify_served_leaves: None,
+            entry_format: None,
+            max_entry_bytes: None,
+            sct_max_future_secs: None,
+            sct_max_past_secs: None,
+            debug_endpoints: None,
+            max_buffered: None,
+            flush_interval_ms: None,
+            admin_bind_addr: None,
         },
         storage: StorageConfig {
             provider: "local".to_string(),
+            prefix: None,
             azure: None,
             aws: None,
+            gcs: None,
             local: Some(LocalConfig {
                 path: "/tmp/ct-log-storage".to_string(),
             }),
@@ -351,11 +1212,30 @@ async fn initialize_config() -> Result<AppConfig, Box<dyn std::error::Error>> {
             enabled: true,
             ccadb: Some("Production".to_string()),
             trusted_roots_dir: "trusted_roots".to_string(),
+            precert_trusted_roots_dir: None,
             temporal_window_start: None,
             temporal_window_end: None,
             max_chain_length: Some(10),
+            max_certificate_size: None,
+            max_signature_verifications: None,
+            signature_cache_size: None,
             allowed_signature_algorithms: None,
+            validation_threads: None,
+            max_pending_validations: None,
+            validation_mode: None,
+            check_validity_period: None,
+            not_before_tolerance_secs: None,
+            not_after_tolerance_secs: None,
         }),
+        gossip: None,
+        rejection_log: None,
+        log_list: None,
+        sth_archive: None,
+        compression: None,
+        submission_auth: None,
+        tls: None,
+        witnesses: None,
+        shards: None,
     };
 
     fs::create_dir_all("keys")?;
@@ -467,60 +1347,13 @@ async fn initialize_storage(
         ..Default::default()
     };
 
-    let retry_config = RetryConfig {
-        backoff: BackoffConfig {
-            init_backoff: Duration::from_millis(10),
-            max_backoff: Duration::from_secs(1),
-            base: 2.0,
-        },
-        max_retries: usize::MAX,
-        retry_timeout: Duration::from_secs(20),
-    };
-
     let path = Path::from("ct_log");
-    let blob_store: Arc<dyn ObjectStore> = match storage_config.provider.as_str() {
-        "azure" => {
-            let azure_config = storage_config
-                .azure
-                .as_ref()
-                .ok_or("Azure configuration is required when provider is 'azure'")?;
-
-            Arc::new(
-                MicrosoftAzureBuilder::new()
-                    .with_account(&azure_config.account)
-                    .with_access_key(&azure_config.access_key)
-                    .with_container_name(&azure_config.container_name)
-                    .with_retry(retry_config)
-                    .build()?,
-            )
-        }
-        "aws" => {
-            let aws_config = storage_config
-                .aws
-                .as_ref()
-                .ok_or("AWS configuration is required when provider is 'aws'")?;
-
-            Arc::new(
-                AmazonS3Builder::new()
-                    .with_region(&aws_config.region)
-                    .with_bucket_name(&aws_config.bucket)
-                    .with_access_key_id(&aws_config.access_key_id)
-                    .with_secret_access_key(&aws_config.secret_access_key)
-                    .with_retry(retry_config)
-                    .build()?,
-            )
-        }
-        "local" => {
-            let local_config = storage_config
-                .local
-                .as_ref()
-                .ok_or("Local configuration is required when provider is 'local'")?;
-            Arc::new(LocalFileSystem::new_with_prefix(&local_config.path)?)
-        }
-        _ => {
-            return Err(format!("Unsupported storage provider: {}", storage_config.provider).into())
-        }
-    };
+    let blob_store = storage::build_object_store(storage_config)?;
+    storage::probe_object_store(blob_store.as_ref()).await?;
+    info!(
+        "Object store '{}' passed the startup probe",
+        storage_config.provider
+    );
 
     let db = Db::builder(path.clone(), blob_store.clone())
         .with_settings(db_options)
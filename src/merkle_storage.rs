@@ -1,14 +1,61 @@
+use crate::merkle_tree::ct_merkle_vendored::{leaf_hash, HashableLeaf};
+use crate::merkle_tree::slatedb_backed_tree::{EpochUsage, LeafAppend};
 use crate::merkle_tree::{ConsistencyProof, InclusionProof, RootHash, SlateDbBackedTree};
-use crate::storage::RateLimitedDb;
-use crate::types::{CtError, Result};
+use crate::metrics::Metrics;
+use crate::storage::{Compression, RateLimitedDb};
+use crate::types::{merkle_leaf::MerkleTreeLeaf, CtError, GetProofByHashResponse, Result};
 use sha2::Sha256;
 use std::sync::Arc;
+use std::time::Instant;
+
+/// Levels at the top of the tree loaded into the node cache on startup by
+/// default, up to 65,535 nodes
+pub const DEFAULT_WARM_CACHE_LEVELS: u32 = 16;
 
 #[derive(Clone, Debug)]
 pub struct Certificate {
     pub data: Vec<u8>, // MerkleTreeLeaf data, not raw certificate
 }
 
+impl Certificate {
+    /// Wrap encoded MerkleTreeLeaf bytes, rejecting anything that does not parse
+    /// as a well-formed leaf so malformed data never reaches the tree.
+    pub fn from_leaf_bytes(data: Vec<u8>) -> Result<Self> {
+        MerkleTreeLeaf::decode(&data)?;
+        Ok(Certificate { data })
+    }
+}
+
+impl Certificate {
+    /// Whether both certificates hash to the same Merkle leaf. The leaf covers
+    /// the entry and its timestamp but not the submitted chain, so entries that
+    /// differ only in `extra_data` compare equal. [`LeafHashEq`] builds on it
+    /// to key sets and maps of leaves. Submissions are not deduplicated with
+    /// it: a resubmitted certificate gets a new timestamp and so a new leaf,
+    /// which is why `add_chain` looks certificates up by their own hash.
+    pub fn leaf_hash_eq(&self, other: &Certificate) -> bool {
+        leaf_hash::<Sha256, _>(self) == leaf_hash::<Sha256, _>(other)
+    }
+}
+
+/// Wrapper that compares and hashes a certificate by its Merkle leaf hash
+#[derive(Clone, Debug)]
+pub struct LeafHashEq(pub Certificate);
+
+impl PartialEq for LeafHashEq {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.leaf_hash_eq(&other.0)
+    }
+}
+
+impl Eq for LeafHashEq {}
+
+impl std::hash::Hash for LeafHashEq {
+    fn hash<S: std::hash::Hasher>(&self, state: &mut S) {
+        std::hash::Hash::hash(&leaf_hash::<Sha256, _>(&self.0), state);
+    }
+}
+
 impl AsRef<[u8]> for Certificate {
     fn as_ref(&self) -> &[u8] {
         &self.data
@@ -34,30 +81,153 @@ impl<'de> serde::Deserialize<'de> for Certificate {
     }
 }
 
-/// Storage-backed Merkle tree using ct-merkle's SlateDbBackedTree
-#[derive(Clone)]
-pub struct StorageBackedMerkleTree {
-    tree: Arc<SlateDbBackedTree<Sha256, Certificate>>,
+/// Storage-backed Merkle tree using ct-merkle's SlateDbBackedTree. Leaves are
+/// CT [`Certificate`]s unless another [`HashableLeaf`] type is given.
+pub struct StorageBackedMerkleTree<T: HashableLeaf = Certificate> {
+    tree: Arc<SlateDbBackedTree<Sha256, T>>,
+}
+
+impl<T: HashableLeaf> Clone for StorageBackedMerkleTree<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tree: self.tree.clone(),
+        }
+    }
 }
 
 impl StorageBackedMerkleTree {
     pub async fn new(db: RateLimitedDb) -> Result<Self> {
-        let tree = SlateDbBackedTree::new(db).await.map_err(|e| {
+        Self::open(
+            db,
+            DEFAULT_WARM_CACHE_LEVELS,
+            Compression::None,
+            &crate::metrics::global(),
+        )
+        .await
+    }
+
+    /// Append encoded MerkleTreeLeaf values, rejecting the whole batch if any
+    /// of them does not parse
+    pub async fn batch_push_with_data(
+        &self,
+        cert_data_vec: Vec<Vec<u8>>,
+        additional_data: Vec<(Vec<u8>, Vec<u8>)>,
+    ) -> Result<u64> {
+        let certificates = cert_data_vec
+            .into_iter()
+            .map(Certificate::from_leaf_bytes)
+            .collect::<Result<Vec<_>>>()?;
+        self.push_leaves(certificates, additional_data).await
+    }
+
+    /// Check that `leaf_input`, about to be served for leaf `index`, is the
+    /// leaf the tree stores there and that the stored leaf hashes to its leaf
+    /// node, so a proof built from the nodes verifies for it
+    pub async fn verify_served_leaf(&self, index: u64, leaf_input: &[u8]) -> Result<()> {
+        let corrupted =
+            |reason: String| CtError::Storage(crate::storage::StorageError::Corrupted(reason));
+        let tree_error = |e: crate::merkle_tree::slatedb_backed_tree::SlateDbTreeError| {
             CtError::Storage(crate::storage::StorageError::InvalidFormat(format!(
-                "Failed to create SlateDbBackedTree: {:?}",
+                "Failed to verify leaf: {:#}",
+                e
+            )))
+        };
+
+        let stored = self
+            .tree
+            .get_range(index, index)
+            .await
+            .map_err(tree_error)?
+            .remove(0);
+        if stored.data != leaf_input {
+            return Err(corrupted(format!(
+                "Leaf {} differs from its log entry",
+                index
+            )));
+        }
+        if !self
+            .tree
+            .leaf_matches_node(index, &stored)
+            .await
+            .map_err(tree_error)?
+        {
+            return Err(corrupted(format!(
+                "Leaf {} does not hash to its stored leaf node",
+                index
+            )));
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    pub(crate) async fn overwrite_leaf(&self, index: u64, leaf: &Certificate) -> Result<()> {
+        self.tree
+            .overwrite_leaf(index, leaf)
+            .await
+            .map_err(|e| CtError::Internal(e.to_string()))
+    }
+}
+
+impl<T> StorageBackedMerkleTree<T>
+where
+    T: HashableLeaf + serde::Serialize + serde::de::DeserializeOwned + Send + Sync + 'static,
+{
+    /// Open the tree, first loading the top `warm_cache_levels` levels into
+    /// the node cache (none when `0`). Warming is best effort: a failure is
+    /// logged and the cache fills on demand instead. Leaves appended are
+    /// compressed with `leaf_compression`. Node cache lookups and the warming
+    /// itself are reported to `metrics`.
+    pub async fn open(
+        db: RateLimitedDb,
+        warm_cache_levels: u32,
+        leaf_compression: Compression,
+        metrics: &Metrics,
+    ) -> Result<Self> {
+        let mut tree = SlateDbBackedTree::new(db).await.map_err(|e| {
+            CtError::Storage(crate::storage::StorageError::InvalidFormat(format!(
+                "Failed to create SlateDbBackedTree: {:#}",
                 e
             )))
         })?;
+        tree.report_node_metrics(metrics);
+        tree.set_leaf_compression(leaf_compression);
+        // Logs written by older versions indexed leaf hashes in the storage layer
+        tree.set_legacy_hash_index(crate::storage::KeyPrefix::HASH_INDEX);
+
+        if warm_cache_levels > 0 {
+            let start = Instant::now();
+            match tree.warm_cache(warm_cache_levels).await {
+                Ok(loaded) => {
+                    let elapsed = start.elapsed();
+                    metrics.node_cache_warm_nodes.set(loaded as i64);
+                    metrics
+                        .node_cache_warm_duration_seconds
+                        .set(elapsed.as_secs_f64());
+                    tracing::info!(
+                        "Loaded {} upper tree nodes into the cache in {:?}",
+                        loaded,
+                        elapsed
+                    );
+                }
+                Err(e) => tracing::warn!("Failed to warm the node cache: {:#}", e),
+            }
+        }
 
         Ok(Self {
             tree: Arc::new(tree),
         })
     }
 
+    /// How appended leaves are compressed, which the storage layer also
+    /// applies to the certificates it stores
+    pub fn leaf_compression(&self) -> Compression {
+        self.tree.leaf_compression()
+    }
+
     pub async fn size(&self) -> Result<u64> {
         self.tree.len().await.map_err(|e| {
             CtError::Storage(crate::storage::StorageError::InvalidFormat(format!(
-                "Failed to get tree size: {:?}",
+                "Failed to get tree size: {:#}",
                 e
             )))
         })
@@ -67,7 +237,18 @@ impl StorageBackedMerkleTree {
     pub async fn committed_size(&self) -> Result<u64> {
         self.tree.get_committed_size().await.map_err(|e| {
             CtError::Storage(crate::storage::StorageError::InvalidFormat(format!(
-                "Failed to get committed tree size: {:?}",
+                "Failed to get committed tree size: {:#}",
+                e
+            )))
+        })
+    }
+
+    /// Key counts and sizes of the tree by epochs of `entries_per_epoch`
+    /// leaves, for tiering sealed epochs to colder storage
+    pub async fn epoch_usage(&self, entries_per_epoch: u64) -> Result<Vec<EpochUsage>> {
+        self.tree.epoch_usage(entries_per_epoch).await.map_err(|e| {
+            CtError::Storage(crate::storage::StorageError::InvalidFormat(format!(
+                "Failed to report epoch usage: {:#}",
                 e
             )))
         })
@@ -78,37 +259,39 @@ impl StorageBackedMerkleTree {
         let tree = self.tree.as_ref();
         tree.get_tile(level, index).await.map_err(|e| {
             CtError::Storage(crate::storage::StorageError::InvalidFormat(format!(
-                "Failed to get tile: {:?}",
+                "Failed to get tile: {:#}",
                 e
             )))
         })
     }
 
-    pub async fn batch_push_with_data(
+    /// Append `leaves` and write `additional_data` in the same batch,
+    /// returning the index of the first leaf
+    pub async fn push_leaves(
         &self,
-        cert_data_vec: Vec<Vec<u8>>,
+        leaves: Vec<T>,
         additional_data: Vec<(Vec<u8>, Vec<u8>)>,
     ) -> Result<u64> {
-        let certificates: Vec<Certificate> = cert_data_vec
-            .into_iter()
-            .map(|data| Certificate { data })
-            .collect();
-        let result = self
-            .tree
-            .batch_push_with_data(certificates, additional_data)
+        self.tree
+            .batch_push_with_data(leaves, additional_data)
             .await
             .map_err(|e| {
                 CtError::Storage(crate::storage::StorageError::InvalidFormat(format!(
-                    "Failed to batch push with data: {:?}",
+                    "Failed to batch push with data: {:#}",
                     e
                 )))
-            })?;
-
-        if let Ok(size) = self.size().await {
-            crate::metrics::MERKLE_TREE_SIZE.set(size as i64);
-        }
+            })
+    }
 
-        Ok(result)
+    /// Append the leaves not already in the tree, so an interrupted import
+    /// can be replayed from any earlier point
+    pub async fn append_if_absent(&self, leaves: Vec<T>) -> Result<Vec<LeafAppend>> {
+        self.tree.append_if_absent(leaves).await.map_err(|e| {
+            CtError::Storage(crate::storage::StorageError::InvalidFormat(format!(
+                "Failed to append absent leaves: {:#}",
+                e
+            )))
+        })
     }
 
     /// Get the root at the committed tree size (for STH generation)
@@ -116,17 +299,39 @@ impl StorageBackedMerkleTree {
         let committed_size = self.committed_size().await?;
         self.tree.root_at_size(committed_size).await.map_err(|e| {
             CtError::Storage(crate::storage::StorageError::InvalidFormat(format!(
-                "Failed to get root at committed size: {:?}",
+                "Failed to get root at committed size: {:#}",
                 e
             )))
         })
     }
 
+    /// Root hash at a previously published tree size
+    pub async fn root_at_size(&self, tree_size: u64) -> Result<RootHash<Sha256>> {
+        self.tree.root_at_size(tree_size).await.map_err(|e| {
+            let error_str = format!("{:#}", e);
+            if error_str.contains("not a published STH boundary")
+                || error_str.contains("exceeds current committed tree size")
+            {
+                CtError::BadRequest(error_str)
+            } else {
+                CtError::Storage(crate::storage::StorageError::InvalidFormat(format!(
+                    "Failed to get root at size {}: {:#}",
+                    tree_size, e
+                )))
+            }
+        })
+    }
+
     pub async fn prove_inclusion_efficient(
         &self,
         tree_size: u64,
         leaf_index: u64,
     ) -> Result<InclusionProof<Sha256>> {
+        if tree_size == 0 {
+            return Err(CtError::BadRequest(
+                "The empty tree has no inclusion proofs".into(),
+            ));
+        }
         if leaf_index >= tree_size {
             return Err(CtError::BadRequest(
                 "Leaf index out of bounds for requested tree size".into(),
@@ -147,7 +352,7 @@ impl StorageBackedMerkleTree {
             .prove_inclusion_at_size(leaf_index, tree_size)
             .await
             .map_err(|e| {
-                let error_str = format!("{:?}", e);
+                let error_str = format!("{:#}", e);
                 if error_str.contains("not a published STH boundary") {
                     CtError::BadRequest(format!(
                         "Tree size {} is not a published STH boundary",
@@ -155,7 +360,7 @@ impl StorageBackedMerkleTree {
                     ))
                 } else {
                     CtError::Storage(crate::storage::StorageError::InvalidFormat(format!(
-                        "Failed to prove inclusion: {:?}",
+                        "Failed to prove inclusion: {:#}",
                         e
                     )))
                 }
@@ -175,23 +380,7 @@ impl StorageBackedMerkleTree {
             ));
         }
 
-        if old_tree_size == new_tree_size {
-            return Ok(ConsistencyProof::from_digests(std::iter::empty()));
-        }
-
-        if old_tree_size == 0 {
-            return Err(CtError::BadRequest(
-                "Cannot produce consistency proof starting from empty tree".into(),
-            ));
-        }
-
         let current_tree_size = self.size().await?;
-        if old_tree_size > current_tree_size {
-            return Err(CtError::BadRequest(format!(
-                "Old tree size {} exceeds current tree size {}",
-                old_tree_size, current_tree_size
-            )));
-        }
         if new_tree_size > current_tree_size {
             return Err(CtError::BadRequest(format!(
                 "New tree size {} exceeds current tree size {}",
@@ -199,17 +388,23 @@ impl StorageBackedMerkleTree {
             )));
         }
 
+        // Any tree is consistent with itself and with the empty tree, and
+        // RFC 6962 clients expect an empty proof for both.
+        if old_tree_size == new_tree_size || old_tree_size == 0 {
+            return Ok(ConsistencyProof::from_digests(std::iter::empty()));
+        }
+
         let proof = self
             .tree
             .prove_consistency_between(old_tree_size, new_tree_size)
             .await
             .map_err(|e| {
-                let error_str = format!("{:?}", e);
+                let error_str = format!("{:#}", e);
                 if error_str.contains("not a published STH boundary") {
                     CtError::BadRequest(error_str)
                 } else {
                     CtError::Storage(crate::storage::StorageError::InvalidFormat(format!(
-                        "Failed to prove consistency: {:?}",
+                        "Failed to prove consistency: {:#}",
                         e
                     )))
                 }
@@ -218,11 +413,63 @@ impl StorageBackedMerkleTree {
         Ok(proof)
     }
 
+    /// Leaves `start..=end_inclusive`, in index order
+    pub async fn get_leaf_range(&self, start: u64, end_inclusive: u64) -> Result<Vec<T>> {
+        if end_inclusive < start {
+            return Err(CtError::BadRequest("Invalid range: start > end".into()));
+        }
+
+        let current_size = self.size().await?;
+        if end_inclusive >= current_size {
+            return Err(CtError::BadRequest(format!(
+                "Leaf index {} is beyond tree size {}",
+                end_inclusive, current_size
+            )));
+        }
+
+        self.tree
+            .get_range(start, end_inclusive)
+            .await
+            .map_err(|e| {
+                CtError::Storage(crate::storage::StorageError::InvalidFormat(format!(
+                    "Failed to get leaves {}..={}: {:#}",
+                    start, end_inclusive, e
+                )))
+            })
+    }
+
+    /// The last `count` committed leaves with their indices, newest first.
+    /// Returns the whole tree if it has fewer than `count` leaves.
+    pub async fn get_recent(&self, count: u64) -> Result<Vec<(u64, T)>> {
+        let tree_size = self.committed_size().await?;
+        let count = count.min(tree_size);
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+
+        let start = tree_size - count;
+        let leaves = self.get_leaf_range(start, tree_size - 1).await?;
+        Ok((start..tree_size)
+            .rev()
+            .zip(leaves.into_iter().rev())
+            .collect())
+    }
+
+    /// Index of the leaf with Merkle leaf hash `hash`, if it has been appended
+    pub async fn get_leaf_index_by_hash(&self, hash: &[u8]) -> Result<Option<u64>> {
+        self.tree.lookup_by_leaf_hash(hash).await.map_err(|e| {
+            CtError::Storage(crate::storage::StorageError::InvalidFormat(format!(
+                "Failed to look up leaf hash: {:#}",
+                e
+            )))
+        })
+    }
+
     /// Check if multiple keys exist in the underlying database
     pub async fn check_keys_exist(&self, keys: &[Vec<u8>]) -> Result<Vec<bool>> {
         self.tree.check_keys_exist(keys).await.map_err(|e| {
             CtError::Storage(crate::storage::StorageError::InvalidFormat(format!(
-                "Failed to check keys: {:?}",
+                "Failed to check keys: {:#}",
                 e
             )))
         })
@@ -239,7 +486,7 @@ impl StorageBackedMerkleTree {
             .await
             .map_err(|e| {
                 CtError::Storage(crate::storage::StorageError::InvalidFormat(format!(
-                    "Failed to get node hash at version: {:?}",
+                    "Failed to get node hash at version: {:#}",
                     e
                 )))
             })
@@ -249,32 +496,577 @@ impl StorageBackedMerkleTree {
 pub mod serialization {
     use super::*;
     use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+    use sha2::Digest;
 
-    pub fn inclusion_proof_to_audit_path(proof: &InclusionProof<Sha256>) -> Vec<String> {
-        // Get the raw proof bytes
-        let proof_bytes = proof.as_bytes();
-
-        // Each hash in the proof is 32 bytes (SHA256 output size)
-        const HASH_SIZE: usize = 32;
+    fn proof_to_path<H: Digest>(proof_bytes: &[u8]) -> Result<Vec<String>> {
+        let hash_size = <H as Digest>::output_size();
+        if proof_bytes.len() % hash_size != 0 {
+            return Err(CtError::Internal(format!(
+                "Proof of {} bytes is not a whole number of {}-byte hashes",
+                proof_bytes.len(),
+                hash_size
+            )));
+        }
 
-        // Split the proof bytes into individual hashes and encode each as base64
-        proof_bytes
-            .chunks(HASH_SIZE)
+        Ok(proof_bytes
+            .chunks_exact(hash_size)
             .map(|hash_bytes| BASE64.encode(hash_bytes))
-            .collect()
+            .collect())
     }
 
-    pub fn consistency_proof_to_path(proof: &ConsistencyProof<Sha256>) -> Vec<String> {
-        // Get the raw proof bytes
-        let proof_bytes = proof.as_bytes();
+    fn path_to_proof_bytes<H: Digest>(path: &[String]) -> Result<Vec<u8>> {
+        let hash_size = <H as Digest>::output_size();
+        let mut proof_bytes = Vec::with_capacity(path.len() * hash_size);
+        for (i, element) in path.iter().enumerate() {
+            let hash = BASE64.decode(element).map_err(|e| {
+                CtError::BadRequest(format!("Proof element {} is not valid base64: {}", i, e))
+            })?;
+            if hash.len() != hash_size {
+                return Err(CtError::BadRequest(format!(
+                    "Proof element {} is {} bytes, expected {}",
+                    i,
+                    hash.len(),
+                    hash_size
+                )));
+            }
+            proof_bytes.extend_from_slice(&hash);
+        }
+        Ok(proof_bytes)
+    }
 
-        // Each hash in the proof is 32 bytes (SHA256 output size)
-        const HASH_SIZE: usize = 32;
+    pub fn inclusion_proof_to_audit_path<H: Digest>(
+        proof: &InclusionProof<H>,
+    ) -> Result<Vec<String>> {
+        proof_to_path::<H>(proof.as_bytes())
+    }
 
-        // Split the proof bytes into individual hashes and encode each as base64
-        proof_bytes
-            .chunks(HASH_SIZE)
-            .map(|hash_bytes| BASE64.encode(hash_bytes))
-            .collect()
+    pub fn consistency_proof_to_path<H: Digest>(
+        proof: &ConsistencyProof<H>,
+    ) -> Result<Vec<String>> {
+        proof_to_path::<H>(proof.as_bytes())
+    }
+
+    /// Decode an `audit_path` array as served by get-proof-by-hash
+    pub fn audit_path_to_inclusion_proof<H: Digest>(path: &[String]) -> Result<InclusionProof<H>> {
+        InclusionProof::try_from_bytes(path_to_proof_bytes::<H>(path)?)
+            .map_err(|e| CtError::BadRequest(e.to_string()))
+    }
+
+    /// A get-proof-by-hash response, RFC 6962's `{leaf_index, audit_path}`
+    pub fn inclusion_proof_to_response<H: Digest>(
+        leaf_index: u64,
+        proof: &InclusionProof<H>,
+    ) -> Result<GetProofByHashResponse> {
+        Ok(GetProofByHashResponse {
+            leaf_index,
+            audit_path: inclusion_proof_to_audit_path(proof)?,
+        })
+    }
+
+    /// First line of a proof in [`RecordProof::to_tlog`]'s layout
+    const TLOG_RECORD_PROOF_HEADER: &str = "tlog-record-proof";
+
+    /// An inclusion proof with the leaf index and tree size it is for, which
+    /// tlog tooling expects alongside the hashes rather than from a separate
+    /// STH
+    pub struct RecordProof<H: Digest> {
+        pub leaf_index: u64,
+        pub tree_size: u64,
+        pub proof: InclusionProof<H>,
+    }
+
+    impl<H: Digest> RecordProof<H> {
+        /// Text layout with one field per line and the hashes leaf to root:
+        ///
+        /// ```text
+        /// tlog-record-proof
+        /// index 42
+        /// size 1234
+        /// <base64 hash>
+        /// ...
+        /// ```
+        pub fn to_tlog(&self) -> Result<String> {
+            let mut text = format!(
+                "{}\nindex {}\nsize {}\n",
+                TLOG_RECORD_PROOF_HEADER, self.leaf_index, self.tree_size
+            );
+            for hash in inclusion_proof_to_audit_path(&self.proof)? {
+                text.push_str(&hash);
+                text.push('\n');
+            }
+            Ok(text)
+        }
+
+        /// Parse a proof written by [`Self::to_tlog`]
+        pub fn from_tlog(text: &str) -> Result<Self> {
+            let mut lines = text.lines();
+            if lines.next() != Some(TLOG_RECORD_PROOF_HEADER) {
+                return Err(CtError::BadRequest(
+                    "Record proof does not start with its header".into(),
+                ));
+            }
+            let mut field = |name: &str| -> Result<u64> {
+                lines
+                    .next()
+                    .and_then(|line| line.strip_prefix(name)?.strip_prefix(' '))
+                    .and_then(|value| value.parse().ok())
+                    .ok_or_else(|| {
+                        CtError::BadRequest(format!("Record proof is missing its {} line", name))
+                    })
+            };
+            let leaf_index = field("index")?;
+            let tree_size = field("size")?;
+            if leaf_index >= tree_size {
+                return Err(CtError::BadRequest(format!(
+                    "Record proof index {} is outside tree size {}",
+                    leaf_index, tree_size
+                )));
+            }
+
+            let path: Vec<String> = lines.map(str::to_string).collect();
+            Ok(Self {
+                leaf_index,
+                tree_size,
+                proof: audit_path_to_inclusion_proof(&path)?,
+            })
+        }
+    }
+
+    /// Decode a `consistency` array as served by get-sth-consistency
+    pub fn path_to_consistency_proof<H: Digest>(path: &[String]) -> Result<ConsistencyProof<H>> {
+        ConsistencyProof::try_from_bytes(path_to_proof_bytes::<H>(path)?)
+            .map_err(|e| CtError::BadRequest(e.to_string()))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use sha2::Sha512;
+
+        fn digests() -> Vec<digest::Output<Sha256>> {
+            (0u8..3).map(|i| Sha256::digest([i])).collect()
+        }
+
+        #[test]
+        fn test_round_trip() {
+            let inclusion = InclusionProof::<Sha256>::from_digests(digests().iter());
+            let path = inclusion_proof_to_audit_path(&inclusion).unwrap();
+            assert_eq!(path.len(), 3);
+            assert_eq!(
+                audit_path_to_inclusion_proof::<Sha256>(&path)
+                    .unwrap()
+                    .as_bytes(),
+                inclusion.as_bytes()
+            );
+
+            let consistency = ConsistencyProof::<Sha256>::from_digests(digests().iter());
+            let path = consistency_proof_to_path(&consistency).unwrap();
+            assert_eq!(
+                path_to_consistency_proof::<Sha256>(&path)
+                    .unwrap()
+                    .as_bytes(),
+                consistency.as_bytes()
+            );
+
+            assert!(audit_path_to_inclusion_proof::<Sha256>(&[])
+                .unwrap()
+                .as_bytes()
+                .is_empty());
+        }
+
+        #[test]
+        fn test_path_follows_digest_size() {
+            let digests: Vec<_> = (0u8..4).map(|i| Sha512::digest([i])).collect();
+            let proof = InclusionProof::<Sha512>::from_digests(digests.iter());
+
+            let path = inclusion_proof_to_audit_path(&proof).unwrap();
+            assert_eq!(path.len(), 4);
+            assert_eq!(BASE64.decode(&path[0]).unwrap().len(), 64);
+            assert_eq!(
+                audit_path_to_inclusion_proof::<Sha512>(&path)
+                    .unwrap()
+                    .as_bytes(),
+                proof.as_bytes()
+            );
+
+            // Four SHA-512 hashes are not a valid SHA-256 path
+            assert!(audit_path_to_inclusion_proof::<Sha256>(&path).is_err());
+        }
+
+        #[test]
+        fn test_truncated_proof_is_rejected() {
+            let mut bytes = InclusionProof::<Sha256>::from_digests(digests().iter())
+                .as_bytes()
+                .to_vec();
+            bytes.pop();
+
+            assert!(matches!(
+                proof_to_path::<Sha256>(&bytes),
+                Err(CtError::Internal(_))
+            ));
+        }
+
+        #[test]
+        fn test_tlog_record_proof_round_trip() {
+            let record = RecordProof {
+                leaf_index: 5,
+                tree_size: 8,
+                proof: InclusionProof::<Sha256>::from_digests(digests().iter()),
+            };
+            let text = record.to_tlog().unwrap();
+            assert_eq!(text.lines().count(), 6);
+            assert!(text.starts_with("tlog-record-proof\nindex 5\nsize 8\n"));
+
+            let parsed = RecordProof::<Sha256>::from_tlog(&text).unwrap();
+            assert_eq!(parsed.leaf_index, 5);
+            assert_eq!(parsed.tree_size, 8);
+            assert_eq!(parsed.proof.as_bytes(), record.proof.as_bytes());
+
+            for malformed in [
+                "",
+                "index 5\nsize 8\n",
+                "tlog-record-proof\nsize 8\nindex 5\n",
+                "tlog-record-proof\nindex 8\nsize 8\n",
+                "tlog-record-proof\nindex five\nsize 8\n",
+            ] {
+                assert!(matches!(
+                    RecordProof::<Sha256>::from_tlog(malformed),
+                    Err(CtError::BadRequest(_))
+                ));
+            }
+        }
+
+        #[test]
+        fn test_wrong_size_element_is_rejected() {
+            let mut path: Vec<String> = digests().iter().map(|d| BASE64.encode(d)).collect();
+            path[1] = BASE64.encode([0u8; 31]);
+            assert!(matches!(
+                audit_path_to_inclusion_proof::<Sha256>(&path),
+                Err(CtError::BadRequest(_))
+            ));
+
+            path[1] = "not base64!".into();
+            assert!(matches!(
+                path_to_consistency_proof::<Sha256>(&path),
+                Err(CtError::BadRequest(_))
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::test_utils::utils::create_test_certificate_with_serial;
+    use crate::types::{LogEntry, LogEntryType};
+    use object_store::memory::InMemory;
+    use slatedb::Db;
+
+    const TREE_SIZE: u64 = 8;
+
+    /// Tree of `size` certificates, each appended in a batch of its own so
+    /// every size from 1 up is a published STH boundary
+    pub(crate) async fn create_test_tree(size: u64) -> StorageBackedMerkleTree {
+        let object_store = Arc::new(InMemory::new());
+        let db = Arc::new(Db::open("test", object_store).await.unwrap());
+        let tree = StorageBackedMerkleTree::new(RateLimitedDb::new(db, None))
+            .await
+            .unwrap();
+
+        for i in 0..size {
+            let leaf = LogEntry::compute_leaf_data_with_index(
+                &create_test_certificate_with_serial(i as u8 + 1),
+                LogEntryType::X509Entry,
+                None,
+                chrono::Utc::now(),
+                i,
+            );
+            tree.batch_push_with_data(vec![leaf], vec![]).await.unwrap();
+        }
+
+        tree
+    }
+
+    #[tokio::test]
+    async fn test_get_leaf_range() {
+        let object_store = Arc::new(InMemory::new());
+        let db = Arc::new(Db::open("test", object_store).await.unwrap());
+        let tree = StorageBackedMerkleTree::new(RateLimitedDb::new(db, None))
+            .await
+            .unwrap();
+
+        let leaves: Vec<Vec<u8>> = (0..100u64)
+            .map(|i| {
+                LogEntry::compute_leaf_data_with_index(
+                    &create_test_certificate_with_serial(i as u8),
+                    LogEntryType::X509Entry,
+                    None,
+                    chrono::Utc::now(),
+                    i,
+                )
+            })
+            .collect();
+        tree.batch_push_with_data(leaves.clone(), vec![])
+            .await
+            .unwrap();
+
+        for (start, end) in [(0, 0), (0, 99), (17, 42), (50, 81), (99, 99)] {
+            let range = tree.get_leaf_range(start, end).await.unwrap();
+            let data: Vec<&[u8]> = range.iter().map(|c| c.data.as_slice()).collect();
+            let expected: Vec<&[u8]> = leaves[start as usize..=end as usize]
+                .iter()
+                .map(Vec::as_slice)
+                .collect();
+            assert_eq!(data, expected, "range {}..={}", start, end);
+        }
+
+        assert!(matches!(
+            tree.get_leaf_range(5, 4).await,
+            Err(CtError::BadRequest(_))
+        ));
+        assert!(matches!(
+            tree.get_leaf_range(90, 100).await,
+            Err(CtError::BadRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_get_recent() {
+        let tree = create_test_tree(TREE_SIZE).await;
+        assert!(tree.get_recent(0).await.unwrap().is_empty());
+
+        let recent = tree.get_recent(3).await.unwrap();
+        let indices: Vec<u64> = recent.iter().map(|(i, _)| *i).collect();
+        assert_eq!(indices, vec![7, 6, 5]);
+        let expected = tree.get_leaf_range(5, 7).await.unwrap();
+        for ((_, leaf), expected) in recent.iter().zip(expected.iter().rev()) {
+            assert_eq!(leaf.data, expected.data);
+        }
+
+        let leaf = LogEntry::compute_leaf_data_with_index(
+            &create_test_certificate_with_serial(100),
+            LogEntryType::X509Entry,
+            None,
+            chrono::Utc::now(),
+            TREE_SIZE,
+        );
+        tree.batch_push_with_data(vec![leaf.clone()], vec![])
+            .await
+            .unwrap();
+        let recent = tree.get_recent(1).await.unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].0, TREE_SIZE);
+        assert_eq!(recent[0].1.data, leaf);
+
+        // Clamped to the tree size
+        let all = tree.get_recent(1_000).await.unwrap();
+        assert_eq!(all.len() as u64, TREE_SIZE + 1);
+        assert_eq!(all.last().unwrap().0, 0);
+    }
+
+    /// A leaf that is not a CT entry
+    #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct SignedStatement {
+        statement: String,
+        signature: Vec<u8>,
+    }
+
+    impl HashableLeaf for SignedStatement {
+        fn hash<H: digest::Update>(&self, hasher: &mut H) {
+            hasher.update(self.statement.as_bytes());
+            hasher.update(&self.signature);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_custom_leaf_type() {
+        let object_store = Arc::new(InMemory::new());
+        let db = Arc::new(Db::open("test", object_store).await.unwrap());
+        let tree: StorageBackedMerkleTree<SignedStatement> = StorageBackedMerkleTree::open(
+            RateLimitedDb::new(db, None),
+            0,
+            Compression::None,
+            &Metrics::new(),
+        )
+        .await
+        .unwrap();
+
+        let statements: Vec<SignedStatement> = (0..5u8)
+            .map(|i| SignedStatement {
+                statement: format!("statement {}", i),
+                signature: vec![i; 64],
+            })
+            .collect();
+        assert_eq!(
+            tree.push_leaves(statements[..2].to_vec(), vec![])
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            tree.push_leaves(statements[2..].to_vec(), vec![])
+                .await
+                .unwrap(),
+            2
+        );
+
+        assert_eq!(tree.get_leaf_range(0, 4).await.unwrap(), statements);
+        let root = tree.committed_root().await.unwrap();
+        assert_eq!(root.num_leaves(), 5);
+        for (index, statement) in statements.iter().enumerate() {
+            let hash = leaf_hash::<Sha256, _>(statement);
+            assert_eq!(
+                tree.get_leaf_index_by_hash(&hash).await.unwrap(),
+                Some(index as u64)
+            );
+            let proof = tree
+                .prove_inclusion_efficient(5, index as u64)
+                .await
+                .unwrap();
+            proof.verify(statement, index as u64, &root).unwrap();
+        }
+        let proof = tree.consistency_proof_between_sizes(2, 5).await.unwrap();
+        proof
+            .verify(&tree.root_at_size(2).await.unwrap(), &root)
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_warmed_cache_saves_reads() {
+        const NUM_LEAVES: u64 = 10_000;
+
+        let object_store = Arc::new(InMemory::new());
+        let db = Arc::new(Db::open("test", object_store).await.unwrap());
+        let tree = StorageBackedMerkleTree::open(
+            RateLimitedDb::new(db.clone(), None),
+            0,
+            Compression::None,
+            &Metrics::new(),
+        )
+        .await
+        .unwrap();
+        let cert = create_test_certificate_with_serial(1);
+        let leaves: Vec<Vec<u8>> = (0..NUM_LEAVES)
+            .map(|i| {
+                LogEntry::compute_leaf_data_with_index(
+                    &cert,
+                    LogEntryType::X509Entry,
+                    None,
+                    chrono::Utc::now(),
+                    i,
+                )
+            })
+            .collect();
+        tree.batch_push_with_data(leaves, vec![]).await.unwrap();
+
+        // Node cache misses while proving a spread of leaves after a restart
+        async fn proof_misses(db: &Arc<Db>, warm_cache_levels: u32) -> (u64, Metrics) {
+            let metrics = Metrics::new();
+            let tree: StorageBackedMerkleTree = StorageBackedMerkleTree::open(
+                RateLimitedDb::new(db.clone(), None),
+                warm_cache_levels,
+                Compression::None,
+                &metrics,
+            )
+            .await
+            .unwrap();
+            for leaf_index in (0..NUM_LEAVES).step_by(997) {
+                tree.prove_inclusion_efficient(NUM_LEAVES, leaf_index)
+                    .await
+                    .unwrap();
+            }
+            let misses = metrics.cache_misses.with_label_values(&["node"]).get();
+            (misses, metrics)
+        }
+
+        let (cold_misses, cold_metrics) = proof_misses(&db, 0).await;
+        assert!(cold_misses > 0);
+        assert_eq!(cold_metrics.node_cache_warm_nodes.get(), 0);
+
+        // 10,000 leaves put the root at level 14, so the default covers the
+        // whole tree and every proof node is already cached
+        let (warm_misses, warm_metrics) = proof_misses(&db, DEFAULT_WARM_CACHE_LEVELS).await;
+        assert_eq!(warm_misses, 0);
+        assert_eq!(
+            warm_metrics.node_cache_warm_nodes.get() as u64,
+            2 * NUM_LEAVES - 1
+        );
+
+        // Only the top few levels still saves the reads through them
+        let (partial_misses, _) = proof_misses(&db, 6).await;
+        assert!(partial_misses < cold_misses);
+    }
+
+    #[tokio::test]
+    async fn test_consistency_proof_parameter_matrix() {
+        let tree = create_test_tree(TREE_SIZE).await;
+
+        // (first, second, expected proof length, None meaning 400)
+        let cases: &[(u64, u64, Option<usize>)] = &[
+            (0, 0, Some(0)),
+            (0, 1, Some(0)),
+            (0, TREE_SIZE, Some(0)),
+            (1, 1, Some(0)),
+            (TREE_SIZE, TREE_SIZE, Some(0)),
+            (1, 2, Some(1)),
+            (3, 7, Some(4)),
+            (4, TREE_SIZE, Some(1)),
+            (1, TREE_SIZE, Some(3)),
+            (2, 1, None),
+            (TREE_SIZE, 1, None),
+            (0, TREE_SIZE + 1, None),
+            (TREE_SIZE, TREE_SIZE + 1, None),
+            (TREE_SIZE + 1, TREE_SIZE + 1, None),
+        ];
+
+        for &(first, second, expected) in cases {
+            let result = tree.consistency_proof_between_sizes(first, second).await;
+            match expected {
+                Some(len) => {
+                    let proof = result.unwrap_or_else(|e| {
+                        panic!("first={} second={} failed: {}", first, second, e)
+                    });
+                    assert_eq!(
+                        serialization::consistency_proof_to_path(&proof)
+                            .unwrap()
+                            .len(),
+                        len,
+                        "first={} second={}",
+                        first,
+                        second
+                    );
+                }
+                None => assert!(
+                    matches!(result, Err(CtError::BadRequest(_))),
+                    "first={} second={} should be a bad request",
+                    first,
+                    second
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn test_leaf_hash_eq() {
+        let timestamp = chrono::Utc::now();
+        let leaf = |serial: u8| {
+            Certificate::from_leaf_bytes(LogEntry::compute_leaf_data_with_index(
+                &create_test_certificate_with_serial(serial),
+                LogEntryType::X509Entry,
+                None,
+                timestamp,
+                0,
+            ))
+            .unwrap()
+        };
+
+        assert!(leaf(1).leaf_hash_eq(&leaf(1)));
+        assert!(!leaf(1).leaf_hash_eq(&leaf(2)));
+
+        let set: std::collections::HashSet<_> = [leaf(1), leaf(1), leaf(2)]
+            .into_iter()
+            .map(LeafHashEq)
+            .collect();
+        assert_eq!(set.len(), 2);
     }
 }
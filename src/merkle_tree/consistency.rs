@@ -6,6 +6,21 @@ use crate::merkle_tree::ct_merkle_vendored::{InternalIdx, LeafIdx};
 /// # Panics
 /// Panics if `num_oldtree_leaves == 0` or `num_oldtree_leaves + num_additions > ⌊u64::MAX / 2⌋ + 1`.
 pub fn indices_for_consistency_proof(num_oldtree_leaves: u64, num_additions: u64) -> Vec<u64> {
+    let mut indices = Vec::new();
+    visit_consistency_proof_indices(num_oldtree_leaves, num_additions, |idx| indices.push(idx));
+    indices
+}
+
+/// Produces the same indices as [`indices_for_consistency_proof`], in the same order, but hands
+/// each one to `visit` as soon as it is computed so callers can start fetching early.
+///
+/// # Panics
+/// Same conditions as [`indices_for_consistency_proof`].
+pub fn visit_consistency_proof_indices(
+    num_oldtree_leaves: u64,
+    num_additions: u64,
+    mut visit: impl FnMut(u64),
+) {
     if num_oldtree_leaves == 0 {
         panic!("cannot produce a consistency proof starting from an empty tree");
     }
@@ -19,46 +34,44 @@ pub fn indices_for_consistency_proof(num_oldtree_leaves: u64, num_additions: u64
     let num_newtree_leaves = num_oldtree_leaves + num_additions;
 
     if num_oldtree_leaves == num_newtree_leaves {
-        return Vec::new();
+        return;
     }
 
-    subproof(num_oldtree_leaves, num_newtree_leaves, true)
+    visit_subproof(num_oldtree_leaves, num_newtree_leaves, true, 0, &mut visit);
 }
 
 /// RFC 6962 SUBPROOF algorithm
+#[cfg(test)]
 pub(crate) fn subproof(m: u64, n: u64, b: bool) -> Vec<u64> {
-    subproof_with_offset(m, n, b, 0)
+    let mut result = Vec::new();
+    visit_subproof(m, n, b, 0, &mut |idx| result.push(idx));
+    result
 }
 
 /// RFC 6962 SUBPROOF algorithm with offset tracking to handle slices correctly
-fn subproof_with_offset(m: u64, n: u64, b: bool, offset: u64) -> Vec<u64> {
+fn visit_subproof(m: u64, n: u64, b: bool, offset: u64, visit: &mut impl FnMut(u64)) {
     if m == n {
-        return if b {
-            vec![]
-        } else {
-            vec![compute_subtree_root(offset, offset + m).as_u64()]
-        };
+        if !b {
+            visit(compute_subtree_root(offset, offset + m).as_u64());
+        }
+        return;
     }
 
     let k = largest_power_of_two_less_than(n);
 
     if m <= k {
         // SUBPROOF(m, D[0:k], b) : MTH(D[k:n])
-        let mut result = subproof_with_offset(m, k, b, offset);
-        let subtree_root = compute_subtree_root(offset + k, offset + n);
-        result.push(subtree_root.as_u64());
-        result
+        visit_subproof(m, k, b, offset, visit);
+        visit(compute_subtree_root(offset + k, offset + n).as_u64());
     } else {
         // SUBPROOF(m - k, D[k:n], false) : MTH(D[0:k])
-        let mut result = subproof_with_offset(m - k, n - k, false, offset + k);
-        let subtree_root = compute_subtree_root(offset, offset + k);
-        result.push(subtree_root.as_u64());
-        result
+        visit_subproof(m - k, n - k, false, offset + k, visit);
+        visit(compute_subtree_root(offset, offset + k).as_u64());
     }
 }
 
 /// Compute the root of a subtree containing leaves [start, end)
-pub fn compute_subtree_root(start: u64, end: u64) -> InternalIdx {
+pub(crate) fn compute_subtree_root(start: u64, end: u64) -> InternalIdx {
     let size = end - start;
 
     if size == 1 {
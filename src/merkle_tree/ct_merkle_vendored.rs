@@ -28,9 +28,34 @@ CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 */
 
 use digest::Digest;
+use std::fmt;
 use std::marker::PhantomData;
 use subtle::ConstantTimeEq;
 
+// ============================================================================
+// From error.rs
+// ============================================================================
+
+/// An error representing what went wrong when decoding a proof from bytes
+// Modification: only the proof case of ct-merkle's `DecodingError` is kept
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodingError {
+    /// The proof length is not a multiple of the digest size
+    MalformedProof,
+}
+
+impl fmt::Display for DecodingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodingError::MalformedProof => {
+                write!(f, "proof length is not a multiple of the digest size")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodingError {}
+
 // ============================================================================
 // From types.rs
 // ============================================================================
@@ -67,6 +92,18 @@ impl<H: Digest> RootHash<H> {
     pub fn num_leaves(&self) -> u64 {
         self.num_leaves
     }
+
+    // Modification: verifies from the leaf hash, for clients that were only
+    // given the hash rather than the leaf
+    /// Verifies that the leaf with RFC 6962 leaf hash `leaf_hash` is at `leaf_idx` in this tree.
+    pub fn verify_inclusion(
+        &self,
+        leaf_hash: &digest::Output<H>,
+        leaf_idx: u64,
+        proof: &InclusionProof<H>,
+    ) -> Result<(), &'static str> {
+        proof.verify_leaf_hash(leaf_hash.clone(), leaf_idx, self)
+    }
 }
 
 /// Represents a leaf that can be included in a Merkle tree. This only requires that the leaf have a
@@ -311,6 +348,22 @@ impl<H: Digest> InclusionProof<H> {
             _marker: PhantomData,
         }
     }
+
+    /// Constructs an `InclusionProof` from its byte representation.
+    ///
+    /// # Errors
+    /// Returns [`DecodingError::MalformedProof`] if `bytes.len()` is not a multiple of the digest
+    /// size.
+    pub fn try_from_bytes(bytes: Vec<u8>) -> Result<Self, DecodingError> {
+        if bytes.len() % <H as Digest>::output_size() != 0 {
+            return Err(DecodingError::MalformedProof);
+        }
+
+        Ok(InclusionProof {
+            proof: bytes,
+            _marker: PhantomData,
+        })
+    }
 }
 
 /// Given a tree size and index, produces a list of tree node indices whose values we need in order
@@ -359,6 +412,34 @@ pub fn indices_for_inclusion_proof(num_leaves: u64, idx: u64) -> Vec<u64> {
     out
 }
 
+/// Size in bytes of the inclusion proof for leaf `idx` in a tree of `num_leaves`, computed from
+/// the sizes alone.
+///
+/// # Panics
+/// Same conditions as [`indices_for_inclusion_proof`].
+pub fn inclusion_proof_size<H: Digest>(num_leaves: u64, idx: u64) -> usize {
+    indices_for_inclusion_proof(num_leaves, idx).len() * <H as Digest>::output_size()
+}
+
+/// Size in bytes of the consistency proof between trees of `old_size` and `new_size` leaves,
+/// computed from the sizes alone.
+///
+/// # Panics
+/// Panics if `old_size > new_size`, and otherwise under the same conditions as
+/// [`indices_for_consistency_proof`](crate::merkle_tree::consistency::indices_for_consistency_proof).
+pub fn consistency_proof_size<H: Digest>(old_size: u64, new_size: u64) -> usize {
+    let num_additions = new_size
+        .checked_sub(old_size)
+        .expect("old tree size cannot be larger than new tree size");
+    let mut num_hashes = 0;
+    crate::merkle_tree::consistency::visit_consistency_proof_indices(
+        old_size,
+        num_additions,
+        |_| num_hashes += 1,
+    );
+    num_hashes * <H as Digest>::output_size()
+}
+
 // ============================================================================
 // From inclusion.rs (verification)
 // ============================================================================
@@ -375,31 +456,56 @@ impl<H: Digest> InclusionProof<H> {
         leaf_idx: u64,
         root_hash: &RootHash<H>,
     ) -> Result<(), &'static str> {
+        self.verify_leaf_hash(leaf_hash::<H, _>(leaf_val), leaf_idx, root_hash)
+    }
+
+    fn verify_leaf_hash(
+        &self,
+        leaf_hash_value: digest::Output<H>,
+        leaf_idx: u64,
+        root_hash: &RootHash<H>,
+    ) -> Result<(), &'static str> {
+        self.verify_with_trace(leaf_hash_value, leaf_idx, root_hash)
+            .result
+    }
+
+    // Modification: verification that records each hash it computes, so a
+    // failing proof can be compared step by step with a good one
+    /// Verifies that the leaf with RFC 6962 leaf hash `leaf_hash_value` is at `leaf_idx` under
+    /// `root_hash`, returning every intermediate hash along with the outcome.
+    pub fn verify_with_trace(
+        &self,
+        leaf_hash_value: digest::Output<H>,
+        leaf_idx: u64,
+        root_hash: &RootHash<H>,
+    ) -> VerificationTrace<H> {
+        let mut trace = VerificationTrace::new(leaf_hash_value.clone());
         let num_leaves = root_hash.num_leaves();
 
         if leaf_idx >= num_leaves {
-            return Err("leaf index out of bounds");
+            return trace.fail("leaf index out of bounds");
         }
 
         // Empty tree edge case
         if num_leaves == 0 {
-            return Err("cannot verify inclusion in empty tree");
+            return trace.fail("cannot verify inclusion in empty tree");
+        }
+        // Modification: indices_for_inclusion_proof panics on trees this large
+        if num_leaves > u64::MAX / 2 + 1 {
+            return trace.fail("tree too big");
         }
 
         // Check that the proof is the right size
-        let expected_proof_size =
-            indices_for_inclusion_proof(num_leaves, leaf_idx).len() * <H as Digest>::output_size();
-        if self.proof.len() != expected_proof_size {
-            return Err("invalid proof length");
+        if self.proof.len() != inclusion_proof_size::<H>(num_leaves, leaf_idx) {
+            return trace.fail("invalid proof length");
         }
 
         // If the proof is empty (single leaf tree), then the leaf hash is the root hash
-        let leaf_hash_value = leaf_hash::<H, _>(leaf_val);
         if self.proof.is_empty() {
             if bool::from(leaf_hash_value.ct_eq(&root_hash.root_hash)) {
-                return Ok(());
+                return trace;
             } else {
-                return Err("root hash mismatch for single leaf");
+                return trace.fail("root hash mismatch for single leaf");
             }
         }
 
@@ -413,23 +519,78 @@ impl<H: Digest> InclusionProof<H> {
             let sibling_hash = digest::Output::<H>::from_slice(sibling_hash_bytes);
 
             // Combine with sibling based on position
-            cur_hash = if cur_idx.is_left(num_leaves) {
-                parent_hash::<H>(&cur_hash, sibling_hash)
-            } else {
+            let sibling_is_left = !cur_idx.is_left(num_leaves);
+            cur_hash = if sibling_is_left {
                 parent_hash::<H>(sibling_hash, &cur_hash)
+            } else {
+                parent_hash::<H>(&cur_hash, sibling_hash)
             };
 
             // Step up the tree
             cur_idx = cur_idx.parent(num_leaves);
+            trace.steps.push(TraceStep {
+                level: cur_idx.level(),
+                sibling: sibling_hash.clone(),
+                sibling_is_left,
+                hash: cur_hash.clone(),
+                old_hash: None,
+            });
         }
 
         // Verify the computed root matches
         if bool::from(cur_hash.ct_eq(&root_hash.root_hash)) {
-            Ok(())
+            trace
         } else {
-            Err("computed root does not match")
+            trace.fail("computed root does not match")
+        }
+    }
+}
+
+// Modification: step-by-step record of a proof verification
+
+/// One hash computed while verifying a proof
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceStep<H: Digest> {
+    /// Level of the computed node, leaves being level 0
+    pub level: u32,
+    /// The proof hash combined at this step
+    pub sibling: digest::Output<H>,
+    /// Whether `sibling` was the left input of the hash
+    pub sibling_is_left: bool,
+    /// The hash computed at this step
+    pub hash: digest::Output<H>,
+    /// For consistency proofs, the old tree's hash if this step also advanced it
+    pub old_hash: Option<digest::Output<H>>,
+}
+
+/// Every hash a verification computed, and its outcome
+#[derive(Clone, Debug)]
+pub struct VerificationTrace<H: Digest> {
+    /// The hash the walk starts from: the leaf hash for inclusion, and for
+    /// consistency the first node the two trees share
+    pub start: digest::Output<H>,
+    pub steps: Vec<TraceStep<H>>,
+    pub result: Result<(), &'static str>,
+}
+
+impl<H: Digest> VerificationTrace<H> {
+    fn new(start: digest::Output<H>) -> Self {
+        VerificationTrace {
+            start,
+            steps: Vec::new(),
+            result: Ok(()),
         }
     }
+
+    fn fail(mut self, reason: &'static str) -> Self {
+        self.result = Err(reason);
+        self
+    }
+
+    /// The last hash computed, which verification compared with the root
+    pub fn computed_root(&self) -> &digest::Output<H> {
+        self.steps.last().map_or(&self.start, |step| &step.hash)
+    }
 }
 
 // ============================================================================
@@ -488,6 +649,22 @@ impl<H: Digest> ConsistencyProof<H> {
         }
     }
 
+    /// Constructs a `ConsistencyProof` from its byte representation.
+    ///
+    /// # Errors
+    /// Returns [`DecodingError::MalformedProof`] if `bytes.len()` is not a multiple of the digest
+    /// size.
+    pub fn try_from_bytes(bytes: Vec<u8>) -> Result<Self, DecodingError> {
+        if bytes.len() % <H as Digest>::output_size() != 0 {
+            return Err(DecodingError::MalformedProof);
+        }
+
+        Ok(ConsistencyProof {
+            proof: bytes,
+            _marker: PhantomData,
+        })
+    }
+
     /// Verifies that `old_root` is consistent with `new_root`.
     ///
     /// This implements verification of `PROOF(m, D[n])` as described in [RFC 6962
@@ -498,31 +675,34 @@ impl<H: Digest> ConsistencyProof<H> {
         old_root: &RootHash<H>,
         new_root: &RootHash<H>,
     ) -> Result<(), &'static str> {
+        self.verify_with_trace(old_root, new_root).result
+    }
+
+    // Modification: verification that records each hash it computes
+    /// Verifies that `old_root` is consistent with `new_root`, returning every intermediate hash
+    /// along with the outcome.
+    pub fn verify_with_trace(
+        &self,
+        old_root: &RootHash<H>,
+        new_root: &RootHash<H>,
+    ) -> VerificationTrace<H> {
         let num_newtree_leaves = new_root.num_leaves();
         let num_oldtree_leaves = old_root.num_leaves();
+        let trace = VerificationTrace::new(old_root.root_hash.clone());
 
         if num_oldtree_leaves == 0 {
-            return Err("cannot verify consistency from empty tree");
+            return trace.fail("cannot verify consistency from empty tree");
         }
         if num_oldtree_leaves > num_newtree_leaves {
-            return Err("old tree size cannot be larger than new tree size");
+            return trace.fail("old tree size cannot be larger than new tree size");
         }
         if num_newtree_leaves > u64::MAX / 2 + 1 {
-            return Err("new tree too big");
+            return trace.fail("new tree too big");
         }
 
         // Check that the proof is the right size
-        let num_additions = num_newtree_leaves - num_oldtree_leaves;
-        let expected_proof_size = {
-            let num_hashes = crate::merkle_tree::consistency::indices_for_consistency_proof(
-                num_oldtree_leaves,
-                num_additions,
-            )
-            .len();
-            <H as Digest>::output_size() * num_hashes
-        };
-        if expected_proof_size != self.proof.len() {
-            return Err("malformed proof");
+        if consistency_proof_size::<H>(num_oldtree_leaves, num_newtree_leaves) != self.proof.len() {
+            return trace.fail("malformed proof");
         }
 
         // We have a special case when the old tree is a subtree of the current tree
@@ -553,19 +733,22 @@ impl<H: Digest> ConsistencyProof<H> {
         };
         let mut running_tree_hash = running_oldtree_hash.clone();
         let mut running_newtree_idx = running_oldtree_idx;
+        let mut trace = VerificationTrace::new(running_tree_hash.clone());
 
         for sibling_hash in digests {
             let sibling_idx = running_newtree_idx.sibling(num_newtree_leaves);
 
-            if running_newtree_idx.is_left(num_newtree_leaves) {
-                running_tree_hash = parent_hash::<H>(&running_tree_hash, &sibling_hash);
-            } else {
+            let sibling_is_left = !running_newtree_idx.is_left(num_newtree_leaves);
+            if sibling_is_left {
                 running_tree_hash = parent_hash::<H>(&sibling_hash, &running_tree_hash);
+            } else {
+                running_tree_hash = parent_hash::<H>(&running_tree_hash, &sibling_hash);
             }
             // Step up the tree
             running_newtree_idx = running_newtree_idx.parent(num_newtree_leaves);
 
             // Now do the same with the old tree
+            let mut old_hash = None;
             if running_oldtree_idx != oldtree_root_idx
                 && sibling_idx == running_oldtree_idx.sibling(num_oldtree_leaves)
             {
@@ -576,16 +759,149 @@ impl<H: Digest> ConsistencyProof<H> {
                 }
                 // Step up the oldtree
                 running_oldtree_idx = running_oldtree_idx.parent(num_oldtree_leaves);
+                old_hash = Some(running_oldtree_hash.clone());
             }
+
+            trace.steps.push(TraceStep {
+                level: running_newtree_idx.level(),
+                sibling: sibling_hash,
+                sibling_is_left,
+                hash: running_tree_hash.clone(),
+                old_hash,
+            });
         }
 
         // At the end, the old hash should be the old root, and the new hash should be the new root
         let oldtree_eq = running_oldtree_hash.ct_eq(&old_root.root_hash);
         let tree_eq = running_tree_hash.ct_eq(&new_root.root_hash);
         if !bool::from(oldtree_eq & tree_eq) {
-            Err("verification failure")
+            trace.fail("verification failure")
         } else {
-            Ok(())
+            trace
         }
     }
 }
+
+// Modification: tests for the byte decoding added above
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::Sha256;
+
+    #[test]
+    fn test_inclusion_proof_try_from_bytes() {
+        let digests = [Sha256::digest(b"a"), Sha256::digest(b"b")];
+        let proof = InclusionProof::<Sha256>::from_digests(digests.iter());
+
+        let decoded = InclusionProof::<Sha256>::try_from_bytes(proof.as_bytes().to_vec()).unwrap();
+        assert_eq!(decoded.as_bytes(), proof.as_bytes());
+
+        let empty = InclusionProof::<Sha256>::try_from_bytes(Vec::new()).unwrap();
+        assert!(empty.as_bytes().is_empty());
+
+        let truncated = proof.as_bytes()[..63].to_vec();
+        assert!(matches!(
+            InclusionProof::<Sha256>::try_from_bytes(truncated),
+            Err(DecodingError::MalformedProof)
+        ));
+    }
+
+    #[test]
+    fn test_proof_sizes() {
+        // RFC 6962 §2.1.3's example tree of seven leaves
+        assert_eq!(inclusion_proof_size::<Sha256>(7, 0), 3 * 32);
+        assert_eq!(inclusion_proof_size::<Sha256>(7, 6), 2 * 32);
+        assert_eq!(inclusion_proof_size::<Sha256>(1, 0), 0);
+        assert_eq!(consistency_proof_size::<Sha256>(3, 7), 4 * 32);
+        assert_eq!(consistency_proof_size::<Sha256>(4, 7), 32);
+        assert_eq!(consistency_proof_size::<Sha256>(6, 7), 3 * 32);
+        assert_eq!(consistency_proof_size::<Sha256>(7, 7), 0);
+        assert_eq!(consistency_proof_size::<sha2::Sha512>(3, 7), 4 * 64);
+
+        for num_leaves in 1..40u64 {
+            for idx in 0..num_leaves {
+                assert_eq!(
+                    inclusion_proof_size::<Sha256>(num_leaves, idx),
+                    indices_for_inclusion_proof(num_leaves, idx).len() * 32
+                );
+            }
+            for old_size in 1..=num_leaves {
+                assert_eq!(
+                    consistency_proof_size::<Sha256>(old_size, num_leaves),
+                    crate::merkle_tree::consistency::indices_for_consistency_proof(
+                        old_size,
+                        num_leaves - old_size
+                    )
+                    .len()
+                        * 32
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "old tree size cannot be larger")]
+    fn test_consistency_proof_size_rejects_shrinking_tree() {
+        consistency_proof_size::<Sha256>(7, 3);
+    }
+
+    #[test]
+    fn test_inclusion_trace() {
+        let leaves = [b"a", b"b", b"c"].map(leaf_hash::<Sha256, _>);
+        let left = parent_hash::<Sha256>(&leaves[0], &leaves[1]);
+        let root = RootHash::<Sha256>::new(parent_hash::<Sha256>(&left, &leaves[2]), 3);
+
+        let proof = InclusionProof::<Sha256>::from_digests([&leaves[1], &leaves[2]]);
+        let trace = proof.verify_with_trace(leaves[0], 0, &root);
+        assert_eq!(trace.result, Ok(()));
+        assert_eq!(trace.start, leaves[0]);
+        assert_eq!(trace.steps.len(), 2);
+        assert_eq!((trace.steps[0].level, trace.steps[1].level), (1, 2));
+        assert!(!trace.steps[0].sibling_is_left && !trace.steps[1].sibling_is_left);
+        assert_eq!(trace.steps[0].hash, left);
+        assert_eq!(trace.computed_root(), root.as_bytes());
+
+        // The right edge of a three-leaf tree skips level 1
+        let proof = InclusionProof::<Sha256>::from_digests([&left]);
+        let trace = proof.verify_with_trace(leaves[2], 2, &root);
+        assert_eq!(trace.result, Ok(()));
+        assert_eq!(trace.steps[0].level, 2);
+        assert!(trace.steps[0].sibling_is_left);
+
+        // A wrong leaf is hashed all the way up before the mismatch shows
+        let proof = InclusionProof::<Sha256>::from_digests([&leaves[1], &leaves[2]]);
+        let trace = proof.verify_with_trace(leaves[2], 0, &root);
+        assert_eq!(trace.result, Err("computed root does not match"));
+        assert_eq!(trace.steps.len(), 2);
+        assert_ne!(trace.computed_root(), root.as_bytes());
+    }
+
+    #[test]
+    fn test_inclusion_verify_rejects_oversized_tree() {
+        let root = RootHash::<Sha256>::new(Sha256::digest(b"root"), u64::MAX);
+        let proof = InclusionProof::<Sha256>::try_from_bytes(Vec::new()).unwrap();
+        assert!(proof.verify(b"leaf", u64::MAX - 1, &root).is_err());
+    }
+
+    #[test]
+    fn test_consistency_proof_try_from_bytes() {
+        let digests = [Sha256::digest(b"a"), Sha256::digest(b"b")];
+        let proof = ConsistencyProof::<Sha256>::from_digests(digests.iter());
+
+        let decoded =
+            ConsistencyProof::<Sha256>::try_from_bytes(proof.as_bytes().to_vec()).unwrap();
+        assert_eq!(decoded.as_bytes(), proof.as_bytes());
+
+        assert!(ConsistencyProof::<Sha256>::try_from_bytes(Vec::new())
+            .unwrap()
+            .as_bytes()
+            .is_empty());
+
+        let mut extended = proof.as_bytes().to_vec();
+        extended.push(0);
+        assert!(matches!(
+            ConsistencyProof::<Sha256>::try_from_bytes(extended),
+            Err(DecodingError::MalformedProof)
+        ));
+    }
+}
@@ -4,6 +4,10 @@ pub mod ct_merkle_vendored;
 pub mod consistency;
 pub mod slatedb_backed_tree;
 
-pub use consistency::compute_subtree_root;
-pub use ct_merkle_vendored::{ConsistencyProof, InclusionProof, RootHash};
-pub use slatedb_backed_tree::SlateDbBackedTree;
+pub(crate) use consistency::compute_subtree_root;
+pub use ct_merkle_vendored::{
+    consistency_proof_size, inclusion_proof_size, ConsistencyProof, InclusionProof, RootHash,
+};
+pub use slatedb_backed_tree::{
+    ReplicatedSlateDbBackedTree, ReplicationSink, SlateDbBackedTree, TreeDiff,
+};
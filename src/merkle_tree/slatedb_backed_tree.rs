@@ -1,50 +1,180 @@
 use crate::merkle_tree::{
     compute_subtree_root,
-    consistency::indices_for_consistency_proof,
+    consistency::visit_consistency_proof_indices,
     ct_merkle_vendored::{
-        indices_for_inclusion_proof, leaf_hash, parent_hash, root_idx, ConsistencyProof,
-        HashableLeaf, InclusionProof, InternalIdx, LeafIdx, RootHash,
+        indices_for_inclusion_proof, leaf_hash, num_internal_nodes, parent_hash, root_idx,
+        ConsistencyProof, HashableLeaf, InclusionProof, InternalIdx, LeafIdx, RootHash,
     },
 };
-use crate::storage::RateLimitedDb;
+use crate::metrics::Metrics;
+use crate::storage::{compression, Compression, DbBatch, RateLimitedDb};
 use digest::Digest;
 use foyer::{Cache, CacheBuilder};
-use slatedb::WriteBatch;
-use std::{fmt, sync::Arc};
-use tokio::sync::Mutex;
+use lazy_static::lazy_static;
+use node_cache::NodeCache;
+use prometheus::IntCounter;
+use std::{
+    borrow::Cow,
+    collections::{hash_map::Entry, HashMap},
+    fmt,
+    panic::Location,
+    sync::Arc,
+};
+use tokio::{sync::Mutex, task::JoinSet};
+
+mod epochs;
+mod lazy;
+mod node_cache;
+mod replication;
+mod snapshot;
+
+pub use epochs::EpochUsage;
+pub use lazy::LazySlateDbTree;
+pub use replication::{
+    NoopReplicationSink, ReplicatedSlateDbBackedTree, ReplicationError, ReplicationSink,
+};
+pub use snapshot::{SnapshotChunk, SnapshotManifest};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
 
 #[derive(Debug)]
 pub enum SlateDbTreeError {
-    DbError(slatedb::SlateDBError),
-    EncodingError(String),
+    DbError(BoxError),
+    EncodingError(BoxError),
     InconsistentState(String),
+    /// Another tree error annotated by [`ResultExt::context`]
+    Context {
+        context: String,
+        location: &'static Location<'static>,
+        source: Box<SlateDbTreeError>,
+    },
 }
 
+/// `{}` prints the outermost message, including the database or encoding
+/// error it wraps; `{:#}` appends every further source, as `anyhow` does.
 impl fmt::Display for SlateDbTreeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         match self {
-            SlateDbTreeError::DbError(e) => write!(f, "SlateDB error: {}", e),
-            SlateDbTreeError::EncodingError(e) => write!(f, "Encoding error: {}", e),
-            SlateDbTreeError::InconsistentState(e) => write!(f, "Inconsistent state: {}", e),
+            SlateDbTreeError::DbError(e) => write!(f, "SlateDB error: {}", e)?,
+            SlateDbTreeError::EncodingError(e) => write!(f, "Encoding error: {}", e)?,
+            SlateDbTreeError::InconsistentState(e) => write!(f, "Inconsistent state: {}", e)?,
+            SlateDbTreeError::Context {
+                context, location, ..
+            } => write!(
+                f,
+                "{} (at {}:{})",
+                context,
+                location.file(),
+                location.line()
+            )?,
+        }
+
+        if f.alternate() {
+            let mut source = unprinted_source(self);
+            while let Some(e) = source {
+                write!(f, ": {}", e)?;
+                source = unprinted_source(e);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The first error in the source chain of `e` that `e` does not already print
+fn unprinted_source<'a>(
+    e: &'a (dyn std::error::Error + 'static),
+) -> Option<&'a (dyn std::error::Error + 'static)> {
+    match e.downcast_ref::<SlateDbTreeError>() {
+        Some(SlateDbTreeError::DbError(inner) | SlateDbTreeError::EncodingError(inner)) => {
+            inner.source()
         }
+        _ => e.source(),
     }
 }
 
 impl std::error::Error for SlateDbTreeError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
-            SlateDbTreeError::DbError(e) => Some(e),
-            _ => None,
+            SlateDbTreeError::DbError(e) | SlateDbTreeError::EncodingError(e) => Some(e.as_ref()),
+            SlateDbTreeError::Context { source, .. } => Some(source.as_ref()),
+            SlateDbTreeError::InconsistentState(_) => None,
         }
     }
 }
 
 impl From<slatedb::SlateDBError> for SlateDbTreeError {
     fn from(e: slatedb::SlateDBError) -> Self {
-        SlateDbTreeError::DbError(e)
+        SlateDbTreeError::DbError(Box::new(e))
+    }
+}
+
+/// Attach a description of the failed operation, and where it was attempted,
+/// to a tree error
+pub trait ResultExt<T> {
+    fn context(self, context: &str) -> Result<T, SlateDbTreeError>;
+}
+
+impl<T, E: Into<SlateDbTreeError>> ResultExt<T> for Result<T, E> {
+    #[track_caller]
+    fn context(self, context: &str) -> Result<T, SlateDbTreeError> {
+        let location = Location::caller();
+        self.map_err(|e| SlateDbTreeError::Context {
+            context: context.to_string(),
+            location,
+            source: Box::new(e.into()),
+        })
     }
 }
 
+/// Outcome of [`SlateDbBackedTree::startup_verify`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartupResult {
+    /// The root stored for the declared tree size matches its recomputation
+    Consistent,
+    /// The declared tree size is not backed by consistent nodes; `actual_size`
+    /// is the largest published size that is
+    Truncated { actual_size: u64 },
+}
+
+/// Outcome of [`SlateDbBackedTree::verify_integrity`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegrityResult {
+    /// Every stored node matches its recomputation from the leaves
+    Intact,
+    /// The lowest, leftmost stored node whose hash does not match; it covers
+    /// leaves `leaf_start..leaf_end`
+    Corrupted {
+        node_idx: u64,
+        leaf_start: u64,
+        leaf_end: u64,
+    },
+}
+
+/// Outcome of [`SlateDbBackedTree::audit_random`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditReport {
+    /// Proofs generated and verified before the audit finished or stopped
+    pub verified: usize,
+    /// The first proof that did not verify; the audit stops there
+    pub failure: Option<AuditFailure>,
+}
+
+/// A proof [`SlateDbBackedTree::audit_random`] generated that did not verify
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditFailure {
+    Inclusion {
+        leaf_index: u64,
+        tree_size: u64,
+        reason: &'static str,
+    },
+    Consistency {
+        old_size: u64,
+        new_size: u64,
+        reason: &'static str,
+    },
+}
+
 /// A SlateDB-backed append-only Merkle tree implementation.
 ///
 /// This implementation stores only the necessary data in SlateDB:
@@ -52,12 +182,16 @@ impl From<slatedb::SlateDBError> for SlateDbTreeError {
 /// - Internal node hashes at keys "node:{index}"
 /// - Tree metadata at key "meta"
 ///
+/// All keys sit under the key prefix of the database handle, so trees opened
+/// on handles with distinct prefixes share one database without colliding.
+///
 /// Operations are designed to minimize reads by only fetching nodes
 /// along the paths needed for proofs and root calculation.
 ///
 /// Thread-safety: This implementation is thread-safe by default.
 /// Write operations (push, batch_push_with_data) are serialized using an internal
-/// RwLock, while read operations can proceed concurrently.
+/// mutex held across the whole read-modify-write of the tree size, so concurrent
+/// callers always get disjoint index ranges. Read operations proceed concurrently.
 pub struct SlateDbBackedTree<H, T>
 where
     H: Digest,
@@ -68,14 +202,129 @@ where
     _phantom_t: core::marker::PhantomData<T>,
     // Cache for frequently accessed upper tree nodes
     // Key: (node index, version), Value: node hash
-    node_cache: Option<Cache<(u64, u64), Vec<u8>>>,
+    node_cache: Option<NodeCache>,
+    // Hit and miss counters of node cache lookups, when reported
+    node_cache_counters: Option<(IntCounter, IntCounter)>,
+    // Counts nodes recomputed from leaves, when reported
+    node_recomputations: Option<IntCounter>,
     // Cache for tile existence checks
     // Key: (level, tile_index), Value: exists (true) or not
     tile_cache: Option<Cache<(u8, u64), bool>>,
-    // Write lock to ensure write operations are serialized
-    write_lock: Arc<Mutex<()>>,
+    // Write lock to ensure write operations are serialized. It also holds the
+    // frontier after the last append, or `None` when it has to be read back
+    // from storage (an append failed or was never made by this instance).
+    write_lock: Arc<Mutex<Option<Frontier<H>>>>,
+    // Most puts written in one batch ahead of an append's final batch
+    max_batch_puts: usize,
+    // Node reads in flight at once while building a proof, `None` for all
+    proof_fetch_concurrency: Option<usize>,
+    // Most leaves read to recompute a node whose versioned record is missing
+    max_recompute_leaves: u64,
+    // How leaves this instance appends are serialized
+    leaf_encoding: LeafEncoding,
+    // Leaves below this index were stored before leaves were tagged
+    untagged_leaves: u64,
+    // Leaves below this index were stored without a compression format byte
+    unframed_leaves: u64,
+    // How leaves this instance appends are compressed
+    leaf_compression: Compression,
+    // Prefix of a leaf hash index written before `lhi:`, read when `lhi:`
+    // has no entry for a hash
+    legacy_hash_index: Option<Vec<u8>>,
+    // Counts this instance among those open on its keyspace
+    open_instance: OpenInstance,
+    // Chunk of the next append whose write fails, to test interrupted appends
+    #[cfg(test)]
+    fail_at_chunk: Option<usize>,
+}
+
+lazy_static! {
+    /// Tree instances open in this process, by database and key prefix
+    static ref OPEN_INSTANCES: std::sync::Mutex<HashMap<(usize, Vec<u8>), usize>> =
+        Default::default();
+}
+
+/// Registers a tree instance in [`OPEN_INSTANCES`] until dropped
+#[derive(Debug)]
+struct OpenInstance {
+    keyspace: (usize, Vec<u8>),
+}
+
+impl OpenInstance {
+    fn register(db: &RateLimitedDb) -> Self {
+        let keyspace = (db.db_id(), db.key_prefix().to_vec());
+        *OPEN_INSTANCES
+            .lock()
+            .unwrap()
+            .entry(keyspace.clone())
+            .or_default() += 1;
+        Self { keyspace }
+    }
+
+    /// Other instances open on the same keyspace in this process
+    fn others(&self) -> usize {
+        OPEN_INSTANCES.lock().unwrap()[&self.keyspace] - 1
+    }
+}
+
+impl Drop for OpenInstance {
+    fn drop(&mut self) {
+        let mut open = OPEN_INSTANCES.lock().unwrap();
+        if let Entry::Occupied(mut entry) = open.entry(self.keyspace.clone()) {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                entry.remove();
+            }
+        }
+    }
 }
 
+/// Serialization of stored leaves. Each `leaf:` value starts with a tag byte
+/// naming its encoding, so a tree reads leaves whichever encoder wrote them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LeafEncoding {
+    Postcard,
+    Cbor,
+}
+
+const LEAF_TAG_POSTCARD: u8 = 0x01;
+const LEAF_TAG_CBOR: u8 = 0x02;
+
+/// Puts of an append that are invisible until its final batch moves `meta`
+/// past them, written in batches of at most `max_puts` as they accumulate
+struct ChunkedPuts {
+    batch: DbBatch,
+    puts: usize,
+    max_puts: usize,
+    chunks_written: usize,
+    /// `PENDING_APPEND_KEY` value, written with the first chunk
+    marker: [u8; 16],
+}
+
+/// Refuse to append `count` leaves to a tree of `size`, all or nothing, when
+/// that would take it past [`MAX_LEAVES`]
+fn check_capacity(size: u64, count: usize) -> Result<(), SlateDbTreeError> {
+    match size.checked_add(count as u64) {
+        Some(new_size) if new_size <= MAX_LEAVES => Ok(()),
+        _ => Err(SlateDbTreeError::InconsistentState(format!(
+            "Tree is full: appending {} leaves to {} would pass the limit of {}",
+            count, size, MAX_LEAVES
+        ))),
+    }
+}
+
+/// Hashes of the perfect subtrees a tree of `size` leaves decomposes into.
+/// Siblings an append needs from the existing tree are always among these,
+/// so keeping them in memory saves re-reading them on every append.
+struct Frontier<H: Digest> {
+    size: u64,
+    nodes: std::collections::BTreeMap<u64, digest::Output<H>>,
+}
+
+/// Most leaves a tree holds: leaf indices stop at `u64::MAX / 2`, as the
+/// node index math doubles them
+const MAX_LEAVES: u64 = u64::MAX / 2;
+
 const LEAF_PREFIX: &[u8] = b"leaf:";
 const META_KEY: &[u8] = b"meta";
 const VERSIONED_NODE_PREFIX: &[u8] = b"vnode:";
@@ -83,6 +332,57 @@ const COMMITTED_SIZE_KEY: &[u8] = b"committed_size";
 const NODE_LATEST_VERSION_PREFIX: &[u8] = b"nver:";
 const TILE_PREFIX: &[u8] = b"tile:";
 const LAST_PRECOMPUTED_SIZE_KEY: &[u8] = b"last_precomputed_size";
+/// Big-endian start and end of an append that has written chunks ahead of
+/// its final batch, which deletes it again
+const PENDING_APPEND_KEY: &[u8] = b"pending_append";
+/// Big-endian count of leading leaves stored as bare postcard, from before
+/// leaves were tagged with their encoding
+const UNTAGGED_LEAVES_KEY: &[u8] = b"untagged_leaves";
+/// Big-endian count of leading leaves stored without a compression format
+/// byte ahead of their tag
+const UNFRAMED_LEAVES_KEY: &[u8] = b"unframed_leaves";
+/// `lhi:{leaf hash}` -> big-endian leaf index. A leaf hash always maps to the
+/// same index, so unlike nodes these entries are not versioned.
+const LEAF_HASH_INDEX_PREFIX: &[u8] = b"lhi:";
+
+// Keys are fixed width, so they are built on the stack
+const LEAF_KEY_LEN: usize = LEAF_PREFIX.len() + 8;
+const VERSIONED_NODE_KEY_LEN: usize = VERSIONED_NODE_PREFIX.len() + 8 + 1 + 8;
+const NODE_LATEST_VERSION_KEY_LEN: usize = NODE_LATEST_VERSION_PREFIX.len() + 8;
+const TILE_KEY_LEN: usize = TILE_PREFIX.len() + 1 + 1 + 8;
+
+/// Reads in flight at once while checking tree integrity
+const INTEGRITY_CHECK_CONCURRENCY: usize = 64;
+
+/// Leaf reads in flight at once in [`SlateDbBackedTree::get_range`]
+const LEAF_RANGE_CONCURRENCY: usize = 32;
+
+/// What [`SlateDbBackedTree::append_if_absent`] did with one leaf
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeafAppend {
+    /// Appended at this index
+    Appended(u64),
+    /// Already in the tree, or earlier in the same call, at this index
+    Skipped(u64),
+}
+
+/// What changed between two published tree sizes, from
+/// [`SlateDbBackedTree::diff`]
+#[derive(Debug, Clone)]
+pub struct TreeDiff<H: Digest, T> {
+    /// Leaves `from_size..to_size` with their indices
+    pub new_leaves: Vec<(u64, T)>,
+    /// Proves `new_root` extends `old_root`
+    pub consistency_proof: ConsistencyProof<H>,
+    pub old_root: RootHash<H>,
+    pub new_root: RootHash<H>,
+}
+
+/// Default for [`SlateDbBackedTree::set_max_batch_puts`]
+pub const DEFAULT_MAX_BATCH_PUTS: usize = 10_000;
+
+/// Default for [`SlateDbBackedTree::set_max_recompute_leaves`]
+pub const DEFAULT_MAX_RECOMPUTE_LEAVES: u64 = 1 << 16;
 
 impl<H, T> SlateDbBackedTree<H, T>
 where
@@ -90,83 +390,678 @@ where
     T: HashableLeaf + serde::Serialize + serde::de::DeserializeOwned,
 {
     /// Check if multiple keys exist in the database
-    pub async fn check_keys_exist(&self, keys: &[Vec<u8>]) -> Result<Vec<bool>, SlateDbTreeError> {
-        let futures: Vec<_> = keys
-            .iter()
-            .map(|key| async move {
-                match self.db.get(key).await {
-                    Ok(Some(_)) => Ok(true),
-                    Ok(None) => Ok(false),
-                    Err(e) => Err(SlateDbTreeError::DbError(e)),
-                }
-            })
-            .collect();
-
-        futures::future::try_join_all(futures).await
+    pub async fn check_keys_exist<K: AsRef<[u8]>>(
+        &self,
+        keys: &[K],
+    ) -> Result<Vec<bool>, SlateDbTreeError> {
+        let values = self.db.get_many(keys).await?;
+        Ok(values.iter().map(Option::is_some).collect())
     }
 
     pub async fn new(db: RateLimitedDb) -> Result<Self, SlateDbTreeError> {
-        let node_cache: Cache<(u64, u64), Vec<u8>> = CacheBuilder::new(100_000).build();
+        Self::open(db, LeafEncoding::Postcard, NodeCache::heap()).await
+    }
+
+    /// Like [`Self::new`], but leaves appended from now on are stored as CBOR
+    /// (RFC 8949) rather than postcard
+    pub async fn new_with_cbor(db: RateLimitedDb) -> Result<Self, SlateDbTreeError> {
+        Self::open(db, LeafEncoding::Cbor, NodeCache::heap()).await
+    }
+
+    /// Like [`Self::new`], but node hashes are cached in a pool of
+    /// `max_bytes` allocated up front, evicting the least recently used hash
+    /// once it is full. Hashes are stored raw in the pool rather than in an
+    /// allocation each, which keeps very large caches compact. `max_bytes`
+    /// also pays for the pool's index and recency list.
+    pub async fn new_with_offheap_cache(
+        db: RateLimitedDb,
+        max_bytes: u64,
+    ) -> Result<Self, SlateDbTreeError> {
+        let node_cache = NodeCache::pool(max_bytes, <H as Digest>::output_size());
+        Self::open(db, LeafEncoding::Postcard, node_cache).await
+    }
 
+    async fn open(
+        db: RateLimitedDb,
+        leaf_encoding: LeafEncoding,
+        node_cache: NodeCache,
+    ) -> Result<Self, SlateDbTreeError> {
         let tile_cache: Cache<(u8, u64), bool> = CacheBuilder::new(100_000).build();
 
-        let tree = Self {
+        let mut tree = Self {
+            open_instance: OpenInstance::register(&db),
             db,
             _phantom_h: core::marker::PhantomData,
             _phantom_t: core::marker::PhantomData,
             node_cache: Some(node_cache),
+            node_cache_counters: None,
+            node_recomputations: None,
             tile_cache: Some(tile_cache),
-            write_lock: Arc::new(Mutex::new(())),
+            write_lock: Arc::new(Mutex::new(None)),
+            max_batch_puts: DEFAULT_MAX_BATCH_PUTS,
+            proof_fetch_concurrency: None,
+            max_recompute_leaves: DEFAULT_MAX_RECOMPUTE_LEAVES,
+            leaf_encoding,
+            untagged_leaves: 0,
+            unframed_leaves: 0,
+            leaf_compression: Compression::None,
+            legacy_hash_index: None,
+            #[cfg(test)]
+            fail_at_chunk: None,
         };
 
-        let existing_leaves = tree.get_num_leaves().await?;
+        let existing_leaves = tree
+            .get_num_leaves()
+            .await
+            .context("reading tree size metadata")?;
+
+        // Every leaf of a tree from before a format change is in the old format
+        tree.untagged_leaves = tree
+            .leaf_format_boundary(UNTAGGED_LEAVES_KEY, existing_leaves)
+            .await
+            .context("recording untagged leaves")?;
+        tree.unframed_leaves = tree
+            .leaf_format_boundary(UNFRAMED_LEAVES_KEY, existing_leaves)
+            .await
+            .context("recording unframed leaves")?;
 
         if existing_leaves.is_none() {
-            tree.set_num_leaves(0).await?;
+            tree.set_num_leaves(0)
+                .await
+                .context("initializing empty tree")?;
             // Also initialize committed size to 0
-            tree.db.put(COMMITTED_SIZE_KEY, &0u64.to_be_bytes()).await?;
+            tree.db
+                .put(COMMITTED_SIZE_KEY, &0u64.to_be_bytes())
+                .await
+                .context("initializing committed size")?;
+        } else if let StartupResult::Truncated { actual_size } = tree
+            .startup_verify()
+            .await
+            .context("verifying stored root at startup")?
+        {
+            tracing::warn!(
+                "Merkle tree metadata declares {} leaves but the last consistent size is {}",
+                existing_leaves.unwrap_or(0),
+                actual_size
+            );
         }
 
+        tree.clear_interrupted_append()
+            .await
+            .context("clearing an interrupted append")?;
+        let num_leaves = tree.len().await?;
+        let frontier = tree
+            .load_frontier(num_leaves)
+            .await
+            .context("reading the tree frontier")?;
+        *tree.write_lock.lock().await = Some(frontier);
+
         Ok(tree)
     }
 
-    fn leaf_key(index: u64) -> Vec<u8> {
-        let mut key = Vec::with_capacity(LEAF_PREFIX.len() + 8);
-        key.extend_from_slice(LEAF_PREFIX);
-        key.extend_from_slice(&index.to_be_bytes());
+    /// The leaf count stored under `key`, first set to `existing_leaves`
+    async fn leaf_format_boundary(
+        &self,
+        key: &[u8],
+        existing_leaves: Option<u64>,
+    ) -> Result<u64, SlateDbTreeError> {
+        match self.db.get(key).await? {
+            Some(bytes) => Ok(u64::from_be_bytes(bytes.as_ref().try_into().map_err(
+                |_| SlateDbTreeError::EncodingError("Invalid leaf format boundary".into()),
+            )?)),
+            None => {
+                let boundary = existing_leaves.unwrap_or(0);
+                self.db.put(key, &boundary.to_be_bytes()).await?;
+                Ok(boundary)
+            }
+        }
+    }
+
+    /// Compress leaves appended from now on with `compression`. Leaves read
+    /// back whichever compression they were written with.
+    pub fn set_leaf_compression(&mut self, compression: Compression) {
+        self.leaf_compression = compression;
+    }
+
+    pub fn leaf_compression(&self) -> Compression {
+        self.leaf_compression
+    }
+
+    /// Also find leaves by the `{prefix}{leaf hash}` -> big-endian index
+    /// entries of an older index, for leaves appended before `lhi:` was
+    pub fn set_legacy_hash_index(&mut self, prefix: &[u8]) {
+        self.legacy_hash_index = Some(prefix.to_vec());
+    }
+
+    /// Split large appends into write batches of at most `max_puts` puts
+    /// ahead of the batch that commits them
+    pub fn set_max_batch_puts(&mut self, max_puts: usize) {
+        self.max_batch_puts = max_puts.max(1);
+    }
+
+    /// Recompute a node whose versioned record is missing from at most
+    /// `max_leaves` leaves, failing reads of larger subtrees instead
+    pub fn set_max_recompute_leaves(&mut self, max_leaves: u64) {
+        self.max_recompute_leaves = max_leaves;
+    }
+
+    /// Read at most `limit` proof nodes at once rather than every node of a
+    /// proof together, bounding the database requests and memory a tall tree
+    /// under many concurrent proofs can take. `None`, the default, fetches
+    /// them all at once.
+    pub fn set_proof_fetch_concurrency(&mut self, limit: Option<usize>) {
+        self.proof_fetch_concurrency = limit.map(|limit| limit.max(1));
+    }
+
+    /// Rewrite every leaf of the tree as CBOR, and append CBOR leaves from
+    /// now on. Returns the number of leaves rewritten.
+    ///
+    /// Leaves are rewritten from the end of the tree backwards, a batch at a
+    /// time, so an interrupted migration leaves every leaf readable and
+    /// running it again picks up where it stopped. Snapshots exported while
+    /// it runs may not restore.
+    ///
+    /// Every instance reads the leaf format boundaries once, when opened, and
+    /// would misread leaves migrated under it. The migration therefore fails
+    /// while another instance is open on the same database and key prefix in
+    /// this process; instances in other processes cannot be detected, so
+    /// stop them first.
+    pub async fn migrate_leaves_to_cbor(&mut self) -> Result<u64, SlateDbTreeError> {
+        let others = self.open_instance.others();
+        if others > 0 {
+            return Err(SlateDbTreeError::InconsistentState(format!(
+                "{} other instances of the tree are open, close them before migrating its leaves",
+                others
+            )));
+        }
+        self.leaf_encoding = LeafEncoding::Cbor;
+
+        let mut rewritten = 0;
+        let mut end = self.len().await?;
+        while end > 0 {
+            let start = end.saturating_sub(self.max_batch_puts as u64);
+            let keys: Vec<_> = (start..end).map(Self::leaf_key).collect();
+            let values = self.db.get_many(&keys).await?;
+
+            let mut batch = self.db.batch();
+            for ((index, key), value) in (start..end).zip(&keys).zip(values) {
+                let bytes = value.ok_or_else(|| {
+                    SlateDbTreeError::InconsistentState(format!("Leaf {} is missing", index))
+                })?;
+                if index >= self.unframed_leaves
+                    && Self::unframe_leaf(index, &bytes)?.first() == Some(&LEAF_TAG_CBOR)
+                {
+                    continue;
+                }
+                let leaf =
+                    Self::decode_leaf(self.untagged_leaves, self.unframed_leaves, index, &bytes)?;
+                batch.put(key, self.encode_leaf(&leaf)?);
+                rewritten += 1;
+            }
+            let untagged_leaves = self.untagged_leaves.min(start);
+            if untagged_leaves < self.untagged_leaves {
+                batch.put(UNTAGGED_LEAVES_KEY, untagged_leaves.to_be_bytes());
+            }
+            let unframed_leaves = self.unframed_leaves.min(start);
+            if unframed_leaves < self.unframed_leaves {
+                batch.put(UNFRAMED_LEAVES_KEY, unframed_leaves.to_be_bytes());
+            }
+            self.db
+                .write_batch(batch)
+                .await
+                .context("rewriting leaves as CBOR")?;
+            self.untagged_leaves = untagged_leaves;
+            self.unframed_leaves = unframed_leaves;
+            end = start;
+        }
+
+        Ok(rewritten)
+    }
+
+    /// Count node cache lookups as `node` hits and misses in `metrics`, and
+    /// nodes recomputed from their leaves
+    pub fn report_node_metrics(&mut self, metrics: &Metrics) {
+        self.node_cache_counters = Some((
+            metrics.cache_hits.with_label_values(&["node"]),
+            metrics.cache_misses.with_label_values(&["node"]),
+        ));
+        self.node_recomputations = Some(metrics.node_recomputations_total.clone());
+    }
+
+    fn count_node_cache_lookup(&self, hit: bool) {
+        if let Some((hits, misses)) = &self.node_cache_counters {
+            if hit {
+                hits.inc()
+            } else {
+                misses.inc()
+            }
+        }
+    }
+
+    /// Check that the root stored for the tree size in `META_KEY` matches the
+    /// root recomputed from the perfect subtrees along the left spine. If it
+    /// does not, go back through the published sizes, largest first, for
+    /// the last one that does.
+    pub async fn startup_verify(&self) -> Result<StartupResult, SlateDbTreeError> {
+        let declared_size = self.len().await?;
+        if declared_size == 0 || self.verify_root_at_size(declared_size).await? {
+            return Ok(StartupResult::Consistent);
+        }
+
+        // Only published sizes have a root node to check
+        let candidates = self.published_sizes().await?;
+        for size in candidates
+            .into_iter()
+            .rev()
+            .filter(|&size| size < declared_size)
+        {
+            if self.verify_root_at_size(size).await? {
+                return Ok(StartupResult::Truncated { actual_size: size });
+            }
+        }
+
+        Ok(StartupResult::Truncated { actual_size: 0 })
+    }
+
+    /// Returns false if no root was published at `tree_size` or if it does not
+    /// match the hash recomputed from its subtrees.
+    async fn verify_root_at_size(&self, tree_size: u64) -> Result<bool, SlateDbTreeError> {
+        let root_key = Self::versioned_node_key(root_idx(tree_size).as_u64(), tree_size);
+        let stored_root = match self.db.get(&root_key).await? {
+            Some(bytes) => bytes,
+            None => return Ok(false),
+        };
+
+        let subtree_roots = Self::spine_subtree_roots(tree_size);
+        let hash_futures: Vec<_> = subtree_roots
+            .iter()
+            .map(|&idx| self.get_node_hash_at_version(idx, tree_size))
+            .collect();
+        let mut hashes = futures::future::try_join_all(hash_futures).await?;
+
+        let mut recomputed = hashes
+            .pop()
+            .ok_or_else(|| SlateDbTreeError::InconsistentState("Tree has no subtrees".into()))?;
+        while let Some(left) = hashes.pop() {
+            recomputed = parent_hash::<H>(&left, &recomputed);
+        }
+
+        Ok(stored_root.as_ref() == recomputed.as_slice())
+    }
+
+    /// Recompute the whole tree at `tree_size` from the stored leaves and
+    /// compare every stored node along the way.
+    ///
+    /// Hashes are recomputed bottom-up from the leaf values, never taken from
+    /// storage, so a tampered leaf or node shows up as a mismatch at exactly
+    /// that node while its ancestors still match. The result therefore names
+    /// the smallest subtree that was modified. A consistency proof that uses a
+    /// tampered node also stops verifying against roots clients saw earlier,
+    /// since it is built from the stored hashes.
+    ///
+    /// This reads every leaf and node of the tree, so it is meant for audits
+    /// rather than the request path. It goes one level at a time and keeps
+    /// only the reads in flight, so memory does not grow with the tree.
+    pub async fn verify_integrity(
+        &self,
+        tree_size: u64,
+    ) -> Result<IntegrityResult, SlateDbTreeError> {
+        let current_size = self.get_committed_size().await?;
+        if tree_size > current_size {
+            return Err(SlateDbTreeError::InconsistentState(format!(
+                "Requested tree size {} exceeds current committed tree size {}",
+                tree_size, current_size
+            )));
+        }
+
+        if tree_size == 0 {
+            return Ok(IntegrityResult::Intact);
+        }
+
+        let leaves = self
+            .first_mismatch(tree_size, 1, tree_size, |i| async move {
+                let bytes = self.db.get(&Self::leaf_key(i)).await?.ok_or_else(|| {
+                    SlateDbTreeError::InconsistentState(format!("Leaf {} is missing", i))
+                })?;
+                let leaf: T =
+                    Self::decode_leaf(self.untagged_leaves, self.unframed_leaves, i, &bytes)?;
+                Ok::<_, SlateDbTreeError>(leaf_hash::<H, _>(&leaf))
+            })
+            .await?;
+        if let Some(corrupted) = leaves {
+            return Ok(corrupted);
+        }
+
+        // Each higher level is recomputed from the stored nodes of the level
+        // below, which have all matched their recomputation by then, so only
+        // a bounded window of hashes is held at once. A trailing node without
+        // a sibling moves up unchanged and has no node of its own at the
+        // next width; `carried` holds its hash while it does.
+        let mut carried: Option<digest::Output<H>> = None;
+        let mut below = 1u64;
+        while below < tree_size {
+            let width = below * 2;
+            let nodes = tree_size.div_ceil(width);
+            let last_child = tree_size.div_ceil(below) - 1;
+            let last_is_pair = tree_size - (nodes - 1) * width > below;
+            let pairs = if last_is_pair { nodes } else { nodes - 1 };
+
+            let carried_ref = carried.as_ref();
+            let child = move |j: u64| async move {
+                match carried_ref {
+                    Some(hash) if j == last_child => Ok(hash.clone()),
+                    _ => {
+                        self.get_node_hash_at_version(2 * j * below + below - 1, tree_size)
+                            .await
+                    }
+                }
+            };
+            let level = self
+                .first_mismatch(pairs, width, tree_size, |k| async move {
+                    let (left, right) = futures::try_join!(child(2 * k), child(2 * k + 1))?;
+                    Ok::<_, SlateDbTreeError>(parent_hash::<H>(&left, &right))
+                })
+                .await?;
+            if let Some(corrupted) = level {
+                return Ok(corrupted);
+            }
+
+            carried = if last_is_pair {
+                None
+            } else {
+                Some(child(last_child).await?)
+            };
+            below = width;
+        }
+
+        Ok(IntegrityResult::Intact)
+    }
+
+    /// First of the `nodes` leftmost nodes `width` leaves wide whose stored
+    /// hash at `tree_size` differs from `recompute` of its position in the
+    /// level. At most [`INTEGRITY_CHECK_CONCURRENCY`] nodes are compared at
+    /// once.
+    async fn first_mismatch<F, Fut>(
+        &self,
+        nodes: u64,
+        width: u64,
+        tree_size: u64,
+        recompute: F,
+    ) -> Result<Option<IntegrityResult>, SlateDbTreeError>
+    where
+        F: Fn(u64) -> Fut,
+        Fut: std::future::Future<Output = Result<digest::Output<H>, SlateDbTreeError>>,
+    {
+        use futures::{StreamExt, TryStreamExt};
+
+        let mut checks = futures::stream::iter(0..nodes)
+            .map(|k| {
+                let recomputed = recompute(k);
+                async move {
+                    let node_idx = 2 * k * width + width - 1;
+                    let (recomputed, stored) = futures::try_join!(
+                        recomputed,
+                        self.get_node_hash_at_version(node_idx, tree_size)
+                    )?;
+                    Ok::<_, SlateDbTreeError>((k * width, node_idx, recomputed == stored))
+                }
+            })
+            .buffered(INTEGRITY_CHECK_CONCURRENCY);
+
+        while let Some((leaf_start, node_idx, matches)) = checks.try_next().await? {
+            if !matches {
+                return Ok(Some(IntegrityResult::Corrupted {
+                    node_idx,
+                    leaf_start,
+                    leaf_end: (leaf_start + width).min(tree_size),
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Check `samples` random proofs against the roots the tree publishes.
+    ///
+    /// Each sample picks two published sizes `old <= new` and a leaf below
+    /// `new`, then verifies a consistency proof from `old` to `new` and an
+    /// inclusion proof of the leaf at `new`, generated the way the API
+    /// serves them. The same `seed` over the same tree checks the same proofs.
+    pub async fn audit_random(
+        self: &Arc<Self>,
+        seed: u64,
+        samples: usize,
+    ) -> Result<AuditReport, SlateDbTreeError>
+    where
+        H: Send + Sync + 'static,
+        T: Send + Sync + 'static,
+    {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let sizes = self.published_sizes().await?;
+        let mut report = AuditReport {
+            verified: 0,
+            failure: None,
+        };
+        if sizes.is_empty() {
+            return Ok(report);
+        }
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        for _ in 0..samples {
+            let new_pos = rng.gen_range(0..sizes.len());
+            let old_size = sizes[rng.gen_range(0..=new_pos)];
+            let new_size = sizes[new_pos];
+            let leaf_index = rng.gen_range(0..new_size);
+
+            let old_root = self.root_at_size(old_size).await?;
+            let new_root = self.root_at_size(new_size).await?;
+
+            let consistency = self.parallel_consistency_proof(old_size, new_size).await?;
+            if let Err(reason) = consistency.verify(&old_root, &new_root) {
+                report.failure = Some(AuditFailure::Consistency {
+                    old_size,
+                    new_size,
+                    reason,
+                });
+                return Ok(report);
+            }
+            report.verified += 1;
+
+            let leaf = self.get_range(leaf_index, leaf_index).await?.remove(0);
+            let inclusion = self.prove_inclusion_at_size(leaf_index, new_size).await?;
+            if let Err(reason) = inclusion.verify(&leaf, leaf_index, &new_root) {
+                report.failure = Some(AuditFailure::Inclusion {
+                    leaf_index,
+                    tree_size: new_size,
+                    reason,
+                });
+                return Ok(report);
+            }
+            report.verified += 1;
+        }
+
+        Ok(report)
+    }
+
+    /// Committed sizes with a published root, ascending. The root of every
+    /// size from `2^(k-1) + 1` to `2^k` is node `2^k - 1`, and it is only
+    /// rewritten while the tree is that small, so its versions are exactly
+    /// the published sizes in that range.
+    async fn published_sizes(&self) -> Result<Vec<u64>, SlateDbTreeError> {
+        let committed = self.get_committed_size().await?;
+        if committed == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut sizes = Vec::new();
+        for level in 0..=root_idx(committed).level() {
+            let idx = (1u64 << level) - 1;
+            let versions = self
+                .db
+                .scan(
+                    &Self::versioned_node_key(idx, 0),
+                    &Self::versioned_node_key(idx, u64::MAX),
+                )
+                .await?;
+            for (key, _) in versions {
+                let version: [u8; 8] = key[key.len() - 8..]
+                    .try_into()
+                    .map_err(|_| SlateDbTreeError::EncodingError("Invalid node key".into()))?;
+                let size = u64::from_be_bytes(version);
+                if size > 0 && size <= committed && root_idx(size).as_u64() == idx {
+                    sizes.push(size);
+                }
+            }
+        }
+        Ok(sizes)
+    }
+
+    /// Indices of the perfect subtrees a tree of `tree_size` leaves decomposes
+    /// into, left to right. A perfect tree is split into its two halves so the
+    /// root is always recomputed rather than compared against itself.
+    fn spine_subtree_roots(tree_size: u64) -> Vec<u64> {
+        if tree_size > 1 && tree_size.is_power_of_two() {
+            let half = tree_size / 2;
+            return vec![half - 1, 3 * half - 1];
+        }
+        Self::perfect_subtree_roots(tree_size)
+    }
+
+    /// Index of the leftmost leaf under node `idx`
+    fn first_leaf(idx: u64) -> u64 {
+        // The node at level `trailing_ones` covering leaves [start, start + width)
+        // has index 2 * start + width - 1
+        let width = 1u64 << idx.trailing_ones();
+        (idx + 1 - width) / 2
+    }
+
+    /// Indices of the perfect subtrees a tree of `tree_size` leaves decomposes
+    /// into, left to right
+    fn perfect_subtree_roots(tree_size: u64) -> Vec<u64> {
+        // The perfect subtree covering leaves [start, start + width) lives at
+        // index 2 * start + width - 1 in the array layout
+        let node_idx = |start: u64, width: u64| 2 * start + width - 1;
+
+        let mut roots = Vec::new();
+        let mut start = 0;
+        for level in (0..u64::BITS).rev() {
+            let width = 1u64 << level;
+            if tree_size & width != 0 {
+                roots.push(node_idx(start, width));
+                start += width;
+            }
+        }
+        roots
+    }
+
+    fn leaf_key(index: u64) -> [u8; LEAF_KEY_LEN] {
+        let mut key = [0; LEAF_KEY_LEN];
+        key[..LEAF_PREFIX.len()].copy_from_slice(LEAF_PREFIX);
+        key[LEAF_PREFIX.len()..].copy_from_slice(&index.to_be_bytes());
+        key
+    }
+
+    /// A `leaf:` value for `leaf` in this tree's encoding and compression
+    fn encode_leaf(&self, leaf: &T) -> Result<Vec<u8>, SlateDbTreeError> {
+        let tagged = match self.leaf_encoding {
+            LeafEncoding::Postcard => postcard::to_extend(leaf, vec![LEAF_TAG_POSTCARD])
+                .map_err(|e| SlateDbTreeError::EncodingError(e.into()))?,
+            LeafEncoding::Cbor => {
+                let mut bytes = vec![LEAF_TAG_CBOR];
+                ciborium::ser::into_writer(leaf, &mut bytes)
+                    .map_err(|e| SlateDbTreeError::EncodingError(e.into()))?;
+                bytes
+            }
+        };
+        Ok(self.leaf_compression.encode(&tagged))
+    }
+
+    /// The tagged leaf inside the framed `leaf:` value of leaf `index`
+    fn unframe_leaf(index: u64, bytes: &[u8]) -> Result<Cow<'_, [u8]>, SlateDbTreeError> {
+        compression::decode(bytes)
+            .map_err(|e| SlateDbTreeError::EncodingError(format!("Leaf {}: {}", index, e).into()))
+    }
+
+    /// Decode the `leaf:` value of leaf `index` of a tree whose first
+    /// `untagged_leaves` leaves are untagged and whose first `unframed_leaves`
+    /// leaves have no compression format byte
+    fn decode_leaf(
+        untagged_leaves: u64,
+        unframed_leaves: u64,
+        index: u64,
+        bytes: &[u8],
+    ) -> Result<T, SlateDbTreeError> {
+        if index < untagged_leaves {
+            return postcard::from_bytes(bytes)
+                .map_err(|e| SlateDbTreeError::EncodingError(e.into()));
+        }
+        let bytes = if index < unframed_leaves {
+            Cow::Borrowed(bytes)
+        } else {
+            Self::unframe_leaf(index, bytes)?
+        };
+        match bytes.split_first() {
+            Some((&LEAF_TAG_POSTCARD, rest)) => {
+                postcard::from_bytes(rest).map_err(|e| SlateDbTreeError::EncodingError(e.into()))
+            }
+            Some((&LEAF_TAG_CBOR, rest)) => ciborium::de::from_reader(rest)
+                .map_err(|e| SlateDbTreeError::EncodingError(e.into())),
+            _ => Err(SlateDbTreeError::EncodingError(
+                format!("Leaf {} has no known encoding tag", index).into(),
+            )),
+        }
+    }
+
+    fn leaf_hash_index_key(hash: &[u8]) -> Vec<u8> {
+        let mut key = Vec::with_capacity(LEAF_HASH_INDEX_PREFIX.len() + hash.len());
+        key.extend_from_slice(LEAF_HASH_INDEX_PREFIX);
+        key.extend_from_slice(hash);
         key
     }
 
-    fn versioned_node_key(index: u64, version: u64) -> Vec<u8> {
-        let mut key = Vec::with_capacity(VERSIONED_NODE_PREFIX.len() + 16);
-        key.extend_from_slice(VERSIONED_NODE_PREFIX);
-        key.extend_from_slice(&index.to_be_bytes());
-        key.push(b'@');
-        key.extend_from_slice(&version.to_be_bytes());
+    fn versioned_node_key(index: u64, version: u64) -> [u8; VERSIONED_NODE_KEY_LEN] {
+        let prefix_len = VERSIONED_NODE_PREFIX.len();
+        let mut key = [0; VERSIONED_NODE_KEY_LEN];
+        key[..prefix_len].copy_from_slice(VERSIONED_NODE_PREFIX);
+        key[prefix_len..prefix_len + 8].copy_from_slice(&index.to_be_bytes());
+        key[prefix_len + 8] = b'@';
+        key[prefix_len + 9..].copy_from_slice(&version.to_be_bytes());
         key
     }
 
-    fn node_latest_version_key(index: u64) -> Vec<u8> {
-        let mut key = Vec::with_capacity(NODE_LATEST_VERSION_PREFIX.len() + 8);
-        key.extend_from_slice(NODE_LATEST_VERSION_PREFIX);
-        key.extend_from_slice(&index.to_be_bytes());
+    fn node_latest_version_key(index: u64) -> [u8; NODE_LATEST_VERSION_KEY_LEN] {
+        let mut key = [0; NODE_LATEST_VERSION_KEY_LEN];
+        key[..NODE_LATEST_VERSION_PREFIX.len()].copy_from_slice(NODE_LATEST_VERSION_PREFIX);
+        key[NODE_LATEST_VERSION_PREFIX.len()..].copy_from_slice(&index.to_be_bytes());
         key
     }
 
     async fn get_num_leaves(&self) -> Result<Option<u64>, SlateDbTreeError> {
         match self.db.get(META_KEY).await? {
-            Some(bytes) => {
-                let bytes_ref: &[u8] = bytes.as_ref();
-                let bytes_array: [u8; 8] = bytes_ref
-                    .try_into()
-                    .map_err(|_| SlateDbTreeError::EncodingError("Invalid metadata".into()))?;
-                let num_leaves = u64::from_be_bytes(bytes_array);
-                Ok(Some(num_leaves))
-            }
+            Some(bytes) => Self::parse_num_leaves(&bytes).map(Some),
             None => Ok(None),
         }
     }
 
+    /// The leaf count is the first 8 bytes of the meta value, big-endian.
+    /// Anything after them is left to later metadata fields, so older
+    /// readers keep working when the record grows.
+    fn parse_num_leaves(meta: &[u8]) -> Result<u64, SlateDbTreeError> {
+        let count: [u8; 8] = meta
+            .get(..8)
+            .and_then(|count| count.try_into().ok())
+            .ok_or_else(|| {
+                SlateDbTreeError::EncodingError(
+                    format!(
+                        "Invalid metadata: {} bytes, expected at least 8",
+                        meta.len()
+                    )
+                    .into(),
+                )
+            })?;
+        Ok(u64::from_be_bytes(count))
+    }
+
     async fn set_num_leaves(&self, num_leaves: u64) -> Result<(), SlateDbTreeError> {
         self.db
             .put(META_KEY, &num_leaves.to_be_bytes())
@@ -178,6 +1073,10 @@ where
         Ok(self.get_num_leaves().await?.unwrap_or(0))
     }
 
+    pub async fn is_empty(&self) -> Result<bool, SlateDbTreeError> {
+        Ok(self.len().await? == 0)
+    }
+
     /// Get the last committed tree size (for STH generation)
     pub async fn get_committed_size(&self) -> Result<u64, SlateDbTreeError> {
         match self.db.get(COMMITTED_SIZE_KEY).await? {
@@ -226,12 +1125,13 @@ where
     }
 
     /// Generate key for storing a precomputed merkle tile
-    fn tile_key(level: u8, index: u64) -> Vec<u8> {
-        let mut key = Vec::with_capacity(TILE_PREFIX.len() + 1 + 8);
-        key.extend_from_slice(TILE_PREFIX);
-        key.push(level);
-        key.push(b':');
-        key.extend_from_slice(&index.to_be_bytes());
+    fn tile_key(level: u8, index: u64) -> [u8; TILE_KEY_LEN] {
+        let prefix_len = TILE_PREFIX.len();
+        let mut key = [0; TILE_KEY_LEN];
+        key[..prefix_len].copy_from_slice(TILE_PREFIX);
+        key[prefix_len] = level;
+        key[prefix_len + 1] = b':';
+        key[prefix_len + 2..].copy_from_slice(&index.to_be_bytes());
         key
     }
 
@@ -248,69 +1148,509 @@ where
         }
     }
 
+    /// Leaves `start..=end_inclusive` in index order, read concurrently
+    pub async fn get_range(
+        &self,
+        start: u64,
+        end_inclusive: u64,
+    ) -> Result<Vec<T>, SlateDbTreeError> {
+        use futures::{StreamExt, TryStreamExt};
+
+        futures::stream::iter(start..=end_inclusive)
+            .map(|i| async move {
+                let bytes = self.db.get(&Self::leaf_key(i)).await?.ok_or_else(|| {
+                    SlateDbTreeError::InconsistentState(format!("Leaf {} is missing", i))
+                })?;
+                Self::decode_leaf(self.untagged_leaves, self.unframed_leaves, i, &bytes)
+            })
+            // `buffered` yields results in input order however the reads complete
+            .buffered(LEAF_RANGE_CONCURRENCY)
+            .try_collect()
+            .await
+    }
+
+    /// Whether `leaf` hashes to the stored hash of the node of leaf `idx`.
+    /// Leaf nodes are never rewritten, so their latest version is the one.
+    pub async fn leaf_matches_node(&self, idx: u64, leaf: &T) -> Result<bool, SlateDbTreeError> {
+        let node_idx: InternalIdx = LeafIdx::new(idx).into();
+        let stored = self.get_node_hash(node_idx.as_u64()).await?;
+        Ok(leaf_hash::<H, _>(leaf) == stored)
+    }
+
+    /// Index of the leaf whose Merkle leaf hash is `hash`. Leaves appended but
+    /// not yet committed are found too; callers compare against a tree size.
+    ///
+    /// An interrupted append can leave index entries for leaves past the end
+    /// of the tree, or overwritten since, so both are checked. Hashes with no
+    /// `lhi:` entry are looked up in the legacy index, if one is set.
+    pub async fn lookup_by_leaf_hash(&self, hash: &[u8]) -> Result<Option<u64>, SlateDbTreeError> {
+        Ok(self.lookup_by_leaf_hashes(&[hash]).await?.pop().flatten())
+    }
+
+    /// [`Self::lookup_by_leaf_hash`] for each of `hashes`, reading the index
+    /// entries and then the leaves they point at with one
+    /// [`RateLimitedDb::get_many`] each
+    pub async fn lookup_by_leaf_hashes<K: AsRef<[u8]>>(
+        &self,
+        hashes: &[K],
+    ) -> Result<Vec<Option<u64>>, SlateDbTreeError> {
+        let keys: Vec<_> = hashes
+            .iter()
+            .map(|hash| Self::leaf_hash_index_key(hash.as_ref()))
+            .collect();
+        let mut entries = self.db.get_many(&keys).await?;
+
+        if let Some(prefix) = &self.legacy_hash_index {
+            let missing: Vec<usize> = (0..entries.len())
+                .filter(|&i| entries[i].is_none())
+                .collect();
+            if !missing.is_empty() {
+                let legacy_keys: Vec<_> = missing
+                    .iter()
+                    .map(|&i| [prefix.as_slice(), hashes[i].as_ref()].concat())
+                    .collect();
+                let legacy_entries = self.db.get_many(&legacy_keys).await?;
+                for (i, entry) in missing.into_iter().zip(legacy_entries) {
+                    entries[i] = entry;
+                }
+            }
+        }
+
+        let num_leaves = self.len().await?;
+        let candidates = entries
+            .into_iter()
+            .map(|entry| {
+                let Some(bytes) = entry else {
+                    return Ok(None);
+                };
+                let index: [u8; 8] = bytes.as_ref().try_into().map_err(|_| {
+                    SlateDbTreeError::InconsistentState(
+                        "Leaf hash index entry is not 8 bytes".into(),
+                    )
+                })?;
+                Ok(Some(u64::from_be_bytes(index)).filter(|&index| index < num_leaves))
+            })
+            .collect::<Result<Vec<_>, SlateDbTreeError>>()?;
+
+        // An entry only counts if the leaf it points at has that hash
+        let leaf_keys: Vec<_> = candidates
+            .iter()
+            .flatten()
+            .map(|&index| Self::leaf_key(index))
+            .collect();
+        let mut leaves = self.db.get_many(&leaf_keys).await?.into_iter();
+        let mut found = Vec::with_capacity(hashes.len());
+        for (hash, candidate) in hashes.iter().zip(candidates) {
+            let Some(index) = candidate else {
+                found.push(None);
+                continue;
+            };
+            let matches = match leaves.next().flatten() {
+                Some(leaf_bytes) => {
+                    let leaf = Self::decode_leaf(
+                        self.untagged_leaves,
+                        self.unframed_leaves,
+                        index,
+                        &leaf_bytes,
+                    )?;
+                    leaf_hash::<H, _>(&leaf).as_slice() == hash.as_ref()
+                }
+                None => false,
+            };
+            found.push(matches.then_some(index));
+        }
+        Ok(found)
+    }
+
+    /// Add a put to `chunk`, writing it out once it is full
+    async fn put_chunked(
+        &self,
+        chunk: &mut ChunkedPuts,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<(), SlateDbTreeError> {
+        chunk.batch.put(key, value);
+        chunk.puts += 1;
+        if chunk.puts >= chunk.max_puts {
+            let mut batch = std::mem::replace(&mut chunk.batch, self.db.batch());
+            if chunk.chunks_written == 0 {
+                batch.put(PENDING_APPEND_KEY, chunk.marker);
+            }
+            #[cfg(test)]
+            if self.fail_at_chunk == Some(chunk.chunks_written) {
+                return Err(SlateDbTreeError::InconsistentState(
+                    "Injected chunk write failure".into(),
+                ));
+            }
+            self.db
+                .write_batch(batch)
+                .await
+                .context("writing a chunk of appended nodes")?;
+            chunk.puts = 0;
+            chunk.chunks_written += 1;
+        }
+        Ok(())
+    }
+
+    /// Read the frontier of the tree at `size` from storage
+    async fn load_frontier(&self, size: u64) -> Result<Frontier<H>, SlateDbTreeError> {
+        let indices = Self::perfect_subtree_roots(size);
+        let hashes = self.get_node_hashes(&indices).await?;
+        Ok(Frontier {
+            size,
+            nodes: indices.into_iter().zip(hashes).collect(),
+        })
+    }
+
+    /// The frontier at `size` after an append that started from `previous`
+    /// and wrote `computed_hashes`. `None` if a node is in neither, which
+    /// leaves the next append to read the frontier from storage.
+    fn advance_frontier(
+        previous: &Frontier<H>,
+        size: u64,
+        computed_hashes: &std::collections::BTreeMap<u64, digest::Output<H>>,
+    ) -> Option<Frontier<H>> {
+        let nodes = Self::perfect_subtree_roots(size)
+            .into_iter()
+            .map(|idx| {
+                let hash = computed_hashes
+                    .get(&idx)
+                    .or_else(|| previous.nodes.get(&idx))?;
+                Some((idx, hash.clone()))
+            })
+            .collect::<Option<_>>()?;
+        Some(Frontier { size, nodes })
+    }
+
+    /// The frontier at `size`, from the last append if it ended there and
+    /// otherwise from storage. Taking it out of `slot` means a failed append
+    /// leaves no frontier behind.
+    async fn take_frontier(
+        &self,
+        slot: &mut Option<Frontier<H>>,
+        size: u64,
+    ) -> Result<Frontier<H>, SlateDbTreeError> {
+        match slot.take() {
+            Some(frontier) if frontier.size == size => Ok(frontier),
+            _ => {
+                self.clear_interrupted_append().await?;
+                self.load_frontier(size).await
+            }
+        }
+    }
+
+    /// Delete what an append that failed after writing some of its chunks
+    /// left behind. Its nodes are stored under the size it was heading for,
+    /// where a later append ending at the same size would otherwise find them.
+    async fn clear_interrupted_append(&self) -> Result<(), SlateDbTreeError> {
+        let Some(marker) = self.db.get(PENDING_APPEND_KEY).await? else {
+            return Ok(());
+        };
+        let marker: [u8; 16] = marker.as_ref().try_into().map_err(|_| {
+            SlateDbTreeError::InconsistentState("Pending append marker is not 16 bytes".into())
+        })?;
+        let start = u64::from_be_bytes(marker[..8].try_into().unwrap());
+        let end = u64::from_be_bytes(marker[8..].try_into().unwrap());
+        let size = self.len().await?;
+        if size != start {
+            return Err(SlateDbTreeError::InconsistentState(format!(
+                "Interrupted append of leaves {}..{} does not start at the tree size {}",
+                start, end, size
+            )));
+        }
+        tracing::warn!(
+            "Clearing an interrupted append of leaves {}..{}",
+            start,
+            end
+        );
+
+        let mut batch = self.db.batch();
+        let mut nodes = std::collections::BTreeSet::new();
+        let mut range_start = start;
+        while range_start < end {
+            let range_end = end.min(range_start + self.max_batch_puts as u64);
+            let leaf_keys: Vec<_> = (range_start..range_end).map(Self::leaf_key).collect();
+            let leaves = self.db.get_many(&leaf_keys).await?;
+
+            let mut hash_keys = Vec::new();
+            for (index, leaf) in (range_start..range_end).zip(leaves) {
+                if let Some(bytes) = leaf {
+                    let leaf = Self::decode_leaf(
+                        self.untagged_leaves,
+                        self.unframed_leaves,
+                        index,
+                        &bytes,
+                    )?;
+                    hash_keys.push((index, Self::leaf_hash_index_key(&leaf_hash::<H, _>(&leaf))));
+                    batch.delete(Self::leaf_key(index));
+                }
+
+                let num_leaves = index + 1;
+                let mut cur_idx: InternalIdx = LeafIdx::new(index).into();
+                nodes.insert(cur_idx.as_u64());
+                let root_idx = root_idx(num_leaves);
+                while cur_idx != root_idx {
+                    cur_idx = cur_idx.parent(num_leaves);
+                    nodes.insert(cur_idx.as_u64());
+                }
+            }
+
+            // Keep index entries a committed leaf with the same hash owns
+            let keys: Vec<_> = hash_keys.iter().map(|(_, key)| key).collect();
+            let owners = self.db.get_many(&keys).await?;
+            for ((index, key), owner) in hash_keys.iter().zip(owners) {
+                if owner.is_some_and(|owner| owner.as_ref() == index.to_be_bytes()) {
+                    batch.delete(key);
+                }
+            }
+
+            self.db
+                .write_batch(std::mem::replace(&mut batch, self.db.batch()))
+                .await?;
+            range_start = range_end;
+        }
+
+        for (count, idx) in nodes.into_iter().enumerate() {
+            batch.delete(Self::versioned_node_key(idx, end));
+            // Nodes that start past the tree size do not exist yet
+            if Self::first_leaf(idx) >= start {
+                batch.delete(Self::node_latest_version_key(idx));
+            }
+            if (count + 1) % self.max_batch_puts == 0 {
+                self.db
+                    .write_batch(std::mem::replace(&mut batch, self.db.batch()))
+                    .await?;
+            }
+        }
+        batch.delete(PENDING_APPEND_KEY);
+        self.db.write_batch(batch).await?;
+        Ok(())
+    }
+
     /// Appends multiple items to the tree along with additional key-value pairs in a single atomic batch.
     /// This ensures consistency between the merkle tree and any associated data.
     /// Returns the starting index of the newly added items.
+    ///
+    /// Safe to call concurrently: `write_lock` is held from reading the current
+    /// size until the batch is written, so appends are applied one at a time.
+    ///
+    /// Existing siblings come from the frontier the previous append left in
+    /// memory, so in steady state no siblings are read back from storage.
+    ///
+    /// Leaves, their hash index entries and the nodes the append creates are
+    /// written ahead in batches of at most `max_batch_puts`. None of them is
+    /// reachable below the current size, so an append that fails part way
+    /// leaves the tree as it was. The final batch moves `meta` and holds
+    /// everything that is: version pointers of nodes that already existed,
+    /// tiles, and `additional_data`.
     pub async fn batch_push_with_data(
         &self,
         items: Vec<T>,
         additional_data: Vec<(Vec<u8>, Vec<u8>)>,
     ) -> Result<u64, SlateDbTreeError> {
-        let _write_guard = self.write_lock.lock().await;
+        let mut write_guard = self.write_lock.lock().await;
+        self.push_locked(&mut write_guard, items, additional_data)
+            .await
+    }
 
+    /// Append `items` that are not in the tree yet, judged by their leaf
+    /// hash, so that an import restarted from an earlier checkpoint does not
+    /// log its leaves twice. A leaf repeated within `items` is appended once.
+    ///
+    /// Returns what happened to each item, in order. `write_lock` is held
+    /// across the lookups and the append, so a concurrent append of the same
+    /// leaf cannot slip in between.
+    pub async fn append_if_absent(
+        &self,
+        items: Vec<T>,
+    ) -> Result<Vec<LeafAppend>, SlateDbTreeError> {
+        let mut write_guard = self.write_lock.lock().await;
+        let starting_index = self.len().await?;
+
+        let hashes: Vec<_> = items.iter().map(leaf_hash::<H, _>).collect();
+        let existing = self.lookup_by_leaf_hashes(&hashes).await?;
+
+        let mut outcomes = Vec::with_capacity(items.len());
+        let mut absent = Vec::new();
+        let mut appending = HashMap::new();
+        for ((item, hash), existing) in items.into_iter().zip(hashes).zip(existing) {
+            if let Some(index) = existing {
+                outcomes.push(LeafAppend::Skipped(index));
+                continue;
+            }
+            let next = starting_index + absent.len() as u64;
+            match appending.entry(hash.to_vec()) {
+                Entry::Occupied(entry) => outcomes.push(LeafAppend::Skipped(*entry.get())),
+                Entry::Vacant(entry) => {
+                    entry.insert(next);
+                    outcomes.push(LeafAppend::Appended(next));
+                    absent.push(item);
+                }
+            }
+        }
+
+        if !absent.is_empty() {
+            self.push_locked(&mut write_guard, absent, vec![]).await?;
+        }
+        Ok(outcomes)
+    }
+
+    /// [`Self::batch_push_with_data`] with `write_lock` already held, its
+    /// frontier in `slot`
+    async fn push_locked(
+        &self,
+        slot: &mut Option<Frontier<H>>,
+        items: Vec<T>,
+        additional_data: Vec<(Vec<u8>, Vec<u8>)>,
+    ) -> Result<u64, SlateDbTreeError> {
         let starting_index = self.len().await?;
 
         if items.is_empty() && additional_data.is_empty() {
             return Ok(starting_index);
         }
+        check_capacity(starting_index, items.len())?;
+
+        let frontier = self.take_frontier(slot, starting_index).await?;
+        let chunk = self.chunked_puts(self.db.batch(), self.max_batch_puts, starting_index, &items);
+        let (batch, final_tree_size, computed_hashes) = self
+            .stage_append(
+                chunk,
+                &frontier,
+                starting_index,
+                &items,
+                false,
+                additional_data,
+            )
+            .await?;
 
-        // Pre-fetch nodes that exist in the original tree
-        let mut nodes_to_prefetch = std::collections::BTreeSet::new();
+        // Single atomic write for both tree updates and tiles
+        self.db
+            .write_batch(batch)
+            .await
+            .context("writing appended leaves")?;
 
-        // Calculate which nodes we'll need that exist in the original tree
-        for i in 0..items.len() {
-            let leaf_position = starting_index + i as u64;
-            let new_leaf_idx = LeafIdx::new(leaf_position);
-            let tree_size_when_processing = leaf_position + 1;
+        *slot = Self::advance_frontier(&frontier, final_tree_size, &computed_hashes);
 
-            let mut cur_idx: InternalIdx = new_leaf_idx.into();
-            let root_idx = root_idx(tree_size_when_processing);
+        Ok(starting_index)
+    }
 
-            while cur_idx != root_idx {
-                let sibling_idx = cur_idx.sibling(tree_size_when_processing);
+    /// Append `items1` to `tree1` and `items2` to `tree2` in one atomic
+    /// write, for logs that keep several trees (say one per entry type) in
+    /// step. Returns the starting index of each tree's new items.
+    ///
+    /// The trees must be opened on handles to the same database with
+    /// distinct key prefixes (see [`RateLimitedDb::with_key_prefix`]), so
+    /// their `meta` keys and nodes do not collide. `tree1` may write its
+    /// leaves and new nodes ahead in chunks as [`Self::batch_push_with_data`]
+    /// does; everything of `tree2` goes into the final batch, which moves
+    /// both trees' `meta` together.
+    pub async fn batch_push_across_trees<T2>(
+        tree1: &Self,
+        items1: Vec<T>,
+        tree2: &SlateDbBackedTree<H, T2>,
+        items2: Vec<T2>,
+    ) -> Result<(u64, u64), SlateDbTreeError>
+    where
+        T2: HashableLeaf + serde::Serialize + serde::de::DeserializeOwned,
+    {
+        if !tree1.db.shares_db_with(&tree2.db) {
+            return Err(SlateDbTreeError::InconsistentState(
+                "Trees appended to together must share a database".into(),
+            ));
+        }
+        if tree1.db.key_prefix() == tree2.db.key_prefix() {
+            return Err(SlateDbTreeError::InconsistentState(
+                "Trees appended to together need distinct key prefixes".into(),
+            ));
+        }
 
-                // Only prefetch siblings that exist in the original tree
-                if sibling_idx.as_u64() < starting_index * 2 {
-                    nodes_to_prefetch.insert(sibling_idx.as_u64());
-                }
+        // Locks are taken in key prefix order, so concurrent calls naming
+        // the trees the other way round cannot deadlock
+        let (mut guard1, mut guard2) = if tree1.db.key_prefix() < tree2.db.key_prefix() {
+            let guard1 = tree1.write_lock.lock().await;
+            (guard1, tree2.write_lock.lock().await)
+        } else {
+            let guard2 = tree2.write_lock.lock().await;
+            (tree1.write_lock.lock().await, guard2)
+        };
 
-                cur_idx = cur_idx.parent(tree_size_when_processing);
-            }
-        }
+        let start1 = tree1.len().await?;
+        let start2 = tree2.len().await?;
+        check_capacity(start1, items1.len())?;
+        check_capacity(start2, items2.len())?;
+        let frontier1 = tree1.take_frontier(&mut guard1, start1).await?;
+        let frontier2 = tree2.take_frontier(&mut guard2, start2).await?;
 
-        let mut prefetched_nodes = std::collections::BTreeMap::new();
-        if !nodes_to_prefetch.is_empty() {
-            // Prefetch latest versions for these nodes
-            let futures: Vec<_> = nodes_to_prefetch
-                .iter()
-                .map(|&idx| self.get_node_hash(idx))
-                .collect();
+        let chunk1 = tree1.chunked_puts(tree1.db.batch(), tree1.max_batch_puts, start1, &items1);
+        let (batch, size1, hashes1) = tree1
+            .stage_append(chunk1, &frontier1, start1, &items1, false, Vec::new())
+            .await?;
+        let chunk2 = tree2.chunked_puts(batch.rebind(&tree2.db), usize::MAX, start2, &items2);
+        let (batch, size2, hashes2) = tree2
+            .stage_append(chunk2, &frontier2, start2, &items2, false, Vec::new())
+            .await?;
 
-            let results = futures::future::try_join_all(futures).await?;
+        tree1
+            .db
+            .write_batch(batch)
+            .await
+            .context("writing leaves appended to both trees")?;
 
-            for (&idx, hash) in nodes_to_prefetch.iter().zip(results.iter()) {
-                prefetched_nodes.insert(idx, hash.clone());
-            }
+        *guard1 = Self::advance_frontier(&frontier1, size1, &hashes1);
+        *guard2 = SlateDbBackedTree::<H, T2>::advance_frontier(&frontier2, size2, &hashes2);
+
+        Ok((start1, start2))
+    }
+
+    /// Chunks for appending `items` at `starting_index`, starting from `batch`
+    fn chunked_puts(
+        &self,
+        batch: DbBatch,
+        max_puts: usize,
+        starting_index: u64,
+        items: &[T],
+    ) -> ChunkedPuts {
+        let mut marker = [0; 16];
+        marker[..8].copy_from_slice(&starting_index.to_be_bytes());
+        marker[8..].copy_from_slice(&(starting_index + items.len() as u64).to_be_bytes());
+        ChunkedPuts {
+            batch,
+            puts: 0,
+            max_puts,
+            chunks_written: 0,
+            marker,
         }
+    }
 
-        let mut batch = WriteBatch::new();
+    /// Write the chunks of an append of `items` and stage its final batch,
+    /// returning that batch unwritten along with the new tree size and the
+    /// node hashes the append computed. `leaves_written` skips the `leaf:`
+    /// puts of items already stored ahead of the append.
+    async fn stage_append(
+        &self,
+        mut chunk: ChunkedPuts,
+        frontier: &Frontier<H>,
+        starting_index: u64,
+        items: &[T],
+        leaves_written: bool,
+        additional_data: Vec<(Vec<u8>, Vec<u8>)>,
+    ) -> Result<
+        (
+            DbBatch,
+            u64,
+            std::collections::BTreeMap<u64, digest::Output<H>>,
+        ),
+        SlateDbTreeError,
+    > {
         let mut current_num_leaves = starting_index;
         let mut computed_hashes = std::collections::BTreeMap::<u64, digest::Output<H>>::new();
 
         for item in items.iter() {
-            let leaf_bytes = postcard::to_stdvec(item)
-                .map_err(|e| SlateDbTreeError::EncodingError(e.to_string()))?;
-            batch.put(Self::leaf_key(current_num_leaves), &leaf_bytes);
+            if !leaves_written {
+                let leaf_bytes = self.encode_leaf(item)?;
+                self.put_chunked(&mut chunk, &Self::leaf_key(current_num_leaves), &leaf_bytes)
+                    .await?;
+            }
 
             let new_leaf_idx = LeafIdx::new(current_num_leaves);
             let new_num_leaves = current_num_leaves + 1;
@@ -318,6 +1658,12 @@ where
             let mut cur_idx: InternalIdx = new_leaf_idx.into();
             let leaf_hash = leaf_hash::<H, _>(item);
             computed_hashes.insert(cur_idx.as_u64(), leaf_hash.clone());
+            self.put_chunked(
+                &mut chunk,
+                &Self::leaf_hash_index_key(&leaf_hash),
+                &current_num_leaves.to_be_bytes(),
+            )
+            .await?;
 
             let root_idx = root_idx(new_num_leaves);
             let mut cur_hash = leaf_hash;
@@ -330,7 +1676,7 @@ where
                     hash.clone()
                 } else if sibling_idx.as_u64() >= current_num_leaves * 2 {
                     digest::Output::<H>::default()
-                } else if let Some(hash) = prefetched_nodes.get(&sibling_idx.as_u64()) {
+                } else if let Some(hash) = frontier.nodes.get(&sibling_idx.as_u64()) {
                     hash.clone()
                 } else {
                     // Read the node at the version before this batch started
@@ -353,15 +1699,39 @@ where
             current_num_leaves = new_num_leaves;
         }
 
-        // After processing all entries, store versioned nodes for the final tree state
+        // After processing all entries, store versioned nodes for the final
+        // tree state. Moving the version pointer of a node that existed
+        // before would change what reads below the new size see, so those
+        // wait for the final batch.
         let final_tree_size = current_num_leaves;
+        let mut existing_nodes = Vec::new();
         for (node_idx, node_hash) in computed_hashes.iter() {
-            batch.put(
-                Self::versioned_node_key(*node_idx, final_tree_size),
+            self.put_chunked(
+                &mut chunk,
+                &Self::versioned_node_key(*node_idx, final_tree_size),
                 node_hash.as_ref(),
-            );
+            )
+            .await?;
+            if Self::first_leaf(*node_idx) < starting_index {
+                existing_nodes.push(*node_idx);
+            } else {
+                self.put_chunked(
+                    &mut chunk,
+                    &Self::node_latest_version_key(*node_idx),
+                    &final_tree_size.to_be_bytes(),
+                )
+                .await?;
+            }
+        }
+
+        // Whatever did not fill a chunk goes out with the final batch
+        let mut batch = chunk.batch;
+        if chunk.chunks_written > 0 {
+            batch.delete(PENDING_APPEND_KEY);
+        }
+        for node_idx in existing_nodes {
             batch.put(
-                Self::node_latest_version_key(*node_idx),
+                Self::node_latest_version_key(node_idx),
                 final_tree_size.to_be_bytes(),
             );
         }
@@ -383,15 +1753,12 @@ where
         )
         .await?;
 
-        // Single atomic write for both tree updates and tiles
-        self.db.write_batch(batch).await?;
-
-        Ok(starting_index)
+        Ok((batch, final_tree_size, computed_hashes))
     }
 
     async fn precompute_tiles_batch(
         &self,
-        batch: &mut WriteBatch,
+        batch: &mut DbBatch,
         starting_index: u64,
         tree_size: u64,
         computed_hashes: &std::collections::BTreeMap<u64, digest::Output<H>>,
@@ -576,15 +1943,13 @@ where
         match (old_exists?, new_exists?) {
             (Some(_), Some(_)) => {
                 // Both are published STH boundaries
-                let idxs = indices_for_consistency_proof(old_size, new_size - old_size);
+                let idxs = crate::merkle_tree::consistency::indices_for_consistency_proof(
+                    old_size,
+                    new_size - old_size,
+                );
 
                 // For consistency proofs, we need nodes at the new_size version
-                let hash_futures: Vec<_> = idxs
-                    .iter()
-                    .map(|&node_idx| self.get_node_hash_at_version(node_idx, new_size))
-                    .collect();
-
-                let proof_hashes = futures::future::try_join_all(hash_futures).await?;
+                let proof_hashes = self.get_proof_node_hashes(&idxs, new_size).await?;
 
                 Ok(ConsistencyProof::from_digests(proof_hashes.iter()))
             }
@@ -599,6 +1964,131 @@ where
         }
     }
 
+    /// Same proof as [`Self::prove_consistency_between`], but each node fetch
+    /// is spawned the moment its index is computed, and the STH boundary
+    /// checks run alongside those fetches instead of before them. When proof
+    /// nodes have fallen out of the block cache this overlaps their reads
+    /// rather than paying for the boundary checks and the fetches in turn.
+    ///
+    /// Fetches only start once `new_size` is known to be committed, so the
+    /// node cache never records hashes for a version that may still change.
+    ///
+    /// `get-sth-consistency` still serves [`Self::prove_consistency_between`];
+    /// this variant is for benchmarking against a real object store first.
+    pub async fn parallel_consistency_proof(
+        self: &Arc<Self>,
+        old_size: u64,
+        new_size: u64,
+    ) -> Result<ConsistencyProof<H>, SlateDbTreeError>
+    where
+        H: Send + Sync + 'static,
+        T: Send + Sync + 'static,
+    {
+        if old_size == 0 {
+            return Err(SlateDbTreeError::InconsistentState(
+                "Cannot create consistency proof from empty tree".into(),
+            ));
+        }
+
+        if old_size > new_size {
+            return Err(SlateDbTreeError::InconsistentState(format!(
+                "Old size {} must be less than or equal to new size {}",
+                old_size, new_size
+            )));
+        }
+
+        if old_size == new_size {
+            return Ok(ConsistencyProof::from_digests(std::iter::empty()));
+        }
+
+        let current_size = self.get_committed_size().await?;
+        if new_size > current_size {
+            return Err(SlateDbTreeError::InconsistentState(format!(
+                "New size {} exceeds current committed tree size {}",
+                new_size, current_size
+            )));
+        }
+
+        let old_version_check = Self::versioned_node_key(root_idx(old_size).as_u64(), old_size);
+        let new_version_check = Self::versioned_node_key(root_idx(new_size).as_u64(), new_size);
+        let boundary_checks = async {
+            tokio::join!(
+                self.db.get(&old_version_check),
+                self.db.get(&new_version_check)
+            )
+        };
+
+        // With bounded fetches there is no spawning ahead: the nodes are read
+        // a few at a time alongside the boundary checks
+        if self.proof_fetch_concurrency.is_some() {
+            let idxs = crate::merkle_tree::consistency::indices_for_consistency_proof(
+                old_size,
+                new_size - old_size,
+            );
+            let ((old_exists, new_exists), proof_hashes) =
+                tokio::join!(boundary_checks, self.get_proof_node_hashes(&idxs, new_size));
+            if let Some(e) = Self::boundary_error(old_size, new_size, old_exists, new_exists) {
+                return Err(e);
+            }
+            return Ok(ConsistencyProof::from_digests(proof_hashes?.iter()));
+        }
+
+        // Dropping the set aborts the fetches still running, so a failed
+        // boundary check or a caller that gives up does not leave them behind
+        let mut fetches = JoinSet::new();
+        let mut num_fetches = 0;
+        visit_consistency_proof_indices(old_size, new_size - old_size, |node_idx| {
+            let tree = self.clone();
+            let position = num_fetches;
+            num_fetches += 1;
+            fetches.spawn(async move {
+                (
+                    position,
+                    tree.get_node_hash_at_version(node_idx, new_size).await,
+                )
+            });
+        });
+
+        let (old_exists, new_exists) = boundary_checks.await;
+        if let Some(e) = Self::boundary_error(old_size, new_size, old_exists, new_exists) {
+            return Err(e);
+        }
+
+        let mut proof_hashes = vec![None; num_fetches];
+        while let Some(fetch) = fetches.join_next().await {
+            let (position, hash) = fetch.map_err(|e| {
+                SlateDbTreeError::InconsistentState(format!("Proof node fetch failed: {}", e))
+            })?;
+            proof_hashes[position] = Some(hash?);
+        }
+
+        Ok(ConsistencyProof::from_digests(
+            proof_hashes.iter().flatten(),
+        ))
+    }
+
+    /// Why a consistency proof between `old_size` and `new_size` cannot be
+    /// served, given whether each size's root was versioned
+    fn boundary_error<V>(
+        old_size: u64,
+        new_size: u64,
+        old_exists: Result<Option<V>, slatedb::SlateDBError>,
+        new_exists: Result<Option<V>, slatedb::SlateDBError>,
+    ) -> Option<SlateDbTreeError> {
+        match (old_exists, new_exists) {
+            (Err(e), _) | (_, Err(e)) => Some(e.into()),
+            (Ok(None), _) => Some(SlateDbTreeError::InconsistentState(format!(
+                "Old tree size {} is not a published STH boundary",
+                old_size
+            ))),
+            (_, Ok(None)) => Some(SlateDbTreeError::InconsistentState(format!(
+                "New tree size {} is not a published STH boundary",
+                new_size
+            ))),
+            (Ok(Some(_)), Ok(Some(_))) => None,
+        }
+    }
+
     pub async fn get_node_hash(&self, idx: u64) -> Result<digest::Output<H>, SlateDbTreeError> {
         // Get the latest version for this node
         match self.db.get(&Self::node_latest_version_key(idx)).await? {
@@ -619,20 +2109,177 @@ where
         }
     }
 
+    /// Hashes of the proof nodes `idxs` at `version`, in order, read as
+    /// [`Self::set_proof_fetch_concurrency`] allows
+    async fn get_proof_node_hashes(
+        &self,
+        idxs: &[u64],
+        version: u64,
+    ) -> Result<Vec<digest::Output<H>>, SlateDbTreeError> {
+        use futures::{StreamExt, TryStreamExt};
+
+        let fetches: Vec<_> = idxs
+            .iter()
+            .map(|&node_idx| self.get_node_hash_at_version(node_idx, version))
+            .collect();
+        match self.proof_fetch_concurrency {
+            None => futures::future::try_join_all(fetches).await,
+            Some(limit) => {
+                futures::stream::iter(fetches)
+                    .buffered(limit)
+                    .try_collect()
+                    .await
+            }
+        }
+    }
+
+    /// Latest hashes of `indices`, as [`Self::get_node_hash`] would return
+    /// them one by one. Version pointers and then node hashes are each read in
+    /// a single sorted multi-get, with keys built into one buffer per phase.
+    async fn get_node_hashes(
+        &self,
+        indices: &[u64],
+    ) -> Result<Vec<digest::Output<H>>, SlateDbTreeError> {
+        let version_keys: Vec<_> = indices
+            .iter()
+            .map(|&idx| Self::node_latest_version_key(idx))
+            .collect();
+        let versions = self.db.get_many(&version_keys).await?;
+
+        let mut hashes = vec![digest::Output::<H>::default(); indices.len()];
+        let mut pending = Vec::new();
+        for (position, (&idx, version_bytes)) in indices.iter().zip(versions).enumerate() {
+            // No version pointer means this node doesn't exist yet
+            let Some(version_bytes) = version_bytes else {
+                continue;
+            };
+            let version_array: [u8; 8] = version_bytes
+                .as_ref()
+                .try_into()
+                .map_err(|_| SlateDbTreeError::EncodingError("Invalid version format".into()))?;
+            let version = u64::from_be_bytes(version_array);
+
+            let cached = self
+                .node_cache
+                .as_ref()
+                .is_some_and(|cache| cache.get_into(&(idx, version), &mut hashes[position]));
+            self.count_node_cache_lookup(cached);
+            if !cached {
+                pending.push((position, idx, version));
+            }
+        }
+
+        let node_keys: Vec<_> = pending
+            .iter()
+            .map(|&(_, idx, version)| Self::versioned_node_key(idx, version))
+            .collect();
+        let nodes = self.db.get_many(&node_keys).await?;
+
+        for ((position, idx, version), bytes) in pending.into_iter().zip(nodes) {
+            let bytes = bytes.ok_or_else(|| {
+                SlateDbTreeError::InconsistentState(format!(
+                    "Node {} has latest version {} but no data",
+                    idx, version
+                ))
+            })?;
+            if bytes.len() != hashes[position].len() {
+                return Err(SlateDbTreeError::EncodingError("Invalid hash size".into()));
+            }
+            hashes[position].copy_from_slice(&bytes);
+
+            if let Some(ref cache) = self.node_cache {
+                cache.insert((idx, version), &bytes);
+            }
+        }
+
+        Ok(hashes)
+    }
+
+    /// Load the latest hashes of the nodes in the top `levels` levels of the
+    /// tree into the node cache, returning how many were loaded. Every proof
+    /// passes through these nodes, so this saves most of the reads a cold
+    /// cache costs after a restart. Nodes unchanged since the committed size
+    /// are also cached under it, which is the version proofs ask for. Does
+    /// nothing without a node cache.
+    pub async fn warm_cache(&self, levels: u32) -> Result<usize, SlateDbTreeError> {
+        let Some(cache) = &self.node_cache else {
+            return Ok(0);
+        };
+        let num_leaves = self.len().await?;
+        if num_leaves == 0 || levels == 0 {
+            return Ok(0);
+        }
+
+        // With in-order indices, level `l` holds every index whose lowest
+        // `l + 1` bits are `0` followed by `l` ones
+        let num_nodes = num_internal_nodes(num_leaves);
+        let top = root_idx(num_leaves).level();
+        let indices: Vec<u64> = (top.saturating_sub(levels - 1)..=top)
+            .flat_map(|level| {
+                let step = 1u64.checked_shl(level + 1).unwrap_or(u64::MAX);
+                std::iter::successors(Some((1u64 << level) - 1), move |idx| idx.checked_add(step))
+                    .take_while(move |&idx| idx < num_nodes)
+            })
+            .collect();
+
+        let version_keys: Vec<_> = indices
+            .iter()
+            .map(|&idx| Self::node_latest_version_key(idx))
+            .collect();
+        let versions = self.db.get_many(&version_keys).await?;
+
+        let mut present = Vec::with_capacity(indices.len());
+        for (&idx, version_bytes) in indices.iter().zip(versions) {
+            let Some(version_bytes) = version_bytes else {
+                continue;
+            };
+            let version_array: [u8; 8] = version_bytes
+                .as_ref()
+                .try_into()
+                .map_err(|_| SlateDbTreeError::EncodingError("Invalid version format".into()))?;
+            present.push((idx, u64::from_be_bytes(version_array)));
+        }
+
+        let node_keys: Vec<_> = present
+            .iter()
+            .map(|&(idx, version)| Self::versioned_node_key(idx, version))
+            .collect();
+        let nodes = self.db.get_many(&node_keys).await?;
+        let committed_size = self.get_committed_size().await?;
+
+        let mut loaded = 0;
+        for ((idx, version), bytes) in present.into_iter().zip(nodes) {
+            let bytes = bytes.ok_or_else(|| {
+                SlateDbTreeError::InconsistentState(format!(
+                    "Node {} has latest version {} but no data",
+                    idx, version
+                ))
+            })?;
+            if bytes.len() != <H as Digest>::output_size() {
+                return Err(SlateDbTreeError::EncodingError("Invalid hash size".into()));
+            }
+            if version < committed_size {
+                cache.insert((idx, committed_size), &bytes);
+            }
+            cache.insert((idx, version), &bytes);
+            loaded += 1;
+        }
+
+        Ok(loaded)
+    }
+
     pub async fn get_node_hash_at_version(
         &self,
         idx: u64,
         version: u64,
     ) -> Result<digest::Output<H>, SlateDbTreeError> {
         if let Some(ref cache) = self.node_cache {
-            if let Some(entry) = cache.get(&(idx, version)) {
-                let cached_hash = entry.value();
-                let mut hash = digest::Output::<H>::default();
-                if cached_hash.len() == hash.len() {
-                    hash.copy_from_slice(cached_hash);
-                    return Ok(hash);
-                }
+            let mut hash = digest::Output::<H>::default();
+            if cache.get_into(&(idx, version), &mut hash) {
+                self.count_node_cache_lookup(true);
+                return Ok(hash);
             }
+            self.count_node_cache_lookup(false);
         }
 
         let exact_key = Self::versioned_node_key(idx, version);
@@ -642,7 +2289,7 @@ where
                 hash.copy_from_slice(&bytes);
 
                 if let Some(ref cache) = self.node_cache {
-                    cache.insert((idx, version), bytes.to_vec());
+                    cache.insert((idx, version), &bytes);
                 }
 
                 return Ok(hash);
@@ -651,65 +2298,114 @@ where
             }
         }
 
-        match self.db.get(&Self::node_latest_version_key(idx)).await? {
+        // Leaves `start..end` sit under the node once its subtree is full. It
+        // exists at `version` once its left child's subtree is full and
+        // another leaf follows, and its hash only changes until it is full.
+        let level = idx.trailing_ones();
+        let start = (idx >> (level + 1)) << level;
+        let end = start + (1 << level);
+        let exists_at_version = version > start + (1 << level >> 1);
+
+        let latest_version = match self.db.get(&Self::node_latest_version_key(idx)).await? {
             Some(latest_version_bytes) => {
                 let latest_version_ref: &[u8] = latest_version_bytes.as_ref();
                 let latest_version_array: [u8; 8] =
                     latest_version_ref.try_into().map_err(|_| {
                         SlateDbTreeError::EncodingError("Invalid version format".into())
                     })?;
-                let latest_version = u64::from_be_bytes(latest_version_array);
-
-                if latest_version > version {
-                    // Node was created after the requested version
-                    let default_hash = digest::Output::<H>::default();
-
-                    if let Some(ref cache) = self.node_cache {
-                        cache.insert((idx, version), default_hash.to_vec());
-                    }
+                Some(u64::from_be_bytes(latest_version_array))
+            }
+            None => None,
+        };
 
-                    Ok(default_hash)
-                } else {
-                    // Node exists at this version, read from its latest version
-                    let versioned_key = Self::versioned_node_key(idx, latest_version);
-                    match self.db.get(&versioned_key).await? {
-                        Some(bytes) => {
-                            let mut hash = digest::Output::<H>::default();
-                            if bytes.len() == hash.len() {
-                                hash.copy_from_slice(&bytes);
-
-                                // Cache the result with the requested version (not latest_version)
-                                if let Some(ref cache) = self.node_cache {
-                                    cache.insert((idx, version), bytes.to_vec());
-                                }
-
-                                Ok(hash)
-                            } else {
-                                Err(SlateDbTreeError::EncodingError("Invalid hash size".into()))
+        match latest_version {
+            // The latest hash is the one at `version` if the node was last
+            // written by then, or its subtree was already full
+            Some(latest_version) if latest_version <= version || end <= version => {
+                // Node exists at this version, read from its latest version
+                let versioned_key = Self::versioned_node_key(idx, latest_version);
+                match self.db.get(&versioned_key).await? {
+                    Some(bytes) => {
+                        let mut hash = digest::Output::<H>::default();
+                        if bytes.len() == hash.len() {
+                            hash.copy_from_slice(&bytes);
+
+                            // Cache the result with the requested version (not latest_version)
+                            if let Some(ref cache) = self.node_cache {
+                                cache.insert((idx, version), &bytes);
                             }
+
+                            Ok(hash)
+                        } else {
+                            Err(SlateDbTreeError::EncodingError("Invalid hash size".into()))
                         }
-                        None => {
-                            // This shouldn't happen if latest_version pointer is correct
-                            Err(SlateDbTreeError::InconsistentState(format!(
-                                "Node {} has latest version {} but no data",
-                                idx, latest_version
-                            )))
-                        }
+                    }
+                    None => {
+                        // This shouldn't happen if latest_version pointer is correct
+                        Err(SlateDbTreeError::InconsistentState(format!(
+                            "Node {} has latest version {} but no data",
+                            idx, latest_version
+                        )))
                     }
                 }
             }
-            None => {
-                // No latest version pointer means node doesn't exist at all
+            _ if !exists_at_version => {
+                // The node only appears after the requested version
                 let default_hash = digest::Output::<H>::default();
 
                 // Cache the default result
                 if let Some(ref cache) = self.node_cache {
-                    cache.insert((idx, version), default_hash.to_vec());
+                    cache.insert((idx, version), &default_hash);
                 }
 
                 Ok(default_hash)
             }
+            // The node existed at `version` and has changed since, or was
+            // never recorded, yet no record of its hash then survives
+            _ => {
+                let hash = self
+                    .recompute_node(idx, start, end.min(version), version)
+                    .await?;
+                if let Some(ref cache) = self.node_cache {
+                    cache.insert((idx, version), &hash);
+                }
+                Ok(hash)
+            }
+        }
+    }
+
+    /// Hash of node `idx` at `version`, rebuilt from leaves `start..end`
+    async fn recompute_node(
+        &self,
+        idx: u64,
+        start: u64,
+        end: u64,
+        version: u64,
+    ) -> Result<digest::Output<H>, SlateDbTreeError> {
+        if end - start > self.max_recompute_leaves {
+            return Err(SlateDbTreeError::InconsistentState(format!(
+                "Node {} has no record at version {}, and recomputing it would read {} leaves, over the limit of {}",
+                idx,
+                version,
+                end - start,
+                self.max_recompute_leaves
+            )));
+        }
+
+        tracing::debug!(
+            "Recomputing node {} at version {} from leaves {}..{}",
+            idx,
+            version,
+            start,
+            end
+        );
+        if let Some(counter) = &self.node_recomputations {
+            counter.inc();
         }
+
+        let leaves = self.get_range(start, end - 1).await?;
+        let hashes: Vec<_> = leaves.iter().map(leaf_hash::<H, _>).collect();
+        Ok(subtree_hash::<H>(&hashes))
     }
 
     /// Returns the root hash at a specific tree size (for committed STH)
@@ -754,6 +2450,12 @@ where
         idx: u64,
         tree_size: u64,
     ) -> Result<InclusionProof<H>, SlateDbTreeError> {
+        if tree_size == 0 {
+            return Err(SlateDbTreeError::InconsistentState(
+                "The empty tree has no inclusion proofs".into(),
+            ));
+        }
+
         let current_leaves = self.get_committed_size().await?;
 
         if tree_size > current_leaves {
@@ -780,12 +2482,7 @@ where
                 // We have versioned nodes for this tree size (published STH)
                 let idxs = indices_for_inclusion_proof(tree_size, idx);
 
-                let hash_futures: Vec<_> = idxs
-                    .iter()
-                    .map(|&node_idx| self.get_node_hash_at_version(node_idx, tree_size))
-                    .collect();
-
-                let sibling_hashes = futures::future::try_join_all(hash_futures).await?;
+                let sibling_hashes = self.get_proof_node_hashes(&idxs, tree_size).await?;
 
                 Ok(InclusionProof::from_digests(sibling_hashes.iter()))
             }
@@ -799,6 +2496,65 @@ where
             }
         }
     }
+
+    /// Everything a monitor that has verified the tree at `from_size` needs
+    /// to move to `to_size`: the leaves in between, both roots and the
+    /// consistency proof linking them. Both sizes must be published STH
+    /// boundaries, and `from_size` may be 0.
+    pub async fn diff(
+        &self,
+        from_size: u64,
+        to_size: u64,
+    ) -> Result<TreeDiff<H, T>, SlateDbTreeError> {
+        if from_size > to_size {
+            return Err(SlateDbTreeError::InconsistentState(format!(
+                "Old size {} must be less than or equal to new size {}",
+                from_size, to_size
+            )));
+        }
+
+        // Reading the roots checks both sizes are committed boundaries
+        let (old_root, new_root) =
+            tokio::try_join!(self.root_at_size(from_size), self.root_at_size(to_size))?;
+
+        let consistency_proof = async {
+            if from_size == 0 || from_size == to_size {
+                return Ok(ConsistencyProof::from_digests(std::iter::empty()));
+            }
+            let idxs = crate::merkle_tree::consistency::indices_for_consistency_proof(
+                from_size,
+                to_size - from_size,
+            );
+            let proof_hashes = self.get_proof_node_hashes(&idxs, to_size).await?;
+            Ok(ConsistencyProof::from_digests(proof_hashes.iter()))
+        };
+        let new_leaves = async {
+            if from_size == to_size {
+                return Ok(Vec::new());
+            }
+            self.get_range(from_size, to_size - 1).await
+        };
+        let (consistency_proof, new_leaves) = tokio::try_join!(consistency_proof, new_leaves)?;
+
+        Ok(TreeDiff {
+            new_leaves: (from_size..to_size).zip(new_leaves).collect(),
+            consistency_proof,
+            old_root,
+            new_root,
+        })
+    }
+}
+
+/// RFC 6962 hash of the subtree over `leaf_hashes`, which is not empty
+fn subtree_hash<H: Digest>(leaf_hashes: &[digest::Output<H>]) -> digest::Output<H> {
+    match leaf_hashes.len() {
+        1 => leaf_hashes[0].clone(),
+        n => {
+            let k = crate::merkle_tree::consistency::largest_power_of_two_less_than(n as u64);
+            let (left, right) = leaf_hashes.split_at(k as usize);
+            parent_hash::<H>(&subtree_hash::<H>(left), &subtree_hash::<H>(right))
+        }
+    }
 }
 
 // Test-only methods
@@ -808,24 +2564,69 @@ where
     H: Digest,
     T: HashableLeaf + serde::Serialize + serde::de::DeserializeOwned,
 {
-    pub async fn is_empty(&self) -> Result<bool, SlateDbTreeError> {
-        Ok(self.len().await? == 0)
+    /// Replace the stored value of leaf `idx` with `leaf`, leaving its node
+    /// hash alone
+    pub async fn overwrite_leaf(&self, idx: u64, leaf: &T) -> Result<(), SlateDbTreeError> {
+        let value = self.encode_leaf(leaf)?;
+        self.db.put(&Self::leaf_key(idx), &value).await?;
+        Ok(())
     }
 
-    pub async fn push(&self, new_val: T) -> Result<(), SlateDbTreeError> {
-        // Acquire write lock to ensure serialization of write operations
-        let _write_guard = self.write_lock.lock().await;
+    /// Overwrite the latest stored version of node `idx` with garbage, the way
+    /// a tampered or bit-rotted store would look, and drop cached node hashes
+    /// so the next reads go to storage.
+    pub async fn corrupt_node(&self, idx: u64) -> Result<(), SlateDbTreeError> {
+        let version_bytes = self
+            .db
+            .get(&Self::node_latest_version_key(idx))
+            .await?
+            .ok_or_else(|| {
+                SlateDbTreeError::InconsistentState(format!("Node {} does not exist", idx))
+            })?;
+        let version = u64::from_be_bytes(
+            version_bytes
+                .as_ref()
+                .try_into()
+                .map_err(|_| SlateDbTreeError::EncodingError("Invalid version format".into()))?,
+        );
 
-        let num_leaves = self.len().await?;
+        let mut hash = self.get_node_hash_at_version(idx, version).await?;
+        for byte in hash.iter_mut() {
+            *byte ^= 0xff;
+        }
+        self.db
+            .put(&Self::versioned_node_key(idx, version), hash.as_ref())
+            .await?;
 
-        if num_leaves >= u64::MAX / 2 {
-            return Err(SlateDbTreeError::InconsistentState("Tree is full".into()));
+        if let Some(ref cache) = self.node_cache {
+            cache.clear();
         }
+        *self.write_lock.lock().await = None;
+        Ok(())
+    }
+
+    /// Append one leaf and return the index it was assigned. Serialized with
+    /// `batch_push_with_data` through `write_lock`.
+    pub async fn push(&self, new_val: T) -> Result<u64, SlateDbTreeError> {
+        // Acquire write lock to ensure serialization of write operations
+        let mut write_guard = self.write_lock.lock().await;
+
+        let num_leaves = self.len().await?;
+        check_capacity(num_leaves, 1)?;
+
+        // Siblings are read through the node cache here, so the frontier is
+        // only carried forward when it is already known
+        let frontier = match write_guard.take() {
+            Some(frontier) if frontier.size == num_leaves => Some(frontier),
+            _ => {
+                self.clear_interrupted_append().await?;
+                None
+            }
+        };
 
-        let mut batch = WriteBatch::new();
+        let mut batch = self.db.batch();
 
-        let leaf_bytes = postcard::to_stdvec(&new_val)
-            .map_err(|e| SlateDbTreeError::EncodingError(e.to_string()))?;
+        let leaf_bytes = self.encode_leaf(&new_val)?;
         batch.put(Self::leaf_key(num_leaves), &leaf_bytes);
 
         let new_leaf_idx = LeafIdx::new(num_leaves);
@@ -841,20 +2642,31 @@ where
             .await?;
 
         // Single atomic write for both tree updates and tiles
-        self.db.write_batch(batch).await?;
+        self.db
+            .write_batch(batch)
+            .await
+            .context("writing appended leaves")?;
 
-        Ok(())
+        *write_guard =
+            frontier.and_then(|f| Self::advance_frontier(&f, num_leaves + 1, &computed_hashes));
+
+        Ok(num_leaves)
     }
 
     async fn recalculate_path_batch(
         &self,
-        batch: &mut WriteBatch,
+        batch: &mut DbBatch,
         leaf_idx: LeafIdx,
         leaf_val: &T,
         num_leaves: u64,
     ) -> Result<std::collections::BTreeMap<u64, digest::Output<H>>, SlateDbTreeError> {
         let mut cur_idx: InternalIdx = leaf_idx.into();
         let leaf_hash = leaf_hash::<H, _>(leaf_val);
+        // The new leaf is the last one
+        batch.put(
+            Self::leaf_hash_index_key(&leaf_hash),
+            (num_leaves - 1).to_be_bytes(),
+        );
 
         let root_idx = root_idx(num_leaves);
 
@@ -925,11 +2737,12 @@ where
 
     pub async fn get(&self, idx: u64) -> Result<Option<T>, SlateDbTreeError> {
         match self.db.get(&Self::leaf_key(idx)).await? {
-            Some(bytes) => {
-                let leaf = postcard::from_bytes(&bytes)
-                    .map_err(|e| SlateDbTreeError::EncodingError(e.to_string()))?;
-                Ok(Some(leaf))
-            }
+            Some(bytes) => Ok(Some(Self::decode_leaf(
+                self.untagged_leaves,
+                self.unframed_leaves,
+                idx,
+                &bytes,
+            )?)),
             None => Ok(None),
         }
     }
@@ -972,7 +2785,7 @@ where
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use crate::storage::RateLimitedDb;
     use sha2::Sha256;
@@ -987,95 +2800,624 @@ mod tests {
         fn hash<H: digest::Update>(&self, hasher: &mut H) {
             hasher.update(&self.data);
         }
-    }
+    }
+
+    /// A database on an in-memory object store, without a read rate limit
+    pub(crate) async fn create_test_db() -> RateLimitedDb {
+        // Use in-memory mode for testing
+        let object_store = Arc::new(object_store::memory::InMemory::new());
+        let db = Db::builder(object_store::path::Path::from("/test"), object_store)
+            .build()
+            .await
+            .unwrap();
+        // Create RateLimitedDb without rate limiting for tests
+        RateLimitedDb::new(Arc::new(db), None)
+    }
+
+    #[tokio::test]
+    async fn test_new_tree() {
+        let db = create_test_db().await;
+        let tree: SlateDbBackedTree<Sha256, TestLeaf> = SlateDbBackedTree::new(db).await.unwrap();
+
+        assert!(tree.is_empty().await.unwrap());
+        assert_eq!(tree.len().await.unwrap(), 0);
+        assert_eq!(tree.get_committed_size().await.unwrap(), 0);
+    }
+
+    #[test]
+    fn test_error_context_chain() {
+        let encoding: Result<(), _> = Err(SlateDbTreeError::EncodingError(
+            u64::from_str_radix("zz", 16).unwrap_err().into(),
+        ));
+        let err = encoding
+            .context("reading leaf 7")
+            .context("verifying integrity")
+            .unwrap_err();
+
+        let short = err.to_string();
+        assert!(short.starts_with("verifying integrity (at src/merkle_tree/"));
+        assert!(!short.contains("reading leaf 7"));
+
+        let full = format!("{:#}", err);
+        assert!(full.contains(": reading leaf 7 (at "));
+        assert!(full.ends_with(": Encoding error: invalid digit found in string"));
+        assert_eq!(full.matches("invalid digit").count(), 1);
+
+        let inner = std::error::Error::source(&err)
+            .and_then(|e| e.source())
+            .unwrap();
+        assert_eq!(
+            inner.to_string(),
+            "Encoding error: invalid digit found in string"
+        );
+
+        let mut depth = 0;
+        let mut source = std::error::Error::source(&err);
+        while let Some(e) = source {
+            depth += 1;
+            source = e.source();
+        }
+        assert_eq!(depth, 3);
+    }
+
+    #[tokio::test]
+    async fn test_single_push() {
+        let db = create_test_db().await;
+        let tree: SlateDbBackedTree<Sha256, TestLeaf> = SlateDbBackedTree::new(db).await.unwrap();
+
+        let leaf = TestLeaf {
+            data: vec![1, 2, 3],
+        };
+        tree.push(leaf.clone()).await.unwrap();
+
+        assert!(!tree.is_empty().await.unwrap());
+        assert_eq!(tree.len().await.unwrap(), 1);
+        assert_eq!(tree.get_committed_size().await.unwrap(), 1);
+
+        let retrieved = tree.get(0).await.unwrap();
+        assert_eq!(retrieved, Some(leaf));
+    }
+
+    #[tokio::test]
+    async fn test_batch_push() {
+        let db = create_test_db().await;
+        let tree: SlateDbBackedTree<Sha256, TestLeaf> = SlateDbBackedTree::new(db).await.unwrap();
+
+        let leaves = vec![
+            TestLeaf { data: vec![1] },
+            TestLeaf { data: vec![2] },
+            TestLeaf { data: vec![3] },
+            TestLeaf { data: vec![4] },
+        ];
+
+        let start_idx = tree
+            .batch_push_with_data(leaves.clone(), vec![])
+            .await
+            .unwrap();
+        assert_eq!(start_idx, 0);
+        assert_eq!(tree.len().await.unwrap(), 4);
+        assert_eq!(tree.get_committed_size().await.unwrap(), 4);
+
+        // Verify all leaves
+        for (i, leaf) in leaves.iter().enumerate() {
+            let retrieved = tree.get(i as u64).await.unwrap();
+            assert_eq!(retrieved.as_ref(), Some(leaf));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_push_with_data() {
+        let db = create_test_db().await;
+        let tree: SlateDbBackedTree<Sha256, TestLeaf> =
+            SlateDbBackedTree::new(db.clone()).await.unwrap();
+
+        let leaves = vec![TestLeaf {
+            data: vec![1, 2, 3],
+        }];
+        let additional_data = vec![
+            (b"key1".to_vec(), b"value1".to_vec()),
+            (b"key2".to_vec(), b"value2".to_vec()),
+        ];
+
+        tree.batch_push_with_data(leaves, additional_data)
+            .await
+            .unwrap();
+
+        // Verify the additional data was written
+        assert!(db.get(b"key1").await.unwrap().is_some());
+        assert!(db.get(b"key2").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_appends_reuse_frontier() {
+        let leaves: Vec<TestLeaf> = (0u32..200)
+            .map(|i| TestLeaf {
+                data: i.to_be_bytes().to_vec(),
+            })
+            .collect();
+        let node_lookups = |metrics: &Metrics| {
+            metrics.cache_hits.with_label_values(&["node"]).get()
+                + metrics.cache_misses.with_label_values(&["node"]).get()
+        };
+
+        let db = create_test_db().await;
+        let tree: SlateDbBackedTree<Sha256, TestLeaf> =
+            SlateDbBackedTree::new(db.clone()).await.unwrap();
+        tree.batch_push_with_data(leaves[..37].to_vec(), vec![])
+            .await
+            .unwrap();
+
+        // A reopened tree reads its frontier once, then appends of every
+        // batch size look up no existing nodes. Staying under 256 leaves
+        // keeps tile precomputation out of the count.
+        let mut tree: SlateDbBackedTree<Sha256, TestLeaf> =
+            SlateDbBackedTree::new(db).await.unwrap();
+        let metrics = Metrics::new();
+        tree.report_node_metrics(&metrics);
+        let mut size = 37;
+        for batch_size in (1..=9).cycle() {
+            if size + batch_size > 190 {
+                break;
+            }
+            tree.batch_push_with_data(leaves[size..size + batch_size].to_vec(), vec![])
+                .await
+                .unwrap();
+            size += batch_size;
+        }
+        assert_eq!(node_lookups(&metrics), 0);
+
+        // A single push carries the frontier forward for the next batch
+        tree.push(leaves[size].clone()).await.unwrap();
+        let after_push = node_lookups(&metrics);
+        tree.batch_push_with_data(leaves[size + 1..].to_vec(), vec![])
+            .await
+            .unwrap();
+        assert_eq!(node_lookups(&metrics), after_push);
+
+        // A dropped frontier is read back from storage
+        *tree.write_lock.lock().await = None;
+        let extra = TestLeaf { data: vec![0xff] };
+        tree.batch_push_with_data(vec![extra.clone()], vec![])
+            .await
+            .unwrap();
+        assert!(node_lookups(&metrics) > after_push);
+
+        let reference: SlateDbBackedTree<Sha256, TestLeaf> =
+            SlateDbBackedTree::new(create_test_db().await)
+                .await
+                .unwrap();
+        let mut all = leaves.clone();
+        all.push(extra);
+        reference.batch_push_with_data(all, vec![]).await.unwrap();
+        assert_eq!(
+            tree.root().await.unwrap().as_bytes(),
+            reference.root().await.unwrap().as_bytes()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lookup_by_leaf_hash() {
+        let db = create_test_db().await;
+        let tree: SlateDbBackedTree<Sha256, TestLeaf> = SlateDbBackedTree::new(db).await.unwrap();
+
+        let leaves: Vec<TestLeaf> = (0u32..100)
+            .map(|i| TestLeaf {
+                data: i.to_be_bytes().to_vec(),
+            })
+            .collect();
+        tree.batch_push_with_data(leaves[..60].to_vec(), vec![])
+            .await
+            .unwrap();
+        tree.batch_push_with_data(leaves[60..99].to_vec(), vec![])
+            .await
+            .unwrap();
+        tree.push(leaves[99].clone()).await.unwrap();
+
+        for (i, leaf) in leaves.iter().enumerate() {
+            let hash = leaf_hash::<Sha256, _>(leaf);
+            assert_eq!(
+                tree.lookup_by_leaf_hash(&hash).await.unwrap(),
+                Some(i as u64),
+                "leaf {}",
+                i
+            );
+        }
+        assert_eq!(tree.lookup_by_leaf_hash(&[0xff; 32]).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_append_if_absent_is_idempotent() {
+        let db = create_test_db().await;
+        let tree: SlateDbBackedTree<Sha256, TestLeaf> = SlateDbBackedTree::new(db).await.unwrap();
+        let leaf = |i: u32| TestLeaf {
+            data: i.to_be_bytes().to_vec(),
+        };
+
+        // The import crashed after its first 30 leaves
+        tree.batch_push_with_data((0..30).map(leaf).collect(), vec![])
+            .await
+            .unwrap();
+
+        // Restarted from an earlier checkpoint, with one leaf submitted twice
+        let mut import: Vec<_> = (20..50).map(leaf).collect();
+        import.push(leaf(45));
+        let outcomes = tree.append_if_absent(import.clone()).await.unwrap();
+        let mut expected: Vec<_> = (20..30).map(LeafAppend::Skipped).collect();
+        expected.extend((30..50).map(LeafAppend::Appended));
+        expected.push(LeafAppend::Skipped(45));
+        assert_eq!(outcomes, expected);
+        assert_eq!(tree.len().await.unwrap(), 50);
+        let root = tree.root().await.unwrap();
+
+        let outcomes = tree.append_if_absent(import).await.unwrap();
+        assert!(outcomes
+            .iter()
+            .all(|outcome| matches!(outcome, LeafAppend::Skipped(_))));
+        assert_eq!(tree.len().await.unwrap(), 50);
+        assert_eq!(tree.root().await.unwrap().as_bytes(), root.as_bytes());
+
+        // The frontier is kept for ordinary appends
+        tree.push(leaf(50)).await.unwrap();
+        let proof = tree.prove_inclusion(50).await.unwrap();
+        proof
+            .verify(&leaf(50), 50, &tree.root().await.unwrap())
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_append_if_absent_reads_the_legacy_hash_index() {
+        let db = create_test_db().await;
+        let mut tree: SlateDbBackedTree<Sha256, TestLeaf> =
+            SlateDbBackedTree::new(db).await.unwrap();
+        tree.set_legacy_hash_index(b"hash:");
+        let leaf = |i: u32| TestLeaf {
+            data: i.to_be_bytes().to_vec(),
+        };
+        tree.batch_push_with_data((0..10).map(leaf).collect(), vec![])
+            .await
+            .unwrap();
+
+        // Leaves 0..5 were indexed only by an older version, and leaf 5's
+        // legacy entry points at the wrong leaf
+        let mut batch = tree.db.batch();
+        for i in 0..6u32 {
+            let hash = leaf_hash::<Sha256, _>(&leaf(i));
+            batch.delete(SlateDbBackedTree::<Sha256, TestLeaf>::leaf_hash_index_key(
+                &hash,
+            ));
+            let index = if i == 5 { 0u64 } else { i as u64 };
+            batch.put(
+                [b"hash:".as_slice(), hash.as_slice()].concat(),
+                index.to_be_bytes(),
+            );
+        }
+        tree.db.write_batch(batch).await.unwrap();
 
-    async fn create_test_db() -> RateLimitedDb {
-        // Use in-memory mode for testing
-        let object_store = Arc::new(object_store::memory::InMemory::new());
-        let db = Db::builder(object_store::path::Path::from("/test"), object_store)
-            .build()
+        let outcomes = tree
+            .append_if_absent((0..12).map(leaf).collect())
             .await
             .unwrap();
-        // Create RateLimitedDb without rate limiting for tests
-        RateLimitedDb::new(Arc::new(db), None)
+        let mut expected: Vec<_> = (0..5).map(LeafAppend::Skipped).collect();
+        expected.push(LeafAppend::Appended(10));
+        expected.extend((6..10).map(LeafAppend::Skipped));
+        expected.extend([LeafAppend::Appended(11), LeafAppend::Appended(12)]);
+        assert_eq!(outcomes, expected);
+        assert_eq!(
+            tree.lookup_by_leaf_hash(&leaf_hash::<Sha256, _>(&leaf(3)))
+                .await
+                .unwrap(),
+            Some(3)
+        );
     }
 
     #[tokio::test]
-    async fn test_new_tree() {
+    async fn test_diff() {
         let db = create_test_db().await;
         let tree: SlateDbBackedTree<Sha256, TestLeaf> = SlateDbBackedTree::new(db).await.unwrap();
+        let leaf = |i: u32| TestLeaf {
+            data: i.to_be_bytes().to_vec(),
+        };
 
-        assert!(tree.is_empty().await.unwrap());
-        assert_eq!(tree.len().await.unwrap(), 0);
-        assert_eq!(tree.get_committed_size().await.unwrap(), 0);
+        for batch in [0..6, 6..7, 7..19] {
+            tree.batch_push_with_data(batch.map(leaf).collect(), vec![])
+                .await
+                .unwrap();
+        }
+
+        for (from_size, to_size) in [(0, 6), (6, 7), (6, 19), (7, 19), (19, 19)] {
+            let diff = tree.diff(from_size, to_size).await.unwrap();
+            let expected: Vec<_> = (from_size..to_size).map(|i| (i, leaf(i as u32))).collect();
+            assert_eq!(diff.new_leaves, expected);
+            assert_eq!(
+                diff.new_root.as_bytes(),
+                tree.root_at_size(to_size).await.unwrap().as_bytes()
+            );
+            if from_size > 0 {
+                diff.consistency_proof
+                    .verify(&diff.old_root, &diff.new_root)
+                    .unwrap();
+            }
+        }
+
+        // Only published sizes can be diffed
+        assert!(tree.diff(6, 10).await.is_err());
+        assert!(tree.diff(3, 19).await.is_err());
+        assert!(tree.diff(7, 6).await.is_err());
+        assert!(tree.diff(19, 20).await.is_err());
     }
 
     #[tokio::test]
-    async fn test_single_push() {
+    async fn test_missing_versioned_nodes_are_recomputed() {
+        type Tree = SlateDbBackedTree<Sha256, TestLeaf>;
         let db = create_test_db().await;
-        let tree: SlateDbBackedTree<Sha256, TestLeaf> = SlateDbBackedTree::new(db).await.unwrap();
+        let mut tree: Tree = SlateDbBackedTree::new(db).await.unwrap();
+        let metrics = Metrics::new();
+        tree.report_node_metrics(&metrics);
 
-        let leaf = TestLeaf {
-            data: vec![1, 2, 3],
-        };
-        tree.push(leaf.clone()).await.unwrap();
+        let leaves: Vec<_> = (0..20u32)
+            .map(|i| TestLeaf {
+                data: i.to_be_bytes().to_vec(),
+            })
+            .collect();
+        for batch in [0..7, 7..13, 13..20] {
+            tree.batch_push_with_data(leaves[batch].to_vec(), vec![])
+                .await
+                .unwrap();
+        }
 
-        assert!(!tree.is_empty().await.unwrap());
-        assert_eq!(tree.len().await.unwrap(), 1);
-        assert_eq!(tree.get_committed_size().await.unwrap(), 1);
+        let sizes = [7u64, 13];
+        let mut proofs = Vec::new();
+        for size in sizes {
+            for idx in 0..size {
+                let proof = tree.prove_inclusion_at_size(idx, size).await.unwrap();
+                proofs.push((idx, size, proof.as_bytes().to_vec()));
+            }
+        }
+        let consistency = tree.prove_consistency_between(7, 13).await.unwrap();
+
+        // Drop the records of the nodes each size left partly filled, as in
+        // a tree pruned of them, and one node's latest version pointer, as
+        // in a tree written before versions were kept
+        let mut batch = tree.db.batch();
+        let mut dropped = 0;
+        for size in sizes {
+            for idx in 0..64u64 {
+                let level = idx.trailing_ones();
+                let start = (idx >> (level + 1)) << level;
+                let partial = start + (1 << level >> 1) < size && size < start + (1 << level);
+                if partial && idx != root_idx(size).as_u64() {
+                    batch.delete(Tree::versioned_node_key(idx, size));
+                    dropped += 1;
+                }
+            }
+        }
+        assert_eq!(dropped, 2);
+        batch.delete(Tree::node_latest_version_key(23));
+        tree.db.write_batch(batch).await.unwrap();
+        if let Some(ref cache) = tree.node_cache {
+            cache.clear();
+        }
 
-        let retrieved = tree.get(0).await.unwrap();
-        assert_eq!(retrieved, Some(leaf));
+        for (idx, size, expected) in &proofs {
+            let proof = tree.prove_inclusion_at_size(*idx, *size).await.unwrap();
+            assert_eq!(
+                &proof.as_bytes().to_vec(),
+                expected,
+                "leaf {} at {}",
+                idx,
+                size
+            );
+            let root = tree.root_at_size(*size).await.unwrap();
+            proof.verify(&leaves[*idx as usize], *idx, &root).unwrap();
+        }
+        let recomputed = tree.prove_consistency_between(7, 13).await.unwrap();
+        assert_eq!(recomputed.as_bytes(), consistency.as_bytes());
+        recomputed
+            .verify(
+                &tree.root_at_size(7).await.unwrap(),
+                &tree.root_at_size(13).await.unwrap(),
+            )
+            .unwrap();
+        assert!(metrics.node_recomputations_total.get() > 0);
+
+        // Past the bound, a missing node is an error rather than a wrong hash
+        tree.set_max_recompute_leaves(2);
+        if let Some(ref cache) = tree.node_cache {
+            cache.clear();
+        }
+        assert!(tree.prove_inclusion_at_size(0, 7).await.is_err());
     }
 
     #[tokio::test]
-    async fn test_batch_push() {
+    async fn test_meta_value_lengths() {
+        type Tree = SlateDbBackedTree<Sha256, TestLeaf>;
+
+        let count = 0x0102_0304_0506_0708u64.to_be_bytes();
+        assert_eq!(
+            Tree::parse_num_leaves(&count).unwrap(),
+            0x0102_0304_0506_0708
+        );
+
+        let mut longer = count.to_vec();
+        longer.extend_from_slice(&[0xff; 24]);
+        assert_eq!(
+            Tree::parse_num_leaves(&longer).unwrap(),
+            0x0102_0304_0506_0708
+        );
+
+        for len in 0..8 {
+            assert!(Tree::parse_num_leaves(&count[..len]).is_err());
+        }
+
+        // A tree opened over a meta value with trailing fields reads its size
         let db = create_test_db().await;
-        let tree: SlateDbBackedTree<Sha256, TestLeaf> = SlateDbBackedTree::new(db).await.unwrap();
+        let tree: Tree = SlateDbBackedTree::new(db.clone()).await.unwrap();
+        tree.push(TestLeaf { data: vec![1] }).await.unwrap();
+        let mut meta = 1u64.to_be_bytes().to_vec();
+        meta.extend_from_slice(b"future fields");
+        db.put(META_KEY, &meta).await.unwrap();
+        let tree: Tree = SlateDbBackedTree::new(db.clone()).await.unwrap();
+        assert_eq!(tree.len().await.unwrap(), 1);
 
-        let leaves = vec![
-            TestLeaf { data: vec![1] },
-            TestLeaf { data: vec![2] },
-            TestLeaf { data: vec![3] },
-            TestLeaf { data: vec![4] },
-        ];
+        db.put(META_KEY, &[0, 1]).await.unwrap();
+        assert!(tree.len().await.is_err());
+    }
 
-        let start_idx = tree
-            .batch_push_with_data(leaves.clone(), vec![])
+    #[tokio::test]
+    async fn test_append_past_max_leaves_is_rejected() {
+        type Tree = SlateDbBackedTree<Sha256, TestLeaf>;
+
+        let db = create_test_db().await;
+        let tree: Tree = SlateDbBackedTree::new(db.clone()).await.unwrap();
+        let near_limit = MAX_LEAVES - 1;
+        db.put(META_KEY, &near_limit.to_be_bytes()).await.unwrap();
+
+        let leaves = |count: u8| (0..count).map(|i| TestLeaf { data: vec![i] }).collect();
+        let full = |result: Result<_, SlateDbTreeError>| matches!(result, Err(SlateDbTreeError::InconsistentState(message)) if message.starts_with("Tree is full"));
+
+        assert!(full(tree.batch_push_with_data(leaves(2), vec![]).await));
+        assert!(full(tree.append_if_absent(leaves(2)).await));
+        assert!(full(
+            Tree::batch_push_across_trees(
+                &tree,
+                leaves(2),
+                &SlateDbBackedTree::<Sha256, TestLeaf>::new(db.with_key_prefix(b"other/"))
+                    .await
+                    .unwrap(),
+                leaves(1),
+            )
             .await
-            .unwrap();
-        assert_eq!(start_idx, 0);
-        assert_eq!(tree.len().await.unwrap(), 4);
-        assert_eq!(tree.get_committed_size().await.unwrap(), 4);
+        ));
 
-        // Verify all leaves
-        for (i, leaf) in leaves.iter().enumerate() {
-            let retrieved = tree.get(i as u64).await.unwrap();
-            assert_eq!(retrieved.as_ref(), Some(leaf));
+        // Nothing of a rejected batch is written
+        assert_eq!(tree.len().await.unwrap(), near_limit);
+        assert!(db.get(&Tree::leaf_key(near_limit)).await.unwrap().is_none());
+
+        db.put(META_KEY, &MAX_LEAVES.to_be_bytes()).await.unwrap();
+        assert!(full(tree.push(TestLeaf { data: vec![0] }).await));
+        assert!(full(tree.batch_push_with_data(leaves(1), vec![]).await));
+    }
+
+    #[tokio::test]
+    async fn test_audit_random() {
+        let db = create_test_db().await;
+        let tree: Arc<SlateDbBackedTree<Sha256, TestLeaf>> =
+            Arc::new(SlateDbBackedTree::new(db).await.unwrap());
+        assert_eq!(tree.audit_random(1, 10).await.unwrap().verified, 0);
+
+        let mut next = 0u8;
+        for batch in [3, 1, 3, 3, 6, 1, 13] {
+            let leaves = (0..batch)
+                .map(|_| {
+                    next += 1;
+                    TestLeaf { data: vec![next] }
+                })
+                .collect();
+            tree.batch_push_with_data(leaves, vec![]).await.unwrap();
         }
+        assert_eq!(
+            tree.published_sizes().await.unwrap(),
+            vec![3, 4, 7, 10, 16, 17, 30]
+        );
+
+        let report = tree.audit_random(7, 50).await.unwrap();
+        assert_eq!(
+            report,
+            AuditReport {
+                verified: 100,
+                failure: None
+            }
+        );
+        assert_eq!(tree.audit_random(7, 50).await.unwrap(), report);
+
+        // The subtree over leaves 0..2 is in most proofs
+        tree.corrupt_node(1).await.unwrap();
+        let report = tree.audit_random(7, 50).await.unwrap();
+        assert!(report.verified < 100);
+        assert!(report.failure.is_some());
     }
 
     #[tokio::test]
-    async fn test_batch_push_with_data() {
+    async fn test_warm_cache() {
         let db = create_test_db().await;
         let tree: SlateDbBackedTree<Sha256, TestLeaf> =
             SlateDbBackedTree::new(db.clone()).await.unwrap();
+        assert_eq!(tree.warm_cache(10).await.unwrap(), 0);
 
-        let leaves = vec![TestLeaf {
-            data: vec![1, 2, 3],
-        }];
-        let additional_data = vec![
-            (b"key1".to_vec(), b"value1".to_vec()),
-            (b"key2".to_vec(), b"value2".to_vec()),
-        ];
+        let leaves: Vec<TestLeaf> = (0u32..100)
+            .map(|i| TestLeaf {
+                data: i.to_be_bytes().to_vec(),
+            })
+            .collect();
+        tree.batch_push_with_data(leaves, vec![]).await.unwrap();
+        let root = tree.root().await.unwrap();
 
-        tree.batch_push_with_data(leaves, additional_data)
-            .await
-            .unwrap();
+        // A restarted tree starts with a cold cache
+        let tree: SlateDbBackedTree<Sha256, TestLeaf> = SlateDbBackedTree::new(db).await.unwrap();
+        assert_eq!(tree.warm_cache(0).await.unwrap(), 0);
+        // 100 leaves put the root at level 7: one node there, two at level 6
+        // and three at level 5
+        assert_eq!(tree.warm_cache(3).await.unwrap(), 6);
+        let cache = tree.node_cache.as_ref().unwrap();
+        assert!(cache.contains(&(127, 100)));
+
+        // Leaves are level 0, so asking for more levels than exist loads
+        // every node
+        assert_eq!(tree.warm_cache(64).await.unwrap(), 199);
+        assert_eq!(tree.root().await.unwrap().as_bytes(), root.as_bytes());
+    }
 
-        // Verify the additional data was written
-        assert!(db.get(b"key1").await.unwrap().is_some());
-        assert!(db.get(b"key2").await.unwrap().is_some());
+    #[tokio::test]
+    async fn test_offheap_cache_matches_default() {
+        let reference: SlateDbBackedTree<Sha256, TestLeaf> =
+            SlateDbBackedTree::new(create_test_db().await)
+                .await
+                .unwrap();
+        // Room for a few hashes and their bookkeeping, far fewer than the
+        // tree has nodes
+        let pooled: SlateDbBackedTree<Sha256, TestLeaf> =
+            SlateDbBackedTree::new_with_offheap_cache(create_test_db().await, 16 * 32)
+                .await
+                .unwrap();
+
+        let mut boundaries = Vec::new();
+        for batch in 0u8..6 {
+            let leaves: Vec<TestLeaf> = (0..batch * 3 + 1)
+                .map(|j| TestLeaf {
+                    data: vec![batch, j],
+                })
+                .collect();
+            reference
+                .batch_push_with_data(leaves.clone(), vec![])
+                .await
+                .unwrap();
+            pooled.batch_push_with_data(leaves, vec![]).await.unwrap();
+            boundaries.push(reference.len().await.unwrap());
+        }
+
+        // Twice, so the second pass reads whatever the pool kept
+        for _ in 0..2 {
+            for &size in &boundaries {
+                assert_eq!(
+                    pooled.root_at_size(size).await.unwrap().as_bytes(),
+                    reference.root_at_size(size).await.unwrap().as_bytes()
+                );
+                for idx in 0..size {
+                    assert_eq!(
+                        pooled
+                            .prove_inclusion_at_size(idx, size)
+                            .await
+                            .unwrap()
+                            .as_bytes(),
+                        reference
+                            .prove_inclusion_at_size(idx, size)
+                            .await
+                            .unwrap()
+                            .as_bytes()
+                    );
+                }
+            }
+        }
     }
 
     #[tokio::test]
@@ -1185,6 +3527,9 @@ mod tests {
         // Should fail for non-STH boundary
         let result = tree.prove_inclusion_at_size(0, 3).await;
         assert!(result.is_err());
+
+        let err = tree.prove_inclusion_at_size(0, 0).await.unwrap_err();
+        assert!(err.to_string().contains("empty tree"), "{}", err);
     }
 
     #[tokio::test]
@@ -1247,33 +3592,345 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_large_batch() {
-        let db = create_test_db().await;
-        let tree: SlateDbBackedTree<Sha256, TestLeaf> = SlateDbBackedTree::new(db).await.unwrap();
+    async fn test_large_batch() {
+        let db = create_test_db().await;
+        let tree: SlateDbBackedTree<Sha256, TestLeaf> = SlateDbBackedTree::new(db).await.unwrap();
+
+        // Create a large batch
+        let leaves: Vec<TestLeaf> = (0..100)
+            .map(|i| TestLeaf {
+                data: vec![i as u8],
+            })
+            .collect();
+
+        let start_idx = tree
+            .batch_push_with_data(leaves.clone(), vec![])
+            .await
+            .unwrap();
+        assert_eq!(start_idx, 0);
+        assert_eq!(tree.len().await.unwrap(), 100);
+
+        // Verify random samples
+        for i in [0, 25, 50, 75, 99] {
+            let retrieved = tree.get(i as u64).await.unwrap();
+            assert_eq!(retrieved, Some(leaves[i].clone()));
+        }
+
+        // Consistency proof requires STH boundaries, so this should fail for size 50
+        let result = tree.prove_consistency_between(50, 100).await;
+        assert!(result.is_err()); // Not a published STH boundary
+    }
+
+    #[tokio::test]
+    async fn test_chunked_batch_push() {
+        let leaves = |range: std::ops::Range<u32>, tag: u8| -> Vec<TestLeaf> {
+            range
+                .map(|i| TestLeaf {
+                    data: [&[tag][..], &i.to_be_bytes()].concat(),
+                })
+                .collect()
+        };
+        let base = leaves(0..100, 0);
+        let failed = leaves(100..2100, 1);
+        let retried = leaves(100..2100, 2);
+
+        let db = create_test_db().await;
+        let mut tree: SlateDbBackedTree<Sha256, TestLeaf> =
+            SlateDbBackedTree::new(db.clone()).await.unwrap();
+        tree.set_max_batch_puts(64);
+        tree.batch_push_with_data(base.clone(), vec![])
+            .await
+            .unwrap();
+        let base_root = tree.root().await.unwrap();
+
+        // An append failing after some chunks were written leaves the tree
+        // as it was
+        tree.fail_at_chunk = Some(3);
+        assert!(tree
+            .batch_push_with_data(failed.clone(), vec![])
+            .await
+            .is_err());
+        tree.fail_at_chunk = None;
+        assert!(db.get(PENDING_APPEND_KEY).await.unwrap().is_some());
+        assert_eq!(tree.len().await.unwrap(), 100);
+        assert_eq!(tree.root().await.unwrap().as_bytes(), base_root.as_bytes());
+        for leaf in &failed[..50] {
+            let hash = leaf_hash::<Sha256, _>(leaf);
+            assert_eq!(tree.lookup_by_leaf_hash(&hash).await.unwrap(), None);
+        }
+
+        // Reaching the size the failed append was heading for reads none of
+        // what it wrote
+        tree.batch_push_with_data(retried[..1000].to_vec(), vec![])
+            .await
+            .unwrap();
+        assert!(db.get(PENDING_APPEND_KEY).await.unwrap().is_none());
+        tree.batch_push_with_data(retried[1000..].to_vec(), vec![])
+            .await
+            .unwrap();
+
+        let reference: SlateDbBackedTree<Sha256, TestLeaf> =
+            SlateDbBackedTree::new(create_test_db().await)
+                .await
+                .unwrap();
+        let all = [base.clone(), retried].concat();
+        reference
+            .batch_push_with_data(all.clone(), vec![])
+            .await
+            .unwrap();
+        let root = tree.root().await.unwrap();
+        assert_eq!(root.as_bytes(), reference.root().await.unwrap().as_bytes());
+        for idx in [0, 99, 100, 1099, 1100, 2099] {
+            tree.prove_inclusion(idx)
+                .await
+                .unwrap()
+                .verify(&all[idx as usize], idx, &root)
+                .expect("Inclusion proof should verify");
+            let hash = leaf_hash::<Sha256, _>(&all[idx as usize]);
+            assert_eq!(tree.lookup_by_leaf_hash(&hash).await.unwrap(), Some(idx));
+        }
+
+        // Reopening clears an interrupted append too
+        let db = create_test_db().await;
+        let mut tree: SlateDbBackedTree<Sha256, TestLeaf> =
+            SlateDbBackedTree::new(db.clone()).await.unwrap();
+        tree.set_max_batch_puts(64);
+        tree.batch_push_with_data(base.clone(), vec![])
+            .await
+            .unwrap();
+        tree.fail_at_chunk = Some(1);
+        assert!(tree
+            .batch_push_with_data(failed.clone(), vec![])
+            .await
+            .is_err());
+        let tree: SlateDbBackedTree<Sha256, TestLeaf> =
+            SlateDbBackedTree::new(db.clone()).await.unwrap();
+        assert!(db.get(PENDING_APPEND_KEY).await.unwrap().is_none());
+        assert!(db
+            .get(&SlateDbBackedTree::<Sha256, TestLeaf>::leaf_key(100))
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(tree.root().await.unwrap().as_bytes(), base_root.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_cbor_leaf_encoding() {
+        type Tree = SlateDbBackedTree<Sha256, TestLeaf>;
+        let leaves: Vec<TestLeaf> = (0u32..100)
+            .map(|i| TestLeaf {
+                data: i.to_be_bytes().to_vec(),
+            })
+            .collect();
+
+        // A tree from before leaves were tagged
+        let db = create_test_db().await;
+        let tree = Tree::new(db.clone()).await.unwrap();
+        tree.batch_push_with_data(leaves[..40].to_vec(), vec![])
+            .await
+            .unwrap();
+        drop(tree);
+        let mut batch = db.batch();
+        for (i, leaf) in leaves[..40].iter().enumerate() {
+            batch.put(Tree::leaf_key(i as u64), postcard::to_stdvec(leaf).unwrap());
+        }
+        batch.delete(UNTAGGED_LEAVES_KEY);
+        batch.delete(UNFRAMED_LEAVES_KEY);
+        db.write_batch(batch).await.unwrap();
+
+        let tag = |index: u64, stored: &[u8]| Tree::unframe_leaf(index, stored).unwrap()[0];
+        let mut tree = Tree::new_with_cbor(db.clone()).await.unwrap();
+        tree.batch_push_with_data(leaves[40..70].to_vec(), vec![])
+            .await
+            .unwrap();
+        let stored = db.get(&Tree::leaf_key(40)).await.unwrap().unwrap();
+        assert_eq!(tag(40, &stored), LEAF_TAG_CBOR);
+        assert_eq!(tree.get_range(0, 69).await.unwrap(), leaves[..70]);
+
+        tree.set_max_batch_puts(16);
+        // Not while another instance holds the old leaf formats
+        let reader = Tree::new(db.clone()).await.unwrap();
+        assert!(tree.migrate_leaves_to_cbor().await.is_err());
+        drop(reader);
+        assert_eq!(tree.migrate_leaves_to_cbor().await.unwrap(), 40);
+        assert_eq!(tree.migrate_leaves_to_cbor().await.unwrap(), 0);
+        for i in 0..70 {
+            let stored = db.get(&Tree::leaf_key(i)).await.unwrap().unwrap();
+            assert_eq!(tag(i, &stored), LEAF_TAG_CBOR, "leaf {}", i);
+        }
+
+        // A postcard writer appends to the migrated tree
+        let tree = Tree::new(db.clone()).await.unwrap();
+        assert_eq!(tree.untagged_leaves, 0);
+        assert_eq!(tree.unframed_leaves, 0);
+        tree.batch_push_with_data(leaves[70..].to_vec(), vec![])
+            .await
+            .unwrap();
+        let stored = db.get(&Tree::leaf_key(70)).await.unwrap().unwrap();
+        assert_eq!(tag(70, &stored), LEAF_TAG_POSTCARD);
+        assert_eq!(tree.get_range(0, 99).await.unwrap(), leaves);
+        let hash = leaf_hash::<Sha256, _>(&leaves[12]);
+        assert_eq!(tree.lookup_by_leaf_hash(&hash).await.unwrap(), Some(12));
+
+        let reference = Tree::new(create_test_db().await).await.unwrap();
+        reference
+            .batch_push_with_data(leaves.clone(), vec![])
+            .await
+            .unwrap();
+        assert_eq!(
+            tree.root().await.unwrap().as_bytes(),
+            reference.root().await.unwrap().as_bytes()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_leaf_compression() {
+        type Tree = SlateDbBackedTree<Sha256, TestLeaf>;
+        // Long leaves compress well, short ones do not compress at all
+        let leaves: Vec<TestLeaf> = (0u32..90)
+            .map(|i| TestLeaf {
+                data: if i % 10 == 9 {
+                    i.to_be_bytes().to_vec()
+                } else {
+                    i.to_be_bytes().repeat(64)
+                },
+            })
+            .collect();
+
+        // A tree from before leaves had a compression format byte
+        let db = create_test_db().await;
+        let tree = Tree::new(db.clone()).await.unwrap();
+        tree.batch_push_with_data(leaves[..30].to_vec(), vec![])
+            .await
+            .unwrap();
+        let mut batch = db.batch();
+        for (i, leaf) in leaves[..30].iter().enumerate() {
+            batch.put(
+                Tree::leaf_key(i as u64),
+                postcard::to_extend(leaf, vec![LEAF_TAG_POSTCARD]).unwrap(),
+            );
+        }
+        batch.delete(UNFRAMED_LEAVES_KEY);
+        db.write_batch(batch).await.unwrap();
+
+        let mut tree = Tree::new(db.clone()).await.unwrap();
+        assert_eq!(tree.unframed_leaves, 30);
+        tree.set_leaf_compression(Compression::Zstd(3));
+        tree.batch_push_with_data(leaves[30..60].to_vec(), vec![])
+            .await
+            .unwrap();
+        let stored = db.get(&Tree::leaf_key(30)).await.unwrap().unwrap();
+        assert_eq!(stored[0], 1);
+        assert!(stored.len() < leaves[30].data.len());
+        let stored = db.get(&Tree::leaf_key(39)).await.unwrap().unwrap();
+        assert_eq!(stored[0], 0);
+
+        tree.set_leaf_compression(Compression::None);
+        tree.batch_push_with_data(leaves[60..].to_vec(), vec![])
+            .await
+            .unwrap();
+        let stored = db.get(&Tree::leaf_key(60)).await.unwrap().unwrap();
+        assert_eq!(stored[0], 0);
+
+        let tree = Tree::new(db.clone()).await.unwrap();
+        assert_eq!(tree.get_range(0, 89).await.unwrap(), leaves);
+        let hash = leaf_hash::<Sha256, _>(&leaves[42]);
+        assert_eq!(tree.lookup_by_leaf_hash(&hash).await.unwrap(), Some(42));
+
+        let reference = Tree::new(create_test_db().await).await.unwrap();
+        for chunk in leaves.chunks(30) {
+            reference
+                .batch_push_with_data(chunk.to_vec(), vec![])
+                .await
+                .unwrap();
+        }
+        for size in [30, 60, 90] {
+            assert_eq!(
+                tree.root_at_size(size).await.unwrap().as_bytes(),
+                reference.root_at_size(size).await.unwrap().as_bytes()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_push_across_trees() {
+        type Tree = SlateDbBackedTree<Sha256, TestLeaf>;
+        let leaves = |range: std::ops::Range<u32>, tag: u8| -> Vec<TestLeaf> {
+            range
+                .map(|i| TestLeaf {
+                    data: [&[tag][..], &i.to_be_bytes()].concat(),
+                })
+                .collect()
+        };
 
-        // Create a large batch
-        let leaves: Vec<TestLeaf> = (0..100)
-            .map(|i| TestLeaf {
-                data: vec![i as u8],
-            })
-            .collect();
+        let db = create_test_db().await;
+        let mut x509 = Tree::new(db.with_key_prefix(b"x509/")).await.unwrap();
+        let precert = Tree::new(db.with_key_prefix(b"precert/")).await.unwrap();
+        x509.set_max_batch_puts(16);
 
-        let start_idx = tree
-            .batch_push_with_data(leaves.clone(), vec![])
-            .await
-            .unwrap();
-        assert_eq!(start_idx, 0);
-        assert_eq!(tree.len().await.unwrap(), 100);
+        let starts =
+            Tree::batch_push_across_trees(&x509, leaves(0..70, 0), &precert, leaves(0..30, 1))
+                .await
+                .unwrap();
+        assert_eq!(starts, (0, 0));
+        let starts =
+            Tree::batch_push_across_trees(&x509, leaves(70..100, 0), &precert, leaves(30..35, 1))
+                .await
+                .unwrap();
+        assert_eq!(starts, (70, 30));
+
+        // Each tree matches one built alone, and reopens from its own keyspace
+        for (tree, prefix, expected) in [
+            (&x509, &b"x509/"[..], leaves(0..100, 0)),
+            (&precert, &b"precert/"[..], leaves(0..35, 1)),
+        ] {
+            let reference = Tree::new(create_test_db().await).await.unwrap();
+            reference
+                .batch_push_with_data(expected.clone(), vec![])
+                .await
+                .unwrap();
+            let root = tree.root().await.unwrap();
+            assert_eq!(root.as_bytes(), reference.root().await.unwrap().as_bytes());
 
-        // Verify random samples
-        for i in [0, 25, 50, 75, 99] {
-            let retrieved = tree.get(i as u64).await.unwrap();
-            assert_eq!(retrieved, Some(leaves[i].clone()));
+            let reopened = Tree::new(db.with_key_prefix(prefix)).await.unwrap();
+            assert_eq!(reopened.len().await.unwrap(), expected.len() as u64);
+            assert_eq!(reopened.root().await.unwrap().as_bytes(), root.as_bytes());
         }
+        assert!(db.get(META_KEY).await.unwrap().is_none());
+
+        // A failed chunk of the first tree moves neither
+        x509.fail_at_chunk = Some(0);
+        assert!(Tree::batch_push_across_trees(
+            &x509,
+            leaves(100..200, 0),
+            &precert,
+            leaves(35..40, 1)
+        )
+        .await
+        .is_err());
+        x509.fail_at_chunk = None;
+        assert_eq!(x509.len().await.unwrap(), 100);
+        assert_eq!(precert.len().await.unwrap(), 35);
+        let starts =
+            Tree::batch_push_across_trees(&x509, leaves(100..101, 0), &precert, leaves(35..36, 1))
+                .await
+                .unwrap();
+        assert_eq!(starts, (100, 35));
 
-        // Consistency proof requires STH boundaries, so this should fail for size 50
-        let result = tree.prove_consistency_between(50, 100).await;
-        assert!(result.is_err()); // Not a published STH boundary
+        let same_keyspace = Tree::new(db.with_key_prefix(b"x509/")).await;
+        assert!(
+            Tree::batch_push_across_trees(&x509, vec![], &same_keyspace.unwrap(), vec![])
+                .await
+                .is_err()
+        );
+        let elsewhere = Tree::new(create_test_db().await.with_key_prefix(b"other/"))
+            .await
+            .unwrap();
+        assert!(
+            Tree::batch_push_across_trees(&x509, vec![], &elsewhere, vec![])
+                .await
+                .is_err()
+        );
     }
 
     #[tokio::test]
@@ -2226,6 +4883,81 @@ mod tests {
             .expect("Consistency proof should verify");
     }
 
+    #[tokio::test]
+    async fn test_tampered_node_is_detected() {
+        let db = create_test_db().await;
+        let tree: SlateDbBackedTree<Sha256, TestLeaf> = SlateDbBackedTree::new(db).await.unwrap();
+
+        for i in 0..7u8 {
+            tree.batch_push_with_data(vec![TestLeaf { data: vec![i] }], vec![])
+                .await
+                .unwrap();
+        }
+        for size in 0..=7 {
+            assert_eq!(
+                tree.verify_integrity(size).await.unwrap(),
+                IntegrityResult::Intact
+            );
+        }
+
+        // Roots a client saw before the store was tampered with
+        let root3 = tree.root_at_size(3).await.unwrap();
+        let root7 = tree.root_at_size(7).await.unwrap();
+
+        // The subtree over leaves 0..2 is part of the 3 -> 7 proof
+        let node_idx = 1;
+        assert!(
+            crate::merkle_tree::consistency::indices_for_consistency_proof(3, 4)
+                .contains(&node_idx)
+        );
+        tree.corrupt_node(node_idx).await.unwrap();
+
+        assert_eq!(
+            tree.verify_integrity(7).await.unwrap(),
+            IntegrityResult::Corrupted {
+                node_idx,
+                leaf_start: 0,
+                leaf_end: 2,
+            }
+        );
+
+        let proof = tree.prove_consistency_between(3, 7).await.unwrap();
+        assert!(proof.verify(&root3, &root7).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tampered_trailing_leaf_is_detected() {
+        let db = create_test_db().await;
+        let tree: SlateDbBackedTree<Sha256, TestLeaf> = SlateDbBackedTree::new(db).await.unwrap();
+
+        for i in 0..13u8 {
+            tree.batch_push_with_data(vec![TestLeaf { data: vec![i] }], vec![])
+                .await
+                .unwrap();
+        }
+        for size in 0..=13 {
+            assert_eq!(
+                tree.verify_integrity(size).await.unwrap(),
+                IntegrityResult::Intact
+            );
+        }
+
+        // Leaf 12 moves up unpaired until the node over leaves 8..13
+        tree.corrupt_node(24).await.unwrap();
+        assert_eq!(
+            tree.verify_integrity(13).await.unwrap(),
+            IntegrityResult::Corrupted {
+                node_idx: 24,
+                leaf_start: 12,
+                leaf_end: 13,
+            }
+        );
+        assert_eq!(
+            tree.verify_integrity(12).await.unwrap(),
+            IntegrityResult::Intact
+        );
+    }
+
     #[tokio::test]
     async fn test_consistency_proof_same_size_verification() {
         let db = create_test_db().await;
@@ -2696,7 +5428,7 @@ mod tests {
                 let leaf = TestLeaf {
                     data: vec![i as u8],
                 };
-                tree_clone.push(leaf).await
+                (tree_clone.push(leaf.clone()).await, leaf)
             });
             handles.push(handle);
         }
@@ -2704,10 +5436,11 @@ mod tests {
         // Wait for all operations
         let results: Vec<_> = join_all(handles).await;
 
-        // All operations should succeed
+        // All operations should succeed, each at the index it was told
         for result in results {
-            assert!(result.is_ok());
-            assert!(result.unwrap().is_ok());
+            let (index, leaf) = result.unwrap();
+            let index = index.unwrap();
+            assert_eq!(tree.get(index).await.unwrap(), Some(leaf));
         }
 
         // All 20 leaves should be added
@@ -3021,4 +5754,422 @@ mod tests {
             );
         }
     }
+
+    #[tokio::test]
+    async fn test_startup_verify_consistent() {
+        let db = create_test_db().await;
+        let tree: SlateDbBackedTree<Sha256, TestLeaf> = SlateDbBackedTree::new(db).await.unwrap();
+        assert_eq!(
+            tree.startup_verify().await.unwrap(),
+            StartupResult::Consistent
+        );
+
+        // Cover power-of-two and ragged sizes
+        for batch in [vec![1u8], vec![2], vec![3, 4, 5], vec![6, 7, 8], vec![9]] {
+            let leaves = batch.into_iter().map(|b| TestLeaf { data: vec![b] });
+            tree.batch_push_with_data(leaves.collect(), vec![])
+                .await
+                .unwrap();
+            assert_eq!(
+                tree.startup_verify().await.unwrap(),
+                StartupResult::Consistent
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_startup_verify_detects_truncation() {
+        let db = create_test_db().await;
+        let tree: SlateDbBackedTree<Sha256, TestLeaf> =
+            SlateDbBackedTree::new(db.clone()).await.unwrap();
+
+        let leaves: Vec<_> = (0..6).map(|i| TestLeaf { data: vec![i] }).collect();
+        tree.batch_push_with_data(leaves[..4].to_vec(), vec![])
+            .await
+            .unwrap();
+        tree.batch_push_with_data(leaves[4..].to_vec(), vec![])
+            .await
+            .unwrap();
+
+        // Metadata claims leaves whose nodes were never written
+        tree.set_num_leaves(9).await.unwrap();
+        assert_eq!(
+            tree.startup_verify().await.unwrap(),
+            StartupResult::Truncated { actual_size: 6 }
+        );
+
+        // A root that no longer matches its subtrees falls back to the previous batch
+        tree.set_num_leaves(6).await.unwrap();
+        db.put(
+            &SlateDbBackedTree::<Sha256, TestLeaf>::versioned_node_key(root_idx(6).as_u64(), 6),
+            &[0xff; 32],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            tree.startup_verify().await.unwrap(),
+            StartupResult::Truncated { actual_size: 4 }
+        );
+
+        // Reopening only warns
+        let reopened: SlateDbBackedTree<Sha256, TestLeaf> =
+            SlateDbBackedTree::new(db).await.unwrap();
+        assert_eq!(reopened.len().await.unwrap(), 6);
+    }
+
+    #[test]
+    fn test_spine_subtree_roots() {
+        type Tree = SlateDbBackedTree<Sha256, TestLeaf>;
+
+        assert_eq!(Tree::spine_subtree_roots(1), vec![0]);
+        assert_eq!(Tree::spine_subtree_roots(2), vec![0, 2]);
+        assert_eq!(Tree::spine_subtree_roots(3), vec![1, 4]);
+        assert_eq!(Tree::spine_subtree_roots(8), vec![3, 11]);
+        assert_eq!(Tree::spine_subtree_roots(13), vec![7, 19, 24]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_batch_push_allocates_disjoint_ranges() {
+        const TASKS: usize = 16;
+        const BATCHES_PER_TASK: usize = 10;
+        const BATCH_SIZE: usize = 3;
+
+        // Reference RFC 6962 MTH over an in-memory leaf list
+        fn mth(leaves: &[TestLeaf]) -> digest::Output<Sha256> {
+            match leaves.len() {
+                0 => Sha256::digest(b""),
+                1 => leaf_hash::<Sha256, _>(&leaves[0]),
+                n => {
+                    let k = 1usize << (usize::BITS - (n - 1).leading_zeros() - 1);
+                    parent_hash::<Sha256>(&mth(&leaves[..k]), &mth(&leaves[k..]))
+                }
+            }
+        }
+
+        let db = create_test_db().await;
+        let tree = Arc::new(
+            SlateDbBackedTree::<Sha256, TestLeaf>::new(db)
+                .await
+                .unwrap(),
+        );
+
+        let handles: Vec<_> = (0..TASKS)
+            .map(|task| {
+                let tree = Arc::clone(&tree);
+                tokio::spawn(async move {
+                    let mut pushed = Vec::new();
+                    for batch in 0..BATCHES_PER_TASK {
+                        let leaves: Vec<TestLeaf> = (0..BATCH_SIZE)
+                            .map(|j| TestLeaf {
+                                data: vec![task as u8, batch as u8, j as u8],
+                            })
+                            .collect();
+                        let start = tree
+                            .batch_push_with_data(leaves.clone(), vec![])
+                            .await
+                            .unwrap();
+                        pushed.push((start, leaves));
+                        tokio::task::yield_now().await;
+                    }
+                    pushed
+                })
+            })
+            .collect();
+
+        let mut memory_tree: Vec<Option<TestLeaf>> =
+            vec![None; TASKS * BATCHES_PER_TASK * BATCH_SIZE];
+        let mut batch_ends = Vec::new();
+        for handle in handles {
+            for (start, leaves) in handle.await.unwrap() {
+                batch_ends.push(start + leaves.len() as u64);
+                for (offset, leaf) in leaves.into_iter().enumerate() {
+                    let slot = &mut memory_tree[start as usize + offset];
+                    assert!(
+                        slot.is_none(),
+                        "index {} allocated twice",
+                        start as usize + offset
+                    );
+                    *slot = Some(leaf);
+                }
+            }
+        }
+        let memory_tree: Vec<TestLeaf> = memory_tree
+            .into_iter()
+            .map(|leaf| leaf.expect("every index is allocated exactly once"))
+            .collect();
+
+        assert_eq!(tree.len().await.unwrap(), memory_tree.len() as u64);
+        for (idx, leaf) in memory_tree.iter().enumerate() {
+            assert_eq!(tree.get(idx as u64).await.unwrap().as_ref(), Some(leaf));
+        }
+
+        let root = tree.root().await.unwrap();
+        assert_eq!(root.as_bytes(), &mth(&memory_tree));
+
+        // Every intermediate size some caller observed is a consistent STH
+        for size in batch_ends {
+            let root = tree.root_at_size(size).await.unwrap();
+            assert_eq!(root.as_bytes(), &mth(&memory_tree[..size as usize]));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parallel_consistency_proof_matches_sequential() {
+        let db = create_test_db().await;
+        let tree = Arc::new(
+            SlateDbBackedTree::<Sha256, TestLeaf>::new(db)
+                .await
+                .unwrap(),
+        );
+
+        // Uneven batches so the boundaries exercise both SUBPROOF branches
+        let mut boundaries = Vec::new();
+        for (batch, len) in [1u8, 2, 3, 5, 8, 13, 1, 4].into_iter().enumerate() {
+            let leaves = (0..len)
+                .map(|j| TestLeaf {
+                    data: vec![batch as u8, j],
+                })
+                .collect();
+            tree.batch_push_with_data(leaves, vec![]).await.unwrap();
+            boundaries.push(tree.len().await.unwrap());
+        }
+
+        for (i, &old_size) in boundaries.iter().enumerate() {
+            let old_root = tree.root_at_size(old_size).await.unwrap();
+            for &new_size in &boundaries[i..] {
+                let sequential = tree
+                    .prove_consistency_between(old_size, new_size)
+                    .await
+                    .unwrap();
+                let parallel = tree
+                    .parallel_consistency_proof(old_size, new_size)
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    parallel.as_bytes(),
+                    sequential.as_bytes(),
+                    "{} -> {}",
+                    old_size,
+                    new_size
+                );
+
+                if old_size < new_size {
+                    let new_root = tree.root_at_size(new_size).await.unwrap();
+                    parallel.verify(&old_root, &new_root).unwrap();
+                }
+            }
+        }
+
+        // Same validation as the sequential path
+        assert!(tree.parallel_consistency_proof(0, 6).await.is_err());
+        assert!(tree.parallel_consistency_proof(6, 3).await.is_err());
+        assert!(tree.parallel_consistency_proof(3, 1000).await.is_err());
+        let err = tree.parallel_consistency_proof(4, 11).await.unwrap_err();
+        assert!(err.to_string().contains("not a published STH boundary"));
+    }
+
+    #[tokio::test]
+    async fn test_bounded_proof_fetches_match_unbounded() {
+        let db = create_test_db().await;
+        let mut tree = Arc::new(
+            SlateDbBackedTree::<Sha256, TestLeaf>::new(db)
+                .await
+                .unwrap(),
+        );
+
+        let mut boundaries = Vec::new();
+        for (batch, len) in [3u8, 1, 7, 16, 2, 9].into_iter().enumerate() {
+            let leaves = (0..len)
+                .map(|j| TestLeaf {
+                    data: vec![batch as u8, j],
+                })
+                .collect();
+            tree.batch_push_with_data(leaves, vec![]).await.unwrap();
+            boundaries.push(tree.len().await.unwrap());
+        }
+
+        async fn all_proofs(
+            tree: &Arc<SlateDbBackedTree<Sha256, TestLeaf>>,
+            boundaries: &[u64],
+        ) -> Vec<Vec<u8>> {
+            let mut proofs = Vec::new();
+            for (i, &old_size) in boundaries.iter().enumerate() {
+                for idx in 0..old_size {
+                    let proof = tree.prove_inclusion_at_size(idx, old_size).await.unwrap();
+                    proofs.push(proof.as_bytes().to_vec());
+                }
+                for &new_size in &boundaries[i..] {
+                    let proof = tree
+                        .parallel_consistency_proof(old_size, new_size)
+                        .await
+                        .unwrap();
+                    proofs.push(proof.as_bytes().to_vec());
+                    let proof = tree
+                        .prove_consistency_between(old_size, new_size)
+                        .await
+                        .unwrap();
+                    proofs.push(proof.as_bytes().to_vec());
+                }
+            }
+            proofs
+        }
+
+        let unbounded = all_proofs(&tree, &boundaries).await;
+        for limit in [1, 3] {
+            Arc::get_mut(&mut tree)
+                .unwrap()
+                .set_proof_fetch_concurrency(Some(limit));
+            assert_eq!(
+                all_proofs(&tree, &boundaries).await,
+                unbounded,
+                "limit {}",
+                limit
+            );
+
+            let err = tree.parallel_consistency_proof(5, 11).await.unwrap_err();
+            assert!(err.to_string().contains("not a published STH boundary"));
+        }
+    }
+
+    /// Compares proof latency of both implementations on a 1M-leaf tree.
+    /// Run with `cargo test --release -- --ignored bench_consistency_proof --nocapture`.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    #[ignore]
+    async fn bench_consistency_proof_latency() {
+        use rand::Rng;
+        use std::time::Instant;
+
+        const TREE_SIZE: u64 = 1 << 20;
+        const BATCH_SIZE: u64 = 4096;
+        const SAMPLES: usize = 2000;
+
+        let db = create_test_db().await;
+        let tree = Arc::new(
+            SlateDbBackedTree::<Sha256, TestLeaf>::new(db)
+                .await
+                .unwrap(),
+        );
+        for batch in 0..TREE_SIZE / BATCH_SIZE {
+            let leaves = (0..BATCH_SIZE)
+                .map(|j| TestLeaf {
+                    data: (batch * BATCH_SIZE + j).to_be_bytes().to_vec(),
+                })
+                .collect();
+            tree.batch_push_with_data(leaves, vec![]).await.unwrap();
+        }
+
+        let mut rng = rand::thread_rng();
+        let pairs: Vec<(u64, u64)> = (0..SAMPLES)
+            .map(|_| {
+                let a = rng.gen_range(1..=TREE_SIZE / BATCH_SIZE) * BATCH_SIZE;
+                let b = rng.gen_range(1..=TREE_SIZE / BATCH_SIZE) * BATCH_SIZE;
+                (a.min(b), a.max(b))
+            })
+            .collect();
+
+        fn p99(mut samples: Vec<std::time::Duration>) -> std::time::Duration {
+            samples.sort();
+            samples[samples.len() * 99 / 100]
+        }
+
+        let mut sequential = Vec::with_capacity(SAMPLES);
+        let mut parallel = Vec::with_capacity(SAMPLES);
+        for &(old_size, new_size) in &pairs {
+            let start = Instant::now();
+            tree.prove_consistency_between(old_size, new_size)
+                .await
+                .unwrap();
+            sequential.push(start.elapsed());
+
+            let start = Instant::now();
+            tree.parallel_consistency_proof(old_size, new_size)
+                .await
+                .unwrap();
+            parallel.push(start.elapsed());
+        }
+
+        println!(
+            "consistency proof p99 over {} samples: sequential {:?}, parallel {:?}",
+            SAMPLES,
+            p99(sequential),
+            p99(parallel)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_epoch_usage_partitions_tree_keys() {
+        let tree: SlateDbBackedTree<Sha256, TestLeaf> =
+            SlateDbBackedTree::new(create_test_db().await)
+                .await
+                .unwrap();
+        for batch in 0u8..4 {
+            let leaves = (0..5).map(|j| TestLeaf {
+                data: vec![batch, j],
+            });
+            tree.batch_push_with_data(leaves.collect(), vec![])
+                .await
+                .unwrap();
+        }
+        assert_eq!(tree.get_committed_size().await.unwrap(), 20);
+
+        // 6 is rounded up to epochs of 8 leaves
+        let epochs = tree.epoch_usage(6).await.unwrap();
+        assert_eq!(epochs.len(), 3);
+        assert_eq!(
+            epochs
+                .iter()
+                .map(|e| (e.first_leaf, e.leaves, e.sealed))
+                .collect::<Vec<_>>(),
+            [(0, 8, true), (8, 8, true), (16, 4, false)]
+        );
+        for epoch in &epochs {
+            assert_eq!(epoch.leaf_keys, epoch.leaves);
+            assert!(epoch.leaf_bytes > 0);
+            // At least a latest version pointer and one version of each node
+            assert!(epoch.node_keys >= 2 * (2 * epoch.leaves - 1));
+        }
+
+        // Only the nodes above an epoch fall outside every epoch
+        let count = |prefix: &'static [u8], end: &'static [u8]| {
+            let db = tree.db.clone();
+            async move { db.scan(prefix, end).await.unwrap().len() as u64 }
+        };
+        let leaves = count(LEAF_PREFIX, b"leaf;").await;
+        assert_eq!(leaves, epochs.iter().map(|e| e.leaf_keys).sum::<u64>());
+        let above_epochs = [15u64, 31];
+        let mut node_keys = count(VERSIONED_NODE_PREFIX, b"vnode;").await
+            + count(NODE_LATEST_VERSION_PREFIX, b"nver;").await;
+        for idx in above_epochs {
+            node_keys -= count_node_keys(&tree, idx).await;
+        }
+        assert_eq!(node_keys, epochs.iter().map(|e| e.node_keys).sum::<u64>());
+
+        // The reported ranges are the ones counted
+        let (start, end) = &epochs[1].key_ranges[0];
+        let scanned = tree
+            .db
+            .scan(&hex::decode(start).unwrap(), &hex::decode(end).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(scanned.len() as u64, epochs[1].leaf_keys);
+    }
+
+    async fn count_node_keys(tree: &SlateDbBackedTree<Sha256, TestLeaf>, idx: u64) -> u64 {
+        let versions = tree
+            .db
+            .scan(
+                &SlateDbBackedTree::<Sha256, TestLeaf>::versioned_node_key(idx, 0),
+                &SlateDbBackedTree::<Sha256, TestLeaf>::versioned_node_key(idx + 1, 0),
+            )
+            .await
+            .unwrap()
+            .len() as u64;
+        let latest = tree
+            .db
+            .get(&SlateDbBackedTree::<Sha256, TestLeaf>::node_latest_version_key(idx))
+            .await
+            .unwrap()
+            .is_some() as u64;
+        versions + latest
+    }
 }
@@ -0,0 +1,277 @@
+//! Bulk appends that defer node hashing to one flush.
+//!
+//! Pushing leaves one at a time through [`SlateDbBackedTree::push`] writes a
+//! version of every node on the new leaf's path per leaf, so an import of `n`
+//! leaves stores about `n log n` node keys, most of them superseded straight
+//! away. [`LazySlateDbTree`] stores each leaf as it is pushed and computes the
+//! nodes once, at the size [`LazySlateDbTree::flush_and_compute_root`] is
+//! called at, as a single append of everything pushed since the last flush.
+//!
+//! Leaves pushed but not flushed sit past the tree size under the
+//! `PENDING_APPEND_KEY` marker, as the early chunks of a failed append do, so
+//! the next append clears them if the wrapper is dropped without a flush.
+//!
+//! Every leaf pushed since the last flush is also kept in memory, since the
+//! flush hashes them all, so a long import should flush every so often, say
+//! every million leaves, rather than once at the end.
+
+use super::{
+    check_capacity, Frontier, ResultExt, SlateDbBackedTree, SlateDbTreeError, PENDING_APPEND_KEY,
+};
+use crate::merkle_tree::ct_merkle_vendored::{root_idx, HashableLeaf, RootHash};
+use crate::storage::DbBatch;
+use digest::Digest;
+use tokio::sync::MutexGuard;
+
+/// Appends to a [`SlateDbBackedTree`] whose nodes are computed on flush.
+/// Holds the tree's write lock, so other appends wait until it is dropped.
+pub struct LazySlateDbTree<'a, H: Digest, T: HashableLeaf> {
+    tree: &'a SlateDbBackedTree<H, T>,
+    _write_guard: MutexGuard<'a, Option<Frontier<H>>>,
+    frontier: Frontier<H>,
+    // Size of the tree at the last flush
+    start: u64,
+    // Leaves pushed since the last flush, held until it hashes them; unbounded,
+    // see the module docs
+    pending: Vec<T>,
+    // `leaf:` puts of pending leaves not written yet
+    leaves: DbBatch,
+    unwritten_leaves: usize,
+}
+
+impl<H, T> SlateDbBackedTree<H, T>
+where
+    H: Digest,
+    T: HashableLeaf + serde::Serialize + serde::de::DeserializeOwned,
+{
+    /// Start a bulk append, such as the initial import of a log, whose root
+    /// is only computed on [`LazySlateDbTree::flush_and_compute_root`]
+    pub async fn recompute_root_lazily(
+        &self,
+    ) -> Result<LazySlateDbTree<'_, H, T>, SlateDbTreeError> {
+        let mut write_guard = self.write_lock.lock().await;
+        let start = self.len().await?;
+        let frontier = self.take_frontier(&mut write_guard, start).await?;
+        Ok(LazySlateDbTree {
+            tree: self,
+            _write_guard: write_guard,
+            frontier,
+            start,
+            pending: Vec::new(),
+            leaves: self.db.batch(),
+            unwritten_leaves: 0,
+        })
+    }
+}
+
+impl<H, T> LazySlateDbTree<'_, H, T>
+where
+    H: Digest,
+    T: HashableLeaf + serde::Serialize + serde::de::DeserializeOwned,
+{
+    /// Store a leaf and return the index it will have once flushed. Leaves
+    /// are written in batches of at most the tree's `max_batch_puts`, and
+    /// kept in memory until the next flush.
+    pub async fn push(&mut self, item: T) -> Result<u64, SlateDbTreeError> {
+        let index = self.start + self.pending.len() as u64;
+        check_capacity(index, 1)?;
+
+        let leaf_bytes = self.tree.encode_leaf(&item)?;
+        self.leaves
+            .put(SlateDbBackedTree::<H, T>::leaf_key(index), &leaf_bytes);
+        self.unwritten_leaves += 1;
+        self.pending.push(item);
+        if self.unwritten_leaves >= self.tree.max_batch_puts {
+            self.write_leaves().await?;
+        }
+        Ok(index)
+    }
+
+    /// Leaves pushed since the last flush
+    pub fn pending(&self) -> u64 {
+        self.pending.len() as u64
+    }
+
+    /// Compute the nodes of every leaf pushed since the last flush, commit
+    /// them as one append and return the new root
+    pub async fn flush_and_compute_root(&mut self) -> Result<RootHash<H>, SlateDbTreeError> {
+        if self.pending.is_empty() {
+            return self.tree.root_at_size(self.start).await;
+        }
+        if self.unwritten_leaves > 0 {
+            self.write_leaves().await?;
+        }
+
+        let tree = self.tree;
+        let chunk = tree.chunked_puts(
+            tree.db.batch(),
+            tree.max_batch_puts,
+            self.start,
+            &self.pending,
+        );
+        let (mut batch, size, computed_hashes) = tree
+            .stage_append(
+                chunk,
+                &self.frontier,
+                self.start,
+                &self.pending,
+                true,
+                Vec::new(),
+            )
+            .await?;
+        batch.delete(PENDING_APPEND_KEY);
+        tree.db
+            .write_batch(batch)
+            .await
+            .context("writing lazily appended nodes")?;
+
+        let root_hash = computed_hashes
+            .get(&root_idx(size).as_u64())
+            .cloned()
+            .ok_or_else(|| {
+                SlateDbTreeError::InconsistentState(format!("Missing root of size {}", size))
+            })?;
+        self.frontier = match SlateDbBackedTree::<H, T>::advance_frontier(
+            &self.frontier,
+            size,
+            &computed_hashes,
+        ) {
+            Some(frontier) => frontier,
+            None => tree.load_frontier(size).await?,
+        };
+        self.start = size;
+        self.pending.clear();
+
+        Ok(RootHash::new(root_hash, size))
+    }
+
+    /// Write the buffered `leaf:` puts, moving the marker's end up to the
+    /// last pending leaf
+    async fn write_leaves(&mut self) -> Result<(), SlateDbTreeError> {
+        let end = self.start + self.pending.len() as u64;
+        let mut marker = [0; 16];
+        marker[..8].copy_from_slice(&self.start.to_be_bytes());
+        marker[8..].copy_from_slice(&end.to_be_bytes());
+
+        let mut batch = std::mem::replace(&mut self.leaves, self.tree.db.batch());
+        batch.put(PENDING_APPEND_KEY, marker);
+        self.tree
+            .db
+            .write_batch(batch)
+            .await
+            .context("writing lazily appended leaves")?;
+        self.unwritten_leaves = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::slatedb_backed_tree::tests::create_test_db;
+    use sha2::Sha256;
+
+    #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct TestLeaf {
+        data: Vec<u8>,
+    }
+
+    impl HashableLeaf for TestLeaf {
+        fn hash<H: digest::Update>(&self, hasher: &mut H) {
+            hasher.update(&self.data);
+        }
+    }
+
+    fn leaves(range: std::ops::Range<u64>) -> Vec<TestLeaf> {
+        range
+            .map(|i| TestLeaf {
+                data: i.to_be_bytes().to_vec(),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_lazy_push_matches_eager_tree() {
+        let eager: SlateDbBackedTree<Sha256, TestLeaf> =
+            SlateDbBackedTree::new(create_test_db().await)
+                .await
+                .unwrap();
+        for batch in [0..1000, 1000..1500] {
+            eager
+                .batch_push_with_data(leaves(batch), vec![])
+                .await
+                .unwrap();
+        }
+
+        let mut tree: SlateDbBackedTree<Sha256, TestLeaf> =
+            SlateDbBackedTree::new(create_test_db().await)
+                .await
+                .unwrap();
+        tree.set_max_batch_puts(64);
+        tree.push(TestLeaf {
+            data: 0u64.to_be_bytes().to_vec(),
+        })
+        .await
+        .unwrap();
+
+        let mut lazy = tree.recompute_root_lazily().await.unwrap();
+        for (i, leaf) in leaves(1..1000).into_iter().enumerate() {
+            assert_eq!(lazy.push(leaf).await.unwrap(), i as u64 + 1);
+        }
+        assert_eq!(lazy.pending(), 999);
+        let root = lazy.flush_and_compute_root().await.unwrap();
+        assert_eq!(root.num_leaves(), 1000);
+        assert_eq!(
+            root.as_bytes(),
+            eager.root_at_size(1000).await.unwrap().as_bytes()
+        );
+
+        // A second flush continues from the first, and an empty one changes
+        // nothing
+        for leaf in leaves(1000..1500) {
+            lazy.push(leaf).await.unwrap();
+        }
+        let root = lazy.flush_and_compute_root().await.unwrap();
+        assert_eq!(
+            lazy.flush_and_compute_root().await.unwrap().as_bytes(),
+            root.as_bytes()
+        );
+        drop(lazy);
+
+        assert_eq!(root.as_bytes(), eager.root().await.unwrap().as_bytes());
+        assert_eq!(tree.len().await.unwrap(), 1500);
+        assert!(tree.db.get(PENDING_APPEND_KEY).await.unwrap().is_none());
+        assert_eq!(
+            tree.get(1234).await.unwrap(),
+            Some(leaves(1234..1235)[0].clone())
+        );
+        tree.prove_consistency(1000).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unflushed_leaves_are_cleared() {
+        let mut tree: SlateDbBackedTree<Sha256, TestLeaf> =
+            SlateDbBackedTree::new(create_test_db().await)
+                .await
+                .unwrap();
+        tree.set_max_batch_puts(16);
+        tree.batch_push_with_data(leaves(0..10), vec![])
+            .await
+            .unwrap();
+        let root = tree.root().await.unwrap();
+
+        let mut lazy = tree.recompute_root_lazily().await.unwrap();
+        for leaf in leaves(100..200) {
+            lazy.push(leaf).await.unwrap();
+        }
+        drop(lazy);
+        assert_eq!(tree.len().await.unwrap(), 10);
+        assert_eq!(tree.root().await.unwrap().as_bytes(), root.as_bytes());
+        assert!(tree.db.get(PENDING_APPEND_KEY).await.unwrap().is_some());
+
+        // The next append clears the leaves written ahead
+        tree.push(leaves(10..11)[0].clone()).await.unwrap();
+        assert!(tree.db.get(PENDING_APPEND_KEY).await.unwrap().is_none());
+        assert_eq!(tree.get(20).await.unwrap(), None);
+    }
+}
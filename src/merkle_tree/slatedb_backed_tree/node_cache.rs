@@ -0,0 +1,370 @@
+//! Node hash caches of [`SlateDbBackedTree`](super::SlateDbBackedTree), keyed
+//! by (node index, version).
+//!
+//! The default cache holds each hash in its own allocation. The pool cache
+//! instead allocates its buffers up front and stores hashes in fixed-width
+//! slots, evicting the least recently used slot when full. Per node it keeps
+//! only the key, two list links and an index entry besides the raw hash, and
+//! the byte budget covers all of them, so its footprint is fixed by that
+//! budget rather than by the allocator's overhead per entry.
+//!
+//! Large pools are split into shards by key, each behind its own lock and
+//! with its own share of the budget, so concurrent proof requests do not
+//! queue on one mutex. Eviction is least recently used within a shard.
+
+use foyer::{Cache, CacheBuilder};
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::sync::Mutex;
+
+type NodeKey = (u64, u64);
+
+/// Entries of the default node cache
+const DEFAULT_NODE_CACHE_ENTRIES: usize = 100_000;
+
+/// Shards of a pool cache large enough to give each [`MIN_SHARD_SLOTS`]
+const POOL_SHARDS: usize = 16;
+
+/// Smallest shard worth splitting a pool for; smaller pools stay one shard
+const MIN_SHARD_SLOTS: usize = 4096;
+
+pub(super) enum NodeCache {
+    Heap(Cache<NodeKey, Vec<u8>>),
+    Pool(Box<[Mutex<HashPool>]>),
+}
+
+impl NodeCache {
+    pub(super) fn heap() -> Self {
+        NodeCache::Heap(CacheBuilder::new(DEFAULT_NODE_CACHE_ENTRIES).build())
+    }
+
+    /// A pool of `max_bytes`, bookkeeping included, holding hashes of
+    /// `hash_len` bytes
+    pub(super) fn pool(max_bytes: u64, hash_len: usize) -> Self {
+        let slots = HashPool::slots_within(max_bytes, hash_len);
+        let shards = if slots >= POOL_SHARDS * MIN_SHARD_SLOTS {
+            POOL_SHARDS
+        } else {
+            1
+        };
+        let shard_bytes = max_bytes / shards as u64;
+        NodeCache::Pool(
+            (0..shards)
+                .map(|_| Mutex::new(HashPool::new(shard_bytes, hash_len)))
+                .collect(),
+        )
+    }
+
+    fn shard<'a>(shards: &'a [Mutex<HashPool>], key: &NodeKey) -> &'a Mutex<HashPool> {
+        // Neighbouring nodes of one version land in different shards
+        let mixed = (key.0 ^ key.1.rotate_left(32)).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        &shards[(mixed >> 32) as usize % shards.len()]
+    }
+
+    /// Copy the cached hash of `key` into `out`, if one of that length is cached
+    pub(super) fn get_into(&self, key: &NodeKey, out: &mut [u8]) -> bool {
+        match self {
+            NodeCache::Heap(cache) => match cache.get(key) {
+                Some(entry) if entry.value().len() == out.len() => {
+                    out.copy_from_slice(entry.value());
+                    true
+                }
+                _ => false,
+            },
+            NodeCache::Pool(shards) => Self::shard(shards, key).lock().unwrap().get_into(key, out),
+        }
+    }
+
+    pub(super) fn insert(&self, key: NodeKey, hash: &[u8]) {
+        match self {
+            NodeCache::Heap(cache) => {
+                cache.insert(key, hash.to_vec());
+            }
+            NodeCache::Pool(shards) => Self::shard(shards, &key).lock().unwrap().insert(key, hash),
+        }
+    }
+
+    #[cfg(test)]
+    pub(super) fn clear(&self) {
+        match self {
+            NodeCache::Heap(cache) => cache.clear(),
+            NodeCache::Pool(shards) => {
+                for shard in shards.iter() {
+                    shard.lock().unwrap().clear();
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub(super) fn contains(&self, key: &NodeKey) -> bool {
+        match self {
+            NodeCache::Heap(cache) => cache.contains(key),
+            NodeCache::Pool(shards) => Self::shard(shards, key)
+                .lock()
+                .unwrap()
+                .index
+                .contains_key(key),
+        }
+    }
+}
+
+/// No slot, at either end of the recency list
+const NIL: u32 = u32::MAX;
+
+/// Fixed-width hash slots in one buffer, with a recency list threaded through
+/// the slots by index
+pub(super) struct HashPool {
+    hash_len: usize,
+    bytes: Box<[u8]>,
+    /// Slot holding each cached key
+    index: HashMap<NodeKey, u32>,
+    /// Key held by each slot in use
+    keys: Vec<NodeKey>,
+    /// Neighbour slots towards the most and the least recently used end
+    newer: Vec<u32>,
+    older: Vec<u32>,
+    newest: u32,
+    oldest: u32,
+}
+
+impl HashPool {
+    fn new(max_bytes: u64, hash_len: usize) -> Self {
+        let slots = Self::slots_within(max_bytes, hash_len);
+        Self {
+            hash_len,
+            bytes: vec![0; slots * hash_len].into_boxed_slice(),
+            index: HashMap::with_capacity(slots),
+            keys: Vec::with_capacity(slots),
+            newer: Vec::with_capacity(slots),
+            older: Vec::with_capacity(slots),
+            newest: NIL,
+            oldest: NIL,
+        }
+    }
+
+    /// Bytes a pool of `slots` hashes of `hash_len` bytes takes: the hashes,
+    /// a key and two links per slot, and the index's table, whose buckets
+    /// hold an entry and a control byte and are kept at most 7/8 full in a
+    /// power-of-two count
+    fn footprint(slots: usize, hash_len: usize) -> u64 {
+        let per_slot = hash_len + size_of::<NodeKey>() + 2 * size_of::<u32>();
+        let buckets = match slots {
+            0 => 0,
+            1..=3 => 4,
+            4..=7 => 8,
+            _ => (slots * 8 / 7).next_power_of_two(),
+        };
+        let per_bucket = size_of::<(NodeKey, u32)>() + 1;
+        (slots * per_slot + buckets * per_bucket) as u64
+    }
+
+    /// Most slots whose [footprint](Self::footprint) fits in `max_bytes`
+    fn slots_within(max_bytes: u64, hash_len: usize) -> usize {
+        if hash_len == 0 {
+            return 0;
+        }
+        let (mut low, mut high) = (0usize, NIL as usize);
+        while low < high {
+            let mid = low + (high - low + 1) / 2;
+            if Self::footprint(mid, hash_len) <= max_bytes {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+        low
+    }
+
+    fn capacity(&self) -> usize {
+        self.bytes.len() / self.hash_len.max(1)
+    }
+
+    fn slot_bytes(&mut self, slot: u32) -> &mut [u8] {
+        let start = slot as usize * self.hash_len;
+        &mut self.bytes[start..start + self.hash_len]
+    }
+
+    fn get_into(&mut self, key: &NodeKey, out: &mut [u8]) -> bool {
+        if out.len() != self.hash_len {
+            return false;
+        }
+        let Some(&slot) = self.index.get(key) else {
+            return false;
+        };
+        self.unlink(slot);
+        self.push_newest(slot);
+        out.copy_from_slice(self.slot_bytes(slot));
+        true
+    }
+
+    fn insert(&mut self, key: NodeKey, hash: &[u8]) {
+        if hash.len() != self.hash_len || self.capacity() == 0 {
+            return;
+        }
+        let slot = match self.index.get(&key) {
+            Some(&slot) => {
+                self.unlink(slot);
+                slot
+            }
+            None if self.keys.len() < self.capacity() => {
+                let slot = self.keys.len() as u32;
+                self.keys.push(key);
+                self.newer.push(NIL);
+                self.older.push(NIL);
+                self.index.insert(key, slot);
+                slot
+            }
+            None => {
+                let slot = self.oldest;
+                self.unlink(slot);
+                self.index.remove(&self.keys[slot as usize]);
+                self.keys[slot as usize] = key;
+                self.index.insert(key, slot);
+                slot
+            }
+        };
+        self.push_newest(slot);
+        self.slot_bytes(slot).copy_from_slice(hash);
+    }
+
+    #[cfg(test)]
+    fn clear(&mut self) {
+        self.index.clear();
+        self.keys.clear();
+        self.newer.clear();
+        self.older.clear();
+        self.newest = NIL;
+        self.oldest = NIL;
+    }
+
+    fn unlink(&mut self, slot: u32) {
+        let (newer, older) = (self.newer[slot as usize], self.older[slot as usize]);
+        match newer {
+            NIL => self.newest = older,
+            newer => self.older[newer as usize] = older,
+        }
+        match older {
+            NIL => self.oldest = newer,
+            older => self.newer[older as usize] = newer,
+        }
+    }
+
+    fn push_newest(&mut self, slot: u32) {
+        self.newer[slot as usize] = NIL;
+        self.older[slot as usize] = self.newest;
+        match self.newest {
+            NIL => self.oldest = slot,
+            newest => self.newer[newest as usize] = slot,
+        }
+        self.newest = slot;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_evicts_least_recently_used() {
+        let cache = NodeCache::pool(HashPool::footprint(3, 4), 4);
+        for i in 0..3u64 {
+            cache.insert((i, 1), &[i as u8; 4]);
+        }
+
+        // Reading node 0 makes node 1 the least recently used
+        let mut hash = [0; 4];
+        assert!(cache.get_into(&(0, 1), &mut hash));
+        assert_eq!(hash, [0; 4]);
+        cache.insert((3, 1), &[3; 4]);
+        assert!(!cache.contains(&(1, 1)));
+        for i in [0, 2, 3] {
+            assert!(cache.get_into(&(i, 1), &mut hash));
+            assert_eq!(hash, [i as u8; 4]);
+        }
+
+        // Rewriting a cached node keeps its slot
+        cache.insert((0, 1), &[9; 4]);
+        cache.insert((4, 1), &[4; 4]);
+        assert!(!cache.contains(&(2, 1)));
+        assert!(cache.get_into(&(0, 1), &mut hash));
+        assert_eq!(hash, [9; 4]);
+
+        // Hashes of another length are neither stored nor returned
+        cache.insert((5, 1), &[5; 8]);
+        assert!(!cache.contains(&(5, 1)));
+        assert!(!cache.get_into(&(0, 1), &mut [0; 8]));
+
+        cache.clear();
+        assert!(!cache.contains(&(0, 1)));
+        cache.insert((6, 1), &[6; 4]);
+        assert!(cache.get_into(&(6, 1), &mut hash));
+
+        // A budget below one slot and its bookkeeping caches nothing
+        let empty = NodeCache::pool(HashPool::footprint(1, 4) - 1, 4);
+        empty.insert((0, 1), &[0; 4]);
+        assert!(!empty.contains(&(0, 1)));
+    }
+
+    #[test]
+    fn test_pool_budget_covers_bookkeeping() {
+        const HASH_LEN: usize = 32;
+        for max_bytes in [0, 1 << 10, 1 << 20, 100 << 20] {
+            let NodeCache::Pool(shards) = NodeCache::pool(max_bytes, HASH_LEN) else {
+                unreachable!()
+            };
+            let slots: usize = shards.iter().map(|s| s.lock().unwrap().capacity()).sum();
+            let footprint: u64 = shards
+                .iter()
+                .map(|s| HashPool::footprint(s.lock().unwrap().capacity(), HASH_LEN))
+                .sum();
+            assert!(footprint <= max_bytes);
+            assert!(slots as u64 * (HASH_LEN as u64) < max_bytes.max(1));
+        }
+
+        // Large pools are sharded
+        let NodeCache::Pool(shards) = NodeCache::pool(100 << 20, HASH_LEN) else {
+            unreachable!()
+        };
+        assert_eq!(shards.len(), POOL_SHARDS);
+    }
+
+    /// Resident memory of the process, from `/proc/self/statm`
+    fn resident_bytes() -> u64 {
+        let statm = std::fs::read_to_string("/proc/self/statm").unwrap();
+        let pages: u64 = statm.split_whitespace().nth(1).unwrap().parse().unwrap();
+        pages * 4096
+    }
+
+    /// Compares the memory both caches take to hold 1M SHA-256 node hashes.
+    /// Linux only; run on its own with
+    /// `cargo test --release -- --ignored bench_node_cache_memory --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_node_cache_memory() {
+        const NODES: u64 = 1 << 20;
+        const HASH_LEN: usize = 32;
+
+        for name in ["heap", "pool"] {
+            let before = resident_bytes();
+            let cache = match name {
+                "heap" => NodeCache::Heap(CacheBuilder::new(NODES as usize).build()),
+                _ => NodeCache::pool(HashPool::footprint(NODES as usize, HASH_LEN), HASH_LEN),
+            };
+            for idx in 0..NODES {
+                let mut hash = [0u8; HASH_LEN];
+                hash[..8].copy_from_slice(&idx.to_be_bytes());
+                cache.insert((idx, NODES), &hash);
+            }
+            let used = resident_bytes().saturating_sub(before);
+            println!(
+                "{}: {:.1} MiB for {} nodes, {} bytes per node",
+                name,
+                used as f64 / (1 << 20) as f64,
+                NODES,
+                used / NODES
+            );
+            assert!(cache.contains(&(NODES - 1, NODES)));
+        }
+    }
+}
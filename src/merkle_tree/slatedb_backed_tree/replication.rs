@@ -0,0 +1,466 @@
+//! Synchronous replication of tree writes to a standby.
+//!
+//! [`ReplicatedSlateDbBackedTree`] opens its tree on a database handle with a
+//! write log, so every batch an append writes is kept. Once the append
+//! returns, the batches are sent in order to a [`ReplicationSink`], and the
+//! caller only gets `Ok` after the sink has acknowledged all of them. A batch
+//! the sink refuses stays queued and is sent ahead of the next append's, so
+//! the standby never sees writes out of order.
+//!
+//! A standby is the same wrapper opened with `standby_mode`: it refuses
+//! appends and applies the batches given to [`ReplicatedSlateDbBackedTree::replicate`]
+//! as they were written on the primary, as long as every key is one the tree
+//! writes or lies under one of its data prefixes. Its tree serves reads as
+//! usual. To promote it, reopen it without `standby_mode`.
+//!
+//! The transport between the two is left to the embedder, which implements
+//! [`ReplicationSink`] on the primary and calls `replicate` on the standby.
+//! It must authenticate its peer: a standby applies whatever batches it is
+//! given. The log server does not use this wrapper, as `CtStorage` writes
+//! entries outside the tree that a standby would also need.
+
+use super::{SlateDbBackedTree, SlateDbTreeError};
+use crate::merkle_tree::ct_merkle_vendored::HashableLeaf;
+use crate::storage::{LoggedWrite, RateLimitedDb, StorageError};
+use digest::Digest;
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Keys the tree writes, relative to its database handle
+const TREE_KEYS: [&[u8]; 6] = [
+    super::META_KEY,
+    super::COMMITTED_SIZE_KEY,
+    super::LAST_PRECOMPUTED_SIZE_KEY,
+    super::PENDING_APPEND_KEY,
+    super::UNTAGGED_LEAVES_KEY,
+    super::UNFRAMED_LEAVES_KEY,
+];
+
+/// Prefixes of the keys the tree writes, relative to its database handle
+const TREE_KEY_PREFIXES: [&[u8]; 5] = [
+    super::LEAF_PREFIX,
+    super::VERSIONED_NODE_PREFIX,
+    super::NODE_LATEST_VERSION_PREFIX,
+    super::TILE_PREFIX,
+    super::LEAF_HASH_INDEX_PREFIX,
+];
+
+#[derive(Debug)]
+pub enum ReplicationError {
+    /// The local tree failed
+    Tree(SlateDbTreeError),
+    /// The standby could not be reached or did not apply the batch
+    Transport(String),
+    /// A replicated batch that does not decode
+    InvalidBatch(String),
+    /// An append on a standby, or a replicated batch sent to a primary
+    WrongMode(&'static str),
+}
+
+impl fmt::Display for ReplicationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            ReplicationError::Tree(e) => write!(f, "{}", e),
+            ReplicationError::Transport(e) => write!(f, "Replication failed: {}", e),
+            ReplicationError::InvalidBatch(e) => write!(f, "Invalid replicated batch: {}", e),
+            ReplicationError::WrongMode(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReplicationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReplicationError::Tree(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<SlateDbTreeError> for ReplicationError {
+    fn from(e: SlateDbTreeError) -> Self {
+        ReplicationError::Tree(e)
+    }
+}
+
+/// Where a primary sends its batches. `replicate` must only return `Ok` once
+/// the batch is applied on the standby.
+pub trait ReplicationSink: Send + Sync {
+    fn replicate(&self, batch: &[u8]) -> impl Future<Output = Result<(), ReplicationError>> + Send;
+}
+
+/// Sink of a standalone log, which has no standby
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopReplicationSink;
+
+impl ReplicationSink for NoopReplicationSink {
+    async fn replicate(&self, _batch: &[u8]) -> Result<(), ReplicationError> {
+        Ok(())
+    }
+}
+
+/// A [`SlateDbBackedTree`] whose appends are acknowledged only once a
+/// standby has applied them, or a standby applying a primary's appends
+pub struct ReplicatedSlateDbBackedTree<H, T, S>
+where
+    H: Digest,
+    T: HashableLeaf,
+{
+    tree: Arc<SlateDbBackedTree<H, T>>,
+    sink: S,
+    standby_mode: bool,
+    /// Encoded batches written locally but not yet acknowledged by the sink,
+    /// oldest first. Held across each append and its replication, so batches
+    /// reach the sink in the order they were written.
+    unreplicated: Mutex<VecDeque<Vec<u8>>>,
+    /// Prefixes, relative to the tree's handle, that `batch_push_with_data`
+    /// may write under besides the tree's own keys
+    data_prefixes: Vec<Vec<u8>>,
+}
+
+impl<H, T, S> ReplicatedSlateDbBackedTree<H, T, S>
+where
+    H: Digest + Send + Sync,
+    T: HashableLeaf + serde::Serialize + serde::de::DeserializeOwned + Send + Sync,
+    S: ReplicationSink,
+{
+    /// Open the tree in `db`. A primary replicates to `sink`; with
+    /// `standby_mode` the tree is read-only apart from [`Self::replicate`].
+    /// Both sides must start from the same state, such as empty databases
+    /// or the same snapshot.
+    pub async fn new(
+        db: RateLimitedDb,
+        sink: S,
+        standby_mode: bool,
+    ) -> Result<Self, SlateDbTreeError> {
+        let tree = SlateDbBackedTree::new(db.with_write_log()).await?;
+        // Opening the tree may initialize it, which the other side does alike
+        tree.db.take_logged_writes();
+        Ok(Self {
+            tree: Arc::new(tree),
+            sink,
+            standby_mode,
+            unreplicated: Mutex::new(VecDeque::new()),
+            data_prefixes: Vec::new(),
+        })
+    }
+
+    /// Let `batch_push_with_data` write keys under `prefixes`, relative to
+    /// the tree's handle. A standby must be given the same prefixes, as it
+    /// refuses batches with keys the tree does not write itself.
+    pub fn with_data_prefixes(mut self, prefixes: Vec<Vec<u8>>) -> Self {
+        self.data_prefixes = prefixes;
+        self
+    }
+
+    /// Whether `key`, relative to the tree's handle, is one a replicated
+    /// batch may write
+    fn is_replicated_key(&self, key: &[u8]) -> bool {
+        TREE_KEYS.contains(&key)
+            || TREE_KEY_PREFIXES
+                .iter()
+                .any(|prefix| key.starts_with(prefix))
+            || self
+                .data_prefixes
+                .iter()
+                .any(|prefix| key.starts_with(prefix))
+    }
+
+    /// The tree, for reads. Writes made on it directly are replicated along
+    /// with the next append made through this wrapper.
+    pub fn tree(&self) -> &Arc<SlateDbBackedTree<H, T>> {
+        &self.tree
+    }
+
+    pub fn is_standby(&self) -> bool {
+        self.standby_mode
+    }
+
+    /// Append one leaf, replicated before its index is returned
+    pub async fn push(&self, item: T) -> Result<u64, ReplicationError> {
+        self.replicated(|tree| async move { tree.push(item).await })
+            .await
+    }
+
+    /// [`SlateDbBackedTree::batch_push_with_data`], replicated before the
+    /// starting index is returned
+    pub async fn batch_push_with_data(
+        &self,
+        items: Vec<T>,
+        additional_data: Vec<(Vec<u8>, Vec<u8>)>,
+    ) -> Result<u64, ReplicationError> {
+        // Checked up front, as the standby would refuse the batch for good
+        if let Some((key, _)) = additional_data
+            .iter()
+            .find(|(key, _)| !self.is_replicated_key(key))
+        {
+            return Err(ReplicationError::InvalidBatch(format!(
+                "{} is outside the data prefixes",
+                hex::encode(key)
+            )));
+        }
+
+        self.replicated(
+            |tree| async move { tree.batch_push_with_data(items, additional_data).await },
+        )
+        .await
+    }
+
+    /// Run `write` on the tree, then send every batch it wrote, and any left
+    /// over from earlier, to the sink. Batches are sent even if `write`
+    /// failed part way, so the standby holds exactly what the primary does.
+    /// If the sink fails, the write stands locally but the caller gets the
+    /// error, as the standby does not have it yet.
+    async fn replicated<F, Fut, R>(&self, write: F) -> Result<R, ReplicationError>
+    where
+        F: FnOnce(Arc<SlateDbBackedTree<H, T>>) -> Fut,
+        Fut: Future<Output = Result<R, SlateDbTreeError>>,
+    {
+        if self.standby_mode {
+            return Err(ReplicationError::WrongMode(
+                "A standby tree only accepts replicated batches",
+            ));
+        }
+
+        let mut unreplicated = self.unreplicated.lock().await;
+        let result = write(self.tree.clone()).await;
+        for writes in self.tree.db.take_logged_writes() {
+            let batch = postcard::to_stdvec(&writes)
+                .map_err(|e| ReplicationError::InvalidBatch(e.to_string()))?;
+            unreplicated.push_back(batch);
+        }
+
+        while let Some(batch) = unreplicated.front() {
+            self.sink.replicate(batch).await?;
+            unreplicated.pop_front();
+        }
+        result.map_err(ReplicationError::from)
+    }
+
+    /// Apply a batch written by the primary. Only a standby accepts them.
+    pub async fn replicate(&self, batch: &[u8]) -> Result<(), ReplicationError> {
+        if !self.standby_mode {
+            return Err(ReplicationError::WrongMode(
+                "A primary tree does not accept replicated batches",
+            ));
+        }
+        let writes: Vec<LoggedWrite> = postcard::from_bytes(batch)
+            .map_err(|e| ReplicationError::InvalidBatch(e.to_string()))?;
+        for write in &writes {
+            let key = match write {
+                LoggedWrite::Put { key, .. } | LoggedWrite::Delete { key } => key,
+            };
+            match self.tree.db.relative_key(key) {
+                Some(key) if self.is_replicated_key(key) => {}
+                _ => {
+                    return Err(ReplicationError::InvalidBatch(format!(
+                        "{} is not a key of this tree",
+                        hex::encode(key)
+                    )))
+                }
+            }
+        }
+        self.tree
+            .db
+            .write_logged(&writes)
+            .await
+            .map_err(|e| match e {
+                StorageError::SlateDb(e) => ReplicationError::Tree(e.into()),
+                e => ReplicationError::InvalidBatch(e.to_string()),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::slatedb_backed_tree::tests::create_test_db;
+    use sha2::Sha256;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct TestLeaf {
+        data: Vec<u8>,
+    }
+
+    impl HashableLeaf for TestLeaf {
+        fn hash<H: digest::Update>(&self, hasher: &mut H) {
+            hasher.update(&self.data);
+        }
+    }
+
+    type Standby = ReplicatedSlateDbBackedTree<Sha256, TestLeaf, NoopReplicationSink>;
+
+    fn leaf(i: u64) -> TestLeaf {
+        TestLeaf {
+            data: i.to_be_bytes().to_vec(),
+        }
+    }
+
+    /// Applies batches to a standby in process, failing while `down` is set
+    struct FlakySink {
+        standby: Arc<Standby>,
+        down: AtomicBool,
+    }
+
+    impl ReplicationSink for FlakySink {
+        async fn replicate(&self, batch: &[u8]) -> Result<(), ReplicationError> {
+            if self.down.load(Ordering::SeqCst) {
+                return Err(ReplicationError::Transport("standby is down".into()));
+            }
+            self.standby.replicate(batch).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replication() {
+        let standby = Arc::new(
+            Standby::new(create_test_db().await, NoopReplicationSink, true)
+                .await
+                .unwrap()
+                .with_data_prefixes(vec![b"extra".to_vec()]),
+        );
+        let primary = ReplicatedSlateDbBackedTree::<Sha256, TestLeaf, _>::new(
+            create_test_db().await,
+            FlakySink {
+                standby: standby.clone(),
+                down: AtomicBool::new(false),
+            },
+            false,
+        )
+        .await
+        .unwrap()
+        .with_data_prefixes(vec![b"extra".to_vec()]);
+        let start = primary
+            .batch_push_with_data(
+                (0..300).map(leaf).collect(),
+                vec![(b"extra".to_vec(), b"data".to_vec())],
+            )
+            .await
+            .unwrap();
+        assert_eq!(start, 0);
+        assert_eq!(primary.push(leaf(300)).await.unwrap(), 300);
+
+        let standby_tree = standby.tree();
+        assert_eq!(standby_tree.len().await.unwrap(), 301);
+        assert_eq!(
+            standby_tree.root().await.unwrap().as_bytes(),
+            primary.tree().root().await.unwrap().as_bytes()
+        );
+        assert_eq!(standby_tree.get(123).await.unwrap(), Some(leaf(123)));
+        assert_eq!(
+            standby_tree.db.get(b"extra").await.unwrap().as_deref(),
+            Some(&b"data"[..])
+        );
+
+        assert!(matches!(
+            standby.push(leaf(0)).await,
+            Err(ReplicationError::WrongMode(_))
+        ));
+        assert!(matches!(
+            primary.replicate(&[]).await,
+            Err(ReplicationError::WrongMode(_))
+        ));
+        assert!(matches!(
+            standby.replicate(b"garbage").await,
+            Err(ReplicationError::InvalidBatch(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_keys_outside_the_tree_are_refused() {
+        let db = create_test_db().await;
+        let standby = Standby::new(db.with_key_prefix(b"tree/"), NoopReplicationSink, true)
+            .await
+            .unwrap();
+        let put = |key: &[u8]| {
+            postcard::to_stdvec(&vec![LoggedWrite::Put {
+                key: key.to_vec(),
+                value: b"value".to_vec(),
+            }])
+            .unwrap()
+        };
+
+        // Outside the tree's handle, and inside it but not a tree key
+        for key in [&b"leaf:00000000"[..], b"other/leaf:00000000", b"tree/extra"] {
+            assert!(matches!(
+                standby.replicate(&put(key)).await,
+                Err(ReplicationError::InvalidBatch(_))
+            ));
+            assert_eq!(db.get(key).await.unwrap(), None);
+        }
+        standby
+            .replicate(&put(b"tree/leaf:00000000"))
+            .await
+            .unwrap();
+
+        let primary = ReplicatedSlateDbBackedTree::<Sha256, TestLeaf, _>::new(
+            create_test_db().await,
+            NoopReplicationSink,
+            false,
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            primary
+                .batch_push_with_data(vec![leaf(0)], vec![(b"extra".to_vec(), vec![])])
+                .await,
+            Err(ReplicationError::InvalidBatch(_))
+        ));
+        assert_eq!(primary.tree().len().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_unacknowledged_batches_are_resent_in_order() {
+        let standby = Arc::new(
+            Standby::new(create_test_db().await, NoopReplicationSink, true)
+                .await
+                .unwrap(),
+        );
+        let primary = ReplicatedSlateDbBackedTree::<Sha256, TestLeaf, _>::new(
+            create_test_db().await,
+            FlakySink {
+                standby: standby.clone(),
+                down: AtomicBool::new(false),
+            },
+            false,
+        )
+        .await
+        .unwrap();
+
+        primary.push(leaf(0)).await.unwrap();
+        primary.sink.down.store(true, Ordering::SeqCst);
+        assert!(matches!(
+            primary.push(leaf(1)).await,
+            Err(ReplicationError::Transport(_))
+        ));
+        // The append stands on the primary, but not yet on the standby
+        assert_eq!(primary.tree().len().await.unwrap(), 2);
+        assert_eq!(standby.tree().len().await.unwrap(), 1);
+
+        primary.sink.down.store(false, Ordering::SeqCst);
+        assert_eq!(primary.push(leaf(2)).await.unwrap(), 2);
+        assert_eq!(standby.tree().len().await.unwrap(), 3);
+        assert_eq!(
+            standby.tree().root().await.unwrap().as_bytes(),
+            primary.tree().root().await.unwrap().as_bytes()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_standalone_tree() {
+        let tree = ReplicatedSlateDbBackedTree::<Sha256, TestLeaf, _>::new(
+            create_test_db().await,
+            NoopReplicationSink,
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(tree.push(leaf(0)).await.unwrap(), 0);
+        assert_eq!(tree.push(leaf(1)).await.unwrap(), 1);
+        assert!(tree.unreplicated.lock().await.is_empty());
+    }
+}
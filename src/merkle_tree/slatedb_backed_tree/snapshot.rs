@@ -0,0 +1,463 @@
+//! Point-in-time export of a tree's leaves and nodes to an object store, and
+//! the matching restore.
+//!
+//! A snapshot of size `n` holds every `leaf:` key below `n` and every `vnode:`
+//! key whose version is at most `n`. Neither is rewritten once stored, so the
+//! export runs alongside appends without taking the write lock. Version
+//! pointers and the leaf hash index are rebuilt on restore; tiles are not
+//! exported and are generated again on demand. Keys the storage layer writes
+//! next to the tree, such as entries, certificates and their indexes, are
+//! outside the snapshot and have to be backed up separately.
+
+use super::{
+    SlateDbBackedTree, SlateDbTreeError, COMMITTED_SIZE_KEY, LEAF_KEY_LEN, LEAF_PREFIX, META_KEY,
+    UNFRAMED_LEAVES_KEY, UNTAGGED_LEAVES_KEY, VERSIONED_NODE_KEY_LEN, VERSIONED_NODE_PREFIX,
+};
+use crate::merkle_tree::ct_merkle_vendored::{leaf_hash, root_idx, HashableLeaf};
+use crate::storage::RateLimitedDb;
+use digest::Digest;
+use object_store::{path::Path, ObjectStore, PutPayload};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::Arc;
+
+/// Leaves per chunk. Node chunks cover twice as many node indices, so both
+/// kinds hold a similar number of records.
+const LEAVES_PER_CHUNK: u64 = 4096;
+
+const MANIFEST_NAME: &str = "manifest.json";
+
+/// `manifest.json` at the root of a snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    pub tree_size: u64,
+    /// Hex root hash at `tree_size`
+    pub root_hash: String,
+    /// When the snapshot was taken, in milliseconds since the epoch
+    pub timestamp: u64,
+    /// In restore order: leaves by index, then nodes by index
+    pub chunks: Vec<SnapshotChunk>,
+    /// Leading leaves stored without an encoding tag. Snapshots from before
+    /// leaves were tagged leave it out, and all their leaves are untagged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub untagged_leaves: Option<u64>,
+    /// Leading leaves stored without a compression format byte. Snapshots
+    /// from before leaves were framed leave it out, and none of their leaves
+    /// are framed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unframed_leaves: Option<u64>,
+}
+
+/// One chunk file: big-endian `u32` key length, key, `u32` value length and
+/// value for each record, in key order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotChunk {
+    /// Object path relative to the snapshot path
+    pub name: String,
+    pub records: u64,
+    /// Hex SHA-256 of the chunk file
+    pub sha256: String,
+}
+
+fn object_store_error(e: object_store::Error) -> SlateDbTreeError {
+    SlateDbTreeError::DbError(Box::new(e))
+}
+
+fn encode_chunk(records: &[(bytes::Bytes, bytes::Bytes)]) -> Vec<u8> {
+    let mut chunk = Vec::new();
+    for (key, value) in records {
+        chunk.extend_from_slice(&(key.len() as u32).to_be_bytes());
+        chunk.extend_from_slice(key);
+        chunk.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        chunk.extend_from_slice(value);
+    }
+    chunk
+}
+
+/// Key and value of one chunk record
+type Record<'a> = (&'a [u8], &'a [u8]);
+
+fn decode_chunk(mut chunk: &[u8]) -> Result<Vec<Record<'_>>, SlateDbTreeError> {
+    fn field<'a>(chunk: &mut &'a [u8]) -> Result<&'a [u8], SlateDbTreeError> {
+        let truncated = || SlateDbTreeError::EncodingError("Truncated snapshot chunk".into());
+        let len_bytes: [u8; 4] = chunk
+            .get(..4)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(truncated)?;
+        let len = u32::from_be_bytes(len_bytes) as usize;
+        let value = chunk.get(4..4 + len).ok_or_else(truncated)?;
+        *chunk = &chunk[4 + len..];
+        Ok(value)
+    }
+
+    let mut records = Vec::new();
+    while !chunk.is_empty() {
+        let key = field(&mut chunk)?;
+        let value = field(&mut chunk)?;
+        records.push((key, value));
+    }
+    Ok(records)
+}
+
+/// Write `records` as chunk number `number` under `path`, unless there are none
+async fn put_chunk(
+    store: &dyn ObjectStore,
+    path: &str,
+    number: usize,
+    records: &[(bytes::Bytes, bytes::Bytes)],
+) -> Result<Option<SnapshotChunk>, SlateDbTreeError> {
+    if records.is_empty() {
+        return Ok(None);
+    }
+
+    let name = format!("chunk-{:08}.bin", number);
+    let chunk = encode_chunk(records);
+    let sha256 = hex::encode(Sha256::digest(&chunk));
+    store
+        .put(
+            &Path::from(format!("{}/{}", path, name)),
+            PutPayload::from(chunk),
+        )
+        .await
+        .map_err(object_store_error)?;
+
+    Ok(Some(SnapshotChunk {
+        name,
+        records: records.len() as u64,
+        sha256,
+    }))
+}
+
+/// (node index, version) of a `vnode:` key
+fn parse_versioned_node_key(key: &[u8]) -> Option<(u64, u64)> {
+    if key.len() != VERSIONED_NODE_KEY_LEN || !key.starts_with(VERSIONED_NODE_PREFIX) {
+        return None;
+    }
+    let key = &key[VERSIONED_NODE_PREFIX.len()..];
+    let index = u64::from_be_bytes(key[..8].try_into().ok()?);
+    let version = u64::from_be_bytes(key[9..].try_into().ok()?);
+    Some((index, version))
+}
+
+impl<H, T> SlateDbBackedTree<H, T>
+where
+    H: Digest,
+    T: HashableLeaf + serde::Serialize + serde::de::DeserializeOwned,
+{
+    /// Write a snapshot of the tree at its current size under `path` in
+    /// `store`: the chunk files first, then `manifest.json`, so a snapshot
+    /// without a manifest is incomplete.
+    pub async fn snapshot_to_object_store(
+        &self,
+        store: Arc<dyn ObjectStore>,
+        path: &str,
+    ) -> Result<SnapshotManifest, SlateDbTreeError> {
+        let snapshot_size = self.len().await?;
+        let timestamp = chrono::Utc::now().timestamp_millis() as u64;
+        let root_hash = if snapshot_size == 0 {
+            H::digest(b"")
+        } else {
+            self.get_node_hash_at_version(root_idx(snapshot_size).as_u64(), snapshot_size)
+                .await?
+        };
+
+        let mut chunks = Vec::new();
+
+        for start in (0..snapshot_size).step_by(LEAVES_PER_CHUNK as usize) {
+            let end = (start + LEAVES_PER_CHUNK).min(snapshot_size);
+            let records = self
+                .db
+                .scan(&Self::leaf_key(start), &Self::leaf_key(end))
+                .await?;
+            chunks.extend(put_chunk(store.as_ref(), path, chunks.len(), &records).await?);
+        }
+
+        // A tree of n leaves has 2n - 1 nodes. Each chunk covers whole node
+        // indices, so restore sees every version of a node together.
+        let node_count = (2 * snapshot_size).saturating_sub(1);
+        for start in (0..node_count).step_by(2 * LEAVES_PER_CHUNK as usize) {
+            let end = (start + 2 * LEAVES_PER_CHUNK).min(node_count);
+            let mut records = self
+                .db
+                .scan(
+                    &Self::versioned_node_key(start, 0),
+                    &Self::versioned_node_key(end, 0),
+                )
+                .await?;
+            // Versions above the snapshot size were appended after it started
+            records.retain(|(key, _)| {
+                parse_versioned_node_key(key).is_some_and(|(_, version)| version <= snapshot_size)
+            });
+            chunks.extend(put_chunk(store.as_ref(), path, chunks.len(), &records).await?);
+        }
+
+        let manifest = SnapshotManifest {
+            tree_size: snapshot_size,
+            root_hash: hex::encode(root_hash),
+            timestamp,
+            chunks,
+            untagged_leaves: Some(self.untagged_leaves.min(snapshot_size)),
+            unframed_leaves: Some(self.unframed_leaves.min(snapshot_size)),
+        };
+        let manifest_json = serde_json::to_vec_pretty(&manifest)
+            .map_err(|e| SlateDbTreeError::EncodingError(e.into()))?;
+        store
+            .put(
+                &Path::from(format!("{}/{}", path, MANIFEST_NAME)),
+                PutPayload::from(manifest_json),
+            )
+            .await
+            .map_err(object_store_error)?;
+
+        Ok(manifest)
+    }
+
+    /// Rebuild a tree in the empty database `db` from the snapshot under
+    /// `path`. Every chunk is checked against its manifest hash, and the
+    /// restored root against the manifest root.
+    pub async fn restore_from_object_store(
+        db: RateLimitedDb,
+        store: Arc<dyn ObjectStore>,
+        path: &str,
+    ) -> Result<Self, SlateDbTreeError> {
+        if db.get(META_KEY).await?.is_some() {
+            return Err(SlateDbTreeError::InconsistentState(
+                "Refusing to restore a snapshot over an existing tree".into(),
+            ));
+        }
+
+        let manifest_bytes = store
+            .get(&Path::from(format!("{}/{}", path, MANIFEST_NAME)))
+            .await
+            .map_err(object_store_error)?
+            .bytes()
+            .await
+            .map_err(object_store_error)?;
+        let manifest: SnapshotManifest = serde_json::from_slice(&manifest_bytes)
+            .map_err(|e| SlateDbTreeError::EncodingError(e.into()))?;
+        let untagged_leaves = manifest.untagged_leaves.unwrap_or(manifest.tree_size);
+        let unframed_leaves = manifest.unframed_leaves.unwrap_or(manifest.tree_size);
+
+        for chunk in &manifest.chunks {
+            let bytes = store
+                .get(&Path::from(format!("{}/{}", path, chunk.name)))
+                .await
+                .map_err(object_store_error)?
+                .bytes()
+                .await
+                .map_err(object_store_error)?;
+            if hex::encode(Sha256::digest(&bytes)) != chunk.sha256 {
+                return Err(SlateDbTreeError::InconsistentState(format!(
+                    "Snapshot chunk {} does not match its manifest hash",
+                    chunk.name
+                )));
+            }
+
+            let mut batch = db.batch();
+            let mut latest_version: Option<(u64, u64)> = None;
+            for (key, value) in decode_chunk(&bytes)? {
+                if let Some((index, version)) = parse_versioned_node_key(key) {
+                    // Keys are sorted, so the last version of a node wins
+                    if let Some((last_index, last_version)) = latest_version {
+                        if last_index != index {
+                            batch.put(
+                                Self::node_latest_version_key(last_index),
+                                last_version.to_be_bytes(),
+                            );
+                        }
+                    }
+                    latest_version = Some((index, version));
+                } else if key.len() == LEAF_KEY_LEN && key.starts_with(LEAF_PREFIX) {
+                    let index = u64::from_be_bytes(key[LEAF_PREFIX.len()..].try_into().unwrap());
+                    let leaf: T =
+                        Self::decode_leaf(untagged_leaves, unframed_leaves, index, value)?;
+                    batch.put(
+                        Self::leaf_hash_index_key(&leaf_hash::<H, _>(&leaf)),
+                        &key[LEAF_PREFIX.len()..],
+                    );
+                } else {
+                    return Err(SlateDbTreeError::EncodingError(
+                        format!("Unexpected key in snapshot chunk {}", chunk.name).into(),
+                    ));
+                }
+                batch.put(key, value);
+            }
+            if let Some((index, version)) = latest_version {
+                batch.put(Self::node_latest_version_key(index), version.to_be_bytes());
+            }
+            db.write_batch(batch).await?;
+        }
+
+        let mut batch = db.batch();
+        batch.put(META_KEY, manifest.tree_size.to_be_bytes());
+        batch.put(COMMITTED_SIZE_KEY, manifest.tree_size.to_be_bytes());
+        batch.put(UNTAGGED_LEAVES_KEY, untagged_leaves.to_be_bytes());
+        batch.put(UNFRAMED_LEAVES_KEY, unframed_leaves.to_be_bytes());
+        db.write_batch(batch).await?;
+
+        let tree = Self::new(db).await?;
+        let root = tree.root_at_size(manifest.tree_size).await?;
+        if hex::encode(root.as_bytes()) != manifest.root_hash {
+            return Err(SlateDbTreeError::InconsistentState(format!(
+                "Restored root at size {} does not match the snapshot manifest",
+                manifest.tree_size
+            )));
+        }
+
+        Ok(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::slatedb_backed_tree::tests::create_test_db;
+    use object_store::memory::InMemory;
+
+    #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct TestLeaf {
+        data: Vec<u8>,
+    }
+
+    impl HashableLeaf for TestLeaf {
+        fn hash<H: digest::Update>(&self, hasher: &mut H) {
+            hasher.update(&self.data);
+        }
+    }
+
+    fn leaves(range: std::ops::Range<u64>) -> Vec<TestLeaf> {
+        range
+            .map(|i| TestLeaf {
+                data: i.to_be_bytes().to_vec(),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_round_trip() {
+        let tree: SlateDbBackedTree<Sha256, TestLeaf> =
+            SlateDbBackedTree::new(create_test_db().await)
+                .await
+                .unwrap();
+        for batch in [0..3, 3..10, 10..5000] {
+            tree.batch_push_with_data(leaves(batch), vec![])
+                .await
+                .unwrap();
+        }
+        let root = tree.root().await.unwrap();
+
+        let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let manifest = tree
+            .snapshot_to_object_store(store.clone(), "backups/1")
+            .await
+            .unwrap();
+        assert_eq!(manifest.tree_size, 5000);
+        assert_eq!(manifest.root_hash, hex::encode(root.as_bytes()));
+        assert!(manifest.chunks.len() > 2);
+
+        // Appends after the snapshot stay out of it
+        tree.batch_push_with_data(leaves(5000..5010), vec![])
+            .await
+            .unwrap();
+
+        let restored: SlateDbBackedTree<Sha256, TestLeaf> =
+            SlateDbBackedTree::restore_from_object_store(
+                create_test_db().await,
+                store.clone(),
+                "backups/1",
+            )
+            .await
+            .unwrap();
+        assert_eq!(restored.len().await.unwrap(), 5000);
+        assert_eq!(restored.root().await.unwrap().as_bytes(), root.as_bytes());
+        assert_eq!(
+            restored.get(4999).await.unwrap(),
+            tree.get(4999).await.unwrap()
+        );
+        assert!(restored.get(5000).await.unwrap().is_none());
+        let hash = leaf_hash::<Sha256, _>(&tree.get(4321).await.unwrap().unwrap());
+        assert_eq!(
+            restored.lookup_by_leaf_hash(&hash).await.unwrap(),
+            Some(4321)
+        );
+
+        // Earlier published sizes and proofs survive the round trip
+        assert_eq!(
+            restored.root_at_size(10).await.unwrap().as_bytes(),
+            tree.root_at_size(10).await.unwrap().as_bytes()
+        );
+        let proof = restored.prove_inclusion(1234).await.unwrap();
+        assert_eq!(
+            proof.as_bytes(),
+            tree.prove_inclusion_at_size(1234, 5000)
+                .await
+                .unwrap()
+                .as_bytes()
+        );
+
+        // The restored tree keeps appending
+        restored
+            .batch_push_with_data(leaves(5000..5010), vec![])
+            .await
+            .unwrap();
+        assert_eq!(
+            restored.root().await.unwrap().as_bytes(),
+            tree.root().await.unwrap().as_bytes()
+        );
+
+        // Not over an existing tree
+        assert!(
+            SlateDbBackedTree::<Sha256, TestLeaf>::restore_from_object_store(
+                restored.db.clone(),
+                store,
+                "backups/1",
+            )
+            .await
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restore_rejects_tampered_chunk() {
+        let tree: SlateDbBackedTree<Sha256, TestLeaf> =
+            SlateDbBackedTree::new(create_test_db().await)
+                .await
+                .unwrap();
+        tree.batch_push_with_data(leaves(0..10), vec![])
+            .await
+            .unwrap();
+
+        let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let manifest = tree
+            .snapshot_to_object_store(store.clone(), "snap")
+            .await
+            .unwrap();
+
+        let chunk_path = Path::from(format!("snap/{}", manifest.chunks[0].name));
+        let mut chunk = store
+            .get(&chunk_path)
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap()
+            .to_vec();
+        let last = chunk.len() - 1;
+        chunk[last] ^= 1;
+        store
+            .put(&chunk_path, PutPayload::from(chunk))
+            .await
+            .unwrap();
+
+        let result = SlateDbBackedTree::<Sha256, TestLeaf>::restore_from_object_store(
+            create_test_db().await,
+            store,
+            "snap",
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(SlateDbTreeError::InconsistentState(_))
+        ));
+    }
+}
@@ -1,175 +1,580 @@
 use lazy_static::lazy_static;
 use prometheus::{
-    register_gauge, register_gauge_vec, register_histogram_vec, register_int_counter_vec,
-    register_int_gauge, Gauge, GaugeVec, HistogramVec, IntCounterVec, IntGauge,
+    core::Collector,
+    proto::{LabelPair, MetricFamily},
+    Encoder, Gauge, GaugeVec, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry, TextEncoder,
 };
+use std::collections::{btree_map::Entry, BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Every metric the log reports, registered in one registry. Each log can
+/// have its own, so several logs in one process neither collide on
+/// registration nor share counts; [`global`] is the instance in the default
+/// registry, used when a log is not given one.
+pub struct Metrics {
+    registry: Registry,
+    pub http_requests_total: IntCounterVec,
+    pub http_request_duration_seconds: HistogramVec,
+    pub http_errors_total: IntCounterVec,
+    pub certificate_submissions_total: IntCounterVec,
+    pub storage_queue_depth: IntGauge,
+    pub storage_queue_capacity: IntGauge,
+    pub storage_batches_flushed: IntCounterVec,
+    pub storage_entries_processed: IntCounterVec,
+    pub storage_flush_duration_seconds: HistogramVec,
+    pub merkle_tree_size: IntGauge,
+    pub deduplicated_certificates: IntCounterVec,
+    pub cache_hits: IntCounterVec,
+    pub cache_misses: IntCounterVec,
+    pub active_connections: Gauge,
+    pub in_flight_requests: IntGaugeVec,
+    pub shed_requests_total: IntCounterVec,
+    pub certificate_chain_length: HistogramVec,
+    pub validation_duration_seconds: HistogramVec,
+    pub validation_queue_depth: IntGauge,
+    pub validation_warnings_total: IntCounterVec,
+    pub get_entries_requests: IntCounterVec,
+    pub get_entries_batch_size: HistogramVec,
+    pub inclusion_proof_requests: IntCounterVec,
+    pub consistency_proof_requests: IntCounterVec,
+    pub consistency_cache_hits: IntCounter,
+    pub consistency_cache_misses: IntCounter,
+    pub health_check_status: GaugeVec,
+    pub static_ct_checkpoint_requests: IntCounterVec,
+    pub static_ct_tile_requests: IntCounterVec,
+    pub static_ct_issuer_requests: IntCounterVec,
+    pub static_ct_tile_size_bytes: HistogramVec,
+    pub gossip_inconsistency_total: IntCounter,
+    pub rejected_submissions_total: IntCounterVec,
+    pub add_chain_rejections_total: IntCounterVec,
+    pub sth_timestamp_seconds: Gauge,
+    pub node_cache_warm_nodes: IntGauge,
+    pub node_cache_warm_duration_seconds: Gauge,
+    pub node_recomputations_total: IntCounter,
+    pub signature_cache_entries: IntGauge,
+    pub log_entries_total: IntCounterVec,
+    /// Samples of `log_entries_total` summed over types, for windowed counts
+    pub log_entries_history: Mutex<CounterHistory>,
+}
 
 lazy_static! {
-    pub static ref HTTP_REQUESTS_TOTAL: IntCounterVec = register_int_counter_vec!(
-        "http_requests_total",
-        "Total number of HTTP requests",
-        &["endpoint", "method", "status"]
-    )
-    .unwrap();
-    pub static ref HTTP_REQUEST_DURATION_SECONDS: HistogramVec = register_histogram_vec!(
-        "http_request_duration_seconds",
-        "HTTP request duration in seconds",
-        &["endpoint", "method"],
-        vec![0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0]
-    )
-    .unwrap();
-    pub static ref CERTIFICATE_SUBMISSIONS_TOTAL: IntCounterVec = register_int_counter_vec!(
-        "certificate_submissions_total",
-        "Total number of certificate submissions",
-        &["type", "status"]
-    )
-    .unwrap();
-    pub static ref STORAGE_QUEUE_DEPTH: IntGauge =
-        register_int_gauge!("storage_queue_depth", "Current depth of the storage queue").unwrap();
-    pub static ref STORAGE_QUEUE_CAPACITY: IntGauge = register_int_gauge!(
-        "storage_queue_capacity",
-        "Maximum capacity of the storage queue"
-    )
-    .unwrap();
-    pub static ref STORAGE_BATCHES_FLUSHED: IntCounterVec = register_int_counter_vec!(
-        "storage_batches_flushed_total",
-        "Total number of storage batches flushed",
-        &["status"]
-    )
-    .unwrap();
-    pub static ref STORAGE_ENTRIES_PROCESSED: IntCounterVec = register_int_counter_vec!(
-        "storage_entries_processed_total",
-        "Total number of entries processed by storage",
-        &["type"]
-    )
-    .unwrap();
-    pub static ref STORAGE_FLUSH_DURATION_SECONDS: HistogramVec = register_histogram_vec!(
-        "storage_flush_duration_seconds",
-        "Duration of storage flush operations in seconds",
-        &["status"],
-        vec![0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0]
-    )
-    .unwrap();
-    pub static ref MERKLE_TREE_SIZE: IntGauge = register_int_gauge!(
-        "merkle_tree_size",
-        "Current size of the Merkle tree (number of entries)"
-    )
-    .unwrap();
-    pub static ref DEDUPLICATED_CERTIFICATES: IntCounterVec = register_int_counter_vec!(
-        "deduplicated_certificates_total",
-        "Total number of deduplicated certificates",
-        &["type"]
-    )
-    .unwrap();
-    pub static ref CACHE_HITS: IntCounterVec = register_int_counter_vec!(
-        "cache_hits_total",
-        "Total number of cache hits",
-        &["cache_type"]
-    )
-    .unwrap();
-    pub static ref CACHE_MISSES: IntCounterVec = register_int_counter_vec!(
-        "cache_misses_total",
-        "Total number of cache misses",
-        &["cache_type"]
-    )
-    .unwrap();
-    pub static ref ACTIVE_CONNECTIONS: Gauge =
-        register_gauge!("active_connections", "Number of active HTTP connections").unwrap();
-    pub static ref CERTIFICATE_CHAIN_LENGTH: HistogramVec = register_histogram_vec!(
-        "certificate_chain_length",
-        "Length of certificate chains submitted",
-        &["type"],
-        vec![1.0, 2.0, 3.0, 4.0, 5.0, 10.0]
-    )
-    .unwrap();
-    pub static ref VALIDATION_DURATION_SECONDS: HistogramVec = register_histogram_vec!(
-        "validation_duration_seconds",
-        "Duration of certificate validation in seconds",
-        &["type"],
-        vec![0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0]
-    )
-    .unwrap();
-    pub static ref GET_ENTRIES_REQUESTS: IntCounterVec = register_int_counter_vec!(
-        "get_entries_requests_total",
-        "Total number of get-entries requests",
-        &["status"]
-    )
-    .unwrap();
-    pub static ref GET_ENTRIES_BATCH_SIZE: HistogramVec = register_histogram_vec!(
-        "get_entries_batch_size",
-        "Number of entries requested in get-entries calls",
-        &[],
-        vec![1.0, 10.0, 50.0, 100.0, 250.0, 500.0, 1000.0]
-    )
-    .unwrap();
-    pub static ref INCLUSION_PROOF_REQUESTS: IntCounterVec = register_int_counter_vec!(
-        "inclusion_proof_requests_total",
-        "Total number of inclusion proof requests",
-        &["status"]
-    )
-    .unwrap();
-    pub static ref CONSISTENCY_PROOF_REQUESTS: IntCounterVec = register_int_counter_vec!(
-        "consistency_proof_requests_total",
-        "Total number of consistency proof requests",
-        &["status"]
-    )
-    .unwrap();
-    pub static ref HEALTH_CHECK_STATUS: GaugeVec = register_gauge_vec!(
-        "health_check_status",
-        "Health check status (1 = healthy, 0 = unhealthy)",
-        &["component"]
-    )
-    .unwrap();
-    pub static ref STATIC_CT_CHECKPOINT_REQUESTS: IntCounterVec = register_int_counter_vec!(
-        "static_ct_checkpoint_requests_total",
-        "Total number of static CT checkpoint requests",
-        &["status"]
-    )
-    .unwrap();
-    pub static ref STATIC_CT_TILE_REQUESTS: IntCounterVec = register_int_counter_vec!(
-        "static_ct_tile_requests_total",
-        "Total number of static CT tile requests",
-        &["tile_type", "status"]
-    )
-    .unwrap();
-    pub static ref STATIC_CT_ISSUER_REQUESTS: IntCounterVec = register_int_counter_vec!(
-        "static_ct_issuer_requests_total",
-        "Total number of static CT issuer lookup requests",
-        &["status"]
-    )
-    .unwrap();
-    pub static ref STATIC_CT_TILE_SIZE_BYTES: HistogramVec = register_histogram_vec!(
-        "static_ct_tile_size_bytes",
-        "Size of static CT tiles in bytes",
-        &["tile_type"],
-        vec![1024.0, 4096.0, 16384.0, 65536.0, 262144.0, 1048576.0]
-    )
-    .unwrap();
+    static ref GLOBAL: Arc<Metrics> =
+        Arc::new(Metrics::in_registry(prometheus::default_registry().clone()));
+}
+
+/// Metrics in the default registry, which also holds the process collector
+pub fn global() -> Arc<Metrics> {
+    GLOBAL.clone()
+}
+
+fn register<C: Collector + Clone + 'static>(
+    registry: &Registry,
+    collector: prometheus::Result<C>,
+) -> C {
+    let collector = collector.unwrap();
+    registry.register(Box::new(collector.clone())).unwrap();
+    collector
+}
+
+impl Metrics {
+    /// Metrics in a registry of their own
+    pub fn new() -> Self {
+        Self::in_registry(Registry::new())
+    }
+
+    fn in_registry(registry: Registry) -> Self {
+        Self {
+            http_requests_total: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new("http_requests_total", "Total number of HTTP requests"),
+                    &["shard", "endpoint", "method", "status"],
+                ),
+            ),
+            http_errors_total: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "ct_http_errors_total",
+                        "Error responses from every endpoint, by error code",
+                    ),
+                    &["code"],
+                ),
+            ),
+            http_request_duration_seconds: register(
+                &registry,
+                HistogramVec::new(
+                    HistogramOpts::new(
+                        "http_request_duration_seconds",
+                        "HTTP request duration in seconds",
+                    )
+                    .buckets(vec![
+                        0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+                    ]),
+                    &["shard", "endpoint", "method"],
+                ),
+            ),
+            certificate_submissions_total: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "certificate_submissions_total",
+                        "Total number of certificate submissions",
+                    ),
+                    &["type", "status"],
+                ),
+            ),
+            storage_queue_depth: register(
+                &registry,
+                IntGauge::new("storage_queue_depth", "Current depth of the storage queue"),
+            ),
+            storage_queue_capacity: register(
+                &registry,
+                IntGauge::new(
+                    "storage_queue_capacity",
+                    "Maximum capacity of the storage queue",
+                ),
+            ),
+            storage_batches_flushed: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "storage_batches_flushed_total",
+                        "Total number of storage batches flushed",
+                    ),
+                    &["status"],
+                ),
+            ),
+            storage_entries_processed: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "storage_entries_processed_total",
+                        "Total number of entries processed by storage",
+                    ),
+                    &["type"],
+                ),
+            ),
+            storage_flush_duration_seconds: register(
+                &registry,
+                HistogramVec::new(
+                    HistogramOpts::new(
+                        "storage_flush_duration_seconds",
+                        "Duration of storage flush operations in seconds",
+                    )
+                    .buckets(vec![0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0]),
+                    &["status"],
+                ),
+            ),
+            merkle_tree_size: register(
+                &registry,
+                IntGauge::new(
+                    "merkle_tree_size",
+                    "Current size of the Merkle tree (number of entries)",
+                ),
+            ),
+            deduplicated_certificates: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "deduplicated_certificates_total",
+                        "Total number of deduplicated certificates",
+                    ),
+                    &["type"],
+                ),
+            ),
+            cache_hits: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new("cache_hits_total", "Total number of cache hits"),
+                    &["cache_type"],
+                ),
+            ),
+            cache_misses: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new("cache_misses_total", "Total number of cache misses"),
+                    &["cache_type"],
+                ),
+            ),
+            active_connections: register(
+                &registry,
+                Gauge::new("active_connections", "Number of active HTTP connections"),
+            ),
+            in_flight_requests: register(
+                &registry,
+                IntGaugeVec::new(
+                    Opts::new(
+                        "ct_in_flight_requests",
+                        "Requests being served, by route class (read or submission)",
+                    ),
+                    &["class"],
+                ),
+            ),
+            shed_requests_total: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "ct_shed_requests_total",
+                        "Requests turned away with 503 at the route class concurrency limit",
+                    ),
+                    &["class"],
+                ),
+            ),
+            certificate_chain_length: register(
+                &registry,
+                HistogramVec::new(
+                    HistogramOpts::new(
+                        "certificate_chain_length",
+                        "Length of certificate chains submitted",
+                    )
+                    .buckets(vec![1.0, 2.0, 3.0, 4.0, 5.0, 10.0]),
+                    &["type"],
+                ),
+            ),
+            validation_duration_seconds: register(
+                &registry,
+                HistogramVec::new(
+                    HistogramOpts::new(
+                        "validation_duration_seconds",
+                        "Duration of certificate validation in seconds",
+                    )
+                    .buckets(vec![0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0]),
+                    &["type"],
+                ),
+            ),
+            validation_queue_depth: register(
+                &registry,
+                IntGauge::new(
+                    "ct_validation_queue_depth",
+                    "Number of chain validations currently pending or running",
+                ),
+            ),
+            validation_warnings_total: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "ct_validation_warnings_total",
+                        "Checks failed by chains accepted in permissive validation mode, by reason",
+                    ),
+                    &["reason"],
+                ),
+            ),
+            get_entries_requests: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "get_entries_requests_total",
+                        "Total number of get-entries requests",
+                    ),
+                    &["status"],
+                ),
+            ),
+            get_entries_batch_size: register(
+                &registry,
+                HistogramVec::new(
+                    HistogramOpts::new(
+                        "get_entries_batch_size",
+                        "Number of entries requested in get-entries calls",
+                    )
+                    .buckets(vec![1.0, 10.0, 50.0, 100.0, 250.0, 500.0, 1000.0]),
+                    &[],
+                ),
+            ),
+            inclusion_proof_requests: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "inclusion_proof_requests_total",
+                        "Total number of inclusion proof requests",
+                    ),
+                    &["status"],
+                ),
+            ),
+            consistency_proof_requests: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "consistency_proof_requests_total",
+                        "Total number of consistency proof requests",
+                    ),
+                    &["status"],
+                ),
+            ),
+            consistency_cache_hits: register(
+                &registry,
+                IntCounter::new(
+                    "ct_consistency_cache_hits_total",
+                    "get-sth-consistency responses served from the proof cache",
+                ),
+            ),
+            consistency_cache_misses: register(
+                &registry,
+                IntCounter::new(
+                    "ct_consistency_cache_misses_total",
+                    "get-sth-consistency requests not found in the proof cache",
+                ),
+            ),
+            health_check_status: register(
+                &registry,
+                GaugeVec::new(
+                    Opts::new(
+                        "health_check_status",
+                        "Health check status (1 = healthy, 0 = unhealthy)",
+                    ),
+                    &["component"],
+                ),
+            ),
+            static_ct_checkpoint_requests: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "static_ct_checkpoint_requests_total",
+                        "Total number of static CT checkpoint requests",
+                    ),
+                    &["status"],
+                ),
+            ),
+            static_ct_tile_requests: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "static_ct_tile_requests_total",
+                        "Total number of static CT tile requests",
+                    ),
+                    &["tile_type", "status"],
+                ),
+            ),
+            static_ct_issuer_requests: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "static_ct_issuer_requests_total",
+                        "Total number of static CT issuer lookup requests",
+                    ),
+                    &["status"],
+                ),
+            ),
+            static_ct_tile_size_bytes: register(
+                &registry,
+                HistogramVec::new(
+                    HistogramOpts::new(
+                        "static_ct_tile_size_bytes",
+                        "Size of static CT tiles in bytes",
+                    )
+                    .buckets(vec![1024.0, 4096.0, 16384.0, 65536.0, 262144.0, 1048576.0]),
+                    &["tile_type"],
+                ),
+            ),
+            gossip_inconsistency_total: register(
+                &registry,
+                IntCounter::new(
+                    "ct_gossip_inconsistency_total",
+                    "Number of gossiped STHs that were inconsistent with the local tree",
+                ),
+            ),
+            rejected_submissions_total: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "ct_rejected_submissions_total",
+                        "Submissions that failed chain validation, by reason",
+                    ),
+                    &["reason"],
+                ),
+            ),
+            add_chain_rejections_total: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "ct_add_chain_rejections_total",
+                        "Submissions that failed chain validation, by the check they failed",
+                    ),
+                    &["reason"],
+                ),
+            ),
+            sth_timestamp_seconds: register(
+                &registry,
+                Gauge::new(
+                    "ct_sth_timestamp_seconds",
+                    "Timestamp of the most recently signed STH",
+                ),
+            ),
+            node_cache_warm_nodes: register(
+                &registry,
+                IntGauge::new(
+                    "ct_node_cache_warm_nodes",
+                    "Upper tree nodes loaded into the node cache on startup",
+                ),
+            ),
+            node_cache_warm_duration_seconds: register(
+                &registry,
+                Gauge::new(
+                    "ct_node_cache_warm_duration_seconds",
+                    "Time taken to warm the node cache on startup",
+                ),
+            ),
+            node_recomputations_total: register(
+                &registry,
+                IntCounter::new(
+                    "ct_node_recomputations_total",
+                    "Tree nodes recomputed from their leaves because no record of their hash at the version read was stored",
+                ),
+            ),
+            signature_cache_entries: register(
+                &registry,
+                IntGauge::new(
+                    "ct_signature_cache_entries",
+                    "Verified certificate signatures held in the validator's cache",
+                ),
+            ),
+            log_entries_total: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "ct_log_entries_total",
+                        "Entries appended to the log since the process started",
+                    ),
+                    &["type"],
+                ),
+            ),
+            log_entries_history: Mutex::new(CounterHistory::new(
+                LOG_ENTRIES_HISTORY_RETENTION_SECS,
+            )),
+            registry,
+        }
+    }
+
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// This instance's metrics in the Prometheus text format
+    pub fn encode(&self) -> prometheus::Result<String> {
+        encode(&self.registry.gather())
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Metric families of several logs, each sample labelled `log` with the name
+/// it is given. Families with the same name are merged, since the text format
+/// allows each name only once.
+pub fn gather_labelled<'a>(
+    instances: impl IntoIterator<Item = (&'a str, &'a Metrics)>,
+) -> Vec<MetricFamily> {
+    let mut families: BTreeMap<String, MetricFamily> = BTreeMap::new();
+    for (log, metrics) in instances {
+        for mut family in metrics.registry.gather() {
+            for metric in family.mut_metric() {
+                let mut labels = metric.take_label();
+                let mut label = LabelPair::new();
+                label.set_name("log".to_string());
+                label.set_value(log.to_string());
+                labels.push(label);
+                metric.set_label(labels);
+            }
+            match families.entry(family.name().to_string()) {
+                Entry::Vacant(entry) => {
+                    entry.insert(family);
+                }
+                Entry::Occupied(mut entry) => {
+                    entry.get_mut().mut_metric().extend(family.take_metric());
+                }
+            }
+        }
+    }
+    families.into_values().collect()
 }
 
+pub fn encode(families: &[MetricFamily]) -> prometheus::Result<String> {
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(families, &mut buffer)?;
+    String::from_utf8(buffer).map_err(|e| prometheus::Error::Msg(e.to_string()))
+}
+
+const LOG_ENTRIES_HISTORY_RETENTION_SECS: i64 = 24 * 60 * 60;
+
+/// Recent values of a counter, one sample per minute, recorded whenever the
+/// counter changes. Lets `/stats` report increases over the last hour or day
+/// from the counter itself rather than from a time-series store.
+#[derive(Debug)]
+pub struct CounterHistory {
+    retention_secs: i64,
+    /// (start of minute in Unix seconds, counter value at the end of it)
+    samples: VecDeque<(i64, u64)>,
+}
+
+impl CounterHistory {
+    pub fn new(retention_secs: i64) -> Self {
+        Self {
+            retention_secs,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Record that the counter read `value` at `now` (Unix seconds)
+    pub fn record(&mut self, now: i64, value: u64) {
+        let minute = now - now.rem_euclid(60);
+        match self.samples.back_mut() {
+            Some((last, last_value)) if *last == minute => *last_value = value,
+            _ => self.samples.push_back((minute, value)),
+        }
+
+        // Keep one sample from before the window as the baseline for it
+        while self.samples.len() > 1 && self.samples[1].0 < now - self.retention_secs {
+            self.samples.pop_front();
+        }
+    }
+
+    /// How much the counter grew from `since` to `current`, to the minute.
+    /// A counter with no sample before `since` started from zero in the window.
+    pub fn increase_since(&self, since: i64, current: u64) -> u64 {
+        let since_minute = since - since.rem_euclid(60);
+        let baseline = self
+            .samples
+            .iter()
+            .rev()
+            .find(|(minute, _)| *minute < since_minute)
+            .map_or(0, |(_, value)| *value);
+        current.saturating_sub(baseline)
+    }
+}
+
+/// Register the global metrics up front, so `/metrics` lists them all from
+/// the first scrape
 pub fn init_metrics() {
-    lazy_static::initialize(&HTTP_REQUESTS_TOTAL);
-    lazy_static::initialize(&HTTP_REQUEST_DURATION_SECONDS);
-    lazy_static::initialize(&CERTIFICATE_SUBMISSIONS_TOTAL);
-    lazy_static::initialize(&STORAGE_QUEUE_DEPTH);
-    lazy_static::initialize(&STORAGE_QUEUE_CAPACITY);
-    lazy_static::initialize(&STORAGE_BATCHES_FLUSHED);
-    lazy_static::initialize(&STORAGE_ENTRIES_PROCESSED);
-    lazy_static::initialize(&STORAGE_FLUSH_DURATION_SECONDS);
-    lazy_static::initialize(&MERKLE_TREE_SIZE);
-    lazy_static::initialize(&DEDUPLICATED_CERTIFICATES);
-    lazy_static::initialize(&CACHE_HITS);
-    lazy_static::initialize(&CACHE_MISSES);
-    lazy_static::initialize(&ACTIVE_CONNECTIONS);
-    lazy_static::initialize(&CERTIFICATE_CHAIN_LENGTH);
-    lazy_static::initialize(&VALIDATION_DURATION_SECONDS);
-    lazy_static::initialize(&GET_ENTRIES_REQUESTS);
-    lazy_static::initialize(&GET_ENTRIES_BATCH_SIZE);
-    lazy_static::initialize(&INCLUSION_PROOF_REQUESTS);
-    lazy_static::initialize(&CONSISTENCY_PROOF_REQUESTS);
-    lazy_static::initialize(&HEALTH_CHECK_STATUS);
-    lazy_static::initialize(&STATIC_CT_CHECKPOINT_REQUESTS);
-    lazy_static::initialize(&STATIC_CT_TILE_REQUESTS);
-    lazy_static::initialize(&STATIC_CT_ISSUER_REQUESTS);
-    lazy_static::initialize(&STATIC_CT_TILE_SIZE_BYTES);
+    lazy_static::initialize(&GLOBAL);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_history() {
+        let start = 1_000_020;
+        let mut history = CounterHistory::new(3600);
+        assert_eq!(history.increase_since(start - 3600, 0), 0);
+
+        history.record(start, 5);
+        history.record(start + 30, 7);
+        history.record(start + 600, 10);
+        assert_eq!(history.samples.len(), 2);
+
+        // Everything since the process started counts
+        assert_eq!(history.increase_since(start - 3600, 10), 10);
+        assert_eq!(history.increase_since(start + 300, 10), 3);
+        assert_eq!(history.increase_since(start + 700, 12), 2);
+
+        // Old samples are dropped, keeping one baseline from before the window
+        history.record(start + 3600 + 700, 15);
+        assert_eq!(history.samples.len(), 2);
+        assert_eq!(history.increase_since(start + 3600 + 60, 15), 5);
+    }
 }
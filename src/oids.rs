@@ -36,6 +36,31 @@ pub const SHA512_WITH_RSA_ENCRYPTION_OID: ObjectIdentifier =
 pub const SHA1_WITH_RSA_ENCRYPTION_OID: ObjectIdentifier =
     ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.5");
 
+pub const EC_PUBLIC_KEY_OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+
+pub const SECP256R1_OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.3.1.7");
+
+pub const SECP384R1_OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.132.0.34");
+
+pub const RSA_ENCRYPTION_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+
+/// Conventional name of a certificate signature algorithm OID
+pub fn signature_algorithm_name(oid: &str) -> Option<&'static str> {
+    [
+        (ECDSA_WITH_SHA256_OID, "ecdsa-with-SHA256"),
+        (ECDSA_WITH_SHA384_OID, "ecdsa-with-SHA384"),
+        (ECDSA_WITH_SHA512_OID, "ecdsa-with-SHA512"),
+        (SHA256_WITH_RSA_ENCRYPTION_OID, "sha256WithRSAEncryption"),
+        (SHA384_WITH_RSA_ENCRYPTION_OID, "sha384WithRSAEncryption"),
+        (SHA512_WITH_RSA_ENCRYPTION_OID, "sha512WithRSAEncryption"),
+        (SHA1_WITH_RSA_ENCRYPTION_OID, "sha1WithRSAEncryption"),
+    ]
+    .into_iter()
+    .find(|(known, _)| known.to_string() == oid)
+    .map(|(_, name)| name)
+}
+
 pub const ASN1_NULL: &[u8] = &[0x05, 0x00];
 
 #[cfg(test)]
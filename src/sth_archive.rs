@@ -0,0 +1,390 @@
+//! Record of every tree head the log signs, published to the object store
+//! so that gossip and forensics do not have to trust the HTTP API.
+//!
+//! Each STH is written once to `{prefix}/{tree_size}` as a single line of
+//! timestamp, tree size, hex root hash and base64 tree head signature,
+//! separated by spaces. Objects are created with a conditional put, so a tree
+//! size already published is never overwritten, whichever instance signed it
+//! first. [`verify_archive`] reads the series back and checks that each entry
+//! is consistent with the one before it.
+
+use crate::merkle_storage::StorageBackedMerkleTree;
+use crate::merkle_tree::RootHash;
+use crate::types::tree_head::{SignedTreeHead, SthResponse};
+use crate::types::{CtError, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use futures::TryStreamExt;
+use object_store::{path::Path, ObjectStore, PutMode, PutOptions, PutPayload};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
+
+pub const DEFAULT_STH_ARCHIVE_PREFIX: &str = "sth";
+
+fn archive_error(e: object_store::Error) -> CtError {
+    CtError::Internal(format!("STH archive: {}", e))
+}
+
+/// The archive line for `sth`, newline included
+pub fn format_entry(sth: &SignedTreeHead) -> String {
+    format!(
+        "{} {} {} {}\n",
+        sth.timestamp,
+        sth.tree_size,
+        hex::encode(&sth.root_hash),
+        sth.to_api_response().tree_head_signature
+    )
+}
+
+/// Parse an archive line. The signature is not checked.
+pub fn parse_entry(line: &str) -> Result<SignedTreeHead> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let [timestamp, tree_size, root_hash, signature] = fields[..] else {
+        return Err(CtError::BadRequest(format!(
+            "Archive entry has {} fields, expected 4",
+            fields.len()
+        )));
+    };
+    let parse_u64 = |field: &str, name: &str| {
+        field
+            .parse::<u64>()
+            .map_err(|_| CtError::BadRequest(format!("Invalid {} '{}'", name, field)))
+    };
+    let root_hash = hex::decode(root_hash)
+        .map_err(|_| CtError::BadRequest(format!("Invalid root hash '{}'", root_hash)))?;
+
+    SignedTreeHead::from_api_response(&SthResponse {
+        tree_size: parse_u64(tree_size, "tree size")?,
+        timestamp: parse_u64(timestamp, "timestamp")?,
+        sha256_root_hash: STANDARD.encode(root_hash),
+        tree_head_signature: signature.to_string(),
+    })
+}
+
+/// Signed tree heads published under one prefix of an object store
+pub struct SthArchive {
+    store: Arc<dyn ObjectStore>,
+    prefix: Path,
+    /// Largest tree size known to be published, to skip re-signed heads
+    published_size: Mutex<Option<u64>>,
+}
+
+impl SthArchive {
+    pub fn new(store: Arc<dyn ObjectStore>, prefix: &str) -> Self {
+        Self {
+            store,
+            prefix: Path::from(prefix),
+            published_size: Mutex::new(None),
+        }
+    }
+
+    fn entry_path(&self, tree_size: u64) -> Path {
+        self.prefix.child(tree_size.to_string())
+    }
+
+    /// Publish `sth` unless its tree size already has an entry. Returns
+    /// whether this call wrote it.
+    pub async fn publish(&self, sth: &SignedTreeHead) -> Result<bool> {
+        if self
+            .published_size
+            .lock()
+            .unwrap()
+            .is_some_and(|size| size >= sth.tree_size)
+        {
+            return Ok(false);
+        }
+
+        let written = match self
+            .store
+            .put_opts(
+                &self.entry_path(sth.tree_size),
+                PutPayload::from(format_entry(sth)),
+                PutOptions {
+                    mode: PutMode::Create,
+                    ..Default::default()
+                },
+            )
+            .await
+        {
+            Ok(_) => true,
+            Err(object_store::Error::AlreadyExists { .. }) => false,
+            Err(e) => return Err(archive_error(e)),
+        };
+
+        let mut published_size = self.published_size.lock().unwrap();
+        *published_size = Some(published_size.map_or(sth.tree_size, |s| s.max(sth.tree_size)));
+        Ok(written)
+    }
+
+    /// Every published entry, by tree size. Objects under the prefix whose
+    /// name is not a tree size are skipped.
+    pub async fn entries(&self) -> Result<Vec<(Path, Result<SignedTreeHead>)>> {
+        let mut objects: Vec<(u64, Path)> = self
+            .store
+            .list(Some(&self.prefix))
+            .map_ok(|meta| meta.location)
+            .try_collect::<Vec<_>>()
+            .await
+            .map_err(archive_error)?
+            .into_iter()
+            .filter_map(|location| {
+                let size = location.filename()?.parse().ok()?;
+                Some((size, location))
+            })
+            .collect();
+        objects.sort();
+
+        let mut entries = Vec::with_capacity(objects.len());
+        for (size, location) in objects {
+            let bytes = self
+                .store
+                .get(&location)
+                .await
+                .map_err(archive_error)?
+                .bytes()
+                .await
+                .map_err(archive_error)?;
+            let entry = std::str::from_utf8(&bytes)
+                .map_err(|_| CtError::BadRequest("Archive entry is not UTF-8".into()))
+                .and_then(parse_entry)
+                .and_then(|sth| {
+                    if sth.tree_size == size {
+                        Ok(sth)
+                    } else {
+                        Err(CtError::BadRequest(format!(
+                            "Entry is for tree size {}",
+                            sth.tree_size
+                        )))
+                    }
+                });
+            entries.push((location, entry));
+        }
+        Ok(entries)
+    }
+}
+
+/// Outcome of [`verify_archive`]
+#[derive(Debug, Serialize)]
+pub struct ArchiveReport {
+    pub entries: usize,
+    /// Newest tree size with a readable entry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest_tree_size: Option<u64>,
+    pub problems: Vec<String>,
+}
+
+impl ArchiveReport {
+    pub fn ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Check every entry's signature and, with proofs from `merkle_tree`, that
+/// each entry's root is consistent with the previous entry's
+pub async fn verify_archive(
+    archive: &SthArchive,
+    merkle_tree: &StorageBackedMerkleTree,
+    public_key_der: &[u8],
+) -> Result<ArchiveReport> {
+    let entries = archive.entries().await?;
+    let mut report = ArchiveReport {
+        entries: entries.len(),
+        latest_tree_size: None,
+        problems: Vec::new(),
+    };
+
+    let mut previous: Option<SignedTreeHead> = None;
+    for (location, entry) in entries {
+        let sth = match entry.and_then(|sth| sth.verify(public_key_der).map(|()| sth)) {
+            Ok(sth) => sth,
+            Err(e) => {
+                report.problems.push(format!("{}: {}", location, e));
+                continue;
+            }
+        };
+
+        if let Some(old) = &previous {
+            if let Err(e) = check_consistency(merkle_tree, old, &sth).await {
+                report.problems.push(format!(
+                    "{}: not consistent with tree size {}: {}",
+                    location, old.tree_size, e
+                ));
+            }
+        }
+        report.latest_tree_size = Some(sth.tree_size);
+        previous = Some(sth);
+    }
+
+    Ok(report)
+}
+
+async fn check_consistency(
+    merkle_tree: &StorageBackedMerkleTree,
+    old: &SignedTreeHead,
+    new: &SignedTreeHead,
+) -> Result<()> {
+    if old.tree_size == 0 {
+        return if old.root_hash == Sha256::digest([]).as_slice() {
+            Ok(())
+        } else {
+            Err(CtError::BadRequest("Empty tree root is wrong".into()))
+        };
+    }
+
+    let root = |sth: &SignedTreeHead| {
+        RootHash::<Sha256>::new(
+            *digest::Output::<Sha256>::from_slice(&sth.root_hash),
+            sth.tree_size,
+        )
+    };
+    merkle_tree
+        .consistency_proof_between_sizes(old.tree_size, new.tree_size)
+        .await?
+        .verify(&root(old), &root(new))
+        .map_err(|e| CtError::BadRequest(format!("Consistency proof invalid: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::handlers::tests::create_test_state;
+    use crate::sth_signer::{SthSigningTask, DEFAULT_STH_SIGNING_INTERVAL};
+    use crate::types::sct::{SctVersion, SignedCertificateTimestamp};
+    use crate::types::{DeduplicatedLogEntry, LogEntry};
+    use object_store::memory::InMemory;
+
+    #[tokio::test]
+    async fn test_archive_follows_merges() {
+        let state = create_test_state(0).await;
+        let store = Arc::new(InMemory::new());
+        let archive = Arc::new(SthArchive::new(store.clone(), DEFAULT_STH_ARCHIVE_PREFIX));
+        let task = SthSigningTask::new(
+            DEFAULT_STH_SIGNING_INTERVAL,
+            state.sth_builder.clone(),
+            state.merkle_tree.clone(),
+            state.cached_sth.clone(),
+            state.metrics.clone(),
+        )
+        .with_archive(archive.clone());
+
+        let mut index = 0u64;
+        for batch in [3, 1, 5] {
+            for _ in 0..batch {
+                let entry = LogEntry::new_with_timestamp(
+                    index,
+                    [b"archive".as_slice(), &index.to_be_bytes()].concat(),
+                    None,
+                    chrono::Utc::now(),
+                );
+                let cert_hash = DeduplicatedLogEntry::hash_certificate(&entry.certificate);
+                let log_id = state.log_id.clone();
+                state
+                    .storage
+                    .add_entry_batched(entry, cert_hash, move |_| {
+                        Ok(SignedCertificateTimestamp {
+                            version: SctVersion::V1,
+                            log_id: log_id.clone(),
+                            timestamp: 0,
+                            extensions: vec![],
+                            signature: vec![],
+                        })
+                    })
+                    .await
+                    .unwrap();
+                index += 1;
+            }
+            task.sign().await.unwrap();
+            // Signing again at the same size publishes nothing new
+            task.sign().await.unwrap();
+        }
+
+        let entries = archive.entries().await.unwrap();
+        let sizes: Vec<u64> = entries
+            .iter()
+            .map(|(_, entry)| entry.as_ref().unwrap().tree_size)
+            .collect();
+        assert_eq!(sizes, [3, 4, 9]);
+
+        let object = store
+            .get(&Path::from("sth/9"))
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        let line = std::str::from_utf8(&object).unwrap();
+        let fields: Vec<&str> = line.trim_end().split(' ').collect();
+        let root = state.merkle_tree.committed_root().await.unwrap();
+        assert_eq!(fields[1], "9");
+        assert_eq!(fields[2], hex::encode(root.as_bytes()));
+        assert_eq!(line, format_entry(&parse_entry(line).unwrap()));
+
+        let report = verify_archive(&archive, &state.merkle_tree, &state.public_key_der)
+            .await
+            .unwrap();
+        assert!(report.ok(), "{:?}", report.problems);
+        assert_eq!(report.entries, 3);
+        assert_eq!(report.latest_tree_size, Some(9));
+
+        // A validly signed head for a size between two others, over a root
+        // the tree never had
+        let forged = state
+            .sth_builder
+            .create_sth(6, vec![0x66; 32], None)
+            .unwrap();
+        store
+            .put(
+                &Path::from("sth/6"),
+                PutPayload::from(format_entry(&forged)),
+            )
+            .await
+            .unwrap();
+        let report = verify_archive(&archive, &state.merkle_tree, &state.public_key_der)
+            .await
+            .unwrap();
+        assert!(!report.ok());
+        assert_eq!(report.problems.len(), 2, "{:?}", report.problems);
+        assert!(report.problems[0].starts_with("sth/6: not consistent with tree size 4"));
+        assert!(report.problems[1].starts_with("sth/9: not consistent with tree size 6"));
+    }
+
+    #[tokio::test]
+    async fn test_published_size_is_not_overwritten() {
+        let state = create_test_state(2).await;
+        let store = Arc::new(InMemory::new());
+        let root = state.merkle_tree.committed_root().await.unwrap();
+        let sth = state
+            .sth_builder
+            .create_sth(2, root.as_bytes().to_vec(), None)
+            .unwrap();
+
+        // Another instance got there first
+        let other = SthArchive::new(store.clone(), "sth");
+        assert!(other.publish(&sth).await.unwrap());
+        let archive = SthArchive::new(store.clone(), "sth");
+        let forged = state
+            .sth_builder
+            .create_sth(2, vec![0x66; 32], None)
+            .unwrap();
+        assert!(!archive.publish(&forged).await.unwrap());
+
+        let entries = archive.entries().await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            entries[0].1.as_ref().unwrap().root_hash,
+            root.as_bytes().to_vec()
+        );
+
+        // Unsigned or garbled entries are reported, not fatal
+        store
+            .put(&Path::from("sth/3"), PutPayload::from("not an sth\n"))
+            .await
+            .unwrap();
+        let report = verify_archive(&archive, &state.merkle_tree, &state.public_key_der)
+            .await
+            .unwrap();
+        assert_eq!(report.entries, 2);
+        assert_eq!(report.latest_tree_size, Some(2));
+        assert_eq!(report.problems.len(), 1);
+    }
+}
@@ -0,0 +1,247 @@
+use crate::merkle_storage::StorageBackedMerkleTree;
+use crate::metrics::Metrics;
+use crate::sth_archive::SthArchive;
+use crate::storage::MergeObserver;
+use crate::types::tree_head::{SignedTreeHead, SthBuilder};
+use crate::types::Result;
+use arc_swap::ArcSwapOption;
+use futures::future::BoxFuture;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const DEFAULT_STH_SIGNING_INTERVAL: Duration = Duration::from_secs(60);
+
+/// First wait before signing again after a merge failed to get its STH
+const RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Most recent STH signed by [`SthSigningTask`]
+#[derive(Debug, Clone)]
+pub struct CachedSth {
+    pub sth: SignedTreeHead,
+}
+
+/// Signs the STH of every merge as the merge completes, and a fresh one over
+/// the committed tree every `interval` while no merge has, so get-sth only
+/// ever serves an STH signed ahead of time
+#[derive(Clone)]
+pub struct SthSigningTask {
+    interval: Duration,
+    sth_builder: Arc<SthBuilder>,
+    merkle_tree: StorageBackedMerkleTree,
+    cached_sth: Arc<ArcSwapOption<CachedSth>>,
+    metrics: Arc<Metrics>,
+    archive: Option<Arc<SthArchive>>,
+    /// Set while a merge's STH is being signed again in the background
+    retrying: Arc<AtomicBool>,
+}
+
+impl SthSigningTask {
+    pub fn new(
+        interval: Duration,
+        sth_builder: Arc<SthBuilder>,
+        merkle_tree: StorageBackedMerkleTree,
+        cached_sth: Arc<ArcSwapOption<CachedSth>>,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        Self {
+            interval,
+            sth_builder,
+            merkle_tree,
+            cached_sth,
+            metrics,
+            archive: None,
+            retrying: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Publish every STH to `archive` as it is signed
+    pub fn with_archive(mut self, archive: Arc<SthArchive>) -> Self {
+        self.archive = Some(archive);
+        self
+    }
+
+    /// Sign the current committed root and replace the cached STH, unless a
+    /// larger or newer one was published meanwhile
+    pub async fn sign(&self) -> Result<()> {
+        if let (Some(sth), Some(archive)) = (self.sign_cached().await?, &self.archive) {
+            archive.publish(&sth).await?;
+        }
+        Ok(())
+    }
+
+    /// [`Self::sign`] without publishing to the archive. Returns the STH if it
+    /// replaced the cached one.
+    async fn sign_cached(&self) -> Result<Option<SignedTreeHead>> {
+        let committed_root = self.merkle_tree.committed_root().await?;
+        let sth = self.sth_builder.create_sth(
+            committed_root.num_leaves(),
+            committed_root.as_bytes().to_vec(),
+            None,
+        )?;
+
+        let signed = Arc::new(CachedSth { sth: sth.clone() });
+        let mut replaced = false;
+        self.cached_sth.rcu(|cached| match cached {
+            Some(cached)
+                if (cached.sth.tree_size, cached.sth.timestamp)
+                    > (sth.tree_size, sth.timestamp) =>
+            {
+                replaced = false;
+                Some(cached.clone())
+            }
+            _ => {
+                replaced = true;
+                Some(signed.clone())
+            }
+        });
+        if !replaced {
+            return Ok(None);
+        }
+
+        self.metrics
+            .sth_timestamp_seconds
+            .set(sth.timestamp as f64 / 1000.0);
+        Ok(Some(sth))
+    }
+
+    /// Sign until it works, backing off up to `interval` between attempts.
+    /// The merge is committed either way, so its submitters are not kept
+    /// waiting for this.
+    async fn retry_sign(self) {
+        let mut backoff = RETRY_BACKOFF;
+        loop {
+            tokio::time::sleep(backoff).await;
+            match self.sign().await {
+                Ok(()) => break,
+                Err(e) => tracing::error!("Failed to sign STH after a merge: {}", e),
+            }
+            backoff = (backoff * 2).min(self.interval);
+        }
+        self.retrying.store(false, Ordering::Release);
+    }
+
+    /// Re-sign every `interval`, skipping intervals in which a merge signed
+    pub async fn run(self) {
+        let mut interval = tokio::time::interval(self.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let mut last_tick = 0;
+
+        loop {
+            interval.tick().await;
+            let signed_since = self
+                .cached_sth
+                .load_full()
+                .is_some_and(|cached| cached.sth.timestamp > last_tick);
+            last_tick = chrono::Utc::now().timestamp_millis() as u64;
+            if signed_since {
+                continue;
+            }
+
+            if let Err(e) = self.sign().await {
+                tracing::error!("Failed to sign STH: {}", e);
+            }
+        }
+    }
+}
+
+impl MergeObserver for SthSigningTask {
+    /// Swaps in the merge's STH before its submitters are answered, and
+    /// leaves the archive upload to a background task
+    fn merged(&self) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            match self.sign_cached().await {
+                Ok(Some(sth)) => {
+                    if let Some(archive) = self.archive.clone() {
+                        tokio::spawn(async move {
+                            if let Err(e) = archive.publish(&sth).await {
+                                tracing::error!("Failed to archive STH: {}", e);
+                            }
+                        });
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::error!("Failed to sign STH after a merge: {}", e);
+                    if !self.retrying.swap(true, Ordering::AcqRel) {
+                        tokio::spawn(self.clone().retry_sign());
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::handlers::{
+        get_sth,
+        tests::{append_test_entries, create_test_state},
+    };
+    use axum::extract::{Query, State};
+
+    #[tokio::test]
+    async fn test_get_sth_serves_cached_sth() {
+        let state = create_test_state(3).await;
+        let task = SthSigningTask::new(
+            DEFAULT_STH_SIGNING_INTERVAL,
+            state.sth_builder.clone(),
+            state.merkle_tree.clone(),
+            state.cached_sth.clone(),
+            state.metrics.clone(),
+        );
+
+        task.sign().await.unwrap();
+        let cached = state.cached_sth.load_full().unwrap().sth.clone();
+        assert_eq!(cached.tree_size, 3);
+        cached.verify(&state.public_key_der).unwrap();
+
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let served = get_sth(State(state.clone()), Query(Default::default()))
+            .await
+            .unwrap()
+            .0
+            .sth;
+        assert_eq!(served.timestamp, cached.timestamp);
+        assert_eq!(
+            served.tree_head_signature,
+            cached.to_api_response().tree_head_signature
+        );
+        assert!(state.metrics.sth_timestamp_seconds.get() > 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_merges_publish_their_sth() {
+        let state = create_test_state(3).await;
+        let task = SthSigningTask::new(
+            DEFAULT_STH_SIGNING_INTERVAL,
+            state.sth_builder.clone(),
+            state.merkle_tree.clone(),
+            state.cached_sth.clone(),
+            state.metrics.clone(),
+        );
+        task.sign().await.unwrap();
+        state.storage.set_merge_observer(Arc::new(task));
+
+        for size in [5, 6, 10] {
+            let tree_size = state.merkle_tree.committed_size().await.unwrap();
+            append_test_entries(&state.storage, &state.log_id, tree_size..size).await;
+
+            // Published before the submitters were answered
+            let served = state.cached_sth.load_full().unwrap().sth.clone();
+            assert_eq!(served.tree_size, size);
+            served.verify(&state.public_key_der).unwrap();
+            let root = state.merkle_tree.committed_root().await.unwrap();
+            assert_eq!(served.root_hash, root.as_bytes().to_vec());
+
+            let response = get_sth(State(state.clone()), Query(Default::default()))
+                .await
+                .unwrap()
+                .0
+                .sth;
+            assert_eq!(response.tree_size, size);
+            assert_eq!(response.timestamp, served.timestamp);
+        }
+    }
+}
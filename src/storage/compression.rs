@@ -0,0 +1,54 @@
+use std::borrow::Cow;
+use std::io;
+
+/// Format prefix of a value stored as is
+const FORMAT_RAW: u8 = 0;
+/// Format prefix of a zstd frame
+const FORMAT_ZSTD: u8 = 1;
+
+/// Default zstd level for [`Compression::Zstd`]
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+/// How certificate-carrying values are written. Every such value starts with
+/// a format byte, so values read back whichever setting wrote them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    /// zstd at the given level, kept only for values it makes smaller
+    Zstd(i32),
+}
+
+impl Compression {
+    /// `value` behind its format byte
+    pub(crate) fn encode(self, value: &[u8]) -> Vec<u8> {
+        if let Compression::Zstd(level) = self {
+            let mut compressed = vec![FORMAT_ZSTD];
+            if zstd::stream::copy_encode(value, &mut compressed, level).is_ok()
+                && compressed.len() < value.len() + 1
+            {
+                return compressed;
+            }
+        }
+        let mut raw = Vec::with_capacity(value.len() + 1);
+        raw.push(FORMAT_RAW);
+        raw.extend_from_slice(value);
+        raw
+    }
+}
+
+/// The value [`Compression::encode`] wrote as `stored`
+pub(crate) fn decode(stored: &[u8]) -> io::Result<Cow<'_, [u8]>> {
+    match stored.split_first() {
+        Some((&FORMAT_RAW, value)) => Ok(Cow::Borrowed(value)),
+        Some((&FORMAT_ZSTD, frame)) => zstd::stream::decode_all(frame).map(Cow::Owned),
+        Some((format, _)) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unknown value format {}", format),
+        )),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "value has no format byte",
+        )),
+    }
+}
@@ -0,0 +1,437 @@
+use super::{CtStorage, KeyPrefix, Result, StorageError};
+use crate::merkle_tree::RootHash;
+use crate::types::signed_note::{checkpoint_body, NoteSignature};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Note signature type of c2sp.org/tlog-cosignature `cosignature/v1`
+const COSIGNATURE_V1_TYPE: u8 = 0x04;
+
+/// Timestamp and Ed25519 signature following the key ID of a cosignature
+const COSIGNATURE_V1_LEN: usize = 8 + 64;
+
+/// A witness whose cosignatures the log records
+#[derive(Debug, Clone)]
+pub struct WitnessKey {
+    name: String,
+    key_id: [u8; 4],
+    verifying_key: VerifyingKey,
+}
+
+impl WitnessKey {
+    /// Witness signing `cosignature/v1` as `name` with an Ed25519 key. Its
+    /// note key ID is the first four bytes of
+    /// SHA-256(name || "\n" || 0x04 || public key).
+    pub fn new(name: String, verifying_key: VerifyingKey) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(&name);
+        hasher.update(b"\n");
+        hasher.update([COSIGNATURE_V1_TYPE]);
+        hasher.update(verifying_key.as_bytes());
+        let mut key_id = [0u8; 4];
+        key_id.copy_from_slice(&hasher.finalize()[..4]);
+        Self {
+            name,
+            key_id,
+            verifying_key,
+        }
+    }
+
+    /// Parse a verifier key as witnesses publish it,
+    /// `<name>+<hex key ID>+<base64 of 0x04 || public key>`
+    pub fn from_vkey(vkey: &str) -> Result<Self> {
+        let invalid =
+            |reason: &str| StorageError::InvalidFormat(format!("vkey {}: {}", vkey, reason));
+
+        let mut parts = vkey.splitn(3, '+');
+        let (Some(name), Some(key_id), Some(key)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid("expected <name>+<key ID>+<key>"));
+        };
+        let key = STANDARD
+            .decode(key)
+            .map_err(|_| invalid("key is not valid base64"))?;
+        let (&COSIGNATURE_V1_TYPE, public_key) =
+            key.split_first().ok_or_else(|| invalid("key is empty"))?
+        else {
+            return Err(invalid("not a cosignature/v1 key"));
+        };
+        let public_key: &[u8; 32] = public_key
+            .try_into()
+            .map_err(|_| invalid("Ed25519 keys are 32 bytes"))?;
+        let verifying_key =
+            VerifyingKey::from_bytes(public_key).map_err(|_| invalid("invalid Ed25519 key"))?;
+
+        let witness = Self::new(name.to_string(), verifying_key);
+        if hex::encode(witness.key_id) != key_id.to_ascii_lowercase() {
+            return Err(invalid("key ID does not match the name and key"));
+        }
+        Ok(witness)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn key_id(&self) -> [u8; 4] {
+        self.key_id
+    }
+}
+
+/// Message a witness signs to cosign `checkpoint_body` at `timestamp`, in
+/// seconds since the epoch
+fn cosignature_v1_message(timestamp: u64, checkpoint_body: &str) -> Vec<u8> {
+    format!("cosignature/v1\ntime {}\n{}", timestamp, checkpoint_body).into_bytes()
+}
+
+/// Witness cosignature as persisted, one list per tree size
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredCosignature {
+    key_name: String,
+    key_id: [u8; 4],
+    signature: Vec<u8>,
+}
+
+impl From<StoredCosignature> for NoteSignature {
+    fn from(stored: StoredCosignature) -> Self {
+        NoteSignature {
+            key_name: stored.key_name,
+            key_id: stored.key_id,
+            signature: stored.signature,
+        }
+    }
+}
+
+fn cosignature_key(tree_size: u64) -> Vec<u8> {
+    let mut key = Vec::with_capacity(KeyPrefix::COSIGNATURE.len() + 8);
+    key.extend_from_slice(KeyPrefix::COSIGNATURE);
+    key.extend_from_slice(&tree_size.to_be_bytes());
+    key
+}
+
+impl CtStorage {
+    /// Record a witness cosignature on the checkpoint of `origin` at `root`,
+    /// which must be the log's own origin and its root at a published size.
+    /// The checkpoint text is rebuilt from them rather than taken from the
+    /// witness. The cosignature must be a `cosignature/v1` signature by one
+    /// of the witnesses set with [`with_witness_keys`](Self::with_witness_keys)
+    /// that verifies over that text; anything else is refused with
+    /// `InvalidCosignature` and nothing is stored. A later cosignature from
+    /// the same witness key ID replaces the earlier one.
+    pub async fn record_cosignature(
+        &self,
+        origin: &str,
+        root: &RootHash<Sha256>,
+        cosignature: &NoteSignature,
+    ) -> Result<()> {
+        let tree_size = root.num_leaves();
+        let witness = self
+            .witness_keys
+            .iter()
+            .find(|w| w.key_id == cosignature.key_id && w.name == cosignature.key_name)
+            .ok_or_else(|| {
+                StorageError::InvalidCosignature(format!(
+                    "unknown witness {} ({})",
+                    cosignature.key_name,
+                    hex::encode(cosignature.key_id)
+                ))
+            })?;
+        let Ok(signed) = <&[u8; COSIGNATURE_V1_LEN]>::try_from(cosignature.signature.as_slice())
+        else {
+            return Err(StorageError::InvalidCosignature(format!(
+                "cosignature from {} is not a timestamp and an Ed25519 signature",
+                witness.name
+            )));
+        };
+        let (timestamp, signature) = signed.split_at(8);
+        let timestamp = u64::from_be_bytes(timestamp.try_into().unwrap());
+        let signature = Signature::from_slice(signature).map_err(|_| {
+            StorageError::InvalidCosignature(format!("malformed signature from {}", witness.name))
+        })?;
+        let body = checkpoint_body(origin, tree_size, root.as_bytes());
+        witness
+            .verifying_key
+            .verify(&cosignature_v1_message(timestamp, &body), &signature)
+            .map_err(|_| {
+                StorageError::InvalidCosignature(format!(
+                    "signature from {} does not verify over the checkpoint at size {}",
+                    witness.name, tree_size
+                ))
+            })?;
+
+        let _guard = self.cosignature_lock.lock().await;
+
+        let mut stored = self.load_cosignatures(tree_size).await?;
+        stored.retain(|existing| existing.key_id != cosignature.key_id);
+        stored.push(StoredCosignature {
+            key_name: cosignature.key_name.clone(),
+            key_id: cosignature.key_id,
+            signature: cosignature.signature.clone(),
+        });
+
+        let value =
+            postcard::to_stdvec(&stored).map_err(|e| StorageError::InvalidFormat(e.to_string()))?;
+        self.db
+            .put(&cosignature_key(tree_size), &value)
+            .await
+            .map_err(StorageError::SlateDb)
+    }
+
+    /// All cosignatures recorded for `tree_size`, sorted by key name
+    pub async fn get_cosignatures(&self, tree_size: u64) -> Result<Vec<NoteSignature>> {
+        let mut cosignatures: Vec<NoteSignature> = self
+            .load_cosignatures(tree_size)
+            .await?
+            .into_iter()
+            .map(NoteSignature::from)
+            .collect();
+        cosignatures.sort_by(|a, b| a.key_name.cmp(&b.key_name));
+        Ok(cosignatures)
+    }
+
+    async fn load_cosignatures(&self, tree_size: u64) -> Result<Vec<StoredCosignature>> {
+        match self
+            .db
+            .get(&cosignature_key(tree_size))
+            .await
+            .map_err(StorageError::SlateDb)?
+        {
+            Some(bytes) => {
+                postcard::from_bytes(&bytes).map_err(|e| StorageError::InvalidFormat(e.to_string()))
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::BatchConfig;
+    use crate::types::signed_note::SignedNote;
+    use crate::types::tree_head::{SignedTreeHead, SthBuilder};
+    use ed25519_dalek::{Signer, SigningKey};
+    use p256::ecdsa::{signature::Verifier as _, DerSignature};
+
+    const ORIGIN: &str = "log.example/2025";
+
+    async fn create_test_storage(witness_keys: Vec<WitnessKey>) -> CtStorage {
+        CtStorage::with_memory_backend(BatchConfig::default())
+            .await
+            .unwrap()
+            .0
+            .with_witness_keys(witness_keys)
+    }
+
+    fn test_witness(name: &str) -> (WitnessKey, SigningKey) {
+        let key = SigningKey::generate(&mut rand::rngs::OsRng);
+        (WitnessKey::new(name.to_string(), key.verifying_key()), key)
+    }
+
+    fn test_root(tree_size: u64) -> RootHash<Sha256> {
+        RootHash::new([tree_size as u8; 32].into(), tree_size)
+    }
+
+    /// `cosignature/v1` by `key` over `body` at `timestamp`
+    fn witness_cosign(
+        witness: &WitnessKey,
+        key: &SigningKey,
+        timestamp: u64,
+        body: &str,
+    ) -> NoteSignature {
+        let signature = key.sign(&cosignature_v1_message(timestamp, body));
+        let mut signed = timestamp.to_be_bytes().to_vec();
+        signed.extend_from_slice(&signature.to_bytes());
+        NoteSignature {
+            key_name: witness.name().to_string(),
+            key_id: witness.key_id(),
+            signature: signed,
+        }
+    }
+
+    fn body_at(root: &RootHash<Sha256>) -> String {
+        checkpoint_body(ORIGIN, root.num_leaves(), root.as_bytes())
+    }
+
+    #[test]
+    fn test_witness_from_vkey() {
+        let (witness, _) = test_witness("witness.example");
+        let mut key = vec![COSIGNATURE_V1_TYPE];
+        key.extend_from_slice(witness.verifying_key.as_bytes());
+        let vkey = format!(
+            "witness.example+{}+{}",
+            hex::encode(witness.key_id()),
+            STANDARD.encode(&key)
+        );
+
+        let parsed = WitnessKey::from_vkey(&vkey).unwrap();
+        assert_eq!(parsed.name(), "witness.example");
+        assert_eq!(parsed.key_id(), witness.key_id());
+
+        // Another name, type or key ID does not match the key
+        let renamed = vkey.replacen("witness.example", "other.example", 1);
+        assert!(WitnessKey::from_vkey(&renamed).is_err());
+        key[0] = 0x01;
+        let ed25519_note_key = format!(
+            "witness.example+{}+{}",
+            hex::encode(witness.key_id()),
+            STANDARD.encode(&key)
+        );
+        assert!(WitnessKey::from_vkey(&ed25519_note_key).is_err());
+        assert!(WitnessKey::from_vkey("witness.example+00000000").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_record_replaces_same_key_id() {
+        let (witness, key) = test_witness("witness.example");
+        let storage = create_test_storage(vec![witness.clone()]).await;
+        let root = test_root(10);
+
+        let first = witness_cosign(&witness, &key, 1, &body_at(&root));
+        let second = witness_cosign(&witness, &key, 2, &body_at(&root));
+        storage
+            .record_cosignature(ORIGIN, &root, &first)
+            .await
+            .unwrap();
+        storage
+            .record_cosignature(ORIGIN, &root, &second)
+            .await
+            .unwrap();
+
+        let stored = storage.get_cosignatures(10).await.unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].signature, second.signature);
+        assert!(storage.get_cosignatures(11).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_record_refuses_unverified_cosignatures() {
+        let (witness, key) = test_witness("witness.example");
+        let (stranger, stranger_key) = test_witness("stranger.example");
+        let storage = create_test_storage(vec![witness.clone()]).await;
+        let root = test_root(10);
+        let body = body_at(&root);
+
+        // Not a configured witness
+        let unknown = witness_cosign(&stranger, &stranger_key, 1, &body);
+        // Configured name and key ID, but signed by another key
+        let forged = witness_cosign(&witness, &stranger_key, 1, &body);
+        // Signed over another checkpoint, or another log's
+        let other_size = witness_cosign(&witness, &key, 1, &body_at(&test_root(11)));
+        let other_log = witness_cosign(
+            &witness,
+            &key,
+            1,
+            &checkpoint_body("other.example", 10, root.as_bytes()),
+        );
+        // Signed at another time than the one it carries
+        let mut other_time = witness_cosign(&witness, &key, 1, &body);
+        other_time.signature[7] = 2;
+        // Not a timestamp and a signature
+        let malformed = NoteSignature {
+            signature: vec![0; 8],
+            ..witness_cosign(&witness, &key, 1, &body)
+        };
+
+        for cosignature in [
+            &unknown,
+            &forged,
+            &other_size,
+            &other_log,
+            &other_time,
+            &malformed,
+        ] {
+            assert!(matches!(
+                storage.record_cosignature(ORIGIN, &root, cosignature).await,
+                Err(StorageError::InvalidCosignature(_))
+            ));
+        }
+        assert!(storage.get_cosignatures(10).await.unwrap().is_empty());
+
+        let valid = witness_cosign(&witness, &key, 1, &body);
+        storage
+            .record_cosignature(ORIGIN, &root, &valid)
+            .await
+            .unwrap();
+        assert_eq!(storage.get_cosignatures(10).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cosigned_checkpoint_verifies() {
+        let (witness_a, key_a) = test_witness("witness-a.example");
+        let (witness_b, key_b) = test_witness("witness-b.example");
+        let storage = create_test_storage(vec![witness_a.clone(), witness_b.clone()]).await;
+
+        let log_key = p256::ecdsa::SigningKey::random(&mut rand::thread_rng());
+        let sth_builder =
+            SthBuilder::new(&log_key.to_bytes(), ORIGIN.to_string(), vec![9u8; 32]).unwrap();
+
+        let root = test_root(123);
+        let mut checkpoint = sth_builder
+            .create_checkpoint(123, root.as_bytes().to_vec(), Some(1700000000000))
+            .unwrap();
+        let body = body_at(&root);
+
+        // Recorded out of order, served sorted by key name
+        let timestamp = 1700000000;
+        storage
+            .record_cosignature(
+                ORIGIN,
+                &root,
+                &witness_cosign(&witness_b, &key_b, timestamp, &body),
+            )
+            .await
+            .unwrap();
+        storage
+            .record_cosignature(
+                ORIGIN,
+                &root,
+                &witness_cosign(&witness_a, &key_a, timestamp, &body),
+            )
+            .await
+            .unwrap();
+
+        for cosignature in storage.get_cosignatures(123).await.unwrap() {
+            checkpoint.add_signature(cosignature);
+        }
+
+        let parsed = SignedNote::parse(&checkpoint.format()).unwrap();
+        assert_eq!(parsed.text, body);
+        let names: Vec<&str> = parsed
+            .signatures
+            .iter()
+            .map(|s| s.key_name.as_str())
+            .collect();
+        assert_eq!(names, [ORIGIN, "witness-a.example", "witness-b.example"]);
+
+        // Log signature is an RFC6962NoteSignature over the tree head
+        let log_sig = &parsed.signatures[0].signature;
+        let log_timestamp = u64::from_be_bytes(log_sig[..8].try_into().unwrap());
+        let sth = SignedTreeHead::new(123, log_timestamp, root.as_bytes().to_vec());
+        let signature = DerSignature::from_bytes(&log_sig[12..]).unwrap();
+        log_key
+            .verifying_key()
+            .verify(&sth.get_signature_input(), &signature)
+            .unwrap();
+
+        // Witness signatures are timestamp || Ed25519 signature over the
+        // cosignature/v1 header and the identical note body
+        for (cosig, key) in parsed.signatures[1..].iter().zip([&key_a, &key_b]) {
+            assert_eq!(cosig.signature.len(), COSIGNATURE_V1_LEN);
+            assert_eq!(&cosig.signature[..8], &timestamp.to_be_bytes());
+            let message = format!("cosignature/v1\ntime {}\n{}", timestamp, parsed.text);
+            let signature = Signature::from_slice(&cosig.signature[8..]).unwrap();
+            key.verifying_key()
+                .verify(message.as_bytes(), &signature)
+                .unwrap();
+        }
+
+        // Each signature line is "— <name> base64(key_id || signature)"
+        let formatted = checkpoint.format();
+        let line = formatted.lines().last().unwrap();
+        let encoded = line.strip_prefix("— witness-b.example ").unwrap();
+        assert_eq!(&STANDARD.decode(encoded).unwrap()[..4], &witness_b.key_id());
+    }
+}
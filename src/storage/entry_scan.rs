@@ -0,0 +1,137 @@
+use super::{CtStorage, KeyPrefix, Result, StorageError};
+use crate::types::{extra_data::ExtraData, DeduplicatedLogEntry};
+use futures::{stream, Stream, StreamExt, TryStreamExt};
+
+/// Entries reconstructed at once within a scanned batch
+const SCAN_RECONSTRUCT_CONCURRENCY: usize = 32;
+
+/// Where an entry scan stands, for a caller to checkpoint and later resume
+/// the scan from with [`CtStorage::resume_scan`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanToken {
+    next_index: u64,
+}
+
+impl ScanToken {
+    /// Index of the first entry a resumed scan yields
+    pub fn next_index(&self) -> u64 {
+        self.next_index
+    }
+
+    pub fn to_bytes(self) -> [u8; 8] {
+        self.next_index.to_be_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let next_index: [u8; 8] = bytes
+            .try_into()
+            .map_err(|_| StorageError::InvalidFormat("Invalid scan token".into()))?;
+        Ok(Self {
+            next_index: u64::from_be_bytes(next_index),
+        })
+    }
+}
+
+/// One entry as served by get-entries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScannedEntry {
+    pub index: u64,
+    /// The serialized MerkleTreeLeaf
+    pub leaf_input: Vec<u8>,
+    /// The RFC 6962 `extra_data` of the entry
+    pub extra_data: Vec<u8>,
+}
+
+impl ScannedEntry {
+    /// Resumes a scan right after this entry
+    pub fn resume_token(&self) -> ScanToken {
+        ScanToken {
+            next_index: self.index + 1,
+        }
+    }
+}
+
+fn entry_key(index: u64) -> Vec<u8> {
+    let mut key = Vec::with_capacity(KeyPrefix::ENTRY.len() + 8);
+    key.extend_from_slice(KeyPrefix::ENTRY);
+    key.extend_from_slice(&index.to_be_bytes());
+    key
+}
+
+impl CtStorage {
+    /// Every logged entry from `start_index` on, in index order. Entries are
+    /// read with one range scan per `batch_size` of them; their certificates
+    /// are read through the same paths as [`Self::get_entry`], so values in
+    /// any stored format come back alike. Entries are logged atomically with
+    /// the tree append that commits them, so the scan ends at the committed
+    /// tree size as of its last batch.
+    pub fn scan_entries(
+        &self,
+        start_index: u64,
+        batch_size: usize,
+    ) -> impl Stream<Item = Result<ScannedEntry>> + '_ {
+        let batch_size = batch_size.max(1) as u64;
+        stream::try_unfold(start_index, move |next| async move {
+            let batch = self
+                .db
+                .scan(
+                    &entry_key(next),
+                    &entry_key(next.saturating_add(batch_size)),
+                )
+                .await?;
+            if batch.is_empty() {
+                return Ok(None);
+            }
+
+            let mut dedup_entries = Vec::with_capacity(batch.len());
+            for (expected, (_, value)) in (next..).zip(&batch) {
+                let dedup_entry: DeduplicatedLogEntry =
+                    postcard::from_bytes(value).map_err(|e| {
+                        StorageError::InvalidFormat(format!(
+                            "Failed to deserialize deduplicated entry: {}",
+                            e
+                        ))
+                    })?;
+                if dedup_entry.index != expected {
+                    return Err(StorageError::InvalidFormat(format!(
+                        "Entry {} is missing",
+                        expected
+                    )));
+                }
+                dedup_entries.push(dedup_entry);
+            }
+
+            let entries: Vec<ScannedEntry> = stream::iter(dedup_entries.iter())
+                .map(|dedup_entry| self.scanned_entry(dedup_entry))
+                .buffered(SCAN_RECONSTRUCT_CONCURRENCY)
+                .try_collect()
+                .await?;
+            let after = next + entries.len() as u64;
+            Ok(Some((stream::iter(entries.into_iter().map(Ok)), after)))
+        })
+        .try_flatten()
+    }
+
+    /// [`Self::scan_entries`] from where `token` was taken
+    pub fn resume_scan(
+        &self,
+        token: ScanToken,
+        batch_size: usize,
+    ) -> impl Stream<Item = Result<ScannedEntry>> + '_ {
+        self.scan_entries(token.next_index, batch_size)
+    }
+
+    async fn scanned_entry(&self, dedup_entry: &DeduplicatedLogEntry) -> Result<ScannedEntry> {
+        let log_entry = self.reconstruct_log_entry(dedup_entry).await?;
+        let encoded = log_entry
+            .serialize()
+            .and_then(|leaf_input| Ok((leaf_input, ExtraData::for_entry(&log_entry)?.encode()?)));
+        let (leaf_input, extra_data) =
+            encoded.map_err(|e| StorageError::InvalidFormat(e.to_string()))?;
+        Ok(ScannedEntry {
+            index: dedup_entry.index,
+            leaf_input,
+            extra_data,
+        })
+    }
+}
@@ -0,0 +1,196 @@
+use super::{CtStorage, KeyPrefix, Result, StorageError};
+use crate::merkle_storage::StorageBackedMerkleTree;
+use crate::storage::RateLimitedDb;
+use crate::types::LogEntryType;
+
+/// First leaf index covered by the leaf type index. Entries logged before the
+/// index existed have no `ltype:` keys.
+const LEAF_TYPE_INDEX_START_KEY: &[u8] = b"ltype_start";
+
+/// `ltype:{type byte}:{big-endian leaf index}`, so one type's entries sort by index
+pub(super) fn leaf_type_key(entry_type: LogEntryType, index: u64) -> Vec<u8> {
+    let mut key = Vec::with_capacity(KeyPrefix::LEAF_TYPE.len() + 10);
+    key.extend_from_slice(KeyPrefix::LEAF_TYPE);
+    key.push(entry_type as u8);
+    key.push(b':');
+    key.extend_from_slice(&index.to_be_bytes());
+    key
+}
+
+/// Record where the leaf type index starts, once, before anything is logged
+/// with it. Every entry at or after that index gets a key in `flush_batch`.
+pub(super) async fn init_leaf_type_index(
+    db: &RateLimitedDb,
+    merkle_tree: &StorageBackedMerkleTree,
+) -> Result<()> {
+    if db.get(LEAF_TYPE_INDEX_START_KEY).await?.is_some() {
+        return Ok(());
+    }
+
+    let size = merkle_tree
+        .size()
+        .await
+        .map_err(|e| StorageError::InvalidFormat(format!("Failed to get tree size: {:?}", e)))?;
+    db.put(LEAF_TYPE_INDEX_START_KEY, &size.to_be_bytes())
+        .await
+        .map_err(StorageError::SlateDb)
+}
+
+impl CtStorage {
+    /// Indices in `start..=end` that may hold an entry of `entry_type`.
+    ///
+    /// Where the leaf type index covers the range the result is exact. Indices
+    /// logged before the index existed are all returned, so callers must still
+    /// check the type of each entry they load.
+    pub async fn candidate_indices_for_type(
+        &self,
+        entry_type: LogEntryType,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<u64>> {
+        let index_start = match self.db.get(LEAF_TYPE_INDEX_START_KEY).await? {
+            Some(bytes) => u64::from_be_bytes(bytes.as_ref().try_into().map_err(|_| {
+                StorageError::InvalidFormat("Invalid leaf type index start".into())
+            })?),
+            None => u64::MAX,
+        };
+
+        let mut indices: Vec<u64> = if start < index_start {
+            (start..=end.min(index_start - 1)).collect()
+        } else {
+            Vec::new()
+        };
+        if index_start > end {
+            return Ok(indices);
+        }
+
+        // Leaf indices stay far below u64::MAX, so end + 1 cannot collide
+        let scan_start = leaf_type_key(entry_type, start.max(index_start));
+        let scan_end = leaf_type_key(entry_type, end.saturating_add(1));
+
+        for (key, _) in self.db.scan(&scan_start, &scan_end).await? {
+            let index_bytes = key
+                .get(KeyPrefix::LEAF_TYPE.len() + 2..)
+                .and_then(|bytes| <[u8; 8]>::try_from(bytes).ok())
+                .ok_or_else(|| StorageError::InvalidFormat("Invalid leaf type key".into()))?;
+            indices.push(u64::from_be_bytes(index_bytes));
+        }
+
+        Ok(indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::BatchConfig;
+    use crate::test_utils::utils::{
+        create_precertificate_with_poison_and_serial, create_test_certificate,
+        create_test_certificate_with_serial, extract_test_issuer_key_hash,
+    };
+    use crate::types::sct::{SctVersion, SignedCertificateTimestamp};
+    use crate::types::{DeduplicatedLogEntry, LogEntry, LogId};
+    use crate::validation::tbs_extractor::TbsExtractor;
+
+    async fn create_test_storage() -> CtStorage {
+        let config = BatchConfig {
+            max_batch_size: 1,
+            max_batch_timeout_ms: 10,
+        };
+        CtStorage::with_memory_backend(config).await.unwrap().0
+    }
+
+    fn test_entry(serial: u8, precert: bool) -> LogEntry {
+        let timestamp = chrono::Utc::now();
+        if !precert {
+            return LogEntry::new_with_timestamp(
+                0,
+                create_test_certificate_with_serial(serial),
+                None,
+                timestamp,
+            );
+        }
+
+        let precert_der = create_precertificate_with_poison_and_serial(serial);
+        let issuer_der = create_test_certificate();
+        let chain = vec![precert_der.clone(), issuer_der.clone()];
+        LogEntry::new_precert_with_timestamp(
+            0,
+            TbsExtractor::extract_tbs_certificate(&precert_der, &chain).unwrap(),
+            Some(vec![issuer_der]),
+            extract_test_issuer_key_hash(&chain),
+            precert_der,
+            timestamp,
+        )
+    }
+
+    async fn log(storage: &CtStorage, entry: LogEntry) -> u64 {
+        let cert_hash = DeduplicatedLogEntry::hash_certificate(&entry.certificate);
+        let (index, _) = storage
+            .add_entry_batched(entry, cert_hash, |_| {
+                Ok(SignedCertificateTimestamp {
+                    version: SctVersion::V1,
+                    log_id: LogId::from_public_key_der(&[0x42; 32]),
+                    timestamp: 0,
+                    extensions: vec![],
+                    signature: vec![],
+                })
+            })
+            .await
+            .unwrap();
+        index
+    }
+
+    #[tokio::test]
+    async fn test_candidates_by_type() {
+        let storage = create_test_storage().await;
+        for (serial, precert) in [(1, false), (2, true), (3, false), (4, true), (5, true)] {
+            log(&storage, test_entry(serial, precert)).await;
+        }
+
+        assert_eq!(
+            storage
+                .candidate_indices_for_type(LogEntryType::PrecertEntry, 0, 4)
+                .await
+                .unwrap(),
+            vec![1, 3, 4]
+        );
+        assert_eq!(
+            storage
+                .candidate_indices_for_type(LogEntryType::X509Entry, 1, 10)
+                .await
+                .unwrap(),
+            vec![2]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_entries_before_index_are_all_candidates() {
+        let storage = create_test_storage().await;
+        for (serial, precert) in [(1, false), (2, true), (3, false), (4, true)] {
+            log(&storage, test_entry(serial, precert)).await;
+        }
+
+        // As if the first two entries were logged by an older version
+        storage
+            .db
+            .put(LEAF_TYPE_INDEX_START_KEY, &2u64.to_be_bytes())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            storage
+                .candidate_indices_for_type(LogEntryType::PrecertEntry, 0, 3)
+                .await
+                .unwrap(),
+            vec![0, 1, 3]
+        );
+        assert_eq!(
+            storage
+                .candidate_indices_for_type(LogEntryType::X509Entry, 2, 3)
+                .await
+                .unwrap(),
+            vec![2]
+        );
+    }
+}
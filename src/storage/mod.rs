@@ -1,9 +1,10 @@
 use crate::merkle_storage::StorageBackedMerkleTree;
+use crate::metrics::Metrics;
 use crate::types::{sct::SignedCertificateTimestamp, DeduplicatedLogEntry, LogEntry};
 use crate::validation::tbs_extractor::TbsExtractor;
 use bytes::Bytes;
 use foyer::{Cache, CacheBuilder};
-use futures::future::join_all;
+use futures::future::{join_all, BoxFuture};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
@@ -11,8 +12,23 @@ use std::time::{Duration, Instant};
 use thiserror::Error;
 use tokio::sync::{mpsc, oneshot, Mutex};
 
+pub(crate) mod compression;
+mod cosignature;
+mod entry_scan;
+mod leaf_type_index;
+mod object_store_factory;
 mod rate_limited_db;
-pub use rate_limited_db::RateLimitedDb;
+mod rejection_log;
+mod root_index;
+mod submitter;
+pub use compression::{Compression, DEFAULT_ZSTD_LEVEL};
+pub use cosignature::WitnessKey;
+pub use entry_scan::{ScanToken, ScannedEntry};
+pub use object_store_factory::{
+    build_object_store, probe_object_store, LocalConfig, StorageConfig,
+};
+pub use rate_limited_db::{DbBatch, LoggedWrite, RateLimitedDb};
+pub use rejection_log::{RejectionLog, RejectionReason, RejectionRecord};
 
 #[derive(Error, Debug)]
 pub enum StorageError {
@@ -24,6 +40,15 @@ pub enum StorageError {
 
     #[error("Queue full - system at capacity")]
     QueueFull,
+
+    #[error("Corrupted data: {0}")]
+    Corrupted(String),
+
+    #[error("SCT timestamp refused: {0}")]
+    InvalidTimestamp(String),
+
+    #[error("Cosignature refused: {0}")]
+    InvalidCosignature(String),
 }
 
 impl Clone for StorageError {
@@ -34,6 +59,9 @@ impl Clone for StorageError {
             }
             StorageError::InvalidFormat(s) => StorageError::InvalidFormat(s.clone()),
             StorageError::QueueFull => StorageError::QueueFull,
+            StorageError::Corrupted(s) => StorageError::Corrupted(s.clone()),
+            StorageError::InvalidTimestamp(s) => StorageError::InvalidTimestamp(s.clone()),
+            StorageError::InvalidCosignature(s) => StorageError::InvalidCosignature(s.clone()),
         }
     }
 }
@@ -46,11 +74,25 @@ impl From<slatedb::SlateDBError> for StorageError {
 
 pub type Result<T> = std::result::Result<T, StorageError>;
 
+/// Told of each merge once it is committed, before the submitters in it are
+/// answered, so what they are told about can already be published
+pub trait MergeObserver: Send + Sync {
+    fn merged(&self) -> BoxFuture<'_, ()>;
+}
+
+type MergeObserverSlot = Arc<std::sync::RwLock<Option<Arc<dyn MergeObserver>>>>;
+
+/// First byte of every DER certificate
+const DER_SEQUENCE_TAG: u8 = 0x30;
+
 /// Entry to be batched and flushed
 pub struct BatchEntry {
     pub log_entry: LogEntry,
     pub cert_hash: [u8; 32],
-    pub sct_callback: Box<dyn FnOnce(u64) -> SignedCertificateTimestamp + Send>,
+    /// Signs the entry's SCT for a leaf index. It may be called again with a
+    /// lower index if another entry in the batch is refused its SCT.
+    pub sct_callback:
+        Box<dyn Fn(u64) -> crate::types::Result<SignedCertificateTimestamp> + Send + Sync>,
     pub completion_tx: oneshot::Sender<Result<(u64, SignedCertificateTimestamp)>>,
 }
 
@@ -91,7 +133,8 @@ impl KeyPrefix {
     /// Log entries
     pub const ENTRY: &'static [u8] = b"entry:";
 
-    /// Hash to index mapping for efficient proof-by-hash lookups
+    /// Leaf hash to index mapping written before the Merkle tree kept its own
+    /// leaf hash index. Only read, for entries logged by older versions.
     pub const HASH_INDEX: &'static [u8] = b"hash:";
 
     /// Certificate hash to SCT mapping for deduplication
@@ -99,6 +142,21 @@ impl KeyPrefix {
 
     /// Certificate store - maps certificate hash to certificate data
     pub const CERT: &'static [u8] = b"cert:";
+
+    /// Witness cosignatures - maps tree size to the cosignatures on its checkpoint
+    pub const COSIGNATURE: &'static [u8] = b"cosig:";
+
+    /// Leaf type index - one empty value per entry, keyed by type then index
+    pub const LEAF_TYPE: &'static [u8] = b"ltype:";
+
+    /// Rejected submissions, keyed by arrival sequence number
+    pub const REJECTION: &'static [u8] = b"reject:";
+
+    /// Accepted roots - maps root certificate hash to its get-roots index
+    pub const ROOT_INDEX: &'static [u8] = b"root:";
+
+    /// Authenticated submitter of each entry, keyed by leaf index
+    pub const SUBMITTER: &'static [u8] = b"submitter:";
 }
 
 /// Storage backend for Certificate Transparency log using SlateDB with batching
@@ -110,10 +168,19 @@ pub struct CtStorage {
     chain_cache: Cache<Vec<u8>, Arc<Vec<u8>>>,
     /// Cache for fully reconstructed log entries
     entry_cache: Cache<u64, Arc<LogEntry>>,
+    /// Serializes read-modify-write of cosignature lists
+    cosignature_lock: Arc<Mutex<()>>,
+    /// Witnesses whose cosignatures are recorded
+    witness_keys: Arc<[WitnessKey]>,
+    /// Serializes assignment of new root indices
+    root_index_lock: Arc<Mutex<()>>,
+    metrics: Arc<Metrics>,
+    /// Shared with the batch worker, which calls it after every merge
+    merge_observer: MergeObserverSlot,
 }
 
-impl BatchConfig {
-    pub fn default() -> Self {
+impl Default for BatchConfig {
+    fn default() -> Self {
         Self {
             max_batch_size: 2_000,
             max_batch_timeout_ms: 50,
@@ -122,11 +189,25 @@ impl BatchConfig {
 }
 
 impl CtStorage {
+    /// Storage reporting to the global metrics
     pub async fn new(
         db: RateLimitedDb,
         config: BatchConfig,
         merkle_tree: StorageBackedMerkleTree,
     ) -> Result<Self> {
+        Self::new_with_metrics(db, config, merkle_tree, crate::metrics::global()).await
+    }
+
+    /// Storage reporting to `metrics`, which the [`ApiState`](crate::api::ApiState)
+    /// built on it shares
+    pub async fn new_with_metrics(
+        db: RateLimitedDb,
+        config: BatchConfig,
+        merkle_tree: StorageBackedMerkleTree,
+        metrics: Arc<Metrics>,
+    ) -> Result<Self> {
+        leaf_type_index::init_leaf_type_index(&db, &merkle_tree).await?;
+
         // Use a bounded channel to provide backpressure
         let channel_capacity = (config.max_batch_size * 2).max(500);
         let (batch_sender, batch_receiver) = mpsc::channel(channel_capacity);
@@ -136,18 +217,31 @@ impl CtStorage {
         let tree_clone = merkle_tree.clone();
         let batch_stats = Arc::new(Mutex::new(BatchStats::default()));
         let stats_clone = batch_stats.clone();
+        let batch_metrics = metrics.clone();
+        let merge_observer = MergeObserverSlot::default();
+        let observer_clone = merge_observer.clone();
 
         tokio::spawn(async move {
-            Self::batch_worker(batch_receiver, config, mutex_clone, tree_clone, stats_clone).await;
+            Self::batch_worker(
+                batch_receiver,
+                config,
+                mutex_clone,
+                tree_clone,
+                stats_clone,
+                batch_metrics,
+                observer_clone,
+            )
+            .await;
         });
 
         // Start metrics logging task
         let metrics_sender = batch_sender.clone();
         let metrics_stats = batch_stats.clone();
         let metrics_db = db.clone();
+        let worker_metrics = metrics.clone();
 
         tokio::spawn(async move {
-            Self::metrics_worker(metrics_sender, metrics_stats, metrics_db).await;
+            Self::metrics_worker(metrics_sender, metrics_stats, metrics_db, worker_metrics).await;
         });
 
         let chain_cache = CacheBuilder::new(10_000).build();
@@ -161,9 +255,51 @@ impl CtStorage {
             batch_sender,
             chain_cache,
             entry_cache,
+            cosignature_lock: Arc::new(Mutex::new(())),
+            witness_keys: Arc::new([]),
+            root_index_lock: Arc::new(Mutex::new(())),
+            metrics,
+            merge_observer,
         })
     }
 
+    /// Storage and its tree over SlateDB on an in-memory object store, for
+    /// tests and short-lived logs. Nothing is written to the file system.
+    ///
+    /// This is deliberately not a separate `HashMap` backend behind a
+    /// storage trait: entries, their indexes and the tree's nodes go into
+    /// the same SlateDB write batches, so a second backend would have to
+    /// reimplement those batches, and tests on it would no longer cover the
+    /// code the log runs.
+    pub async fn with_memory_backend(
+        config: BatchConfig,
+    ) -> Result<(Self, StorageBackedMerkleTree)> {
+        let db =
+            slatedb::Db::open("memory", Arc::new(object_store::memory::InMemory::new())).await?;
+        let db = RateLimitedDb::new(Arc::new(db), None);
+        let merkle_tree = StorageBackedMerkleTree::new(db.clone())
+            .await
+            .map_err(|e| StorageError::InvalidFormat(format!("Failed to open tree: {}", e)))?;
+        let storage = Self::new(db, config, merkle_tree.clone()).await?;
+        Ok((storage, merkle_tree))
+    }
+
+    /// Accept cosignatures from `witness_keys` only, replacing any set before.
+    /// Without any, every cosignature is refused.
+    pub fn with_witness_keys(mut self, witness_keys: Vec<WitnessKey>) -> Self {
+        self.witness_keys = witness_keys.into();
+        self
+    }
+
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    /// Have `observer` called after every merge, replacing any set before
+    pub fn set_merge_observer(&self, observer: Arc<dyn MergeObserver>) {
+        *self.merge_observer.write().unwrap() = Some(observer);
+    }
+
     /// Add entry to batch queue and return assigned index and SCT
     pub async fn add_entry_batched<F>(
         &self,
@@ -172,7 +308,7 @@ impl CtStorage {
         sct_callback: F,
     ) -> Result<(u64, SignedCertificateTimestamp)>
     where
-        F: FnOnce(u64) -> SignedCertificateTimestamp + Send + 'static,
+        F: Fn(u64) -> crate::types::Result<SignedCertificateTimestamp> + Send + Sync + 'static,
     {
         let (completion_tx, completion_rx) = oneshot::channel();
 
@@ -223,6 +359,8 @@ impl CtStorage {
         batch_mutex: Arc<Mutex<()>>,
         merkle_tree: StorageBackedMerkleTree,
         batch_stats: Arc<Mutex<BatchStats>>,
+        metrics: Arc<Metrics>,
+        merge_observer: MergeObserverSlot,
     ) {
         tracing::trace!("batch_worker: Starting background worker");
         let mut pending_entries = Vec::with_capacity(config.max_batch_size);
@@ -246,6 +384,8 @@ impl CtStorage {
                             batch_mutex.clone(),
                             merkle_tree.clone(),
                             batch_stats.clone(),
+                            &metrics,
+                            &merge_observer,
                         )
                         .await;
                         oldest_entry_time = None;
@@ -285,6 +425,8 @@ impl CtStorage {
                             batch_mutex.clone(),
                             merkle_tree.clone(),
                             batch_stats.clone(),
+                            &metrics,
+                            &merge_observer,
                         )
                         .await;
                         oldest_entry_time = None;
@@ -301,6 +443,8 @@ impl CtStorage {
                             batch_mutex.clone(),
                             merkle_tree.clone(),
                             batch_stats.clone(),
+                            &metrics,
+                            &merge_observer,
                         )
                         .await;
                     }
@@ -311,12 +455,53 @@ impl CtStorage {
         }
     }
 
+    /// Sign the SCT of each entry, the first at `starting_index`. Entries
+    /// refused an SCT are answered and dropped, and the rest signed again so
+    /// that leaf indices stay contiguous.
+    fn sign_batch(
+        mut entries: Vec<BatchEntry>,
+        starting_index: u64,
+    ) -> (Vec<BatchEntry>, Vec<SignedCertificateTimestamp>) {
+        loop {
+            let signed: Vec<_> = entries
+                .par_iter()
+                .enumerate()
+                .map(|(i, entry)| (entry.sct_callback)(starting_index + i as u64))
+                .collect();
+            if signed.iter().all(|sct| sct.is_ok()) {
+                return (entries, signed.into_iter().flatten().collect());
+            }
+
+            entries = entries
+                .into_iter()
+                .zip(signed)
+                .filter_map(|(entry, sct)| match sct {
+                    Ok(_) => Some(entry),
+                    Err(e) => {
+                        tracing::warn!("flush_batch: Entry refused an SCT: {}", e);
+                        let error = match e {
+                            crate::types::CtError::InvalidTimestamp(reason) => {
+                                StorageError::InvalidTimestamp(reason)
+                            }
+                            crate::types::CtError::Storage(e) => e,
+                            e => StorageError::InvalidFormat(e.to_string()),
+                        };
+                        let _ = entry.completion_tx.send(Err(error));
+                        None
+                    }
+                })
+                .collect();
+        }
+    }
+
     /// Flush a batch of entries atomically
     async fn flush_batch(
         entries: &mut Vec<BatchEntry>,
         batch_mutex: Arc<Mutex<()>>,
         merkle_tree: StorageBackedMerkleTree,
         batch_stats: Arc<Mutex<BatchStats>>,
+        metrics: &Metrics,
+        merge_observer: &MergeObserverSlot,
     ) {
         if entries.is_empty() {
             tracing::trace!("flush_batch: No entries to flush");
@@ -359,19 +544,20 @@ impl CtStorage {
         );
 
         let entries_vec: Vec<BatchEntry> = std::mem::take(entries);
-        let entries_count = entries_vec.len();
 
         let (processed_data, completion_info) = tokio::task::spawn_blocking(move || {
+            // CPU-intensive ECDSA signing
+            let (entries_vec, scts) = Self::sign_batch(entries_vec, starting_index);
+            let entries_count = entries_vec.len();
+
             let results: Vec<_> = entries_vec
                 .into_par_iter()
+                .zip(scts)
                 .enumerate()
-                .map(|(i, mut entry)| {
+                .map(|(i, (mut entry, sct))| {
                     let assigned_index = starting_index + i as u64;
                     entry.log_entry.index = assigned_index;
 
-                    // CPU-intensive ECDSA signing
-                    let sct = (entry.sct_callback)(assigned_index);
-
                     let leaf_data_with_index = LogEntry::compute_leaf_data_with_index(
                         &entry.log_entry.certificate,
                         entry.log_entry.entry_type,
@@ -563,7 +749,7 @@ impl CtStorage {
 
             // Prepare all additional data with correct indices
             let mut additional_data = Vec::new();
-            for (vec_idx, (_orig_idx, entry_data, cert_hash, sct, _log_entry)) in
+            for (vec_idx, (_orig_idx, entry_data, cert_hash, sct, log_entry)) in
                 entry_metadata.iter().enumerate()
             {
                 let index = starting_index + vec_idx as u64;
@@ -572,17 +758,6 @@ impl CtStorage {
                 entry_key.extend_from_slice(KeyPrefix::ENTRY);
                 entry_key.extend_from_slice(&index.to_be_bytes());
 
-                // For get-proof-by-hash API, we need to store hash->index mapping
-                use sha2::{Digest, Sha256};
-                let mut hasher = Sha256::new();
-                hasher.update([0x00]); // Leaf prefix
-                hasher.update(&leaf_data_vec[vec_idx]);
-                let computed_leaf_hash: [u8; 32] = hasher.finalize().into();
-
-                let mut hash_key = Vec::with_capacity(KeyPrefix::HASH_INDEX.len() + 32);
-                hash_key.extend_from_slice(KeyPrefix::HASH_INDEX);
-                hash_key.extend_from_slice(&computed_leaf_hash);
-
                 let mut cert_sct_key = Vec::with_capacity(KeyPrefix::CERT_SCT.len() + 32);
                 cert_sct_key.extend_from_slice(KeyPrefix::CERT_SCT);
                 cert_sct_key.extend_from_slice(cert_hash);
@@ -601,17 +776,21 @@ impl CtStorage {
 
                 // Store deduplicated entry
                 additional_data.push((entry_key.clone(), entry_data.clone()));
-                additional_data.push((hash_key.clone(), index.to_be_bytes().to_vec()));
                 additional_data.push((cert_sct_key, sct_data));
+                additional_data.push((
+                    leaf_type_index::leaf_type_key(log_entry.entry_type, index),
+                    Vec::new(),
+                ));
             }
 
             // Add only new certificates to additional_data
+            let compression = merkle_tree.leaf_compression();
             for (hash, cert_data) in cert_data_map.iter() {
                 if !existing_certs.contains(hash) {
                     let mut cert_key = Vec::with_capacity(KeyPrefix::CERT.len() + 32);
                     cert_key.extend_from_slice(KeyPrefix::CERT);
                     cert_key.extend_from_slice(hash);
-                    additional_data.push((cert_key, cert_data.clone()));
+                    additional_data.push((cert_key, compression.encode(cert_data)));
                 }
             }
 
@@ -638,6 +817,13 @@ impl CtStorage {
             push_result.is_ok()
         );
 
+        if push_result.is_ok() {
+            let observer = merge_observer.read().unwrap().clone();
+            if let Some(observer) = observer {
+                observer.merged().await;
+            }
+        }
+
         // Notify all entries with their results
         match push_result {
             Ok(_) => {
@@ -687,33 +873,61 @@ impl CtStorage {
 
         match &push_result {
             Ok(_) => {
-                crate::metrics::STORAGE_BATCHES_FLUSHED
+                metrics
+                    .storage_batches_flushed
                     .with_label_values(&["success"])
                     .inc();
-                crate::metrics::STORAGE_FLUSH_DURATION_SECONDS
+                metrics
+                    .storage_flush_duration_seconds
                     .with_label_values(&["success"])
                     .observe(flush_time_secs);
             }
             Err(_) => {
-                crate::metrics::STORAGE_BATCHES_FLUSHED
+                metrics
+                    .storage_batches_flushed
                     .with_label_values(&["failed"])
                     .inc();
-                crate::metrics::STORAGE_FLUSH_DURATION_SECONDS
+                metrics
+                    .storage_flush_duration_seconds
                     .with_label_values(&["failed"])
                     .observe(flush_time_secs);
             }
         }
 
-        crate::metrics::STORAGE_ENTRIES_PROCESSED
+        metrics
+            .storage_entries_processed
             .with_label_values(&["total"])
             .inc_by(batch_size as u64);
 
+        if push_result.is_ok() {
+            if let Ok(size) = merkle_tree.size().await {
+                metrics.merkle_tree_size.set(size as i64);
+            }
+
+            let precerts = entry_metadata
+                .iter()
+                .filter(|(_, _, _, _, log_entry)| {
+                    log_entry.entry_type == crate::types::LogEntryType::PrecertEntry
+                })
+                .count() as u64;
+            let x509 = metrics.log_entries_total.with_label_values(&["x509"]);
+            let precert = metrics.log_entries_total.with_label_values(&["precert"]);
+            x509.inc_by(entry_metadata.len() as u64 - precerts);
+            precert.inc_by(precerts);
+
+            if let Ok(mut history) = metrics.log_entries_history.lock() {
+                history.record(chrono::Utc::now().timestamp(), x509.get() + precert.get());
+            }
+        }
+
         // Update deduplication metrics
         if total_certs_in_batch > 0 {
-            crate::metrics::DEDUPLICATED_CERTIFICATES
+            metrics
+                .deduplicated_certificates
                 .with_label_values(&["checked"])
                 .inc_by(total_certs_in_batch as u64);
-            crate::metrics::DEDUPLICATED_CERTIFICATES
+            metrics
+                .deduplicated_certificates
                 .with_label_values(&["skipped"])
                 .inc_by(total_certs_skipped as u64);
         }
@@ -751,7 +965,8 @@ impl CtStorage {
         Ok(self.db.get(key).await?)
     }
 
-    /// Find index by hash
+    /// Find index by leaf hash in the legacy `hash:` index. New entries are
+    /// indexed by the tree; see `StorageBackedMerkleTree::get_leaf_index_by_hash`.
     pub async fn find_index_by_hash(&self, hash: &[u8]) -> Result<Option<u64>> {
         let mut hash_key = Vec::with_capacity(KeyPrefix::HASH_INDEX.len() + hash.len());
         hash_key.extend_from_slice(KeyPrefix::HASH_INDEX);
@@ -793,7 +1008,14 @@ impl CtStorage {
         key.extend_from_slice(KeyPrefix::CERT);
         key.extend_from_slice(cert_hash);
         match self.get(&key).await? {
-            Some(bytes) => Ok(Some(bytes.to_vec())),
+            // Certificates stored before values had a format byte are bare DER,
+            // which starts with a SEQUENCE tag no format byte uses
+            Some(bytes) if bytes.first() == Some(&DER_SEQUENCE_TAG) => Ok(Some(bytes.to_vec())),
+            Some(bytes) => compression::decode(&bytes)
+                .map(|cert| Some(cert.into_owned()))
+                .map_err(|e| {
+                    StorageError::InvalidFormat(format!("Failed to decode certificate: {}", e))
+                }),
             None => Ok(None),
         }
     }
@@ -802,15 +1024,11 @@ impl CtStorage {
     async fn get_chain_certificate(&self, cert_hash: &[u8]) -> Result<Option<Vec<u8>>> {
         let cache_key = cert_hash.to_vec();
         if let Some(entry) = self.chain_cache.get(&cache_key) {
-            crate::metrics::CACHE_HITS
-                .with_label_values(&["cert"])
-                .inc();
+            self.metrics.cache_hits.with_label_values(&["cert"]).inc();
             return Ok(Some(entry.value().as_ref().clone()));
         }
 
-        crate::metrics::CACHE_MISSES
-            .with_label_values(&["cert"])
-            .inc();
+        self.metrics.cache_misses.with_label_values(&["cert"]).inc();
 
         match self.get_certificate(cert_hash).await? {
             Some(cert) => {
@@ -929,13 +1147,12 @@ impl CtStorage {
     /// Get a full log entry by index (with reconstruction)
     pub async fn get_entry(&self, index: u64) -> Result<Option<LogEntry>> {
         if let Some(entry) = self.entry_cache.get(&index) {
-            crate::metrics::CACHE_HITS
-                .with_label_values(&["entry"])
-                .inc();
+            self.metrics.cache_hits.with_label_values(&["entry"]).inc();
             return Ok(Some(entry.value().as_ref().clone()));
         }
 
-        crate::metrics::CACHE_MISSES
+        self.metrics
+            .cache_misses
             .with_label_values(&["entry"])
             .inc();
 
@@ -957,6 +1174,7 @@ impl CtStorage {
         batch_sender: mpsc::Sender<BatchEntry>,
         batch_stats: Arc<Mutex<BatchStats>>,
         db: RateLimitedDb,
+        metrics: Arc<Metrics>,
     ) {
         let mut interval = tokio::time::interval(Duration::from_secs(5));
         interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
@@ -974,8 +1192,8 @@ impl CtStorage {
             let current_depth = max_capacity - capacity;
             let utilization_percent = (current_depth as f64 / max_capacity as f64) * 100.0;
 
-            crate::metrics::STORAGE_QUEUE_DEPTH.set(current_depth as i64);
-            crate::metrics::STORAGE_QUEUE_CAPACITY.set(max_capacity as i64);
+            metrics.storage_queue_depth.set(current_depth as i64);
+            metrics.storage_queue_capacity.set(max_capacity as i64);
 
             // Get batch statistics
             let stats = batch_stats.lock().await;
@@ -1069,7 +1287,8 @@ impl CtStorage {
             drop(stats);
 
             if utilization_percent > 90.0 {
-                crate::metrics::HEALTH_CHECK_STATUS
+                metrics
+                    .health_check_status
                     .with_label_values(&["storage_queue"])
                     .set(0.0);
                 tracing::warn!(
@@ -1077,12 +1296,14 @@ impl CtStorage {
                     utilization_percent as u32
                 );
             } else if utilization_percent > 75.0 {
-                crate::metrics::HEALTH_CHECK_STATUS
+                metrics
+                    .health_check_status
                     .with_label_values(&["storage_queue"])
                     .set(0.5);
                 tracing::warn!("Queue utilization high: {}%", utilization_percent as u32);
             } else {
-                crate::metrics::HEALTH_CHECK_STATUS
+                metrics
+                    .health_check_status
                     .with_label_values(&["storage_queue"])
                     .set(1.0);
             }
@@ -1093,8 +1314,9 @@ impl CtStorage {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::{sct::SctVersion, LogEntryType, LogId};
+    use crate::types::{extra_data::ExtraData, sct::SctVersion, LogEntryType, LogId};
     use chrono::{TimeZone, Utc};
+    use futures::{StreamExt, TryStreamExt};
     use object_store::memory::InMemory;
     use slatedb::Db;
 
@@ -1153,21 +1375,11 @@ mod tests {
     }
 
     fn create_test_log_id() -> LogId {
-        LogId::new(&[0x42; 32])
+        LogId::from_public_key_der(&[0x42; 32])
     }
 
     async fn create_test_storage(config: BatchConfig) -> (CtStorage, StorageBackedMerkleTree) {
-        let object_store = Arc::new(InMemory::new());
-        let db = Arc::new(Db::open("test", object_store).await.unwrap());
-        let rate_limited_db = RateLimitedDb::new(db.clone(), None); // No rate limiting for tests
-        let merkle_tree = StorageBackedMerkleTree::new(rate_limited_db.clone())
-            .await
-            .unwrap();
-        let storage = CtStorage::new(rate_limited_db, config, merkle_tree.clone())
-            .await
-            .unwrap();
-
-        (storage, merkle_tree)
+        CtStorage::with_memory_backend(config).await.unwrap()
     }
 
     #[tokio::test]
@@ -1184,7 +1396,7 @@ mod tests {
         let sct = create_test_sct(log_id, 1234567890000);
 
         let (index, _sct) = storage
-            .add_entry_batched(log_entry.clone(), cert_hash, move |_| sct)
+            .add_entry_batched(log_entry.clone(), cert_hash, move |_| Ok(sct.clone()))
             .await
             .unwrap();
         assert_eq!(index, 0);
@@ -1209,7 +1421,7 @@ mod tests {
         let sct = create_test_sct(log_id, 1234567890000);
 
         let (index, _sct) = storage
-            .add_entry_batched(log_entry.clone(), cert_hash, move |_| sct)
+            .add_entry_batched(log_entry.clone(), cert_hash, move |_| Ok(sct.clone()))
             .await
             .unwrap();
 
@@ -1261,7 +1473,7 @@ mod tests {
 
             let handle: tokio::task::JoinHandle<Result<u64>> = tokio::spawn(async move {
                 storage_clone
-                    .add_entry_batched(log_entry, cert_hash, move |_| sct)
+                    .add_entry_batched(log_entry, cert_hash, move |_| Ok(sct.clone()))
                     .await
                     .map(|(idx, _)| idx)
             });
@@ -1285,6 +1497,57 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_refused_sct_keeps_indices_contiguous() {
+        let config = BatchConfig {
+            max_batch_size: 3,
+            max_batch_timeout_ms: 1000,
+        };
+        let (storage, tree) = create_test_storage(config).await;
+
+        let log_id = create_test_log_id();
+        let mut handles = vec![];
+        for i in 0..3 {
+            let storage_clone = storage.clone();
+            let log_entry = create_test_log_entry(i);
+            let cert_hash = DeduplicatedLogEntry::hash_certificate(&log_entry.certificate);
+            let sct = create_test_sct(log_id.clone(), 1234567890000 + i);
+
+            handles.push(tokio::spawn(async move {
+                storage_clone
+                    .add_entry_batched(log_entry, cert_hash, move |index| {
+                        if i == 1 {
+                            return Err(crate::types::CtError::InvalidTimestamp("too old".into()));
+                        }
+                        // The SCT records the index it was signed for
+                        let mut sct = sct.clone();
+                        sct.extensions = index.to_be_bytes().to_vec();
+                        Ok(sct)
+                    })
+                    .await
+            }));
+        }
+
+        let mut indices = vec![];
+        for (i, handle) in handles.into_iter().enumerate() {
+            match handle.await.unwrap() {
+                Ok((index, sct)) => {
+                    assert_ne!(i, 1);
+                    assert_eq!(sct.extensions, index.to_be_bytes());
+                    indices.push(index);
+                }
+                Err(e) => {
+                    assert_eq!(i, 1);
+                    assert!(matches!(e, StorageError::InvalidTimestamp(_)));
+                }
+            }
+        }
+
+        indices.sort();
+        assert_eq!(indices, vec![0, 1]);
+        assert_eq!(tree.size().await.unwrap(), 2);
+    }
+
     #[tokio::test]
     async fn test_timeout_based_flush() {
         let config = BatchConfig {
@@ -1300,7 +1563,7 @@ mod tests {
 
         // Add single entry
         let (index, _sct) = storage
-            .add_entry_batched(log_entry, cert_hash, move |_| sct)
+            .add_entry_batched(log_entry, cert_hash, move |_| Ok(sct.clone()))
             .await
             .unwrap();
         assert_eq!(index, 0);
@@ -1336,11 +1599,11 @@ mod tests {
 
         // Add both entries
         let (index1, _sct1) = storage
-            .add_entry_batched(entry1, cert_hash, move |_| sct1)
+            .add_entry_batched(entry1, cert_hash, move |_| Ok(sct1.clone()))
             .await
             .unwrap();
         let (index2, _sct2) = storage
-            .add_entry_batched(entry2, cert_hash, move |_| sct2)
+            .add_entry_batched(entry2, cert_hash, move |_| Ok(sct2.clone()))
             .await
             .unwrap();
 
@@ -1363,7 +1626,7 @@ mod tests {
             max_batch_size: 1,
             max_batch_timeout_ms: 100,
         };
-        let (storage, _tree) = create_test_storage(config).await;
+        let (storage, tree) = create_test_storage(config).await;
 
         let log_entry = create_test_log_entry(0);
         let cert_hash = DeduplicatedLogEntry::hash_certificate(&log_entry.certificate);
@@ -1372,7 +1635,7 @@ mod tests {
 
         // Add entry
         let (index, _sct) = storage
-            .add_entry_batched(log_entry.clone(), cert_hash, move |_| sct)
+            .add_entry_batched(log_entry.clone(), cert_hash, move |_| Ok(sct.clone()))
             .await
             .unwrap();
 
@@ -1390,13 +1653,27 @@ mod tests {
         hasher.update(&leaf_data_with_index);
         let leaf_hash = hasher.finalize();
 
-        // Find index by hash
-        let found_index = storage.find_index_by_hash(&leaf_hash).await.unwrap();
+        // The tree indexes new leaves; the legacy index is no longer written
+        let found_index = tree.get_leaf_index_by_hash(&leaf_hash).await.unwrap();
         assert_eq!(found_index, Some(index));
+        assert_eq!(storage.find_index_by_hash(&leaf_hash).await.unwrap(), None);
+
+        // A legacy entry is still found
+        let mut legacy_key = KeyPrefix::HASH_INDEX.to_vec();
+        legacy_key.extend_from_slice(&[0xee; 32]);
+        storage
+            .db
+            .put(&legacy_key, &7u64.to_be_bytes())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.find_index_by_hash(&[0xee; 32]).await.unwrap(),
+            Some(7)
+        );
 
         // Try non-existent hash
         let random_hash = [0xff; 32];
-        let not_found = storage.find_index_by_hash(&random_hash).await.unwrap();
+        let not_found = tree.get_leaf_index_by_hash(&random_hash).await.unwrap();
         assert_eq!(not_found, None);
     }
 
@@ -1415,7 +1692,7 @@ mod tests {
 
         // Add entry
         storage
-            .add_entry_batched(log_entry.clone(), cert_hash, move |_| sct)
+            .add_entry_batched(log_entry.clone(), cert_hash, move |_| Ok(sct.clone()))
             .await
             .unwrap();
 
@@ -1439,7 +1716,7 @@ mod tests {
         let sct = create_test_sct(log_id, log_entry.timestamp.timestamp_millis() as u64);
 
         let (index, _sct) = storage
-            .add_entry_batched(log_entry.clone(), cert_hash, move |_| sct)
+            .add_entry_batched(log_entry.clone(), cert_hash, move |_| Ok(sct.clone()))
             .await
             .unwrap();
 
@@ -1476,6 +1753,189 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_compressed_certificates() {
+        use crate::test_utils::utils::{
+            create_test_certificate, create_test_certificate_with_serial,
+        };
+
+        let config = BatchConfig {
+            max_batch_size: 1,
+            max_batch_timeout_ms: 10,
+        };
+        let issuer = create_test_certificate();
+        let entries: Vec<LogEntry> = (0..6u8)
+            .map(|i| {
+                let timestamp = Utc.timestamp_millis_opt(1234567890000).unwrap();
+                let certificate = create_test_certificate_with_serial(i + 2);
+                LogEntry::new_with_timestamp(
+                    i as u64,
+                    certificate,
+                    Some(vec![issuer.clone()]),
+                    timestamp,
+                )
+            })
+            .collect();
+
+        async fn add_entries(storage: &CtStorage, entries: &[LogEntry]) {
+            for entry in entries {
+                let cert_hash = DeduplicatedLogEntry::hash_certificate(&entry.certificate);
+                let sct = create_test_sct(create_test_log_id(), 1234567890000);
+                storage
+                    .add_entry_batched(entry.clone(), cert_hash, move |_| Ok(sct.clone()))
+                    .await
+                    .unwrap();
+            }
+        }
+
+        // The first half is written uncompressed, the second half compressed
+        let db = Arc::new(Db::open("test", Arc::new(InMemory::new())).await.unwrap());
+        let rate_limited_db = RateLimitedDb::new(db.clone(), None);
+        let mut storage = None;
+        for (half, compression) in [Compression::None, Compression::Zstd(3)]
+            .into_iter()
+            .enumerate()
+        {
+            let tree = StorageBackedMerkleTree::open(
+                rate_limited_db.clone(),
+                0,
+                compression,
+                &Metrics::new(),
+            )
+            .await
+            .unwrap();
+            let half_storage = CtStorage::new(rate_limited_db.clone(), config.clone(), tree)
+                .await
+                .unwrap();
+            add_entries(&half_storage, &entries[half * 3..half * 3 + 3]).await;
+            storage = Some(half_storage);
+        }
+        let storage = storage.unwrap();
+
+        let cert_key = |certificate: &[u8]| {
+            let mut key = KeyPrefix::CERT.to_vec();
+            key.extend_from_slice(&DeduplicatedLogEntry::hash_certificate(certificate));
+            key
+        };
+        let stored = db
+            .get(&cert_key(&entries[5].certificate))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored[0], 1);
+        assert!(stored.len() < entries[5].certificate.len());
+        let stored = db
+            .get(&cert_key(&entries[0].certificate))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored[0], 0);
+
+        // A certificate stored before values had a format byte
+        db.put(&cert_key(&entries[1].certificate), &entries[1].certificate)
+            .await
+            .unwrap();
+
+        for entry in &entries {
+            let stored_entry = storage.get_entry(entry.index).await.unwrap().unwrap();
+            assert_eq!(stored_entry.certificate, entry.certificate);
+            assert_eq!(stored_entry.chain, entry.chain);
+        }
+
+        // Leaf hashes cover the uncompressed leaves, so the tree matches one
+        // that was never compressed
+        let (reference, reference_tree) = create_test_storage(config).await;
+        add_entries(&reference, &entries).await;
+        let tree = StorageBackedMerkleTree::new(rate_limited_db).await.unwrap();
+        for size in [3, 6] {
+            assert_eq!(
+                tree.root_at_size(size).await.unwrap().as_bytes(),
+                reference_tree.root_at_size(size).await.unwrap().as_bytes()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scan_entries() {
+        let config = BatchConfig {
+            max_batch_size: 2_000,
+            max_batch_timeout_ms: 10,
+        };
+        let (storage, _tree) = create_test_storage(config).await;
+
+        // Certificates start with a SEQUENCE tag so they can be stored bare
+        let certificate = |i: u64| {
+            let mut certificate = vec![DER_SEQUENCE_TAG];
+            certificate.extend_from_slice(&i.to_be_bytes());
+            certificate
+        };
+        // In chunks that fit the submission queue
+        for chunk in 0..5u64 {
+            let submissions = (chunk * 1_000..(chunk + 1) * 1_000).map(|i| {
+                let storage = &storage;
+                let timestamp = Utc.timestamp_millis_opt(1234567890000).unwrap();
+                let entry = LogEntry::new_with_timestamp(
+                    i,
+                    certificate(i),
+                    Some(vec![vec![0x04, 0x05]]),
+                    timestamp,
+                );
+                let cert_hash = DeduplicatedLogEntry::hash_certificate(&entry.certificate);
+                let sct = create_test_sct(create_test_log_id(), 1234567890000);
+                async move {
+                    storage
+                        .add_entry_batched(entry, cert_hash, move |_| Ok(sct.clone()))
+                        .await
+                        .unwrap()
+                }
+            });
+            join_all(submissions).await;
+        }
+
+        // Certificates stored before values had a format byte
+        for i in [0, 1_999, 4_321] {
+            let mut key = KeyPrefix::CERT.to_vec();
+            key.extend_from_slice(&DeduplicatedLogEntry::hash_certificate(&certificate(i)));
+            storage.db.put(&key, &certificate(i)).await.unwrap();
+        }
+
+        let mut expected = Vec::new();
+        for i in 0..5_000 {
+            let entry = storage.get_entry(i).await.unwrap().unwrap();
+            expected.push(ScannedEntry {
+                index: i,
+                leaf_input: entry.serialize().unwrap(),
+                extra_data: ExtraData::for_entry(&entry).unwrap().encode().unwrap(),
+            });
+        }
+
+        for batch_size in [1, 333, 1_000, 8_192] {
+            let scanned: Vec<_> = storage
+                .scan_entries(0, batch_size)
+                .try_collect()
+                .await
+                .unwrap();
+            assert_eq!(scanned, expected, "batch size {}", batch_size);
+        }
+        let tail: Vec<_> = storage.scan_entries(4_990, 3).try_collect().await.unwrap();
+        assert_eq!(tail, expected[4_990..]);
+
+        // Checkpoint mid-stream, then resume from the stored token
+        let head: Vec<_> = storage
+            .scan_entries(0, 500)
+            .take(1_234)
+            .try_collect()
+            .await
+            .unwrap();
+        let checkpoint = head.last().unwrap().resume_token().to_bytes();
+        let token = ScanToken::from_bytes(&checkpoint).unwrap();
+        assert_eq!(token.next_index(), 1_234);
+        let rest: Vec<_> = storage.resume_scan(token, 700).try_collect().await.unwrap();
+        assert_eq!([head, rest].concat(), expected);
+
+        assert!(ScanToken::from_bytes(&[0; 3]).is_err());
+    }
+
     #[tokio::test]
     async fn test_get_operations_on_non_existent_entries() {
         let config = BatchConfig::default();
@@ -1533,7 +1993,7 @@ mod tests {
 
             let handle: tokio::task::JoinHandle<Result<u64>> = tokio::spawn(async move {
                 storage_clone
-                    .add_entry_batched(entry, cert_hash, move |_| sct)
+                    .add_entry_batched(entry, cert_hash, move |_| Ok(sct.clone()))
                     .await
                     .map(|(idx, _)| idx)
             });
@@ -1585,7 +2045,7 @@ mod tests {
 
             let handle: tokio::task::JoinHandle<Result<u64>> = tokio::spawn(async move {
                 storage_clone
-                    .add_entry_batched(entry, cert_hash, move |_| sct)
+                    .add_entry_batched(entry, cert_hash, move |_| Ok(sct.clone()))
                     .await
                     .map(|(idx, _)| idx)
             });
@@ -1634,7 +2094,7 @@ mod tests {
         let sct1_timestamp = sct1.timestamp;
 
         let (index1, _sct1) = storage
-            .add_entry_batched(entry1, cert_hash, move |_| sct1)
+            .add_entry_batched(entry1, cert_hash, move |_| Ok(sct1.clone()))
             .await
             .unwrap();
 
@@ -1660,7 +2120,7 @@ mod tests {
         let sct2_timestamp = sct2.timestamp;
 
         let (index2, _sct2) = storage
-            .add_entry_batched(entry2, cert_hash, move |_| sct2)
+            .add_entry_batched(entry2, cert_hash, move |_| Ok(sct2.clone()))
             .await
             .unwrap();
 
@@ -1700,7 +2160,7 @@ mod tests {
 
         // Add entry
         let (index, _sct) = storage
-            .add_entry_batched(entry, cert_hash, move |_| sct)
+            .add_entry_batched(entry, cert_hash, move |_| Ok(sct.clone()))
             .await
             .unwrap();
 
@@ -1818,11 +2278,11 @@ mod tests {
         let sct2 = create_test_sct(log_id.clone(), timestamp2.timestamp_millis() as u64);
 
         let (index1, _sct1) = storage
-            .add_entry_batched(entry1, cert_hash1, move |_| sct1)
+            .add_entry_batched(entry1, cert_hash1, move |_| Ok(sct1.clone()))
             .await
             .unwrap();
         let (index2, _sct2) = storage
-            .add_entry_batched(entry2, cert_hash2, move |_| sct2)
+            .add_entry_batched(entry2, cert_hash2, move |_| Ok(sct2.clone()))
             .await
             .unwrap();
 
@@ -1843,4 +2303,23 @@ mod tests {
         assert_eq!(retrieved1.original_precert, Some(precert1));
         assert_eq!(retrieved2.original_precert, Some(precert2));
     }
+
+    #[tokio::test]
+    async fn test_get_many_preserves_key_order() {
+        let object_store = Arc::new(InMemory::new());
+        let db = Arc::new(Db::open("test", object_store).await.unwrap());
+        let db = RateLimitedDb::new(db, None);
+
+        db.put(b"b", b"2").await.unwrap();
+        db.put(b"a", b"1").await.unwrap();
+
+        let keys: [&[u8]; 4] = [b"b", b"c", b"a", b"b"];
+        let values = db.get_many(&keys).await.unwrap();
+        let values: Vec<_> = values.iter().map(|v| v.as_deref()).collect();
+        assert_eq!(
+            values,
+            vec![Some(&b"2"[..]), None, Some(&b"1"[..]), Some(&b"2"[..])]
+        );
+        assert_eq!(db.get_read_stats().0, 4);
+    }
 }
@@ -0,0 +1,394 @@
+use object_store::{path::Path, prefix::PrefixStore, ObjectStore};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+#[cfg(any(feature = "aws", feature = "azure", feature = "gcp"))]
+use object_store::{BackoffConfig, RetryConfig};
+#[cfg(any(feature = "aws", feature = "azure", feature = "gcp"))]
+use std::time::Duration;
+
+#[cfg(feature = "aws")]
+use object_store::aws::AmazonS3Builder;
+#[cfg(feature = "azure")]
+use object_store::azure::MicrosoftAzureBuilder;
+#[cfg(feature = "gcp")]
+use object_store::gcp::GoogleCloudStorageBuilder;
+use object_store::local::LocalFileSystem;
+
+const PROBE_DIR: &str = ".startup-probe";
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StorageConfig {
+    /// "aws", "azure", "gcs" or "local"
+    pub provider: String,
+    /// Place the log under this key prefix instead of the bucket root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    pub azure: Option<AzureConfig>,
+    pub aws: Option<AwsConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gcs: Option<GcsConfig>,
+    pub local: Option<LocalConfig>,
+}
+
+/// Credentials left unset are read from the `AZURE_*` environment variables
+/// or managed identity
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AzureConfig {
+    pub account: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_key: Option<String>,
+    pub container_name: String,
+}
+
+/// Credentials left unset are read from the `AWS_*` environment variables or
+/// the instance profile
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AwsConfig {
+    pub region: String,
+    pub bucket: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_key_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_access_key: Option<String>,
+    /// S3-compatible endpoint such as MinIO; requests then use path-style URLs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    /// Allow a plain http:// endpoint
+    #[serde(default)]
+    pub allow_http: bool,
+}
+
+/// Without a service account file, credentials come from
+/// `GOOGLE_APPLICATION_CREDENTIALS` or the instance metadata server
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GcsConfig {
+    pub bucket: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_account_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LocalConfig {
+    pub path: String,
+}
+
+#[derive(Error, Debug)]
+pub enum ObjectStoreError {
+    #[error("Invalid storage configuration: {0}")]
+    Config(String),
+
+    #[error("Storage provider '{provider}' is not compiled in; rebuild with --features {feature}")]
+    ProviderNotEnabled {
+        provider: String,
+        feature: &'static str,
+    },
+
+    #[error("Object store rejected the configured credentials: {0}")]
+    AuthFailed(object_store::Error),
+
+    #[error("Bucket or container does not exist: {0}")]
+    MissingBucket(String),
+
+    #[error("Object store probe failed: {0}")]
+    Probe(object_store::Error),
+}
+
+#[cfg(any(feature = "aws", feature = "azure", feature = "gcp"))]
+fn retry_config() -> RetryConfig {
+    RetryConfig {
+        backoff: BackoffConfig {
+            init_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+            base: 2.0,
+        },
+        max_retries: usize::MAX,
+        retry_timeout: Duration::from_secs(20),
+    }
+}
+
+fn missing_section(provider: &str) -> ObjectStoreError {
+    ObjectStoreError::Config(format!(
+        "[storage.{}] is required when provider is '{}'",
+        provider, provider
+    ))
+}
+
+/// Build the object store selected by `config.provider`, scoped to
+/// `config.prefix` when one is set
+pub fn build_object_store(
+    config: &StorageConfig,
+) -> Result<Arc<dyn ObjectStore>, ObjectStoreError> {
+    let store: Arc<dyn ObjectStore> = match config.provider.as_str() {
+        "aws" => build_aws(config.aws.as_ref().ok_or_else(|| missing_section("aws"))?)?,
+        "azure" => build_azure(
+            config
+                .azure
+                .as_ref()
+                .ok_or_else(|| missing_section("azure"))?,
+        )?,
+        "gcs" => build_gcs(config.gcs.as_ref().ok_or_else(|| missing_section("gcs"))?)?,
+        "local" => {
+            let local = config
+                .local
+                .as_ref()
+                .ok_or_else(|| missing_section("local"))?;
+            if !std::path::Path::new(&local.path).is_dir() {
+                return Err(ObjectStoreError::MissingBucket(format!(
+                    "local directory {} does not exist",
+                    local.path
+                )));
+            }
+            Arc::new(
+                LocalFileSystem::new_with_prefix(&local.path)
+                    .map_err(|e| ObjectStoreError::Config(e.to_string()))?,
+            )
+        }
+        other => {
+            return Err(ObjectStoreError::Config(format!(
+                "Unsupported storage provider: {}",
+                other
+            )))
+        }
+    };
+
+    Ok(
+        match config.prefix.as_deref().map(|p| p.trim_matches('/')) {
+            Some(prefix) if !prefix.is_empty() => Arc::new(PrefixStore::new(store, prefix)),
+            _ => store,
+        },
+    )
+}
+
+#[cfg(feature = "aws")]
+fn build_aws(config: &AwsConfig) -> Result<Arc<dyn ObjectStore>, ObjectStoreError> {
+    let mut builder = AmazonS3Builder::from_env()
+        .with_region(&config.region)
+        .with_bucket_name(&config.bucket)
+        .with_allow_http(config.allow_http)
+        .with_retry(retry_config());
+    if let Some(access_key_id) = &config.access_key_id {
+        builder = builder.with_access_key_id(access_key_id);
+    }
+    if let Some(secret_access_key) = &config.secret_access_key {
+        builder = builder.with_secret_access_key(secret_access_key);
+    }
+    if let Some(endpoint) = &config.endpoint {
+        builder = builder
+            .with_endpoint(endpoint)
+            .with_virtual_hosted_style_request(false);
+    }
+
+    let store = builder
+        .build()
+        .map_err(|e| ObjectStoreError::Config(e.to_string()))?;
+    Ok(Arc::new(store))
+}
+
+#[cfg(not(feature = "aws"))]
+fn build_aws(_config: &AwsConfig) -> Result<Arc<dyn ObjectStore>, ObjectStoreError> {
+    Err(ObjectStoreError::ProviderNotEnabled {
+        provider: "aws".into(),
+        feature: "aws",
+    })
+}
+
+#[cfg(feature = "azure")]
+fn build_azure(config: &AzureConfig) -> Result<Arc<dyn ObjectStore>, ObjectStoreError> {
+    let mut builder = MicrosoftAzureBuilder::from_env()
+        .with_account(&config.account)
+        .with_container_name(&config.container_name)
+        .with_retry(retry_config());
+    if let Some(access_key) = &config.access_key {
+        builder = builder.with_access_key(access_key);
+    }
+
+    let store = builder
+        .build()
+        .map_err(|e| ObjectStoreError::Config(e.to_string()))?;
+    Ok(Arc::new(store))
+}
+
+#[cfg(not(feature = "azure"))]
+fn build_azure(_config: &AzureConfig) -> Result<Arc<dyn ObjectStore>, ObjectStoreError> {
+    Err(ObjectStoreError::ProviderNotEnabled {
+        provider: "azure".into(),
+        feature: "azure",
+    })
+}
+
+#[cfg(feature = "gcp")]
+fn build_gcs(config: &GcsConfig) -> Result<Arc<dyn ObjectStore>, ObjectStoreError> {
+    let mut builder = GoogleCloudStorageBuilder::from_env()
+        .with_bucket_name(&config.bucket)
+        .with_retry(retry_config());
+    if let Some(path) = &config.service_account_path {
+        builder = builder.with_service_account_path(path);
+    }
+
+    let store = builder
+        .build()
+        .map_err(|e| ObjectStoreError::Config(e.to_string()))?;
+    Ok(Arc::new(store))
+}
+
+#[cfg(not(feature = "gcp"))]
+fn build_gcs(_config: &GcsConfig) -> Result<Arc<dyn ObjectStore>, ObjectStoreError> {
+    Err(ObjectStoreError::ProviderNotEnabled {
+        provider: "gcs".into(),
+        feature: "gcp",
+    })
+}
+
+/// Write, read back and delete a small object so that bad credentials or a
+/// missing bucket fail at startup rather than on the first flush
+pub async fn probe_object_store(store: &dyn ObjectStore) -> Result<(), ObjectStoreError> {
+    let nonce = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let path = Path::from(format!("{}/{}", PROBE_DIR, nonce));
+    let payload = nonce.to_be_bytes();
+
+    store
+        .put(&path, payload.to_vec().into())
+        .await
+        .map_err(classify)?;
+
+    let read = store
+        .get(&path)
+        .await
+        .map_err(classify)?
+        .bytes()
+        .await
+        .map_err(classify)?;
+    if read.as_ref() != payload {
+        return Err(ObjectStoreError::Probe(object_store::Error::Generic {
+            store: "probe",
+            source: format!("read back {} bytes that differ from the write", read.len()).into(),
+        }));
+    }
+
+    store.delete(&path).await.map_err(classify)
+}
+
+fn classify(e: object_store::Error) -> ObjectStoreError {
+    match e {
+        object_store::Error::Unauthenticated { .. }
+        | object_store::Error::PermissionDenied { .. } => ObjectStoreError::AuthFailed(e),
+        // The probe object was just written, so a 404 means the bucket itself is missing
+        object_store::Error::NotFound { .. } => ObjectStoreError::MissingBucket(e.to_string()),
+        e => ObjectStoreError::Probe(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_config(path: &std::path::Path, prefix: Option<&str>) -> StorageConfig {
+        StorageConfig {
+            provider: "local".into(),
+            prefix: prefix.map(String::from),
+            azure: None,
+            aws: None,
+            gcs: None,
+            local: Some(LocalConfig {
+                path: path.to_string_lossy().into_owned(),
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_local_store_probe() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = build_object_store(&local_config(dir.path(), Some("/logs/a/"))).unwrap();
+
+        probe_object_store(store.as_ref()).await.unwrap();
+
+        store
+            .put(&Path::from("ct_log/marker"), b"x".to_vec().into())
+            .await
+            .unwrap();
+        assert!(dir.path().join("logs/a/ct_log/marker").is_file());
+        // The probe cleans up after itself
+        assert!(std::fs::read_dir(dir.path().join("logs/a").join(PROBE_DIR))
+            .map(|entries| entries.count() == 0)
+            .unwrap_or(true));
+    }
+
+    #[test]
+    fn test_configuration_errors() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let missing = build_object_store(&local_config(&dir.path().join("absent"), None));
+        assert!(matches!(missing, Err(ObjectStoreError::MissingBucket(_))));
+
+        let mut unknown = local_config(dir.path(), None);
+        unknown.provider = "tape".into();
+        assert!(matches!(
+            build_object_store(&unknown),
+            Err(ObjectStoreError::Config(_))
+        ));
+
+        let mut no_section = local_config(dir.path(), None);
+        no_section.provider = "gcs".into();
+        assert!(matches!(
+            build_object_store(&no_section),
+            Err(ObjectStoreError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_classify_distinguishes_auth_from_missing_bucket() {
+        let denied = object_store::Error::PermissionDenied {
+            path: "p".into(),
+            source: "403".into(),
+        };
+        assert!(matches!(classify(denied), ObjectStoreError::AuthFailed(_)));
+
+        let not_found = object_store::Error::NotFound {
+            path: "p".into(),
+            source: "404".into(),
+        };
+        assert!(matches!(
+            classify(not_found),
+            ObjectStoreError::MissingBucket(_)
+        ));
+    }
+
+    /// Runs against an S3-compatible server such as MinIO when
+    /// COMPACTLOG_TEST_S3_ENDPOINT and COMPACTLOG_TEST_S3_BUCKET are set;
+    /// credentials come from AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY
+    #[cfg(feature = "aws")]
+    #[tokio::test]
+    async fn test_s3_compatible_probe() {
+        let (Ok(endpoint), Ok(bucket)) = (
+            std::env::var("COMPACTLOG_TEST_S3_ENDPOINT"),
+            std::env::var("COMPACTLOG_TEST_S3_BUCKET"),
+        ) else {
+            return;
+        };
+
+        let config = StorageConfig {
+            provider: "aws".into(),
+            prefix: Some("compactlog-test".into()),
+            azure: None,
+            aws: Some(AwsConfig {
+                region: "us-east-1".into(),
+                bucket,
+                access_key_id: None,
+                secret_access_key: None,
+                allow_http: endpoint.starts_with("http://"),
+                endpoint: Some(endpoint),
+            }),
+            gcs: None,
+            local: None,
+        };
+
+        let store = build_object_store(&config).unwrap();
+        probe_object_store(store.as_ref()).await.unwrap();
+    }
+}
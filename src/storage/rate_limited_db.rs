@@ -1,8 +1,15 @@
+use super::StorageError;
 use crate::rate_limiter::{ReadPermit, ReadRateLimiter};
 use bytes::Bytes;
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
 use slatedb::{config::WriteOptions, Db, WriteBatch};
+use std::borrow::Cow;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+
+/// Reads in flight at once in [`RateLimitedDb::get_many`]
+const MULTI_GET_CONCURRENCY: usize = 64;
 
 /// A wrapper around SlateDB that enforces rate limiting on read operations
 #[derive(Clone)]
@@ -11,6 +18,67 @@ pub struct RateLimitedDb {
     read_rate_limiter: Option<ReadRateLimiter>,
     read_ops: Arc<AtomicU64>,
     bytes_read: Arc<AtomicU64>,
+    /// Prepended to every key read or written through this handle
+    key_prefix: Arc<[u8]>,
+    /// Writes made through this handle, when it was made with
+    /// [`RateLimitedDb::with_write_log`]
+    write_log: Option<Arc<Mutex<Vec<Vec<LoggedWrite>>>>>,
+}
+
+/// One write of a logged batch, keyed from the database root so it can be
+/// replayed on another database whatever handle made it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LoggedWrite {
+    Put { key: Vec<u8>, value: Vec<u8> },
+    Delete { key: Vec<u8> },
+}
+
+/// `key` under `prefix`, borrowed when there is no prefix
+fn prefixed<'a>(prefix: &[u8], key: &'a [u8]) -> Cow<'a, [u8]> {
+    if prefix.is_empty() {
+        Cow::Borrowed(key)
+    } else {
+        Cow::Owned([prefix, key].concat())
+    }
+}
+
+/// A write batch whose keys get the key prefix of the handle it belongs to
+pub struct DbBatch {
+    batch: WriteBatch,
+    key_prefix: Arc<[u8]>,
+    /// Copy of the writes, kept when the batch comes from a logged handle
+    log: Option<Vec<LoggedWrite>>,
+}
+
+impl DbBatch {
+    pub fn put<K: AsRef<[u8]>, V: AsRef<[u8]>>(&mut self, key: K, value: V) {
+        let key = prefixed(&self.key_prefix, key.as_ref());
+        if let Some(log) = &mut self.log {
+            log.push(LoggedWrite::Put {
+                key: key.to_vec(),
+                value: value.as_ref().to_vec(),
+            });
+        }
+        self.batch.put(key, value.as_ref());
+    }
+
+    pub fn delete<K: AsRef<[u8]>>(&mut self, key: K) {
+        let key = prefixed(&self.key_prefix, key.as_ref());
+        if let Some(log) = &mut self.log {
+            log.push(LoggedWrite::Delete { key: key.to_vec() });
+        }
+        self.batch.delete(key);
+    }
+
+    /// This batch, with writes from now on going under the key prefix of
+    /// `db`. Lets one atomic batch span keyspaces of the same database.
+    pub fn rebind(self, db: &RateLimitedDb) -> Self {
+        Self {
+            batch: self.batch,
+            key_prefix: db.key_prefix.clone(),
+            log: self.log,
+        }
+    }
 }
 
 impl RateLimitedDb {
@@ -21,6 +89,98 @@ impl RateLimitedDb {
             read_rate_limiter,
             read_ops: Arc::new(AtomicU64::new(0)),
             bytes_read: Arc::new(AtomicU64::new(0)),
+            key_prefix: Arc::from(&[][..]),
+            write_log: None,
+        }
+    }
+
+    /// A handle to the same database that keeps a copy of every batch and
+    /// put written through it, or through handles derived from it, until
+    /// [`Self::take_logged_writes`]. Used to replicate writes to a standby.
+    pub fn with_write_log(&self) -> Self {
+        Self {
+            write_log: Some(Arc::new(Mutex::new(Vec::new()))),
+            ..self.clone()
+        }
+    }
+
+    /// Batches written through this handle since the last call, oldest first.
+    /// Empty for a handle without a write log.
+    pub fn take_logged_writes(&self) -> Vec<Vec<LoggedWrite>> {
+        match &self.write_log {
+            Some(write_log) => std::mem::take(&mut *write_log.lock().unwrap()),
+            None => Vec::new(),
+        }
+    }
+
+    /// `key`, taken from the database root, relative to this handle's prefix,
+    /// or `None` if it lies outside this handle's keyspace
+    pub fn relative_key<'a>(&self, key: &'a [u8]) -> Option<&'a [u8]> {
+        key.strip_prefix(&self.key_prefix[..])
+    }
+
+    /// Apply a batch logged by another database as one atomic write. Keys
+    /// are taken from the database root, and the batch is refused without
+    /// writing anything if one lies outside this handle's keyspace.
+    pub async fn write_logged(&self, writes: &[LoggedWrite]) -> Result<(), StorageError> {
+        let mut batch = WriteBatch::new();
+        for write in writes {
+            let key = match write {
+                LoggedWrite::Put { key, .. } | LoggedWrite::Delete { key } => key,
+            };
+            if self.relative_key(key).is_none() {
+                return Err(StorageError::InvalidFormat(format!(
+                    "Logged write to {} is outside this keyspace",
+                    hex::encode(key)
+                )));
+            }
+            match write {
+                LoggedWrite::Put { key, value } => batch.put(key, value),
+                LoggedWrite::Delete { key } => batch.delete(key),
+            }
+        }
+        let write_options = WriteOptions {
+            await_durable: false,
+        };
+        self.db
+            .write_with_options(batch, &write_options)
+            .await
+            .map_err(StorageError::SlateDb)?;
+        self.db.flush().await.map_err(StorageError::SlateDb)
+    }
+
+    /// A handle to the keyspace under `prefix` of the same database, sharing
+    /// its rate limit and read counters. Keys this handle returns from scans
+    /// have the prefix removed.
+    pub fn with_key_prefix(&self, prefix: &[u8]) -> Self {
+        Self {
+            key_prefix: Arc::from([&self.key_prefix[..], prefix].concat()),
+            ..self.clone()
+        }
+    }
+
+    pub fn key_prefix(&self) -> &[u8] {
+        &self.key_prefix
+    }
+
+    /// Whether `other` writes to the same database, so a batch of one can be
+    /// written through the other
+    pub fn shares_db_with(&self, other: &RateLimitedDb) -> bool {
+        Arc::ptr_eq(&self.db, &other.db)
+    }
+
+    /// Identifies the database behind this handle, shared by every handle
+    /// derived from it
+    pub(crate) fn db_id(&self) -> usize {
+        Arc::as_ptr(&self.db) as usize
+    }
+
+    /// An empty batch for [`Self::write_batch`]
+    pub fn batch(&self) -> DbBatch {
+        DbBatch {
+            batch: WriteBatch::new(),
+            key_prefix: self.key_prefix.clone(),
+            log: self.write_log.as_ref().map(|_| Vec::new()),
         }
     }
 
@@ -34,7 +194,7 @@ impl RateLimitedDb {
 
         self.read_ops.fetch_add(1, Ordering::Relaxed);
 
-        let result = self.db.get(key).await?;
+        let result = self.db.get(prefixed(&self.key_prefix, key)).await?;
 
         if let Some(ref value) = result {
             self.bytes_read
@@ -44,18 +204,88 @@ impl RateLimitedDb {
         Ok(result)
     }
 
+    /// Values for `keys`, in the order given. Reads are issued in key order,
+    /// so neighbouring keys are served from the same SST blocks; each key
+    /// counts as one rate-limited read.
+    pub async fn get_many<K: AsRef<[u8]>>(
+        &self,
+        keys: &[K],
+    ) -> Result<Vec<Option<Bytes>>, slatedb::SlateDBError> {
+        let mut order: Vec<usize> = (0..keys.len()).collect();
+        order.sort_unstable_by(|&a, &b| keys[a].as_ref().cmp(keys[b].as_ref()));
+
+        let sorted: Vec<_> = stream::iter(order.iter().copied())
+            .map(|i| self.get(keys[i].as_ref()))
+            .buffered(MULTI_GET_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        let mut values = vec![None; keys.len()];
+        for (i, value) in order.into_iter().zip(sorted) {
+            values[i] = value;
+        }
+        Ok(values)
+    }
+
+    /// Key-value pairs in `[start, end)`, in key order. Counts as a single
+    /// rate-limited read.
+    pub async fn scan(
+        &self,
+        start: &[u8],
+        end: &[u8],
+    ) -> Result<Vec<(Bytes, Bytes)>, slatedb::SlateDBError> {
+        let _permit: Option<ReadPermit> = if let Some(ref limiter) = self.read_rate_limiter {
+            limiter.acquire().await
+        } else {
+            None
+        };
+
+        self.read_ops.fetch_add(1, Ordering::Relaxed);
+
+        let start = prefixed(&self.key_prefix, start).into_owned();
+        let end = prefixed(&self.key_prefix, end).into_owned();
+        let mut iter = self.db.scan(start..end).await?;
+        let mut entries = Vec::new();
+        while let Some(kv) = iter.next().await? {
+            self.bytes_read
+                .fetch_add((kv.key.len() + kv.value.len()) as u64, Ordering::Relaxed);
+            entries.push((kv.key.slice(self.key_prefix.len()..), kv.value));
+        }
+
+        Ok(entries)
+    }
+
     /// Put a value into the database (not rate limited)
     pub async fn put(&self, key: &[u8], value: &[u8]) -> Result<(), slatedb::SlateDBError> {
-        self.db.put(key, value).await
+        let key = prefixed(&self.key_prefix, key);
+        self.db.put(&key, value).await?;
+        self.log_writes(vec![LoggedWrite::Put {
+            key: key.into_owned(),
+            value: value.to_vec(),
+        }]);
+        Ok(())
     }
 
-    /// Write a batch of operations (not rate limited)
-    pub async fn write_batch(&self, batch: WriteBatch) -> Result<(), slatedb::SlateDBError> {
+    /// Write a batch of operations (not rate limited). The batch may come
+    /// from any handle to the same database.
+    pub async fn write_batch(&self, batch: DbBatch) -> Result<(), slatedb::SlateDBError> {
         let write_options = WriteOptions {
             await_durable: false,
         };
-        self.db.write_with_options(batch, &write_options).await?;
-        self.db.flush().await
+        self.db
+            .write_with_options(batch.batch, &write_options)
+            .await?;
+        self.db.flush().await?;
+        if let Some(writes) = batch.log {
+            self.log_writes(writes);
+        }
+        Ok(())
+    }
+
+    fn log_writes(&self, writes: Vec<LoggedWrite>) {
+        if let Some(write_log) = &self.write_log {
+            write_log.lock().unwrap().push(writes);
+        }
     }
 
     pub fn get_read_stats(&self) -> (u64, u64) {
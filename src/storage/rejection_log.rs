@@ -0,0 +1,402 @@
+use super::{KeyPrefix, RateLimitedDb, Result, StorageError};
+use crate::types::CtError;
+use crate::validation::RejectionCheck;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+/// Client buckets kept before stale ones are dropped
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Why a submitted chain was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectionReason {
+    /// The chain or one of its certificates could not be parsed
+    Malformed,
+    /// The chain does not lead to an accepted root
+    UntrustedRoot,
+    /// A certificate's signature does not verify with its issuer's key
+    BadSignature,
+    /// The chain is well formed but breaks log policy (algorithms, temporal
+    /// window, length, precertificate rules)
+    PolicyViolation,
+    /// Validation failed for reasons unrelated to the chain
+    Internal,
+}
+
+impl RejectionReason {
+    /// Map a validation error to a reason, coarsening the check it failed
+    pub fn classify(error: &CtError) -> Self {
+        match RejectionCheck::classify(error) {
+            RejectionCheck::Malformed => RejectionReason::Malformed,
+            RejectionCheck::UntrustedRoot => RejectionReason::UntrustedRoot,
+            RejectionCheck::BadSignature => RejectionReason::BadSignature,
+            RejectionCheck::Internal => RejectionReason::Internal,
+            RejectionCheck::UnorderedChain
+            | RejectionCheck::ChainLimit
+            | RejectionCheck::SignatureAlgorithm
+            | RejectionCheck::Expired
+            | RejectionCheck::OutsideShard
+            | RejectionCheck::PrecertPoison
+            | RejectionCheck::PrecertIssuer
+            | RejectionCheck::Other => RejectionReason::PolicyViolation,
+        }
+    }
+
+    /// Label used for the rejection metrics
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RejectionReason::Malformed => "malformed",
+            RejectionReason::UntrustedRoot => "untrusted_root",
+            RejectionReason::BadSignature => "bad_signature",
+            RejectionReason::PolicyViolation => "policy_violation",
+            RejectionReason::Internal => "internal",
+        }
+    }
+}
+
+/// One rejected submission as stored in the rejection log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RejectionRecord {
+    pub timestamp_ms: u64,
+    pub client_ip: Option<IpAddr>,
+    pub reason: RejectionReason,
+    /// SHA-256 over the submitted certificates' DER, concatenated in order
+    pub chain_sha256: [u8; 32],
+    /// Leading certificates of the chain, as many as fit in the DER budget
+    pub chain: Vec<Vec<u8>>,
+}
+
+struct RejectionLogState {
+    /// Sequence number of the oldest retained record
+    oldest: u64,
+    /// Sequence number the next record gets
+    next: u64,
+    /// Records written per client in the current minute
+    clients: HashMap<Option<IpAddr>, (u64, u32)>,
+}
+
+/// Bounded log of rejected submissions, kept under its own key prefix and
+/// outside the Merkle tree. Records are numbered in arrival order and the
+/// oldest are evicted once `max_records` is exceeded.
+pub struct RejectionLog {
+    db: RateLimitedDb,
+    max_records: u64,
+    max_der_bytes: usize,
+    per_client_per_minute: u32,
+    state: Mutex<RejectionLogState>,
+}
+
+fn record_key(seq: u64) -> Vec<u8> {
+    let mut key = Vec::with_capacity(KeyPrefix::REJECTION.len() + 8);
+    key.extend_from_slice(KeyPrefix::REJECTION);
+    key.extend_from_slice(&seq.to_be_bytes());
+    key
+}
+
+fn seq_from_key(key: &[u8]) -> Result<u64> {
+    key.get(KeyPrefix::REJECTION.len()..)
+        .and_then(|bytes| <[u8; 8]>::try_from(bytes).ok())
+        .map(u64::from_be_bytes)
+        .ok_or_else(|| StorageError::InvalidFormat("Invalid rejection log key".into()))
+}
+
+impl RejectionLog {
+    /// Open the log, continuing the numbering of records already stored
+    pub async fn open(
+        db: RateLimitedDb,
+        max_records: u64,
+        max_der_bytes: usize,
+        per_client_per_minute: u32,
+    ) -> Result<Self> {
+        let existing = db
+            .scan(&record_key(0), &record_key(u64::MAX))
+            .await
+            .map_err(StorageError::SlateDb)?;
+        let (oldest, next) = match (existing.first(), existing.last()) {
+            (Some((first, _)), Some((last, _))) => (seq_from_key(first)?, seq_from_key(last)? + 1),
+            _ => (0, 0),
+        };
+
+        Ok(Self {
+            db,
+            max_records: max_records.max(1),
+            max_der_bytes,
+            per_client_per_minute,
+            state: Mutex::new(RejectionLogState {
+                oldest,
+                next,
+                clients: HashMap::new(),
+            }),
+        })
+    }
+
+    /// Store a record of a rejected chain. Returns false without writing when
+    /// `client_ip` already used up its records for this minute.
+    pub async fn record(
+        &self,
+        client_ip: Option<IpAddr>,
+        reason: RejectionReason,
+        chain: &[Vec<u8>],
+    ) -> Result<bool> {
+        let timestamp_ms = chrono::Utc::now().timestamp_millis() as u64;
+        let minute = timestamp_ms / 60_000;
+
+        // Only the numbering is decided under the lock; the write happens
+        // outside it so one slow write does not hold up every rejection
+        let (seq, evicted) = {
+            let mut state = self.state.lock().unwrap();
+
+            if state.clients.len() >= MAX_TRACKED_CLIENTS {
+                state.clients.retain(|_, (bucket, _)| *bucket == minute);
+            }
+            let (bucket, count) = state.clients.entry(client_ip).or_insert((minute, 0));
+            if *bucket != minute {
+                *bucket = minute;
+                *count = 0;
+            }
+            if *count >= self.per_client_per_minute {
+                return Ok(false);
+            }
+            *count += 1;
+
+            let seq = state.next;
+            state.next += 1;
+            let evicted = state.oldest
+                ..state
+                    .oldest
+                    .max(state.next.saturating_sub(self.max_records));
+            state.oldest = evicted.end;
+            (seq, evicted)
+        };
+
+        let mut hasher = Sha256::new();
+        for cert in chain {
+            hasher.update(cert);
+        }
+
+        let mut der_budget = self.max_der_bytes;
+        let kept: Vec<Vec<u8>> = chain
+            .iter()
+            .take_while(|cert| match der_budget.checked_sub(cert.len()) {
+                Some(remaining) => {
+                    der_budget = remaining;
+                    true
+                }
+                None => false,
+            })
+            .cloned()
+            .collect();
+
+        let record = RejectionRecord {
+            timestamp_ms,
+            client_ip,
+            reason,
+            chain_sha256: hasher.finalize().into(),
+            chain: kept,
+        };
+        let value =
+            postcard::to_stdvec(&record).map_err(|e| StorageError::InvalidFormat(e.to_string()))?;
+
+        // A failed write leaves a gap in the numbering, which readers skip
+        let mut batch = self.db.batch();
+        batch.put(record_key(seq), &value);
+        for oldest in evicted {
+            batch.delete(record_key(oldest));
+        }
+        self.db
+            .write_batch(batch)
+            .await
+            .map_err(StorageError::SlateDb)?;
+
+        Ok(true)
+    }
+
+    /// Retained records with a timestamp at or after `since_ms`, oldest first,
+    /// at most `limit` of them
+    pub async fn records_since(&self, since_ms: u64, limit: usize) -> Result<Vec<RejectionRecord>> {
+        let (oldest, next) = {
+            let state = self.state.lock().unwrap();
+            (state.oldest, state.next)
+        };
+
+        let mut records = Vec::new();
+        for (_, value) in self
+            .db
+            .scan(&record_key(oldest), &record_key(next))
+            .await
+            .map_err(StorageError::SlateDb)?
+        {
+            let record: RejectionRecord = postcard::from_bytes(&value)
+                .map_err(|e| StorageError::InvalidFormat(e.to_string()))?;
+            if record.timestamp_ms >= since_ms {
+                records.push(record);
+                if records.len() >= limit {
+                    break;
+                }
+            }
+        }
+
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::slatedb_backed_tree::tests::create_test_db;
+
+    #[test]
+    fn test_classify_validator_errors() {
+        use crate::validation::ValidationError;
+
+        let cases = [
+            (
+                ValidationError::UnparsableCertificate {
+                    index: 1,
+                    reason: "ASN.1 error".into(),
+                },
+                RejectionReason::Malformed,
+            ),
+            (ValidationError::EmptyChain, RejectionReason::Malformed),
+            (
+                ValidationError::UntrustedRoot {
+                    issuer: "CN=Unknown".into(),
+                },
+                RejectionReason::UntrustedRoot,
+            ),
+            (
+                ValidationError::NoTrustedRoots,
+                RejectionReason::UntrustedRoot,
+            ),
+            (
+                ValidationError::InvalidSignature { index: 0 },
+                RejectionReason::BadSignature,
+            ),
+            (
+                ValidationError::InvalidRootSignature,
+                RejectionReason::BadSignature,
+            ),
+            (
+                ValidationError::DisallowedSignatureAlgorithm {
+                    index: 0,
+                    oid: "1.2.840.113549.1.1.5".into(),
+                },
+                RejectionReason::PolicyViolation,
+            ),
+            (
+                ValidationError::ExpiresAfterWindow {
+                    not_after: chrono::Utc::now(),
+                    window_end: chrono::Utc::now(),
+                },
+                RejectionReason::PolicyViolation,
+            ),
+            (
+                ValidationError::ChainTooLong {
+                    length: 12,
+                    max: 10,
+                },
+                RejectionReason::PolicyViolation,
+            ),
+        ];
+        for (error, expected) in cases {
+            let message = error.to_string();
+            assert_eq!(
+                RejectionReason::classify(&error.into()),
+                expected,
+                "{}",
+                message
+            );
+        }
+
+        assert_eq!(
+            RejectionReason::classify(&CtError::InvalidCertificate("bad".into())),
+            RejectionReason::Malformed
+        );
+        assert_eq!(
+            RejectionReason::classify(&CtError::Internal("task failed".into())),
+            RejectionReason::Internal
+        );
+    }
+
+    #[tokio::test]
+    async fn test_oldest_records_evicted() {
+        let db = create_test_db().await;
+        let log = RejectionLog::open(db.clone(), 3, 1024, u32::MAX)
+            .await
+            .unwrap();
+
+        for i in 0..5u8 {
+            let ip = Some(IpAddr::from([10, 0, 0, i]));
+            assert!(log
+                .record(ip, RejectionReason::BadSignature, &[vec![i]])
+                .await
+                .unwrap());
+        }
+
+        let records = log.records_since(0, usize::MAX).await.unwrap();
+        let chains: Vec<_> = records.iter().map(|r| r.chain.clone()).collect();
+        assert_eq!(chains, vec![vec![vec![2]], vec![vec![3]], vec![vec![4]]]);
+        assert!(db.get(&record_key(1)).await.unwrap().is_none());
+
+        // Reopening continues the numbering and keeps evicting from the front
+        let log = RejectionLog::open(db.clone(), 3, 1024, u32::MAX)
+            .await
+            .unwrap();
+        log.record(None, RejectionReason::Malformed, &[vec![5]])
+            .await
+            .unwrap();
+        let records = log.records_since(0, usize::MAX).await.unwrap();
+        let chains: Vec<_> = records.iter().map(|r| r.chain.clone()).collect();
+        assert_eq!(chains, vec![vec![vec![3]], vec![vec![4]], vec![vec![5]]]);
+    }
+
+    #[tokio::test]
+    async fn test_records_rate_limited_per_client() {
+        let log = RejectionLog::open(create_test_db().await, 100, 1024, 2)
+            .await
+            .unwrap();
+        let noisy = Some(IpAddr::from([192, 0, 2, 1]));
+        let quiet = Some(IpAddr::from([192, 0, 2, 2]));
+
+        let mut written = Vec::new();
+        for client in [noisy, noisy, noisy, quiet] {
+            written.push(
+                log.record(client, RejectionReason::UntrustedRoot, &[vec![1]])
+                    .await
+                    .unwrap(),
+            );
+        }
+        // A minute boundary between the calls would reset the noisy bucket
+        if written[2] {
+            return;
+        }
+        assert_eq!(written, vec![true, true, false, true]);
+        assert_eq!(log.records_since(0, usize::MAX).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_der_capped_and_hashed() {
+        let log = RejectionLog::open(create_test_db().await, 10, 5, u32::MAX)
+            .await
+            .unwrap();
+        let chain = vec![vec![1, 2, 3], vec![4, 5, 6], vec![7]];
+        log.record(None, RejectionReason::PolicyViolation, &chain)
+            .await
+            .unwrap();
+
+        let record = &log.records_since(0, 10).await.unwrap()[0];
+        assert_eq!(record.reason, RejectionReason::PolicyViolation);
+        assert_eq!(record.chain, vec![vec![1, 2, 3]]);
+        assert_eq!(
+            record.chain_sha256,
+            <[u8; 32]>::from(Sha256::digest([1, 2, 3, 4, 5, 6, 7]))
+        );
+
+        let later = record.timestamp_ms + 60_000;
+        assert!(log.records_since(later, 10).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,66 @@
+use super::{CtStorage, KeyPrefix, Result, StorageError};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Index the next root seen for the first time gets
+const ROOT_INDEX_NEXT_KEY: &[u8] = b"root_next";
+
+/// `root:{SHA-256 of the root's DER}`
+fn root_index_key(der: &[u8]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(KeyPrefix::ROOT_INDEX.len() + 32);
+    key.extend_from_slice(KeyPrefix::ROOT_INDEX);
+    key.extend_from_slice(&Sha256::digest(der));
+    key
+}
+
+fn decode_index(bytes: &[u8]) -> Result<u64> {
+    bytes
+        .try_into()
+        .map(u64::from_be_bytes)
+        .map_err(|_| StorageError::InvalidFormat("Invalid root index".into()))
+}
+
+impl CtStorage {
+    /// Stable index of each DER root in `roots`. Roots seen for the first
+    /// time get the next unused indices, and indices are never reused, so a
+    /// root keeps its place in get-roots pages as other roots come and go.
+    pub async fn root_indices(&self, roots: &[Vec<u8>]) -> Result<Vec<u64>> {
+        let keys: Vec<Vec<u8>> = roots.iter().map(|der| root_index_key(der)).collect();
+        let stored = self.db.get_many(&keys).await?;
+        if stored.iter().all(Option::is_some) {
+            return stored
+                .iter()
+                .flatten()
+                .map(|bytes| decode_index(bytes))
+                .collect();
+        }
+
+        let _guard = self.root_index_lock.lock().await;
+
+        // Another request may have assigned them while we waited
+        let stored = self.db.get_many(&keys).await?;
+        let mut next = match self.db.get(ROOT_INDEX_NEXT_KEY).await? {
+            Some(bytes) => decode_index(&bytes)?,
+            None => 0,
+        };
+        let mut batch = self.db.batch();
+        let mut assigned: HashMap<&[u8], u64> = HashMap::new();
+        let mut indices = Vec::with_capacity(roots.len());
+        for (key, stored) in keys.iter().zip(stored) {
+            let index = match stored {
+                Some(bytes) => decode_index(&bytes)?,
+                // The same root may appear twice in `roots`
+                None => *assigned.entry(key).or_insert_with(|| {
+                    batch.put(key, next.to_be_bytes());
+                    next += 1;
+                    next - 1
+                }),
+            };
+            indices.push(index);
+        }
+        batch.put(ROOT_INDEX_NEXT_KEY, next.to_be_bytes());
+        self.db.write_batch(batch).await?;
+
+        Ok(indices)
+    }
+}
@@ -0,0 +1,30 @@
+use super::{CtStorage, KeyPrefix, Result, StorageError};
+
+/// `submitter:{index}`
+fn submitter_key(index: u64) -> Vec<u8> {
+    let mut key = Vec::with_capacity(KeyPrefix::SUBMITTER.len() + 8);
+    key.extend_from_slice(KeyPrefix::SUBMITTER);
+    key.extend_from_slice(&index.to_be_bytes());
+    key
+}
+
+impl CtStorage {
+    /// Record who submitted the entry at `index`, as authenticated by the API
+    pub async fn record_submitter(&self, index: u64, principal: &str) -> Result<()> {
+        self.db
+            .put(&submitter_key(index), principal.as_bytes())
+            .await
+            .map_err(StorageError::SlateDb)
+    }
+
+    /// Principal recorded for the entry at `index`. Entries submitted without
+    /// authentication, or logged before it was enabled, have none.
+    pub async fn get_submitter(&self, index: u64) -> Result<Option<String>> {
+        match self.db.get(&submitter_key(index)).await? {
+            Some(bytes) => String::from_utf8(bytes.to_vec())
+                .map(Some)
+                .map_err(|_| StorageError::InvalidFormat("Submitter is not UTF-8".into())),
+            None => Ok(None),
+        }
+    }
+}
@@ -236,3 +236,69 @@ pub mod utils {
         hasher.finalize().to_vec()
     }
 }
+
+/// Global allocator for the test binary that can report the peak number of
+/// live bytes allocated by the current thread while tracking is enabled.
+#[cfg(test)]
+pub mod alloc {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    struct CountingAllocator;
+
+    #[global_allocator]
+    static GLOBAL: CountingAllocator = CountingAllocator;
+
+    thread_local! {
+        static TRACKING: Cell<bool> = const { Cell::new(false) };
+        static LIVE: Cell<isize> = const { Cell::new(0) };
+        static PEAK: Cell<isize> = const { Cell::new(0) };
+    }
+
+    fn record(delta: isize) {
+        // try_with: the allocator also runs while thread locals are torn down
+        let _ = TRACKING.try_with(|tracking| {
+            if tracking.get() {
+                let live = LIVE.get() + delta;
+                LIVE.set(live);
+                PEAK.set(PEAK.get().max(live));
+            }
+        });
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            record(layout.size() as isize);
+            System.alloc(layout)
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            record(layout.size() as isize);
+            System.alloc_zeroed(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            record(-(layout.size() as isize));
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            record(new_size as isize - layout.size() as isize);
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    /// Start counting allocations made by this thread from zero
+    pub fn start_tracking() {
+        LIVE.set(0);
+        PEAK.set(0);
+        TRACKING.set(true);
+    }
+
+    /// Stop counting and return the highest live byte count seen since
+    /// `start_tracking`
+    pub fn peak_bytes() -> usize {
+        TRACKING.set(false);
+        PEAK.get().max(0) as usize
+    }
+}
@@ -0,0 +1,246 @@
+//! RFC 6962 `extra_data` of get-entries and get-entry-and-proof entries
+//!
+//! ```text
+//! opaque ASN.1Cert<1..2^24-1>;
+//!
+//! // x509_entry
+//! ASN.1Cert certificate_chain<0..2^24-1>;
+//!
+//! // precert_entry
+//! struct {
+//!     ASN.1Cert pre_certificate;
+//!     ASN.1Cert precertificate_chain<0..2^24-1>;
+//! } PrecertChainEntry;
+//! ```
+//!
+//! Which of the two an entry carries follows from the entry type in its
+//! `leaf_input`, so decoding needs that type.
+
+use crate::types::{CtError, LogEntry, LogEntryType, Result};
+
+const MAX_OPAQUE_24: usize = (1 << 24) - 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtraData {
+    /// Certificates after the leaf, each followed by its issuer
+    X509Chain(Vec<Vec<u8>>),
+    PrecertChain {
+        /// The precertificate as submitted, poison extension included
+        pre_certificate: Vec<u8>,
+        precertificate_chain: Vec<Vec<u8>>,
+    },
+}
+
+impl ExtraData {
+    /// The extra data served for a stored entry
+    pub fn for_entry(log_entry: &LogEntry) -> Result<Self> {
+        let chain = log_entry.chain.clone().unwrap_or_default();
+        match log_entry.entry_type {
+            LogEntryType::X509Entry => Ok(ExtraData::X509Chain(chain)),
+            LogEntryType::PrecertEntry => {
+                let pre_certificate = log_entry.original_precert.clone().ok_or_else(|| {
+                    CtError::Internal("Missing original pre-certificate for PrecertEntry".into())
+                })?;
+                Ok(ExtraData::PrecertChain {
+                    pre_certificate,
+                    precertificate_chain: chain,
+                })
+            }
+        }
+    }
+
+    pub fn entry_type(&self) -> LogEntryType {
+        match self {
+            ExtraData::X509Chain(_) => LogEntryType::X509Entry,
+            ExtraData::PrecertChain { .. } => LogEntryType::PrecertEntry,
+        }
+    }
+
+    /// The certificates after the leaf or precertificate
+    pub fn chain(&self) -> &[Vec<u8>] {
+        match self {
+            ExtraData::X509Chain(chain) => chain,
+            ExtraData::PrecertChain {
+                precertificate_chain,
+                ..
+            } => precertificate_chain,
+        }
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        if let ExtraData::PrecertChain {
+            pre_certificate, ..
+        } = self
+        {
+            write_certificate(&mut out, pre_certificate)?;
+        }
+
+        let chain = self.chain();
+        let chain_len: usize = chain.iter().map(|cert| 3 + cert.len()).sum();
+        if chain_len > MAX_OPAQUE_24 {
+            return Err(malformed(format!("chain too long ({} bytes)", chain_len)));
+        }
+        write_u24(&mut out, chain_len);
+        for cert in chain {
+            write_certificate(&mut out, cert)?;
+        }
+
+        Ok(out)
+    }
+
+    /// Decode the extra data of an entry of `entry_type`, requiring the input
+    /// to be fully consumed
+    pub fn decode(entry_type: LogEntryType, mut bytes: &[u8]) -> Result<Self> {
+        let pre_certificate = match entry_type {
+            LogEntryType::X509Entry => None,
+            LogEntryType::PrecertEntry => Some(read_certificate(&mut bytes)?.to_vec()),
+        };
+
+        let mut chain_bytes = read_opaque_24(&mut bytes)?;
+        if !bytes.is_empty() {
+            return Err(malformed(format!("{} trailing bytes", bytes.len())));
+        }
+        let mut chain = Vec::new();
+        while !chain_bytes.is_empty() {
+            chain.push(read_certificate(&mut chain_bytes)?.to_vec());
+        }
+
+        Ok(match pre_certificate {
+            None => ExtraData::X509Chain(chain),
+            Some(pre_certificate) => ExtraData::PrecertChain {
+                pre_certificate,
+                precertificate_chain: chain,
+            },
+        })
+    }
+}
+
+fn malformed(reason: String) -> CtError {
+    CtError::Internal(format!("Malformed extra_data: {}", reason))
+}
+
+fn write_u24(out: &mut Vec<u8>, len: usize) {
+    out.extend_from_slice(&(len as u32).to_be_bytes()[1..]);
+}
+
+fn write_certificate(out: &mut Vec<u8>, cert: &[u8]) -> Result<()> {
+    if cert.is_empty() || cert.len() > MAX_OPAQUE_24 {
+        return Err(malformed(format!(
+            "invalid certificate length {}",
+            cert.len()
+        )));
+    }
+    write_u24(out, cert.len());
+    out.extend_from_slice(cert);
+    Ok(())
+}
+
+fn read_opaque_24<'a>(input: &mut &'a [u8]) -> Result<&'a [u8]> {
+    if input.len() < 3 {
+        return Err(malformed("truncated length".to_string()));
+    }
+    let len = u32::from_be_bytes([0, input[0], input[1], input[2]]) as usize;
+    if input.len() - 3 < len {
+        return Err(malformed(format!(
+            "truncated (wanted {} bytes, have {})",
+            len,
+            input.len() - 3
+        )));
+    }
+    let (value, rest) = input[3..].split_at(len);
+    *input = rest;
+    Ok(value)
+}
+
+fn read_certificate<'a>(input: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let cert = read_opaque_24(input)?;
+    if cert.is_empty() {
+        return Err(malformed("empty certificate".to_string()));
+    }
+    Ok(cert)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Laid out as certificate-transparency-go's tls package marshals
+    // CertificateChain and PrecertChainEntry: 24-bit lengths throughout
+    const KNOWN_X509_EXTRA_DATA: &[u8] = &[
+        0x00, 0x00, 0x0b, // certificate_chain length
+        0x00, 0x00, 0x02, 0xaa, 0xbb, // first certificate
+        0x00, 0x00, 0x03, 0xcc, 0xdd, 0xee, // second certificate
+    ];
+    const KNOWN_PRECERT_EXTRA_DATA: &[u8] = &[
+        0x00, 0x00, 0x04, 0x30, 0x02, 0x05, 0x00, // pre_certificate
+        0x00, 0x00, 0x05, // precertificate_chain length
+        0x00, 0x00, 0x02, 0xaa, 0xbb, // issuer
+    ];
+
+    fn x509_extra_data() -> ExtraData {
+        ExtraData::X509Chain(vec![vec![0xaa, 0xbb], vec![0xcc, 0xdd, 0xee]])
+    }
+
+    fn precert_extra_data() -> ExtraData {
+        ExtraData::PrecertChain {
+            pre_certificate: vec![0x30, 0x02, 0x05, 0x00],
+            precertificate_chain: vec![vec![0xaa, 0xbb]],
+        }
+    }
+
+    #[test]
+    fn test_known_answers() {
+        for (extra_data, known) in [
+            (x509_extra_data(), KNOWN_X509_EXTRA_DATA),
+            (precert_extra_data(), KNOWN_PRECERT_EXTRA_DATA),
+        ] {
+            assert_eq!(extra_data.encode().unwrap(), known);
+            assert_eq!(
+                ExtraData::decode(extra_data.entry_type(), known).unwrap(),
+                extra_data
+            );
+        }
+
+        // An empty chain is still a length prefix
+        let empty = ExtraData::X509Chain(Vec::new());
+        assert_eq!(empty.encode().unwrap(), [0x00, 0x00, 0x00]);
+        assert_eq!(
+            ExtraData::decode(LogEntryType::X509Entry, &[0x00, 0x00, 0x00]).unwrap(),
+            empty
+        );
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed() {
+        for (entry_type, known) in [
+            (LogEntryType::X509Entry, KNOWN_X509_EXTRA_DATA),
+            (LogEntryType::PrecertEntry, KNOWN_PRECERT_EXTRA_DATA),
+        ] {
+            for len in 0..known.len() {
+                assert!(ExtraData::decode(entry_type, &known[..len]).is_err());
+            }
+
+            let mut trailing = known.to_vec();
+            trailing.push(0);
+            assert!(ExtraData::decode(entry_type, &trailing).is_err());
+        }
+
+        // The same bytes mean something else under the other entry type
+        assert!(ExtraData::decode(LogEntryType::PrecertEntry, KNOWN_X509_EXTRA_DATA).is_err());
+
+        // A zero-length certificate inside the chain
+        assert!(ExtraData::decode(LogEntryType::X509Entry, &[0, 0, 3, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_encode_rejects_empty_certificate() {
+        assert!(ExtraData::X509Chain(vec![Vec::new()]).encode().is_err());
+        assert!(ExtraData::PrecertChain {
+            pre_certificate: Vec::new(),
+            precertificate_chain: Vec::new(),
+        }
+        .encode()
+        .is_err());
+    }
+}
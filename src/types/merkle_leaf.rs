@@ -0,0 +1,409 @@
+//! RFC 6962 MerkleTreeLeaf encoding and parsing
+//!
+//! ```text
+//! struct {
+//!     Version version;                      // v1(0)
+//!     MerkleLeafType leaf_type;             // timestamped_entry(0)
+//!     select (leaf_type) {
+//!         case timestamped_entry: TimestampedEntry;
+//!     }
+//! } MerkleTreeLeaf;
+//!
+//! struct {
+//!     uint64 timestamp;
+//!     LogEntryType entry_type;
+//!     select (entry_type) {
+//!         case x509_entry: ASN.1Cert;                 // opaque<1..2^24-1>
+//!         case precert_entry: PreCert;                // issuer_key_hash[32] + TBSCertificate
+//!     } signed_entry;
+//!     CtExtensions extensions;                        // opaque<0..2^16-1>
+//! } TimestampedEntry;
+//! ```
+
+use crate::types::{CtError, LogEntryType, Result};
+
+/// MerkleTreeLeaf version, always v1(0)
+pub const MERKLE_LEAF_VERSION_V1: u8 = 0;
+
+/// MerkleLeafType, only timestamped_entry(0) is defined
+pub const MERKLE_LEAF_TYPE_TIMESTAMPED_ENTRY: u8 = 0;
+
+const MAX_OPAQUE_24: usize = (1 << 24) - 1;
+const MAX_OPAQUE_16: usize = (1 << 16) - 1;
+
+impl TryFrom<u16> for LogEntryType {
+    type Error = CtError;
+
+    fn try_from(value: u16) -> Result<Self> {
+        match value {
+            0 => Ok(LogEntryType::X509Entry),
+            1 => Ok(LogEntryType::PrecertEntry),
+            other => Err(malformed(format!("unknown LogEntryType {}", other))),
+        }
+    }
+}
+
+/// The signed_entry part of a TimestampedEntry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignedEntry {
+    /// DER-encoded leaf certificate
+    X509(Vec<u8>),
+    /// PreCert: issuer key hash and DER-encoded TBSCertificate
+    Precert {
+        issuer_key_hash: [u8; 32],
+        tbs_certificate: Vec<u8>,
+    },
+}
+
+impl SignedEntry {
+    pub fn entry_type(&self) -> LogEntryType {
+        match self {
+            SignedEntry::X509(_) => LogEntryType::X509Entry,
+            SignedEntry::Precert { .. } => LogEntryType::PrecertEntry,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimestampedEntry {
+    /// Milliseconds since the epoch
+    pub timestamp: u64,
+    pub signed_entry: SignedEntry,
+    /// Raw CtExtensions contents, without the 2-byte length prefix
+    pub extensions: Vec<u8>,
+}
+
+impl TimestampedEntry {
+    #[cfg(test)]
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        self.encode_into(&mut out)?;
+        Ok(out)
+    }
+
+    fn encode_into(&self, out: &mut Vec<u8>) -> Result<()> {
+        out.extend_from_slice(&self.timestamp.to_be_bytes());
+        out.extend_from_slice(&(self.signed_entry.entry_type() as u16).to_be_bytes());
+
+        match &self.signed_entry {
+            SignedEntry::X509(certificate) => write_opaque_24(out, certificate)?,
+            SignedEntry::Precert {
+                issuer_key_hash,
+                tbs_certificate,
+            } => {
+                out.extend_from_slice(issuer_key_hash);
+                write_opaque_24(out, tbs_certificate)?;
+            }
+        }
+
+        if self.extensions.len() > MAX_OPAQUE_16 {
+            return Err(malformed(format!(
+                "extensions too long ({} bytes)",
+                self.extensions.len()
+            )));
+        }
+        out.extend_from_slice(&(self.extensions.len() as u16).to_be_bytes());
+        out.extend_from_slice(&self.extensions);
+
+        Ok(())
+    }
+
+    /// Decode a TimestampedEntry, requiring the input to be fully consumed
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        let entry = Self::decode_from(&mut reader)?;
+        reader.finish()?;
+        Ok(entry)
+    }
+
+    fn decode_from(reader: &mut Reader<'_>) -> Result<Self> {
+        let timestamp = reader.read_u64()?;
+        let entry_type = LogEntryType::try_from(reader.read_u16()?)?;
+
+        let signed_entry = match entry_type {
+            LogEntryType::X509Entry => SignedEntry::X509(reader.read_opaque_24()?.to_vec()),
+            LogEntryType::PrecertEntry => {
+                let mut issuer_key_hash = [0u8; 32];
+                issuer_key_hash.copy_from_slice(reader.read_bytes(32)?);
+                let tbs_certificate = reader.read_opaque_24()?.to_vec();
+                SignedEntry::Precert {
+                    issuer_key_hash,
+                    tbs_certificate,
+                }
+            }
+        };
+
+        let extensions = reader.read_opaque_16()?.to_vec();
+
+        Ok(Self {
+            timestamp,
+            signed_entry,
+            extensions,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleTreeLeaf {
+    pub timestamped_entry: TimestampedEntry,
+}
+
+impl MerkleTreeLeaf {
+    pub fn new(timestamped_entry: TimestampedEntry) -> Self {
+        Self { timestamped_entry }
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut out = vec![MERKLE_LEAF_VERSION_V1, MERKLE_LEAF_TYPE_TIMESTAMPED_ENTRY];
+        self.timestamped_entry.encode_into(&mut out)?;
+        Ok(out)
+    }
+
+    /// Decode a MerkleTreeLeaf, requiring the input to be fully consumed
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let timestamped_entry = TimestampedEntry::decode(Self::timestamped_entry_bytes(bytes)?)?;
+        Ok(Self { timestamped_entry })
+    }
+
+    /// Check the version and leaf type header and return the raw TimestampedEntry
+    /// bytes that follow it, without parsing them.
+    pub fn timestamped_entry_bytes(bytes: &[u8]) -> Result<&[u8]> {
+        let mut reader = Reader::new(bytes);
+        let version = reader.read_u8()?;
+        if version != MERKLE_LEAF_VERSION_V1 {
+            return Err(malformed(format!("unsupported version {}", version)));
+        }
+        let leaf_type = reader.read_u8()?;
+        if leaf_type != MERKLE_LEAF_TYPE_TIMESTAMPED_ENTRY {
+            return Err(malformed(format!("unsupported leaf type {}", leaf_type)));
+        }
+        Ok(reader.remaining())
+    }
+}
+
+fn malformed(reason: String) -> CtError {
+    CtError::Internal(format!("Malformed MerkleTreeLeaf: {}", reason))
+}
+
+fn write_opaque_24(out: &mut Vec<u8>, data: &[u8]) -> Result<()> {
+    if data.is_empty() || data.len() > MAX_OPAQUE_24 {
+        return Err(malformed(format!(
+            "invalid certificate length {}",
+            data.len()
+        )));
+    }
+    let len = data.len() as u32;
+    out.push((len >> 16) as u8);
+    out.push((len >> 8) as u8);
+    out.push(len as u8);
+    out.extend_from_slice(data);
+    Ok(())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() - self.pos < len {
+            return Err(malformed(format!(
+                "truncated at offset {} (wanted {} bytes, have {})",
+                self.pos,
+                len,
+                self.data.len() - self.pos
+            )));
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16> {
+        let b = self.read_bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(self.read_bytes(8)?);
+        Ok(u64::from_be_bytes(buf))
+    }
+
+    fn read_opaque_24(&mut self) -> Result<&'a [u8]> {
+        let b = self.read_bytes(3)?;
+        let len = ((b[0] as usize) << 16) | ((b[1] as usize) << 8) | (b[2] as usize);
+        if len == 0 {
+            return Err(malformed("empty certificate".to_string()));
+        }
+        self.read_bytes(len)
+    }
+
+    fn read_opaque_16(&mut self) -> Result<&'a [u8]> {
+        let len = self.read_u16()? as usize;
+        self.read_bytes(len)
+    }
+
+    fn remaining(&self) -> &'a [u8] {
+        &self.data[self.pos..]
+    }
+
+    fn finish(&self) -> Result<()> {
+        if self.pos != self.data.len() {
+            return Err(malformed(format!(
+                "{} trailing bytes",
+                self.data.len() - self.pos
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::sct_extensions::CtExtensions;
+
+    // Hand-assembled from the RFC 6962 section 3.4 layout: an x509_entry at
+    // 2024-01-01T00:00:00Z carrying a 4 byte certificate and a leaf_index(5)
+    // extension. It pins the layout but shares any misreading of the RFC with
+    // the encoder; `test_real_log_leaf_inputs` checks entries a log wrote.
+    const KNOWN_X509_LEAF: &[u8] = &[
+        0x00, // version v1
+        0x00, // leaf_type timestamped_entry
+        0x00, 0x00, 0x01, 0x8c, 0xc2, 0x51, 0xf4, 0x00, // timestamp 1704067200000
+        0x00, 0x00, // entry_type x509_entry
+        0x00, 0x00, 0x04, // certificate length
+        0x30, 0x02, 0x05, 0x00, // certificate
+        0x00, 0x08, // extensions length
+        0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x05, // leaf_index extension
+    ];
+
+    fn x509_leaf() -> MerkleTreeLeaf {
+        MerkleTreeLeaf::new(TimestampedEntry {
+            timestamp: 1704067200000,
+            signed_entry: SignedEntry::X509(vec![0x30, 0x02, 0x05, 0x00]),
+            extensions: CtExtensions::with_leaf_index(5).unwrap()[2..].to_vec(),
+        })
+    }
+
+    fn precert_leaf() -> MerkleTreeLeaf {
+        MerkleTreeLeaf::new(TimestampedEntry {
+            timestamp: 1704067200123,
+            signed_entry: SignedEntry::Precert {
+                issuer_key_hash: [0xab; 32],
+                tbs_certificate: vec![0x30, 0x81, 0x80, 0x01, 0x02],
+            },
+            extensions: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn test_known_answer_encode() {
+        assert_eq!(x509_leaf().encode().unwrap(), KNOWN_X509_LEAF);
+    }
+
+    #[test]
+    fn test_known_answer_decode() {
+        let leaf = MerkleTreeLeaf::decode(KNOWN_X509_LEAF).unwrap();
+        assert_eq!(leaf, x509_leaf());
+        assert_eq!(
+            leaf.timestamped_entry.signed_entry.entry_type(),
+            LogEntryType::X509Entry
+        );
+    }
+
+    /// Decodes and re-encodes `leaf_input` values from a real log's
+    /// get-entries, base64 and comma-separated in `CT_LEAF_INPUTS`, which
+    /// must include an x509_entry and a precert_entry. Needs entries fetched
+    /// from a log, so it is not run by default:
+    /// `CT_LEAF_INPUTS=<x509>,<precert> cargo test -- --ignored test_real_log_leaf_inputs`
+    #[test]
+    #[ignore]
+    fn test_real_log_leaf_inputs() {
+        use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+        let inputs = std::env::var("CT_LEAF_INPUTS").expect("CT_LEAF_INPUTS is not set");
+        let (mut x509, mut precert) = (false, false);
+        for input in inputs.split(',') {
+            let bytes = STANDARD.decode(input.trim()).unwrap();
+            let leaf = MerkleTreeLeaf::decode(&bytes).unwrap();
+            assert_eq!(leaf.encode().unwrap(), bytes);
+            match leaf.timestamped_entry.signed_entry.entry_type() {
+                LogEntryType::X509Entry => x509 = true,
+                LogEntryType::PrecertEntry => precert = true,
+            }
+        }
+        assert!(x509 && precert, "need an x509_entry and a precert_entry");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for leaf in [x509_leaf(), precert_leaf()] {
+            let encoded = leaf.encode().unwrap();
+            assert_eq!(MerkleTreeLeaf::decode(&encoded).unwrap(), leaf);
+
+            let entry_bytes = leaf.timestamped_entry.encode().unwrap();
+            assert_eq!(
+                MerkleTreeLeaf::timestamped_entry_bytes(&encoded).unwrap(),
+                entry_bytes.as_slice()
+            );
+            assert_eq!(
+                TimestampedEntry::decode(&entry_bytes).unwrap(),
+                leaf.timestamped_entry
+            );
+        }
+    }
+
+    #[test]
+    fn test_precert_layout() {
+        let encoded = precert_leaf().encode().unwrap();
+        assert_eq!(&encoded[10..12], &[0x00, 0x01]);
+        assert_eq!(&encoded[12..44], &[0xab; 32]);
+        assert_eq!(&encoded[44..47], &[0x00, 0x00, 0x05]);
+        assert_eq!(&encoded[52..], &[0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed() {
+        // Truncated at every possible length
+        for len in 0..KNOWN_X509_LEAF.len() {
+            assert!(MerkleTreeLeaf::decode(&KNOWN_X509_LEAF[..len]).is_err());
+        }
+
+        let mut trailing = KNOWN_X509_LEAF.to_vec();
+        trailing.push(0);
+        assert!(MerkleTreeLeaf::decode(&trailing).is_err());
+
+        let mut bad_version = KNOWN_X509_LEAF.to_vec();
+        bad_version[0] = 1;
+        assert!(MerkleTreeLeaf::decode(&bad_version).is_err());
+
+        let mut bad_leaf_type = KNOWN_X509_LEAF.to_vec();
+        bad_leaf_type[1] = 1;
+        assert!(MerkleTreeLeaf::decode(&bad_leaf_type).is_err());
+
+        let mut bad_entry_type = KNOWN_X509_LEAF.to_vec();
+        bad_entry_type[11] = 2;
+        assert!(MerkleTreeLeaf::decode(&bad_entry_type).is_err());
+    }
+
+    #[test]
+    fn test_encode_rejects_empty_certificate() {
+        let leaf = MerkleTreeLeaf::new(TimestampedEntry {
+            timestamp: 0,
+            signed_entry: SignedEntry::X509(Vec::new()),
+            extensions: Vec::new(),
+        });
+        assert!(leaf.encode().is_err());
+    }
+}
@@ -1,4 +1,6 @@
 use crate::oids::*;
+use crate::types::merkle_leaf::{MerkleTreeLeaf, SignedEntry, TimestampedEntry};
+use base64::{engine::general_purpose::STANDARD, DecodeError, Engine};
 use chrono::{DateTime, Utc};
 use der::Decode;
 use serde::{Deserialize, Serialize};
@@ -6,10 +8,13 @@ use sha2::{Digest, Sha256};
 use thiserror::Error;
 use x509_cert::Certificate;
 
+pub mod extra_data;
+pub mod merkle_leaf;
 pub mod pages;
 pub mod sct;
 pub mod sct_extensions;
 pub mod signed_note;
+pub mod signer;
 pub mod tiles;
 pub mod tree_head;
 
@@ -32,8 +37,15 @@ pub enum CtError {
     #[error("Bad request: {0}")]
     BadRequest(String),
 
+    /// A submitted chain failed one of the validator's checks
+    #[error("Bad request: {0}")]
+    Validation(#[from] crate::validation::ValidationError),
+
     #[error("Internal error: {0}")]
     Internal(String),
+
+    #[error("Invalid timestamp: {0}")]
+    InvalidTimestamp(String),
 }
 
 pub type Result<T> = std::result::Result<T, CtError>;
@@ -43,18 +55,28 @@ pub type Result<T> = std::result::Result<T, CtError>;
 pub struct LogId([u8; 32]);
 
 impl LogId {
-    pub fn new(public_key_der: &[u8]) -> Self {
-        let mut hasher = Sha256::new();
-        hasher.update(public_key_der);
-        let hash = hasher.finalize();
-        let mut id = [0u8; 32];
-        id.copy_from_slice(&hash);
-        Self(id)
+    /// The ID of the log with DER-encoded SubjectPublicKeyInfo `spki_der`,
+    /// as defined in RFC 6962 section 3.2
+    pub fn from_public_key_der(spki_der: &[u8]) -> Self {
+        Self(Sha256::digest(spki_der).into())
     }
 
     pub fn to_bytes(&self) -> &[u8; 32] {
         &self.0
     }
+
+    /// Standard padded base64, as log lists carry it
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(self.0)
+    }
+
+    pub fn from_base64(s: &str) -> std::result::Result<Self, DecodeError> {
+        let bytes = STANDARD.decode(s)?;
+        let id = bytes
+            .try_into()
+            .map_err(|bytes: Vec<u8>| DecodeError::InvalidLength(bytes.len()))?;
+        Ok(Self(id))
+    }
 }
 
 /// Certificate entry in the log
@@ -222,79 +244,54 @@ impl LogEntry {
         }
     }
 
-    /// Serialize MerkleTreeLeaf structure for a given entry data
-    fn serialize_merkle_tree_leaf(
+    /// Build the typed MerkleTreeLeaf for a given entry data
+    fn merkle_tree_leaf(
         certificate: &[u8],
         entry_type: LogEntryType,
         issuer_key_hash: Option<&[u8]>,
         timestamp: DateTime<Utc>,
         index: Option<u64>,
-    ) -> Vec<u8> {
-        let mut data = Vec::new();
-
-        // Version (1 byte) - RFC 6962 MerkleTreeLeaf version is always 0
-        data.push(0x00);
-
-        // LeafType (1 byte) - always timestamped_entry (0)
-        data.push(0x00);
-
-        // Timestamp (8 bytes, milliseconds since epoch)
-        data.extend_from_slice(&(timestamp.timestamp_millis() as u64).to_be_bytes());
-
-        // LogEntryType (2 bytes)
-        match entry_type {
-            LogEntryType::X509Entry => {
-                data.extend_from_slice(&[0x00, 0x00]);
-
-                // Certificate length (3 bytes)
-                let cert_len = certificate.len() as u32;
-                data.push((cert_len >> 16) as u8);
-                data.push((cert_len >> 8) as u8);
-                data.push(cert_len as u8);
-
-                // Certificate data
-                data.extend_from_slice(certificate);
-            }
+    ) -> MerkleTreeLeaf {
+        let signed_entry = match entry_type {
+            LogEntryType::X509Entry => SignedEntry::X509(certificate.to_vec()),
             LogEntryType::PrecertEntry => {
-                data.extend_from_slice(&[0x00, 0x01]);
-
-                // PreCert structure: issuer_key_hash[32] + TBSCertificate
-                if let Some(key_hash) = issuer_key_hash {
-                    // Issuer key hash (32 bytes) - directly included, no length prefix
-                    data.extend_from_slice(key_hash);
-                } else {
-                    // This should not happen for precerts, but handle gracefully
-                    data.extend_from_slice(&[0u8; 32]);
+                // This should not happen for precerts, but handle gracefully
+                let issuer_key_hash = issuer_key_hash
+                    .and_then(|hash| hash.try_into().ok())
+                    .unwrap_or([0u8; 32]);
+                SignedEntry::Precert {
+                    issuer_key_hash,
+                    tbs_certificate: certificate.to_vec(),
                 }
-
-                // TBSCertificate length (3 bytes)
-                let cert_len = certificate.len() as u32;
-                data.push((cert_len >> 16) as u8);
-                data.push((cert_len >> 8) as u8);
-                data.push(cert_len as u8);
-
-                // TBSCertificate data
-                data.extend_from_slice(certificate);
             }
-        }
+        };
 
-        // Extensions
-        if let Some(idx) = index {
-            // Create extension data without outer length (matching SCT format)
-            let leaf_ext = crate::types::sct_extensions::Extension::leaf_index(idx)
-                .expect("Failed to create leaf index extension");
-            let ext_data = leaf_ext.encode();
-
-            // Add length prefix for MerkleTreeLeaf serialization
-            let ext_len = ext_data.len() as u16;
-            data.extend_from_slice(&ext_len.to_be_bytes());
-            data.extend_from_slice(&ext_data);
-        } else {
-            // Extensions length (2 bytes) - no extensions
-            data.extend_from_slice(&[0x00, 0x00]);
-        }
+        // Extension data without outer length (matching SCT format)
+        let extensions = match index {
+            Some(idx) => sct_extensions::Extension::leaf_index(idx)
+                .expect("Failed to create leaf index extension")
+                .encode(),
+            None => Vec::new(),
+        };
+
+        MerkleTreeLeaf::new(TimestampedEntry {
+            timestamp: timestamp.timestamp_millis() as u64,
+            signed_entry,
+            extensions,
+        })
+    }
 
-        data
+    /// Serialize MerkleTreeLeaf structure for a given entry data
+    fn serialize_merkle_tree_leaf(
+        certificate: &[u8],
+        entry_type: LogEntryType,
+        issuer_key_hash: Option<&[u8]>,
+        timestamp: DateTime<Utc>,
+        index: Option<u64>,
+    ) -> Vec<u8> {
+        Self::merkle_tree_leaf(certificate, entry_type, issuer_key_hash, timestamp, index)
+            .encode()
+            .expect("Failed to encode MerkleTreeLeaf")
     }
 
     /// Get the serialized leaf data for the Merkle tree
@@ -393,6 +390,20 @@ pub struct AddChainResponse {
 pub struct GetProofByHashRequest {
     pub hash: String,
     pub tree_size: u64,
+    /// Layout of the proof; when absent, the `Accept` header decides
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<ProofFormat>,
+}
+
+/// Layout of a get-proof-by-hash response
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProofFormat {
+    /// RFC 6962's `{leaf_index, audit_path}` JSON
+    #[default]
+    Rfc6962,
+    /// tlog record proof text, which carries the tree size too
+    Tlog,
 }
 
 /// Response containing an inclusion proof
@@ -402,6 +413,23 @@ pub struct GetProofByHashResponse {
     pub audit_path: Vec<String>,
 }
 
+/// Query of get-sth
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GetSthRequest {
+    /// Tree size of an STH the client holds, to prove the served STH consistent with
+    pub since: Option<u64>,
+}
+
+/// An STH, with the proof it is consistent with the size asked for in `since`.
+/// RFC 6962 clients ignore the extra field.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetSthResponse {
+    #[serde(flatten)]
+    pub sth: tree_head::SthResponse,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub consistency: Option<Vec<String>>,
+}
+
 /// Request for a consistency proof
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetConsistencyProofRequest {
@@ -415,17 +443,92 @@ pub struct GetConsistencyProofResponse {
     pub consistency: Vec<String>,
 }
 
-/// Request for log entries
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Request for log entries, by `start` and `end` or by `cursor`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GetEntriesRequest {
-    pub start: u64,
-    pub end: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<u64>,
+    /// Token from a previous response's cursor header; empty starts at entry 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    /// Only return entries of this type (`type=x509` or `type=precert`)
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub entry_type: Option<EntryTypeFilter>,
+    /// Leave `extra_data` empty, for monitors that only need the leaves
+    #[serde(default)]
+    pub leaf_only: bool,
+}
+
+/// Position of a client paging through get-entries: the next index to
+/// serve and the tree size pages are cut against. Pages end at that size
+/// until it is reached, so a page never straddles entries logged mid-walk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntriesCursor {
+    pub next: u64,
+    pub tree_size: u64,
 }
 
-/// Response containing log entries
+impl EntriesCursor {
+    /// Opaque base64url token
+    pub fn encode(&self) -> String {
+        let mut bytes = [0u8; 16];
+        bytes[..8].copy_from_slice(&self.next.to_be_bytes());
+        bytes[8..].copy_from_slice(&self.tree_size.to_be_bytes());
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    pub fn decode(token: &str) -> Result<Self> {
+        let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(token)
+            .map_err(|e| CtError::BadRequest(format!("invalid cursor: {}", e)))?;
+        let bytes: [u8; 16] = bytes
+            .try_into()
+            .map_err(|_| CtError::BadRequest("invalid cursor length".to_string()))?;
+        let cursor = Self {
+            next: u64::from_be_bytes(bytes[..8].try_into().unwrap()),
+            tree_size: u64::from_be_bytes(bytes[8..].try_into().unwrap()),
+        };
+        if cursor.next > cursor.tree_size {
+            return Err(CtError::BadRequest(
+                "cursor position is past its tree size".to_string(),
+            ));
+        }
+        Ok(cursor)
+    }
+}
+
+/// Entry type as named in query parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryTypeFilter {
+    X509,
+    Precert,
+}
+
+impl From<EntryTypeFilter> for LogEntryType {
+    fn from(filter: EntryTypeFilter) -> Self {
+        match filter {
+            EntryTypeFilter::X509 => LogEntryType::X509Entry,
+            EntryTypeFilter::Precert => LogEntryType::PrecertEntry,
+        }
+    }
+}
+
+/// Response containing log entries. get-entries writes this shape
+/// incrementally instead of building it, so only clients and tests construct it.
+#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetEntriesResponse {
     pub entries: Vec<LeafEntry>,
+    /// Extension: the index to request next when the page stopped short of
+    /// the requested range, `null` when it covered it
+    #[serde(default)]
+    pub next_entry: Option<u64>,
+    /// Extension: the committed tree size when the page was served
+    #[serde(default)]
+    pub tree_size: Option<u64>,
 }
 
 /// A leaf entry in the response
@@ -435,10 +538,73 @@ pub struct LeafEntry {
     pub extra_data: String,
 }
 
+/// Request for the newest log entries
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetRecentEntriesRequest {
+    pub count: u64,
+}
+
+/// Response containing the newest log entries, newest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetRecentEntriesResponse {
+    pub entries: Vec<RecentLeafEntry>,
+}
+
+/// A leaf and its index in the log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentLeafEntry {
+    pub index: u64,
+    pub leaf_input: String,
+}
+
+/// Request to add a raw entry to a log in raw entry mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddEntryRequest {
+    /// The base64-encoded entry
+    pub data: String,
+}
+
+/// Response containing raw entries, oldest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetRawEntriesResponse {
+    pub entries: Vec<RawEntry>,
+}
+
+/// A raw entry and the leaf it was logged as
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RawEntry {
+    pub leaf_input: String,
+    pub data: String,
+    /// Milliseconds since the epoch, as in the entry's receipt
+    pub timestamp: u64,
+}
+
+/// Request for get-roots. Without parameters every root is returned, up to
+/// the log's page limit.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GetRootsRequest {
+    /// Index of the first root, as given by a previous page's `next_start`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u64>,
+}
+
 /// Response for get-roots endpoint
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetRootsResponse {
     pub certificates: Vec<String>,
+    /// Accepted roots across all pages
+    pub total: u64,
+    /// `start` of the next page, or `null` after the last one
+    pub next_start: Option<u64>,
+}
+
+/// Response for get-timestamp
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetTimestampResponse {
+    /// The log's clock, in milliseconds since the epoch
+    pub timestamp: u64,
 }
 
 /// Temporal interval for the inclusion request
@@ -448,14 +614,55 @@ pub struct TemporalInterval {
     pub end_exclusive: String,
 }
 
-/// Inclusion request response for RFC 6962 log
+/// Query of `/inclusion_request.json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InclusionRequestParams {
+    /// Base64 RFC 6962 leaf hash. When given, the endpoint answers with a
+    /// [`LeafInclusionResponse`] for that leaf instead of the log's details.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+/// The log's details in the shape log list operators ask for when a log
+/// applies for inclusion: its key, ID, MMD, accepted expiry range and URL
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InclusionRequestResponse {
     pub key: String,
+    /// Algorithm of the log's signing key, such as `ecdsa-p256`
+    pub key_algorithm: String,
     pub log_id: String,
     pub mmd: u64,
     pub temporal_interval: TemporalInterval,
     pub url: String,
+    /// Signature algorithms accepted on submitted certificates, absent when
+    /// chains are not validated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accepted_signature_algorithms: Option<Vec<String>>,
+}
+
+/// Self-contained proof that a leaf is in the log, from
+/// `/inclusion_request.json?hash=`:
+///
+/// ```json
+/// {"log_id": "base64", "tree_size": 1234, "timestamp": 1700000000000,
+///  "leaf_index": 42, "leaf_hash": "base64", "proof": ["base64", ...]}
+/// ```
+///
+/// `tree_size` and `timestamp` are those of the STH the proof was built
+/// against, so an auditor checks `proof` against the root of the STH the log
+/// issued at that size and time. `leaf_index` is the leaf's position, which
+/// the audit path needs to be verified.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeafInclusionResponse {
+    /// Base64 SHA-256 of the log's public key
+    pub log_id: String,
+    pub tree_size: u64,
+    /// Milliseconds since the epoch
+    pub timestamp: u64,
+    pub leaf_index: u64,
+    pub leaf_hash: String,
+    /// Base64 audit path, leaf to root
+    pub proof: Vec<String>,
 }
 
 #[cfg(test)]
@@ -624,19 +831,36 @@ mod tests {
     #[test]
     fn test_log_id_creation() {
         let public_key = create_test_public_key();
-        let log_id = LogId::new(&public_key);
+        let log_id = LogId::from_public_key_der(&public_key);
 
         // Verify it produces a consistent hash
-        let log_id2 = LogId::new(&public_key);
+        let log_id2 = LogId::from_public_key_der(&public_key);
         assert_eq!(log_id, log_id2);
 
         // Verify different keys produce different IDs
         let mut different_key = public_key.clone();
         different_key[10] = 0xff;
-        let different_log_id = LogId::new(&different_key);
+        let different_log_id = LogId::from_public_key_der(&different_key);
         assert_ne!(log_id, different_log_id);
     }
 
+    #[test]
+    fn test_log_id_base64() {
+        // SHA-256 of "abc", from FIPS 180-2
+        let log_id = LogId::from_public_key_der(b"abc");
+        assert_eq!(
+            log_id.to_base64(),
+            "ungWv48Bz+pBQUDeXa4iI7ADYaOWF3qctBD/YfIAFa0="
+        );
+        assert_eq!(LogId::from_base64(&log_id.to_base64()).unwrap(), log_id);
+
+        assert_eq!(
+            LogId::from_base64("AAAA"),
+            Err(DecodeError::InvalidLength(3))
+        );
+        assert!(LogId::from_base64("not base64!").is_err());
+    }
+
     #[test]
     fn test_log_entry_new_with_timestamp() {
         let cert = create_test_certificate();
@@ -878,6 +1102,7 @@ mod tests {
         let proof_req = GetProofByHashRequest {
             hash: "somehash".to_string(),
             tree_size: 1000,
+            format: None,
         };
         let json = serde_json::to_string(&proof_req).unwrap();
         let deserialized: GetProofByHashRequest = serde_json::from_str(&json).unwrap();
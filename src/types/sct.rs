@@ -1,7 +1,10 @@
 use crate::types::sct_extensions::CtExtensions;
+use crate::types::signer::Signer;
 use crate::types::{CtError, LogEntryType, LogId, Result};
-use p256::ecdsa::{signature::Signer, DerSignature, SigningKey};
+use p256::ecdsa::SigningKey;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Version of the SCT structure
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -115,20 +118,74 @@ impl LogId {
     }
 }
 
+/// How far the timestamp of an SCT may be from the log's clock when the SCT
+/// is signed. RFC 6962 section 3.1 forbids timestamps in the future; the slack
+/// allows for clock adjustments, and the past bound for time spent queued.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampWindow {
+    pub max_future: Duration,
+    pub max_past: Duration,
+}
+
+impl Default for TimestampWindow {
+    fn default() -> Self {
+        Self {
+            max_future: Duration::from_secs(10),
+            max_past: Duration::from_secs(30),
+        }
+    }
+}
+
+impl TimestampWindow {
+    /// Check `timestamp` against `now`, both in milliseconds since the epoch
+    pub fn check(&self, timestamp: u64, now: u64) -> Result<()> {
+        if timestamp > now.saturating_add(self.max_future.as_millis() as u64) {
+            return Err(CtError::InvalidTimestamp(format!(
+                "timestamp {} is {} ms ahead of the log's clock",
+                timestamp,
+                timestamp - now
+            )));
+        }
+        if timestamp < now.saturating_sub(self.max_past.as_millis() as u64) {
+            return Err(CtError::InvalidTimestamp(format!(
+                "timestamp {} is {} ms behind the log's clock",
+                timestamp,
+                now - timestamp
+            )));
+        }
+        Ok(())
+    }
+}
+
 /// Builder for creating SCTs with proper signatures
 pub struct SctBuilder {
     log_id: LogId,
-    signing_key: SigningKey,
+    signer: Arc<dyn Signer>,
+    timestamp_window: TimestampWindow,
 }
 
 impl SctBuilder {
+    pub fn new(log_id: LogId, signer: Arc<dyn Signer>) -> Self {
+        Self {
+            log_id,
+            signer,
+            timestamp_window: TimestampWindow::default(),
+        }
+    }
+
     pub fn from_private_key_bytes(log_id: LogId, private_key_bytes: &[u8]) -> Result<Self> {
         let signing_key = SigningKey::from_slice(private_key_bytes)
             .map_err(|_| CtError::InvalidCertificate("Invalid private key".into()))?;
-        Ok(Self {
-            log_id,
-            signing_key,
-        })
+        Ok(Self::new(log_id, Arc::new(signing_key)))
+    }
+
+    /// Same signer and log ID, accepting timestamps within `timestamp_window`
+    pub fn with_timestamp_window(&self, timestamp_window: TimestampWindow) -> Self {
+        Self {
+            log_id: self.log_id.clone(),
+            signer: self.signer.clone(),
+            timestamp_window,
+        }
     }
 
     /// Create and sign an SCT for a certificate with a specific timestamp
@@ -140,16 +197,12 @@ impl SctBuilder {
         issuer_key_hash: Option<&[u8]>,
         timestamp: u64,
     ) -> Result<SignedCertificateTimestamp> {
-        self.create_sct_with_timestamp_and_index(
-            certificate,
-            entry_type,
-            issuer_key_hash,
-            timestamp,
-            None,
-        )
+        self.sign(certificate, entry_type, issuer_key_hash, timestamp, None)
     }
 
-    /// Create and sign an SCT for a certificate with a specific timestamp and optional index
+    /// Create and sign an SCT for a certificate with a specific timestamp and
+    /// optional index. Fails with `InvalidTimestamp` if `timestamp` is outside
+    /// the builder's window around the current time.
     pub fn create_sct_with_timestamp_and_index(
         &self,
         certificate: &[u8],
@@ -157,6 +210,19 @@ impl SctBuilder {
         issuer_key_hash: Option<&[u8]>,
         timestamp: u64,
         index: Option<u64>,
+    ) -> Result<SignedCertificateTimestamp> {
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+        self.timestamp_window.check(timestamp, now)?;
+        self.sign(certificate, entry_type, issuer_key_hash, timestamp, index)
+    }
+
+    fn sign(
+        &self,
+        certificate: &[u8],
+        entry_type: LogEntryType,
+        issuer_key_hash: Option<&[u8]>,
+        timestamp: u64,
+        index: Option<u64>,
     ) -> Result<SignedCertificateTimestamp> {
         let mut sct = SignedCertificateTimestamp::new(self.log_id.clone(), timestamp);
 
@@ -170,7 +236,7 @@ impl SctBuilder {
 
         let signature_input = sct.get_signature_input(certificate, entry_type, issuer_key_hash);
 
-        let signature: DerSignature = self.signing_key.sign(&signature_input);
+        let signature = self.signer.sign(&signature_input)?;
 
         let mut tls_signature = Vec::new();
 
@@ -194,7 +260,7 @@ impl SctBuilder {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use p256::ecdsa::{signature::Verifier, VerifyingKey};
+    use p256::ecdsa::{signature::Verifier, DerSignature, VerifyingKey};
 
     fn create_test_log_id() -> LogId {
         use x509_cert::spki::EncodePublicKey;
@@ -206,7 +272,11 @@ mod tests {
 
         // Export as SubjectPublicKeyInfo DER and create LogId
         let spki_der = verifying_key.to_public_key_der().unwrap();
-        LogId::new(spki_der.as_bytes())
+        LogId::from_public_key_der(spki_der.as_bytes())
+    }
+
+    fn now_ms() -> u64 {
+        chrono::Utc::now().timestamp_millis() as u64
     }
 
     fn create_test_key_pair() -> (SigningKey, VerifyingKey) {
@@ -506,7 +576,7 @@ mod tests {
             SctBuilder::from_private_key_bytes(log_id.clone(), &private_key_bytes).unwrap();
 
         let certificate = vec![0x01, 0x02, 0x03, 0x04, 0x05];
-        let timestamp = 1234567890000u64;
+        let timestamp = now_ms();
         let leaf_index = 12345u64;
 
         let sct = builder
@@ -554,7 +624,7 @@ mod tests {
         let builder = SctBuilder::from_private_key_bytes(log_id, &private_key_bytes).unwrap();
 
         let certificate = vec![0x01, 0x02, 0x03, 0x04, 0x05];
-        let timestamp = 1234567890000u64;
+        let timestamp = now_ms();
         let max_leaf_index = 0xFFFFFFFFFF_u64; // Maximum 40-bit value
 
         let sct = builder
@@ -581,7 +651,7 @@ mod tests {
         let builder = SctBuilder::from_private_key_bytes(log_id, &private_key_bytes).unwrap();
 
         let certificate = vec![0x01, 0x02, 0x03, 0x04, 0x05];
-        let timestamp = 1234567890000u64;
+        let timestamp = now_ms();
 
         let sct = builder
             .create_sct_with_timestamp_and_index(
@@ -607,7 +677,7 @@ mod tests {
 
         let certificate = vec![0x01, 0x02, 0x03, 0x04, 0x05];
         let issuer_key_hash = vec![0xbb; 32];
-        let timestamp = 1234567890000u64;
+        let timestamp = now_ms();
         let leaf_index = 54321u64;
 
         let sct = builder
@@ -640,4 +710,56 @@ mod tests {
             | (encoded_index[4] as u64);
         assert_eq!(decoded_index, leaf_index);
     }
+
+    #[test]
+    fn test_timestamp_window() {
+        let window = TimestampWindow::default();
+        let now = 1_700_000_000_000;
+
+        assert!(window.check(now, now).is_ok());
+        assert!(window.check(now + 10_000, now).is_ok());
+        assert!(window.check(now - 30_000, now).is_ok());
+        assert!(matches!(
+            window.check(now + 10_001, now),
+            Err(CtError::InvalidTimestamp(_))
+        ));
+        assert!(matches!(
+            window.check(now - 30_001, now),
+            Err(CtError::InvalidTimestamp(_))
+        ));
+    }
+
+    #[test]
+    fn test_sct_outside_window_is_not_signed() {
+        let (signing_key, _) = create_test_key_pair();
+        let builder =
+            SctBuilder::from_private_key_bytes(create_test_log_id(), &signing_key.to_bytes())
+                .unwrap();
+        let certificate = vec![0x01, 0x02, 0x03, 0x04];
+        let sign = |builder: &SctBuilder, timestamp| {
+            builder.create_sct_with_timestamp_and_index(
+                &certificate,
+                LogEntryType::X509Entry,
+                None,
+                timestamp,
+                Some(0),
+            )
+        };
+
+        let hour_ago = now_ms() - 3_600_000;
+        assert!(matches!(
+            sign(&builder, hour_ago),
+            Err(CtError::InvalidTimestamp(_))
+        ));
+        assert!(matches!(
+            sign(&builder, now_ms() + 60_000),
+            Err(CtError::InvalidTimestamp(_))
+        ));
+
+        let lenient = builder.with_timestamp_window(TimestampWindow {
+            max_future: Duration::from_secs(10),
+            max_past: Duration::from_secs(7200),
+        });
+        assert_eq!(sign(&lenient, hour_ago).unwrap().timestamp, hour_ago);
+    }
 }
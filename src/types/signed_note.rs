@@ -1,7 +1,8 @@
-use crate::types::Result;
+use crate::types::signer::Signer;
+use crate::types::{CtError, Result};
 use base64::{engine::general_purpose::STANDARD, Engine as _};
-use p256::ecdsa::{signature::Signer, DerSignature, SigningKey};
 use sha2::{Digest, Sha256};
+use std::sync::Arc;
 
 /// A signed note following the C2SP specification
 #[derive(Debug, Clone)]
@@ -30,6 +31,45 @@ impl SignedNote {
         self.signatures.push(signature);
     }
 
+    /// Parse a note in the format produced by [`SignedNote::format`]
+    pub fn parse(note: &str) -> Result<Self> {
+        let malformed = |reason: &str| CtError::BadRequest(format!("Malformed note: {}", reason));
+
+        let (text, signatures) = note
+            .rsplit_once("\n\n")
+            .ok_or_else(|| malformed("missing signature block"))?;
+
+        let mut parsed = SignedNote::new(format!("{}\n", text));
+        for line in signatures.lines() {
+            let rest = line
+                .strip_prefix("— ")
+                .ok_or_else(|| malformed("signature line must start with an em dash"))?;
+            let (key_name, encoded) = rest
+                .split_once(' ')
+                .ok_or_else(|| malformed("signature line missing key name"))?;
+            let sig_bytes = STANDARD
+                .decode(encoded)
+                .map_err(|_| malformed("signature is not valid base64"))?;
+            if sig_bytes.len() < 4 {
+                return Err(malformed("signature shorter than key id"));
+            }
+
+            let mut key_id = [0u8; 4];
+            key_id.copy_from_slice(&sig_bytes[..4]);
+            parsed.add_signature(NoteSignature {
+                key_name: key_name.to_string(),
+                key_id,
+                signature: sig_bytes[4..].to_vec(),
+            });
+        }
+
+        if parsed.signatures.is_empty() {
+            return Err(malformed("no signatures"));
+        }
+
+        Ok(parsed)
+    }
+
     pub fn format(&self) -> String {
         let mut output = String::new();
 
@@ -82,17 +122,28 @@ impl RFC6962NoteSignature {
     }
 }
 
+/// Text of the checkpoint of `origin` at `tree_size`, the note body its
+/// signatures cover, including the final newline
+pub fn checkpoint_body(origin: &str, tree_size: u64, root_hash: &[u8]) -> String {
+    format!(
+        "{}\n{}\n{}\n",
+        origin,
+        tree_size,
+        STANDARD.encode(root_hash)
+    )
+}
+
 /// Builder for creating signed checkpoints
 pub struct CheckpointBuilder {
-    signing_key: SigningKey,
+    signer: Arc<dyn Signer>,
     origin: String,
     log_id: Vec<u8>,
 }
 
 impl CheckpointBuilder {
-    pub fn new(signing_key: SigningKey, origin: String, log_id: Vec<u8>) -> Self {
+    pub fn new(signer: Arc<dyn Signer>, origin: String, log_id: Vec<u8>) -> Self {
         Self {
-            signing_key,
+            signer,
             origin,
             log_id,
         }
@@ -117,19 +168,11 @@ impl CheckpointBuilder {
         root_hash: &[u8],
         timestamp: u64,
     ) -> Result<SignedNote> {
-        let checkpoint_text = format!(
-            "{}\n{}\n{}",
-            self.origin,
-            tree_size,
-            STANDARD.encode(root_hash)
-        );
-
-        let mut note = SignedNote::new(checkpoint_text.clone());
+        let mut note = SignedNote::new(checkpoint_body(&self.origin, tree_size, root_hash));
 
         let signature_input = self.build_signature_input(timestamp, tree_size, root_hash);
 
-        let signature: DerSignature = self.signing_key.sign(&signature_input);
-        let signature_bytes = signature.to_bytes().to_vec();
+        let signature_bytes = self.signer.sign(&signature_input)?.to_bytes().to_vec();
 
         let rfc6962_sig = RFC6962NoteSignature {
             timestamp,
@@ -206,7 +249,7 @@ mod tests {
         let origin = "example.com/test-log".to_string();
         let log_id = create_test_log_id();
 
-        let builder = CheckpointBuilder::new(key, origin.clone(), log_id);
+        let builder = CheckpointBuilder::new(Arc::new(key), origin.clone(), log_id);
 
         let tree_size = 12345u64;
         let root_hash = vec![0u8; 32];
@@ -232,7 +275,7 @@ mod tests {
         let origin = "example.com/test-log".to_string();
         let log_id = create_test_log_id();
 
-        let builder = CheckpointBuilder::new(key, origin.clone(), log_id.clone());
+        let builder = CheckpointBuilder::new(Arc::new(key), origin.clone(), log_id.clone());
         let key_id = builder.calculate_key_id();
 
         let mut hasher = Sha256::new();
@@ -264,6 +307,29 @@ mod tests {
         assert_eq!(&encoded[12..], &[0xaa, 0xbb, 0xcc]);
     }
 
+    #[test]
+    fn test_parse_round_trip() {
+        let builder = CheckpointBuilder::new(
+            Arc::new(create_test_key()),
+            "example.com/log".to_string(),
+            vec![1; 32],
+        );
+        let note = builder.create_checkpoint(7, &[0xab; 32], 1000).unwrap();
+
+        let parsed = SignedNote::parse(&note.format()).unwrap();
+        assert_eq!(parsed.format(), note.format());
+        assert_eq!(parsed.signatures.len(), 1);
+        assert_eq!(parsed.signatures[0].key_name, "example.com/log");
+        assert_eq!(parsed.signatures[0].key_id, builder.calculate_key_id());
+    }
+
+    #[test]
+    fn test_parse_rejects_unsigned_note() {
+        assert!(SignedNote::parse("example.com/log\n7\nAAAA\n").is_err());
+        assert!(SignedNote::parse("example.com/log\n7\nAAAA\n\n").is_err());
+        assert!(SignedNote::parse("example.com/log\n7\nAAAA\n\n- bad line\n").is_err());
+    }
+
     #[test]
     fn test_note_with_trailing_newline() {
         let note = SignedNote::new("Test note\n".to_string());
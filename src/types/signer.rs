@@ -0,0 +1,117 @@
+use crate::types::{CtError, Result};
+use p256::ecdsa::{signature, DerSignature, SigningKey};
+use p256::pkcs8::EncodePublicKey;
+
+/// Signs SCTs, STHs and checkpoints with the log key. The log only needs
+/// signatures and the public key, so an implementation can keep the key in
+/// an HSM or KMS rather than in memory.
+///
+/// Signing is synchronous because SCTs are signed inside the batch worker's
+/// completion callback; a remote signer should block on its client.
+pub trait Signer: Send + Sync {
+    /// ECDSA P-256 signature over the SHA-256 digest of `message`
+    fn sign(&self, message: &[u8]) -> Result<DerSignature>;
+
+    /// DER-encoded SubjectPublicKeyInfo of the signing key
+    fn public_key_der(&self) -> Result<Vec<u8>>;
+}
+
+/// A key held in process memory
+impl Signer for SigningKey {
+    fn sign(&self, message: &[u8]) -> Result<DerSignature> {
+        Ok(signature::Signer::sign(self, message))
+    }
+
+    fn public_key_der(&self) -> Result<Vec<u8>> {
+        Ok(self
+            .verifying_key()
+            .to_public_key_der()
+            .map_err(|e| CtError::Internal(format!("Failed to encode public key: {}", e)))?
+            .into_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{sct::SctBuilder, tree_head::SthBuilder, LogEntryType, LogId};
+    use p256::ecdsa::{signature::Verifier, VerifyingKey};
+    use p256::pkcs8::DecodePublicKey;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Stands in for a remote signer: the builders only see this wrapper,
+    /// and it can be made to fail
+    struct MockSigner {
+        key: SigningKey,
+        signed: AtomicUsize,
+        unavailable: bool,
+    }
+
+    impl Signer for MockSigner {
+        fn sign(&self, message: &[u8]) -> Result<DerSignature> {
+            if self.unavailable {
+                return Err(CtError::Internal("signer unavailable".into()));
+            }
+            self.signed.fetch_add(1, Ordering::Relaxed);
+            Signer::sign(&self.key, message)
+        }
+
+        fn public_key_der(&self) -> Result<Vec<u8>> {
+            self.key.public_key_der()
+        }
+    }
+
+    fn mock_signer(unavailable: bool) -> Arc<MockSigner> {
+        Arc::new(MockSigner {
+            key: SigningKey::random(&mut rand::thread_rng()),
+            signed: AtomicUsize::new(0),
+            unavailable,
+        })
+    }
+
+    #[test]
+    fn test_builders_sign_through_signer() {
+        let signer = mock_signer(false);
+        let public_key_der = signer.public_key_der().unwrap();
+        let verifying_key = VerifyingKey::from_public_key_der(&public_key_der).unwrap();
+        let log_id = LogId::from_public_key_der(&public_key_der);
+
+        let certificate = b"certificate".to_vec();
+        let sct = SctBuilder::new(log_id.clone(), signer.clone())
+            .create_sct_with_timestamp_and_index(
+                &certificate,
+                LogEntryType::X509Entry,
+                None,
+                chrono::Utc::now().timestamp_millis() as u64,
+                Some(7),
+            )
+            .unwrap();
+        let signature = DerSignature::from_bytes(&sct.signature[4..]).unwrap();
+        let signed = sct.get_signature_input(&certificate, LogEntryType::X509Entry, None);
+        verifying_key.verify(&signed, &signature).unwrap();
+
+        let sth_builder = SthBuilder::with_signer(
+            signer.clone(),
+            "example.com/log".to_string(),
+            log_id.to_bytes().to_vec(),
+        );
+        let sth = sth_builder.create_sth(10, vec![1; 32], None).unwrap();
+        sth.verify(&public_key_der).unwrap();
+        sth_builder
+            .create_checkpoint(10, vec![1; 32], None)
+            .unwrap();
+        assert_eq!(signer.signed.load(Ordering::Relaxed), 3);
+
+        // A failing signer fails the request instead of producing a bad signature
+        let unavailable = SthBuilder::with_signer(
+            mock_signer(true),
+            "example.com/log".to_string(),
+            log_id.to_bytes().to_vec(),
+        );
+        assert!(unavailable.create_sth(10, vec![1; 32], None).is_err());
+        assert!(unavailable
+            .create_checkpoint(10, vec![1; 32], None)
+            .is_err());
+    }
+}
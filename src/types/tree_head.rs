@@ -1,7 +1,10 @@
 use crate::types::signed_note::{CheckpointBuilder, SignedNote};
+use crate::types::signer::Signer;
 use crate::types::{CtError, Result};
-use p256::ecdsa::{signature::Signer, DerSignature, SigningKey};
+use p256::ecdsa::{signature::Verifier, DerSignature, SigningKey, VerifyingKey};
+use p256::pkcs8::DecodePublicKey;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 /// Signed Tree Head (STH) as defined in RFC 6962
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -78,6 +81,60 @@ impl SignedTreeHead {
             },
         }
     }
+
+    /// Parse an STH received over the CT API, the inverse of `to_api_response`
+    pub fn from_api_response(response: &SthResponse) -> Result<Self> {
+        use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+        let root_hash = STANDARD
+            .decode(&response.sha256_root_hash)
+            .map_err(|_| CtError::BadRequest("Invalid base64 root hash".into()))?;
+        if root_hash.len() != 32 {
+            return Err(CtError::BadRequest("Root hash must be 32 bytes".into()));
+        }
+
+        let digitally_signed = STANDARD
+            .decode(&response.tree_head_signature)
+            .map_err(|_| CtError::BadRequest("Invalid base64 tree head signature".into()))?;
+        if digitally_signed.len() < 4 || digitally_signed[..2] != [4, 3] {
+            return Err(CtError::BadRequest(
+                "Tree head signature must be SHA-256 with ECDSA".into(),
+            ));
+        }
+        let sig_len = u16::from_be_bytes([digitally_signed[2], digitally_signed[3]]) as usize;
+        if digitally_signed.len() != 4 + sig_len {
+            return Err(CtError::BadRequest(
+                "Tree head signature length mismatch".into(),
+            ));
+        }
+
+        Ok(Self {
+            tree_size: response.tree_size,
+            timestamp: response.timestamp,
+            root_hash,
+            signature: digitally_signed[4..].to_vec(),
+        })
+    }
+
+    /// Check the signature against a DER-encoded SubjectPublicKeyInfo
+    pub fn verify(&self, public_key_der: &[u8]) -> Result<()> {
+        let verifying_key = VerifyingKey::from_public_key_der(public_key_der)
+            .map_err(|e| CtError::Internal(format!("Invalid log public key: {}", e)))?;
+        let signature = DerSignature::from_bytes(&self.signature)
+            .map_err(|_| CtError::BadRequest("Malformed tree head signature".into()))?;
+        verifying_key
+            .verify(&self.get_signature_input(), &signature)
+            .map_err(|_| CtError::BadRequest("Tree head signature does not verify".into()))
+    }
+}
+
+/// Parse an STH as served by get-sth and check its TreeHeadSignature against
+/// a DER-encoded SubjectPublicKeyInfo. The signed input is rebuilt from the
+/// version, signature type, timestamp, tree size and root hash in `sth`.
+pub fn verify_sth(public_key_der: &[u8], sth: &SthResponse) -> Result<SignedTreeHead> {
+    let sth = SignedTreeHead::from_api_response(sth)?;
+    sth.verify(public_key_der)?;
+    Ok(sth)
 }
 
 /// STH response format for the CT API
@@ -90,7 +147,7 @@ pub struct SthResponse {
 }
 
 pub struct SthBuilder {
-    signing_key: SigningKey,
+    signer: Arc<dyn Signer>,
     origin: String,
     log_id: Vec<u8>,
 }
@@ -99,11 +156,29 @@ impl SthBuilder {
     pub fn new(private_key_bytes: &[u8], origin: String, log_id: Vec<u8>) -> Result<Self> {
         let signing_key = SigningKey::from_slice(private_key_bytes)
             .map_err(|_| CtError::InvalidCertificate("Invalid private key".into()))?;
-        Ok(Self {
-            signing_key,
+        Ok(Self::with_signer(Arc::new(signing_key), origin, log_id))
+    }
+
+    pub fn with_signer(signer: Arc<dyn Signer>, origin: String, log_id: Vec<u8>) -> Self {
+        Self {
+            signer,
             origin,
             log_id,
-        })
+        }
+    }
+
+    /// Same signer and log ID, with a different checkpoint origin line
+    pub fn with_origin(&self, origin: String) -> Self {
+        Self {
+            signer: self.signer.clone(),
+            origin,
+            log_id: self.log_id.clone(),
+        }
+    }
+
+    /// Origin line written into checkpoints
+    pub fn origin(&self) -> &str {
+        &self.origin
     }
 
     pub fn create_sth(
@@ -118,8 +193,7 @@ impl SthBuilder {
 
         let signature_input = sth.get_signature_input();
 
-        let signature: DerSignature = self.signing_key.sign(&signature_input);
-        sth.signature = signature.to_bytes().to_vec();
+        sth.signature = self.signer.sign(&signature_input)?.to_bytes().to_vec();
 
         Ok(sth)
     }
@@ -135,7 +209,7 @@ impl SthBuilder {
             checkpoint_timestamp.unwrap_or_else(|| chrono::Utc::now().timestamp_millis() as u64);
 
         let checkpoint_builder = CheckpointBuilder::new(
-            self.signing_key.clone(),
+            self.signer.clone(),
             self.origin.clone(),
             self.log_id.clone(),
         );
@@ -166,7 +240,6 @@ pub struct Checkpoint {
 mod tests {
     use super::*;
     use base64::{engine::general_purpose::STANDARD, Engine as _};
-    use p256::ecdsa::{signature::Verifier, VerifyingKey};
 
     fn create_test_root_hash() -> Vec<u8> {
         use sha2::{Digest, Sha256};
@@ -330,6 +403,104 @@ mod tests {
         assert!(verifying_key.verify(&signature_input, &signature).is_ok());
     }
 
+    #[test]
+    fn test_checkpoint_note_verifies() {
+        use crate::types::signed_note::SignedNote;
+
+        let (signing_key, verifying_key) = create_test_key_pair();
+        let private_key_bytes = signing_key.to_bytes();
+        let builder = SthBuilder::new(&private_key_bytes, "ignored".to_string(), vec![7u8; 32])
+            .unwrap()
+            .with_origin("ct.example.com/2025h1".to_string());
+
+        let tree_size = 4242u64;
+        let root_hash = create_test_root_hash();
+        let timestamp = 1234567890000u64;
+        let note = builder
+            .create_checkpoint(tree_size, root_hash.clone(), Some(timestamp))
+            .unwrap()
+            .format();
+
+        let parsed = SignedNote::parse(&note).unwrap();
+        let body: Vec<&str> = parsed.text.lines().collect();
+        assert_eq!(body[0], "ct.example.com/2025h1");
+        assert_eq!(body[1].parse::<u64>().unwrap(), tree_size);
+        assert_eq!(STANDARD.decode(body[2]).unwrap(), root_hash);
+
+        let sig = &parsed.signatures[0];
+        assert_eq!(sig.key_name, "ct.example.com/2025h1");
+
+        // RFC6962NoteSignature: timestamp || DigitallySigned(SHA256, ECDSA)
+        let sig_timestamp = u64::from_be_bytes(sig.signature[..8].try_into().unwrap());
+        assert_eq!(sig_timestamp, timestamp);
+        assert_eq!(&sig.signature[8..10], &[4, 3]);
+        let sig_len = u16::from_be_bytes([sig.signature[10], sig.signature[11]]) as usize;
+        assert_eq!(sig.signature.len(), 12 + sig_len);
+
+        let sth = SignedTreeHead::new(tree_size, sig_timestamp, root_hash);
+        let signature = DerSignature::from_bytes(&sig.signature[12..]).unwrap();
+        assert!(verifying_key
+            .verify(&sth.get_signature_input(), &signature)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_api_response_round_trip_verifies() {
+        use p256::pkcs8::EncodePublicKey;
+
+        let (signing_key, verifying_key) = create_test_key_pair();
+        let public_key_der = verifying_key.to_public_key_der().unwrap().into_vec();
+        let builder = SthBuilder::new(
+            &signing_key.to_bytes(),
+            "example.com/test-log".to_string(),
+            vec![0u8; 32],
+        )
+        .unwrap();
+
+        let sth = builder
+            .create_sth(1000, create_test_root_hash(), Some(1234567890000))
+            .unwrap();
+        let parsed = SignedTreeHead::from_api_response(&sth.to_api_response()).unwrap();
+        assert_eq!(parsed.root_hash, sth.root_hash);
+        assert_eq!(parsed.signature, sth.signature);
+        assert!(parsed.verify(&public_key_der).is_ok());
+
+        // A different tree size invalidates the signature
+        let mut forged = parsed.clone();
+        forged.tree_size += 1;
+        assert!(matches!(
+            forged.verify(&public_key_der),
+            Err(CtError::BadRequest(_))
+        ));
+
+        // Wrong key
+        let (_, other_key) = create_test_key_pair();
+        let other_der = other_key.to_public_key_der().unwrap().into_vec();
+        assert!(parsed.verify(&other_der).is_err());
+    }
+
+    #[test]
+    fn test_from_api_response_rejects_malformed() {
+        let mut response = SthResponse {
+            tree_size: 1,
+            timestamp: 1,
+            sha256_root_hash: STANDARD.encode([0u8; 31]),
+            tree_head_signature: STANDARD.encode([4, 3, 0, 1, 0]),
+        };
+        assert!(SignedTreeHead::from_api_response(&response).is_err());
+
+        response.sha256_root_hash = STANDARD.encode([0u8; 32]);
+        assert!(SignedTreeHead::from_api_response(&response).is_ok());
+
+        // Declared length runs past the end
+        response.tree_head_signature = STANDARD.encode([4, 3, 0, 2, 0]);
+        assert!(SignedTreeHead::from_api_response(&response).is_err());
+
+        // RSA is not what this log signs with
+        response.tree_head_signature = STANDARD.encode([4, 1, 0, 1, 0]);
+        assert!(SignedTreeHead::from_api_response(&response).is_err());
+    }
+
     #[test]
     fn test_sth_signature_is_der_format() {
         let (signing_key, _) = create_test_key_pair();
@@ -476,4 +647,134 @@ mod tests {
         let size_value = u64::from_be_bytes(size_bytes.try_into().unwrap());
         assert_eq!(size_value, u64::MAX);
     }
+
+    #[test]
+    fn test_sth_signature_input_known_answer() {
+        let root_hash: Vec<u8> = (0u8..32).collect();
+        let sth = SignedTreeHead::new(1000, 1234567890000, root_hash);
+
+        // RFC 6962 section 3.5: version v1, signature_type tree_hash, then
+        // timestamp, tree_size and sha256_root_hash
+        let expected = concat!(
+            "0001",
+            "0000011f71fb0450",
+            "00000000000003e8",
+            "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f"
+        );
+        assert_eq!(hex::encode(sth.get_signature_input()), expected);
+    }
+
+    #[test]
+    fn test_sth_signature_known_answer() {
+        use p256::pkcs8::EncodePublicKey;
+
+        // p256 signs deterministically (RFC 6979), so a fixed key signing the
+        // tree head of the known answer above gives fixed bytes. These match
+        // pyca/cryptography's deterministic ECDSA over the same input.
+        let signing_key = SigningKey::from_slice(&[0x11; 32]).unwrap();
+        let builder = SthBuilder::new(
+            &signing_key.to_bytes(),
+            "example.com/log".into(),
+            vec![0; 32],
+        )
+        .unwrap();
+        let sth = builder
+            .create_sth(1000, (0u8..32).collect(), Some(1234567890000))
+            .unwrap();
+
+        let expected_signature = concat!(
+            "304402206cbd5e514519a37ed9acc9719633a8901cd5130639494299290e6a25dd807627",
+            "02204b7d4ea623bc5ddc58450f92bef2c7f94a40cc4d0d0175885881161647665d34"
+        );
+        assert_eq!(hex::encode(&sth.signature), expected_signature);
+        let public_key_der = signing_key
+            .verifying_key()
+            .to_public_key_der()
+            .unwrap()
+            .into_vec();
+        assert!(verify_sth(&public_key_der, &sth.to_api_response()).is_ok());
+    }
+
+    #[test]
+    fn test_sth_verifies_with_openssl() {
+        use openssl::{hash::MessageDigest, pkey::PKey, sign::Verifier};
+        use p256::pkcs8::EncodePublicKey;
+
+        let (signing_key, verifying_key) = create_test_key_pair();
+        let public_key_der = verifying_key.to_public_key_der().unwrap().into_vec();
+        let builder = SthBuilder::new(
+            &signing_key.to_bytes(),
+            "example.com/log".into(),
+            vec![0; 32],
+        )
+        .unwrap();
+        let sth = builder
+            .create_sth(1000, create_test_root_hash(), Some(1234567890000))
+            .unwrap();
+
+        // Check the served DigitallySigned blob with an independent verifier
+        let response = sth.to_api_response();
+        let digitally_signed = STANDARD.decode(&response.tree_head_signature).unwrap();
+        assert_eq!(digitally_signed[..2], [4, 3]);
+        let key = PKey::public_key_from_der(&public_key_der).unwrap();
+        let mut verifier = Verifier::new(MessageDigest::sha256(), &key).unwrap();
+        verifier.update(&sth.get_signature_input()).unwrap();
+        assert!(verifier.verify(&digitally_signed[4..]).unwrap());
+
+        assert!(verify_sth(&public_key_der, &response).is_ok());
+    }
+
+    #[test]
+    fn test_verify_sth_rejects_perturbed_fields() {
+        use p256::pkcs8::EncodePublicKey;
+        use rand::Rng;
+
+        let (signing_key, verifying_key) = create_test_key_pair();
+        let public_key_der = verifying_key.to_public_key_der().unwrap().into_vec();
+        let builder = SthBuilder::new(
+            &signing_key.to_bytes(),
+            "example.com/log".into(),
+            vec![0; 32],
+        )
+        .unwrap();
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..32 {
+            let root_hash: [u8; 32] = rng.gen();
+            let sth = builder
+                .create_sth(rng.gen(), root_hash.to_vec(), Some(rng.gen()))
+                .unwrap();
+            let response = sth.to_api_response();
+            let verified = verify_sth(&public_key_der, &response).unwrap();
+            assert_eq!(verified.tree_size, sth.tree_size);
+            assert_eq!(verified.timestamp, sth.timestamp);
+
+            let mut perturbed = Vec::new();
+
+            let mut tree_size = response.clone();
+            tree_size.tree_size ^= 1 << rng.gen_range(0..64);
+            perturbed.push(tree_size);
+
+            let mut timestamp = response.clone();
+            timestamp.timestamp ^= 1 << rng.gen_range(0..64);
+            perturbed.push(timestamp);
+
+            let mut flipped_root = root_hash;
+            flipped_root[rng.gen_range(0..32)] ^= 1 << rng.gen_range(0..8);
+            let mut root = response.clone();
+            root.sha256_root_hash = STANDARD.encode(flipped_root);
+            perturbed.push(root);
+
+            let mut digitally_signed = STANDARD.decode(&response.tree_head_signature).unwrap();
+            let index = rng.gen_range(0..digitally_signed.len());
+            digitally_signed[index] ^= 1 << rng.gen_range(0..8);
+            let mut signature = response.clone();
+            signature.tree_head_signature = STANDARD.encode(&digitally_signed);
+            perturbed.push(signature);
+
+            for response in perturbed {
+                assert!(verify_sth(&public_key_der, &response).is_err());
+            }
+        }
+    }
 }
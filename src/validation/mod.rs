@@ -1,7 +1,13 @@
 pub mod issuer_key_hash;
+pub mod pool;
 pub mod rfc6962_validator;
 pub mod tbs_extractor;
 
 pub use issuer_key_hash::extract_issuer_key_hash_minimal;
-pub use rfc6962_validator::{CcadbEnvironment, Rfc6962ValidationConfig, Rfc6962Validator};
+pub use pool::{ValidationPool, ValidationPoolError};
+pub use rfc6962_validator::{
+    check_tbs_signature_algorithm, CcadbEnvironment, RejectionCheck, Rfc6962ValidationConfig,
+    Rfc6962Validator, SignatureAlgorithmOid, ValidatedChain, ValidationError, ValidationMode,
+    ValidationWarning,
+};
 pub use tbs_extractor::TbsExtractor;
@@ -0,0 +1,173 @@
+use crate::metrics::Metrics;
+use crate::types::{CtError, LogEntryType, Result};
+use crate::validation::Rfc6962Validator;
+use prometheus::{IntCounterVec, IntGauge};
+use std::sync::Arc;
+use tokio::sync::{oneshot, RwLock, Semaphore};
+
+/// Why a chain did not pass through the validation pool
+#[derive(Debug)]
+pub enum ValidationPoolError {
+    /// Every validation slot is taken; the caller should back off
+    Busy,
+    /// The chain was validated and rejected, or validation could not run
+    Rejected(CtError),
+}
+
+/// Runs chain validation on a dedicated rayon pool so signature checks and path
+/// building cannot starve the Tokio workers. A semaphore bounds the number of
+/// in-flight validations; when it is exhausted new submissions are turned away
+/// instead of queuing.
+pub struct ValidationPool {
+    pool: Arc<rayon::ThreadPool>,
+    semaphore: Arc<Semaphore>,
+    /// `ct_validation_queue_depth` of the log this pool validates for
+    queue_depth: IntGauge,
+    /// `ct_validation_warnings_total` of the same log
+    warnings: IntCounterVec,
+}
+
+/// Keeps `ct_validation_queue_depth` accurate even if the request is dropped
+/// while its validation is still running.
+struct QueueDepthGuard(IntGauge);
+
+impl QueueDepthGuard {
+    fn new(queue_depth: IntGauge) -> Self {
+        queue_depth.inc();
+        Self(queue_depth)
+    }
+}
+
+impl Drop for QueueDepthGuard {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+impl ValidationPool {
+    pub fn new(num_threads: usize, max_in_flight: usize) -> Result<Self> {
+        if num_threads == 0 || max_in_flight == 0 {
+            return Err(CtError::Internal(
+                "Validation pool needs at least one thread and one slot".to_string(),
+            ));
+        }
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .thread_name(|i| format!("ct-validation-{}", i))
+            .build()
+            .map_err(|e| CtError::Internal(format!("Failed to build validation pool: {}", e)))?;
+
+        Ok(Self {
+            pool: Arc::new(pool),
+            semaphore: Arc::new(Semaphore::new(max_in_flight)),
+            queue_depth: crate::metrics::global().validation_queue_depth.clone(),
+            warnings: crate::metrics::global().validation_warnings_total.clone(),
+        })
+    }
+
+    /// Report the queue depth and validation warnings to `metrics` instead of
+    /// the global metrics
+    pub fn with_metrics(mut self, metrics: &Metrics) -> Self {
+        self.queue_depth = metrics.validation_queue_depth.clone();
+        self.warnings = metrics.validation_warnings_total.clone();
+        self
+    }
+
+    /// Validate `chain` as `entry_type` on the pool, returning the chain to log.
+    /// Warnings of a permissive validator are logged and counted.
+    pub async fn validate(
+        &self,
+        validator: &Arc<RwLock<Rfc6962Validator>>,
+        chain: Vec<Vec<u8>>,
+        entry_type: LogEntryType,
+    ) -> std::result::Result<Vec<Vec<u8>>, ValidationPoolError> {
+        let permit = self
+            .semaphore
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| ValidationPoolError::Busy)?;
+        let depth_guard = QueueDepthGuard::new(self.queue_depth.clone());
+
+        let validator = validator.clone().read_owned().await;
+
+        let (tx, rx) = oneshot::channel();
+        self.pool.spawn(move || {
+            let _permit = permit;
+            let _depth_guard = depth_guard;
+            // Validation never awaits on I/O, so blocking on it inside the pool is fine
+            let result =
+                futures::executor::block_on(validator.validate_chain_as(&chain, entry_type));
+            let _ = tx.send(result);
+        });
+
+        match rx.await {
+            Ok(Ok(validated)) => {
+                for warning in &validated.warnings {
+                    tracing::warn!("Accepted chain despite validation failure: {}", warning);
+                    self.warnings.with_label_values(&[warning.reason()]).inc();
+                }
+                Ok(validated.chain)
+            }
+            Ok(Err(e)) => Err(ValidationPoolError::Rejected(e)),
+            Err(_) => Err(ValidationPoolError::Rejected(CtError::Internal(
+                "Validation task ended without a result".to_string(),
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::utils::create_test_certificate;
+    use crate::validation::Rfc6962ValidationConfig;
+
+    fn test_validator() -> Arc<RwLock<Rfc6962Validator>> {
+        let validator =
+            Rfc6962Validator::with_trusted_roots(Rfc6962ValidationConfig::default(), vec![])
+                .unwrap();
+        Arc::new(RwLock::new(validator))
+    }
+
+    #[test]
+    fn test_rejects_empty_pool() {
+        assert!(ValidationPool::new(0, 1).is_err());
+        assert!(ValidationPool::new(1, 0).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_busy_when_slots_exhausted() {
+        let pool = ValidationPool::new(1, 1).unwrap();
+        let validator = test_validator();
+
+        let _held = pool.semaphore.clone().try_acquire_owned().unwrap();
+        let result = pool
+            .validate(
+                &validator,
+                vec![create_test_certificate()],
+                LogEntryType::X509Entry,
+            )
+            .await;
+        assert!(matches!(result, Err(ValidationPoolError::Busy)));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_chain_releases_slot() {
+        let pool = ValidationPool::new(1, 1).unwrap();
+        let validator = test_validator();
+
+        // No trusted roots, so validation must fail
+        for _ in 0..2 {
+            let result = pool
+                .validate(
+                    &validator,
+                    vec![create_test_certificate()],
+                    LogEntryType::X509Entry,
+                )
+                .await;
+            assert!(matches!(result, Err(ValidationPoolError::Rejected(_))));
+        }
+        assert_eq!(pool.semaphore.available_permits(), 1);
+    }
+}
@@ -1,11 +1,16 @@
+use crate::metrics::Metrics;
 use crate::oids::*;
-use crate::types::{CtError, Result};
+use crate::types::{CtError, LogEntryType, Result};
 use chrono::{DateTime, Utc};
-use der::{Decode, Encode};
-use foyer::{Cache, CacheBuilder};
+use der::{asn1::ObjectIdentifier, Decode, Encode};
+use foyer::{Cache, CacheBuilder, LruConfig};
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Public};
 use openssl::x509::X509;
+use prometheus::{IntCounter, IntGauge};
 use sha2::{Digest, Sha256};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
 use x509_cert::{
@@ -41,6 +46,318 @@ pub struct TemporalWindow {
     pub end: DateTime<Utc>,
 }
 
+/// Clock skew allowed when checking a leaf's validity period against the
+/// time it is submitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockTolerance {
+    /// How long before its notBefore a leaf is already accepted
+    pub not_before: std::time::Duration,
+    /// How long after its notAfter a leaf is still accepted
+    pub not_after: std::time::Duration,
+}
+
+impl ClockTolerance {
+    /// The same tolerance on both ends of the validity period
+    pub fn symmetric(tolerance: std::time::Duration) -> Self {
+        Self {
+            not_before: tolerance,
+            not_after: tolerance,
+        }
+    }
+}
+
+impl Default for ClockTolerance {
+    /// Ten minutes either way, as browsers allow
+    fn default() -> Self {
+        Self::symmetric(std::time::Duration::from_secs(10 * 60))
+    }
+}
+
+/// OID of a certificate signature algorithm, such as `ECDSA_WITH_SHA256_OID`
+pub type SignatureAlgorithmOid = ObjectIdentifier;
+
+/// Chain rejections that callers may want to tell apart
+#[derive(Debug, thiserror::Error)]
+pub enum ValidationError {
+    #[error("Certificate at index {index} uses disallowed signature algorithm: {oid}")]
+    DisallowedSignatureAlgorithm { index: usize, oid: String },
+
+    #[error("TBS signature algorithm {tbs_oid} does not match the certificate's signature algorithm {outer_oid}")]
+    SignatureAlgorithmMismatch { tbs_oid: String, outer_oid: String },
+
+    #[error("Certificate chain is out of order: the issuer of the certificate at index {index} is neither the next certificate nor a trusted root")]
+    UnorderedChain { index: usize },
+
+    #[error("Certificate chain length {length} exceeds maximum {max}")]
+    ChainTooLong { length: usize, max: usize },
+
+    #[error("Certificate at index {index} is {size} bytes, exceeding the maximum certificate size {max}")]
+    CertificateTooLarge {
+        index: usize,
+        size: usize,
+        max: usize,
+    },
+
+    #[error("Certificate at index {index} repeats the certificate at index {first}")]
+    DuplicateCertificate { index: usize, first: usize },
+
+    #[error("Certificate chain needs more than {max} signature verifications")]
+    TooManySignatureVerifications { max: usize },
+
+    #[error("Certificate chain is empty")]
+    EmptyChain,
+
+    #[error("Failed to parse certificate at index {index}: {reason}")]
+    UnparsableCertificate { index: usize, reason: String },
+
+    #[error("No trusted roots configured")]
+    NoTrustedRoots,
+
+    #[error("Certificate chain does not terminate in a trusted root: no accepted root issued '{issuer}'")]
+    UntrustedRoot { issuer: String },
+
+    #[error("Certificate at index {index} has invalid signature")]
+    InvalidSignature { index: usize },
+
+    #[error("Self-signed certificate has invalid signature")]
+    InvalidRootSignature,
+
+    #[error(
+        "Certificate expires before log temporal window starts: {} < {}",
+        .not_after.format("%Y-%m-%d %H:%M:%S UTC"),
+        .window_start.format("%Y-%m-%d %H:%M:%S UTC")
+    )]
+    ExpiresBeforeWindow {
+        not_after: DateTime<Utc>,
+        window_start: DateTime<Utc>,
+    },
+
+    #[error(
+        "Certificate expires outside log temporal window: {} >= {}",
+        .not_after.format("%Y-%m-%d %H:%M:%S UTC"),
+        .window_end.format("%Y-%m-%d %H:%M:%S UTC")
+    )]
+    ExpiresAfterWindow {
+        not_after: DateTime<Utc>,
+        window_end: DateTime<Utc>,
+    },
+
+    #[error(
+        "Certificate is not yet valid: valid from {}",
+        .not_before.format("%Y-%m-%d %H:%M:%S UTC")
+    )]
+    NotYetValid { not_before: DateTime<Utc> },
+
+    #[error(
+        "Certificate has expired: valid until {}",
+        .not_after.format("%Y-%m-%d %H:%M:%S UTC")
+    )]
+    Expired { not_after: DateTime<Utc> },
+
+    #[error("Precertificate poison extension must be critical")]
+    PoisonNotCritical,
+
+    #[error("Precertificate poison extension must contain ASN.1 NULL")]
+    PoisonNotNull,
+
+    #[error("Precertificate signed by signing certificate requires real issuer in chain")]
+    PrecertIssuerMissing,
+
+    #[error("Precertificate Signing Certificate must be directly issued by the CA")]
+    PrecertSigningCertNotDirectlyIssued,
+}
+
+/// Whether chains failing a soft check are rejected or only reported
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ValidationMode {
+    #[default]
+    Strict,
+    /// Accept chains whose only failures are soft checks, reporting those as
+    /// [`ValidationWarning`]s. Meant for importing historical certificates.
+    /// Signatures, roots and precertificate poison are checked as strictly as
+    /// ever.
+    Permissive,
+}
+
+/// A soft check that an accepted chain failed in permissive mode
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ValidationWarning {
+    #[error("Certificate at index {index} uses disallowed signature algorithm: {oid}")]
+    DisallowedSignatureAlgorithm { index: usize, oid: String },
+
+    #[error("Certificate expires outside log temporal window: {not_after}")]
+    OutsideTemporalWindow { not_after: DateTime<Utc> },
+
+    #[error("Certificate is not valid at submission: valid from {not_before} to {not_after}")]
+    OutsideValidityPeriod {
+        not_before: DateTime<Utc>,
+        not_after: DateTime<Utc>,
+    },
+}
+
+impl ValidationWarning {
+    /// `reason` label of `ct_validation_warnings_total`
+    pub fn reason(&self) -> &'static str {
+        match self {
+            ValidationWarning::DisallowedSignatureAlgorithm { .. } => {
+                "disallowed_signature_algorithm"
+            }
+            ValidationWarning::OutsideTemporalWindow { .. } => "outside_temporal_window",
+            ValidationWarning::OutsideValidityPeriod { .. } => "outside_validity_period",
+        }
+    }
+}
+
+/// The check a rejected chain failed, finer grained than
+/// [`RejectionReason`](crate::storage::RejectionReason) so that a CA starting
+/// to fail one particular check stands out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectionCheck {
+    /// A certificate could not be parsed, or the chain is empty
+    Malformed,
+    UntrustedRoot,
+    BadSignature,
+    UnorderedChain,
+    /// Chain length, certificate size, repeated certificates or signature
+    /// verifications over the configured limits
+    ChainLimit,
+    /// Disallowed or mismatched signature algorithm
+    SignatureAlgorithm,
+    /// The leaf expires before the temporal window starts, or had expired
+    /// when submitted
+    Expired,
+    /// The leaf expires at or after the temporal window ends, so it belongs
+    /// to a later shard
+    OutsideShard,
+    /// Precertificate poison missing, not critical or not NULL
+    PrecertPoison,
+    /// Precertificate issued in a way RFC 6962 does not allow
+    PrecertIssuer,
+    Other,
+    /// Validation failed for reasons unrelated to the chain
+    Internal,
+}
+
+impl RejectionCheck {
+    /// Map a validation error to the check it failed
+    pub fn classify(error: &CtError) -> Self {
+        match error {
+            CtError::Validation(error) => error.into(),
+            CtError::InvalidCertificate(_) => RejectionCheck::Malformed,
+            CtError::BadRequest(_) => RejectionCheck::Other,
+            CtError::Storage(_) | CtError::Internal(_) | CtError::InvalidTimestamp(_) => {
+                RejectionCheck::Internal
+            }
+        }
+    }
+
+    /// `reason` label of `ct_add_chain_rejections_total`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RejectionCheck::Malformed => "malformed",
+            RejectionCheck::UntrustedRoot => "untrusted_root",
+            RejectionCheck::BadSignature => "bad_signature",
+            RejectionCheck::UnorderedChain => "unordered_chain",
+            RejectionCheck::ChainLimit => "chain_limit",
+            RejectionCheck::SignatureAlgorithm => "signature_algorithm",
+            RejectionCheck::Expired => "expired",
+            RejectionCheck::OutsideShard => "outside_shard",
+            RejectionCheck::PrecertPoison => "precert_poison",
+            RejectionCheck::PrecertIssuer => "precert_issuer",
+            RejectionCheck::Other => "other",
+            RejectionCheck::Internal => "internal",
+        }
+    }
+}
+
+impl From<&ValidationError> for RejectionCheck {
+    fn from(error: &ValidationError) -> Self {
+        match error {
+            ValidationError::EmptyChain | ValidationError::UnparsableCertificate { .. } => {
+                RejectionCheck::Malformed
+            }
+            ValidationError::NoTrustedRoots | ValidationError::UntrustedRoot { .. } => {
+                RejectionCheck::UntrustedRoot
+            }
+            ValidationError::InvalidSignature { .. } | ValidationError::InvalidRootSignature => {
+                RejectionCheck::BadSignature
+            }
+            ValidationError::UnorderedChain { .. } => RejectionCheck::UnorderedChain,
+            ValidationError::ChainTooLong { .. }
+            | ValidationError::CertificateTooLarge { .. }
+            | ValidationError::DuplicateCertificate { .. }
+            | ValidationError::TooManySignatureVerifications { .. } => RejectionCheck::ChainLimit,
+            ValidationError::DisallowedSignatureAlgorithm { .. }
+            | ValidationError::SignatureAlgorithmMismatch { .. } => {
+                RejectionCheck::SignatureAlgorithm
+            }
+            ValidationError::ExpiresBeforeWindow { .. } | ValidationError::Expired { .. } => {
+                RejectionCheck::Expired
+            }
+            ValidationError::ExpiresAfterWindow { .. } => RejectionCheck::OutsideShard,
+            ValidationError::NotYetValid { .. } => RejectionCheck::Other,
+            ValidationError::PoisonNotCritical | ValidationError::PoisonNotNull => {
+                RejectionCheck::PrecertPoison
+            }
+            ValidationError::PrecertIssuerMissing
+            | ValidationError::PrecertSigningCertNotDirectlyIssued => RejectionCheck::PrecertIssuer,
+        }
+    }
+}
+
+/// A chain the validator accepted
+#[derive(Debug, Clone)]
+pub struct ValidatedChain {
+    /// The chain to log, up to and including its trusted root
+    pub chain: Vec<Vec<u8>>,
+    /// Soft checks the chain failed, only ever set in permissive mode
+    pub warnings: Vec<ValidationWarning>,
+}
+
+/// RFC 5280 §4.1.1.2: the algorithm inside the TBS certificate must be the
+/// one the certificate is actually signed with
+pub fn check_tbs_signature_algorithm(
+    cert: &Certificate,
+) -> std::result::Result<(), ValidationError> {
+    if cert.tbs_certificate.signature == cert.signature_algorithm {
+        return Ok(());
+    }
+
+    Err(ValidationError::SignatureAlgorithmMismatch {
+        tbs_oid: cert.tbs_certificate.signature.oid.to_string(),
+        outer_oid: cert.signature_algorithm.oid.to_string(),
+    })
+}
+
+/// Roots with the issuer's name tried per certificate when looking for the
+/// root of a chain, after dropping roots that share a subject and key
+const MAX_ISSUER_CANDIDATES: usize = 8;
+
+/// Signature verifications left for one submission. Checks answered by the
+/// signature cache are charged too, so whether a chain fits the budget does not
+/// depend on what happens to be cached.
+struct SignatureBudget {
+    remaining: usize,
+    max: usize,
+}
+
+impl SignatureBudget {
+    fn new(max: usize) -> Self {
+        Self {
+            remaining: max,
+            max,
+        }
+    }
+
+    fn spend(&mut self) -> std::result::Result<(), ValidationError> {
+        if self.remaining == 0 {
+            return Err(ValidationError::TooManySignatureVerifications { max: self.max });
+        }
+        self.remaining -= 1;
+        Ok(())
+    }
+}
+
 /// Configuration for RFC 6962 compliant certificate validation
 #[derive(Debug, Clone)]
 pub struct Rfc6962ValidationConfig {
@@ -48,12 +365,24 @@ pub struct Rfc6962ValidationConfig {
     pub trusted_roots_dir: PathBuf,
     /// Maximum allowed certificate chain length
     pub max_chain_length: usize,
+    /// Maximum DER size of any one certificate in a chain
+    pub max_certificate_size: usize,
+    /// Maximum signature verifications spent on one submission
+    pub max_signature_verifications: usize,
     /// Allowed signature algorithms (OIDs)
     pub allowed_signature_algorithms: HashSet<String>,
     /// Optional temporal window for log operation
     pub temporal_window: Option<TemporalWindow>,
     /// CCADB environment for fetching root certificates
     pub ccadb: CcadbEnvironment,
+    /// Whether soft check failures reject a chain
+    pub mode: ValidationMode,
+    /// Successful signature checks remembered across submissions, 0 to
+    /// verify every signature
+    pub signature_cache_size: usize,
+    /// Reject leaves not valid when submitted, allowing this much clock
+    /// skew. Validity periods are not checked if unset.
+    pub clock_tolerance: Option<ClockTolerance>,
 }
 
 impl Default for Rfc6962ValidationConfig {
@@ -61,6 +390,8 @@ impl Default for Rfc6962ValidationConfig {
         Self {
             trusted_roots_dir: PathBuf::from("trusted_roots"),
             max_chain_length: 10,
+            max_certificate_size: 64 * 1024,
+            max_signature_verifications: 32,
             allowed_signature_algorithms: vec![
                 ECDSA_WITH_SHA256_OID.to_string(),
                 ECDSA_WITH_SHA384_OID.to_string(),
@@ -68,12 +399,60 @@ impl Default for Rfc6962ValidationConfig {
                 SHA256_WITH_RSA_ENCRYPTION_OID.to_string(),
                 SHA384_WITH_RSA_ENCRYPTION_OID.to_string(),
                 SHA512_WITH_RSA_ENCRYPTION_OID.to_string(),
-                SHA1_WITH_RSA_ENCRYPTION_OID.to_string(),
             ]
             .into_iter()
             .collect(),
             temporal_window: None,
             ccadb: CcadbEnvironment::Production,
+            mode: ValidationMode::Strict,
+            signature_cache_size: 10_000,
+            clock_tolerance: None,
+        }
+    }
+}
+
+/// Roots that a chain may terminate in
+struct RootSet {
+    roots: Vec<Certificate>,
+    /// Hashes of the roots for fast lookup
+    hashes: HashSet<[u8; 32]>,
+}
+
+impl RootSet {
+    fn new(roots: Vec<Certificate>) -> Result<Self> {
+        let hashes = roots
+            .iter()
+            .map(Rfc6962Validator::certificate_hash)
+            .collect::<Result<HashSet<_>>>()?;
+        Ok(Self { roots, hashes })
+    }
+}
+
+/// SHA-256 of a certificate's DER and of its issuer's SubjectPublicKeyInfo.
+/// The whole certificate is hashed, not just its signature, so a certificate
+/// with a valid signature copied over a different TBS never hits the cache.
+type SignatureCacheKey = ([u8; 32], [u8; 32]);
+
+/// Signature checks that succeeded, with the metrics they are reported to.
+/// Failures are never cached, so a chain rejected once is checked again when
+/// resubmitted.
+struct SignatureCache {
+    verified: Cache<SignatureCacheKey, ()>,
+    hits: IntCounter,
+    misses: IntCounter,
+    entries: IntGauge,
+}
+
+impl SignatureCache {
+    fn new(capacity: usize, metrics: &Metrics) -> Self {
+        Self {
+            verified: CacheBuilder::new(capacity)
+                .with_name("ct_validation_signature")
+                .with_eviction_config(LruConfig::default())
+                .build(),
+            hits: metrics.cache_hits.with_label_values(&["signature"]),
+            misses: metrics.cache_misses.with_label_values(&["signature"]),
+            entries: metrics.signature_cache_entries.clone(),
         }
     }
 }
@@ -81,11 +460,12 @@ impl Default for Rfc6962ValidationConfig {
 /// RFC 6962 compliant certificate validator
 pub struct Rfc6962Validator {
     config: Rfc6962ValidationConfig,
-    pub(crate) trusted_roots: Vec<Certificate>,
-    /// Hashes of trusted root certificates for fast lookup
-    trusted_root_hashes: HashSet<[u8; 32]>,
+    /// Roots for add-chain, and for add-pre-chain unless `precert_roots` is set
+    trusted_roots: RootSet,
+    precert_roots: Option<RootSet>,
     /// Cache for DER to X509 conversions
     x509_cache: Cache<[u8; 32], Arc<openssl::x509::X509>>,
+    signature_cache: Option<SignatureCache>,
 }
 
 /// Context for chain validation that captures the chain type and issuer information
@@ -111,51 +491,157 @@ impl Rfc6962Validator {
             tracing::warn!("No trusted root certificates provided");
         }
 
-        let trusted_root_hashes = trusted_roots
-            .iter()
-            .map(Self::certificate_hash)
-            .collect::<Result<HashSet<_>>>()?;
-
         let x509_cache: Cache<[u8; 32], Arc<openssl::x509::X509>> =
             CacheBuilder::new(10_000).build();
 
+        let signature_cache = (config.signature_cache_size > 0)
+            .then(|| SignatureCache::new(config.signature_cache_size, &crate::metrics::global()));
+
         Ok(Self {
             config,
-            trusted_roots,
-            trusted_root_hashes,
+            trusted_roots: RootSet::new(trusted_roots)?,
+            precert_roots: None,
             x509_cache,
+            signature_cache,
         })
     }
 
+    /// Report signature cache hits, misses and entries to `metrics` instead
+    /// of the global metrics
+    pub fn with_metrics(mut self, metrics: &Metrics) -> Self {
+        if let Some(cache) = &mut self.signature_cache {
+            cache.hits = metrics.cache_hits.with_label_values(&["signature"]);
+            cache.misses = metrics.cache_misses.with_label_values(&["signature"]);
+            cache.entries = metrics.signature_cache_entries.clone();
+        }
+        self
+    }
+
+    /// Validator that only accepts chains whose certificates are signed with
+    /// one of `allowed_algorithms`, replacing the configured set
+    pub fn new_with_signature_policy(
+        mut config: Rfc6962ValidationConfig,
+        trusted_roots: Vec<Certificate>,
+        allowed_algorithms: Vec<SignatureAlgorithmOid>,
+    ) -> Result<Self> {
+        if allowed_algorithms.is_empty() {
+            return Err(CtError::Internal(
+                "At least one signature algorithm must be allowed".to_string(),
+            ));
+        }
+
+        config.allowed_signature_algorithms = allowed_algorithms
+            .iter()
+            .map(ObjectIdentifier::to_string)
+            .collect();
+        Self::with_trusted_roots(config, trusted_roots)
+    }
+
+    /// Validator in [`ValidationMode::Permissive`], which accepts chains that
+    /// only fail soft checks
+    pub fn new_permissive(
+        mut config: Rfc6962ValidationConfig,
+        trusted_roots: Vec<Certificate>,
+    ) -> Result<Self> {
+        config.mode = ValidationMode::Permissive;
+        Self::with_trusted_roots(config, trusted_roots)
+    }
+
+    /// Validator that also rejects leaves outside their validity period when
+    /// submitted, give or take `tolerance`
+    pub fn new_with_clock_tolerance(
+        mut config: Rfc6962ValidationConfig,
+        trusted_roots: Vec<Certificate>,
+        tolerance: ClockTolerance,
+    ) -> Result<Self> {
+        config.clock_tolerance = Some(tolerance);
+        Self::with_trusted_roots(config, trusted_roots)
+    }
+
+    /// Accept precertificates from `precert_roots` instead of the roots used
+    /// for final certificates
+    pub fn with_precert_roots(mut self, precert_roots: Vec<Certificate>) -> Result<Self> {
+        if precert_roots.is_empty() {
+            tracing::warn!("No precertificate root certificates provided");
+        }
+        self.precert_roots = Some(RootSet::new(precert_roots)?);
+        Ok(self)
+    }
+
+    /// The separate precertificate roots, if any
+    pub fn precert_roots(&self) -> Option<Vec<Certificate>> {
+        self.precert_roots.as_ref().map(|set| set.roots.clone())
+    }
+
+    fn roots_for(&self, entry_type: LogEntryType) -> &RootSet {
+        match (entry_type, &self.precert_roots) {
+            (LogEntryType::PrecertEntry, Some(precert_roots)) => precert_roots,
+            _ => &self.trusted_roots,
+        }
+    }
+
     /// Get the validation configuration
     pub fn get_config(&self) -> &Rfc6962ValidationConfig {
         &self.config
     }
 
-    /// Analyze a certificate chain and return validation context
-    fn analyze_chain(&self, chain: &[Vec<u8>]) -> Result<ChainValidationContext> {
+    /// Check the size limits of a submitted chain and parse every certificate
+    fn parse_chain(&self, chain: &[Vec<u8>]) -> Result<Vec<Certificate>> {
         if chain.is_empty() {
-            return Err(CtError::BadRequest(
-                "Certificate chain is empty".to_string(),
-            ));
+            return Err(ValidationError::EmptyChain.into());
         }
 
         if chain.len() > self.config.max_chain_length {
-            return Err(CtError::BadRequest(format!(
-                "Certificate chain length {} exceeds maximum {}",
-                chain.len(),
-                self.config.max_chain_length
-            )));
+            return Err(ValidationError::ChainTooLong {
+                length: chain.len(),
+                max: self.config.max_chain_length,
+            }
+            .into());
         }
 
-        let mut parsed_chain = Vec::new();
-        for (i, cert_der) in chain.iter().enumerate() {
-            let cert = Certificate::from_der(cert_der).map_err(|e| {
-                CtError::BadRequest(format!("Failed to parse certificate at index {}: {}", i, e))
-            })?;
-            parsed_chain.push(cert);
+        let mut seen = HashMap::with_capacity(chain.len());
+        for (index, cert_der) in chain.iter().enumerate() {
+            if cert_der.len() > self.config.max_certificate_size {
+                return Err(ValidationError::CertificateTooLarge {
+                    index,
+                    size: cert_der.len(),
+                    max: self.config.max_certificate_size,
+                }
+                .into());
+            }
+
+            // A certificate listed twice can only make path building loop
+            let hash: [u8; 32] = Sha256::digest(cert_der).into();
+            if let Some(&first) = seen.get(&hash) {
+                return Err(ValidationError::DuplicateCertificate { index, first }.into());
+            }
+            seen.insert(hash, index);
         }
 
+        chain
+            .iter()
+            .enumerate()
+            .map(|(i, cert_der)| {
+                Certificate::from_der(cert_der).map_err(|e| {
+                    CtError::from(ValidationError::UnparsableCertificate {
+                        index: i,
+                        reason: e.to_string(),
+                    })
+                })
+            })
+            .collect()
+    }
+
+    /// Analyze a certificate chain and return validation context
+    fn analyze_chain(&self, chain: &[Vec<u8>]) -> Result<ChainValidationContext> {
+        let parsed_chain = self.parse_chain(chain)?;
+        self.analyze_parsed_chain(parsed_chain)
+    }
+
+    fn analyze_parsed_chain(
+        &self,
+        parsed_chain: Vec<Certificate>,
+    ) -> Result<ChainValidationContext> {
         let is_precert = self.is_precertificate(&parsed_chain[0])?;
 
         let mut has_signing_cert = false;
@@ -166,10 +652,7 @@ impl Rfc6962Validator {
 
             if has_signing_cert {
                 if parsed_chain.len() < 3 {
-                    return Err(CtError::BadRequest(
-                        "Precertificate signed by signing certificate requires real issuer in chain"
-                            .to_string(),
-                    ));
+                    return Err(ValidationError::PrecertIssuerMissing.into());
                 }
                 real_issuer_index = 2;
             }
@@ -183,16 +666,83 @@ impl Rfc6962Validator {
         })
     }
 
-    /// Validate a certificate chain according to RFC 6962
-    pub async fn validate_chain(&self, chain: &[Vec<u8>]) -> Result<()> {
-        let context = self.analyze_chain(chain)?;
-        self.validate_chain_with_context(&context).await
+    /// Validate a certificate chain according to RFC 6962.
+    ///
+    /// Returns the chain the log should store: the submitted certificates up to
+    /// the trusted root, with that root appended when the submitter left it out
+    /// or sent a different certificate for the same root key (such as a
+    /// cross-sign).
+    ///
+    /// The root set is chosen by whether the first certificate is a
+    /// precertificate; see [`Self::validate_chain_as`] to choose it by endpoint.
+    #[cfg(test)]
+    pub async fn validate_chain(&self, chain: &[Vec<u8>]) -> Result<Vec<Vec<u8>>> {
+        let parsed_chain = self.parse_chain(chain)?;
+        let entry_type = if self.is_precertificate(&parsed_chain[0])? {
+            LogEntryType::PrecertEntry
+        } else {
+            LogEntryType::X509Entry
+        };
+        self.validate_parsed_chain(chain, parsed_chain, entry_type)
+            .await
+            .map(|validated| validated.chain)
+    }
+
+    /// Validate a chain submitted as `entry_type`, against that type's roots
+    pub async fn validate_chain_as(
+        &self,
+        chain: &[Vec<u8>],
+        entry_type: LogEntryType,
+    ) -> Result<ValidatedChain> {
+        let parsed_chain = self.parse_chain(chain)?;
+        self.validate_parsed_chain(chain, parsed_chain, entry_type)
+            .await
+    }
+
+    async fn validate_parsed_chain(
+        &self,
+        chain: &[Vec<u8>],
+        parsed_chain: Vec<Certificate>,
+        entry_type: LogEntryType,
+    ) -> Result<ValidatedChain> {
+        let roots = self.roots_for(entry_type);
+        let mut budget = SignatureBudget::new(self.config.max_signature_verifications);
+        let (kept, root) = self
+            .verify_chain_to_root(&parsed_chain, roots, &mut budget)
+            .await?;
+
+        let mut path_der = chain[..kept].to_vec();
+        let mut path = parsed_chain;
+        path.truncate(kept);
+        if let Some(root) = root {
+            path_der.push(
+                root.to_der()
+                    .map_err(|e| CtError::Internal(format!("Failed to encode root: {}", e)))?,
+            );
+            path.push(root);
+        }
+        Self::check_chain_order(&path, roots)?;
+
+        let context = self.analyze_parsed_chain(path)?;
+        let mut warnings = Vec::new();
+        self.validate_chain_with_context(&context, &mut budget, &mut warnings)
+            .await?;
+
+        Ok(ValidatedChain {
+            chain: path_der,
+            warnings,
+        })
     }
 
     /// Validate a chain using the analyzed context
-    async fn validate_chain_with_context(&self, context: &ChainValidationContext) -> Result<()> {
+    async fn validate_chain_with_context(
+        &self,
+        context: &ChainValidationContext,
+        budget: &mut SignatureBudget,
+        warnings: &mut Vec<ValidationWarning>,
+    ) -> Result<()> {
         for (i, cert) in context.parsed_chain.iter().enumerate() {
-            self.validate_certificate_basic(cert, i)?;
+            self.validate_certificate_basic(cert, i, warnings)?;
         }
 
         // Additional validation for precertificate chains
@@ -203,8 +753,8 @@ impl Rfc6962Validator {
             self.verify_precert_signing_cert(signing_cert, real_issuer)?;
         }
 
-        self.verify_chain_to_root(&context.parsed_chain).await?;
-        self.verify_chain_signatures(&context.parsed_chain).await?;
+        self.verify_chain_signatures(&context.parsed_chain, budget)
+            .await?;
 
         Ok(())
     }
@@ -229,15 +779,11 @@ impl Rfc6962Validator {
             for ext in extensions.iter() {
                 if ext.extn_id == CT_POISON_EXTENSION_OID {
                     if !ext.critical {
-                        return Err(CtError::BadRequest(
-                            "Precertificate poison extension must be critical".to_string(),
-                        ));
+                        return Err(ValidationError::PoisonNotCritical.into());
                     }
 
                     if ext.extn_value.as_bytes() != ASN1_NULL {
-                        return Err(CtError::BadRequest(
-                            "Precertificate poison extension must contain ASN.1 NULL".to_string(),
-                        ));
+                        return Err(ValidationError::PoisonNotNull.into());
                     }
 
                     return Ok(true);
@@ -288,29 +834,60 @@ impl Rfc6962Validator {
     ) -> Result<()> {
         // Verify the signing cert is directly issued by the real issuer
         if signing_cert.tbs_certificate.issuer != real_issuer.tbs_certificate.subject {
-            return Err(CtError::BadRequest(
-                "Precertificate Signing Certificate must be directly issued by the CA".to_string(),
-            ));
+            return Err(ValidationError::PrecertSigningCertNotDirectlyIssued.into());
         }
 
         Ok(())
     }
 
+    /// Fail with `error` in strict mode, or record `warning` in permissive mode
+    fn soft_failure(
+        &self,
+        warnings: &mut Vec<ValidationWarning>,
+        warning: ValidationWarning,
+        error: CtError,
+    ) -> Result<()> {
+        match self.config.mode {
+            ValidationMode::Strict => Err(error),
+            ValidationMode::Permissive => {
+                warnings.push(warning);
+                Ok(())
+            }
+        }
+    }
+
     /// Validate basic certificate properties
-    fn validate_certificate_basic(&self, cert: &Certificate, index: usize) -> Result<()> {
+    fn validate_certificate_basic(
+        &self,
+        cert: &Certificate,
+        index: usize,
+        warnings: &mut Vec<ValidationWarning>,
+    ) -> Result<()> {
         let sig_alg_oid = cert.signature_algorithm.oid.to_string();
         if !self
             .config
             .allowed_signature_algorithms
             .contains(&sig_alg_oid)
         {
-            return Err(CtError::BadRequest(format!(
-                "Certificate at index {} uses disallowed signature algorithm: {}",
-                index, sig_alg_oid
-            )));
+            self.soft_failure(
+                warnings,
+                ValidationWarning::DisallowedSignatureAlgorithm {
+                    index,
+                    oid: sig_alg_oid.clone(),
+                },
+                ValidationError::DisallowedSignatureAlgorithm {
+                    index,
+                    oid: sig_alg_oid,
+                }
+                .into(),
+            )?;
         }
 
         if index == 0 {
+            if let Some(tolerance) = self.config.clock_tolerance {
+                self.check_validity_period(cert, tolerance, Utc::now(), warnings)?;
+            }
+
             // Only check end-entity certificate against temporal window
             if let Some(window) = self.config.temporal_window {
                 let not_after: DateTime<Utc> = cert
@@ -322,19 +899,25 @@ impl Rfc6962Validator {
 
                 // Check if certificate expires within the log's temporal window
                 if not_after < window.start {
-                    return Err(CtError::BadRequest(format!(
-                        "Certificate expires before log temporal window starts: {} < {}",
-                        not_after.format("%Y-%m-%d %H:%M:%S UTC"),
-                        window.start.format("%Y-%m-%d %H:%M:%S UTC")
-                    )));
-                }
-
-                if not_after >= window.end {
-                    return Err(CtError::BadRequest(format!(
-                        "Certificate expires outside log temporal window: {} >= {}",
-                        not_after.format("%Y-%m-%d %H:%M:%S UTC"),
-                        window.end.format("%Y-%m-%d %H:%M:%S UTC")
-                    )));
+                    self.soft_failure(
+                        warnings,
+                        ValidationWarning::OutsideTemporalWindow { not_after },
+                        ValidationError::ExpiresBeforeWindow {
+                            not_after,
+                            window_start: window.start,
+                        }
+                        .into(),
+                    )?;
+                } else if not_after >= window.end {
+                    self.soft_failure(
+                        warnings,
+                        ValidationWarning::OutsideTemporalWindow { not_after },
+                        ValidationError::ExpiresAfterWindow {
+                            not_after,
+                            window_end: window.end,
+                        }
+                        .into(),
+                    )?;
                 }
             }
         }
@@ -342,6 +925,40 @@ impl Rfc6962Validator {
         Ok(())
     }
 
+    /// Check that `cert` is valid at `now`, widening its validity period by
+    /// `tolerance` for clocks that disagree with the submitter's
+    fn check_validity_period(
+        &self,
+        cert: &Certificate,
+        tolerance: ClockTolerance,
+        now: DateTime<Utc>,
+        warnings: &mut Vec<ValidationWarning>,
+    ) -> Result<()> {
+        let validity = &cert.tbs_certificate.validity;
+        let not_before: DateTime<Utc> = validity.not_before.to_system_time().into();
+        let not_after: DateTime<Utc> = validity.not_after.to_system_time().into();
+        let warning = ValidationWarning::OutsideValidityPeriod {
+            not_before,
+            not_after,
+        };
+
+        if not_before > now + tolerance.not_before {
+            self.soft_failure(
+                warnings,
+                warning,
+                ValidationError::NotYetValid { not_before }.into(),
+            )
+        } else if not_after < now - tolerance.not_after {
+            self.soft_failure(
+                warnings,
+                warning,
+                ValidationError::Expired { not_after }.into(),
+            )
+        } else {
+            Ok(())
+        }
+    }
+
     /// Convert a certificate to X509
     async fn cert_to_x509(&self, cert: &Certificate) -> Result<Arc<openssl::x509::X509>> {
         let cert_der = cert
@@ -369,18 +986,62 @@ impl Rfc6962Validator {
         Ok(x509_arc)
     }
 
-    /// Verify the chain terminates in a trusted root
-    async fn verify_chain_to_root(&self, chain: &[Certificate]) -> Result<()> {
-        if self.trusted_roots.is_empty() {
-            return Err(CtError::BadRequest(
-                "No trusted roots configured".to_string(),
-            ));
+    /// Whether `cert` is signed by `issuer_key`, answered from the signature
+    /// cache when this pair verified before
+    fn verify_signed_by(
+        &self,
+        cert: &X509,
+        issuer_key: &PKey<Public>,
+    ) -> std::result::Result<bool, ErrorStack> {
+        let Some(cache) = &self.signature_cache else {
+            return cert.verify(issuer_key);
+        };
+
+        let mut key: SignatureCacheKey = ([0; 32], [0; 32]);
+        key.0
+            .copy_from_slice(&cert.digest(MessageDigest::sha256())?);
+        key.1
+            .copy_from_slice(&Sha256::digest(issuer_key.public_key_to_der()?));
+        if cache.verified.get(&key).is_some() {
+            cache.hits.inc();
+            return Ok(true);
+        }
+        cache.misses.inc();
+
+        let verified = cert.verify(issuer_key)?;
+        if verified {
+            cache.verified.insert(key, ());
+            cache.entries.set(cache.verified.usage() as i64);
+        }
+        Ok(verified)
+    }
+
+    /// Find where the chain reaches a trusted root.
+    ///
+    /// Returns how many submitted certificates belong on the path to the root,
+    /// and the root to append after them if the submission did not end with it.
+    /// Certificates after the one issued by the root are dropped only when the
+    /// next one carries the root's subject and key, i.e. it is another
+    /// certificate for the same CA such as an expired cross-sign; anything else
+    /// stays in the path and must verify like the rest of the chain.
+    ///
+    /// Roots sharing a subject and key are tried once, at most
+    /// [`MAX_ISSUER_CANDIDATES`] roots are tried per certificate, and every
+    /// attempt is charged to `budget`.
+    async fn verify_chain_to_root(
+        &self,
+        chain: &[Certificate],
+        trusted_roots: &RootSet,
+        budget: &mut SignatureBudget,
+    ) -> Result<(usize, Option<Certificate>)> {
+        if trusted_roots.roots.is_empty() {
+            return Err(ValidationError::NoTrustedRoots.into());
         }
 
         tracing::debug!(
             "Verifying chain with {} certificates against {} trusted roots",
             chain.len(),
-            self.trusted_roots.len()
+            trusted_roots.roots.len()
         );
 
         let last_cert = &chain[chain.len() - 1];
@@ -395,8 +1056,8 @@ impl Rfc6962Validator {
             last_cert.tbs_certificate.subject
         );
 
-        if self.trusted_root_hashes.contains(&last_cert_hash) {
-            return Ok(());
+        if trusted_roots.hashes.contains(&last_cert_hash) {
+            return Ok((chain.len(), None));
         }
 
         // Log issuer of last cert for debugging
@@ -423,47 +1084,68 @@ impl Rfc6962Validator {
             );
 
             let cert_x509 = &chain_x509s[idx];
-            let mut matched_issuers = 0;
-
-            for root in &self.trusted_roots {
-                if cert.tbs_certificate.issuer == root.tbs_certificate.subject {
-                    matched_issuers += 1;
+            let mut candidates: Vec<&Certificate> = Vec::new();
+            for root in &trusted_roots.roots {
+                if cert.tbs_certificate.issuer != root.tbs_certificate.subject
+                    || candidates.iter().any(|seen| Self::same_ca(seen, root))
+                {
+                    continue;
+                }
+                if candidates.len() == MAX_ISSUER_CANDIDATES {
                     tracing::debug!(
-                        "Found potential issuer match - root subject: {}",
-                        root.tbs_certificate.subject
+                        "More than {} roots named '{}', ignoring the rest",
+                        MAX_ISSUER_CANDIDATES,
+                        cert_issuer
                     );
+                    break;
+                }
+                candidates.push(root);
+            }
 
-                    let root_x509 = self.cert_to_x509(root).await?;
-
-                    let root_pubkey = root_x509.public_key().map_err(|e| {
-                        CtError::Internal(format!("Failed to extract root public key: {}", e))
-                    })?;
-
-                    match cert_x509.verify(&root_pubkey) {
-                        Ok(true) => {
-                            let root_hash = Self::certificate_hash(root)?;
-                            let root_fingerprint = hex::encode(root_hash);
-                            tracing::debug!(
-                                "Certificate at index {} verified by root {} ({})",
-                                idx,
-                                &root_fingerprint[..16],
-                                root.tbs_certificate.subject
-                            );
-                            return Ok(());
-                        }
-                        Ok(false) => {
-                            tracing::debug!("Signature verification returned false");
-                            continue;
-                        }
-                        Err(e) => {
-                            tracing::debug!("Signature verification failed: {}", e);
-                            continue;
-                        }
+            for &root in &candidates {
+                tracing::debug!(
+                    "Found potential issuer match - root subject: {}",
+                    root.tbs_certificate.subject
+                );
+                budget.spend()?;
+
+                let root_x509 = self.cert_to_x509(root).await?;
+
+                let root_pubkey = root_x509.public_key().map_err(|e| {
+                    CtError::Internal(format!("Failed to extract root public key: {}", e))
+                })?;
+
+                match self.verify_signed_by(cert_x509, &root_pubkey) {
+                    Ok(true) => {
+                        let root_hash = Self::certificate_hash(root)?;
+                        let root_fingerprint = hex::encode(root_hash);
+                        tracing::debug!(
+                            "Certificate at index {} verified by root {} ({})",
+                            idx,
+                            &root_fingerprint[..16],
+                            root.tbs_certificate.subject
+                        );
+
+                        return Ok(match chain.get(idx + 1) {
+                            None => (chain.len(), Some(root.clone())),
+                            Some(next) if Self::same_ca(next, root) => {
+                                (idx + 1, Some(root.clone()))
+                            }
+                            Some(_) => (chain.len(), None),
+                        });
+                    }
+                    Ok(false) => {
+                        tracing::debug!("Signature verification returned false");
+                        continue;
+                    }
+                    Err(e) => {
+                        tracing::debug!("Signature verification failed: {}", e);
+                        continue;
                     }
                 }
             }
 
-            if matched_issuers == 0 {
+            if candidates.is_empty() {
                 tracing::debug!(
                     "No trusted roots matched the issuer '{}' for cert[{}]",
                     cert_issuer,
@@ -472,9 +1154,46 @@ impl Rfc6962Validator {
             }
         }
 
-        Err(CtError::BadRequest(
-            "Certificate chain does not terminate in a trusted root".to_string(),
-        ))
+        Err(ValidationError::UntrustedRoot {
+            issuer: last_cert.tbs_certificate.issuer.to_string(),
+        }
+        .into())
+    }
+
+    /// RFC 6962 §4.1: each certificate is followed by its issuer, up to one
+    /// issued by (or being) a trusted root
+    fn check_chain_order(
+        path: &[Certificate],
+        roots: &RootSet,
+    ) -> std::result::Result<(), ValidationError> {
+        for (index, pair) in path.windows(2).enumerate() {
+            if pair[0].tbs_certificate.issuer != pair[1].tbs_certificate.subject {
+                return Err(ValidationError::UnorderedChain { index });
+            }
+        }
+
+        let last = &path[path.len() - 1];
+        let is_root = Self::certificate_hash(last)
+            .map(|hash| roots.hashes.contains(&hash))
+            .unwrap_or(false);
+        let issued_by_root = roots
+            .roots
+            .iter()
+            .any(|root| root.tbs_certificate.subject == last.tbs_certificate.issuer);
+        if !is_root && !issued_by_root {
+            return Err(ValidationError::UnorderedChain {
+                index: path.len() - 1,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Whether two certificates name the same subject with the same key
+    fn same_ca(a: &Certificate, b: &Certificate) -> bool {
+        a.tbs_certificate.subject == b.tbs_certificate.subject
+            && a.tbs_certificate.subject_public_key_info
+                == b.tbs_certificate.subject_public_key_info
     }
 
     /// Compute SHA256 hash of a certificate's DER encoding
@@ -488,7 +1207,11 @@ impl Rfc6962Validator {
     }
 
     /// Verify signatures in the certificate chain
-    async fn verify_chain_signatures(&self, chain: &[Certificate]) -> Result<()> {
+    async fn verify_chain_signatures(
+        &self,
+        chain: &[Certificate],
+        budget: &mut SignatureBudget,
+    ) -> Result<()> {
         if chain.is_empty() {
             return Ok(());
         }
@@ -514,15 +1237,13 @@ impl Rfc6962Validator {
                 ))
             })?;
 
-            let verified = cert.verify(&issuer_pubkey).map_err(|e| {
+            budget.spend()?;
+            let verified = self.verify_signed_by(cert, &issuer_pubkey).map_err(|e| {
                 CtError::Internal(format!("Failed to verify signature at index {}: {}", i, e))
             })?;
 
             if !verified {
-                return Err(CtError::BadRequest(format!(
-                    "Certificate at index {} has invalid signature",
-                    i
-                )));
+                return Err(ValidationError::InvalidSignature { index: i }.into());
             }
         }
 
@@ -538,24 +1259,34 @@ impl Rfc6962Validator {
                 ))
             })?;
 
-            let verified = last_cert.verify(&pubkey).map_err(|e| {
+            budget.spend()?;
+            let verified = self.verify_signed_by(last_cert, &pubkey).map_err(|e| {
                 CtError::Internal(format!("Failed to verify self-signed certificate: {}", e))
             })?;
 
             if !verified {
-                return Err(CtError::BadRequest(
-                    "Self-signed certificate has invalid signature".to_string(),
-                ));
+                return Err(ValidationError::InvalidRootSignature.into());
             }
         }
 
         Ok(())
     }
 
-    /// Get the list of accepted root certificates
+    /// Get the list of accepted root certificates, for either entry type
     pub fn get_accepted_roots(&self) -> Result<Vec<Vec<u8>>> {
+        let precert_only = self.precert_roots.iter().flat_map(|precert_roots| {
+            precert_roots
+                .roots
+                .iter()
+                .zip(&precert_roots.hashes)
+                .filter(|(_, hash)| !self.trusted_roots.hashes.contains(*hash))
+                .map(|(cert, _)| cert)
+        });
+
         self.trusted_roots
+            .roots
             .iter()
+            .chain(precert_only)
             .map(|cert| {
                 cert.to_der()
                     .map_err(|e| CtError::Internal(format!("Failed to encode certificate: {}", e)))
@@ -588,7 +1319,7 @@ impl Rfc6962Validator {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use crate::validation::tbs_extractor::TbsExtractor;
     use base64::Engine;
@@ -600,13 +1331,37 @@ mod tests {
         Certificate, TbsCertificate,
     };
 
-    fn create_test_certificate_with_key(
+    pub(crate) fn create_test_certificate_with_key(
+        subject: &str,
+        issuer: &str,
+        is_ca: bool,
+        extensions: Vec<Extension>,
+        subject_key: &p256::ecdsa::SigningKey,
+        issuer_key: &p256::ecdsa::SigningKey,
+    ) -> Vec<u8> {
+        let now = std::time::SystemTime::now();
+        create_test_certificate_with_validity(
+            subject,
+            issuer,
+            is_ca,
+            extensions,
+            subject_key,
+            issuer_key,
+            now - std::time::Duration::from_secs(60),
+            now + std::time::Duration::from_secs(365 * 24 * 60 * 60),
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_test_certificate_with_validity(
         subject: &str,
         issuer: &str,
         is_ca: bool,
         mut extensions: Vec<Extension>,
         subject_key: &p256::ecdsa::SigningKey,
         issuer_key: &p256::ecdsa::SigningKey,
+        not_before: std::time::SystemTime,
+        not_after: std::time::SystemTime,
     ) -> Vec<u8> {
         use p256::ecdsa::signature::Signer;
         use spki::{
@@ -621,16 +1376,10 @@ mod tests {
         let issuer = RdnSequence::from_str(issuer).unwrap();
 
         let not_before = x509_cert::time::Time::UtcTime(
-            der::asn1::UtcTime::from_system_time(
-                std::time::SystemTime::now() - std::time::Duration::from_secs(60),
-            )
-            .unwrap(),
+            der::asn1::UtcTime::from_system_time(not_before).unwrap(),
         );
         let not_after = x509_cert::time::Time::UtcTime(
-            der::asn1::UtcTime::from_system_time(
-                std::time::SystemTime::now() + std::time::Duration::from_secs(365 * 24 * 60 * 60),
-            )
-            .unwrap(),
+            der::asn1::UtcTime::from_system_time(not_after).unwrap(),
         );
 
         let validity = x509_cert::time::Validity {
@@ -815,11 +1564,16 @@ mod tests {
         let config = Rfc6962ValidationConfig {
             trusted_roots_dir: roots_dir.clone(),
             max_chain_length: 10,
+            max_certificate_size: 64 * 1024,
+            max_signature_verifications: 32,
             allowed_signature_algorithms: vec![ECDSA_WITH_SHA256_OID.to_string()]
                 .into_iter()
                 .collect(),
             temporal_window: None,
             ccadb: CcadbEnvironment::Test,
+            mode: ValidationMode::Strict,
+            signature_cache_size: 10_000,
+            clock_tolerance: None,
         };
 
         let validator = create_test_validator(config).unwrap();
@@ -874,11 +1628,16 @@ mod tests {
         let config = Rfc6962ValidationConfig {
             trusted_roots_dir: roots_dir.clone(),
             max_chain_length: 10,
+            max_certificate_size: 64 * 1024,
+            max_signature_verifications: 32,
             allowed_signature_algorithms: vec![ECDSA_WITH_SHA256_OID.to_string()]
                 .into_iter()
                 .collect(),
             temporal_window: None,
             ccadb: CcadbEnvironment::Test,
+            mode: ValidationMode::Strict,
+            signature_cache_size: 10_000,
+            clock_tolerance: None,
         };
 
         let validator = create_test_validator(config).unwrap();
@@ -1075,11 +1834,16 @@ mod tests {
         let config = Rfc6962ValidationConfig {
             trusted_roots_dir: roots_dir.clone(),
             max_chain_length: 10,
+            max_certificate_size: 64 * 1024,
+            max_signature_verifications: 32,
             allowed_signature_algorithms: vec![ECDSA_WITH_SHA256_OID.to_string()]
                 .into_iter()
                 .collect(),
             temporal_window: None,
             ccadb: CcadbEnvironment::Test,
+            mode: ValidationMode::Strict,
+            signature_cache_size: 10_000,
+            clock_tolerance: None,
         };
 
         let validator = create_test_validator(config).unwrap();
@@ -1164,11 +1928,16 @@ mod tests {
         let config = Rfc6962ValidationConfig {
             trusted_roots_dir: roots_dir.clone(),
             max_chain_length: 10,
+            max_certificate_size: 64 * 1024,
+            max_signature_verifications: 32,
             allowed_signature_algorithms: vec![ECDSA_WITH_SHA256_OID.to_string()]
                 .into_iter()
                 .collect(),
             temporal_window: None,
             ccadb: CcadbEnvironment::Test,
+            mode: ValidationMode::Strict,
+            signature_cache_size: 10_000,
+            clock_tolerance: None,
         };
         let validator = create_test_validator(config).unwrap();
 
@@ -1180,6 +1949,15 @@ mod tests {
             "Chain with intermediate signed by trusted root should pass: {:?}",
             result
         );
+        // The root is appended so the logged chain is complete
+        assert_eq!(
+            result.unwrap(),
+            vec![
+                ee_cert.clone(),
+                intermediate_cert.clone(),
+                root_cert.clone()
+            ]
+        );
 
         // Test 2: Chain with root included should also succeed
         let chain_with_root = vec![
@@ -1193,6 +1971,7 @@ mod tests {
             "Chain including the trusted root should pass: {:?}",
             result
         );
+        assert_eq!(result.unwrap(), chain_with_root);
 
         // Test 3: Incomplete chain (missing intermediate) should fail
         let incomplete_chain = vec![ee_cert.clone()];
@@ -1203,20 +1982,754 @@ mod tests {
         );
     }
 
-    #[tokio::test]
-    async fn test_chain_termination_attack() {
+    /// Validator trusting a single root, and a correctly ordered chain of a
+    /// leaf and two intermediates issued under it
+    pub(crate) fn two_intermediate_chain() -> (Rfc6962Validator, Vec<Vec<u8>>) {
         use p256::ecdsa::SigningKey;
 
-        // This test demonstrates a vulnerability where an attacker can append
-        // certificates after a valid intermediate
+        let keys: Vec<_> = (0..4)
+            .map(|_| SigningKey::random(&mut rand::thread_rng()))
+            .collect();
+        let names = ["CN=Root CA", "CN=Intermediate 1", "CN=Intermediate 2"];
+        let root =
+            create_test_certificate_with_key(names[0], names[0], true, vec![], &keys[0], &keys[0]);
+        let intermediate_1 =
+            create_test_certificate_with_key(names[1], names[0], true, vec![], &keys[1], &keys[0]);
+        let intermediate_2 =
+            create_test_certificate_with_key(names[2], names[1], true, vec![], &keys[2], &keys[1]);
+        let leaf = create_test_certificate_with_key(
+            "CN=example.com",
+            names[2],
+            false,
+            vec![],
+            &keys[3],
+            &keys[2],
+        );
 
-        let temp_dir = TempDir::new().unwrap();
-        let roots_dir = temp_dir.path().join("roots");
-        fs::create_dir(&roots_dir).unwrap();
+        let config = Rfc6962ValidationConfig {
+            allowed_signature_algorithms: vec![ECDSA_WITH_SHA256_OID.to_string()]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let validator = Rfc6962Validator::with_trusted_roots(
+            config,
+            vec![Certificate::from_der(&root).unwrap()],
+        )
+        .unwrap();
 
-        let real_root_key = SigningKey::random(&mut rand::thread_rng());
-        let intermediate_key = SigningKey::random(&mut rand::thread_rng());
-        let attacker_root_key = SigningKey::random(&mut rand::thread_rng());
+        (validator, vec![leaf, intermediate_2, intermediate_1])
+    }
+
+    #[tokio::test]
+    async fn test_unordered_chain_rejected() {
+        let (validator, chain) = two_intermediate_chain();
+        validator
+            .validate_chain(&chain)
+            .await
+            .expect("ordered chain should be accepted");
+
+        // Intermediates swapped: the leaf's issuer is no longer next
+        let swapped = vec![chain[0].clone(), chain[2].clone(), chain[1].clone()];
+        let err = validator.validate_chain(&swapped).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            CtError::from(ValidationError::UnorderedChain { index: 0 }).to_string()
+        );
+
+        // Nothing unrelated may follow the intermediate the root issued
+        let unrelated_key = p256::ecdsa::SigningKey::random(&mut rand::thread_rng());
+        let mut trailing = chain.clone();
+        trailing.push(create_test_certificate_with_key(
+            "CN=Unrelated",
+            "CN=Unrelated",
+            true,
+            vec![],
+            &unrelated_key,
+            &unrelated_key,
+        ));
+        let err = validator.validate_chain(&trailing).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            CtError::from(ValidationError::UnorderedChain { index: 2 }).to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_signature_cache_replay() {
+        let metrics = Metrics::new();
+        let (validator, chain) = two_intermediate_chain();
+        let validator = validator.with_metrics(&metrics);
+        let hits = metrics.cache_hits.with_label_values(&["signature"]);
+        let misses = metrics.cache_misses.with_label_values(&["signature"]);
+
+        // The root's signature on the top intermediate is checked while
+        // looking for the root, then again with the rest of the path
+        validator.validate_chain(&chain).await.unwrap();
+        assert_eq!((hits.get(), misses.get()), (1, 4));
+        assert_eq!(metrics.signature_cache_entries.get(), 4);
+
+        validator.validate_chain(&chain).await.unwrap();
+        assert_eq!((hits.get(), misses.get()), (6, 4));
+        assert_eq!(metrics.signature_cache_entries.get(), 4);
+
+        let config = Rfc6962ValidationConfig {
+            signature_cache_size: 0,
+            ..validator.get_config().clone()
+        };
+        let uncached =
+            Rfc6962Validator::with_trusted_roots(config, validator.trusted_roots.roots.clone())
+                .unwrap()
+                .with_metrics(&metrics);
+        uncached.validate_chain(&chain).await.unwrap();
+        assert_eq!((hits.get(), misses.get()), (6, 4));
+    }
+
+    #[tokio::test]
+    async fn test_tampered_signature_not_served_from_cache() {
+        let metrics = Metrics::new();
+        let (validator, chain) = two_intermediate_chain();
+        let validator = validator.with_metrics(&metrics);
+        validator.validate_chain(&chain).await.unwrap();
+        let cached = metrics.signature_cache_entries.get();
+
+        let leaf = Certificate::from_der(&chain[0]).unwrap();
+
+        // Same TBS, signature altered
+        let mut bad_signature = leaf.clone();
+        let mut signature = leaf.signature.raw_bytes().to_vec();
+        *signature.last_mut().unwrap() ^= 1;
+        bad_signature.signature = der::asn1::BitString::from_bytes(&signature).unwrap();
+
+        // The valid signature over a different TBS
+        let mut bad_tbs = leaf.clone();
+        bad_tbs.tbs_certificate.serial_number =
+            x509_cert::serial_number::SerialNumber::new(&[9, 9, 9, 9]).unwrap();
+
+        for tampered in [bad_signature, bad_tbs] {
+            let mut tampered_chain = chain.clone();
+            tampered_chain[0] = tampered.to_der().unwrap();
+            let err = validator.validate_chain(&tampered_chain).await.unwrap_err();
+            assert!(
+                err.to_string().contains("invalid signature"),
+                "unexpected error: {}",
+                err
+            );
+        }
+        assert_eq!(metrics.signature_cache_entries.get(), cached);
+
+        // Failures are not cached, and the genuine chain still verifies
+        validator.validate_chain(&chain).await.unwrap();
+    }
+
+    /// A root and a chain from a leaf up through `intermediates` CAs, each
+    /// issued by the next, the last one issued by the root
+    fn generated_chain(intermediates: usize) -> (Certificate, Vec<Vec<u8>>) {
+        use p256::ecdsa::SigningKey;
+
+        let keys: Vec<_> = (0..intermediates + 2)
+            .map(|_| SigningKey::random(&mut rand::thread_rng()))
+            .collect();
+        let names: Vec<_> = std::iter::once("CN=Root CA".to_string())
+            .chain((1..=intermediates).map(|i| format!("CN=Intermediate {}", i)))
+            .collect();
+
+        let root = create_test_certificate_with_key(
+            &names[0],
+            &names[0],
+            true,
+            vec![],
+            &keys[0],
+            &keys[0],
+        );
+        let mut chain: Vec<_> = (1..=intermediates)
+            .rev()
+            .map(|i| {
+                create_test_certificate_with_key(
+                    &names[i],
+                    &names[i - 1],
+                    true,
+                    vec![],
+                    &keys[i],
+                    &keys[i - 1],
+                )
+            })
+            .collect();
+        chain.insert(
+            0,
+            create_test_certificate_with_key(
+                "CN=example.com",
+                &names[intermediates],
+                false,
+                vec![],
+                &keys[intermediates + 1],
+                &keys[intermediates],
+            ),
+        );
+
+        (Certificate::from_der(&root).unwrap(), chain)
+    }
+
+    fn ecdsa_only_config() -> Rfc6962ValidationConfig {
+        Rfc6962ValidationConfig {
+            allowed_signature_algorithms: vec![ECDSA_WITH_SHA256_OID.to_string()]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chain_limits() {
+        let (root, chain) = generated_chain(30);
+        let validator =
+            Rfc6962Validator::with_trusted_roots(ecdsa_only_config(), vec![root.clone()]).unwrap();
+        let err = validator.validate_chain(&chain).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            CtError::from(ValidationError::ChainTooLong {
+                length: 31,
+                max: 10
+            })
+            .to_string()
+        );
+
+        // Allowed by length, but checking every link costs 33 verifications
+        let config = Rfc6962ValidationConfig {
+            max_chain_length: 50,
+            ..ecdsa_only_config()
+        };
+        let validator =
+            Rfc6962Validator::with_trusted_roots(config.clone(), vec![root.clone()]).unwrap();
+        let err = validator.validate_chain(&chain).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            CtError::from(ValidationError::TooManySignatureVerifications { max: 32 }).to_string()
+        );
+
+        let config = Rfc6962ValidationConfig {
+            max_signature_verifications: 33,
+            ..config
+        };
+        let validator =
+            Rfc6962Validator::with_trusted_roots(config.clone(), vec![root.clone()]).unwrap();
+        assert_eq!(validator.validate_chain(&chain).await.unwrap().len(), 32);
+
+        let config = Rfc6962ValidationConfig {
+            max_certificate_size: chain[0].len() - 1,
+            ..config
+        };
+        let validator = Rfc6962Validator::with_trusted_roots(config, vec![root]).unwrap();
+        let err = validator.validate_chain(&chain).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            CtError::from(ValidationError::CertificateTooLarge {
+                index: 0,
+                size: chain[0].len(),
+                max: chain[0].len() - 1
+            })
+            .to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cyclic_chain_rejected() {
+        use p256::ecdsa::SigningKey;
+
+        let keys: Vec<_> = (0..3)
+            .map(|_| SigningKey::random(&mut rand::thread_rng()))
+            .collect();
+        // Two CAs that cross-sign each other
+        let ca_a = create_test_certificate_with_key(
+            "CN=CA A",
+            "CN=CA B",
+            true,
+            vec![],
+            &keys[0],
+            &keys[1],
+        );
+        let ca_b = create_test_certificate_with_key(
+            "CN=CA B",
+            "CN=CA A",
+            true,
+            vec![],
+            &keys[1],
+            &keys[0],
+        );
+        let leaf = create_test_certificate_with_key(
+            "CN=example.com",
+            "CN=CA A",
+            false,
+            vec![],
+            &keys[2],
+            &keys[0],
+        );
+
+        let (root, _) = generated_chain(0);
+        let validator =
+            Rfc6962Validator::with_trusted_roots(ecdsa_only_config(), vec![root]).unwrap();
+        let cyclic = vec![leaf, ca_a.clone(), ca_b.clone(), ca_a, ca_b];
+        let err = validator.validate_chain(&cyclic).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            CtError::from(ValidationError::DuplicateCertificate { index: 3, first: 1 }).to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_root_candidates_deduplicated_and_bounded() {
+        use p256::ecdsa::SigningKey;
+        use std::time::{Duration, SystemTime};
+
+        let (root, chain) = generated_chain(0);
+        let name = "CN=Root CA";
+        let now = SystemTime::now();
+        let other_root = |key: &SigningKey, offset: u64| {
+            let der = create_test_certificate_with_validity(
+                name,
+                name,
+                true,
+                vec![],
+                key,
+                key,
+                now - Duration::from_secs(60 + offset),
+                now + Duration::from_secs(3600),
+            );
+            Certificate::from_der(&der).unwrap()
+        };
+
+        // Many certificates for one other key count as a single candidate
+        let other_key = SigningKey::random(&mut rand::thread_rng());
+        let mut roots: Vec<_> = (0..20).map(|i| other_root(&other_key, i)).collect();
+        roots.push(root.clone());
+        let validator = Rfc6962Validator::with_trusted_roots(ecdsa_only_config(), roots).unwrap();
+        validator
+            .validate_chain(&chain)
+            .await
+            .expect("copies of one key should not hide the issuing root");
+
+        // Past the candidate limit, further roots with the same name are not tried
+        let mut roots: Vec<_> = (0..MAX_ISSUER_CANDIDATES)
+            .map(|_| other_root(&SigningKey::random(&mut rand::thread_rng()), 0))
+            .collect();
+        roots.push(root);
+        let validator = Rfc6962Validator::with_trusted_roots(ecdsa_only_config(), roots).unwrap();
+        let err = validator.validate_chain(&chain).await.unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("does not terminate in a trusted root"));
+    }
+
+    #[tokio::test]
+    async fn test_expired_cross_sign_replaced_by_trusted_root() {
+        use p256::ecdsa::SigningKey;
+        use std::time::{Duration, SystemTime};
+
+        let temp_dir = TempDir::new().unwrap();
+        let roots_dir = temp_dir.path().join("roots");
+        fs::create_dir(&roots_dir).unwrap();
+
+        let old_root_key = SigningKey::random(&mut rand::thread_rng());
+        let root_key = SigningKey::random(&mut rand::thread_rng());
+        let intermediate_key = SigningKey::random(&mut rand::thread_rng());
+        let ee_key = SigningKey::random(&mut rand::thread_rng());
+
+        let root_cert = create_test_certificate_with_key(
+            "CN=New Root CA",
+            "CN=New Root CA",
+            true,
+            vec![],
+            &root_key,
+            &root_key,
+        );
+
+        let root_b64 = base64::engine::general_purpose::STANDARD.encode(&root_cert);
+        let mut root_pem = String::from("-----BEGIN CERTIFICATE-----\n");
+        for chunk in root_b64.as_bytes().chunks(64) {
+            root_pem.push_str(&String::from_utf8_lossy(chunk));
+            root_pem.push('\n');
+        }
+        root_pem.push_str("-----END CERTIFICATE-----\n");
+        fs::write(roots_dir.join("root.pem"), root_pem).unwrap();
+
+        // The new root's key, cross-signed by an untrusted root and since expired
+        let year = Duration::from_secs(365 * 24 * 60 * 60);
+        let expired_cross_sign = create_test_certificate_with_validity(
+            "CN=New Root CA",
+            "CN=Old Root CA",
+            true,
+            vec![],
+            &root_key,
+            &old_root_key,
+            SystemTime::now() - 3 * year,
+            SystemTime::now() - year,
+        );
+
+        let intermediate_cert = create_test_certificate_with_key(
+            "CN=Test Intermediate CA",
+            "CN=New Root CA",
+            true,
+            vec![],
+            &intermediate_key,
+            &root_key,
+        );
+        let ee_cert = create_test_certificate_with_key(
+            "CN=example.com",
+            "CN=Test Intermediate CA",
+            false,
+            vec![],
+            &ee_key,
+            &intermediate_key,
+        );
+
+        let config = Rfc6962ValidationConfig {
+            trusted_roots_dir: roots_dir.clone(),
+            max_chain_length: 10,
+            max_certificate_size: 64 * 1024,
+            max_signature_verifications: 32,
+            allowed_signature_algorithms: vec![ECDSA_WITH_SHA256_OID.to_string()]
+                .into_iter()
+                .collect(),
+            temporal_window: None,
+            ccadb: CcadbEnvironment::Test,
+            mode: ValidationMode::Strict,
+            signature_cache_size: 10_000,
+            clock_tolerance: None,
+        };
+        let validator = create_test_validator(config).unwrap();
+
+        let chain = vec![
+            ee_cert.clone(),
+            intermediate_cert.clone(),
+            expired_cross_sign,
+        ];
+        let result = validator.validate_chain(&chain).await;
+        assert!(
+            result.is_ok(),
+            "Chain with an expired cross-sign of a trusted root should pass: {:?}",
+            result
+        );
+        assert_eq!(
+            result.unwrap(),
+            vec![ee_cert, intermediate_cert, root_cert],
+            "The cross-sign should be replaced by the trusted root"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_signature_policy() {
+        use p256::ecdsa::SigningKey;
+
+        let root_key = SigningKey::random(&mut rand::thread_rng());
+        let leaf_key = SigningKey::random(&mut rand::thread_rng());
+        let root = create_test_certificate_with_key(
+            "CN=Test Root CA",
+            "CN=Test Root CA",
+            true,
+            vec![],
+            &root_key,
+            &root_key,
+        );
+        let leaf = create_test_certificate_with_key(
+            "CN=example.com",
+            "CN=Test Root CA",
+            false,
+            vec![],
+            &leaf_key,
+            &root_key,
+        );
+        let roots = || vec![Certificate::from_der(&root).unwrap()];
+        let chain = [leaf.clone(), root.clone()];
+
+        assert!(!Rfc6962ValidationConfig::default()
+            .allowed_signature_algorithms
+            .contains(&SHA1_WITH_RSA_ENCRYPTION_OID.to_string()));
+
+        let ecdsa_only = Rfc6962Validator::new_with_signature_policy(
+            Rfc6962ValidationConfig::default(),
+            roots(),
+            vec![ECDSA_WITH_SHA256_OID],
+        )
+        .unwrap();
+        ecdsa_only.validate_chain(&chain).await.unwrap();
+
+        let rsa_only = Rfc6962Validator::new_with_signature_policy(
+            Rfc6962ValidationConfig::default(),
+            roots(),
+            vec![SHA256_WITH_RSA_ENCRYPTION_OID],
+        )
+        .unwrap();
+        let err = rsa_only.validate_chain(&chain).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            CtError::from(ValidationError::DisallowedSignatureAlgorithm {
+                index: 0,
+                oid: ECDSA_WITH_SHA256_OID.to_string(),
+            })
+            .to_string()
+        );
+
+        assert!(Rfc6962Validator::new_with_signature_policy(
+            Rfc6962ValidationConfig::default(),
+            roots(),
+            vec![],
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_permissive_mode() {
+        use p256::ecdsa::SigningKey;
+
+        let root_key = SigningKey::random(&mut rand::thread_rng());
+        let leaf_key = SigningKey::random(&mut rand::thread_rng());
+        let root = create_test_certificate_with_key(
+            "CN=Test Root CA",
+            "CN=Test Root CA",
+            true,
+            vec![],
+            &root_key,
+            &root_key,
+        );
+        let leaf = create_test_certificate_with_key(
+            "CN=example.com",
+            "CN=Test Root CA",
+            false,
+            vec![],
+            &leaf_key,
+            &root_key,
+        );
+        let config = Rfc6962ValidationConfig {
+            allowed_signature_algorithms: vec![SHA256_WITH_RSA_ENCRYPTION_OID.to_string()]
+                .into_iter()
+                .collect(),
+            temporal_window: Some(TemporalWindow {
+                start: Utc::now() - chrono::Duration::days(730),
+                end: Utc::now() - chrono::Duration::days(365),
+            }),
+            ..Default::default()
+        };
+        let roots = || vec![Certificate::from_der(&root).unwrap()];
+        let chain = [leaf, root.clone()];
+
+        let strict = Rfc6962Validator::with_trusted_roots(config.clone(), roots()).unwrap();
+        assert!(strict
+            .validate_chain_as(&chain, LogEntryType::X509Entry)
+            .await
+            .is_err());
+
+        let permissive = Rfc6962Validator::new_permissive(config, roots()).unwrap();
+        let validated = permissive
+            .validate_chain_as(&chain, LogEntryType::X509Entry)
+            .await
+            .unwrap();
+        assert_eq!(validated.chain, chain);
+        let reasons: Vec<_> = validated.warnings.iter().map(|w| w.reason()).collect();
+        assert_eq!(
+            reasons,
+            [
+                "disallowed_signature_algorithm",
+                "outside_temporal_window",
+                "disallowed_signature_algorithm"
+            ]
+        );
+
+        // A leaf the root did not sign is still rejected
+        let forged = create_test_certificate_with_key(
+            "CN=example.com",
+            "CN=Test Root CA",
+            false,
+            vec![],
+            &leaf_key,
+            &leaf_key,
+        );
+        assert!(permissive
+            .validate_chain_as(&[forged, root], LogEntryType::X509Entry)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_tbs_signature_algorithm_must_match() {
+        let precert = create_test_precertificate("CN=example.com", "CN=Test CA");
+        let mut cert = Certificate::from_der(&precert).unwrap();
+        check_tbs_signature_algorithm(&cert).unwrap();
+
+        cert.tbs_certificate.signature.oid = SHA256_WITH_RSA_ENCRYPTION_OID;
+        assert!(matches!(
+            check_tbs_signature_algorithm(&cert),
+            Err(ValidationError::SignatureAlgorithmMismatch { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_precert_only_root() {
+        use p256::ecdsa::SigningKey;
+
+        let cert_root_key = SigningKey::random(&mut rand::thread_rng());
+        let precert_root_key = SigningKey::random(&mut rand::thread_rng());
+        let leaf_key = SigningKey::random(&mut rand::thread_rng());
+
+        let cert_root = create_test_certificate_with_key(
+            "CN=Cert Root CA",
+            "CN=Cert Root CA",
+            true,
+            vec![],
+            &cert_root_key,
+            &cert_root_key,
+        );
+        let precert_root = create_test_certificate_with_key(
+            "CN=Precert Root CA",
+            "CN=Precert Root CA",
+            true,
+            vec![],
+            &precert_root_key,
+            &precert_root_key,
+        );
+
+        let config = Rfc6962ValidationConfig {
+            allowed_signature_algorithms: vec![ECDSA_WITH_SHA256_OID.to_string()]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let validator = Rfc6962Validator::with_trusted_roots(
+            config,
+            vec![Certificate::from_der(&cert_root).unwrap()],
+        )
+        .unwrap()
+        .with_precert_roots(vec![Certificate::from_der(&precert_root).unwrap()])
+        .unwrap();
+
+        let precert = create_test_precertificate_with_key(
+            "CN=example.com",
+            "CN=Precert Root CA",
+            &leaf_key,
+            &precert_root_key,
+        );
+        let cert = create_test_certificate_with_key(
+            "CN=example.com",
+            "CN=Precert Root CA",
+            false,
+            vec![],
+            &leaf_key,
+            &precert_root_key,
+        );
+
+        validator
+            .validate_chain_as(&[precert, precert_root.clone()], LogEntryType::PrecertEntry)
+            .await
+            .expect("precertificate root should be accepted for precertificates");
+
+        let err = validator
+            .validate_chain_as(&[cert, precert_root], LogEntryType::X509Entry)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("does not terminate in a trusted root"),
+            "Unexpected error: {}",
+            err
+        );
+
+        assert_eq!(validator.get_accepted_roots().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_root_rejected() {
+        use p256::ecdsa::SigningKey;
+
+        let temp_dir = TempDir::new().unwrap();
+        let roots_dir = temp_dir.path().join("roots");
+        fs::create_dir(&roots_dir).unwrap();
+
+        let trusted_key = SigningKey::random(&mut rand::thread_rng());
+        let trusted_root = create_test_certificate_with_key(
+            "CN=Trusted Root CA",
+            "CN=Trusted Root CA",
+            true,
+            vec![],
+            &trusted_key,
+            &trusted_key,
+        );
+
+        let root_b64 = base64::engine::general_purpose::STANDARD.encode(&trusted_root);
+        let mut root_pem = String::from("-----BEGIN CERTIFICATE-----\n");
+        for chunk in root_b64.as_bytes().chunks(64) {
+            root_pem.push_str(&String::from_utf8_lossy(chunk));
+            root_pem.push('\n');
+        }
+        root_pem.push_str("-----END CERTIFICATE-----\n");
+        fs::write(roots_dir.join("root.pem"), root_pem).unwrap();
+
+        let unknown_key = SigningKey::random(&mut rand::thread_rng());
+        let intermediate_key = SigningKey::random(&mut rand::thread_rng());
+        let ee_key = SigningKey::random(&mut rand::thread_rng());
+
+        let intermediate_cert = create_test_certificate_with_key(
+            "CN=Test Intermediate CA",
+            "CN=Unknown Root CA",
+            true,
+            vec![],
+            &intermediate_key,
+            &unknown_key,
+        );
+        let ee_cert = create_test_certificate_with_key(
+            "CN=example.com",
+            "CN=Test Intermediate CA",
+            false,
+            vec![],
+            &ee_key,
+            &intermediate_key,
+        );
+
+        let config = Rfc6962ValidationConfig {
+            trusted_roots_dir: roots_dir.clone(),
+            max_chain_length: 10,
+            max_certificate_size: 64 * 1024,
+            max_signature_verifications: 32,
+            allowed_signature_algorithms: vec![ECDSA_WITH_SHA256_OID.to_string()]
+                .into_iter()
+                .collect(),
+            temporal_window: None,
+            ccadb: CcadbEnvironment::Test,
+            mode: ValidationMode::Strict,
+            signature_cache_size: 10_000,
+            clock_tolerance: None,
+        };
+        let validator = create_test_validator(config).unwrap();
+
+        let err = validator
+            .validate_chain(&[ee_cert, intermediate_cert])
+            .await
+            .unwrap_err();
+        let message = err.to_string();
+        assert!(
+            message.contains("does not terminate in a trusted root")
+                && message.contains("Unknown Root CA"),
+            "Unexpected error: {}",
+            message
+        );
+        assert!(matches!(
+            err,
+            CtError::Validation(ValidationError::UntrustedRoot { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_chain_termination_attack() {
+        use p256::ecdsa::SigningKey;
+
+        // This test demonstrates a vulnerability where an attacker can append
+        // certificates after a valid intermediate
+
+        let temp_dir = TempDir::new().unwrap();
+        let roots_dir = temp_dir.path().join("roots");
+        fs::create_dir(&roots_dir).unwrap();
+
+        let real_root_key = SigningKey::random(&mut rand::thread_rng());
+        let intermediate_key = SigningKey::random(&mut rand::thread_rng());
+        let attacker_root_key = SigningKey::random(&mut rand::thread_rng());
         let attacker_ee_key = SigningKey::random(&mut rand::thread_rng());
 
         // Create the real root certificate
@@ -1273,11 +2786,16 @@ mod tests {
         let config = Rfc6962ValidationConfig {
             trusted_roots_dir: roots_dir.clone(),
             max_chain_length: 10,
+            max_certificate_size: 64 * 1024,
+            max_signature_verifications: 32,
             allowed_signature_algorithms: vec![ECDSA_WITH_SHA256_OID.to_string()]
                 .into_iter()
                 .collect(),
             temporal_window: None,
             ccadb: CcadbEnvironment::Test,
+            mode: ValidationMode::Strict,
+            signature_cache_size: 10_000,
+            clock_tolerance: None,
         };
         let validator = create_test_validator(config).unwrap();
 
@@ -1363,11 +2881,16 @@ mod tests {
         let config = Rfc6962ValidationConfig {
             trusted_roots_dir: roots_dir.clone(),
             max_chain_length: 10,
+            max_certificate_size: 64 * 1024,
+            max_signature_verifications: 32,
             allowed_signature_algorithms: vec![ECDSA_WITH_SHA256_OID.to_string()]
                 .into_iter()
                 .collect(),
             temporal_window: None,
             ccadb: CcadbEnvironment::Test,
+            mode: ValidationMode::Strict,
+            signature_cache_size: 10_000,
+            clock_tolerance: None,
         };
         let validator = create_test_validator(config).unwrap();
 
@@ -1381,6 +2904,88 @@ mod tests {
         assert!(result.is_err(), "Disconnected chain should fail validation");
     }
 
+    #[test]
+    fn test_clock_tolerance() {
+        use p256::ecdsa::SigningKey;
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+        assert_eq!(
+            ClockTolerance::default(),
+            ClockTolerance::symmetric(Duration::from_secs(600))
+        );
+
+        // Whole seconds, as certificates store them
+        let issued = UNIX_EPOCH
+            + Duration::from_secs(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+            );
+        let key = SigningKey::random(&mut rand::thread_rng());
+        let leaf = Certificate::from_der(&create_test_certificate_with_validity(
+            "CN=example.com",
+            "CN=Test Root CA",
+            false,
+            vec![],
+            &key,
+            &key,
+            issued,
+            issued + Duration::from_secs(3600),
+        ))
+        .unwrap();
+        let not_before: DateTime<Utc> = issued.into();
+        let not_after = not_before + Duration::from_secs(3600);
+
+        let tolerance = ClockTolerance {
+            not_before: Duration::from_secs(600),
+            not_after: Duration::from_secs(60),
+        };
+        let validator =
+            Rfc6962Validator::new_with_clock_tolerance(ecdsa_only_config(), vec![], tolerance)
+                .unwrap();
+        let check = |now: DateTime<Utc>| {
+            validator.check_validity_period(&leaf, tolerance, now, &mut Vec::new())
+        };
+
+        // A submitter's clock running ahead of the log's
+        check(not_before - Duration::from_secs(599)).unwrap();
+        let err = check(not_before - Duration::from_secs(601)).unwrap_err();
+        assert!(err.to_string().contains("not yet valid"), "{}", err);
+
+        // Just expired by the log's clock, within the other tolerance
+        check(not_after + Duration::from_secs(59)).unwrap();
+        let err = check(not_after + Duration::from_secs(61)).unwrap_err();
+        assert!(err.to_string().contains("has expired"), "{}", err);
+        assert_eq!(RejectionCheck::classify(&err), RejectionCheck::Expired);
+
+        // A soft check, so permissive mode only warns
+        let permissive = Rfc6962Validator::new_permissive(
+            Rfc6962ValidationConfig {
+                clock_tolerance: Some(tolerance),
+                ..ecdsa_only_config()
+            },
+            vec![],
+        )
+        .unwrap();
+        let mut warnings = Vec::new();
+        permissive
+            .check_validity_period(
+                &leaf,
+                tolerance,
+                not_after + Duration::from_secs(3600),
+                &mut warnings,
+            )
+            .unwrap();
+        assert_eq!(
+            warnings,
+            vec![ValidationWarning::OutsideValidityPeriod {
+                not_before,
+                not_after
+            }]
+        );
+    }
+
     #[tokio::test]
     async fn test_temporal_window_validation() {
         use p256::ecdsa::SigningKey;
@@ -1462,7 +3067,9 @@ mod tests {
             result.is_err(),
             "Certificate expiring before temporal window should fail"
         );
-        let err_msg = result.unwrap_err().to_string();
+        let err = result.unwrap_err();
+        assert_eq!(RejectionCheck::classify(&err), RejectionCheck::Expired);
+        let err_msg = err.to_string();
         assert!(
             err_msg.contains("expires before log temporal window starts"),
             "Error message should mention certificate expires before window: {}",
@@ -1485,7 +3092,9 @@ mod tests {
             result.is_err(),
             "Certificate expiring after temporal window should fail"
         );
-        let err_msg = result.unwrap_err().to_string();
+        let err = result.unwrap_err();
+        assert_eq!(RejectionCheck::classify(&err), RejectionCheck::OutsideShard);
+        let err_msg = err.to_string();
         assert!(
             err_msg.contains("expires outside log temporal window"),
             "Error message should mention certificate expires outside window: {}",